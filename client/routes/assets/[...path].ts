@@ -14,7 +14,9 @@ export const handler: Handlers = {
     }
     return new Response(res.body, {
       status: 200,
-      headers: { "Content-Type": "image/webp" },
+      headers: {
+        "Content-Type": res.headers.get("Content-Type") ?? "image/webp",
+      },
     });
   },
 };
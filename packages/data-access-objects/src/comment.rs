@@ -0,0 +1,9 @@
+use crate::ownership::OwnedEntity;
+use models::comments::Column;
+
+impl OwnedEntity for models::comments::Entity {
+    type UserIdColumn = Column;
+    fn user_id_column() -> Self::UserIdColumn {
+        Column::UserId
+    }
+}
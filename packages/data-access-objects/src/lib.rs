@@ -1,4 +1,5 @@
 pub mod asset;
+pub mod comment;
 pub mod ownership;
 pub mod post;
 pub mod user;
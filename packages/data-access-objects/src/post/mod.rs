@@ -30,6 +30,30 @@ impl PostDao {
         verify_ownership::<Entity>(db, id, user_id).await
     }
 
+    pub async fn find_all_for_user(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+    ) -> Result<Vec<Model>, DbErr> {
+        Posts::find()
+            .filter(Column::UserId.eq(user_id))
+            .order_by_asc(Column::CreatedAt)
+            .order_by_asc(Column::Id)
+            .all(db)
+            .await
+    }
+
+    pub async fn find_by_slug(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        slug: &str,
+    ) -> Result<Option<Model>, DbErr> {
+        Posts::find()
+            .filter(Column::UserId.eq(user_id))
+            .filter(Column::Slug.eq(slug))
+            .one(db)
+            .await
+    }
+
     pub async fn find_paginated(
         db: &DatabaseConnection,
         user_id: Uuid,
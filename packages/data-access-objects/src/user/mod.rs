@@ -10,6 +10,10 @@ impl UserDao {
         Users::find().count(db).await
     }
 
+    pub async fn find_all(db: &DatabaseConnection) -> Result<Vec<Model>, DbErr> {
+        Users::find().order_by_asc(Column::CreatedAt).all(db).await
+    }
+
     pub async fn insert(db: &DatabaseConnection, model: ActiveModel) -> Result<Model, DbErr> {
         let res = Users::insert(model).exec(db).await?;
         Users::find_by_id(res.last_insert_id)
@@ -43,7 +43,16 @@ impl AssetMutation {
         }
 
         match repositories::AssetRepository::delete(db, user.id, id).await {
-            Ok(_) => Ok(AssetMutationResult::DeletedAsset(DeletedAsset { id })),
+            Ok(_) => {
+                // Rendered posts may embed the asset through an `asset:` reference.
+                if let Ok(cache) = ctx.data::<crate::utilities::MarkdownCache>() {
+                    cache.clear();
+                }
+                if let Err(e) = repositories::PostRepository::clear_rendered_markdown(db, user.id).await {
+                    tracing::warn!("clearing rendered posts: {e}");
+                }
+                Ok(AssetMutationResult::DeletedAsset(DeletedAsset { id }))
+            }
             Err(e) => Ok(AssetMutationResult::DbError(DbError { message: e })),
         }
     }
@@ -0,0 +1,182 @@
+use crate::errors::{AuthError, DbError, ValidationErrorType};
+use crate::types::comment::{Comment, CommentPolicy, CommentSettings, CommentStatus, DeletedComment};
+use crate::utilities::requires_auth::RequiresAuth;
+use async_graphql::{Context, Object, Result, Union};
+use models::sea_orm_active_enums as enums;
+use repositories::{CommentRepository, SpamRepository, UserRepository};
+use sea_orm::DatabaseConnection;
+use sea_orm::entity::prelude::Uuid;
+
+/// Longest auto-close period a blog can set, about ten years.
+const MAX_CLOSE_AFTER_DAYS: i32 = 3650;
+
+#[derive(Union)]
+pub enum CommentMutationResult {
+    Comment(Comment),
+    DeletedComment(DeletedComment),
+    CommentSettings(CommentSettings),
+    ValidationError(ValidationErrorType),
+    DbError(DbError),
+    AuthError(AuthError),
+}
+
+#[derive(Default)]
+pub struct CommentMutation;
+
+impl RequiresAuth for CommentMutation {}
+
+#[Object]
+impl CommentMutation {
+    /// Approve, reject or mark spam a comment on one of your posts. Approving
+    /// teaches the spam filter the comment is ham and marking it spam that
+    /// it is spam; rejecting or re-queueing it unlearns either.
+    async fn moderate_comment(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        status: CommentStatus,
+    ) -> Result<CommentMutationResult> {
+        let user = match self.require_authenticate_as_user(ctx).await {
+            Ok(u) => u,
+            Err(e) => return Ok(CommentMutationResult::AuthError(AuthError { message: e.to_string() })),
+        };
+        let db = ctx.data::<DatabaseConnection>().unwrap();
+
+        match CommentRepository::set_status(db, user.id, id, status.into()).await {
+            Ok(Some(comment)) => {
+                let spam = match comment.status {
+                    enums::CommentStatus::Spam => Some(true),
+                    enums::CommentStatus::Approved => Some(false),
+                    enums::CommentStatus::Pending | enums::CommentStatus::Rejected => None,
+                };
+                if let Err(e) = SpamRepository::learn_from_comment(db, &comment, spam).await {
+                    tracing::warn!("training spam filter on comment {id}: {e}");
+                }
+                Ok(CommentMutationResult::Comment(comment.into()))
+            }
+            Ok(None) => Ok(CommentMutationResult::AuthError(AuthError { message: "Comment not found".to_string() })),
+            Err(e) => Ok(CommentMutationResult::DbError(DbError { message: e })),
+        }
+    }
+
+    /// Delete a comment on one of your posts, along with its replies
+    async fn delete_comment(&self, ctx: &Context<'_>, id: Uuid) -> Result<CommentMutationResult> {
+        let user = match self.require_authenticate_as_user(ctx).await {
+            Ok(u) => u,
+            Err(e) => return Ok(CommentMutationResult::AuthError(AuthError { message: e.to_string() })),
+        };
+        let db = ctx.data::<DatabaseConnection>().unwrap();
+
+        match CommentRepository::delete(db, user.id, id).await {
+            Ok(true) => Ok(CommentMutationResult::DeletedComment(DeletedComment { id })),
+            Ok(false) => Ok(CommentMutationResult::AuthError(AuthError { message: "Comment not found".to_string() })),
+            Err(e) => Ok(CommentMutationResult::DbError(DbError { message: e })),
+        }
+    }
+
+    /// Set who may comment on your posts. With `closeAfterDays`, posts stop
+    /// taking comments that many days after they're first published.
+    async fn update_comment_settings(
+        &self,
+        ctx: &Context<'_>,
+        policy: CommentPolicy,
+        close_after_days: Option<i32>,
+    ) -> Result<CommentMutationResult> {
+        let user = match self.require_authenticate_as_user(ctx).await {
+            Ok(u) => u,
+            Err(e) => return Ok(CommentMutationResult::AuthError(AuthError { message: e.to_string() })),
+        };
+        if close_after_days.is_some_and(|d| !(1..=MAX_CLOSE_AFTER_DAYS).contains(&d)) {
+            return Ok(CommentMutationResult::ValidationError(ValidationErrorType {
+                message: format!("closeAfterDays must be between 1 and {MAX_CLOSE_AFTER_DAYS}"),
+            }));
+        }
+        let db = ctx.data::<DatabaseConnection>().unwrap();
+
+        match UserRepository::set_comment_settings(db, user.id, policy.into(), close_after_days).await {
+            Ok(user) => Ok(CommentMutationResult::CommentSettings(CommentSettings::from(&user))),
+            Err(e) => Ok(CommentMutationResult::DbError(DbError { message: e })),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_helpers::*;
+    use async_graphql::Request;
+    use models::sea_orm_active_enums::CommentStatus;
+    use repositories::{CommentRepository, NewComment};
+    use services::authentication::Token;
+
+    #[tokio::test]
+    async fn test_moderation_queue_and_settings() {
+        let db = setup_test_db().await;
+        let schema = create_test_schema(db.clone());
+        let email = generate_unique_email("comment_moderation");
+        let user = create_test_user_with_password(&db, &email, &valid_password()).await;
+        let token = create_access_token(&user);
+        let post = create_test_post(&db, user.id, "Title", "content", true).await;
+        let pending = CommentRepository::create(
+            &db,
+            NewComment {
+                post_id: post.id,
+                user_id: user.id,
+                parent_id: None,
+                author_name: "Reader".to_string(),
+                author_email: Some("reader@example.com".to_string()),
+                author_url: None,
+                body: "Nice *post*".to_string(),
+            },
+            CommentStatus::Pending,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let res = schema
+            .execute(
+                Request::new("query { comments { edges { node { id authorEmail html status } } } commentSettings { policy closeAfterDays } }")
+                    .data(Token(token.clone())),
+            )
+            .await;
+        let data = res.data.into_json().unwrap();
+        let edges = data["comments"]["edges"].as_array().unwrap();
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0]["node"]["id"], pending.id.to_string());
+        assert_eq!(edges[0]["node"]["html"], "<p>Nice <em>post</em></p>\n");
+        assert_eq!(data["commentSettings"]["policy"], "MODERATED");
+
+        let query = format!(
+            r#"mutation {{
+                moderateComment(id: "{}", status: APPROVED) {{ ... on Comment {{ status moderatedAt }} }}
+                updateCommentSettings(policy: OPEN, closeAfterDays: 14) {{
+                    ... on CommentSettings {{ policy closeAfterDays }}
+                }}
+            }}"#,
+            pending.id
+        );
+        let res = schema.execute(Request::new(&query).data(Token(token.clone()))).await;
+        let data = res.data.into_json().unwrap();
+        assert_eq!(data["moderateComment"]["status"], "APPROVED");
+        assert!(data["moderateComment"]["moderatedAt"].is_string());
+        assert_eq!(data["updateCommentSettings"]["policy"], "OPEN");
+        assert_eq!(data["updateCommentSettings"]["closeAfterDays"], 14);
+
+        let res = schema
+            .execute(Request::new("query { comments { edges { node { id } } } }").data(Token(token.clone())))
+            .await;
+        let data = res.data.into_json().unwrap();
+        assert!(data["comments"]["edges"].as_array().unwrap().is_empty());
+
+        let res = schema
+            .execute(
+                Request::new("mutation { updateCommentSettings(policy: OPEN, closeAfterDays: 0) { ... on ValidationErrorType { message } } }")
+                    .data(Token(token)),
+            )
+            .await;
+        let data = res.data.into_json().unwrap();
+        assert!(data["updateCommentSettings"]["message"].as_str().unwrap().contains("closeAfterDays"));
+
+        cleanup_test_user_by_email(&db, &email).await;
+    }
+}
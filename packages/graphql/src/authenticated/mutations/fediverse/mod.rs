@@ -0,0 +1,236 @@
+use crate::errors::{AuthError, DbError, ValidationErrorType};
+use crate::types::fediverse::FediverseAccount;
+use crate::utilities::activitypub::{actor_url, handle, valid_username};
+use crate::utilities::requires_auth::RequiresAuth;
+use async_graphql::{Context, Object, Result, Union};
+use repositories::ActivityPubRepository;
+use sea_orm::DatabaseConnection;
+use services::config::SiteConfig;
+
+#[derive(Union)]
+pub enum FediverseMutationResult {
+    FediverseAccount(FediverseAccount),
+    ValidationError(ValidationErrorType),
+    DbError(DbError),
+    AuthError(AuthError),
+}
+
+#[derive(Default)]
+pub struct FediverseMutation;
+
+impl RequiresAuth for FediverseMutation {}
+
+#[Object]
+impl FediverseMutation {
+    /// Give your blog a fediverse account named `username`: lowercase
+    /// letters, digits and underscores, up to 30. From then on your posts are
+    /// sent to its followers as you publish, edit and unpublish them, and
+    /// their replies come in as comments. The name can't be changed later.
+    async fn enable_federation(&self, ctx: &Context<'_>, username: String) -> Result<FediverseMutationResult> {
+        let user = match self.require_authenticate_as_user(ctx).await {
+            Ok(u) => u,
+            Err(e) => return Ok(FediverseMutationResult::AuthError(AuthError { message: e.to_string() })),
+        };
+        let db = ctx.data::<DatabaseConnection>().unwrap();
+        let site = ctx.data_opt::<SiteConfig>().cloned().unwrap_or_default();
+        let invalid = |message: &str| Ok(FediverseMutationResult::ValidationError(ValidationErrorType { message: message.to_string() }));
+
+        let username = username.trim().to_lowercase();
+        if !valid_username(&username) {
+            return invalid("Username must be 1 to 30 lowercase letters, digits or underscores");
+        }
+        match ActivityPubRepository::actor_for_user(db, user.id).await {
+            Ok(Some(_)) => return invalid("Federation is already enabled"),
+            Ok(None) => {}
+            Err(e) => return Ok(FediverseMutationResult::DbError(DbError { message: e })),
+        }
+        match ActivityPubRepository::actor_by_username(db, &username).await {
+            Ok(Some(_)) => return invalid("Username is taken"),
+            Ok(None) => {}
+            Err(e) => return Ok(FediverseMutationResult::DbError(DbError { message: e })),
+        }
+
+        // Making a key pair takes a while; keep it off the executor.
+        let keys = actix_web::rt::task::spawn_blocking(services::activitypub::generate_keys)
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|keys| keys)
+            .map_err(async_graphql::Error::new)?;
+        match ActivityPubRepository::create_actor(db, user.id, &username, keys.public_pem, keys.private_pem).await {
+            Ok(actor) => Ok(FediverseMutationResult::FediverseAccount(FediverseAccount {
+                handle: handle(&site, &actor.username),
+                actor_url: actor_url(&site, &actor.username),
+                username: actor.username,
+                follower_count: 0,
+                created_at: actor.created_at,
+            })),
+            Err(e) => Ok(FediverseMutationResult::DbError(DbError { message: e })),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_helpers::*;
+    use crate::utilities::activitypub::{deliver_due, receive, InboxError, InboxRequest};
+    use async_graphql::Request;
+    use repositories::ActivityPubRepository;
+    use sea_orm::{ActiveModelTrait, ActiveValue, IntoActiveModel};
+    use services::activitypub::{parse_signature, sign, verify};
+    use services::authentication::Token;
+    use services::config::{QueueConfig, SiteConfig, SpamConfig};
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{SocketAddr, TcpListener};
+    use std::time::Duration;
+
+    /// Method, path, headers and body of a request.
+    type Received = (String, String, HashMap<String, String>, Vec<u8>);
+
+    /// Take one request on a local port, as a follower's inbox would, and
+    /// hand it back. The port is reached as `inbox.test`, since local
+    /// addresses are refused; see [`client`].
+    fn stub_inbox() -> (String, SocketAddr, std::thread::JoinHandle<Received>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let base = format!("http://inbox.test:{}", addr.port());
+        let handle = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut parts = request_line.split_whitespace();
+            let (method, path) = (parts.next().unwrap().to_string(), parts.next().unwrap().to_string());
+            let mut headers = HashMap::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    headers.insert(name.to_lowercase(), value.trim().to_string());
+                }
+            }
+            let length = headers.get("content-length").map_or(0, |l| l.parse().unwrap());
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            let mut stream = stream;
+            stream.write_all(b"HTTP/1.1 202 Accepted\r\ncontent-length: 0\r\nconnection: close\r\n\r\n").unwrap();
+            (method, path, headers, body)
+        });
+        (base, addr, handle)
+    }
+
+    /// The ActivityPub client, with `inbox.test` pointed at a local inbox.
+    fn client(addr: SocketAddr, config: &QueueConfig) -> services::webhooks::Client {
+        services::net::client_builder()
+            .resolve("inbox.test", addr)
+            .timeout(config.timeout)
+            .redirect(services::net::redirect_policy(0))
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_published_posts_reach_followers_signed() {
+        let db = setup_test_db().await;
+        let schema = create_test_schema(db.clone());
+        let email = generate_unique_email("fediverse");
+        let user = create_test_user_with_password(&db, &email, &valid_password()).await;
+        let mut am = user.into_active_model();
+        am.email_verified_at = ActiveValue::Set(Some(chrono::Utc::now().naive_utc()));
+        let user = am.update(&db).await.unwrap();
+        let run = |query: String| Request::new(query).data(Token::new(create_access_token(&user)));
+
+        let res = schema.execute(run(r#"{ fediverseAccount { username } }"#.to_string())).await;
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+        assert!(res.data.into_json().unwrap()["fediverseAccount"].is_null());
+
+        let username = format!("t{}", &uuid::Uuid::new_v4().simple().to_string()[..12]);
+        let enable = |name: &str| {
+            format!(
+                r#"mutation {{ enableFederation(username: "{name}") {{
+                    ... on FediverseAccount {{ username handle actorUrl followerCount }}
+                    ... on ValidationErrorType {{ message }} }} }}"#
+            )
+        };
+        let res = schema.execute(run(enable("Not Valid"))).await;
+        let data = res.data.into_json().unwrap();
+        assert!(data["enableFederation"]["message"].as_str().unwrap().contains("lowercase"));
+        let res = schema.execute(run(enable(&username.to_uppercase()))).await;
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+        let data = res.data.into_json().unwrap();
+        assert_eq!(data["enableFederation"]["username"], username);
+        assert_eq!(data["enableFederation"]["handle"], format!("@{username}@localhost:8000"));
+        assert_eq!(data["enableFederation"]["actorUrl"], format!("http://localhost:8000/ap/users/{username}"));
+        let res = schema.execute(run(enable(&username))).await;
+        let data = res.data.into_json().unwrap();
+        assert_eq!(data["enableFederation"]["message"], "Federation is already enabled");
+
+        let (inbox, addr, request) = stub_inbox();
+        let follower = "https://remote.example/users/bob";
+        ActivityPubRepository::add_follower(&db, user.id, follower, &format!("{inbox}/inbox"), None).await.unwrap();
+        let res = schema.execute(run(r#"{ fediverseAccount { followerCount } fediverseFollowers { nodes { actor } } }"#.to_string())).await;
+        let data = res.data.into_json().unwrap();
+        assert_eq!(data["fediverseAccount"]["followerCount"], 1);
+        assert_eq!(data["fediverseFollowers"]["nodes"][0]["actor"], follower);
+
+        let post = create_test_post(&db, user.id, "Hello", "draft", false).await;
+        let publish = format!(
+            r#"mutation {{ updatePost(post: {{ id: "{}", title: "Hello", content: "Hi, *fediverse*.", isPublished: true }}) {{ ... on Post {{ id }} }} }}"#,
+            post.id
+        );
+        let res = schema.execute(run(publish)).await;
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+
+        let site = SiteConfig::default();
+        let config = QueueConfig { timeout: Duration::from_secs(5), ..Default::default() };
+        let client = client(addr, &config);
+        assert!(deliver_due(&db, &client, &site, &config).await.unwrap() >= 1);
+
+        let (method, path, headers, body) = request.join().unwrap();
+        assert_eq!((method.as_str(), path.as_str()), ("POST", "/inbox"));
+        let actor = ActivityPubRepository::actor_for_user(&db, user.id).await.unwrap().unwrap();
+        let signature = parse_signature(&headers["signature"]).unwrap();
+        assert_eq!(signature.key_id, format!("http://localhost:8000/ap/users/{username}#main-key"));
+        let header = |name: &str| headers.get(name).cloned();
+        verify(&actor.public_key_pem, &signature, "POST", &path, &body, chrono::Utc::now(), header).unwrap();
+        let activity: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(activity["type"], "Create");
+        assert_eq!(activity["object"]["type"], "Article");
+        assert_eq!(activity["object"]["name"], "Hello");
+        assert!(activity["object"]["content"].as_str().unwrap().contains("<em>fediverse</em>"));
+
+        let res = schema.execute(run(r#"{ fediverseDeliveries { nodes { activityType status responseStatus } } }"#.to_string())).await;
+        let data = res.data.into_json().unwrap();
+        let nodes = data["fediverseDeliveries"]["nodes"].as_array().unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0]["activityType"], "Create");
+        assert_eq!(nodes[0]["status"], "SENT");
+        assert_eq!(nodes[0]["responseStatus"], 202);
+
+        // Unsigned activities are turned away before anything is fetched.
+        let follow = format!(r#"{{"type":"Follow","actor":"{follower}","object":"{}/ap/users/{username}"}}"#, site.api_url);
+        let request = InboxRequest { method: "POST", path: "/inbox", headers: HashMap::new(), body: follow.as_bytes() };
+        let refused = receive(&db, &client, &site, &SpamConfig::default(), &username, request).await;
+        assert!(matches!(refused, Err(InboxError::Unauthorized(_))));
+
+        // So are activities signed with a key from another origin than
+        // their actor's, or signed too long ago.
+        let inbox_url = url::Url::parse(&format!("{}/ap/users/{username}/inbox", site.api_url)).unwrap();
+        let path = inbox_url.path().to_string();
+        for (key_id, date) in [
+            ("https://elsewhere.example/users/eve#main-key", chrono::Utc::now()),
+            ("https://remote.example/users/bob#main-key", chrono::Utc::now() - chrono::Duration::hours(1)),
+        ] {
+            let signed = sign(&actor.private_key_pem, key_id, "POST", &inbox_url, Some(follow.as_bytes()), date).unwrap();
+            let headers = signed.into_iter().map(|(name, value)| (name.to_lowercase(), value)).collect();
+            let request = InboxRequest { method: "POST", path: &path, headers, body: follow.as_bytes() };
+            let refused = receive(&db, &client, &site, &SpamConfig::default(), &username, request).await;
+            assert!(matches!(refused, Err(InboxError::Unauthorized(_))), "{key_id}");
+        }
+
+        cleanup_test_user_by_email(&db, &email).await;
+    }
+}
@@ -1,8 +1,22 @@
 use async_graphql::MergedObject;
 mod assets;
+mod comments;
+mod fediverse;
 mod input_validators;
+mod newsletter;
 mod posts;
 mod users;
+mod webhooks;
+mod webmentions;
 
 #[derive(MergedObject, Default)]
-pub struct Mutations(posts::PostMutation, users::UserMutation, assets::AssetMutation);
+pub struct Mutations(
+    posts::PostMutation,
+    users::UserMutation,
+    assets::AssetMutation,
+    comments::CommentMutation,
+    newsletter::NewsletterMutation,
+    webhooks::WebhookMutation,
+    webmentions::WebmentionMutation,
+    fediverse::FediverseMutation,
+);
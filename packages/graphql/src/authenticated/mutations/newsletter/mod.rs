@@ -0,0 +1,37 @@
+use crate::errors::{AuthError, DbError};
+use crate::types::newsletter::DeletedSubscriber;
+use crate::utilities::requires_auth::RequiresAuth;
+use async_graphql::{Context, Object, Result, Union};
+use repositories::SubscriberRepository;
+use sea_orm::DatabaseConnection;
+use sea_orm::entity::prelude::Uuid;
+
+#[derive(Union)]
+pub enum NewsletterMutationResult {
+    DeletedSubscriber(DeletedSubscriber),
+    DbError(DbError),
+    AuthError(AuthError),
+}
+
+#[derive(Default)]
+pub struct NewsletterMutation;
+
+impl RequiresAuth for NewsletterMutation {}
+
+#[Object]
+impl NewsletterMutation {
+    /// Forget a subscriber, along with the record of what was sent to them
+    async fn delete_subscriber(&self, ctx: &Context<'_>, id: Uuid) -> Result<NewsletterMutationResult> {
+        let user = match self.require_authenticate_as_user(ctx).await {
+            Ok(u) => u,
+            Err(e) => return Ok(NewsletterMutationResult::AuthError(AuthError { message: e.to_string() })),
+        };
+        let db = ctx.data::<DatabaseConnection>().unwrap();
+
+        match SubscriberRepository::delete(db, user.id, id).await {
+            Ok(true) => Ok(NewsletterMutationResult::DeletedSubscriber(DeletedSubscriber { id })),
+            Ok(false) => Ok(NewsletterMutationResult::AuthError(AuthError { message: "Subscriber not found".to_string() })),
+            Err(e) => Ok(NewsletterMutationResult::DbError(DbError { message: e })),
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use super::{AddPostInput, PostMutation, PostMutationResult, model_to_post_type};
+use super::{apply_settings, publishing, AddPostInput, PostMutation, PostMutationResult, model_to_post_type};
 use crate::errors::{AuthError, DbError};
 use crate::utilities::requires_auth::RequiresAuth;
 use async_graphql::{Context, Result};
@@ -46,9 +46,13 @@ pub(super) async fn add_post(
         }
         Url::parse(url).map_err(|_| async_graphql::Error::new("cover_image must be a valid URL"))?;
     }
+    if let Some(ref seo) = new_post.seo {
+        seo.validate().map_err(async_graphql::Error::new)?;
+    }
 
     let db = ctx.data::<DatabaseConnection>().unwrap();
     let is_published = new_post.is_published.unwrap_or(false);
+    let (render_math, seo) = (new_post.render_math, new_post.seo);
 
     match repositories::PostRepository::create_post(
         db,
@@ -62,7 +66,13 @@ pub(super) async fn add_post(
     )
     .await
     {
-        Ok(p) => Ok(PostMutationResult::ChangedPost(model_to_post_type(&p))),
+        Ok(p) => match apply_settings(db, user.id, p, render_math, seo).await {
+            Ok(p) => {
+                publishing(ctx).saved(&p, None).await;
+                Ok(PostMutationResult::ChangedPost(model_to_post_type(&p)))
+            }
+            Err(e) => Ok(PostMutationResult::DbError(DbError { message: e })),
+        },
         Err(e) => {
             tracing::error!("failed to insert post");
             Ok(PostMutationResult::DbError(DbError { message: e }))
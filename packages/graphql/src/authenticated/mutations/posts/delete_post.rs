@@ -1,4 +1,4 @@
-use super::{DeletePostInput, PostMutation, PostMutationResult};
+use super::{publishing, DeletePostInput, PostMutation, PostMutationResult};
 use crate::errors::{AuthError, DbError};
 use crate::utilities::requires_auth::RequiresAuth;
 use async_graphql::{Context, Result};
@@ -24,11 +24,18 @@ pub(super) async fn delete_post(
     }
 
     let db = ctx.data::<DatabaseConnection>().unwrap();
+    let previous = repositories::PostRepository::get_post(db, user.id, post.id)
+        .await
+        .ok()
+        .flatten();
 
     match repositories::PostRepository::delete_post(db, user.id, post.id).await {
-        Ok(id) => Ok(PostMutationResult::DeletedPost(
-            crate::types::post::DeletedPost { id },
-        )),
+        Ok(id) => {
+            if let Some(previous) = previous {
+                publishing(ctx).deleted(&previous).await;
+            }
+            Ok(PostMutationResult::DeletedPost(crate::types::post::DeletedPost { id }))
+        }
         Err(e) => Ok(PostMutationResult::DbError(DbError { message: e })),
     }
 }
@@ -1,13 +1,22 @@
 use crate::errors::{AuthError, DbError};
 use crate::types::post::{DeletedPost, Post as PostType};
+use crate::types::seo::SeoOverrides;
+use crate::utilities::publishing::Publishing;
 use crate::utilities::requires_auth::RequiresAuth;
 use async_graphql::{Context, InputObject, Object, Result, Union};
+use sea_orm::DatabaseConnection;
 use sea_orm::entity::prelude::Uuid;
+use services::assets::StorageDriver;
+use services::config::{FeedPingConfig, SiteConfig};
+use std::sync::Arc;
 
 mod add_post;
 mod delete_post;
 mod update_post;
 
+// Built once per mutation and handed straight to the executor, so boxing
+// the post would only add an allocation.
+#[allow(clippy::large_enum_variant)]
 #[derive(Union)]
 pub enum PostMutationResult {
     ChangedPost(PostType),
@@ -24,6 +33,9 @@ struct AddPostInput {
     description: Option<String>,
     slug: Option<String>,
     cover_image: Option<String>,
+    render_math: Option<bool>,
+    /// Share-metadata overrides; replaces any stored ones
+    seo: Option<SeoOverrides>,
 }
 
 #[derive(InputObject)]
@@ -35,6 +47,9 @@ struct UpdatePostInput {
     description: Option<String>,
     slug: Option<String>,
     cover_image: Option<String>,
+    render_math: Option<bool>,
+    /// Share-metadata overrides; replaces any stored ones
+    seo: Option<SeoOverrides>,
 }
 
 #[derive(InputObject)]
@@ -45,18 +60,50 @@ struct DeletePostInput {
 fn model_to_post_type(p: &models::posts::Model) -> PostType {
     PostType {
         id: p.id,
+        user_id: p.user_id,
         title: p.title.clone(),
         markdown_content: p.markdown_content.clone().unwrap_or_default(),
         description: p.description.clone(),
         slug: p.slug.clone(),
         cover_image: p.cover_image.clone(),
         is_published: p.is_published,
+        render_math: p.render_math,
+        rendered_markdown: p.rendered_markdown.clone(),
+        seo: p.seo.clone(),
         first_published_at: p.first_published_at,
         created_at: p.created_at,
         updated_at: p.updated_at,
     }
 }
 
+/// Apply the settings that `create_post`/`update_post` don't take, writing
+/// only those that change.
+async fn apply_settings(
+    db: &DatabaseConnection,
+    user_id: Uuid,
+    mut post: models::posts::Model,
+    render_math: Option<bool>,
+    seo: Option<SeoOverrides>,
+) -> Result<models::posts::Model, String> {
+    if let Some(render_math) = render_math.filter(|&r| r != post.render_math) {
+        post = repositories::PostRepository::set_render_math(db, user_id, post.id, render_math).await?;
+    }
+    if let Some(seo) = seo.map(|s| s.to_json()).filter(|s| *s != post.seo) {
+        post = repositories::PostRepository::set_seo(db, user_id, post.id, seo).await?;
+    }
+    Ok(post)
+}
+
+/// The follow-ups to saves and deletions, with what this schema was given.
+fn publishing<'a>(ctx: &Context<'a>) -> Publishing<'a> {
+    Publishing {
+        db: ctx.data::<DatabaseConnection>().unwrap(),
+        driver: ctx.data_opt::<Arc<StorageDriver>>(),
+        site: ctx.data_opt::<SiteConfig>().cloned().unwrap_or_default(),
+        feed_pings: ctx.data_opt::<FeedPingConfig>(),
+    }
+}
+
 #[derive(Default)]
 pub struct PostMutation;
 
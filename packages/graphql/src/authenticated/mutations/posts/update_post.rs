@@ -1,4 +1,4 @@
-use super::{PostMutation, PostMutationResult, UpdatePostInput, model_to_post_type};
+use super::{apply_settings, publishing, PostMutation, PostMutationResult, UpdatePostInput, model_to_post_type};
 use crate::errors::{AuthError, DbError};
 use crate::utilities::requires_auth::RequiresAuth;
 use async_graphql::{Context, Result};
@@ -46,13 +46,16 @@ pub(super) async fn update_post(
         }
         Url::parse(url).map_err(|_| async_graphql::Error::new("cover_image must be a valid URL"))?;
     }
+    if let Some(ref seo) = post.seo {
+        seo.validate().map_err(async_graphql::Error::new)?;
+    }
 
     let db = ctx.data::<DatabaseConnection>().unwrap();
-
-    // Invalidate cache for this post
-    if let Ok(cache) = ctx.data::<crate::utilities::MarkdownCache>() {
-        cache.invalidate(&post.id);
-    }
+    let (render_math, seo) = (post.render_math, post.seo);
+    let previous = repositories::PostRepository::get_post(db, user.id, post.id)
+        .await
+        .ok()
+        .flatten();
 
     match repositories::PostRepository::update_post(
         db,
@@ -67,7 +70,13 @@ pub(super) async fn update_post(
     )
     .await
     {
-        Ok(p) => Ok(PostMutationResult::ChangedPost(model_to_post_type(&p))),
+        Ok(p) => match apply_settings(db, user.id, p, render_math, seo).await {
+            Ok(p) => {
+                publishing(ctx).saved(&p, previous.as_ref()).await;
+                Ok(PostMutationResult::ChangedPost(model_to_post_type(&p)))
+            }
+            Err(e) => Ok(PostMutationResult::DbError(DbError { message: e })),
+        },
         Err(e) => Ok(PostMutationResult::DbError(DbError { message: e })),
     }
 }
@@ -102,4 +111,99 @@ mod tests {
 
         cleanup_test_user_by_email(&db, &email).await;
     }
+
+    #[tokio::test]
+    async fn test_update_post_sets_and_clears_seo_overrides() {
+        use sea_orm::{ActiveModelTrait, ActiveValue, IntoActiveModel};
+        use services::authentication::Token;
+
+        let db = setup_test_db().await;
+        let schema = create_test_schema(db.clone());
+        let email = generate_unique_email("update_seo");
+        let user = create_test_user_with_password(&db, &email, &valid_password()).await;
+        let mut am = user.into_active_model();
+        am.email_verified_at = ActiveValue::Set(Some(chrono::Utc::now().naive_utc()));
+        let user = am.update(&db).await.unwrap();
+        let token = create_access_token(&user);
+        let post = create_test_post(&db, user.id, "Title", "content", false).await;
+
+        let mutation = |seo: &str| {
+            format!(
+                r#"mutation {{
+                    updatePost(post: {{ id: "{}", title: "Title", content: "content", seo: {seo} }}) {{
+                        ... on Post {{ seoOverrides {{ title canonicalUrl noindex }} }}
+                    }}
+                }}"#,
+                post.id
+            )
+        };
+
+        let res = schema
+            .execute(
+                Request::new(mutation(r#"{ title: " Shared ", canonicalUrl: "https://elsewhere.example/p", noindex: true }"#))
+                    .data(Token::new(token.clone())),
+            )
+            .await;
+        assert!(res.errors.is_empty(), "Errors: {:?}", res.errors);
+        let data = res.data.into_json().unwrap();
+        let seo = &data["updatePost"]["seoOverrides"];
+        assert_eq!(seo["title"], "Shared");
+        assert_eq!(seo["canonicalUrl"], "https://elsewhere.example/p");
+        assert_eq!(seo["noindex"], true);
+
+        let res = schema
+            .execute(Request::new(mutation(r#"{ canonicalUrl: "ftp://x" }"#)).data(Token::new(token.clone())))
+            .await;
+        assert!(res.errors[0].message.contains("seo.canonical_url"), "{:?}", res.errors);
+
+        let res = schema
+            .execute(Request::new(mutation("{}")).data(Token::new(token)))
+            .await;
+        assert!(res.errors.is_empty(), "Errors: {:?}", res.errors);
+        let data = res.data.into_json().unwrap();
+        assert_eq!(data["updatePost"]["seoOverrides"]["title"], serde_json::Value::Null);
+        let stored = repositories::PostRepository::find_by_id(&db, post.id).await.unwrap().unwrap();
+        assert_eq!(stored.seo, None);
+
+        cleanup_test_user_by_email(&db, &email).await;
+    }
+
+    #[tokio::test]
+    async fn test_update_post_redraws_share_card_when_title_changes() {
+        use sea_orm::{ActiveModelTrait, ActiveValue, IntoActiveModel};
+        use services::authentication::Token;
+
+        let db = setup_test_db().await;
+        let schema = create_test_schema(db.clone());
+        let email = generate_unique_email("update_preview");
+        let user = create_test_user_with_password(&db, &email, &valid_password()).await;
+        let mut am = user.into_active_model();
+        am.email_verified_at = ActiveValue::Set(Some(chrono::Utc::now().naive_utc()));
+        let user = am.update(&db).await.unwrap();
+        let token = create_access_token(&user);
+        let post = create_test_post(&db, user.id, "Title", "content", false).await;
+        let driver = test_storage_driver();
+
+        let mut keys = Vec::new();
+        for (title, content) in [("First title", "content"), ("First title", "edited"), ("Second title", "edited")] {
+            let query = format!(
+                r#"mutation {{
+                    updatePost(post: {{ id: "{}", title: "{title}", content: "{content}" }}) {{ ... on Post {{ id }} }}
+                }}"#,
+                post.id
+            );
+            let res = schema.execute(Request::new(query).data(Token::new(token.clone()))).await;
+            assert!(res.errors.is_empty(), "Errors: {:?}", res.errors);
+            let stored = repositories::PostRepository::find_by_id(&db, post.id).await.unwrap().unwrap();
+            keys.push(stored.preview_image.expect("share card key"));
+        }
+        assert_eq!(keys[0], keys[1], "an edit that leaves the card alone keeps it");
+        assert_ne!(keys[1], keys[2]);
+        assert!(driver.get(&keys[0]).await.is_err(), "the old card is removed");
+        let png = driver.get(&keys[2]).await.unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+
+        cleanup_test_user_by_email(&db, &email).await;
+        crate::utilities::preview::delete_previews(&driver, post.id).await.unwrap();
+    }
 }
@@ -1,4 +1,5 @@
 use crate::errors::{AuthError, DbError};
+use crate::types::api_key::ApiKeyScope;
 use crate::utilities::requires_auth::RequiresAuth;
 use async_graphql::{Context, Object, Result, SimpleObject, Union};
 use sea_orm::DatabaseConnection;
@@ -9,6 +10,7 @@ use uuid::Uuid;
 pub struct CreateApiKeyResult {
     pub id: Uuid,
     pub label: String,
+    pub scopes: Vec<ApiKeyScope>,
     pub raw_key: String,
 }
 
@@ -26,10 +28,12 @@ impl RequiresAuth for CreateApiKeyMutation {}
 
 #[Object]
 impl CreateApiKeyMutation {
+    /// A key for the public API. `scopes` also let it post through Micropub.
     async fn create_api_key(
         &self,
         ctx: &Context<'_>,
         label: String,
+        #[graphql(default)] scopes: Vec<ApiKeyScope>,
     ) -> Result<CreateApiKeyMutationResult> {
         let user = match self.require_authenticate_as_user(ctx).await {
             Ok(u) => u,
@@ -41,8 +45,10 @@ impl CreateApiKeyMutation {
         };
         let db = ctx.data::<DatabaseConnection>().unwrap();
         let (raw_key, key_hash) = api_keys::generate();
-        match api_keys::create(db, user.id, label.clone(), key_hash).await {
+        let scopes: Vec<_> = scopes.into_iter().map(Into::into).collect();
+        match api_keys::create_scoped(db, user.id, label.clone(), key_hash, &scopes).await {
             Ok(record) => Ok(CreateApiKeyMutationResult::CreateApiKey(CreateApiKeyResult {
+                scopes: api_keys::scopes(&record).into_iter().map(Into::into).collect(),
                 id: record.id,
                 label: record.label,
                 raw_key,
@@ -154,4 +160,36 @@ mod tests {
 
         cleanup_test_user(&db, user.id).await;
     }
+
+    #[tokio::test]
+    async fn test_create_api_key_keeps_its_scopes() {
+        let db = setup_test_db().await;
+        let schema = create_test_schema(db.clone());
+        let email = generate_unique_email("cak_scopes");
+        let user = create_test_user_with_password(&db, &email, &valid_password()).await;
+        let token = create_access_token(&user);
+
+        let query = r#"mutation { createApiKey(label: "phone", scopes: [CREATE, MEDIA, CREATE]) {
+            ... on CreateApiKeyResult { rawKey scopes }
+        } }"#;
+
+        let res = schema
+            .execute(Request::new(query).data(services::authentication::Token::new(token.clone())))
+            .await;
+        assert!(res.errors.is_empty(), "Errors: {:?}", res.errors);
+        let data = res.data.into_json().unwrap();
+        assert_eq!(data["createApiKey"]["scopes"], serde_json::json!(["CREATE", "MEDIA"]));
+        let raw_key = data["createApiKey"]["rawKey"].as_str().unwrap();
+        let (user_id, scopes) = api_keys::validate_scoped(&db, raw_key).await.unwrap();
+        assert_eq!(user_id, user.id);
+        assert_eq!(scopes, vec![api_keys::Scope::Create, api_keys::Scope::Media]);
+
+        let res = schema
+            .execute(Request::new("{ apiKeys { label scopes } }").data(services::authentication::Token::new(token)))
+            .await;
+        let data = res.data.into_json().unwrap();
+        assert_eq!(data["apiKeys"][0]["scopes"], serde_json::json!(["CREATE", "MEDIA"]));
+
+        cleanup_test_user(&db, user.id).await;
+    }
 }
@@ -3,6 +3,7 @@ use async_graphql::{Context, Object, Result};
 use sea_orm::DatabaseConnection;
 use services::authentication::refresh_token::{cleanup_expired_tokens, revoke_all_refresh_tokens};
 use services::authentication::token::Token;
+use services::config::AuthConfig;
 
 #[derive(Default)]
 pub struct LogoutAllDevicesMutation;
@@ -15,9 +16,10 @@ impl LogoutAllDevicesMutation {
         access_token: String,
     ) -> Result<bool, AuthError> {
         let db = ctx.data::<DatabaseConnection>().unwrap();
+        let auth = ctx.data::<AuthConfig>().unwrap();
 
         let token = Token::new(access_token);
-        let user = services::authentication::authenticator::get_user(db, &token).await?;
+        let user = services::authentication::authenticator::get_user(db, auth, &token).await?;
 
         revoke_all_refresh_tokens(db, user.id)
             .await
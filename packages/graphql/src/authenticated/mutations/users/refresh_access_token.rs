@@ -5,6 +5,7 @@ use async_graphql::{Context, Object, Result, Union};
 use sea_orm::DatabaseConnection;
 use services::authentication::refresh_token::{cleanup_expired_tokens, validate_refresh_token};
 use services::authentication::token::{generate_token, Token};
+use services::config::AuthConfig;
 
 #[derive(Union)]
 pub enum RefreshAccessTokenResult {
@@ -23,6 +24,7 @@ impl RefreshAccessTokenMutation {
         refresh_token: String,
     ) -> Result<RefreshAccessTokenResult> {
         let db = ctx.data::<DatabaseConnection>().unwrap();
+        let auth = ctx.data::<AuthConfig>().unwrap();
 
         let refresh_token_record = match validate_refresh_token(db, &refresh_token).await {
             Ok(record) => record,
@@ -35,6 +37,7 @@ impl RefreshAccessTokenMutation {
 
         let user = match services::authentication::authenticator::get_user(
             db,
+            auth,
             &Token::new(refresh_token.clone()),
         )
         .await
@@ -54,7 +57,7 @@ impl RefreshAccessTokenMutation {
             }));
         }
 
-        let new_access_token = generate_token(&user);
+        let new_access_token = generate_token(auth, &user);
 
         set_access_cookie(ctx, &new_access_token);
 
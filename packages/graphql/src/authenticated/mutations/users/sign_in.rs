@@ -12,6 +12,7 @@ use repositories::UserRepository;
 use sea_orm::*;
 use services::authentication::refresh_token::{cleanup_expired_tokens, create_refresh_token};
 use services::authentication::token::generate_token;
+use services::config::AuthConfig;
 use services::validation::input_validator::InputValidator;
 
 #[derive(Union)]
@@ -29,6 +30,7 @@ pub struct SignInMutation;
 impl SignInMutation {
     async fn sign_in(&self, ctx: &Context<'_>, input: SignInInput) -> Result<SignInResult> {
         let db = ctx.data::<DatabaseConnection>().unwrap();
+        let auth = ctx.data::<AuthConfig>().unwrap();
 
         if let Err(validation_errors) = input.validate() {
             return Ok(SignInResult::ValidationError(ValidationErrorType {
@@ -66,7 +68,7 @@ impl SignInMutation {
             }));
         }
 
-        let refresh_token = match create_refresh_token(db, user.id, None).await {
+        let refresh_token = match create_refresh_token(db, auth, user.id, None).await {
             Ok(token) => token,
             Err(e) => {
                 return Ok(SignInResult::AuthError(AuthError {
@@ -77,7 +79,7 @@ impl SignInMutation {
 
         let _ = cleanup_expired_tokens(db).await;
 
-        let access_token = generate_token(&user);
+        let access_token = generate_token(auth, &user);
 
         set_auth_cookies(ctx, &access_token, &refresh_token);
 
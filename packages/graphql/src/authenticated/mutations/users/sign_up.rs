@@ -14,6 +14,7 @@ use sea_orm::entity::prelude::Uuid;
 use sea_orm::*;
 use services::authentication::refresh_token::{cleanup_expired_tokens, create_refresh_token};
 use services::authentication::token::generate_token;
+use services::config::AuthConfig;
 use services::email::EmailService;
 use services::validation::input_validator::InputValidator;
 use services::verification_token::{create_token, TokenKind};
@@ -33,6 +34,7 @@ pub struct SignUpMutation;
 impl SignUpMutation {
     async fn sign_up(&self, ctx: &Context<'_>, input: SignUpInput) -> Result<SignUpResult> {
         let db = ctx.data::<DatabaseConnection>().unwrap();
+        let auth = ctx.data::<AuthConfig>().unwrap();
 
         if let Err(validation_errors) = input.validate() {
             return Ok(SignUpResult::ValidationError(ValidationErrorType {
@@ -71,7 +73,7 @@ impl SignUpMutation {
             }
         };
 
-        let refresh_token = match create_refresh_token(db, res.id, None).await {
+        let refresh_token = match create_refresh_token(db, auth, res.id, None).await {
             Ok(token) => token,
             Err(e) => {
                 return Ok(SignUpResult::AuthError(AuthError {
@@ -93,7 +95,7 @@ impl SignUpMutation {
             }
         }
 
-        let access_token = generate_token(&res);
+        let access_token = generate_token(auth, &res);
 
         set_auth_cookies(ctx, &access_token, &refresh_token);
 
@@ -0,0 +1,326 @@
+use crate::errors::{AuthError, DbError, ValidationErrorType};
+use crate::types::webhook::{CreatedWebhook, DeletedWebhook, Webhook, WebhookDelivery};
+use crate::utilities::requires_auth::RequiresAuth;
+use async_graphql::{Context, InputObject, Object, Result, Union};
+use repositories::WebhookRepository;
+use sea_orm::DatabaseConnection;
+use sea_orm::entity::prelude::Uuid;
+use services::webhooks::{generate_secret, WebhookEvent};
+use url::Url;
+
+const MAX_URL_LENGTH: usize = 2000;
+const MIN_SECRET_LENGTH: usize = 16;
+const MAX_SECRET_LENGTH: usize = 255;
+
+#[derive(Union)]
+pub enum WebhookMutationResult {
+    Webhook(Webhook),
+    CreatedWebhook(CreatedWebhook),
+    DeletedWebhook(DeletedWebhook),
+    WebhookDelivery(WebhookDelivery),
+    ValidationError(ValidationErrorType),
+    DbError(DbError),
+    AuthError(AuthError),
+}
+
+#[derive(InputObject)]
+pub struct CreateWebhookInput {
+    /// Where payloads are POSTed; http or https
+    pub url: String,
+    /// Events to send: `post.published`, `post.updated`, `post.unpublished`,
+    /// `post.deleted` and `asset.created`
+    pub events: Vec<String>,
+    /// Key to sign payloads with, 16 to 255 characters. One is generated if
+    /// left out.
+    pub secret: Option<String>,
+}
+
+#[derive(InputObject)]
+pub struct UpdateWebhookInput {
+    pub id: Uuid,
+    pub url: Option<String>,
+    pub events: Option<Vec<String>>,
+    pub is_active: Option<bool>,
+}
+
+async fn validate_url(url: &str) -> Result<String, String> {
+    let url = url.trim();
+    let parsed = match Url::parse(url) {
+        Ok(u) if url.len() <= MAX_URL_LENGTH && matches!(u.scheme(), "http" | "https") => u,
+        _ => return Err("url must be an http(s) URL".to_string()),
+    };
+    // Deliveries are checked again as they go out, in case the name moves.
+    if services::net::check_destination(&parsed).await.is_err() {
+        return Err("url must be a public address".to_string());
+    }
+    Ok(url.to_string())
+}
+
+/// Check every name is a known event, dropping repeats.
+fn validate_events(events: Vec<String>) -> Result<Vec<String>, String> {
+    let mut names: Vec<String> = Vec::new();
+    for name in events {
+        if WebhookEvent::parse(&name).is_none() {
+            let known: Vec<&str> = WebhookEvent::ALL.iter().map(|e| e.as_str()).collect();
+            return Err(format!("unknown event {name:?}; events are {}", known.join(", ")));
+        }
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    if names.is_empty() {
+        return Err("events must name at least one event".to_string());
+    }
+    Ok(names)
+}
+
+#[derive(Default)]
+pub struct WebhookMutation;
+
+impl RequiresAuth for WebhookMutation {}
+
+#[Object]
+impl WebhookMutation {
+    /// Send events on your blog to a URL, as signed JSON POSTs
+    async fn create_webhook(&self, ctx: &Context<'_>, input: CreateWebhookInput) -> Result<WebhookMutationResult> {
+        let user = match self.require_authenticate_as_user(ctx).await {
+            Ok(u) => u,
+            Err(e) => return Ok(WebhookMutationResult::AuthError(AuthError { message: e.to_string() })),
+        };
+        let checked = match validate_url(&input.url).await {
+            Ok(url) => validate_events(input.events).map(|events| (url, events)),
+            Err(message) => Err(message),
+        };
+        let (url, events) = match checked {
+            Ok(checked) => checked,
+            Err(message) => return Ok(WebhookMutationResult::ValidationError(ValidationErrorType { message })),
+        };
+        let secret = input.secret.unwrap_or_else(generate_secret);
+        if !(MIN_SECRET_LENGTH..=MAX_SECRET_LENGTH).contains(&secret.chars().count()) {
+            return Ok(WebhookMutationResult::ValidationError(ValidationErrorType {
+                message: format!("secret must be {MIN_SECRET_LENGTH} to {MAX_SECRET_LENGTH} characters"),
+            }));
+        }
+        let db = ctx.data::<DatabaseConnection>().unwrap();
+
+        match WebhookRepository::create(db, user.id, url, secret, events).await {
+            Ok(webhook) => Ok(WebhookMutationResult::CreatedWebhook(CreatedWebhook {
+                secret: webhook.secret.clone(),
+                webhook: webhook.into(),
+            })),
+            Err(e) => Ok(WebhookMutationResult::DbError(DbError { message: e })),
+        }
+    }
+
+    /// Change a webhook's URL or events, or pause it with `isActive: false`
+    async fn update_webhook(&self, ctx: &Context<'_>, input: UpdateWebhookInput) -> Result<WebhookMutationResult> {
+        let user = match self.require_authenticate_as_user(ctx).await {
+            Ok(u) => u,
+            Err(e) => return Ok(WebhookMutationResult::AuthError(AuthError { message: e.to_string() })),
+        };
+        let url = match input.url.as_deref() {
+            Some(url) => validate_url(url).await.map(Some),
+            None => Ok(None),
+        };
+        let events = input.events.map(validate_events).transpose();
+        let (url, events) = match (url, events) {
+            (Ok(url), Ok(events)) => (url, events),
+            (Err(message), _) | (_, Err(message)) => {
+                return Ok(WebhookMutationResult::ValidationError(ValidationErrorType { message }));
+            }
+        };
+        let db = ctx.data::<DatabaseConnection>().unwrap();
+
+        match WebhookRepository::update(db, user.id, input.id, url, events, input.is_active).await {
+            Ok(Some(webhook)) => Ok(WebhookMutationResult::Webhook(webhook.into())),
+            Ok(None) => Ok(WebhookMutationResult::AuthError(AuthError { message: "Webhook not found".to_string() })),
+            Err(e) => Ok(WebhookMutationResult::DbError(DbError { message: e })),
+        }
+    }
+
+    /// Delete a webhook along with its delivery log
+    async fn delete_webhook(&self, ctx: &Context<'_>, id: Uuid) -> Result<WebhookMutationResult> {
+        let user = match self.require_authenticate_as_user(ctx).await {
+            Ok(u) => u,
+            Err(e) => return Ok(WebhookMutationResult::AuthError(AuthError { message: e.to_string() })),
+        };
+        let db = ctx.data::<DatabaseConnection>().unwrap();
+
+        match WebhookRepository::delete(db, user.id, id).await {
+            Ok(true) => Ok(WebhookMutationResult::DeletedWebhook(DeletedWebhook { id })),
+            Ok(false) => Ok(WebhookMutationResult::AuthError(AuthError { message: "Webhook not found".to_string() })),
+            Err(e) => Ok(WebhookMutationResult::DbError(DbError { message: e })),
+        }
+    }
+
+    /// Send a past delivery's payload again. It goes out as a new delivery,
+    /// signed afresh, leaving the old one in the log.
+    async fn redeliver_webhook(&self, ctx: &Context<'_>, delivery_id: Uuid) -> Result<WebhookMutationResult> {
+        let user = match self.require_authenticate_as_user(ctx).await {
+            Ok(u) => u,
+            Err(e) => return Ok(WebhookMutationResult::AuthError(AuthError { message: e.to_string() })),
+        };
+        let db = ctx.data::<DatabaseConnection>().unwrap();
+
+        match WebhookRepository::redeliver(db, user.id, delivery_id).await {
+            Ok(Some(delivery)) => Ok(WebhookMutationResult::WebhookDelivery(delivery.into())),
+            Ok(None) => Ok(WebhookMutationResult::AuthError(AuthError { message: "Delivery not found".to_string() })),
+            Err(e) => Ok(WebhookMutationResult::DbError(DbError { message: e })),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_helpers::*;
+    use crate::utilities::webhooks::deliver_due;
+    use async_graphql::Request;
+    use sea_orm::{ActiveModelTrait, ActiveValue, IntoActiveModel};
+    use services::authentication::Token;
+    use services::config::QueueConfig;
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{SocketAddr, TcpListener};
+
+    /// Headers, lowercased, and body of a request.
+    type Received = (HashMap<String, String>, String);
+
+    /// Answer one request on a local port with 200, handing back its
+    /// headers and body. The port is reached as `hooks.test`, since local
+    /// addresses are refused; see [`client`].
+    fn endpoint() -> (String, SocketAddr, std::thread::JoinHandle<Received>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let url = format!("http://hooks.test:{}/hook", addr.port());
+        let handle = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut headers = HashMap::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    headers.insert(name.to_lowercase(), value.trim().to_string());
+                }
+            }
+            let mut body = vec![0; headers["content-length"].parse().unwrap()];
+            reader.read_exact(&mut body).unwrap();
+            let mut stream = stream;
+            stream.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok").unwrap();
+            (headers, String::from_utf8(body).unwrap())
+        });
+        (url, addr, handle)
+    }
+
+    /// The delivery client, with `hooks.test` pointed at a local endpoint.
+    fn client(addr: SocketAddr) -> services::webhooks::Client {
+        services::net::client_builder().resolve("hooks.test", addr).build().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_webhooks_get_signed_events_and_can_be_redelivered() {
+        let db = setup_test_db().await;
+        let schema = create_test_schema(db.clone());
+        let email = generate_unique_email("webhooks");
+        let user = create_test_user_with_password(&db, &email, &valid_password()).await;
+        let mut am = user.into_active_model();
+        am.email_verified_at = ActiveValue::Set(Some(chrono::Utc::now().naive_utc()));
+        let user = am.update(&db).await.unwrap();
+        let run = |query: String| Request::new(query).data(Token::new(create_access_token(&user)));
+
+        let create = |url: &str, events: &str| {
+            run(format!(
+                r#"mutation {{ createWebhook(input: {{ url: "{url}", events: {events}, secret: "whsec_0123456789abcdef" }}) {{
+                    ... on CreatedWebhook {{ secret webhook {{ id events isActive }} }}
+                    ... on ValidationErrorType {{ message }}
+                }} }}"#
+            ))
+        };
+        let res = schema.execute(create("ftp://ci.example", r#"["post.published"]"#)).await;
+        let data = res.data.into_json().unwrap();
+        assert_eq!(data["createWebhook"]["message"], "url must be an http(s) URL");
+        let res = schema.execute(create("https://ci.example", r#"["post.liked"]"#)).await;
+        let data = res.data.into_json().unwrap();
+        assert!(data["createWebhook"]["message"].as_str().unwrap().starts_with("unknown event"));
+        for private in ["http://127.0.0.1:8000/hook", "http://169.254.169.254/latest", "http://[::1]/hook"] {
+            let res = schema.execute(create(private, r#"["post.published"]"#)).await;
+            let data = res.data.into_json().unwrap();
+            assert_eq!(data["createWebhook"]["message"], "url must be a public address", "{private}");
+        }
+
+        let (url, addr, request) = endpoint();
+        let res = schema.execute(create(&url, r#"["post.published", "post.published"]"#)).await;
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+        let data = res.data.into_json().unwrap();
+        assert_eq!(data["createWebhook"]["secret"], "whsec_0123456789abcdef");
+        assert_eq!(data["createWebhook"]["webhook"]["events"], serde_json::json!(["post.published"]));
+        let webhook_id = data["createWebhook"]["webhook"]["id"].as_str().unwrap().to_string();
+
+        // Publishing is sent; the later edit is a post.updated it doesn't want.
+        let post = create_test_post(&db, user.id, "Hello", "content", false).await;
+        for _ in 0..2 {
+            let publish = format!(
+                r#"mutation {{ updatePost(post: {{ id: "{}", title: "Hello", content: "content", isPublished: true }}) {{ ... on Post {{ id }} }} }}"#,
+                post.id
+            );
+            let res = schema.execute(run(publish)).await;
+            assert!(res.errors.is_empty(), "{:?}", res.errors);
+        }
+
+        let log = format!(
+            r#"{{ webhookDeliveries(webhookId: "{webhook_id}") {{ nodes {{ id event payload status attempts responseStatus }} }} }}"#
+        );
+        let res = schema.execute(run(log.clone())).await;
+        let data = res.data.into_json().unwrap();
+        let nodes = data["webhookDeliveries"]["nodes"].as_array().unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0]["event"], "post.published");
+        assert_eq!(nodes[0]["status"], "QUEUED");
+        let payload: serde_json::Value = serde_json::from_str(nodes[0]["payload"].as_str().unwrap()).unwrap();
+        assert_eq!(payload["data"]["post"]["id"], post.id.to_string());
+        let delivery_id = nodes[0]["id"].as_str().unwrap().to_string();
+
+        let delivered = deliver_due(&db, &client(addr), &QueueConfig::default())
+            .await
+            .unwrap();
+        assert!(delivered >= 1);
+        let (headers, body) = request.join().unwrap();
+        assert_eq!(headers["x-soliloquio-event"], "post.published");
+        assert_eq!(headers["x-soliloquio-delivery"], delivery_id);
+        let timestamp: i64 = headers["x-soliloquio-timestamp"].parse().unwrap();
+        assert_eq!(
+            headers["x-soliloquio-signature"],
+            services::webhooks::sign("whsec_0123456789abcdef", timestamp, body.as_bytes())
+        );
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&body).unwrap(), payload);
+
+        let res = schema.execute(run(log.clone())).await;
+        let data = res.data.into_json().unwrap();
+        assert_eq!(data["webhookDeliveries"]["nodes"][0]["status"], "SENT");
+        assert_eq!(data["webhookDeliveries"]["nodes"][0]["attempts"], 1);
+        assert_eq!(data["webhookDeliveries"]["nodes"][0]["responseStatus"], 200);
+
+        let redeliver = format!(
+            r#"mutation {{ redeliverWebhook(deliveryId: "{delivery_id}") {{ ... on WebhookDelivery {{ id status attempts payload }} }} }}"#
+        );
+        let res = schema.execute(run(redeliver)).await;
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+        let data = res.data.into_json().unwrap();
+        assert_ne!(data["redeliverWebhook"]["id"], delivery_id);
+        assert_eq!(data["redeliverWebhook"]["status"], "QUEUED");
+        assert_eq!(data["redeliverWebhook"]["attempts"], 0);
+
+        let delete = format!(r#"mutation {{ deleteWebhook(id: "{webhook_id}") {{ ... on DeletedWebhook {{ id }} }} }}"#);
+        let res = schema.execute(run(delete)).await;
+        let data = res.data.into_json().unwrap();
+        assert_eq!(data["deleteWebhook"]["id"], webhook_id);
+        let res = schema.execute(run(log)).await;
+        let data = res.data.into_json().unwrap();
+        assert_eq!(data["webhookDeliveries"]["nodes"], serde_json::json!([]));
+
+        cleanup_test_user_by_email(&db, &email).await;
+    }
+}
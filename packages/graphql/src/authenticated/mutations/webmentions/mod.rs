@@ -0,0 +1,255 @@
+use crate::errors::{AuthError, DbError};
+use crate::types::comment::CommentStatus;
+use crate::types::webmention::{DeletedWebmention, Webmention};
+use crate::utilities::requires_auth::RequiresAuth;
+use async_graphql::{Context, Object, Result, Union};
+use repositories::WebmentionRepository;
+use sea_orm::DatabaseConnection;
+use sea_orm::entity::prelude::Uuid;
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Union)]
+pub enum WebmentionMutationResult {
+    Webmention(Webmention),
+    DeletedWebmention(DeletedWebmention),
+    DbError(DbError),
+    AuthError(AuthError),
+}
+
+#[derive(Default)]
+pub struct WebmentionMutation;
+
+impl RequiresAuth for WebmentionMutation {}
+
+#[Object]
+impl WebmentionMutation {
+    /// Approve, reject or mark spam a Webmention of one of your posts. Only
+    /// approved mentions are shown on the post.
+    async fn moderate_webmention(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        status: CommentStatus,
+    ) -> Result<WebmentionMutationResult> {
+        let user = match self.require_authenticate_as_user(ctx).await {
+            Ok(u) => u,
+            Err(e) => return Ok(WebmentionMutationResult::AuthError(AuthError { message: e.to_string() })),
+        };
+        let db = ctx.data::<DatabaseConnection>().unwrap();
+
+        match WebmentionRepository::set_status(db, user.id, id, status.into()).await {
+            Ok(Some(mention)) => Ok(WebmentionMutationResult::Webmention(mention.into())),
+            Ok(None) => Ok(WebmentionMutationResult::AuthError(AuthError { message: "Webmention not found".to_string() })),
+            Err(e) => Ok(WebmentionMutationResult::DbError(DbError { message: e })),
+        }
+    }
+
+    /// Delete a Webmention of one of your posts. The source may send it
+    /// again.
+    async fn delete_webmention(&self, ctx: &Context<'_>, id: Uuid) -> Result<WebmentionMutationResult> {
+        let user = match self.require_authenticate_as_user(ctx).await {
+            Ok(u) => u,
+            Err(e) => return Ok(WebmentionMutationResult::AuthError(AuthError { message: e.to_string() })),
+        };
+        let db = ctx.data::<DatabaseConnection>().unwrap();
+
+        match WebmentionRepository::delete(db, user.id, id).await {
+            Ok(true) => Ok(WebmentionMutationResult::DeletedWebmention(DeletedWebmention { id })),
+            Ok(false) => Ok(WebmentionMutationResult::AuthError(AuthError { message: "Webmention not found".to_string() })),
+            Err(e) => Ok(WebmentionMutationResult::DbError(DbError { message: e })),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::public::{build_public_schema, PublicApiKey};
+    use crate::test_helpers::*;
+    use crate::utilities::webmentions::{receive, send_due, verify_due, ReceiveError};
+    use crate::utilities::MarkdownCache;
+    use async_graphql::Request;
+    use sea_orm::{ActiveModelTrait, ActiveValue, IntoActiveModel};
+    use services::authentication::Token;
+    use services::config::{NewsletterConfig, PowConfig, PublicApiConfig, QueueConfig, SiteConfig, SpamConfig};
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{SocketAddr, TcpListener};
+    use std::time::Duration;
+
+    /// Method, path and body of a request.
+    type Received = (String, String, String);
+
+    /// Answer `count` requests on a local port with `respond(method, path)`,
+    /// handing back what was asked. The port is reached as `site.test`,
+    /// since local addresses are refused; see [`client`].
+    fn site_elsewhere(
+        count: usize,
+        respond: impl Fn(&str, &str) -> String + Send + 'static,
+    ) -> (String, SocketAddr, std::thread::JoinHandle<Vec<Received>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let base = format!("http://site.test:{}", addr.port());
+        let handle = std::thread::spawn(move || {
+            let mut received = Vec::new();
+            for _ in 0..count {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut parts = request_line.split_whitespace();
+                let (method, path) = (parts.next().unwrap().to_string(), parts.next().unwrap().to_string());
+                let mut headers = HashMap::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        headers.insert(name.to_lowercase(), value.trim().to_string());
+                    }
+                }
+                let length = headers.get("content-length").map_or(0, |l| l.parse().unwrap());
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let page = respond(&method, &path);
+                let mut stream = stream;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: text/html\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{page}",
+                    page.len()
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+                received.push((method, path, String::from_utf8(body).unwrap()));
+            }
+            received
+        });
+        (base, addr, handle)
+    }
+
+    /// The Webmention client, with `site.test` pointed at a local site.
+    fn client(addr: SocketAddr, config: &QueueConfig) -> services::webhooks::Client {
+        services::net::client_builder()
+            .resolve("site.test", addr)
+            .timeout(config.timeout)
+            .redirect(services::net::redirect_policy(5))
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_webmentions_are_sent_verified_and_moderated() {
+        let db = setup_test_db().await;
+        let schema = create_test_schema(db.clone());
+        let email = generate_unique_email("webmentions");
+        let user = create_test_user_with_password(&db, &email, &valid_password()).await;
+        let mut am = user.into_active_model();
+        am.email_verified_at = ActiveValue::Set(Some(chrono::Utc::now().naive_utc()));
+        let user = am.update(&db).await.unwrap();
+        let run = |query: String| Request::new(query).data(Token::new(create_access_token(&user)));
+
+        let site = SiteConfig::default();
+        let post = create_test_post(&db, user.id, "Hello", "draft", false).await;
+        let target = site.post_url(&post.id.to_string(), None);
+        let reply = format!(
+            r#"<article class="h-entry"><a class="p-author h-card" href="https://ana.example/">Ana</a>
+            <p class="e-content">Replying to <a class="u-in-reply-to" href="{target}">your post</a>.</p></article>"#
+        );
+        let (elsewhere, addr, requests) = site_elsewhere(3, move |method, path| match (method, path) {
+            ("GET", "/article") => r#"<html><head><link rel="webmention" href="/webmention"></head></html>"#.to_string(),
+            ("GET", "/reply") => reply.clone(),
+            _ => String::new(),
+        });
+
+        let publish = format!(
+            r#"mutation {{ updatePost(post: {{ id: "{}", title: "Hello", content: "See [this]({elsewhere}/article).", isPublished: true }}) {{ ... on Post {{ id }} }} }}"#,
+            post.id
+        );
+        let res = schema.execute(run(publish)).await;
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+
+        let outgoing = format!(
+            r#"{{ outgoingWebmentions(postId: "{}") {{ nodes {{ source target endpoint status }} }} }}"#,
+            post.id
+        );
+        let res = schema.execute(run(outgoing.clone())).await;
+        let data = res.data.into_json().unwrap();
+        let nodes = data["outgoingWebmentions"]["nodes"].as_array().unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0]["target"], format!("{elsewhere}/article"));
+        assert_eq!(nodes[0]["status"], "QUEUED");
+
+        let config = QueueConfig { timeout: Duration::from_secs(5), ..Default::default() };
+        let client = client(addr, &config);
+        assert!(send_due(&db, &client, &config).await.unwrap() >= 1);
+        let res = schema.execute(run(outgoing)).await;
+        let data = res.data.into_json().unwrap();
+        assert_eq!(data["outgoingWebmentions"]["nodes"][0]["status"], "SENT");
+        assert_eq!(data["outgoingWebmentions"]["nodes"][0]["endpoint"], format!("{elsewhere}/webmention"));
+
+        let refused = receive(&db, &site, "http://127.0.0.1:8000/reply", &target).await;
+        assert!(matches!(refused, Err(ReceiveError::Invalid(m)) if m.contains("public")));
+        let elsewhere_target = receive(&db, &site, "https://ana.example/reply", "https://ana.example/").await;
+        assert!(matches!(elsewhere_target, Err(ReceiveError::Invalid(m)) if m.contains("not a post")));
+        let mention = receive(&db, &site, &format!("{elsewhere}/reply"), &target).await.unwrap();
+        assert!(verify_due(&db, &client, &config).await.unwrap() >= 1);
+
+        let received = requests.join().unwrap();
+        assert_eq!(received[1].0, "POST");
+        assert_eq!(received[1].1, "/webmention");
+        let form: HashMap<String, String> = form_urlencoded(&received[1].2);
+        assert_eq!(form["source"], target);
+        assert_eq!(form["target"], format!("{elsewhere}/article"));
+
+        let queue = r#"{ webmentions { nodes { id kind source authorName authorUrl content status } } }"#;
+        let res = schema.execute(run(queue.to_string())).await;
+        let data = res.data.into_json().unwrap();
+        let nodes = data["webmentions"]["nodes"].as_array().unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0]["id"], mention.id.to_string());
+        assert_eq!(nodes[0]["kind"], "REPLY");
+        assert_eq!(nodes[0]["authorName"], "Ana");
+        assert_eq!(nodes[0]["content"], "Replying to your post.");
+
+        let moderate = format!(
+            r#"mutation {{ moderateWebmention(id: "{}", status: APPROVED) {{ ... on Webmention {{ status }} }} }}"#,
+            mention.id
+        );
+        let res = schema.execute(run(moderate)).await;
+        let data = res.data.into_json().unwrap();
+        assert_eq!(data["moderateWebmention"]["status"], "APPROVED");
+
+        let pow = PowConfig { secret: "test".to_string(), difficulty: 4, max_difficulty: 8, ttl: Duration::from_secs(60) };
+        let public = build_public_schema(
+            db.clone(),
+            MarkdownCache::new(),
+            test_storage_driver(),
+            site.clone(),
+            SpamConfig::default(),
+            pow,
+            NewsletterConfig::default(),
+            None,
+            &PublicApiConfig::default(),
+        );
+        let (raw_key, key_hash) = services::api_keys::generate();
+        services::api_keys::create(&db, user.id, "test".to_string(), key_hash).await.unwrap();
+        let query = format!(r#"{{ post(id: "{}") {{ mentions {{ kind url authorName authorUrl }} }} }}"#, post.id);
+        let res = public.execute(Request::new(query).data(PublicApiKey(raw_key))).await;
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+        let data = res.data.into_json().unwrap();
+        assert_eq!(
+            data["post"]["mentions"],
+            serde_json::json!([{ "kind": "REPLY", "url": format!("{elsewhere}/reply"), "authorName": "Ana", "authorUrl": "https://ana.example/" }])
+        );
+
+        let delete = format!(r#"mutation {{ deleteWebmention(id: "{}") {{ ... on DeletedWebmention {{ id }} }} }}"#, mention.id);
+        let res = schema.execute(run(delete)).await;
+        let data = res.data.into_json().unwrap();
+        assert_eq!(data["deleteWebmention"]["id"], mention.id.to_string());
+
+        cleanup_test_user_by_email(&db, &email).await;
+    }
+
+    fn form_urlencoded(body: &str) -> HashMap<String, String> {
+        url::form_urlencoded::parse(body.as_bytes()).into_owned().collect()
+    }
+}
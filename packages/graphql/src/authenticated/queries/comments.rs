@@ -0,0 +1,92 @@
+use crate::errors::AuthError;
+use crate::types::comment::{Comment, CommentSettings, CommentStatus};
+use crate::utilities::requires_auth::RequiresAuth;
+use async_graphql::connection::{Connection, Edge, EmptyFields};
+use async_graphql::{Context, Object, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use repositories::COMMENT_DEFAULT_PAGE_SIZE;
+use sea_orm::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize)]
+struct CommentCursor {
+    id: Uuid,
+    created_at: String,
+}
+
+fn encode_cursor(m: &models::comments::Model) -> String {
+    let c = CommentCursor {
+        id: m.id,
+        created_at: m.created_at.and_utc().to_rfc3339(),
+    };
+    URL_SAFE_NO_PAD.encode(serde_json::to_string(&c).unwrap())
+}
+
+fn decode_cursor(s: &str) -> Option<CommentCursor> {
+    let bytes = URL_SAFE_NO_PAD.decode(s).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+#[derive(Default)]
+pub struct CommentQueries;
+
+impl RequiresAuth for CommentQueries {}
+
+#[Object]
+impl CommentQueries {
+    /// Comments on your posts, newest first. Without a `status`, the
+    /// moderation queue: comments still pending.
+    async fn comments(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default_with = "Some(CommentStatus::Pending)")] status: Option<CommentStatus>,
+        post_id: Option<Uuid>,
+        after: Option<String>,
+        first: Option<i32>,
+    ) -> Result<Connection<String, Comment, EmptyFields, EmptyFields>> {
+        let user = self.require_authenticate_as_user(ctx).await?;
+        let db = ctx.data::<DatabaseConnection>().unwrap();
+
+        let limit = first
+            .map(|n| (n as u64).min(100))
+            .unwrap_or(COMMENT_DEFAULT_PAGE_SIZE);
+
+        let (after_id, after_created_at) = match after.as_deref().and_then(decode_cursor) {
+            Some(c) => {
+                let dt = chrono::DateTime::parse_from_rfc3339(&c.created_at)
+                    .ok()
+                    .map(|d| d.naive_utc());
+                (Some(c.id), dt)
+            }
+            None => (None, None),
+        };
+
+        let rows = repositories::CommentRepository::list(
+            db,
+            user.id,
+            status.map(Into::into),
+            post_id,
+            after_id,
+            after_created_at,
+            Some(limit + 1),
+        )
+        .await
+        .map_err(async_graphql::Error::new)?;
+
+        let has_next_page = rows.len() as u64 > limit;
+        let has_previous_page = after.is_some();
+
+        let mut connection = Connection::new(has_previous_page, has_next_page);
+        for row in rows.into_iter().take(limit as usize) {
+            connection.edges.push(Edge::new(encode_cursor(&row), Comment::from(row)));
+        }
+        Ok(connection)
+    }
+
+    /// Who may comment on your posts, and for how long
+    async fn comment_settings(&self, ctx: &Context<'_>) -> Result<CommentSettings, AuthError> {
+        let user = self.require_authenticate_as_user(ctx).await?;
+        Ok(CommentSettings::from(&user))
+    }
+}
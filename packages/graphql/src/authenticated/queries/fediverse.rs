@@ -0,0 +1,136 @@
+use crate::types::fediverse::{FediverseAccount, FediverseDelivery, FediverseFollower};
+use crate::types::newsletter::DeliveryStatus;
+use crate::utilities::activitypub::{actor_url, handle};
+use crate::utilities::requires_auth::RequiresAuth;
+use async_graphql::connection::{Connection, Edge, EmptyFields};
+use async_graphql::{Context, Object, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::NaiveDateTime;
+use repositories::{ActivityPubRepository, ACTIVITYPUB_DEFAULT_PAGE_SIZE};
+use sea_orm::*;
+use serde::{Deserialize, Serialize};
+use services::config::SiteConfig;
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize)]
+struct FediverseCursor {
+    id: Uuid,
+    created_at: String,
+}
+
+fn encode_cursor(id: Uuid, created_at: NaiveDateTime) -> String {
+    let c = FediverseCursor {
+        id,
+        created_at: created_at.and_utc().to_rfc3339(),
+    };
+    URL_SAFE_NO_PAD.encode(serde_json::to_string(&c).unwrap())
+}
+
+fn decode_cursor(s: &str) -> (Option<Uuid>, Option<NaiveDateTime>) {
+    let c: Option<FediverseCursor> = URL_SAFE_NO_PAD
+        .decode(s)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok());
+    match c {
+        Some(c) => {
+            let dt = chrono::DateTime::parse_from_rfc3339(&c.created_at)
+                .ok()
+                .map(|d| d.naive_utc());
+            (Some(c.id), dt)
+        }
+        None => (None, None),
+    }
+}
+
+#[derive(Default)]
+pub struct FediverseQueries;
+
+impl RequiresAuth for FediverseQueries {}
+
+#[Object]
+impl FediverseQueries {
+    /// Your blog's fediverse account, or null until you enable federation
+    async fn fediverse_account(&self, ctx: &Context<'_>) -> Result<Option<FediverseAccount>> {
+        let user = self.require_authenticate_as_user(ctx).await?;
+        let db = ctx.data::<DatabaseConnection>().unwrap();
+        let site = ctx.data_opt::<SiteConfig>().cloned().unwrap_or_default();
+
+        let Some(actor) = ActivityPubRepository::actor_for_user(db, user.id)
+            .await
+            .map_err(async_graphql::Error::new)?
+        else {
+            return Ok(None);
+        };
+        let follower_count = ActivityPubRepository::count_followers(db, user.id)
+            .await
+            .map_err(async_graphql::Error::new)?;
+        Ok(Some(FediverseAccount {
+            handle: handle(&site, &actor.username),
+            actor_url: actor_url(&site, &actor.username),
+            username: actor.username,
+            follower_count,
+            created_at: actor.created_at,
+        }))
+    }
+
+    /// The fediverse accounts following your blog, newest first
+    async fn fediverse_followers(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        first: Option<i32>,
+    ) -> Result<Connection<String, FediverseFollower, EmptyFields, EmptyFields>> {
+        let user = self.require_authenticate_as_user(ctx).await?;
+        let db = ctx.data::<DatabaseConnection>().unwrap();
+
+        let limit = first
+            .map(|n| (n as u64).min(100))
+            .unwrap_or(ACTIVITYPUB_DEFAULT_PAGE_SIZE);
+        let (after_id, after_created_at) = after.as_deref().map_or((None, None), decode_cursor);
+
+        let rows = ActivityPubRepository::list_followers(db, user.id, after_id, after_created_at, Some(limit + 1))
+            .await
+            .map_err(async_graphql::Error::new)?;
+
+        let mut connection = Connection::new(after.is_some(), rows.len() as u64 > limit);
+        for row in rows.into_iter().take(limit as usize) {
+            connection.edges.push(Edge::new(encode_cursor(row.id, row.created_at), FediverseFollower::from(row)));
+        }
+        Ok(connection)
+    }
+
+    /// The activities your blog has sent, or will send, to its followers'
+    /// inboxes, newest first
+    async fn fediverse_deliveries(
+        &self,
+        ctx: &Context<'_>,
+        status: Option<DeliveryStatus>,
+        after: Option<String>,
+        first: Option<i32>,
+    ) -> Result<Connection<String, FediverseDelivery, EmptyFields, EmptyFields>> {
+        let user = self.require_authenticate_as_user(ctx).await?;
+        let db = ctx.data::<DatabaseConnection>().unwrap();
+
+        let limit = first
+            .map(|n| (n as u64).min(100))
+            .unwrap_or(ACTIVITYPUB_DEFAULT_PAGE_SIZE);
+        let (after_id, after_created_at) = after.as_deref().map_or((None, None), decode_cursor);
+
+        let rows = ActivityPubRepository::list_deliveries(
+            db,
+            user.id,
+            status.map(Into::into),
+            after_id,
+            after_created_at,
+            Some(limit + 1),
+        )
+        .await
+        .map_err(async_graphql::Error::new)?;
+
+        let mut connection = Connection::new(after.is_some(), rows.len() as u64 > limit);
+        for row in rows.into_iter().take(limit as usize) {
+            connection.edges.push(Edge::new(encode_cursor(row.id, row.created_at), FediverseDelivery::from(row)));
+        }
+        Ok(connection)
+    }
+}
@@ -1,7 +1,23 @@
 use async_graphql::MergedObject;
 mod assets;
+mod comments;
+mod fediverse;
+mod feed_pings;
+mod newsletter;
 mod posts;
 mod users;
+mod webhooks;
+mod webmentions;
 
 #[derive(MergedObject, Default)]
-pub struct Queries(users::UserQueries, posts::PostQueries, assets::AssetQueries);
+pub struct Queries(
+    users::UserQueries,
+    posts::PostQueries,
+    assets::AssetQueries,
+    comments::CommentQueries,
+    newsletter::NewsletterQueries,
+    webhooks::WebhookQueries,
+    feed_pings::FeedPingQueries,
+    webmentions::WebmentionQueries,
+    fediverse::FediverseQueries,
+);
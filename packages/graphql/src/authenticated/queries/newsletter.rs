@@ -0,0 +1,119 @@
+use crate::types::newsletter::{DeliveryStatus, NewsletterDelivery, Subscriber, SubscriberStatus};
+use crate::utilities::requires_auth::RequiresAuth;
+use async_graphql::connection::{Connection, Edge, EmptyFields};
+use async_graphql::{Context, Object, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::NaiveDateTime;
+use repositories::{NewsletterRepository, SubscriberRepository, SUBSCRIBER_DEFAULT_PAGE_SIZE};
+use sea_orm::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize)]
+struct NewsletterCursor {
+    id: Uuid,
+    created_at: String,
+}
+
+fn encode_cursor(id: Uuid, created_at: NaiveDateTime) -> String {
+    let c = NewsletterCursor {
+        id,
+        created_at: created_at.and_utc().to_rfc3339(),
+    };
+    URL_SAFE_NO_PAD.encode(serde_json::to_string(&c).unwrap())
+}
+
+fn decode_cursor(s: &str) -> (Option<Uuid>, Option<NaiveDateTime>) {
+    let c: Option<NewsletterCursor> = URL_SAFE_NO_PAD
+        .decode(s)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok());
+    match c {
+        Some(c) => {
+            let dt = chrono::DateTime::parse_from_rfc3339(&c.created_at)
+                .ok()
+                .map(|d| d.naive_utc());
+            (Some(c.id), dt)
+        }
+        None => (None, None),
+    }
+}
+
+#[derive(Default)]
+pub struct NewsletterQueries;
+
+impl RequiresAuth for NewsletterQueries {}
+
+#[Object]
+impl NewsletterQueries {
+    /// People subscribed to your posts by email, newest first
+    async fn subscribers(
+        &self,
+        ctx: &Context<'_>,
+        status: Option<SubscriberStatus>,
+        after: Option<String>,
+        first: Option<i32>,
+    ) -> Result<Connection<String, Subscriber, EmptyFields, EmptyFields>> {
+        let user = self.require_authenticate_as_user(ctx).await?;
+        let db = ctx.data::<DatabaseConnection>().unwrap();
+
+        let limit = first
+            .map(|n| (n as u64).min(100))
+            .unwrap_or(SUBSCRIBER_DEFAULT_PAGE_SIZE);
+        let (after_id, after_created_at) = after.as_deref().map_or((None, None), decode_cursor);
+
+        let rows = SubscriberRepository::list(
+            db,
+            user.id,
+            status.map(Into::into),
+            after_id,
+            after_created_at,
+            Some(limit + 1),
+        )
+        .await
+        .map_err(async_graphql::Error::new)?;
+
+        let mut connection = Connection::new(after.is_some(), rows.len() as u64 > limit);
+        for row in rows.into_iter().take(limit as usize) {
+            connection.edges.push(Edge::new(encode_cursor(row.id, row.created_at), Subscriber::from(row)));
+        }
+        Ok(connection)
+    }
+
+    /// Emails of one of your posts to its subscribers, with how sending went
+    async fn newsletter_deliveries(
+        &self,
+        ctx: &Context<'_>,
+        post_id: Uuid,
+        status: Option<DeliveryStatus>,
+        after: Option<String>,
+        first: Option<i32>,
+    ) -> Result<Connection<String, NewsletterDelivery, EmptyFields, EmptyFields>> {
+        let user = self.require_authenticate_as_user(ctx).await?;
+        let db = ctx.data::<DatabaseConnection>().unwrap();
+
+        let limit = first
+            .map(|n| (n as u64).min(100))
+            .unwrap_or(SUBSCRIBER_DEFAULT_PAGE_SIZE);
+        let (after_id, after_created_at) = after.as_deref().map_or((None, None), decode_cursor);
+
+        let rows = NewsletterRepository::list_for_post(
+            db,
+            user.id,
+            post_id,
+            status.map(Into::into),
+            after_id,
+            after_created_at,
+            Some(limit + 1),
+        )
+        .await
+        .map_err(async_graphql::Error::new)?;
+
+        let mut connection = Connection::new(after.is_some(), rows.len() as u64 > limit);
+        for row in rows.into_iter().take(limit as usize) {
+            let cursor = encode_cursor(row.0.id, row.0.created_at);
+            connection.edges.push(Edge::new(cursor, NewsletterDelivery::from(row)));
+        }
+        Ok(connection)
+    }
+}
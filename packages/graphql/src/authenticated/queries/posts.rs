@@ -29,12 +29,16 @@ impl From<SortDirection> for repositories::SortDirection {
 fn model_to_post_type(p: &models::posts::Model) -> PostType {
     PostType {
         id: p.id,
+        user_id: p.user_id,
         title: p.title.clone(),
         markdown_content: p.markdown_content.clone().unwrap_or_default(),
         description: p.description.clone(),
         slug: p.slug.clone(),
         cover_image: p.cover_image.clone(),
         is_published: p.is_published,
+        render_math: p.render_math,
+        rendered_markdown: p.rendered_markdown.clone(),
+        seo: p.seo.clone(),
         first_published_at: p.first_published_at,
         created_at: p.created_at,
         updated_at: p.updated_at,
@@ -319,4 +323,147 @@ mod tests {
         cleanup_test_user_by_email(&db, &email).await;
     }
 
+    #[tokio::test]
+    async fn test_post_table_of_contents_matches_content_anchors() {
+        let db = setup_test_db().await;
+        let schema = create_test_schema(db.clone());
+        let email = generate_unique_email("post_toc");
+        let user = create_test_user_with_password(&db, &email, &valid_password()).await;
+        let token = create_access_token(&user);
+        let post = create_test_post(&db, user.id, "Guide", "# Guide\n\n## Install\n\n## Install", false).await;
+
+        let query = format!(
+            r#"query {{ post(id: "{}") {{
+                content
+                tableOfContents {{ level text anchor children {{ level text anchor }} }}
+            }} }}"#,
+            post.id
+        );
+
+        let res = schema
+            .execute(Request::new(&query).data(Token::new(token)))
+            .await;
+        assert!(res.errors.is_empty(), "Errors: {:?}", res.errors);
+        let data = res.data.into_json().unwrap();
+
+        let toc = &data["post"]["tableOfContents"];
+        assert_eq!(toc[0]["anchor"], "guide");
+        assert_eq!(toc[0]["children"][0]["anchor"], "install");
+        assert_eq!(toc[0]["children"][1]["anchor"], "install-1");
+        assert_eq!(toc[0]["children"][1]["level"], 2);
+        let content = data["post"]["content"].as_str().unwrap();
+        assert!(content.contains(r#"<h2 id="install-1">"#), "{content}");
+
+        cleanup_test_user_by_email(&db, &email).await;
+    }
+
+    #[tokio::test]
+    async fn test_post_content_respects_render_math() {
+        let db = setup_test_db().await;
+        let schema = create_test_schema(db.clone());
+        let email = generate_unique_email("post_math");
+        let user = create_test_user_with_password(&db, &email, &valid_password()).await;
+        let token = create_access_token(&user);
+        let post = create_test_post(&db, user.id, "Math", "$x^2$", false).await;
+        let query = format!(r#"query {{ post(id: "{}") {{ renderMath content }} }}"#, post.id);
+
+        let res = schema
+            .execute(Request::new(&query).data(Token::new(token.clone())))
+            .await;
+        assert!(res.errors.is_empty(), "Errors: {:?}", res.errors);
+        let data = res.data.into_json().unwrap();
+        assert_eq!(data["post"]["renderMath"], true);
+        assert!(data["post"]["content"].as_str().unwrap().contains("<math"));
+
+        repositories::PostRepository::set_render_math(&db, user.id, post.id, false).await.unwrap();
+        let res = schema
+            .execute(Request::new(&query).data(Token::new(token)))
+            .await;
+        let data = res.data.into_json().unwrap();
+        assert_eq!(data["post"]["renderMath"], false);
+        assert_eq!(data["post"]["content"], "<p>$x^2$</p>\n");
+
+        cleanup_test_user_by_email(&db, &email).await;
+    }
+
+    #[tokio::test]
+    async fn test_post_resolves_own_asset_references() {
+        use sea_orm::{ActiveModelTrait, ActiveValue, IntoActiveModel};
+
+        let db = setup_test_db().await;
+        let schema = create_test_schema(db.clone());
+        let email = generate_unique_email("post_assets");
+        let other_email = generate_unique_email("post_assets_other");
+        let user = create_test_user_with_password(&db, &email, &valid_password()).await;
+        let other = create_test_user_with_password(&db, &other_email, &valid_password()).await;
+        let token = create_access_token(&user);
+
+        let own = repositories::AssetRepository::create(&db, uuid::Uuid::new_v4(), user.id, "a.png".into(), "image/png".into(), 1, 2400, 1200)
+            .await
+            .unwrap();
+        let foreign = repositories::AssetRepository::create(&db, uuid::Uuid::new_v4(), other.id, "b.png".into(), "image/png".into(), 1, 10, 10)
+            .await
+            .unwrap();
+        let markdown = format!("![mine](asset:{}) ![theirs](asset:{})", own.id, foreign.id);
+        let post = create_test_post(&db, user.id, "Pics", &markdown, false).await;
+        let mut am = post.into_active_model();
+        am.cover_image = ActiveValue::Set(Some(format!("asset:{}", own.id)));
+        let post = am.update(&db).await.unwrap();
+
+        let query = format!(
+            r#"query {{ post(id: "{}") {{ coverImage content responsiveCoverImage {{ src srcset width height }} }} }}"#,
+            post.id
+        );
+        let res = schema
+            .execute(Request::new(&query).data(Token::new(token)))
+            .await;
+        assert!(res.errors.is_empty(), "Errors: {:?}", res.errors);
+        let data = res.data.into_json().unwrap();
+
+        assert_eq!(data["post"]["coverImage"], format!("asset:{}", own.id));
+        let cover = &data["post"]["responsiveCoverImage"];
+        assert_eq!(cover["src"], format!("/assets/{}/large.webp", own.id));
+        assert_eq!(cover["width"], 1920);
+        assert_eq!(cover["height"], 960);
+        assert!(cover["srcset"].as_str().unwrap().contains("thumbnail.webp 200w"));
+        let content = data["post"]["content"].as_str().unwrap();
+        assert!(content.contains(&format!("srcset=\"/assets/{}/thumbnail.webp 200w", own.id)), "{content}");
+        assert!(content.contains(&format!("src=\"asset:{}\"", foreign.id)), "{content}");
+
+        cleanup_test_user_by_email(&db, &email).await;
+        cleanup_test_user_by_email(&db, &other_email).await;
+    }
+
+    #[tokio::test]
+    async fn test_post_reading_stats_and_excerpt() {
+        let db = setup_test_db().await;
+        let schema = create_test_schema(db.clone());
+        let email = generate_unique_email("post_excerpt");
+        let user = create_test_user_with_password(&db, &email, &valid_password()).await;
+        let token = create_access_token(&user);
+        let markdown = format!("# Intro\n\nA **short** lead.\n\n<!--more-->\n\n{}", "word ".repeat(400));
+        let post = create_test_post(&db, user.id, "Long", &markdown, false).await;
+
+        let query = format!(
+            r#"query {{ post(id: "{}") {{ description wordCount readingTimeMinutes excerpt short: excerpt(length: 3) }} }}"#,
+            post.id
+        );
+        let res = schema
+            .execute(Request::new(&query).data(Token::new(token.clone())))
+            .await;
+        assert!(res.errors.is_empty(), "Errors: {:?}", res.errors);
+        let data = res.data.into_json().unwrap();
+
+        assert_eq!(data["post"]["description"], serde_json::Value::Null);
+        assert_eq!(data["post"]["wordCount"], 404);
+        assert_eq!(data["post"]["readingTimeMinutes"], 3);
+        assert_eq!(data["post"]["excerpt"], "Intro A short lead.");
+        assert_eq!(data["post"]["short"], "Intro A short lead.");
+
+        let query = format!(r#"query {{ post(id: "{}") {{ excerpt(length: 0) }} }}"#, post.id);
+        let res = schema.execute(Request::new(&query).data(Token::new(token))).await;
+        assert!(!res.errors.is_empty());
+
+        cleanup_test_user_by_email(&db, &email).await;
+    }
 }
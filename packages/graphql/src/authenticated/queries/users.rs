@@ -36,6 +36,7 @@ impl UserQueries {
         Ok(keys
             .into_iter()
             .map(|k| ApiKeyInfo {
+                scopes: api_keys::scopes(&k).into_iter().map(Into::into).collect(),
                 id: k.id,
                 label: k.label,
                 last_used_at: k.last_used_at,
@@ -0,0 +1,96 @@
+use crate::types::newsletter::DeliveryStatus;
+use crate::types::webhook::{Webhook, WebhookDelivery};
+use crate::utilities::requires_auth::RequiresAuth;
+use async_graphql::connection::{Connection, Edge, EmptyFields};
+use async_graphql::{Context, Object, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::NaiveDateTime;
+use repositories::{WebhookRepository, WEBHOOK_DELIVERY_DEFAULT_PAGE_SIZE};
+use sea_orm::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize)]
+struct DeliveryCursor {
+    id: Uuid,
+    created_at: String,
+}
+
+fn encode_cursor(m: &models::webhook_deliveries::Model) -> String {
+    let c = DeliveryCursor {
+        id: m.id,
+        created_at: m.created_at.and_utc().to_rfc3339(),
+    };
+    URL_SAFE_NO_PAD.encode(serde_json::to_string(&c).unwrap())
+}
+
+fn decode_cursor(s: &str) -> (Option<Uuid>, Option<NaiveDateTime>) {
+    let c: Option<DeliveryCursor> = URL_SAFE_NO_PAD
+        .decode(s)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok());
+    match c {
+        Some(c) => {
+            let dt = chrono::DateTime::parse_from_rfc3339(&c.created_at)
+                .ok()
+                .map(|d| d.naive_utc());
+            (Some(c.id), dt)
+        }
+        None => (None, None),
+    }
+}
+
+#[derive(Default)]
+pub struct WebhookQueries;
+
+impl RequiresAuth for WebhookQueries {}
+
+#[Object]
+impl WebhookQueries {
+    /// Your webhooks, oldest first
+    async fn webhooks(&self, ctx: &Context<'_>) -> Result<Vec<Webhook>> {
+        let user = self.require_authenticate_as_user(ctx).await?;
+        let db = ctx.data::<DatabaseConnection>().unwrap();
+
+        let rows = WebhookRepository::list(db, user.id)
+            .await
+            .map_err(async_graphql::Error::new)?;
+        Ok(rows.into_iter().map(Webhook::from).collect())
+    }
+
+    /// The delivery log of your webhooks, or of one of them, newest first
+    async fn webhook_deliveries(
+        &self,
+        ctx: &Context<'_>,
+        webhook_id: Option<Uuid>,
+        status: Option<DeliveryStatus>,
+        after: Option<String>,
+        first: Option<i32>,
+    ) -> Result<Connection<String, WebhookDelivery, EmptyFields, EmptyFields>> {
+        let user = self.require_authenticate_as_user(ctx).await?;
+        let db = ctx.data::<DatabaseConnection>().unwrap();
+
+        let limit = first
+            .map(|n| (n as u64).min(100))
+            .unwrap_or(WEBHOOK_DELIVERY_DEFAULT_PAGE_SIZE);
+        let (after_id, after_created_at) = after.as_deref().map_or((None, None), decode_cursor);
+
+        let rows = WebhookRepository::list_deliveries(
+            db,
+            user.id,
+            webhook_id,
+            status.map(Into::into),
+            after_id,
+            after_created_at,
+            Some(limit + 1),
+        )
+        .await
+        .map_err(async_graphql::Error::new)?;
+
+        let mut connection = Connection::new(after.is_some(), rows.len() as u64 > limit);
+        for row in rows.into_iter().take(limit as usize) {
+            connection.edges.push(Edge::new(encode_cursor(&row), WebhookDelivery::from(row)));
+        }
+        Ok(connection)
+    }
+}
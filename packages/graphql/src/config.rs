@@ -1 +1,3 @@
 pub struct SingleUserMode(pub bool);
+
+pub struct SecureCookies(pub bool);
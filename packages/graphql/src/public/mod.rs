@@ -1,43 +1,55 @@
+mod mutations;
+mod pow;
 mod queries;
 mod rate_limiter;
 mod types;
 
-pub use queries::{PublicApiKey, PublicQueryRoot};
+pub use mutations::PublicMutationRoot;
+pub use pow::{PowChallenge, PowGuard, ProofOfWork};
+pub use queries::{ClientIp, PublicApiKey, PublicQueryRoot};
 
-use async_graphql::{EmptyMutation, EmptySubscription, Schema};
+use async_graphql::{EmptySubscription, Schema};
 use rate_limiter::{BudgetLimiterFactory, SlidingBudget};
 use sea_orm::DatabaseConnection;
+use services::assets::StorageDriver;
+use services::config::{NewsletterConfig, PowConfig, PublicApiConfig, SiteConfig, SpamConfig};
+use services::email::EmailService;
 use std::sync::Arc;
 
 use crate::utilities::MarkdownCache;
 
-pub type PublicSchema = Schema<PublicQueryRoot, EmptyMutation, EmptySubscription>;
+pub type PublicSchema = Schema<PublicQueryRoot, PublicMutationRoot, EmptySubscription>;
 
-pub fn build_public_schema(db: DatabaseConnection, markdown_cache: MarkdownCache) -> PublicSchema {
-    let max_complexity = std::env::var("PUBLIC_MAX_COMPLEXITY")
-        .ok()
-        .and_then(|v| v.parse().ok())
-        .unwrap_or(200_usize);
-    let max_depth = std::env::var("PUBLIC_MAX_DEPTH")
-        .ok()
-        .and_then(|v| v.parse().ok())
-        .unwrap_or(5_usize);
-    let budget = std::env::var("PUBLIC_COMPLEXITY_BUDGET")
-        .ok()
-        .and_then(|v| v.parse().ok())
-        .unwrap_or(2000_usize);
-    let window_secs = std::env::var("PUBLIC_COMPLEXITY_WINDOW_SECS")
-        .ok()
-        .and_then(|v| v.parse().ok())
-        .unwrap_or(60_u64);
+#[allow(clippy::too_many_arguments)]
+pub fn build_public_schema(
+    db: DatabaseConnection,
+    markdown_cache: MarkdownCache,
+    storage_driver: Arc<StorageDriver>,
+    site: SiteConfig,
+    spam: SpamConfig,
+    pow: PowConfig,
+    newsletter: NewsletterConfig,
+    email: Option<EmailService>,
+    config: &PublicApiConfig,
+) -> PublicSchema {
+    let limiter = Arc::new(SlidingBudget::new(
+        config.complexity_window_secs,
+        config.complexity_budget,
+    ));
 
-    let limiter = Arc::new(SlidingBudget::new(window_secs, budget));
-
-    Schema::build(PublicQueryRoot::default(), EmptyMutation, EmptySubscription)
+    let mut builder = Schema::build(PublicQueryRoot, PublicMutationRoot, EmptySubscription)
         .data(db)
         .data(markdown_cache)
-        .limit_complexity(max_complexity)
-        .limit_depth(max_depth)
-        .extension(BudgetLimiterFactory(limiter))
-        .finish()
+        .data(storage_driver)
+        .data(site)
+        .data(spam)
+        .data(PowGuard::new(pow, limiter.clone()))
+        .data(newsletter)
+        .limit_complexity(config.max_complexity)
+        .limit_depth(config.max_depth)
+        .extension(BudgetLimiterFactory(limiter));
+    if let Some(email) = email {
+        builder = builder.data(email);
+    }
+    builder.finish()
 }
@@ -0,0 +1,535 @@
+use super::pow::{require_proof, ProofOfWork};
+use super::queries::require_user;
+use crate::types::comment::CommentStatus;
+use crate::utilities::comments::{comments_open, screen};
+use crate::utilities::newsletter::confirm_url;
+use async_graphql::{Context, InputObject, Object, Result, SimpleObject};
+use models::posts;
+use models::sea_orm_active_enums::CommentStatus as StoredStatus;
+use models::sea_orm_active_enums::SubscriberStatus;
+use repositories::{CommentRepository, NewComment, SubscriberRepository, UserRepository};
+use sea_orm::entity::prelude::Uuid;
+use sea_orm::*;
+use services::config::{NewsletterConfig, SiteConfig, SpamConfig};
+use services::email::EmailService;
+use services::newsletter::{create_confirmation_token, redeem_confirmation_token, verify_unsubscribe_token};
+use services::spam::Submission;
+use url::Url;
+
+const MAX_NAME_LENGTH: usize = 100;
+const MAX_EMAIL_LENGTH: usize = 254;
+const MAX_URL_LENGTH: usize = 2000;
+const MAX_BODY_LENGTH: usize = 5000;
+
+#[derive(InputObject)]
+pub struct SubmitCommentInput {
+    pub post_id: Uuid,
+    /// The approved comment on the same post this one replies to
+    pub parent_id: Option<Uuid>,
+    pub author_name: String,
+    /// Shown only to the blog's owner
+    pub author_email: Option<String>,
+    pub author_url: Option<String>,
+    /// Markdown: emphasis, code, quotes, lists and links
+    pub body: String,
+    /// A form field hidden from people, sent as it is. Bots fill it in.
+    pub honeypot: Option<String>,
+    /// When the comment form was shown, for telling bots that submit
+    /// instantly from people
+    pub form_rendered_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(SimpleObject)]
+pub struct SubmittedComment {
+    pub id: Uuid,
+    /// `APPROVED` if the comment is already shown, `PENDING` if it awaits
+    /// moderation. Comments filed as spam are reported as `PENDING` too.
+    pub status: CommentStatus,
+}
+
+fn blank_to_none(value: Option<String>) -> Option<String> {
+    value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+/// Trim the input and check it's within bounds.
+fn validate(input: &SubmitCommentInput) -> Result<NewComment, String> {
+    let author_name = input.author_name.trim().to_string();
+    if author_name.is_empty() || author_name.chars().count() > MAX_NAME_LENGTH {
+        return Err(format!("authorName must be 1 to {MAX_NAME_LENGTH} characters"));
+    }
+    let author_email = blank_to_none(input.author_email.clone());
+    if let Some(email) = &author_email
+        && (email.len() > MAX_EMAIL_LENGTH || !email.contains('@'))
+    {
+        return Err("authorEmail must be an email address".to_string());
+    }
+    let author_url = blank_to_none(input.author_url.clone());
+    if let Some(url) = &author_url {
+        let valid = url.len() <= MAX_URL_LENGTH
+            && Url::parse(url).is_ok_and(|u| matches!(u.scheme(), "http" | "https"));
+        if !valid {
+            return Err("authorUrl must be an http(s) URL".to_string());
+        }
+    }
+    let body = input.body.trim().to_string();
+    if body.is_empty() || body.chars().count() > MAX_BODY_LENGTH {
+        return Err(format!("body must be 1 to {MAX_BODY_LENGTH} characters"));
+    }
+    Ok(NewComment {
+        post_id: input.post_id,
+        user_id: Uuid::nil(),
+        parent_id: input.parent_id,
+        author_name,
+        author_email,
+        author_url,
+        body,
+    })
+}
+
+#[derive(Default)]
+pub struct PublicMutationRoot;
+
+#[Object]
+impl PublicMutationRoot {
+    /// Comment on one of the blog's published posts. Depending on the blog's
+    /// settings the comment is shown straight away or queued for moderation.
+    /// Needs a solved `powChallenge`.
+    #[graphql(complexity = 10)]
+    async fn submit_comment(
+        &self,
+        ctx: &Context<'_>,
+        input: SubmitCommentInput,
+        proof: ProofOfWork,
+    ) -> Result<SubmittedComment> {
+        let user_id = require_user(ctx).await?;
+        require_proof(ctx, &proof).await?;
+        let db = ctx.data::<DatabaseConnection>().unwrap();
+        let mut comment = validate(&input).map_err(async_graphql::Error::new)?;
+        comment.user_id = user_id;
+
+        let post = posts::Entity::find_by_id(comment.post_id)
+            .filter(posts::Column::UserId.eq(user_id))
+            .filter(posts::Column::IsPublished.eq(true))
+            .one(db)
+            .await
+            .map_err(|e| async_graphql::Error::new(e.to_string()))?
+            .ok_or_else(|| async_graphql::Error::new("Post not found"))?;
+        let user = UserRepository::find_by_id(db, user_id)
+            .await
+            .map_err(|e| async_graphql::Error::new(e.to_string()))?
+            .ok_or_else(|| async_graphql::Error::new("User not found"))?;
+        let now = chrono::Utc::now().naive_utc();
+        if !comments_open(user.comment_policy, user.comments_close_after_days, post.first_published_at, now) {
+            return Err(async_graphql::Error::new("Comments are closed"));
+        }
+
+        if let Some(parent_id) = comment.parent_id {
+            let parent = CommentRepository::find_by_id(db, parent_id)
+                .await
+                .map_err(async_graphql::Error::new)?;
+            if !parent.is_some_and(|p| p.post_id == post.id && p.status == StoredStatus::Approved) {
+                return Err(async_graphql::Error::new("Parent comment not found"));
+            }
+        }
+
+        let submission = Submission {
+            author_name: &comment.author_name,
+            author_email: comment.author_email.as_deref(),
+            author_url: comment.author_url.as_deref(),
+            body: &comment.body,
+            honeypot: input.honeypot.as_deref(),
+            elapsed: input.form_rendered_at.and_then(|at| (chrono::Utc::now() - at).to_std().ok()),
+        };
+        let config = ctx.data_opt::<SpamConfig>().cloned().unwrap_or_default();
+        let (status, score) = screen(db, &user, post.id, &submission, &config)
+            .await
+            .map_err(async_graphql::Error::new)?;
+
+        let saved = CommentRepository::create(db, comment, status, Some(score as f32))
+            .await
+            .map_err(async_graphql::Error::new)?;
+        // Telling a spammer their comment was caught only helps them retry.
+        let status = match saved.status {
+            StoredStatus::Spam => CommentStatus::Pending,
+            status => status.into(),
+        };
+        Ok(SubmittedComment { id: saved.id, status })
+    }
+
+    /// Subscribe an address to the blog's new posts. A confirmation link is
+    /// emailed to it, and nothing else is sent until it's followed. Returns
+    /// true whether or not the address was already subscribed. Needs a
+    /// solved `powChallenge`.
+    #[graphql(complexity = 10)]
+    async fn subscribe(&self, ctx: &Context<'_>, email: String, proof: ProofOfWork) -> Result<bool> {
+        let user_id = require_user(ctx).await?;
+        require_proof(ctx, &proof).await?;
+        let db = ctx.data::<DatabaseConnection>().unwrap();
+        let email = email.trim().to_lowercase();
+        if email.len() > MAX_EMAIL_LENGTH || !email.contains('@') {
+            return Err(async_graphql::Error::new("email must be an email address"));
+        }
+
+        let Some(subscriber) = SubscriberRepository::subscribe(db, user_id, email)
+            .await
+            .map_err(async_graphql::Error::new)?
+        else {
+            return Ok(true);
+        };
+        if let Ok(email_service) = ctx.data::<EmailService>() {
+            let config = ctx.data::<NewsletterConfig>().unwrap();
+            match create_confirmation_token(db, subscriber.id, config.confirm_ttl).await {
+                Ok(raw_token) => {
+                    let site = ctx.data::<SiteConfig>().unwrap();
+                    let link = confirm_url(site, &raw_token);
+                    if let Err(e) = email_service
+                        .send_subscription_confirmation(&subscriber.email, &site.title, &link)
+                        .await
+                    {
+                        tracing::warn!(subscriber_id = %subscriber.id, error = %e, "failed to send subscription confirmation");
+                    }
+                }
+                Err(e) => tracing::warn!(subscriber_id = %subscriber.id, error = %e.message, "failed to create confirmation token"),
+            }
+        }
+        Ok(true)
+    }
+
+    /// Confirm a subscription with the token from its confirmation email.
+    /// The token stands in for a `powChallenge`.
+    async fn confirm_subscription(&self, ctx: &Context<'_>, token: String) -> Result<bool> {
+        let user_id = require_user(ctx).await?;
+        let db = ctx.data::<DatabaseConnection>().unwrap();
+        let subscriber_id = redeem_confirmation_token(db, &token, user_id)
+            .await
+            .map_err(|e| async_graphql::Error::new(e.message))?;
+        let subscriber = SubscriberRepository::find_by_id(db, subscriber_id)
+            .await
+            .map_err(async_graphql::Error::new)?
+            .filter(|s| s.user_id == user_id)
+            .ok_or_else(|| async_graphql::Error::new("Invalid or expired token"))?;
+        if subscriber.status == SubscriberStatus::Unsubscribed {
+            return Err(async_graphql::Error::new("Invalid or expired token"));
+        }
+        SubscriberRepository::confirm(db, subscriber.id)
+            .await
+            .map_err(async_graphql::Error::new)?;
+        Ok(true)
+    }
+
+    /// Stop emailing a subscriber, with the `subscriber` and `token` from the
+    /// unsubscribe link in any of their emails. The token stands in for a
+    /// `powChallenge`.
+    async fn unsubscribe(&self, ctx: &Context<'_>, subscriber_id: Uuid, token: String) -> Result<bool> {
+        let user_id = require_user(ctx).await?;
+        let db = ctx.data::<DatabaseConnection>().unwrap();
+        let config = ctx.data::<NewsletterConfig>().unwrap();
+        if !verify_unsubscribe_token(&config.secret, subscriber_id, &token) {
+            return Err(async_graphql::Error::new("Invalid unsubscribe link"));
+        }
+        let subscriber = SubscriberRepository::find_by_id(db, subscriber_id)
+            .await
+            .map_err(async_graphql::Error::new)?
+            .filter(|s| s.user_id == user_id)
+            .ok_or_else(|| async_graphql::Error::new("Invalid unsubscribe link"))?;
+        if subscriber.status != SubscriberStatus::Unsubscribed {
+            SubscriberRepository::unsubscribe(db, subscriber.id)
+                .await
+                .map_err(async_graphql::Error::new)?;
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::public::{build_public_schema, PublicApiKey};
+    use crate::test_helpers::*;
+    use crate::utilities::MarkdownCache;
+    use async_graphql::Request;
+    use models::sea_orm_active_enums::CommentPolicy;
+    use crate::public::PublicSchema;
+    use services::config::{PowConfig, PublicApiConfig};
+    use repositories::SpamRepository;
+    use services::authentication::Token;
+    use services::spam;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    fn public_schema(db: &DatabaseConnection) -> PublicSchema {
+        let pow = PowConfig { secret: "test".to_string(), difficulty: 4, max_difficulty: 8, ttl: Duration::from_secs(60) };
+        build_public_schema(
+            db.clone(),
+            MarkdownCache::new(),
+            test_storage_driver(),
+            SiteConfig::default(),
+            SpamConfig::default(),
+            pow,
+            newsletter_config(),
+            None,
+            &PublicApiConfig::default(),
+        )
+    }
+
+    fn newsletter_config() -> NewsletterConfig {
+        NewsletterConfig { secret: "test".to_string(), batch_size: 1000, ..Default::default() }
+    }
+
+    /// A solved challenge for `raw_key`, as a `proof` argument.
+    async fn proof(schema: &PublicSchema, raw_key: &str) -> String {
+        let res = schema
+            .execute(Request::new("{ powChallenge { challenge difficulty } }").data(PublicApiKey(raw_key.to_string())))
+            .await;
+        let data = res.data.into_json().unwrap();
+        let challenge = data["powChallenge"]["challenge"].as_str().unwrap();
+        let difficulty = data["powChallenge"]["difficulty"].as_u64().unwrap() as u32;
+        let solution = services::pow::solve(challenge, difficulty);
+        format!(r#"{{ challenge: "{challenge}", solution: "{solution}" }}"#)
+    }
+
+    fn input(body: &str) -> SubmitCommentInput {
+        SubmitCommentInput {
+            post_id: Uuid::nil(),
+            parent_id: None,
+            author_name: " Reader ".to_string(),
+            author_email: Some("".to_string()),
+            author_url: None,
+            body: body.to_string(),
+            honeypot: None,
+            form_rendered_at: None,
+        }
+    }
+
+    #[test]
+    fn validate_trims_and_bounds_fields() {
+        let comment = validate(&input(" hi ")).unwrap();
+        assert_eq!(comment.author_name, "Reader");
+        assert_eq!(comment.author_email, None);
+        assert_eq!(comment.body, "hi");
+        assert!(validate(&input("  ")).unwrap_err().contains("body"));
+        let url = SubmitCommentInput { author_url: Some("javascript:alert(1)".to_string()), ..input("hi") };
+        assert!(validate(&url).unwrap_err().contains("authorUrl"));
+    }
+
+    #[tokio::test]
+    async fn test_submit_comment_follows_blog_policy() {
+        let db = setup_test_db().await;
+        let schema = public_schema(&db);
+        let email = generate_unique_email("public_comment");
+        let user = create_test_user_with_password(&db, &email, &valid_password()).await;
+        let (raw_key, key_hash) = services::api_keys::generate();
+        services::api_keys::create(&db, user.id, "test".to_string(), key_hash).await.unwrap();
+        let post = create_test_post(&db, user.id, "Title", "content", true).await;
+        let draft = create_test_post(&db, user.id, "Draft", "content", false).await;
+
+        let submit = |post_id: Uuid, parent: Option<Uuid>, proof: String| {
+            let parent = parent.map(|p| format!(r#", parentId: "{p}""#)).unwrap_or_default();
+            Request::new(format!(
+                r#"mutation {{ submitComment(input: {{ postId: "{post_id}"{parent}, authorName: "Ada", body: "Hello" }}, proof: {proof}) {{ id status }} }}"#
+            ))
+            .data(PublicApiKey(raw_key.clone()))
+        };
+
+        let res = schema.execute(submit(post.id, None, proof(&schema, &raw_key).await)).await;
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+        let data = res.data.into_json().unwrap();
+        assert_eq!(data["submitComment"]["status"], "PENDING");
+        let pending: Uuid = data["submitComment"]["id"].as_str().unwrap().parse().unwrap();
+
+        let res = schema.execute(submit(post.id, Some(pending), proof(&schema, &raw_key).await)).await;
+        assert_eq!(res.errors[0].message, "Parent comment not found");
+        let res = schema.execute(submit(draft.id, None, proof(&schema, &raw_key).await)).await;
+        assert_eq!(res.errors[0].message, "Post not found");
+
+        UserRepository::set_comment_settings(&db, user.id, CommentPolicy::Open, None).await.unwrap();
+        let res = schema.execute(submit(post.id, None, proof(&schema, &raw_key).await)).await;
+        let data = res.data.into_json().unwrap();
+        assert_eq!(data["submitComment"]["status"], "APPROVED");
+        let approved: Uuid = data["submitComment"]["id"].as_str().unwrap().parse().unwrap();
+        let res = schema.execute(submit(post.id, Some(approved), proof(&schema, &raw_key).await)).await;
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+
+        let query = format!(
+            r#"query {{ post(id: "{}") {{ commentCount commentsOpen comments {{ id html replies {{ authorName }} }} }} }}"#,
+            post.id
+        );
+        let res = schema.execute(Request::new(query).data(PublicApiKey(raw_key.clone()))).await;
+        let data = res.data.into_json().unwrap();
+        assert_eq!(data["post"]["commentCount"], 2);
+        assert_eq!(data["post"]["commentsOpen"], true);
+        assert_eq!(data["post"]["comments"][0]["id"], approved.to_string());
+        assert_eq!(data["post"]["comments"][0]["html"], "<p>Hello</p>\n");
+        assert_eq!(data["post"]["comments"][0]["replies"][0]["authorName"], "Ada");
+
+        UserRepository::set_comment_settings(&db, user.id, CommentPolicy::Closed, None).await.unwrap();
+        let res = schema.execute(submit(post.id, None, proof(&schema, &raw_key).await)).await;
+        assert_eq!(res.errors[0].message, "Comments are closed");
+
+        cleanup_test_user_by_email(&db, &email).await;
+    }
+
+    #[tokio::test]
+    async fn test_spam_is_filed_quietly_and_moderation_trains_the_filter() {
+        let db = setup_test_db().await;
+        let schema = public_schema(&db);
+        let email = generate_unique_email("public_comment_spam");
+        let user = create_test_user_with_password(&db, &email, &valid_password()).await;
+        let (raw_key, key_hash) = services::api_keys::generate();
+        services::api_keys::create(&db, user.id, "test".to_string(), key_hash).await.unwrap();
+        let post = create_test_post(&db, user.id, "Title", "content", true).await;
+
+        let query = format!(
+            r#"mutation {{ submitComment(input: {{ postId: "{}", authorName: "Bot", body: "Cheap pills", honeypot: "x" }}, proof: {}) {{ id status }} }}"#,
+            post.id,
+            proof(&schema, &raw_key).await
+        );
+        let res = schema.execute(Request::new(query).data(PublicApiKey(raw_key))).await;
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+        let data = res.data.into_json().unwrap();
+        assert_eq!(data["submitComment"]["status"], "PENDING");
+        let id: Uuid = data["submitComment"]["id"].as_str().unwrap().parse().unwrap();
+        let stored = CommentRepository::get(&db, user.id, id).await.unwrap().unwrap();
+        assert_eq!(stored.status, StoredStatus::Spam);
+        assert_eq!(stored.spam_score, Some(1.0));
+
+        let admin = create_test_schema(db.clone());
+        let query = format!(r#"mutation {{ moderateComment(id: "{id}", status: SPAM) {{ ... on Comment {{ spamScore }} }} }}"#);
+        let res = admin.execute(Request::new(query).data(Token(create_access_token(&user)))).await;
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+        let tokens = spam::tokenize(&Submission::from(&stored));
+        let corpus = SpamRepository::corpus(&db, user.id, &tokens).await.unwrap();
+        assert_eq!(corpus.spam_messages, 1);
+        assert_eq!(corpus.tokens["pills"], (1, 0));
+
+        cleanup_test_user_by_email(&db, &email).await;
+    }
+
+    #[tokio::test]
+    async fn test_submit_comment_needs_a_fresh_proof_for_its_key() {
+        let db = setup_test_db().await;
+        let schema = public_schema(&db);
+        let email = generate_unique_email("public_comment_pow");
+        let user = create_test_user_with_password(&db, &email, &valid_password()).await;
+        let (raw_key, key_hash) = services::api_keys::generate();
+        services::api_keys::create(&db, user.id, "test".to_string(), key_hash).await.unwrap();
+        let (other_key, other_hash) = services::api_keys::generate();
+        services::api_keys::create(&db, user.id, "other".to_string(), other_hash).await.unwrap();
+        let post = create_test_post(&db, user.id, "Title", "content", true).await;
+
+        let submit = |key: &str, proof: &str| {
+            Request::new(format!(
+                r#"mutation {{ submitComment(input: {{ postId: "{}", authorName: "Ada", body: "Hello" }}, proof: {proof}) {{ id }} }}"#,
+                post.id
+            ))
+            .data(PublicApiKey(key.to_string()))
+        };
+
+        let res = schema.execute(submit(&raw_key, r#"{ challenge: "v1.x.0.0.x", solution: "0" }"#)).await;
+        assert_eq!(res.errors[0].message, "Malformed challenge");
+
+        let proof = proof(&schema, &raw_key).await;
+        let res = schema.execute(submit(&other_key, &proof)).await;
+        assert_eq!(res.errors[0].message, "Challenge was not issued for this API key");
+        let res = schema.execute(submit(&raw_key, &proof)).await;
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+        let res = schema.execute(submit(&raw_key, &proof)).await;
+        assert_eq!(res.errors[0].message, "Challenge already used");
+
+        cleanup_test_user_by_email(&db, &email).await;
+    }
+
+    #[tokio::test]
+    async fn test_newsletter_subscription_delivery_and_unsubscribe() {
+        use sea_orm::{ActiveValue, IntoActiveModel};
+
+        let db = setup_test_db().await;
+        let schema = public_schema(&db);
+        let email = generate_unique_email("newsletter");
+        let user = create_test_user_with_password(&db, &email, &valid_password()).await;
+        let mut am = user.into_active_model();
+        am.email_verified_at = ActiveValue::Set(Some(chrono::Utc::now().naive_utc()));
+        let user = am.update(&db).await.unwrap();
+        let (raw_key, key_hash) = services::api_keys::generate();
+        services::api_keys::create(&db, user.id, "test".to_string(), key_hash).await.unwrap();
+        let reader = generate_unique_email("newsletter_reader");
+        let public = |query: String| Request::new(query).data(PublicApiKey(raw_key.clone()));
+
+        let subscribe = format!(
+            r#"mutation {{ subscribe(email: " {} ", proof: {}) }}"#,
+            reader.to_uppercase(),
+            proof(&schema, &raw_key).await
+        );
+        let res = schema.execute(public(subscribe)).await;
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+        let subscriber = SubscriberRepository::subscribe(&db, user.id, reader.clone()).await.unwrap().unwrap();
+        assert_eq!(subscriber.status, SubscriberStatus::Pending);
+
+        let res = schema.execute(public(r#"mutation { confirmSubscription(token: "nope") }"#.to_string())).await;
+        assert_eq!(res.errors[0].message, "Invalid or expired token");
+        let token = create_confirmation_token(&db, subscriber.id, Duration::from_secs(60)).await.unwrap();
+        let confirm = format!(r#"mutation {{ confirmSubscription(token: "{token}") }}"#);
+        // Another blog's key can't use up the token.
+        let other_email = generate_unique_email("newsletter_other");
+        let other = create_test_user_with_password(&db, &other_email, &valid_password()).await;
+        let (other_key, other_hash) = services::api_keys::generate();
+        services::api_keys::create(&db, other.id, "test".to_string(), other_hash).await.unwrap();
+        let res = schema.execute(Request::new(confirm.clone()).data(PublicApiKey(other_key))).await;
+        assert_eq!(res.errors[0].message, "Invalid or expired token");
+        cleanup_test_user_by_email(&db, &other_email).await;
+        let res = schema.execute(public(confirm.clone())).await;
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+        let res = schema.execute(public(confirm)).await;
+        assert_eq!(res.errors[0].message, "Token already used");
+
+        // Publishing a draft queues it once; later edits don't queue it again.
+        let admin = create_test_schema(db.clone());
+        let post = create_test_post(&db, user.id, "News & views", "Hello *readers*", false).await;
+        let publish = format!(
+            r#"mutation {{ updatePost(post: {{ id: "{}", title: "News & views", content: "Hello *readers*", isPublished: true }}) {{ ... on Post {{ id }} }} }}"#,
+            post.id
+        );
+        for _ in 0..2 {
+            let res = admin.execute(Request::new(&publish).data(Token::new(create_access_token(&user)))).await;
+            assert!(res.errors.is_empty(), "{:?}", res.errors);
+        }
+
+        let outbox = Mutex::new(Vec::new());
+        let send = async |n: &services::email::Newsletter<'_>| {
+            outbox.lock().unwrap().push((n.to.to_string(), n.subject.to_string(), n.text.to_string(), n.unsubscribe_url.to_string()));
+            Ok(())
+        };
+        let site = SiteConfig::default();
+        crate::utilities::newsletter::send_due(&db, &test_storage_driver(), &site, &newsletter_config(), send)
+            .await
+            .unwrap();
+        let outbox = outbox.into_inner().unwrap();
+        let sent: Vec<_> = outbox.iter().filter(|(to, ..)| *to == reader).collect();
+        assert_eq!(sent.len(), 1);
+        let (_, subject, text, one_click) = sent[0];
+        assert_eq!(subject, "News & views");
+        assert!(text.contains("Hello readers"), "{text}");
+        assert!(one_click.starts_with(&format!("{}/newsletter/unsubscribe?subscriber={}", site.api_url, subscriber.id)));
+
+        let deliveries = format!(r#"{{ newsletterDeliveries(postId: "{}") {{ nodes {{ email status attempts }} }} }}"#, post.id);
+        let res = admin.execute(Request::new(&deliveries).data(Token::new(create_access_token(&user)))).await;
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+        let data = res.data.into_json().unwrap();
+        assert_eq!(data["newsletterDeliveries"]["nodes"][0]["email"], reader);
+        assert_eq!(data["newsletterDeliveries"]["nodes"][0]["status"], "SENT");
+        assert_eq!(data["newsletterDeliveries"]["nodes"][0]["attempts"], 1);
+
+        let token = one_click.split("token=").nth(1).unwrap();
+        let unsubscribe = |token: &str| {
+            public(format!(r#"mutation {{ unsubscribe(subscriberId: "{}", token: "{token}") }}"#, subscriber.id))
+        };
+        let res = schema.execute(unsubscribe("forged")).await;
+        assert_eq!(res.errors[0].message, "Invalid unsubscribe link");
+        let res = schema.execute(unsubscribe(token)).await;
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+        let stored = SubscriberRepository::find_by_id(&db, subscriber.id).await.unwrap().unwrap();
+        assert_eq!(stored.status, SubscriberStatus::Unsubscribed);
+
+        cleanup_test_user_by_email(&db, &email).await;
+    }
+}
@@ -0,0 +1,84 @@
+use super::queries::{ClientIp, PublicApiKey};
+use super::rate_limiter::SlidingBudget;
+use async_graphql::{Context, InputObject, SimpleObject};
+use chrono::{DateTime, Utc};
+use repositories::PowRepository;
+use sea_orm::DatabaseConnection;
+use services::config::PowConfig;
+use services::pow;
+use std::sync::Arc;
+
+/// Issues and redeems proof-of-work challenges for public mutations.
+pub struct PowGuard {
+    config: PowConfig,
+    limiter: Arc<SlidingBudget>,
+}
+
+/// A puzzle to solve before calling a public mutation: find a `solution`
+/// for which `sha256("{challenge}:{solution}")` starts with `difficulty`
+/// zero bits.
+#[derive(SimpleObject)]
+pub struct PowChallenge {
+    pub challenge: String,
+    pub difficulty: u32,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A solved `powChallenge`.
+#[derive(InputObject)]
+pub struct ProofOfWork {
+    pub challenge: String,
+    pub solution: String,
+}
+
+impl PowGuard {
+    pub fn new(config: PowConfig, limiter: Arc<SlidingBudget>) -> Self {
+        Self { config, limiter }
+    }
+
+    /// Difficulty for the caller: the more of its complexity budget the API
+    /// key or address has used lately, the harder the puzzle.
+    fn difficulty(&self, ctx: &Context<'_>) -> u32 {
+        let key = ctx.data_opt::<PublicApiKey>().map_or(0.0, |k| self.limiter.pressure(&k.0));
+        let ip = ctx.data_opt::<ClientIp>().map_or(0.0, |ip| self.limiter.pressure(&ip.budget_key()));
+        pow::difficulty_for(&self.config, key.max(ip))
+    }
+
+    pub fn issue(&self, ctx: &Context<'_>, api_key: &str) -> PowChallenge {
+        let challenge = pow::issue(&self.config, api_key, self.difficulty(ctx), Utc::now().timestamp());
+        PowChallenge {
+            challenge: challenge.token,
+            difficulty: challenge.difficulty,
+            expires_at: DateTime::from_timestamp(challenge.expires_at, 0).unwrap_or_default(),
+        }
+    }
+
+    /// Check `proof` was issued to `api_key`, is solved and hasn't been used.
+    /// Spent challenges are kept in the database, so a challenge is only good
+    /// once whichever instance sees it.
+    pub async fn redeem(
+        &self,
+        db: &DatabaseConnection,
+        api_key: &str,
+        proof: &ProofOfWork,
+    ) -> Result<(), async_graphql::Error> {
+        let now = Utc::now().timestamp();
+        let (nonce, expires_at) = pow::verify(&self.config, api_key, &proof.challenge, &proof.solution, now)
+            .map_err(|e| async_graphql::Error::new(e.to_string()))?;
+        let expires_at = DateTime::from_timestamp(expires_at, 0).unwrap_or_default().naive_utc();
+        if !PowRepository::spend(db, &nonce, expires_at).await.map_err(async_graphql::Error::new)? {
+            return Err(async_graphql::Error::new("Challenge already used"));
+        }
+        Ok(())
+    }
+}
+
+/// Refuse the request unless it carries a fresh solved challenge for its
+/// API key.
+pub(super) async fn require_proof(ctx: &Context<'_>, proof: &ProofOfWork) -> Result<(), async_graphql::Error> {
+    let api_key = ctx
+        .data::<PublicApiKey>()
+        .map_err(|_| async_graphql::Error::new("Missing API key"))?;
+    let db = ctx.data::<DatabaseConnection>()?;
+    ctx.data::<PowGuard>().unwrap().redeem(db, &api_key.0, proof).await
+}
@@ -1,3 +1,4 @@
+use super::pow::{PowChallenge, PowGuard};
 use super::types::{PublicAuthor, PublicPost};
 use crate::types::sort::SortDirection;
 use async_graphql::connection::{Connection, Edge, EmptyFields};
@@ -39,7 +40,18 @@ struct PostConnectionExtra {
 
 pub struct PublicApiKey(pub String);
 
-async fn require_user(ctx: &Context<'_>) -> Result<Uuid, async_graphql::Error> {
+/// Address the request came from.
+pub struct ClientIp(pub String);
+
+impl ClientIp {
+    /// Key for the address's usage in the complexity limiter, apart from API
+    /// keys.
+    pub fn budget_key(&self) -> String {
+        format!("ip:{}", self.0)
+    }
+}
+
+pub(super) async fn require_user(ctx: &Context<'_>) -> Result<Uuid, async_graphql::Error> {
     let api_key_str = ctx
         .data::<PublicApiKey>()
         .map_err(|_| async_graphql::Error::new("Missing API key"))?;
@@ -58,6 +70,10 @@ fn model_to_public_post(p: &models::posts::Model) -> PublicPost {
         slug: p.slug.clone(),
         cover_image: p.cover_image.clone(),
         markdown_content: p.markdown_content.clone().unwrap_or_default(),
+        render_math: p.render_math,
+        rendered_markdown: p.rendered_markdown.clone(),
+        seo: p.seo.clone(),
+        preview_image: p.preview_image.clone(),
         first_published_at: p.first_published_at,
         created_at: p.created_at,
         updated_at: p.updated_at,
@@ -164,4 +180,13 @@ impl PublicQueryRoot {
             bio: user.bio,
         })
     }
+
+    /// A proof-of-work challenge for the next public mutation. It is bound
+    /// to the API key, good for one mutation, and gets harder the more of
+    /// its complexity budget the key or address has used.
+    async fn pow_challenge(&self, ctx: &Context<'_>) -> Result<PowChallenge> {
+        require_user(ctx).await?;
+        let api_key = ctx.data::<PublicApiKey>().unwrap();
+        Ok(ctx.data::<PowGuard>().unwrap().issue(ctx, &api_key.0))
+    }
 }
@@ -1,38 +1,91 @@
 use std::collections::VecDeque;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
 use async_graphql::extensions::{Extension, ExtensionContext, ExtensionFactory, NextValidation};
 use async_graphql::{ServerError, ValidationResult};
 use dashmap::DashMap;
 
-use crate::public::PublicApiKey;
+use crate::public::{ClientIp, PublicApiKey};
 
 pub struct SlidingBudget {
     windows: DashMap<String, VecDeque<(Instant, usize)>>,
     window: Duration,
     max_budget: usize,
+    /// When keys with nothing left in their window were last dropped
+    last_sweep: Mutex<Instant>,
 }
 
 impl SlidingBudget {
     pub fn new(window_secs: u64, max_budget: usize) -> Self {
-        Self { windows: DashMap::new(), window: Duration::from_secs(window_secs), max_budget }
+        Self {
+            windows: DashMap::new(),
+            window: Duration::from_secs(window_secs),
+            max_budget,
+            last_sweep: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Drop the keys whose window has emptied, at most once per window, so
+    /// one-off callers don't hold memory forever.
+    fn sweep(&self, now: Instant) {
+        {
+            let mut last = self.last_sweep.lock().unwrap_or_else(|e| e.into_inner());
+            if now.duration_since(*last) < self.window {
+                return;
+            }
+            *last = now;
+        }
+        let cutoff = now - self.window;
+        self.windows.retain(|_, entry| {
+            entry.retain(|(t, _)| *t > cutoff);
+            !entry.is_empty()
+        });
     }
 
     /// Returns false if budget exceeded (does NOT deduct in that case)
     pub fn check_and_deduct(&self, key: &str, cost: usize) -> bool {
         let now = Instant::now();
+        self.sweep(now);
         let cutoff = now - self.window;
         let mut entry = self.windows.entry(key.to_string()).or_default();
         entry.retain(|(t, _)| *t > cutoff);
         let used: usize = entry.iter().map(|(_, c)| c).sum();
         if used + cost <= self.max_budget {
-            entry.push_back((now, cost));
+            // Free requests take nothing, so they aren't kept either
+            if cost > 0 {
+                entry.push_back((now, cost));
+            }
             true
         } else {
             false
         }
     }
+
+    /// Count `cost` against `key` without enforcing its budget. Once the
+    /// budget is used up nothing more is kept, since pressure can't rise
+    /// past full; each key holds at most `max_budget` entries.
+    pub fn record(&self, key: &str, cost: usize) {
+        let now = Instant::now();
+        self.sweep(now);
+        let cutoff = now - self.window;
+        let mut entry = self.windows.entry(key.to_string()).or_default();
+        entry.retain(|(t, _)| *t > cutoff);
+        let used: usize = entry.iter().map(|(_, c)| c).sum();
+        if cost > 0 && used < self.max_budget {
+            entry.push_back((now, cost));
+        }
+    }
+
+    /// Share of `key`'s budget used in the current window, from 0 to 1.
+    pub fn pressure(&self, key: &str) -> f64 {
+        let cutoff = Instant::now() - self.window;
+        let used: usize = self
+            .windows
+            .get(key)
+            .map_or(0, |entry| entry.iter().filter(|(t, _)| *t > cutoff).map(|(_, c)| c).sum());
+        (used as f64 / self.max_budget as f64).min(1.0)
+    }
 }
 
 pub struct BudgetLimiterFactory(pub Arc<SlidingBudget>);
@@ -53,6 +106,10 @@ impl Extension for BudgetLimiterExt {
         next: NextValidation<'_>,
     ) -> Result<ValidationResult, Vec<ServerError>> {
         let result = next.run(ctx).await?;
+        // Callers are tracked by address too, for proof-of-work difficulty.
+        if let Ok(ip) = ctx.data::<ClientIp>() {
+            self.0.record(&ip.budget_key(), result.complexity);
+        }
         if let Ok(key) = ctx.data::<PublicApiKey>() {
             if !self.0.check_and_deduct(&key.0, result.complexity) {
                 return Err(vec![ServerError::new(
@@ -64,3 +121,35 @@ impl Extension for BudgetLimiterExt {
         Ok(result)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idle_keys_are_dropped() {
+        let budget = SlidingBudget::new(60, 10);
+        budget.record("ip:192.0.2.1", 3);
+        assert!(budget.check_and_deduct("key", 3));
+        assert_eq!(budget.windows.len(), 2);
+        assert!((budget.pressure("ip:192.0.2.1") - 0.3).abs() < 1e-9);
+
+        // A window later both have emptied, and the next write clears them out.
+        let later = Instant::now() + Duration::from_secs(61);
+        budget.sweep(later);
+        assert!(budget.windows.is_empty());
+    }
+
+    #[test]
+    fn windows_stay_bounded() {
+        let budget = SlidingBudget::new(60, 10);
+        for _ in 0..1000 {
+            budget.record("ip:192.0.2.1", 1);
+            budget.check_and_deduct("key", 0);
+            budget.check_and_deduct("key", 1);
+        }
+        assert_eq!(budget.windows.get("ip:192.0.2.1").unwrap().len(), 10);
+        assert_eq!(budget.pressure("ip:192.0.2.1"), 1.0);
+        assert_eq!(budget.windows.get("key").unwrap().len(), 10);
+    }
+}
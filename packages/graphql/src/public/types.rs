@@ -1,8 +1,19 @@
-use crate::utilities::markdown::{render_markdown_cached, MarkdownCache};
+use crate::types::seo::{build_seo, Seo, SeoOverrides, SeoSource};
+use crate::types::webmention::WebmentionKind;
+use crate::utilities::assets::{resolve_image, ResponsiveImage};
+use crate::utilities::comments::{comments_open, render_comment};
+use crate::utilities::headings::TocEntry;
+use crate::utilities::markdown::{render_post, ContentFormat, PostSource};
+use crate::utilities::text::{description_or_excerpt, PlainText, DEFAULT_EXCERPT_LENGTH};
 use async_graphql::{Context, Object, Result, SimpleObject};
 use chrono::NaiveDateTime;
-use repositories::{PostRepository, UserRepository};
+use repositories::{CommentRepository, PostRepository, UserRepository, WebmentionRepository};
 use sea_orm::DatabaseConnection;
+use sea_orm::entity::prelude::Json;
+use services::assets::{StorageDriver, PREVIEW_HEIGHT, PREVIEW_WIDTH};
+use services::config::SiteConfig;
+use std::collections::HashMap;
+use std::sync::Arc;
 use uuid::Uuid;
 
 pub struct PublicPost {
@@ -13,28 +24,103 @@ pub struct PublicPost {
     pub slug: Option<String>,
     pub cover_image: Option<String>,
     pub markdown_content: String,
+    pub render_math: bool,
+    /// Render persisted in `posts.rendered_markdown`, if any
+    pub rendered_markdown: Option<Json>,
+    /// Overrides stored in `posts.seo`
+    pub seo: Option<Json>,
+    /// Storage key of the generated share card, if one was made
+    pub preview_image: Option<String>,
     pub first_published_at: Option<NaiveDateTime>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
 }
 
+impl PublicPost {
+    fn source(&self) -> PostSource<'_> {
+        PostSource {
+            id: self.id,
+            user_id: self.user_id,
+            markdown: &self.markdown_content,
+            render_math: self.render_math,
+            rendered_markdown: self.rendered_markdown.as_ref(),
+            format: ContentFormat::Html,
+        }
+    }
+}
+
 #[Object]
 impl PublicPost {
     async fn id(&self) -> Uuid { self.id }
     async fn title(&self) -> &str { &self.title }
-    async fn description(&self) -> Option<&str> { self.description.as_deref() }
+
+    /// The author's description, or else an excerpt of the content
+    #[graphql(complexity = 2)]
+    async fn description(&self) -> Option<String> {
+        description_or_excerpt(self.description.as_deref(), &self.markdown_content)
+    }
+
     async fn slug(&self) -> Option<&str> { self.slug.as_deref() }
-    async fn cover_image(&self) -> Option<&str> { self.cover_image.as_deref() }
+
+    /// Cover image URL; `asset:` references resolve to the large variant
+    #[graphql(complexity = 2)]
+    async fn cover_image(&self, ctx: &Context<'_>) -> Option<String> {
+        resolve_image(ctx, self.user_id, self.cover_image.as_deref()?).await.map(|image| image.src)
+    }
+
+    /// The cover image with a `srcset` for uploaded assets
+    #[graphql(complexity = 2)]
+    async fn responsive_cover_image(&self, ctx: &Context<'_>) -> Option<ResponsiveImage> {
+        resolve_image(ctx, self.user_id, self.cover_image.as_deref()?).await
+    }
+
+    /// URL of the generated 1200×630 share card, drawn from the title,
+    /// author and blog name over the cover
+    async fn preview_image(&self, ctx: &Context<'_>) -> Option<String> {
+        let driver = ctx.data_opt::<Arc<StorageDriver>>()?;
+        self.preview_image.as_deref().map(|key| driver.url(key))
+    }
+
     async fn first_published_at(&self) -> Option<NaiveDateTime> { self.first_published_at }
     async fn created_at(&self) -> NaiveDateTime { self.created_at }
     async fn updated_at(&self) -> NaiveDateTime { self.updated_at }
 
-    /// Rendered HTML content
+    /// The content rendered to `format`: HTML by default, or plain text,
+    /// AMP-safe HTML or the markdown source
+    #[graphql(complexity = 5)]
+    async fn content(&self, ctx: &Context<'_>, #[graphql(default)] format: ContentFormat) -> String {
+        if format == ContentFormat::Markdown {
+            return self.markdown_content.clone();
+        }
+        render_post(ctx, PostSource { format, ..self.source() }).await.html
+    }
+
+    /// Headings of the rendered content, nested by level
     #[graphql(complexity = 5)]
-    async fn content(&self, ctx: &Context<'_>) -> String {
-        let default_cache = MarkdownCache::default();
-        let cache = ctx.data::<MarkdownCache>().unwrap_or(&default_cache);
-        render_markdown_cached(self.id, &self.markdown_content, cache)
+    async fn table_of_contents(&self, ctx: &Context<'_>) -> Vec<TocEntry> {
+        render_post(ctx, self.source()).await.toc
+    }
+
+    /// Words in the content, code blocks included
+    #[graphql(complexity = 2)]
+    async fn word_count(&self) -> i32 {
+        PlainText::new(&self.markdown_content).words as i32
+    }
+
+    /// Minutes to read the content at 200 words a minute, rounded up
+    #[graphql(complexity = 2)]
+    async fn reading_time_minutes(&self) -> i32 {
+        PlainText::new(&self.markdown_content).reading_time_minutes() as i32
+    }
+
+    /// Plain-text summary: everything before a `<!--more-->` marker, or else
+    /// the first `length` characters, cut at a word
+    #[graphql(complexity = 2)]
+    async fn excerpt(
+        &self,
+        #[graphql(default_with = "DEFAULT_EXCERPT_LENGTH as i32", validator(minimum = 1, maximum = 2000))] length: i32,
+    ) -> String {
+        PlainText::new(&self.markdown_content).excerpt(length as usize)
     }
 
     #[graphql(complexity = 2)]
@@ -67,6 +153,93 @@ impl PublicPost {
         }))
     }
 
+    /// Canonical URL, Open Graph and Twitter tags and JSON-LD for the page
+    #[graphql(complexity = 5)]
+    async fn seo(&self, ctx: &Context<'_>) -> Result<Seo> {
+        let db = ctx.data::<DatabaseConnection>().unwrap();
+        let default_site = SiteConfig::default();
+        let site = ctx.data_opt::<SiteConfig>().unwrap_or(&default_site);
+        let author = UserRepository::find_by_id(db, self.user_id)
+            .await
+            .map_err(|e| async_graphql::Error::new(e.to_string()))?
+            .and_then(|user| user.display_name);
+        let overrides = SeoOverrides::from_json(self.seo.as_ref());
+        // An explicit share image, then the generated card, then the cover
+        let resolved = |image: ResponsiveImage| (image.src, image.width.zip(image.height));
+        let driver = ctx.data_opt::<Arc<StorageDriver>>();
+        let image = if let Some(url) = overrides.image.as_deref() {
+            resolve_image(ctx, self.user_id, url).await.map(resolved)
+        } else if let (Some(driver), Some(key)) = (driver, self.preview_image.as_deref()) {
+            Some((driver.url(key), Some((PREVIEW_WIDTH as i32, PREVIEW_HEIGHT as i32))))
+        } else if let Some(url) = self.cover_image.as_deref() {
+            resolve_image(ctx, self.user_id, url).await.map(resolved)
+        } else {
+            None
+        };
+        let plain = PlainText::new(&self.markdown_content);
+        Ok(build_seo(
+            site,
+            SeoSource {
+                id: self.id,
+                slug: self.slug.as_deref(),
+                title: &self.title,
+                description: description_or_excerpt(self.description.as_deref(), &self.markdown_content),
+                image,
+                author,
+                published_at: self.first_published_at,
+                updated_at: self.updated_at,
+                word_count: plain.words,
+                overrides: &overrides,
+            },
+        ))
+    }
+
+    /// Approved comments, oldest first, with replies nested under the
+    /// comment they answer
+    #[graphql(complexity = 10)]
+    async fn comments(&self, ctx: &Context<'_>) -> Result<Vec<PublicComment>> {
+        let db = ctx.data::<DatabaseConnection>().unwrap();
+        let comments = CommentRepository::approved_for_post(db, self.id)
+            .await
+            .map_err(async_graphql::Error::new)?;
+        Ok(thread(comments))
+    }
+
+    /// Approved Webmentions from pages elsewhere, oldest first
+    #[graphql(complexity = 10)]
+    async fn mentions(&self, ctx: &Context<'_>) -> Result<Vec<PublicMention>> {
+        let db = ctx.data::<DatabaseConnection>().unwrap();
+        let mentions = WebmentionRepository::approved_for_post(db, self.id)
+            .await
+            .map_err(async_graphql::Error::new)?;
+        Ok(mentions.into_iter().map(PublicMention::from).collect())
+    }
+
+    #[graphql(complexity = 2)]
+    async fn comment_count(&self, ctx: &Context<'_>) -> Result<i32> {
+        let db = ctx.data::<DatabaseConnection>().unwrap();
+        let count = CommentRepository::count_approved_for_post(db, self.id)
+            .await
+            .map_err(async_graphql::Error::new)?;
+        Ok(count as i32)
+    }
+
+    /// Whether `submitComment` takes comments on this post
+    #[graphql(complexity = 2)]
+    async fn comments_open(&self, ctx: &Context<'_>) -> Result<bool> {
+        let db = ctx.data::<DatabaseConnection>().unwrap();
+        let user = UserRepository::find_by_id(db, self.user_id)
+            .await
+            .map_err(|e| async_graphql::Error::new(e.to_string()))?
+            .ok_or_else(|| async_graphql::Error::new("Author not found"))?;
+        Ok(comments_open(
+            user.comment_policy,
+            user.comments_close_after_days,
+            self.first_published_at,
+            chrono::Utc::now().naive_utc(),
+        ))
+    }
+
     #[graphql(complexity = 3)]
     async fn author(&self, ctx: &Context<'_>) -> Result<PublicAuthor> {
         let db = ctx.data::<DatabaseConnection>().unwrap();
@@ -92,3 +265,74 @@ pub struct PublicAuthor {
     pub display_name: Option<String>,
     pub bio: Option<String>,
 }
+
+#[derive(SimpleObject)]
+pub struct PublicComment {
+    pub id: Uuid,
+    pub parent_id: Option<Uuid>,
+    pub author_name: String,
+    pub author_url: Option<String>,
+    /// The body rendered and sanitized
+    pub html: String,
+    pub created_at: NaiveDateTime,
+    pub replies: Vec<PublicComment>,
+}
+
+/// A page elsewhere that links to a post, as its h-entry describes itself.
+#[derive(SimpleObject)]
+pub struct PublicMention {
+    pub id: Uuid,
+    pub kind: WebmentionKind,
+    /// The page that links to the post
+    pub url: String,
+    pub author_name: Option<String>,
+    pub author_url: Option<String>,
+    pub author_photo: Option<String>,
+    pub title: Option<String>,
+    /// A plain-text excerpt of the page
+    pub content: Option<String>,
+    pub published_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<models::webmentions::Model> for PublicMention {
+    fn from(m: models::webmentions::Model) -> Self {
+        PublicMention {
+            id: m.id,
+            kind: m.kind.into(),
+            url: m.source,
+            author_name: m.author_name,
+            author_url: m.author_url,
+            author_photo: m.author_photo,
+            title: m.title,
+            content: m.content,
+            published_at: m.published_at,
+            created_at: m.created_at,
+        }
+    }
+}
+
+/// Nest approved comments under their parents, keeping each level in the
+/// order given. Replies to comments that aren't shown are left out with them.
+fn thread(comments: Vec<models::comments::Model>) -> Vec<PublicComment> {
+    let mut children: HashMap<Option<Uuid>, Vec<models::comments::Model>> = HashMap::new();
+    for comment in comments {
+        children.entry(comment.parent_id).or_default().push(comment);
+    }
+    fn build(parent: Option<Uuid>, children: &mut HashMap<Option<Uuid>, Vec<models::comments::Model>>) -> Vec<PublicComment> {
+        let level = children.remove(&parent).unwrap_or_default();
+        level
+            .into_iter()
+            .map(|c| PublicComment {
+                id: c.id,
+                parent_id: c.parent_id,
+                html: render_comment(&c.body),
+                author_name: c.author_name,
+                author_url: c.author_url,
+                created_at: c.created_at,
+                replies: build(Some(c.id), children),
+            })
+            .collect()
+    }
+    build(None, &mut children)
+}
@@ -1,11 +1,53 @@
-use async_graphql::SimpleObject;
+use async_graphql::{Enum, SimpleObject};
 use chrono::NaiveDateTime;
+use services::api_keys::Scope;
 use uuid::Uuid;
 
+/// What a key may do beyond reading the public API, as Micropub clients
+/// name it.
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ApiKeyScope {
+    /// Create posts
+    #[graphql(name = "CREATE")]
+    Create,
+    /// Change posts
+    #[graphql(name = "UPDATE")]
+    Update,
+    /// Delete posts
+    #[graphql(name = "DELETE")]
+    Delete,
+    /// Upload images
+    #[graphql(name = "MEDIA")]
+    Media,
+}
+
+impl From<ApiKeyScope> for Scope {
+    fn from(v: ApiKeyScope) -> Self {
+        match v {
+            ApiKeyScope::Create => Self::Create,
+            ApiKeyScope::Update => Self::Update,
+            ApiKeyScope::Delete => Self::Delete,
+            ApiKeyScope::Media => Self::Media,
+        }
+    }
+}
+
+impl From<Scope> for ApiKeyScope {
+    fn from(v: Scope) -> Self {
+        match v {
+            Scope::Create => Self::Create,
+            Scope::Update => Self::Update,
+            Scope::Delete => Self::Delete,
+            Scope::Media => Self::Media,
+        }
+    }
+}
+
 #[derive(SimpleObject)]
 pub struct ApiKeyInfo {
     pub id: Uuid,
     pub label: String,
+    pub scopes: Vec<ApiKeyScope>,
     pub last_used_at: Option<NaiveDateTime>,
     pub created_at: NaiveDateTime,
 }
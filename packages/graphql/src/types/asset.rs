@@ -9,6 +9,8 @@ pub struct Asset {
     pub original_filename: String,
     pub mime_type: String,
     pub size_bytes: i64,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
     pub created_at: NaiveDateTime,
 }
 
@@ -19,6 +21,8 @@ impl From<models::assets::Model> for Asset {
             original_filename: m.original_filename,
             mime_type: m.mime_type,
             size_bytes: m.size_bytes,
+            width: m.width,
+            height: m.height,
             created_at: m.created_at,
         }
     }
@@ -44,6 +48,9 @@ impl Asset {
     async fn original_filename(&self) -> &str { &self.original_filename }
     async fn mime_type(&self) -> &str { &self.mime_type }
     async fn size_bytes(&self) -> i64 { self.size_bytes }
+    /// Pixel size of the original; unknown for uploads that predate it
+    async fn width(&self) -> Option<i32> { self.width }
+    async fn height(&self) -> Option<i32> { self.height }
     async fn created_at(&self) -> NaiveDateTime { self.created_at }
 
     async fn urls(&self, ctx: &async_graphql::Context<'_>) -> AssetUrls {
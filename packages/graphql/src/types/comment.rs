@@ -0,0 +1,147 @@
+use crate::utilities::comments::render_comment;
+use async_graphql::{Enum, Object, SimpleObject};
+use chrono::NaiveDateTime;
+use models::sea_orm_active_enums as enums;
+use uuid::Uuid;
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum CommentStatus {
+    #[graphql(name = "PENDING")]
+    Pending,
+    #[graphql(name = "APPROVED")]
+    Approved,
+    #[graphql(name = "REJECTED")]
+    Rejected,
+    #[graphql(name = "SPAM")]
+    Spam,
+}
+
+impl From<CommentStatus> for enums::CommentStatus {
+    fn from(v: CommentStatus) -> Self {
+        match v {
+            CommentStatus::Pending => Self::Pending,
+            CommentStatus::Approved => Self::Approved,
+            CommentStatus::Rejected => Self::Rejected,
+            CommentStatus::Spam => Self::Spam,
+        }
+    }
+}
+
+impl From<enums::CommentStatus> for CommentStatus {
+    fn from(v: enums::CommentStatus) -> Self {
+        match v {
+            enums::CommentStatus::Pending => Self::Pending,
+            enums::CommentStatus::Approved => Self::Approved,
+            enums::CommentStatus::Rejected => Self::Rejected,
+            enums::CommentStatus::Spam => Self::Spam,
+        }
+    }
+}
+
+/// Who may comment on a blog's posts.
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum CommentPolicy {
+    /// Comments appear as soon as they're submitted
+    #[graphql(name = "OPEN")]
+    Open,
+    /// Comments wait in the moderation queue until approved
+    #[graphql(name = "MODERATED")]
+    Moderated,
+    /// No new comments are taken
+    #[graphql(name = "CLOSED")]
+    Closed,
+}
+
+impl From<CommentPolicy> for enums::CommentPolicy {
+    fn from(v: CommentPolicy) -> Self {
+        match v {
+            CommentPolicy::Open => Self::Open,
+            CommentPolicy::Moderated => Self::Moderated,
+            CommentPolicy::Closed => Self::Closed,
+        }
+    }
+}
+
+impl From<enums::CommentPolicy> for CommentPolicy {
+    fn from(v: enums::CommentPolicy) -> Self {
+        match v {
+            enums::CommentPolicy::Open => Self::Open,
+            enums::CommentPolicy::Moderated => Self::Moderated,
+            enums::CommentPolicy::Closed => Self::Closed,
+        }
+    }
+}
+
+/// A comment as its blog's owner sees it, contact details included.
+pub struct Comment {
+    pub id: Uuid,
+    pub post_id: Uuid,
+    pub parent_id: Option<Uuid>,
+    pub author_name: String,
+    pub author_email: Option<String>,
+    pub author_url: Option<String>,
+    pub body: String,
+    pub status: CommentStatus,
+    pub spam_score: Option<f32>,
+    pub created_at: NaiveDateTime,
+    pub moderated_at: Option<NaiveDateTime>,
+}
+
+impl From<models::comments::Model> for Comment {
+    fn from(m: models::comments::Model) -> Self {
+        Comment {
+            id: m.id,
+            post_id: m.post_id,
+            parent_id: m.parent_id,
+            author_name: m.author_name,
+            author_email: m.author_email,
+            author_url: m.author_url,
+            body: m.body,
+            status: m.status.into(),
+            spam_score: m.spam_score,
+            created_at: m.created_at,
+            moderated_at: m.moderated_at,
+        }
+    }
+}
+
+#[Object]
+impl Comment {
+    async fn id(&self) -> Uuid { self.id }
+    async fn post_id(&self) -> Uuid { self.post_id }
+    /// The comment this one replies to
+    async fn parent_id(&self) -> Option<Uuid> { self.parent_id }
+    async fn author_name(&self) -> &str { &self.author_name }
+    async fn author_email(&self) -> Option<&str> { self.author_email.as_deref() }
+    async fn author_url(&self) -> Option<&str> { self.author_url.as_deref() }
+    /// The body as submitted
+    async fn body(&self) -> &str { &self.body }
+    /// The body rendered and sanitized, as readers see it
+    async fn html(&self) -> String { render_comment(&self.body) }
+    async fn status(&self) -> CommentStatus { self.status }
+    /// The spam filter's verdict on submission, from 0 (ham) to 1 (spam)
+    async fn spam_score(&self) -> Option<f64> { self.spam_score.map(f64::from) }
+    async fn created_at(&self) -> NaiveDateTime { self.created_at }
+    async fn moderated_at(&self) -> Option<NaiveDateTime> { self.moderated_at }
+}
+
+#[derive(SimpleObject)]
+pub struct CommentSettings {
+    pub policy: CommentPolicy,
+    /// Days after a post is first published that it stops taking comments
+    pub close_after_days: Option<i32>,
+}
+
+impl From<&models::users::Model> for CommentSettings {
+    fn from(user: &models::users::Model) -> Self {
+        CommentSettings {
+            policy: user.comment_policy.into(),
+            close_after_days: user.comments_close_after_days,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct DeletedComment {
+    pub id: Uuid,
+}
@@ -0,0 +1,81 @@
+use crate::types::newsletter::DeliveryStatus;
+use async_graphql::SimpleObject;
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+/// Your blog's account on the fediverse, which people follow to see your
+/// posts in Mastodon and the like.
+#[derive(SimpleObject)]
+pub struct FediverseAccount {
+    pub username: String,
+    /// `@username@host`, as people search for it
+    pub handle: String,
+    /// The ActivityPub actor's id
+    pub actor_url: String,
+    pub follower_count: u64,
+    pub created_at: NaiveDateTime,
+}
+
+/// A fediverse account following your blog.
+#[derive(SimpleObject)]
+pub struct FediverseFollower {
+    pub id: Uuid,
+    /// The follower's ActivityPub actor id
+    pub actor: String,
+    pub inbox: String,
+    /// The inbox its server shares between accounts, used instead of
+    /// `inbox` when given
+    pub shared_inbox: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<models::activitypub_followers::Model> for FediverseFollower {
+    fn from(m: models::activitypub_followers::Model) -> Self {
+        FediverseFollower {
+            id: m.id,
+            actor: m.actor,
+            inbox: m.inbox,
+            shared_inbox: m.shared_inbox,
+            created_at: m.created_at,
+        }
+    }
+}
+
+/// An activity sent, or to be sent, to a follower's inbox.
+#[derive(SimpleObject)]
+pub struct FediverseDelivery {
+    pub id: Uuid,
+    pub inbox: String,
+    /// The activity's type: `Create`, `Update`, `Delete` or `Accept`
+    pub activity_type: String,
+    /// The activity as sent, as JSON
+    pub activity: String,
+    pub status: DeliveryStatus,
+    /// Times delivery has been tried
+    pub attempts: i32,
+    /// When a queued delivery is next tried
+    pub next_attempt_at: NaiveDateTime,
+    /// HTTP status of the latest attempt's response
+    pub response_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub delivered_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<models::activitypub_deliveries::Model> for FediverseDelivery {
+    fn from(m: models::activitypub_deliveries::Model) -> Self {
+        FediverseDelivery {
+            id: m.id,
+            inbox: m.inbox,
+            activity_type: m.activity["type"].as_str().unwrap_or_default().to_string(),
+            activity: m.activity.to_string(),
+            status: m.status.into(),
+            attempts: m.attempts,
+            next_attempt_at: m.next_attempt_at,
+            response_status: m.response_status,
+            last_error: m.last_error,
+            delivered_at: m.delivered_at,
+            created_at: m.created_at,
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use crate::types::newsletter::DeliveryStatus;
+use async_graphql::{Enum, SimpleObject};
+use chrono::NaiveDateTime;
+use models::sea_orm_active_enums as enums;
+use uuid::Uuid;
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum PingKind {
+    /// A WebSub hub, sent `hub.mode=publish`
+    #[graphql(name = "WEBSUB")]
+    Websub,
+    /// An XML-RPC ping service, sent `weblogUpdates.extendedPing`
+    #[graphql(name = "XMLRPC")]
+    Xmlrpc,
+}
+
+impl From<enums::PingKind> for PingKind {
+    fn from(v: enums::PingKind) -> Self {
+        match v {
+            enums::PingKind::Websub => Self::Websub,
+            enums::PingKind::Xmlrpc => Self::Xmlrpc,
+        }
+    }
+}
+
+/// A hub or ping service told, or to be told, that one of your feeds has a
+/// new post.
+#[derive(SimpleObject)]
+pub struct FeedPing {
+    pub id: Uuid,
+    /// The post whose publishing made the ping, unless it's been deleted
+    pub post_id: Option<Uuid>,
+    pub kind: PingKind,
+    pub endpoint: String,
+    pub feed_url: String,
+    pub status: DeliveryStatus,
+    /// Times sending has been tried
+    pub attempts: i32,
+    /// When a queued ping is next tried
+    pub next_attempt_at: NaiveDateTime,
+    /// HTTP status of the latest attempt's response
+    pub response_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub sent_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<models::feed_pings::Model> for FeedPing {
+    fn from(m: models::feed_pings::Model) -> Self {
+        FeedPing {
+            id: m.id,
+            post_id: m.post_id,
+            kind: m.kind.into(),
+            endpoint: m.endpoint,
+            feed_url: m.feed_url,
+            status: m.status.into(),
+            attempts: m.attempts,
+            next_attempt_at: m.next_attempt_at,
+            response_status: m.response_status,
+            last_error: m.last_error,
+            sent_at: m.sent_at,
+            created_at: m.created_at,
+        }
+    }
+}
@@ -1,6 +1,13 @@
 pub mod api_key;
 pub mod asset;
 pub mod authorized_user;
+pub mod comment;
+pub mod fediverse;
+pub mod feed_ping;
+pub mod newsletter;
 pub mod post;
+pub mod seo;
 pub mod sort;
 pub mod user;
+pub mod webhook;
+pub mod webmention;
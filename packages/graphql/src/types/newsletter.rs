@@ -0,0 +1,127 @@
+use async_graphql::{Enum, SimpleObject};
+use chrono::NaiveDateTime;
+use models::sea_orm_active_enums as enums;
+use uuid::Uuid;
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum SubscriberStatus {
+    /// Waiting for the reader to follow the confirmation link
+    #[graphql(name = "PENDING")]
+    Pending,
+    #[graphql(name = "ACTIVE")]
+    Active,
+    #[graphql(name = "UNSUBSCRIBED")]
+    Unsubscribed,
+}
+
+impl From<SubscriberStatus> for enums::SubscriberStatus {
+    fn from(v: SubscriberStatus) -> Self {
+        match v {
+            SubscriberStatus::Pending => Self::Pending,
+            SubscriberStatus::Active => Self::Active,
+            SubscriberStatus::Unsubscribed => Self::Unsubscribed,
+        }
+    }
+}
+
+impl From<enums::SubscriberStatus> for SubscriberStatus {
+    fn from(v: enums::SubscriberStatus) -> Self {
+        match v {
+            enums::SubscriberStatus::Pending => Self::Pending,
+            enums::SubscriberStatus::Active => Self::Active,
+            enums::SubscriberStatus::Unsubscribed => Self::Unsubscribed,
+        }
+    }
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum DeliveryStatus {
+    /// Waiting to be sent, or to be tried again
+    #[graphql(name = "QUEUED")]
+    Queued,
+    #[graphql(name = "SENT")]
+    Sent,
+    /// Given up on; `lastError` says why
+    #[graphql(name = "FAILED")]
+    Failed,
+}
+
+impl From<DeliveryStatus> for enums::DeliveryStatus {
+    fn from(v: DeliveryStatus) -> Self {
+        match v {
+            DeliveryStatus::Queued => Self::Queued,
+            DeliveryStatus::Sent => Self::Sent,
+            DeliveryStatus::Failed => Self::Failed,
+        }
+    }
+}
+
+impl From<enums::DeliveryStatus> for DeliveryStatus {
+    fn from(v: enums::DeliveryStatus) -> Self {
+        match v {
+            enums::DeliveryStatus::Queued => Self::Queued,
+            enums::DeliveryStatus::Sent => Self::Sent,
+            enums::DeliveryStatus::Failed => Self::Failed,
+        }
+    }
+}
+
+/// A reader who gets new posts by email.
+#[derive(SimpleObject)]
+pub struct Subscriber {
+    pub id: Uuid,
+    pub email: String,
+    pub status: SubscriberStatus,
+    pub created_at: NaiveDateTime,
+    pub confirmed_at: Option<NaiveDateTime>,
+    pub unsubscribed_at: Option<NaiveDateTime>,
+}
+
+impl From<models::subscribers::Model> for Subscriber {
+    fn from(m: models::subscribers::Model) -> Self {
+        Subscriber {
+            id: m.id,
+            email: m.email,
+            status: m.status.into(),
+            created_at: m.created_at,
+            confirmed_at: m.confirmed_at,
+            unsubscribed_at: m.unsubscribed_at,
+        }
+    }
+}
+
+/// One post's email to one subscriber.
+#[derive(SimpleObject)]
+pub struct NewsletterDelivery {
+    pub id: Uuid,
+    pub post_id: Uuid,
+    pub subscriber_id: Uuid,
+    pub email: String,
+    pub status: DeliveryStatus,
+    /// Times sending has been tried
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub sent_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<(models::newsletter_deliveries::Model, models::subscribers::Model)> for NewsletterDelivery {
+    fn from((d, s): (models::newsletter_deliveries::Model, models::subscribers::Model)) -> Self {
+        NewsletterDelivery {
+            id: d.id,
+            post_id: d.post_id,
+            subscriber_id: d.subscriber_id,
+            email: s.email,
+            status: d.status.into(),
+            attempts: d.attempts,
+            last_error: d.last_error,
+            sent_at: d.sent_at,
+            created_at: d.created_at,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct DeletedSubscriber {
+    pub id: Uuid,
+}
@@ -1,21 +1,45 @@
-use crate::utilities::markdown::{render_markdown_cached, MarkdownCache};
+use crate::types::seo::SeoOverrides;
+use crate::utilities::assets::{resolve_image, ResponsiveImage};
+use crate::utilities::headings::TocEntry;
+use crate::utilities::markdown::{render_post, ContentFormat, PostSource};
+use crate::utilities::text::{PlainText, DEFAULT_EXCERPT_LENGTH};
 use async_graphql::{Context, Object, SimpleObject};
 use chrono::NaiveDateTime;
+use sea_orm::entity::prelude::Json;
 use uuid::Uuid;
 
 pub struct Post {
     pub id: Uuid,
+    pub user_id: Uuid,
     pub title: String,
     pub markdown_content: String,
     pub description: Option<String>,
     pub slug: Option<String>,
     pub cover_image: Option<String>,
     pub is_published: bool,
+    pub render_math: bool,
+    /// Render persisted in `posts.rendered_markdown`, if any
+    pub rendered_markdown: Option<Json>,
+    /// Overrides stored in `posts.seo`
+    pub seo: Option<Json>,
     pub first_published_at: Option<NaiveDateTime>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
 }
 
+impl Post {
+    fn source(&self) -> PostSource<'_> {
+        PostSource {
+            id: self.id,
+            user_id: self.user_id,
+            markdown: &self.markdown_content,
+            render_math: self.render_math,
+            rendered_markdown: self.rendered_markdown.as_ref(),
+            format: ContentFormat::Html,
+        }
+    }
+}
+
 #[Object]
 impl Post {
     async fn id(&self) -> Uuid {
@@ -30,6 +54,11 @@ impl Post {
         self.is_published
     }
 
+    /// Whether `$…$` and `$$…$$` are rendered as math
+    async fn render_math(&self) -> bool {
+        self.render_math
+    }
+
     async fn first_published_at(&self) -> Option<NaiveDateTime> {
         self.first_published_at
     }
@@ -42,6 +71,7 @@ impl Post {
         self.updated_at
     }
 
+    /// The description as stored; see `excerpt` for a fallback
     async fn description(&self) -> Option<&String> {
         self.description.as_ref()
     }
@@ -50,10 +80,21 @@ impl Post {
         self.slug.as_ref()
     }
 
+    /// Overrides for the share metadata that `PublicPost.seo` generates
+    async fn seo_overrides(&self) -> SeoOverrides {
+        SeoOverrides::from_json(self.seo.as_ref())
+    }
+
+    /// The cover image as stored, which may be an `asset:{uuid}` reference
     async fn cover_image(&self) -> Option<&String> {
         self.cover_image.as_ref()
     }
 
+    /// The cover image resolved to URLs, with a `srcset` for uploaded assets
+    async fn responsive_cover_image(&self, ctx: &Context<'_>) -> Option<ResponsiveImage> {
+        resolve_image(ctx, self.user_id, self.cover_image.as_deref()?).await
+    }
+
     /// Returns the original markdown content for editing
     async fn markdown_content(&self) -> &String {
         &self.markdown_content
@@ -61,9 +102,31 @@ impl Post {
 
     /// Returns the rendered HTML content for display
     async fn content(&self, ctx: &Context<'_>) -> String {
-        let default_cache = MarkdownCache::default();
-        let cache = ctx.data::<MarkdownCache>().unwrap_or(&default_cache);
-        render_markdown_cached(self.id, &self.markdown_content, cache)
+        render_post(ctx, self.source()).await.html
+    }
+
+    /// Headings of the rendered content, nested by level
+    async fn table_of_contents(&self, ctx: &Context<'_>) -> Vec<TocEntry> {
+        render_post(ctx, self.source()).await.toc
+    }
+
+    /// Words in the content, code blocks included
+    async fn word_count(&self) -> i32 {
+        PlainText::new(&self.markdown_content).words as i32
+    }
+
+    /// Minutes to read the content at 200 words a minute, rounded up
+    async fn reading_time_minutes(&self) -> i32 {
+        PlainText::new(&self.markdown_content).reading_time_minutes() as i32
+    }
+
+    /// Plain-text summary: everything before a `<!--more-->` marker, or else
+    /// the first `length` characters, cut at a word
+    async fn excerpt(
+        &self,
+        #[graphql(default_with = "DEFAULT_EXCERPT_LENGTH as i32", validator(minimum = 1, maximum = 2000))] length: i32,
+    ) -> String {
+        PlainText::new(&self.markdown_content).excerpt(length as usize)
     }
 }
 
@@ -0,0 +1,312 @@
+use crate::utilities::oembed::{discovery_url, OembedFormat};
+use async_graphql::{InputObject, SimpleObject};
+use chrono::{NaiveDateTime, SecondsFormat};
+use sea_orm::entity::prelude::Json;
+use serde::{Deserialize, Serialize};
+use services::config::SiteConfig;
+use url::Url;
+use uuid::Uuid;
+
+/// Per-post replacements for the generated share metadata. Fields left out
+/// fall back to the post's own title, description and cover image.
+#[derive(SimpleObject, InputObject, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[graphql(input_name = "SeoOverridesInput")]
+pub struct SeoOverrides {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Share image URL or `asset:{uuid}` reference
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    /// Where the post was first published, if not on this blog
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canonical_url: Option<String>,
+    /// Ask search engines not to index the post
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub noindex: Option<bool>,
+}
+
+impl SeoOverrides {
+    /// Overrides stored in `posts.seo`; missing or unreadable ones are empty.
+    pub fn from_json(json: Option<&Json>) -> Self {
+        json.and_then(|j| Self::deserialize(j).ok()).unwrap_or_default()
+    }
+
+    /// The value for `posts.seo`: blank fields dropped, `None` if nothing is
+    /// left.
+    pub fn to_json(&self) -> Option<Json> {
+        let blank = |v: &Option<String>| v.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(str::to_string);
+        let trimmed = Self {
+            title: blank(&self.title),
+            description: blank(&self.description),
+            image: blank(&self.image),
+            canonical_url: blank(&self.canonical_url),
+            noindex: self.noindex.filter(|&n| n),
+        };
+        (trimmed != Self::default()).then(|| serde_json::to_value(trimmed).unwrap_or_default())
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let limits = [("title", &self.title, 200), ("description", &self.description, 500)];
+        for (name, value, max) in limits {
+            if value.as_ref().is_some_and(|v| v.len() > max) {
+                return Err(format!("seo.{name} must be {max} characters or fewer"));
+            }
+        }
+        for (name, value) in [("image", &self.image), ("canonical_url", &self.canonical_url)] {
+            let Some(url) = value.as_deref().map(str::trim).filter(|v| !v.is_empty()) else { continue };
+            if url.len() > 2000 {
+                return Err(format!("seo.{name} must be 2000 characters or fewer"));
+            }
+            let parsed = Url::parse(url).map_err(|_| format!("seo.{name} must be a valid URL"))?;
+            let allowed: &[&str] = if name == "image" { &["http", "https", "asset"] } else { &["http", "https"] };
+            if !allowed.contains(&parsed.scheme()) {
+                return Err(format!("seo.{name} must be an http(s) URL"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// One `<meta>` tag. Open Graph tags go in `property`, Twitter tags in `name`.
+#[derive(SimpleObject, Clone, Debug, PartialEq)]
+pub struct MetaTag {
+    pub property: String,
+    pub content: String,
+}
+
+/// One `<link>` tag.
+#[derive(SimpleObject, Clone, Debug, PartialEq)]
+pub struct LinkTag {
+    pub rel: String,
+    /// Media type of what the link points at
+    #[graphql(name = "type")]
+    pub media_type: String,
+    pub href: String,
+    pub title: String,
+}
+
+/// Everything a page needs in its `<head>` to share well.
+#[derive(SimpleObject, Clone, Debug, PartialEq)]
+pub struct Seo {
+    pub canonical_url: String,
+    pub title: String,
+    pub description: Option<String>,
+    /// Absolute URL of the share image
+    pub image: Option<String>,
+    /// Content for `<meta name="robots">`, when the post shouldn't be indexed
+    pub robots: Option<String>,
+    pub open_graph: Vec<MetaTag>,
+    pub twitter: Vec<MetaTag>,
+    /// A schema.org `BlogPosting`, ready for `<script type="application/ld+json">`
+    pub json_ld: String,
+    /// Alternates for `<link>`: the post's oEmbed, as JSON and as XML
+    pub links: Vec<LinkTag>,
+}
+
+/// What [`build_seo`] derives the metadata from, after the post's own
+/// fallbacks have been applied.
+pub struct SeoSource<'a> {
+    pub id: Uuid,
+    pub slug: Option<&'a str>,
+    pub title: &'a str,
+    pub description: Option<String>,
+    /// Share image, resolved but possibly relative to this server
+    pub image: Option<(String, Option<(i32, i32)>)>,
+    pub author: Option<String>,
+    pub published_at: Option<NaiveDateTime>,
+    pub updated_at: NaiveDateTime,
+    pub word_count: usize,
+    pub overrides: &'a SeoOverrides,
+}
+
+fn iso(time: NaiveDateTime) -> String {
+    time.and_utc().to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+pub fn build_seo(site: &SiteConfig, post: SeoSource<'_>) -> Seo {
+    let overrides = post.overrides;
+    let canonical_url = overrides
+        .canonical_url
+        .clone()
+        .unwrap_or_else(|| site.post_url(&post.id.to_string(), post.slug));
+    let title = overrides.title.clone().unwrap_or_else(|| post.title.to_string());
+    let description = overrides.description.clone().or(post.description);
+    let image = post.image.map(|(url, size)| (site.absolute_api_url(&url), size));
+
+    let mut open_graph = Vec::new();
+    let mut og = |property: &str, content: &str| {
+        open_graph.push(MetaTag { property: property.to_string(), content: content.to_string() })
+    };
+    og("og:type", "article");
+    og("og:site_name", &site.title);
+    og("og:title", &title);
+    og("og:url", &canonical_url);
+    if let Some(description) = &description {
+        og("og:description", description);
+    }
+    if let Some((url, size)) = &image {
+        og("og:image", url);
+        if let Some((width, height)) = size {
+            og("og:image:width", &width.to_string());
+            og("og:image:height", &height.to_string());
+        }
+    }
+    if let Some(published) = post.published_at {
+        og("article:published_time", &iso(published));
+    }
+    og("article:modified_time", &iso(post.updated_at));
+    if let Some(author) = &post.author {
+        og("article:author", author);
+    }
+
+    let mut twitter = Vec::new();
+    let mut tw = |name: &str, content: &str| {
+        twitter.push(MetaTag { property: name.to_string(), content: content.to_string() })
+    };
+    tw("twitter:card", if image.is_some() { "summary_large_image" } else { "summary" });
+    tw("twitter:title", &title);
+    if let Some(description) = &description {
+        tw("twitter:description", description);
+    }
+    if let Some((url, _)) = &image {
+        tw("twitter:image", url);
+    }
+    if let Some(handle) = &site.twitter_site {
+        tw("twitter:site", handle);
+    }
+
+    let mut ld = serde_json::json!({
+        "@context": "https://schema.org",
+        "@type": "BlogPosting",
+        "headline": title,
+        "url": canonical_url,
+        "mainEntityOfPage": { "@type": "WebPage", "@id": canonical_url },
+        "dateModified": iso(post.updated_at),
+        "wordCount": post.word_count,
+        "publisher": { "@type": "Organization", "name": site.title },
+    });
+    if let Some(description) = &description {
+        ld["description"] = description.clone().into();
+    }
+    if let Some((url, _)) = &image {
+        ld["image"] = url.clone().into();
+    }
+    if let Some(published) = post.published_at {
+        ld["datePublished"] = iso(published).into();
+    }
+    if let Some(author) = &post.author {
+        ld["author"] = serde_json::json!({ "@type": "Person", "name": author });
+    }
+    // Keep `</script>` in a title from ending the script element.
+    let json_ld = ld.to_string().replace('<', "\\u003c");
+
+    // Embeds are of the post here, even when it was first published elsewhere.
+    let post_url = site.post_url(&post.id.to_string(), post.slug);
+    let links = [OembedFormat::Json, OembedFormat::Xml]
+        .into_iter()
+        .map(|format| LinkTag {
+            rel: "alternate".to_string(),
+            media_type: format.discovery_type().to_string(),
+            href: discovery_url(site, &post_url, format),
+            title: title.clone(),
+        })
+        .collect();
+
+    Seo {
+        canonical_url,
+        title,
+        description,
+        image: image.map(|(url, _)| url),
+        robots: overrides.noindex.unwrap_or(false).then(|| "noindex".to_string()),
+        open_graph,
+        twitter,
+        json_ld,
+        links,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(overrides: &SeoOverrides) -> SeoSource<'_> {
+        SeoSource {
+            id: Uuid::nil(),
+            slug: Some("hello"),
+            title: "Hello </script>",
+            description: Some("A post".to_string()),
+            image: Some(("/assets/x/large.webp".to_string(), Some((1200, 630)))),
+            author: Some("Ada".to_string()),
+            published_at: chrono::DateTime::from_timestamp(0, 0).map(|t| t.naive_utc()),
+            updated_at: chrono::DateTime::from_timestamp(60, 0).unwrap().naive_utc(),
+            word_count: 42,
+            overrides,
+        }
+    }
+
+    fn tag<'a>(tags: &'a [MetaTag], property: &str) -> Option<&'a str> {
+        tags.iter().find(|t| t.property == property).map(|t| t.content.as_str())
+    }
+
+    #[test]
+    fn builds_open_graph_twitter_and_json_ld() {
+        let site = SiteConfig { twitter_site: Some("@blog".to_string()), ..Default::default() };
+        let seo = build_seo(&site, source(&SeoOverrides::default()));
+        assert_eq!(seo.canonical_url, "http://localhost:3000/posts/hello");
+        assert_eq!(seo.image.as_deref(), Some("http://localhost:8000/assets/x/large.webp"));
+        assert_eq!(tag(&seo.open_graph, "og:image:width"), Some("1200"));
+        assert_eq!(tag(&seo.open_graph, "article:published_time"), Some("1970-01-01T00:00:00Z"));
+        assert_eq!(tag(&seo.twitter, "twitter:card"), Some("summary_large_image"));
+        assert_eq!(tag(&seo.twitter, "twitter:site"), Some("@blog"));
+        assert_eq!(seo.robots, None);
+
+        assert!(!seo.json_ld.contains("</script>"), "{}", seo.json_ld);
+        let ld: serde_json::Value = serde_json::from_str(&seo.json_ld).unwrap();
+        assert_eq!(ld["@type"], "BlogPosting");
+        assert_eq!(ld["headline"], "Hello </script>");
+        assert_eq!(ld["author"]["name"], "Ada");
+        assert_eq!(ld["dateModified"], "1970-01-01T00:01:00Z");
+        assert_eq!(ld["wordCount"], 42);
+
+        assert_eq!(seo.links.len(), 2);
+        assert_eq!(seo.links[0].media_type, "application/json+oembed");
+        assert_eq!(
+            seo.links[0].href,
+            "http://localhost:8000/oembed?url=http%3A%2F%2Flocalhost%3A3000%2Fposts%2Fhello&format=json"
+        );
+        assert_eq!(seo.links[1].media_type, "text/xml+oembed");
+    }
+
+    #[test]
+    fn overrides_replace_generated_values() {
+        let overrides = SeoOverrides {
+            title: Some("Better title".to_string()),
+            canonical_url: Some("https://elsewhere.example/p".to_string()),
+            noindex: Some(true),
+            ..Default::default()
+        };
+        let seo = build_seo(&SiteConfig::default(), source(&overrides));
+        assert_eq!(seo.title, "Better title");
+        assert_eq!(seo.canonical_url, "https://elsewhere.example/p");
+        assert_eq!(tag(&seo.open_graph, "og:url"), Some("https://elsewhere.example/p"));
+        assert_eq!(seo.description.as_deref(), Some("A post"));
+        assert_eq!(seo.robots.as_deref(), Some("noindex"));
+        assert!(seo.links[0].href.contains("url=http%3A%2F%2Flocalhost%3A3000%2Fposts%2Fhello"));
+    }
+
+    #[test]
+    fn overrides_round_trip_through_json() {
+        let blank = SeoOverrides { title: Some("  ".to_string()), noindex: Some(false), ..Default::default() };
+        assert_eq!(blank.to_json(), None);
+        let set = SeoOverrides { title: Some(" T ".to_string()), ..Default::default() };
+        let json = set.to_json().unwrap();
+        assert_eq!(json, serde_json::json!({"title": "T"}));
+        assert_eq!(SeoOverrides::from_json(Some(&json)).title.as_deref(), Some("T"));
+        assert_eq!(SeoOverrides::from_json(Some(&serde_json::json!(3))), SeoOverrides::default());
+
+        let bad = SeoOverrides { canonical_url: Some("asset:x".to_string()), ..Default::default() };
+        assert!(bad.validate().unwrap_err().contains("canonical_url"));
+    }
+}
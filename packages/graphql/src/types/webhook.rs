@@ -0,0 +1,84 @@
+use crate::types::newsletter::DeliveryStatus;
+use async_graphql::SimpleObject;
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+/// An endpoint told about events on your blog.
+#[derive(SimpleObject)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub url: String,
+    /// The events sent to it, e.g. `post.published`
+    pub events: Vec<String>,
+    /// Inactive webhooks are sent nothing
+    pub is_active: bool,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl From<models::webhooks::Model> for Webhook {
+    fn from(m: models::webhooks::Model) -> Self {
+        Webhook {
+            id: m.id,
+            url: m.url,
+            events: serde_json::from_value(m.events).unwrap_or_default(),
+            is_active: m.is_active,
+            created_at: m.created_at,
+            updated_at: m.updated_at,
+        }
+    }
+}
+
+/// A new webhook with the secret its payloads are signed with. This is the
+/// only time the secret is shown.
+#[derive(SimpleObject)]
+pub struct CreatedWebhook {
+    pub webhook: Webhook,
+    pub secret: String,
+}
+
+/// One event sent, or to be sent, to one webhook.
+#[derive(SimpleObject)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub event: String,
+    /// The JSON body, as posted
+    pub payload: String,
+    pub status: DeliveryStatus,
+    /// Times delivery has been tried
+    pub attempts: i32,
+    /// When a queued delivery is next tried
+    pub next_attempt_at: NaiveDateTime,
+    /// HTTP status of the latest attempt's response
+    pub response_status: Option<i32>,
+    /// Start of the latest attempt's response body
+    pub response_body: Option<String>,
+    pub last_error: Option<String>,
+    pub delivered_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<models::webhook_deliveries::Model> for WebhookDelivery {
+    fn from(m: models::webhook_deliveries::Model) -> Self {
+        WebhookDelivery {
+            id: m.id,
+            webhook_id: m.webhook_id,
+            event: m.event,
+            payload: m.payload.to_string(),
+            status: m.status.into(),
+            attempts: m.attempts,
+            next_attempt_at: m.next_attempt_at,
+            response_status: m.response_status,
+            response_body: m.response_body,
+            last_error: m.last_error,
+            delivered_at: m.delivered_at,
+            created_at: m.created_at,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct DeletedWebhook {
+    pub id: Uuid,
+}
@@ -0,0 +1,123 @@
+use crate::types::comment::CommentStatus;
+use crate::types::newsletter::DeliveryStatus;
+use async_graphql::{Enum, SimpleObject};
+use chrono::NaiveDateTime;
+use models::sea_orm_active_enums as enums;
+use uuid::Uuid;
+
+/// What a page mentioning a post does with it, from its h-entry.
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum WebmentionKind {
+    #[graphql(name = "MENTION")]
+    Mention,
+    #[graphql(name = "REPLY")]
+    Reply,
+    #[graphql(name = "LIKE")]
+    Like,
+    #[graphql(name = "REPOST")]
+    Repost,
+    #[graphql(name = "BOOKMARK")]
+    Bookmark,
+}
+
+impl From<enums::WebmentionKind> for WebmentionKind {
+    fn from(v: enums::WebmentionKind) -> Self {
+        match v {
+            enums::WebmentionKind::Mention => Self::Mention,
+            enums::WebmentionKind::Reply => Self::Reply,
+            enums::WebmentionKind::Like => Self::Like,
+            enums::WebmentionKind::Repost => Self::Repost,
+            enums::WebmentionKind::Bookmark => Self::Bookmark,
+        }
+    }
+}
+
+/// A verified page elsewhere that links to one of your posts.
+#[derive(SimpleObject)]
+pub struct Webmention {
+    pub id: Uuid,
+    pub post_id: Uuid,
+    /// The page that links to the post
+    pub source: String,
+    /// The post's URL, as the source gave it
+    pub target: String,
+    pub kind: WebmentionKind,
+    pub author_name: Option<String>,
+    pub author_url: Option<String>,
+    pub author_photo: Option<String>,
+    pub title: Option<String>,
+    /// A plain-text excerpt of the source's content
+    pub content: Option<String>,
+    pub published_at: Option<NaiveDateTime>,
+    pub status: CommentStatus,
+    pub verified_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub moderated_at: Option<NaiveDateTime>,
+}
+
+impl From<models::webmentions::Model> for Webmention {
+    fn from(m: models::webmentions::Model) -> Self {
+        Webmention {
+            id: m.id,
+            post_id: m.post_id,
+            source: m.source,
+            target: m.target,
+            kind: m.kind.into(),
+            author_name: m.author_name,
+            author_url: m.author_url,
+            author_photo: m.author_photo,
+            title: m.title,
+            content: m.content,
+            published_at: m.published_at,
+            status: m.status.into(),
+            verified_at: m.verified_at,
+            created_at: m.created_at,
+            moderated_at: m.moderated_at,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct DeletedWebmention {
+    pub id: Uuid,
+}
+
+/// A page one of your posts links to, told, or to be told, about the link.
+#[derive(SimpleObject)]
+pub struct OutgoingWebmention {
+    pub id: Uuid,
+    pub post_id: Uuid,
+    pub source: String,
+    pub target: String,
+    /// The target's Webmention endpoint, once discovered
+    pub endpoint: Option<String>,
+    pub status: DeliveryStatus,
+    /// Times sending has been tried
+    pub attempts: i32,
+    /// When a queued mention is next tried
+    pub next_attempt_at: NaiveDateTime,
+    /// HTTP status of the latest attempt's response
+    pub response_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub sent_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<models::outgoing_webmentions::Model> for OutgoingWebmention {
+    fn from(m: models::outgoing_webmentions::Model) -> Self {
+        OutgoingWebmention {
+            id: m.id,
+            post_id: m.post_id,
+            source: m.source,
+            target: m.target,
+            endpoint: m.endpoint,
+            status: m.status.into(),
+            attempts: m.attempts,
+            next_attempt_at: m.next_attempt_at,
+            response_status: m.response_status,
+            last_error: m.last_error,
+            sent_at: m.sent_at,
+            created_at: m.created_at,
+        }
+    }
+}
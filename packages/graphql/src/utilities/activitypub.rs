@@ -0,0 +1,696 @@
+//! ActivityPub (https://www.w3.org/TR/activitypub/): fediverse accounts
+//! following a blog, hearing of its posts, and replying to them.
+
+use super::assets::AssetImages;
+use super::comments::{comments_open, screen};
+use super::highlight::escape_html;
+use super::html::{parse_tag, skip_element, REMOVED_WITH_CONTENT};
+use super::markdown::{render_document_with, ContentFormat, RenderOptions};
+use super::newsletter::email_safe;
+use super::webmentions::{decode_entities, is_private};
+use models::sea_orm_active_enums::CommentStatus;
+use models::{activitypub_actors, posts, users};
+use repositories::{ActivityPubRepository, NewComment, PostRepository, UserRepository, WebmentionRepository};
+use sea_orm::DatabaseConnection;
+use serde_json::{json, Value};
+use services::activitypub::{check_signed_headers, deliver, fetch, parse_signature, verify, ACTIVITY_JSON};
+use services::assets::StorageDriver;
+use services::config::{QueueConfig, SiteConfig, SpamConfig};
+use services::spam::Submission;
+use services::webhooks::{Client, WebhookEvent};
+use std::collections::HashMap;
+use std::sync::Arc;
+use url::Url;
+use uuid::Uuid;
+
+/// The audience that makes an object public.
+pub const PUBLIC: &str = "https://www.w3.org/ns/activitystreams#Public";
+
+/// Longest username an actor may take.
+const MAX_USERNAME_LENGTH: usize = 30;
+
+/// Longest reply body kept, as for comments.
+const MAX_BODY_CHARS: usize = 5000;
+
+/// Longest reply author name kept, as for comments.
+const MAX_NAME_CHARS: usize = 100;
+
+/// Posts listed in an outbox.
+const OUTBOX_SIZE: i32 = 20;
+
+/// Why an inbox turned an activity away.
+#[derive(Debug)]
+pub enum InboxError {
+    /// No such actor here
+    NotFound,
+    /// The signature is missing or doesn't check out; the message says how
+    Unauthorized(String),
+    /// The activity is wrong; the message says how
+    Invalid(String),
+    Database(String),
+}
+
+/// A POST to an inbox, as it arrived.
+pub struct InboxRequest<'a> {
+    pub method: &'a str,
+    /// Path and query, as signed
+    pub path: &'a str,
+    /// Keyed by lowercase name
+    pub headers: HashMap<String, String>,
+    pub body: &'a [u8],
+}
+
+/// Whether `username` is one an actor may take: lowercase letters, digits
+/// and underscores.
+pub fn valid_username(username: &str) -> bool {
+    !username.is_empty()
+        && username.len() <= MAX_USERNAME_LENGTH
+        && username.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_')
+}
+
+pub fn actor_url(site: &SiteConfig, username: &str) -> String {
+    format!("{}/ap/users/{username}", site.api_url)
+}
+
+fn key_id(site: &SiteConfig, username: &str) -> String {
+    format!("{}#main-key", actor_url(site, username))
+}
+
+/// The id of the object a post federates as.
+pub fn object_url(site: &SiteConfig, post_id: Uuid) -> String {
+    format!("{}/ap/posts/{post_id}", site.api_url)
+}
+
+/// The host in the blog's `acct:` handles: this server's.
+pub fn webfinger_host(site: &SiteConfig) -> String {
+    let url = Url::parse(&site.api_url).ok();
+    let host = url.as_ref().and_then(|u| u.host_str()).unwrap_or_default();
+    match url.as_ref().and_then(Url::port) {
+        Some(port) => format!("{host}:{port}"),
+        None => host.to_string(),
+    }
+}
+
+/// `@username@host`, as people type it to follow the blog.
+pub fn handle(site: &SiteConfig, username: &str) -> String {
+    format!("@{username}@{}", webfinger_host(site))
+}
+
+/// The username a WebFinger `resource` asks about: `acct:name@host` on
+/// this server, or an actor's URL.
+pub fn webfinger_username(site: &SiteConfig, resource: &str) -> Option<String> {
+    let username = match resource.strip_prefix("acct:") {
+        Some(acct) => {
+            let (name, host) = acct.trim_start_matches('@').rsplit_once('@')?;
+            host.eq_ignore_ascii_case(&webfinger_host(site)).then_some(name)?
+        }
+        None => resource.strip_prefix(&format!("{}/ap/users/", site.api_url))?,
+    };
+    let username = username.to_lowercase();
+    valid_username(&username).then_some(username)
+}
+
+/// The WebFinger answer for `actor`, pointing at its actor document.
+pub fn webfinger(site: &SiteConfig, actor: &activitypub_actors::Model) -> Value {
+    let url = actor_url(site, &actor.username);
+    json!({
+        "subject": format!("acct:{}@{}", actor.username, webfinger_host(site)),
+        "aliases": [url],
+        "links": [
+            {"rel": "self", "type": ACTIVITY_JSON, "href": url},
+            {"rel": "http://webfinger.net/rel/profile-page", "type": "text/html", "href": site.url},
+        ],
+    })
+}
+
+fn context() -> Value {
+    json!(["https://www.w3.org/ns/activitystreams", "https://w3id.org/security/v1"])
+}
+
+/// `object` as a document of its own.
+pub fn with_context(mut object: Value) -> Value {
+    if let Value::Object(map) = &mut object {
+        map.insert("@context".to_string(), context());
+    }
+    object
+}
+
+/// The blog as a `Person`: who the fediverse follows.
+pub fn actor_document(site: &SiteConfig, actor: &activitypub_actors::Model, user: &users::Model) -> Value {
+    let url = actor_url(site, &actor.username);
+    let name = user.display_name.clone().filter(|n| !n.trim().is_empty()).unwrap_or_else(|| site.title.clone());
+    let summary = user.bio.as_deref().filter(|b| !b.trim().is_empty()).map(|bio| {
+        let mut html = String::from("<p>");
+        escape_html(&mut html, bio);
+        html.push_str("</p>");
+        html
+    });
+    json!({
+        "@context": context(),
+        "id": url,
+        "type": "Person",
+        "preferredUsername": actor.username,
+        "name": name,
+        "summary": summary,
+        "url": site.url,
+        "inbox": format!("{url}/inbox"),
+        "outbox": format!("{url}/outbox"),
+        "followers": format!("{url}/followers"),
+        "manuallyApprovesFollowers": false,
+        "discoverable": true,
+        "publicKey": {
+            "id": key_id(site, &actor.username),
+            "owner": url,
+            "publicKeyPem": actor.public_key_pem,
+        },
+    })
+}
+
+/// How many follow the blog, without saying who.
+pub fn followers_collection(site: &SiteConfig, actor: &activitypub_actors::Model, total: u64) -> Value {
+    json!({
+        "@context": context(),
+        "id": format!("{}/followers", actor_url(site, &actor.username)),
+        "type": "OrderedCollection",
+        "totalItems": total,
+    })
+}
+
+/// The post's body as fediverse servers show it: rendered as for email,
+/// with absolute URLs and nothing active.
+pub async fn render_content(
+    db: &DatabaseConnection,
+    driver: Option<&Arc<StorageDriver>>,
+    site: &SiteConfig,
+    post: &posts::Model,
+) -> Result<String, String> {
+    let markdown = post.markdown_content.as_deref().unwrap_or_default();
+    let assets = match driver {
+        Some(driver) => AssetImages::load(db, driver, post.user_id, &[markdown]).await?,
+        None => AssetImages::default(),
+    };
+    let options = RenderOptions {
+        highlight_code: false,
+        line_numbers: false,
+        heading_anchors: false,
+        math: false,
+        format: ContentFormat::Html,
+    };
+    let html = render_document_with(markdown, &options, &assets).html;
+    Ok(email_safe(&html, site, &site.post_url(&post.id.to_string(), post.slug.as_deref())))
+}
+
+/// A published post as an `Article`, with `content` its rendered body.
+pub fn article(site: &SiteConfig, actor: &activitypub_actors::Model, post: &posts::Model, content: &str) -> Value {
+    let actor = actor_url(site, &actor.username);
+    json!({
+        "id": object_url(site, post.id),
+        "type": "Article",
+        "attributedTo": actor,
+        "name": post.title,
+        "summary": post.description,
+        "content": content,
+        "mediaType": "text/html",
+        "url": site.post_url(&post.id.to_string(), post.slug.as_deref()),
+        "published": post.first_published_at.map(|at| at.and_utc().to_rfc3339()),
+        "updated": post.updated_at.and_utc().to_rfc3339(),
+        "to": [PUBLIC],
+        "cc": [format!("{actor}/followers")],
+    })
+}
+
+/// What's left of a post that is no longer published.
+pub fn tombstone(site: &SiteConfig, post_id: Uuid) -> Value {
+    json!({"id": object_url(site, post_id), "type": "Tombstone"})
+}
+
+/// `actor` doing `kind` to `object`, addressed to everyone and its
+/// followers. A post is created once, so creating it has a fixed id; the
+/// rest are told apart by when they happened.
+pub fn activity(site: &SiteConfig, actor: &activitypub_actors::Model, kind: &str, object: Value) -> Value {
+    let actor = actor_url(site, &actor.username);
+    let object_id = object["id"].as_str().unwrap_or_default();
+    let id = match kind {
+        "Create" => format!("{object_id}#create"),
+        kind => format!("{object_id}#{}-{}", kind.to_lowercase(), chrono::Utc::now().timestamp_millis()),
+    };
+    json!({
+        "@context": context(),
+        "id": id,
+        "type": kind,
+        "actor": actor,
+        "object": object,
+        "to": [PUBLIC],
+        "cc": [format!("{actor}/followers")],
+    })
+}
+
+/// The blog's latest posts, as the activities that created them.
+pub async fn outbox(
+    db: &DatabaseConnection,
+    driver: Option<&Arc<StorageDriver>>,
+    site: &SiteConfig,
+    actor: &activitypub_actors::Model,
+) -> Result<Value, String> {
+    let page = PostRepository::get_published_posts(
+        db,
+        actor.user_id,
+        Some(1),
+        Some(OUTBOX_SIZE),
+        repositories::PostSortBy::FirstPublishedAt,
+        repositories::SortDirection::Desc,
+    )
+    .await?;
+    let mut items = Vec::new();
+    for post in &page.posts {
+        let content = render_content(db, driver, site, post).await?;
+        let mut create = activity(site, actor, "Create", article(site, actor, post, &content));
+        create["published"] = create["object"]["published"].clone();
+        if let Value::Object(map) = &mut create {
+            map.remove("@context");
+        }
+        items.push(create);
+    }
+    Ok(json!({
+        "@context": context(),
+        "id": format!("{}/outbox", actor_url(site, &actor.username)),
+        "type": "OrderedCollection",
+        "totalItems": PostRepository::count_published(db, actor.user_id).await?,
+        "orderedItems": items,
+    }))
+}
+
+/// Queue the activity that `event` on `post` makes for the blog's
+/// followers. Returns how many deliveries were queued.
+async fn queue_announcement(
+    db: &DatabaseConnection,
+    driver: Option<&Arc<StorageDriver>>,
+    site: &SiteConfig,
+    post: &posts::Model,
+    event: WebhookEvent,
+) -> Result<u64, String> {
+    let kind = match event {
+        WebhookEvent::PostPublished => "Create",
+        WebhookEvent::PostUpdated => "Update",
+        WebhookEvent::PostUnpublished | WebhookEvent::PostDeleted => "Delete",
+        WebhookEvent::AssetCreated => return Ok(0),
+    };
+    let Some(actor) = ActivityPubRepository::actor_for_user(db, post.user_id).await? else { return Ok(0) };
+    let inboxes = ActivityPubRepository::follower_inboxes(db, post.user_id).await?;
+    if inboxes.is_empty() {
+        return Ok(0);
+    }
+    let object = match kind {
+        "Delete" => tombstone(site, post.id),
+        _ => article(site, &actor, post, &render_content(db, driver, site, post).await?),
+    };
+    ActivityPubRepository::enqueue(db, post.user_id, inboxes, activity(site, &actor, kind, object)).await
+}
+
+/// Tell the blog's followers that `post` was published, changed, or went
+/// away, as `event` says. A failure is logged rather than returned: the post
+/// is already saved.
+pub async fn announce(
+    db: &DatabaseConnection,
+    driver: Option<&Arc<StorageDriver>>,
+    site: &SiteConfig,
+    post: &posts::Model,
+    event: WebhookEvent,
+) {
+    match queue_announcement(db, driver, site, post, event).await {
+        Ok(0) => {}
+        Ok(queued) => tracing::info!(post_id = %post.id, event = event.as_str(), queued, "queued activitypub deliveries"),
+        Err(e) => tracing::warn!(post_id = %post.id, event = event.as_str(), "queueing activitypub deliveries: {e}"),
+    }
+}
+
+/// Make the deliveries that are due, up to `config.batch_size`, signing each
+/// as its blog's actor. Returns how many inboxes took theirs. An inbox that
+/// is gone for good isn't tried again.
+pub async fn deliver_due(
+    db: &DatabaseConnection,
+    client: &Client,
+    site: &SiteConfig,
+    config: &QueueConfig,
+) -> Result<usize, String> {
+    let mut delivered = 0;
+    for (delivery, actor) in ActivityPubRepository::claim_due(db, config.batch_size).await? {
+        let body = serde_json::to_vec(&delivery.activity).map_err(|e| e.to_string())?;
+        let key_id = key_id(site, &actor.username);
+        let attempt = deliver(client, &actor.private_key_pem, &key_id, &delivery.inbox, body).await;
+        let status = attempt.status.map(i32::from);
+        match attempt.error {
+            None => {
+                ActivityPubRepository::mark_delivered(db, delivery.id, status).await?;
+                delivered += 1;
+            }
+            Some(e) => {
+                tracing::warn!(delivery_id = %delivery.id, inbox = %delivery.inbox, attempts = delivery.attempts, "activitypub delivery: {e}");
+                let max_attempts = if status == Some(410) { 0 } else { config.max_attempts };
+                ActivityPubRepository::mark_failed(db, &delivery, status, e, max_attempts).await?;
+            }
+        }
+    }
+    Ok(delivered)
+}
+
+/// An object's id, whether it is given whole or by reference.
+fn id_of(value: &Value) -> Option<&str> {
+    value.as_str().or_else(|| value["id"].as_str())
+}
+
+fn same_origin(a: &str, b: &str) -> bool {
+    match (Url::parse(a), Url::parse(b)) {
+        (Ok(a), Ok(b)) => a.origin() == b.origin(),
+        _ => false,
+    }
+}
+
+/// Refuse URLs this server shouldn't be made to fetch or post to on a
+/// stranger's say-so.
+fn public_url(url: &str) -> Result<(), String> {
+    match Url::parse(url) {
+        Ok(u) if matches!(u.scheme(), "http" | "https") && !is_private(&u) => Ok(()),
+        _ => Err(format!("{url} isn't a public http(s) URL")),
+    }
+}
+
+/// The PEM of the key `key_id` among those an actor document gives.
+fn public_key_pem<'a>(document: &'a Value, key_id: &str) -> Option<&'a str> {
+    let keys = match &document["publicKey"] {
+        Value::Array(keys) => keys.iter().collect(),
+        key => vec![key],
+    };
+    keys.into_iter().find(|key| key["id"].as_str() == Some(key_id))?["publicKeyPem"].as_str()
+}
+
+/// The text of a reply's HTML, paragraphs and line breaks kept.
+pub fn html_to_text(html: &str) -> String {
+    let mut out = String::new();
+    let mut rest = html;
+    while let Some(at) = rest.find('<') {
+        out.push_str(&decode_entities(&rest[..at]));
+        rest = &rest[at..];
+        let Some(tag) = parse_tag(rest) else {
+            out.push('<');
+            rest = &rest[1..];
+            continue;
+        };
+        let mut consumed = tag.len;
+        match tag.name.as_str() {
+            name if REMOVED_WITH_CONTENT.contains(&name) && !tag.closing => {
+                consumed += skip_element(&rest[tag.len..], name);
+            }
+            "br" => out.push('\n'),
+            "p" | "blockquote" | "pre" | "ul" | "ol" | "li" if tag.closing => out.push_str("\n\n"),
+            _ => {}
+        }
+        rest = &rest[consumed..];
+    }
+    out.push_str(&decode_entities(rest));
+
+    let mut text = String::new();
+    for paragraph in out.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+        if !text.is_empty() {
+            text.push_str("\n\n");
+        }
+        let lines: Vec<&str> = paragraph.lines().map(str::trim).collect();
+        text.push_str(&lines.join("\n"));
+    }
+    text
+}
+
+/// The blog's published post, and the reply's parent comment if any, that
+/// `url` names: a post's object or page, or a reply recorded from the
+/// fediverse.
+async fn replied_to(
+    db: &DatabaseConnection,
+    site: &SiteConfig,
+    user_id: Uuid,
+    url: &str,
+) -> Result<Option<(posts::Model, Option<Uuid>)>, String> {
+    if let Some(comment) = ActivityPubRepository::reply(db, user_id, url).await? {
+        let post = PostRepository::find_by_id(db, comment.post_id).await?.filter(|p| p.is_published);
+        // Replies to a reply that isn't shown go on the post itself.
+        let parent = (comment.status == CommentStatus::Approved).then_some(comment.id);
+        return Ok(post.map(|p| (p, parent)));
+    }
+    let key = url
+        .strip_prefix(&format!("{}/ap/posts/", site.api_url))
+        .map(str::to_string)
+        .or_else(|| site.post_key(url));
+    let Some(key) = key else { return Ok(None) };
+    let post = WebmentionRepository::published_post(db, &key).await?;
+    Ok(post.filter(|p| p.user_id == user_id).map(|p| (p, None)))
+}
+
+/// Take `sender`'s follow of the blog, and queue the `Accept` that tells
+/// its server so.
+async fn follow(
+    db: &DatabaseConnection,
+    site: &SiteConfig,
+    actor: &activitypub_actors::Model,
+    sender: &str,
+    sender_document: &Value,
+    activity: &Value,
+) -> Result<(), InboxError> {
+    let url = actor_url(site, &actor.username);
+    if id_of(&activity["object"]) != Some(url.as_str()) {
+        return Err(InboxError::Invalid("Follow is for another actor".to_string()));
+    }
+    let inbox = sender_document["inbox"].as_str().ok_or(InboxError::Invalid("sender has no inbox".to_string()))?;
+    let shared_inbox = sender_document["endpoints"]["sharedInbox"].as_str();
+    for url in [Some(inbox), shared_inbox].into_iter().flatten() {
+        public_url(url).map_err(InboxError::Invalid)?;
+    }
+    ActivityPubRepository::add_follower(db, actor.user_id, sender, inbox, shared_inbox)
+        .await
+        .map_err(InboxError::Database)?;
+    let accept = json!({
+        "@context": context(),
+        "id": format!("{url}#accepts/{}", Uuid::new_v4()),
+        "type": "Accept",
+        "actor": url,
+        "object": activity,
+    });
+    ActivityPubRepository::enqueue(db, actor.user_id, vec![inbox.to_string()], accept)
+        .await
+        .map_err(InboxError::Database)?;
+    tracing::info!(user_id = %actor.user_id, %sender, "new fediverse follower");
+    Ok(())
+}
+
+/// Record `object`, a note `sender` wrote, as a comment if it replies to one
+/// of the blog's posts and the post takes comments. Anything else is
+/// ignored.
+#[allow(clippy::too_many_arguments)]
+async fn record_reply(
+    db: &DatabaseConnection,
+    site: &SiteConfig,
+    spam: &SpamConfig,
+    actor: &activitypub_actors::Model,
+    sender: &str,
+    sender_document: &Value,
+    object: &Value,
+) -> Result<(), InboxError> {
+    let Some(object_id) = object["id"].as_str() else {
+        return Err(InboxError::Invalid("object has no id".to_string()));
+    };
+    if !same_origin(object_id, sender) || id_of(&object["attributedTo"]) != Some(sender) {
+        return Err(InboxError::Unauthorized("object isn't the sender's".to_string()));
+    }
+    let Some(in_reply_to) = id_of(&object["inReplyTo"]) else { return Ok(()) };
+    let replied = replied_to(db, site, actor.user_id, in_reply_to).await.map_err(InboxError::Database)?;
+    let Some((post, parent_id)) = replied else { return Ok(()) };
+    let user = UserRepository::find_by_id(db, actor.user_id)
+        .await
+        .map_err(|e| InboxError::Database(e.to_string()))?
+        .ok_or(InboxError::NotFound)?;
+    if !comments_open(user.comment_policy, user.comments_close_after_days, post.first_published_at, chrono::Utc::now().naive_utc()) {
+        return Ok(());
+    }
+
+    let text = html_to_text(object["content"].as_str().unwrap_or_default());
+    // Replies start by mentioning the blog, which needn't be kept.
+    let mention = format!("@{}", actor.username);
+    let text = match text.strip_prefix(&mention) {
+        Some(rest) if !rest.starts_with(|c: char| c.is_alphanumeric() || c == '_') => rest.trim_start().to_string(),
+        _ => text,
+    };
+    let body: String = text.chars().take(MAX_BODY_CHARS).collect();
+    if body.is_empty() {
+        return Ok(());
+    }
+    let name = [&sender_document["name"], &sender_document["preferredUsername"]]
+        .into_iter()
+        .filter_map(Value::as_str)
+        .map(str::trim)
+        .find(|n| !n.is_empty())
+        .unwrap_or(sender);
+    let comment = NewComment {
+        post_id: post.id,
+        user_id: actor.user_id,
+        parent_id,
+        author_name: name.chars().take(MAX_NAME_CHARS).collect(),
+        author_email: None,
+        author_url: Some(id_of(&sender_document["url"]).unwrap_or(sender).to_string()),
+        body,
+    };
+
+    let submission = Submission {
+        author_name: &comment.author_name,
+        author_email: None,
+        author_url: comment.author_url.as_deref(),
+        body: &comment.body,
+        honeypot: None,
+        elapsed: None,
+    };
+    let (status, score) = screen(db, &user, post.id, &submission, spam).await.map_err(InboxError::Database)?;
+    let saved = ActivityPubRepository::record_reply(db, comment, object_id, sender, status, Some(score as f32))
+        .await
+        .map_err(InboxError::Database)?;
+    if let Some(saved) = saved {
+        tracing::info!(post_id = %post.id, comment_id = %saved.id, %sender, "fediverse reply recorded");
+    }
+    Ok(())
+}
+
+/// Take an activity posted to `username`'s inbox: check its signature
+/// against the sender's key, then act on follows, unfollows, replies to the
+/// blog's posts and their deletion. Other activities are accepted and
+/// ignored.
+pub async fn receive(
+    db: &DatabaseConnection,
+    client: &Client,
+    site: &SiteConfig,
+    spam: &SpamConfig,
+    username: &str,
+    request: InboxRequest<'_>,
+) -> Result<(), InboxError> {
+    let actor = ActivityPubRepository::actor_by_username(db, username)
+        .await
+        .map_err(InboxError::Database)?
+        .ok_or(InboxError::NotFound)?;
+    let activity: Value = serde_json::from_slice(request.body)
+        .map_err(|e| InboxError::Invalid(format!("body isn't JSON: {e}")))?;
+    let kind = activity["type"].as_str().unwrap_or_default();
+    let sender = id_of(&activity["actor"]).ok_or(InboxError::Invalid("activity has no actor".to_string()))?;
+    // A deleted account's key goes with it, so its deletion can't be
+    // checked; nothing here needs it either.
+    if kind == "Delete" && id_of(&activity["object"]) == Some(sender) {
+        return Ok(());
+    }
+
+    let signature = request
+        .headers
+        .get("signature")
+        .and_then(|s| parse_signature(s))
+        .ok_or(InboxError::Unauthorized("missing or unreadable Signature header".to_string()))?;
+    // Everything that can be checked without the sender's key is, before
+    // an unsigned or stale request gets us to fetch anything.
+    let header = |name: &str| request.headers.get(name).cloned();
+    let now = chrono::Utc::now();
+    check_signed_headers(&signature, request.body, now, header).map_err(InboxError::Unauthorized)?;
+    if !same_origin(&signature.key_id, sender) {
+        return Err(InboxError::Unauthorized("the signing key isn't the sender's".to_string()));
+    }
+    public_url(sender).map_err(InboxError::Invalid)?;
+    let sender_document = fetch(client, &actor.private_key_pem, &key_id(site, &actor.username), sender)
+        .await
+        .map_err(|e| InboxError::Unauthorized(format!("fetching the sender: {e}")))?;
+    if sender_document["id"].as_str() != Some(sender) {
+        return Err(InboxError::Unauthorized("the sender's document is someone else's".to_string()));
+    }
+    let pem = public_key_pem(&sender_document, &signature.key_id)
+        .ok_or(InboxError::Unauthorized("the signing key isn't the sender's".to_string()))?;
+    verify(pem, &signature, request.method, request.path, request.body, now, header)
+        .map_err(InboxError::Unauthorized)?;
+
+    let object = &activity["object"];
+    match kind {
+        "Follow" => follow(db, site, &actor, sender, &sender_document, &activity).await,
+        "Undo" if object["type"] == "Follow" && id_of(&object["actor"]) == Some(sender) => {
+            ActivityPubRepository::remove_follower(db, actor.user_id, sender)
+                .await
+                .map_err(InboxError::Database)?;
+            Ok(())
+        }
+        "Create" if object.is_object() => record_reply(db, site, spam, &actor, sender, &sender_document, object).await,
+        "Delete" => {
+            let Some(object_id) = id_of(object) else { return Ok(()) };
+            if !same_origin(object_id, sender) {
+                return Err(InboxError::Unauthorized("object isn't the sender's".to_string()));
+            }
+            ActivityPubRepository::delete_reply(db, actor.user_id, sender, object_id)
+                .await
+                .map_err(InboxError::Database)?;
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{post, site};
+
+    #[test]
+    fn webfinger_resources_name_local_actors() {
+        let site = site();
+        assert_eq!(webfinger_username(&site, "acct:Ana@api.blog.example").as_deref(), Some("ana"));
+        assert_eq!(webfinger_username(&site, "acct:@ana@API.blog.example").as_deref(), Some("ana"));
+        assert_eq!(webfinger_username(&site, "https://api.blog.example/ap/users/ana").as_deref(), Some("ana"));
+        assert_eq!(webfinger_username(&site, "acct:ana@blog.example"), None);
+        assert_eq!(webfinger_username(&site, "acct:ana/x@api.blog.example"), None);
+        assert_eq!(handle(&site, "ana"), "@ana@api.blog.example");
+        let local = SiteConfig::default();
+        assert_eq!(handle(&local, "ana"), "@ana@localhost:8000");
+        assert!(valid_username("ana_2"));
+        assert!(!valid_username("Ana") && !valid_username("") && !valid_username("a-b"));
+    }
+
+    #[test]
+    fn posts_federate_as_articles() {
+        let site = site();
+        let actor = activitypub_actors::Model {
+            user_id: Uuid::nil(),
+            username: "ana".to_string(),
+            public_key_pem: "PEM".to_string(),
+            private_key_pem: String::new(),
+            created_at: chrono::NaiveDateTime::default(),
+        };
+        let post = post("Hello", "hello");
+        let create = activity(&site, &actor, "Create", article(&site, &actor, &post, "<p>Hi</p>"));
+        let object = "https://api.blog.example/ap/posts/00000000-0000-0000-0000-000000000000";
+        assert_eq!(create["id"], format!("{object}#create"));
+        assert_eq!(create["actor"], "https://api.blog.example/ap/users/ana");
+        assert_eq!(create["cc"][0], "https://api.blog.example/ap/users/ana/followers");
+        assert_eq!(create["object"]["id"], object);
+        assert_eq!(create["object"]["url"], "https://blog.example/posts/hello");
+        assert_eq!(create["object"]["content"], "<p>Hi</p>");
+        assert_eq!(create["object"]["published"], "1970-01-01T00:00:00+00:00");
+
+        let delete = activity(&site, &actor, "Delete", tombstone(&site, post.id));
+        assert!(delete["id"].as_str().unwrap().starts_with(&format!("{object}#delete-")));
+        assert_eq!(delete["object"]["type"], "Tombstone");
+    }
+
+    #[test]
+    fn replies_keep_their_paragraphs() {
+        let html = r#"<p><span class="h-card"><a href="https://api.blog.example/ap/users/ana" class="u-url mention">@<span>ana</span></a></span> Great   post &amp; thanks!</p><p>Line one<br>line two<script>x()</script></p>"#;
+        assert_eq!(html_to_text(html), "@ana Great   post & thanks!\n\nLine one\nline two");
+        assert_eq!(html_to_text("plain"), "plain");
+    }
+
+    #[test]
+    fn keys_are_picked_by_id() {
+        let document = json!({"publicKey": [{"id": "https://a.example/u#k1", "publicKeyPem": "one"}, {"id": "https://a.example/u#k2", "publicKeyPem": "two"}]});
+        assert_eq!(public_key_pem(&document, "https://a.example/u#k2"), Some("two"));
+        let document = json!({"publicKey": {"id": "https://a.example/u#main-key", "publicKeyPem": "main"}});
+        assert_eq!(public_key_pem(&document, "https://a.example/u#main-key"), Some("main"));
+        assert_eq!(public_key_pem(&document, "https://b.example/u#main-key"), None);
+        assert!(same_origin("https://a.example/notes/1", "https://a.example/users/u"));
+        assert!(!same_origin("https://a.example/notes/1", "https://a.example:8443/users/u"));
+    }
+}
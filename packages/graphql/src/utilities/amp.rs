@@ -0,0 +1,153 @@
+use super::highlight::escape_html;
+use super::html::{parse_tag, push_attr, skip_element, REMOVED_WITH_CONTENT};
+
+/// Elements AMP forbids whose content can stay, e.g. a form's text.
+const UNWRAPPED: &[&str] = &["form", "embed", "frame", "frameset", "base", "link", "meta", "video", "audio", "source"];
+
+fn youtube_id(src: &str) -> Option<&str> {
+    let (_, rest) = src.split_once("youtube-nocookie.com/embed/").or_else(|| src.split_once("youtube.com/embed/"))?;
+    let id = rest.split(['?', '&', '"']).next()?;
+    (!id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')).then_some(id)
+}
+
+/// A link standing in for something AMP can't show inline.
+fn link(out: &mut String, href: &str, text: &str) {
+    out.push_str("<a href=\"");
+    out.push_str(href);
+    out.push_str("\">");
+    out.push_str(if text.is_empty() { href } else { text });
+    out.push_str("</a>");
+}
+
+fn is_dimension(value: Option<&str>) -> bool {
+    value.is_some_and(|v| !v.is_empty() && v.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Rewrite rendered HTML into what AMP pages accept: images become
+/// `<amp-img>` (or a link when their size is unknown), YouTube embeds become
+/// `<amp-youtube>`, other iframes become links, forbidden elements are
+/// dropped, and `style`, event-handler attributes and `javascript:` URLs
+/// are removed.
+pub fn amp_safe(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(at) = rest.find('<') {
+        out.push_str(&rest[..at]);
+        rest = &rest[at..];
+        if rest.starts_with("<!--") {
+            let end = rest.find("-->").map_or(rest.len(), |e| e + 3);
+            out.push_str(&rest[..end]);
+            rest = &rest[end..];
+            continue;
+        }
+        let Some(tag) = parse_tag(rest) else {
+            out.push_str("&lt;");
+            rest = &rest[1..];
+            continue;
+        };
+        let mut consumed = tag.len;
+        match tag.name.as_str() {
+            name if REMOVED_WITH_CONTENT.contains(&name) => {
+                if !tag.closing {
+                    consumed += skip_element(&rest[tag.len..], name);
+                }
+            }
+            name if UNWRAPPED.contains(&name) => {}
+            "img" => {
+                let src = tag.attr("src").unwrap_or_default();
+                if is_dimension(tag.attr("width")) && is_dimension(tag.attr("height")) {
+                    out.push_str("<amp-img");
+                    for attr in ["src", "srcset", "alt", "title", "width", "height"] {
+                        if let Some(value) = tag.attr(attr) {
+                            push_attr(&mut out, attr, Some(value));
+                        }
+                    }
+                    out.push_str(" layout=\"responsive\"></amp-img>");
+                } else if !src.is_empty() {
+                    link(&mut out, src, tag.attr("alt").unwrap_or_default());
+                }
+            }
+            "iframe" if !tag.closing => {
+                let src = tag.attr("src").unwrap_or_default();
+                match youtube_id(src) {
+                    Some(id) => out.push_str(&format!(
+                        "<amp-youtube data-videoid=\"{id}\" layout=\"responsive\" width=\"16\" height=\"9\"></amp-youtube>"
+                    )),
+                    None if !src.is_empty() => link(&mut out, src, tag.attr("title").unwrap_or_default()),
+                    None => {}
+                }
+                consumed += skip_element(&rest[tag.len..], "iframe");
+            }
+            "iframe" => {}
+            "input" => {
+                if tag.attr("type") == Some("checkbox") {
+                    out.push_str(if tag.attr("checked").is_some() { "\u{2611}" } else { "\u{2610}" });
+                }
+            }
+            _ if tag.closing => out.push_str(&rest[..tag.len]),
+            name => {
+                out.push('<');
+                out.push_str(name);
+                for (attr, value) in &tag.attrs {
+                    let lower = attr.to_ascii_lowercase();
+                    let script_url = matches!(lower.as_str(), "href" | "src")
+                        && value.as_deref().is_some_and(|v| v.trim_start().to_ascii_lowercase().starts_with("javascript:"));
+                    if lower == "style" || lower.starts_with("on") || script_url {
+                        continue;
+                    }
+                    push_attr(&mut out, attr, value.as_deref());
+                }
+                out.push_str(if rest[..tag.len].ends_with("/>") { " />" } else { ">" });
+            }
+        }
+        rest = &rest[consumed..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// TeX for the `amp-mathml` component, which typesets it on the page.
+pub fn amp_math(tex: &str, display: bool) -> String {
+    let mut formula = String::new();
+    escape_html(&mut formula, tex);
+    if display {
+        format!("<amp-mathml layout=\"container\" data-formula=\"\\[{formula}\\]\"></amp-mathml>")
+    } else {
+        format!("<amp-mathml layout=\"container\" inline data-formula=\"\\({formula}\\)\"></amp-mathml>")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn images_and_embeds_become_amp_components() {
+        let html = amp_safe(
+            "<p><img src=\"/a.webp\" srcset=\"/a.webp 640w\" alt=\"A\" width=\"640\" height=\"480\" loading=\"lazy\" /> \
+             <img src=\"/b.png\" alt=\"B\" /></p>\
+             <div class=\"embed\"><iframe src=\"https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ?start=3\" allowfullscreen></iframe></div>\
+             <iframe src=\"https://maps.example/x\" title=\"Map\">fallback</iframe>",
+        );
+        assert_eq!(
+            html,
+            "<p><amp-img src=\"/a.webp\" srcset=\"/a.webp 640w\" alt=\"A\" width=\"640\" height=\"480\" layout=\"responsive\"></amp-img> \
+             <a href=\"/b.png\">B</a></p>\
+             <div class=\"embed\"><amp-youtube data-videoid=\"dQw4w9WgXcQ\" layout=\"responsive\" width=\"16\" height=\"9\"></amp-youtube></div>\
+             <a href=\"https://maps.example/x\">Map</a>"
+        );
+    }
+
+    #[test]
+    fn forbidden_markup_is_removed() {
+        let html = amp_safe(
+            "<p style=\"color:red\" onclick='x()' class=a>Hi<script>alert(\"</p>\")</script></p>\
+             <form action=\"/x\">Text</form><ul><li><input disabled=\"\" type=\"checkbox\" checked=\"\"/>\nDone</li></ul>\
+             <!-- note --> 1 < 2<br /><a href=\" JavaScript:x()\">y</a>",
+        );
+        assert_eq!(
+            html,
+            "<p class=\"a\">Hi</p>Text<ul><li>\u{2611}\nDone</li></ul><!-- note --> 1 &lt; 2<br /><a>y</a>"
+        );
+    }
+}
@@ -0,0 +1,214 @@
+use super::highlight::escape_html;
+use async_graphql::{Context, SimpleObject};
+use models::assets::Model as Asset;
+use repositories::AssetRepository;
+use sea_orm::DatabaseConnection;
+use services::assets::{resized_variants, StorageDriver};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Prefix of a storage-independent image reference, e.g. in `![alt](asset:{uuid})`
+/// or a post's `cover_image`.
+pub const ASSET_SCHEME: &str = "asset:";
+
+/// The asset id of an `asset:{uuid}` reference.
+pub fn asset_ref(url: &str) -> Option<Uuid> {
+    url.strip_prefix(ASSET_SCHEME)?.parse().ok()
+}
+
+/// Every asset id referenced anywhere in `text`.
+pub fn asset_refs(text: &str) -> Vec<Uuid> {
+    let mut ids: Vec<Uuid> = text
+        .match_indices(ASSET_SCHEME)
+        .filter_map(|(i, _)| text.get(i + ASSET_SCHEME.len()..i + ASSET_SCHEME.len() + 36)?.parse().ok())
+        .collect();
+    ids.sort();
+    ids.dedup();
+    ids
+}
+
+/// An uploaded image as `<img>` attributes: the large variant as `src`, and
+/// every resized variant in `srcset` when the original's size is known.
+#[derive(SimpleObject, Clone, Debug, PartialEq)]
+pub struct ResponsiveImage {
+    pub src: String,
+    pub srcset: Option<String>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    /// Full-size original
+    pub original: String,
+}
+
+impl ResponsiveImage {
+    pub fn new(asset: &Asset, driver: &StorageDriver) -> Self {
+        let url = |variant: &str| driver.url(&format!("{}/{variant}.webp", asset.id));
+        let (width, height) = match (asset.width, asset.height) {
+            (Some(w), Some(h)) if w > 0 && h > 0 => (w as u32, h as u32),
+            _ => {
+                return Self { src: url("large"), srcset: None, width: None, height: None, original: url("original") };
+            }
+        };
+
+        let variants = resized_variants(width, height);
+        let mut srcset = String::new();
+        let mut widths = Vec::new();
+        for (name, w, _) in &variants {
+            // Small originals give several identical variants; list each width once.
+            if !widths.contains(w) {
+                widths.push(*w);
+                let sep = if srcset.is_empty() { "" } else { ", " };
+                let _ = write!(srcset, "{sep}{} {w}w", url(name));
+            }
+        }
+        let (_, w, h) = variants.last().copied().unwrap_or(("large", width, height));
+        Self {
+            src: url("large"),
+            srcset: Some(srcset),
+            width: Some(w as i32),
+            height: Some(h as i32),
+            original: url("original"),
+        }
+    }
+
+    /// A plain image URL, e.g. a cover image hosted elsewhere.
+    pub fn external(url: &str) -> Self {
+        Self { src: url.to_string(), srcset: None, width: None, height: None, original: url.to_string() }
+    }
+
+    pub fn to_html(&self, alt: &str, title: Option<&str>) -> String {
+        let mut html = String::from("<img src=\"");
+        escape_html(&mut html, &self.src);
+        if let Some(srcset) = &self.srcset {
+            html.push_str("\" srcset=\"");
+            escape_html(&mut html, srcset);
+        }
+        html.push_str("\" alt=\"");
+        escape_html(&mut html, alt);
+        html.push('"');
+        if let Some(title) = title.filter(|t| !t.is_empty()) {
+            html.push_str(" title=\"");
+            escape_html(&mut html, title);
+            html.push('"');
+        }
+        if let (Some(w), Some(h)) = (self.width, self.height) {
+            let _ = write!(html, " width=\"{w}\" height=\"{h}\"");
+        }
+        html.push_str(" loading=\"lazy\" />");
+        html
+    }
+}
+
+/// The images that `asset:` references in a document resolve to.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AssetImages {
+    images: HashMap<Uuid, ResponsiveImage>,
+    /// The storage base URL and the rows the images were made from
+    fingerprint: String,
+}
+
+impl AssetImages {
+    pub fn new<'a>(assets: impl IntoIterator<Item = &'a Asset>, driver: &StorageDriver) -> Self {
+        let mut assets: Vec<&Asset> = assets.into_iter().collect();
+        assets.sort_by_key(|a| a.id);
+        let mut fingerprint = driver.url("");
+        for a in &assets {
+            let _ = write!(fingerprint, "\n{} {:?} {:?} {} {}", a.id, a.width, a.height, a.size_bytes, a.created_at);
+        }
+        let images = assets.into_iter().map(|a| (a.id, ResponsiveImage::new(a, driver))).collect();
+        Self { images, fingerprint }
+    }
+
+    /// Changes whenever the resolved images would: a referenced asset is
+    /// added, replaced or removed, or the storage URLs move.
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    /// Look up the assets referenced in `texts`. Only `user_id`'s own assets
+    /// resolve, so a post can't embed someone else's uploads by id.
+    pub async fn load(
+        db: &DatabaseConnection,
+        driver: &StorageDriver,
+        user_id: Uuid,
+        texts: &[&str],
+    ) -> Result<Self, String> {
+        let mut ids: Vec<Uuid> = texts.iter().flat_map(|t| asset_refs(t)).collect();
+        ids.sort();
+        ids.dedup();
+        let assets = AssetRepository::find_many_for_user(db, user_id, &ids).await?;
+        Ok(Self::new(&assets, driver))
+    }
+
+    /// The image behind an `asset:{uuid}` reference, if it is one of ours.
+    pub fn resolve(&self, url: &str) -> Option<&ResponsiveImage> {
+        self.images.get(&asset_ref(url)?)
+    }
+
+    /// `url` with an `asset:` reference replaced by its large variant.
+    pub fn resolve_url(&self, url: &str) -> String {
+        self.resolve(url).map_or_else(|| url.to_string(), |image| image.src.clone())
+    }
+}
+
+/// A post's cover image: `asset:` references resolve against the author's
+/// uploads (and vanish if the asset is gone), other URLs pass through.
+pub async fn resolve_image(ctx: &Context<'_>, user_id: Uuid, url: &str) -> Option<ResponsiveImage> {
+    if asset_ref(url).is_none() {
+        return Some(ResponsiveImage::external(url));
+    }
+    let db = ctx.data_opt::<DatabaseConnection>()?;
+    let driver = ctx.data_opt::<Arc<StorageDriver>>()?;
+    match AssetImages::load(db, driver, user_id, &[url]).await {
+        Ok(images) => images.resolve(url).cloned(),
+        Err(e) => {
+            tracing::warn!("resolving {url}: {e}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::asset;
+    use services::assets::LocalStorageDriver;
+
+    #[test]
+    fn asset_refs_finds_each_id_once() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let text = format!("![x](asset:{a}) [y](asset:{b}) ![z](asset:{a}) asset:nope");
+        let mut expected = vec![a, b];
+        expected.sort();
+        assert_eq!(asset_refs(&text), expected);
+        assert_eq!(asset_ref(&format!("asset:{a}")), Some(a));
+        assert_eq!(asset_ref("/assets/x/large.webp"), None);
+    }
+
+    #[test]
+    fn responsive_image_lists_distinct_variant_widths() {
+        let driver = StorageDriver::Local(LocalStorageDriver::new("/tmp"));
+        let id = Uuid::nil();
+        let image = ResponsiveImage::new(&asset(id, Some((3000, 1500))), &driver);
+        assert_eq!(image.src, format!("/assets/{id}/large.webp"));
+        assert_eq!(
+            image.srcset.as_deref(),
+            Some(
+                format!(
+                    "/assets/{id}/thumbnail.webp 200w, /assets/{id}/small.webp 640w, \
+                     /assets/{id}/medium.webp 1280w, /assets/{id}/large.webp 1920w"
+                )
+                .as_str()
+            )
+        );
+        assert_eq!((image.width, image.height), (Some(1920), Some(960)));
+
+        let small = ResponsiveImage::new(&asset(id, Some((300, 100))), &driver);
+        assert_eq!(small.srcset.unwrap().matches("w,").count(), 1);
+        assert_eq!((small.width, small.height), (Some(300), Some(100)));
+
+        let unknown = ResponsiveImage::new(&asset(id, None), &driver);
+        assert!(unknown.to_html("a \"b\"", None).starts_with(&format!("<img src=\"/assets/{id}/large.webp\" alt=\"a &quot;b&quot;\"")));
+    }
+}
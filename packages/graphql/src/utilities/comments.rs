@@ -0,0 +1,108 @@
+use super::sanitize::sanitize_html;
+use chrono::{Duration, NaiveDateTime};
+use models::sea_orm_active_enums::{CommentPolicy, CommentStatus};
+use models::users;
+use pulldown_cmark::{html, Event, Options, Parser, Tag, TagEnd};
+use repositories::SpamRepository;
+use sea_orm::DatabaseConnection;
+use services::config::SpamConfig;
+use services::spam::{self, Submission};
+use uuid::Uuid;
+
+/// Render a comment's markdown-lite body: paragraphs, emphasis,
+/// strikethrough, code, quotes, lists and links. Raw HTML shows as text,
+/// headings become paragraphs and images become links to the image, and the
+/// result goes through the sanitizer.
+pub fn render_comment(body: &str) -> String {
+    let events = Parser::new_ext(body, Options::ENABLE_STRIKETHROUGH).map(|event| match event {
+        Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+        Event::Start(Tag::Heading { .. }) => Event::Start(Tag::Paragraph),
+        Event::End(TagEnd::Heading(_)) => Event::End(TagEnd::Paragraph),
+        Event::Start(Tag::Image { link_type, dest_url, title, id }) => {
+            Event::Start(Tag::Link { link_type, dest_url, title, id })
+        }
+        Event::End(TagEnd::Image) => Event::End(TagEnd::Link),
+        Event::Rule => Event::HardBreak,
+        event => event,
+    });
+    let mut out = String::new();
+    html::push_html(&mut out, events);
+    sanitize_html(&out)
+}
+
+/// Whether a post first published at `published_at` takes comments at `now`
+/// under its blog's settings.
+pub fn comments_open(
+    policy: CommentPolicy,
+    close_after_days: Option<i32>,
+    published_at: Option<NaiveDateTime>,
+    now: NaiveDateTime,
+) -> bool {
+    if policy == CommentPolicy::Closed {
+        return false;
+    }
+    match (close_after_days, published_at) {
+        (Some(days), Some(published)) => now < published + Duration::days(days.into()),
+        _ => true,
+    }
+}
+
+/// The status a new comment starts in: shown straight away on open blogs,
+/// queued for moderation otherwise.
+pub fn initial_status(policy: CommentPolicy) -> CommentStatus {
+    match policy {
+        CommentPolicy::Open => CommentStatus::Approved,
+        CommentPolicy::Moderated | CommentPolicy::Closed => CommentStatus::Pending,
+    }
+}
+
+/// The status a comment on `user`'s post `post_id` is filed under, and its
+/// spam score: spam if the blog's filter says so, or else as the blog's
+/// policy has it.
+pub async fn screen(
+    db: &DatabaseConnection,
+    user: &users::Model,
+    post_id: Uuid,
+    submission: &Submission<'_>,
+    config: &SpamConfig,
+) -> Result<(CommentStatus, f64), String> {
+    let corpus = SpamRepository::corpus(db, user.id, &spam::tokenize(submission)).await?;
+    let verdict = spam::score(submission, &corpus, config);
+    if verdict.score >= config.threshold {
+        tracing::info!(%post_id, score = verdict.score, signals = ?verdict.signals, "comment filed as spam");
+        return Ok((CommentStatus::Spam, verdict.score));
+    }
+    Ok((initial_status(user.comment_policy), verdict.score))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_markdown_lite_safely() {
+        let html = render_comment(
+            "# Hi\n\nSome *emphasis*, ~~strike~~ and `code`.\n\n<b onclick=\"x()\">bold</b>\n\n\
+             ![cat](https://example.com/cat.png) [js](javascript:alert(1))",
+        );
+        assert_eq!(
+            html,
+            "<p>Hi</p>\n<p>Some <em>emphasis</em>, <del>strike</del> and <code>code</code>.</p>\n\
+             <p>&lt;b onclick=\"x()\"&gt;bold&lt;/b&gt;</p>\n\
+             <p><a href=\"https://example.com/cat.png\" rel=\"nofollow ugc noopener\">cat</a> \
+             <a rel=\"nofollow ugc noopener\">js</a></p>\n"
+        );
+    }
+
+    #[test]
+    fn comments_close_with_policy_or_age() {
+        let published = chrono::DateTime::from_timestamp(0, 0).unwrap().naive_utc();
+        let later = |days| published + Duration::days(days);
+        assert!(comments_open(CommentPolicy::Moderated, None, Some(published), later(1000)));
+        assert!(!comments_open(CommentPolicy::Closed, None, Some(published), later(0)));
+        assert!(comments_open(CommentPolicy::Open, Some(30), Some(published), later(29)));
+        assert!(!comments_open(CommentPolicy::Open, Some(30), Some(published), later(30)));
+        assert_eq!(initial_status(CommentPolicy::Open), CommentStatus::Approved);
+        assert_eq!(initial_status(CommentPolicy::Moderated), CommentStatus::Pending);
+    }
+}
@@ -1,10 +1,11 @@
+use crate::config::SecureCookies;
 use actix_web::cookie::{Cookie, SameSite, time::Duration};
 use async_graphql::Context;
 
-fn build_cookie<'a>(key: &'a str, value: &'a str, duration: Duration) -> Cookie<'a> {
+fn build_cookie<'a>(ctx: &Context<'_>, key: &'a str, value: &'a str, duration: Duration) -> Cookie<'a> {
     Cookie::build(key, value)
         .http_only(true)
-        .secure(std::env::var("SECURE_COOKIES").as_deref() == Ok("true"))
+        .secure(ctx.data::<SecureCookies>().map(|s| s.0).unwrap_or(false))
         .same_site(SameSite::Lax)
         .path("/")
         .max_age(duration)
@@ -12,17 +13,17 @@ fn build_cookie<'a>(key: &'a str, value: &'a str, duration: Duration) -> Cookie<
 }
 
 pub fn set_auth_cookies(ctx: &Context<'_>, access_token: &str, refresh_token: &str) {
-    ctx.insert_http_header("Set-Cookie", build_cookie("access_token", access_token, Duration::hours(1)).to_string());
-    ctx.append_http_header("Set-Cookie", build_cookie("refresh_token", refresh_token, Duration::days(7)).to_string());
+    ctx.insert_http_header("Set-Cookie", build_cookie(ctx, "access_token", access_token, Duration::hours(1)).to_string());
+    ctx.append_http_header("Set-Cookie", build_cookie(ctx, "refresh_token", refresh_token, Duration::days(7)).to_string());
 }
 
 pub fn set_access_cookie(ctx: &Context<'_>, access_token: &str) {
-    ctx.insert_http_header("Set-Cookie", build_cookie("access_token", access_token, Duration::hours(1)).to_string());
+    ctx.insert_http_header("Set-Cookie", build_cookie(ctx, "access_token", access_token, Duration::hours(1)).to_string());
 }
 
 pub fn clear_auth_cookies(ctx: &Context<'_>) {
-    ctx.insert_http_header("Set-Cookie", build_cookie("access_token", "", Duration::seconds(0)).to_string());
-    ctx.append_http_header("Set-Cookie", build_cookie("refresh_token", "", Duration::seconds(0)).to_string());
+    ctx.insert_http_header("Set-Cookie", build_cookie(ctx, "access_token", "", Duration::seconds(0)).to_string());
+    ctx.append_http_header("Set-Cookie", build_cookie(ctx, "refresh_token", "", Duration::seconds(0)).to_string());
 }
 
 #[cfg(test)]
@@ -102,6 +103,23 @@ mod tests {
         assert!(cookie.contains("Max-Age=604800")); // 7 days
     }
 
+    #[tokio::test]
+    async fn test_cookies_not_secure_by_default() {
+        let res = make_schema().execute(Request::new("{ setAuth }")).await;
+        let cookie = find_cookie(&get_set_cookies(&res), "access_token");
+        assert!(!cookie.contains("Secure"));
+    }
+
+    #[tokio::test]
+    async fn test_cookies_secure_when_configured() {
+        let schema = Schema::build(TestQuery, EmptyMutation, EmptySubscription)
+            .data(SecureCookies(true))
+            .finish();
+        let res = schema.execute(Request::new("{ setAuth }")).await;
+        let cookie = find_cookie(&get_set_cookies(&res), "access_token");
+        assert!(cookie.contains("Secure"));
+    }
+
     #[tokio::test]
     async fn test_set_access_cookie_sets_one_cookie() {
         let res = make_schema().execute(Request::new("{ setAccess }")).await;
@@ -0,0 +1,115 @@
+use async_graphql::SimpleObject;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// One heading in a post's table of contents, with the headings nested under it.
+#[derive(SimpleObject, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TocEntry {
+    pub level: i32,
+    pub text: String,
+    /// Fragment id of the heading, without `#`
+    pub anchor: String,
+    pub children: Vec<TocEntry>,
+}
+
+/// Hands out heading ids GitHub-style: lowercase words joined by `-`, with
+/// `-1`, `-2`, … appended to repeats so every id in a document is unique.
+#[derive(Default)]
+pub struct AnchorSet {
+    seen: HashMap<String, usize>,
+}
+
+impl AnchorSet {
+    pub fn anchor(&mut self, text: &str) -> String {
+        let mut base = String::new();
+        for c in text.trim().chars() {
+            if c.is_alphanumeric() || c == '_' {
+                base.extend(c.to_lowercase());
+            } else if (c == ' ' || c == '-') && !base.ends_with('-') {
+                base.push('-');
+            }
+        }
+        let base = match base.trim_matches('-') {
+            "" => "section".to_string(),
+            trimmed => trimmed.to_string(),
+        };
+
+        let mut candidate = base.clone();
+        while self.seen.contains_key(&candidate) {
+            let count = self.seen.entry(base.clone()).or_insert(0);
+            *count += 1;
+            candidate = format!("{base}-{count}");
+        }
+        self.seen.insert(candidate.clone(), 0);
+        candidate
+    }
+
+    /// Reserve an id that came from the source (e.g. raw HTML) so generated
+    /// ones don't collide with it.
+    pub fn reserve(&mut self, id: &str) {
+        self.seen.entry(id.to_string()).or_insert(0);
+    }
+}
+
+/// Nest headings in document order: each entry collects the following
+/// headings of a deeper level until one of the same or a shallower level.
+pub fn nest(flat: Vec<TocEntry>) -> Vec<TocEntry> {
+    let mut roots = Vec::new();
+    let mut stack: Vec<TocEntry> = Vec::new();
+    for entry in flat {
+        while stack.last().is_some_and(|open| open.level >= entry.level) {
+            close(&mut stack, &mut roots);
+        }
+        stack.push(entry);
+    }
+    while !stack.is_empty() {
+        close(&mut stack, &mut roots);
+    }
+    roots
+}
+
+fn close(stack: &mut Vec<TocEntry>, roots: &mut Vec<TocEntry>) {
+    if let Some(done) = stack.pop() {
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(done),
+            None => roots.push(done),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(level: i32, text: &str) -> TocEntry {
+        TocEntry { level, text: text.into(), anchor: text.to_lowercase(), children: vec![] }
+    }
+
+    #[test]
+    fn anchors_are_slugged_and_unique() {
+        let mut anchors = AnchorSet::default();
+        assert_eq!(anchors.anchor("Hello, World!"), "hello-world");
+        assert_eq!(anchors.anchor("Hello World"), "hello-world-1");
+        assert_eq!(anchors.anchor("hello world"), "hello-world-2");
+        assert_eq!(anchors.anchor("Café  au lait"), "café-au-lait");
+        assert_eq!(anchors.anchor("!!!"), "section");
+        anchors.reserve("intro");
+        assert_eq!(anchors.anchor("Intro"), "intro-1");
+    }
+
+    #[test]
+    fn nest_builds_tree_from_levels() {
+        let toc = nest(vec![entry(2, "A"), entry(3, "A1"), entry(4, "A1a"), entry(3, "A2"), entry(2, "B"), entry(1, "C")]);
+        assert_eq!(toc.iter().map(|e| e.text.as_str()).collect::<Vec<_>>(), ["A", "B", "C"]);
+        assert_eq!(toc[0].children.len(), 2);
+        assert_eq!(toc[0].children[0].children[0].text, "A1a");
+        assert!(toc[1].children.is_empty());
+    }
+
+    #[test]
+    fn nest_keeps_skipped_levels_under_nearest_parent() {
+        let toc = nest(vec![entry(3, "deep"), entry(1, "top"), entry(3, "child")]);
+        assert_eq!(toc.len(), 2);
+        assert_eq!(toc[1].children[0].text, "child");
+    }
+}
@@ -0,0 +1,203 @@
+use std::fmt::Write;
+use std::ops::RangeInclusive;
+use std::sync::LazyLock;
+use syntect::highlighting::ThemeSet;
+use syntect::html::{css_for_theme_with_class_style, line_tokens_to_classed_spans, ClassStyle};
+use syntect::parsing::{ParseState, ScopeStack, SyntaxSet};
+use syntect::util::LinesWithEndings;
+
+/// Every token class is prefixed so highlighter styles can't collide with a
+/// frontend's own CSS.
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+static THEMES: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
+
+/// What a fence's info string asks for, e.g. `rust {1,3-5} linenos`.
+#[derive(Debug, Default, PartialEq)]
+pub struct CodeInfo {
+    pub lang: Option<String>,
+    pub highlighted: Vec<RangeInclusive<usize>>,
+    /// `linenos` / `nolinenos`; `None` falls back to the configured default.
+    pub line_numbers: Option<bool>,
+}
+
+impl CodeInfo {
+    fn is_highlighted(&self, line: usize) -> bool {
+        self.highlighted.iter().any(|r| r.contains(&line))
+    }
+}
+
+fn parse_ranges(spec: &str) -> Vec<RangeInclusive<usize>> {
+    spec.split(',')
+        .filter_map(|part| {
+            let part = part.trim();
+            let (start, end) = part.split_once('-').unwrap_or((part, part));
+            Some(start.trim().parse().ok()?..=end.trim().parse().ok()?)
+        })
+        .collect()
+}
+
+pub fn parse_info(info: &str) -> CodeInfo {
+    let (rest, highlighted) = match info.find('{').zip(info.find('}')) {
+        Some((open, close)) if open < close => (
+            format!("{} {}", &info[..open], &info[close + 1..]),
+            parse_ranges(&info[open + 1..close]),
+        ),
+        _ => (info.to_string(), Vec::new()),
+    };
+
+    let mut code = CodeInfo { highlighted, ..Default::default() };
+    for (i, token) in rest.split_whitespace().enumerate() {
+        match token {
+            "linenos" => code.line_numbers = Some(true),
+            "nolinenos" => code.line_numbers = Some(false),
+            // rustdoc-style `rust,ignore` keeps only the language
+            lang if i == 0 => {
+                let lang = lang.split(',').next().unwrap_or_default();
+                let valid = lang.chars().all(|c| c.is_ascii_alphanumeric() || "_+#.-".contains(c));
+                code.lang = (valid && !lang.is_empty()).then(|| lang.to_string());
+            }
+            _ => {}
+        }
+    }
+    code
+}
+
+pub(crate) fn escape_html(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '&' => out.push_str("&amp;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+}
+
+/// Re-open the spans for scopes still active from previous lines, so each
+/// line element is self-contained.
+fn open_scopes(out: &mut String, stack: &ScopeStack) {
+    for scope in stack.as_slice() {
+        out.push_str("<span class=\"");
+        let name = scope.build_string();
+        for (i, atom) in name.split('.').enumerate() {
+            if i > 0 {
+                out.push(' ');
+            }
+            out.push_str("hl-");
+            out.push_str(atom);
+        }
+        out.push_str("\">");
+    }
+}
+
+/// Render a code block as `<pre class="hl-code">` with one `<span class="line">`
+/// per line. Unknown languages are escaped but keep the line structure.
+pub fn highlight_block(code: &str, info: &CodeInfo, default_line_numbers: bool) -> String {
+    let line_numbers = info.line_numbers.unwrap_or(default_line_numbers);
+    let syntax = info.lang.as_deref().and_then(|l| SYNTAXES.find_syntax_by_token(l));
+    let mut state = syntax.map(ParseState::new);
+    let mut stack = ScopeStack::new();
+
+    let mut out = String::from("<pre class=\"hl-code\"><code");
+    if let Some(lang) = &info.lang {
+        let _ = write!(out, " class=\"language-{lang}\"");
+    }
+    out.push('>');
+
+    for (i, line) in LinesWithEndings::from(code).enumerate() {
+        let number = i + 1;
+        let content = line.trim_end_matches(['\n', '\r']);
+        out.push_str(if info.is_highlighted(number) { "<span class=\"line hl\">" } else { "<span class=\"line\">" });
+        if line_numbers {
+            let _ = write!(out, "<span class=\"ln\">{number}</span>");
+        }
+
+        let ops = state.as_mut().and_then(|s| s.parse_line(line, &SYNTAXES).ok());
+        match ops {
+            Some(ops) => {
+                // Drop the newline from the output; ops past it just close spans.
+                let ops: Vec<_> = ops.into_iter().map(|(i, op)| (i.min(content.len()), op)).collect();
+                open_scopes(&mut out, &stack);
+                match line_tokens_to_classed_spans(content, &ops, CLASS_STYLE, &mut stack) {
+                    Ok((html, _)) => out.push_str(&html),
+                    Err(_) => escape_html(&mut out, content),
+                }
+                out.push_str(&"</span>".repeat(stack.len()));
+            }
+            None => {
+                // A grammar error stops highlighting for the rest of the block.
+                state = None;
+                escape_html(&mut out, content);
+            }
+        }
+        out.push_str("</span>\n");
+    }
+    out.push_str("</code></pre>\n");
+    out
+}
+
+/// Stylesheet for one of the bundled themes, plus the rules for line numbers
+/// and highlighted lines.
+pub fn theme_css(name: &str) -> Option<String> {
+    let theme = THEMES.themes.get(name)?;
+    let mut css = css_for_theme_with_class_style(theme, CLASS_STYLE).ok()?;
+    let highlight = theme
+        .settings
+        .line_highlight
+        .map(|c| format!("rgba({}, {}, {}, {:.2})", c.r, c.g, c.b, c.a as f32 / 255.0))
+        .unwrap_or_else(|| "rgba(128, 128, 128, 0.15)".to_string());
+    let _ = write!(
+        css,
+        "\n.hl-code .line.hl {{\n display: inline-block;\n width: 100%;\n background-color: {highlight};\n}}\n\
+         .hl-code .ln {{\n display: inline-block;\n min-width: 2em;\n margin-right: 1em;\n text-align: right;\n opacity: 0.5;\n user-select: none;\n}}\n"
+    );
+    Some(css)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_info_reads_language_ranges_and_flags() {
+        assert_eq!(
+            parse_info("rust {1,3-5} linenos"),
+            CodeInfo { lang: Some("rust".into()), highlighted: vec![1..=1, 3..=5], line_numbers: Some(true) }
+        );
+        assert_eq!(parse_info("py{2}").highlighted, vec![2..=2]);
+        assert_eq!(parse_info("rust,ignore").lang.as_deref(), Some("rust"));
+        assert_eq!(parse_info("\"><script>").lang, None);
+        assert_eq!(parse_info(""), CodeInfo::default());
+    }
+
+    #[test]
+    fn highlight_emits_classes_per_line() {
+        let html = highlight_block("fn main() {\n    let s = \"a\nb\";\n}\n", &parse_info("rust {2}"), false);
+        assert!(html.starts_with("<pre class=\"hl-code\"><code class=\"language-rust\">"), "{html}");
+        assert!(html.contains("hl-storage"), "{html}");
+        assert_eq!(html.matches("<span class=\"line").count(), 4);
+        assert_eq!(html.matches("<span class=\"line hl\">").count(), 1);
+        // every line closes the spans it opened
+        for line in html.lines().filter(|l| l.starts_with("<span")) {
+            assert_eq!(line.matches("<span").count(), line.matches("</span>").count(), "{line}");
+        }
+    }
+
+    #[test]
+    fn highlight_unknown_language_escapes_with_line_numbers() {
+        let html = highlight_block("<b>\n", &parse_info("nonsense linenos"), false);
+        assert!(html.contains("<span class=\"line\"><span class=\"ln\">1</span>&lt;b&gt;</span>"), "{html}");
+    }
+
+    #[test]
+    fn every_configurable_theme_has_css() {
+        for name in services::config::HIGHLIGHT_THEMES {
+            let css = theme_css(name).unwrap_or_else(|| panic!("missing theme {name}"));
+            assert!(css.contains(".hl-code"));
+        }
+        assert!(theme_css("nope").is_none());
+    }
+}
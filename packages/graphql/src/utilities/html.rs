@@ -0,0 +1,98 @@
+//! Just enough of an HTML tokenizer to rewrite markup the renderer produced
+//! or an author wrote inline.
+
+/// Elements dropped together with everything inside them.
+pub(crate) const REMOVED_WITH_CONTENT: &[&str] = &["script", "style", "template", "noscript", "object", "applet"];
+
+/// One parsed start or end tag.
+pub(crate) struct Tag<'a> {
+    pub name: String,
+    pub closing: bool,
+    pub attrs: Vec<(&'a str, Option<String>)>,
+    /// Bytes of the source the tag spans
+    pub len: usize,
+}
+
+impl Tag<'_> {
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_deref().unwrap_or_default())
+    }
+}
+
+/// Parse the tag at the start of `html`, which begins with `<`.
+pub(crate) fn parse_tag(html: &str) -> Option<Tag<'_>> {
+    let bytes = html.as_bytes();
+    let mut i = 1;
+    let closing = bytes.get(i) == Some(&b'/');
+    if closing {
+        i += 1;
+    }
+    let start = i;
+    while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'-') {
+        i += 1;
+    }
+    if i == start || !bytes[start].is_ascii_alphabetic() {
+        return None;
+    }
+    let name = html[start..i].to_ascii_lowercase();
+    let mut attrs = Vec::new();
+    loop {
+        while i < bytes.len() && (bytes[i].is_ascii_whitespace() || bytes[i] == b'/') {
+            i += 1;
+        }
+        match bytes.get(i)? {
+            b'>' => return Some(Tag { name, closing, attrs, len: i + 1 }),
+            _ => {
+                let name_start = i;
+                while i < bytes.len() && !b" \t\n\r/>=".contains(&bytes[i]) {
+                    i += 1;
+                }
+                let attr = &html[name_start..i];
+                if bytes.get(i) != Some(&b'=') {
+                    attrs.push((attr, None));
+                    continue;
+                }
+                i += 1;
+                let value = match bytes.get(i)? {
+                    quote @ (b'"' | b'\'') => {
+                        let end = html[i + 1..].find(*quote as char)? + i + 1;
+                        let value = &html[i + 1..end];
+                        i = end + 1;
+                        value
+                    }
+                    _ => {
+                        let value_start = i;
+                        while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'>' {
+                            i += 1;
+                        }
+                        &html[value_start..i]
+                    }
+                };
+                attrs.push((attr, Some(value.replace('"', "&quot;"))));
+            }
+        }
+    }
+}
+
+/// Offset just past `</name ...>` in `html`, or its end.
+pub(crate) fn skip_element(html: &str, name: &str) -> usize {
+    let lower = html.to_ascii_lowercase();
+    let close = format!("</{name}");
+    match lower.find(&close) {
+        Some(at) => html[at..].find('>').map_or(html.len(), |end| at + end + 1),
+        None => html.len(),
+    }
+}
+
+pub(crate) fn push_attr(out: &mut String, name: &str, value: Option<&str>) {
+    out.push(' ');
+    out.push_str(name);
+    if let Some(value) = value {
+        out.push_str("=\"");
+        out.push_str(value);
+        out.push('"');
+    }
+}
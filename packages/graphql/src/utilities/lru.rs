@@ -0,0 +1,119 @@
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+/// A map that holds at most `capacity` worth of values, evicting the least
+/// recently used entries first. Callers say how much each value weighs.
+pub struct WeightedLru<K, V> {
+    entries: HashMap<K, Entry<V>>,
+    /// Last use of every key, oldest first
+    order: BTreeMap<u64, K>,
+    tick: u64,
+    weight: usize,
+    capacity: usize,
+}
+
+struct Entry<V> {
+    value: V,
+    weight: usize,
+    used: u64,
+}
+
+impl<K: Hash + Eq + Clone, V> WeightedLru<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Self { entries: HashMap::new(), order: BTreeMap::new(), tick: 0, weight: 0, capacity }
+    }
+
+    fn touch(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        let tick = self.touch();
+        let entry = self.entries.get_mut(key)?;
+        let key = self.order.remove(&entry.used).expect("every entry is in the order");
+        self.order.insert(tick, key);
+        entry.used = tick;
+        Some(&entry.value)
+    }
+
+    /// Store `value`, evicting old entries to make room. A value heavier than
+    /// the whole capacity is not stored.
+    pub fn insert(&mut self, key: K, value: V, weight: usize) {
+        self.remove(&key);
+        if weight > self.capacity {
+            return;
+        }
+        while self.weight + weight > self.capacity {
+            let Some((_, oldest)) = self.order.pop_first() else { break };
+            if let Some(entry) = self.entries.remove(&oldest) {
+                self.weight -= entry.weight;
+            }
+        }
+        let used = self.touch();
+        self.order.insert(used, key.clone());
+        self.entries.insert(key, Entry { value, weight, used });
+        self.weight += weight;
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let entry = self.entries.remove(key)?;
+        self.order.remove(&entry.used);
+        self.weight -= entry.weight;
+        Some(entry.value)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+        self.weight = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Total weight of the stored values
+    pub fn weight(&self) -> usize {
+        self.weight
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used_by_weight() {
+        let mut lru = WeightedLru::new(10);
+        lru.insert("a", 1, 4);
+        lru.insert("b", 2, 4);
+        assert_eq!(lru.get(&"a"), Some(&1));
+
+        // "b" is now the oldest and goes to make room.
+        lru.insert("c", 3, 4);
+        assert_eq!(lru.get(&"b"), None);
+        assert_eq!((lru.len(), lru.weight()), (2, 8));
+
+        // Replacing a key swaps its weight rather than adding to it.
+        lru.insert("a", 10, 6);
+        assert_eq!((lru.len(), lru.weight()), (2, 10));
+
+        lru.insert("huge", 0, 11);
+        assert_eq!(lru.get(&"huge"), None);
+        assert_eq!(lru.get(&"a"), Some(&10));
+
+        assert_eq!(lru.remove(&"c"), Some(3));
+        lru.clear();
+        assert!(lru.is_empty());
+        assert_eq!(lru.weight(), 0);
+    }
+}
@@ -1,35 +1,250 @@
-use dashmap::DashMap;
-use pulldown_cmark::{html, Options, Parser};
-use std::sync::Arc;
+use super::assets::AssetImages;
+use super::headings::{nest, AnchorSet, TocEntry};
+use super::highlight::{escape_html, highlight_block, parse_info};
+use super::shortcodes::{self, placeholder_index, Registry, ShortcodeContext};
+use super::amp::{amp_math, amp_safe};
+use async_graphql::{Context, Enum};
+use pulldown_cmark::{html, CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use services::assets::StorageDriver;
+use services::config::MarkdownConfig;
+use super::lru::WeightedLru;
+use repositories::PostRepository;
+use sea_orm::entity::prelude::Json;
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
 use uuid::Uuid;
 
-/// Simple in-memory cache for rendered markdown
+/// What a document is rendered to.
+#[derive(Enum, Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ContentFormat {
+    #[default]
+    Html,
+    /// Text without markup, e.g. for newsletters and search indexes
+    PlainText,
+    /// The markdown as written
+    Markdown,
+    /// HTML that AMP pages accept
+    AmpHtml,
+}
+
+/// Settings that change the rendered HTML. The highlight theme is not one of
+/// them: highlighting only emits classes and the theme lives in CSS.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RenderOptions {
+    pub highlight_code: bool,
+    pub line_numbers: bool,
+    pub heading_anchors: bool,
+    /// `$inline$` and `$$display$$` TeX rendered to MathML
+    pub math: bool,
+    #[serde(default)]
+    pub format: ContentFormat,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            highlight_code: true,
+            line_numbers: false,
+            heading_anchors: false,
+            math: true,
+            format: ContentFormat::Html,
+        }
+    }
+}
+
+impl From<&MarkdownConfig> for RenderOptions {
+    fn from(config: &MarkdownConfig) -> Self {
+        Self {
+            highlight_code: config.highlight_code,
+            line_numbers: config.line_numbers,
+            heading_anchors: config.heading_anchors,
+            math: config.render_math,
+            format: ContentFormat::Html,
+        }
+    }
+}
+
+/// Rendered output plus the table of contents taken from the same event
+/// stream. `html` holds whatever the options' format asks for.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RenderedMarkdown {
+    pub html: String,
+    pub toc: Vec<TocEntry>,
+}
+
+impl RenderedMarkdown {
+    /// Roughly the memory an entry takes, for sizing the cache.
+    fn weight(&self) -> usize {
+        fn toc_weight(entries: &[TocEntry]) -> usize {
+            entries
+                .iter()
+                .map(|e| std::mem::size_of::<TocEntry>() + e.text.len() + e.anchor.len() + toc_weight(&e.children))
+                .sum()
+        }
+        std::mem::size_of::<(CacheKey, Self)>() + self.html.len() + toc_weight(&self.toc)
+    }
+}
+
+/// Bumped whenever rendering changes in a way the options don't capture, so
+/// renders persisted by an older build are not served.
+const RENDERER_VERSION: &str = "1";
+
+/// Content address of a render: a hash of the markdown, the options it is
+/// rendered with, whose `asset:` references it resolves against and what
+/// they resolved to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CacheKey([u8; 32]);
+
+impl CacheKey {
+    pub fn new(markdown: &str, options: &RenderOptions, scope: Uuid, assets: &AssetImages) -> Self {
+        let mut hasher = Sha256::new();
+        for part in [
+            RENDERER_VERSION.as_bytes(),
+            &serde_json::to_vec(options).unwrap_or_default(),
+            scope.as_bytes(),
+            assets.fingerprint().as_bytes(),
+        ] {
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part);
+        }
+        hasher.update(markdown.as_bytes());
+        Self(hasher.finalize().into())
+    }
+
+    pub fn to_hex(&self) -> String {
+        self.0.iter().fold(String::new(), |mut s, b| {
+            let _ = write!(s, "{b:02x}");
+            s
+        })
+    }
+}
+
+/// A render as stored in `posts.rendered_markdown`.
+#[derive(Serialize, Deserialize)]
+struct PersistedRender {
+    key: String,
+    #[serde(flatten)]
+    rendered: RenderedMarkdown,
+}
+
+/// Hit and miss counts of the in-memory cache since startup.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+    pub bytes: usize,
+    pub capacity_bytes: usize,
+}
+
+struct CacheStore {
+    entries: Mutex<WeightedLru<CacheKey, RenderedMarkdown>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// In-memory cache for rendered markdown, bounded by size. Entries are keyed
+/// by content, so an edited post simply misses and its old render ages out.
 #[derive(Clone)]
 pub struct MarkdownCache {
-    cache: Arc<DashMap<Uuid, String>>,
+    store: Arc<CacheStore>,
+    options: RenderOptions,
+    persist: bool,
 }
 
 impl MarkdownCache {
     pub fn new() -> Self {
+        Self::with_capacity(MarkdownConfig::default().cache_mb as usize * 1024 * 1024)
+    }
+
+    pub fn with_capacity(bytes: usize) -> Self {
         Self {
-            cache: Arc::new(DashMap::new()),
+            store: Arc::new(CacheStore {
+                entries: Mutex::new(WeightedLru::new(bytes)),
+                hits: AtomicU64::new(0),
+                misses: AtomicU64::new(0),
+            }),
+            options: RenderOptions::default(),
+            persist: false,
         }
     }
 
-    pub fn get(&self, id: &Uuid) -> Option<String> {
-        self.cache.get(id).map(|entry| entry.clone())
+    pub fn from_config(config: &MarkdownConfig) -> Self {
+        let cache = Self::with_capacity((config.cache_mb as usize).saturating_mul(1024 * 1024));
+        Self { persist: config.persist_rendered, ..cache.with_options(RenderOptions::from(config)) }
+    }
+
+    /// A handle on the same storage that renders with `options`.
+    pub fn with_options(&self, options: RenderOptions) -> Self {
+        Self {
+            store: self.store.clone(),
+            options,
+            persist: self.persist,
+        }
+    }
+
+    pub fn options(&self) -> RenderOptions {
+        self.options
+    }
+
+    /// Whether renders are also written to `posts.rendered_markdown`
+    pub fn persists(&self) -> bool {
+        self.persist
     }
 
-    pub fn set(&self, id: Uuid, html: String) {
-        self.cache.insert(id, html);
+    /// A handle for rendering one post, honouring its own math setting.
+    pub fn for_post(&self, render_math: bool) -> Self {
+        self.with_options(RenderOptions {
+            math: self.options.math && render_math,
+            ..self.options
+        })
     }
 
-    pub fn invalidate(&self, id: &Uuid) {
-        self.cache.remove(id);
+    /// Key of `markdown` rendered with this handle's options, resolving
+    /// `asset:` references against `scope`'s uploads.
+    pub fn key(&self, markdown: &str, scope: Uuid) -> CacheKey {
+        self.key_with(markdown, scope, &AssetImages::default())
+    }
+
+    /// [`MarkdownCache::key`] for a render whose `asset:` references resolve
+    /// to `assets`, so replacing an image or moving storage misses.
+    pub fn key_with(&self, markdown: &str, scope: Uuid, assets: &AssetImages) -> CacheKey {
+        CacheKey::new(markdown, &self.options, scope, assets)
+    }
+
+    fn entries(&self) -> MutexGuard<'_, WeightedLru<CacheKey, RenderedMarkdown>> {
+        self.store.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn get(&self, key: &CacheKey) -> Option<RenderedMarkdown> {
+        let found = self.entries().get(key).cloned();
+        let counter = if found.is_some() { &self.store.hits } else { &self.store.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        found
+    }
+
+    pub fn set(&self, key: CacheKey, rendered: RenderedMarkdown) {
+        let weight = rendered.weight();
+        self.entries().insert(key, rendered, weight);
     }
 
     pub fn clear(&self) {
-        self.cache.clear();
+        self.entries().clear();
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let entries = self.entries();
+        CacheStats {
+            hits: self.store.hits.load(Ordering::Relaxed),
+            misses: self.store.misses.load(Ordering::Relaxed),
+            entries: entries.len(),
+            bytes: entries.weight(),
+            capacity_bytes: entries.capacity(),
+        }
     }
 }
 
@@ -39,32 +254,395 @@ impl Default for MarkdownCache {
     }
 }
 
-/// Render markdown to HTML
+/// Render markdown to HTML with default options
 pub fn render_markdown(markdown: &str) -> String {
-    let mut options = Options::empty();
-    options.insert(Options::ENABLE_STRIKETHROUGH);
-    options.insert(Options::ENABLE_TABLES);
-    options.insert(Options::ENABLE_FOOTNOTES);
-    options.insert(Options::ENABLE_TASKLISTS);
-    options.insert(Options::ENABLE_SMART_PUNCTUATION);
+    render_markdown_with(markdown, &RenderOptions::default())
+}
 
-    let parser = Parser::new_ext(markdown, options);
-    let mut html_output = String::new();
-    html::push_html(&mut html_output, parser);
-    html_output
+/// Render markdown to HTML, highlighting fenced code blocks if enabled
+pub fn render_markdown_with(markdown: &str, options: &RenderOptions) -> String {
+    render_document(markdown, options).html
 }
 
-/// Render markdown with caching
-pub fn render_markdown_cached(id: Uuid, markdown: &str, cache: &MarkdownCache) -> String {
-    // Check cache first
-    if let Some(cached_html) = cache.get(&id) {
-        return cached_html;
+/// Render TeX to MathML, which browsers display without any script. On a
+/// parse error the source is kept, escaped, with its delimiters.
+fn render_math(tex: &str, display: bool) -> String {
+    let mathml = katex::Opts::builder()
+        .display_mode(display)
+        .output_type(katex::OutputType::Mathml)
+        .throw_on_error(true)
+        .build()
+        .ok()
+        .and_then(|opts| katex::render_with_opts(tex, &opts).ok());
+    match mathml {
+        Some(mathml) => mathml,
+        None => {
+            let delimiter = if display { "$$" } else { "$" };
+            let mut escaped = String::new();
+            escape_html(&mut escaped, tex);
+            format!("<code class=\"math-error\">{delimiter}{escaped}{delimiter}</code>")
+        }
+    }
+}
+
+/// TeX in the requested format: MathML, `amp-mathml` or the source itself.
+fn math_event(tex: &str, display: bool, format: ContentFormat) -> Event<'static> {
+    match format {
+        ContentFormat::PlainText => {
+            let delimiter = if display { "$$" } else { "$" };
+            Event::Text(format!("{delimiter}{tex}{delimiter}").into())
+        }
+        ContentFormat::AmpHtml => Event::InlineHtml(amp_math(tex, display).into()),
+        ContentFormat::Html | ContentFormat::Markdown => Event::InlineHtml(render_math(tex, display).into()),
+    }
+}
+
+/// The text of an HTML fragment, with the common entities decoded.
+fn strip_tags(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(at) = rest.find('<') {
+        text.push_str(&rest[..at]);
+        rest = rest[at..].find('>').map_or("", |end| &rest[at + end + 1..]);
+    }
+    text.push_str(rest);
+    [("&lt;", "<"), ("&gt;", ">"), ("&quot;", "\""), ("&#39;", "'"), ("&nbsp;", " "), ("&amp;", "&")]
+        .iter()
+        .fold(text, |text, (entity, c)| text.replace(entity, c))
+}
+
+/// End a block: a blank line after it, or just a newline inside a list.
+fn end_block(out: &mut String, in_list: bool) {
+    out.truncate(out.trim_end_matches([' ', '\t']).len());
+    let wanted = if in_list { "\n" } else { "\n\n" };
+    while !out.is_empty() && !out.ends_with(wanted) {
+        out.push('\n');
+    }
+}
+
+/// Plain text from a document's events: blocks separated by blank lines, list
+/// items bulleted, link targets in parentheses and HTML reduced to its text.
+fn plain_text<'a>(events: impl IntoIterator<Item = Event<'a>>) -> String {
+    let mut out = String::new();
+    // Open lists, with the next number for ordered ones
+    let mut lists: Vec<Option<u64>> = Vec::new();
+    // Targets of the open links, written after their text
+    let mut links = Vec::new();
+    // Whether the last event was block HTML, which ends with the run of it
+    let mut in_html = false;
+    for event in events {
+        if in_html && !matches!(event, Event::Html(_)) {
+            end_block(&mut out, !lists.is_empty());
+        }
+        in_html = matches!(event, Event::Html(_));
+        match event {
+            Event::Text(text) | Event::Code(text) => out.push_str(&text),
+            Event::InlineHtml(html) => out.push_str(&strip_tags(&html)),
+            Event::Html(html) => out.push_str(&strip_tags(&html)),
+            Event::SoftBreak => out.push(' '),
+            Event::HardBreak => out.push('\n'),
+            Event::Rule => {
+                out.push_str("---");
+                end_block(&mut out, false);
+            }
+            Event::TaskListMarker(done) => out.push_str(if done { "[x] " } else { "[ ] " }),
+            Event::FootnoteReference(label) => {
+                let _ = write!(out, "[{label}]");
+            }
+            Event::Start(Tag::FootnoteDefinition(label)) => {
+                let _ = write!(out, "[{label}]: ");
+            }
+            Event::Start(Tag::List(start)) => {
+                if !lists.is_empty() {
+                    end_block(&mut out, true);
+                }
+                lists.push(start);
+            }
+            Event::End(TagEnd::List(_)) => {
+                lists.pop();
+                end_block(&mut out, !lists.is_empty());
+            }
+            Event::Start(Tag::Item) => {
+                end_block(&mut out, true);
+                out.push_str(&"  ".repeat(lists.len().saturating_sub(1)));
+                match lists.last_mut() {
+                    Some(Some(number)) => {
+                        let _ = write!(out, "{number}. ");
+                        *number += 1;
+                    }
+                    _ => out.push_str("- "),
+                }
+            }
+            Event::End(TagEnd::Item) => end_block(&mut out, true),
+            Event::Start(Tag::Link { dest_url, .. }) => links.push(dest_url),
+            Event::End(TagEnd::Link) => {
+                if let Some(url) = links.pop()
+                    && !url.starts_with('#')
+                    && !out.ends_with(url.as_ref())
+                {
+                    let _ = write!(out, " ({url})");
+                }
+            }
+            Event::End(TagEnd::TableCell) => out.push('\t'),
+            Event::End(TagEnd::TableHead | TagEnd::TableRow) => {
+                out.truncate(out.trim_end_matches('\t').len());
+                out.push('\n');
+            }
+            Event::End(
+                TagEnd::Paragraph
+                | TagEnd::Heading(_)
+                | TagEnd::CodeBlock
+                | TagEnd::BlockQuote(_)
+                | TagEnd::FootnoteDefinition
+                | TagEnd::Table,
+            ) => end_block(&mut out, !lists.is_empty()),
+            _ => {}
+        }
+    }
+    let mut text = out.trim().to_string();
+    if !text.is_empty() {
+        text.push('\n');
+    }
+    text
+}
+
+fn heading_level(level: HeadingLevel) -> i32 {
+    match level {
+        HeadingLevel::H1 => 1,
+        HeadingLevel::H2 => 2,
+        HeadingLevel::H3 => 3,
+        HeadingLevel::H4 => 4,
+        HeadingLevel::H5 => 5,
+        HeadingLevel::H6 => 6,
+    }
+}
+
+/// Render markdown to HTML and collect its headings. Every heading gets a
+/// unique `id` (an explicit `{#id}` attribute wins) that the table of
+/// contents links to.
+pub fn render_document(markdown: &str, options: &RenderOptions) -> RenderedMarkdown {
+    render_document_with(markdown, options, &AssetImages::default())
+}
+
+/// [`render_document`], expanding `![alt](asset:{uuid})` into a responsive
+/// `<img>` and pointing `[text](asset:{uuid})` links at the original. References
+/// missing from `assets` are left as they are. Shortcodes are expanded first.
+/// Other formats than HTML share the same pipeline; markdown is returned as is.
+pub fn render_document_with(markdown: &str, options: &RenderOptions, assets: &AssetImages) -> RenderedMarkdown {
+    if options.format == ContentFormat::Markdown {
+        return RenderedMarkdown { html: markdown.to_string(), toc: Vec::new() };
+    }
+    let as_text = options.format == ContentFormat::PlainText;
+    let render_nested = |nested: &str| render_document_with(nested, options, assets).html;
+    let ctx = ShortcodeContext { assets, render: &render_nested };
+    let (markdown, fragments) = shortcodes::expand(markdown, Registry::builtin(), &ctx);
+    let fragment = |html: &str| placeholder_index(html).and_then(|i| fragments.get(i)).cloned();
+
+    let mut parser_options = Options::empty();
+    parser_options.insert(Options::ENABLE_STRIKETHROUGH);
+    parser_options.insert(Options::ENABLE_TABLES);
+    parser_options.insert(Options::ENABLE_FOOTNOTES);
+    parser_options.insert(Options::ENABLE_TASKLISTS);
+    parser_options.insert(Options::ENABLE_SMART_PUNCTUATION);
+    parser_options.insert(Options::ENABLE_HEADING_ATTRIBUTES);
+    if options.math {
+        parser_options.insert(Options::ENABLE_MATH);
+    }
+
+    let source: Vec<Event> = Parser::new_ext(&markdown, parser_options).collect();
+    let mut anchors = AnchorSet::default();
+    for event in &source {
+        if let Event::Start(Tag::Heading { id: Some(id), .. }) = event {
+            anchors.reserve(id);
+        }
+    }
+
+    let mut events = Vec::with_capacity(source.len());
+    let mut toc = Vec::new();
+    // Fenced code being buffered for highlighting: (info string, code)
+    let mut fence: Option<(String, String)> = None;
+    // Index in `events` of the open heading's start tag, and its plain text
+    let mut heading: Option<(usize, String)> = None;
+    // Asset image being replaced: (html to emit once the alt text is known, title, alt)
+    let mut image = None;
+    for event in source {
+        match event {
+            Event::Html(ref html) if fragment(html).is_some() => {
+                events.extend(fragment(html).map(|f| Event::Html(f.into())));
+            }
+            Event::InlineHtml(ref html) if fragment(html).is_some() => {
+                events.extend(fragment(html).map(|f| Event::InlineHtml(f.into())));
+            }
+            Event::Start(Tag::Image { ref dest_url, ref title, .. }) if !as_text && assets.resolve(dest_url).is_some() => {
+                image = assets.resolve(dest_url).map(|i| (i.clone(), title.to_string(), String::new()));
+            }
+            Event::Text(text) | Event::Code(text) if image.is_some() => {
+                if let Some((_, _, alt)) = image.as_mut() {
+                    alt.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::Image) if image.is_some() => {
+                if let Some((img, title, alt)) = image.take() {
+                    events.push(Event::InlineHtml(img.to_html(&alt, Some(&title)).into()));
+                }
+            }
+            _ if image.is_some() => {}
+            Event::Start(Tag::Link { link_type, dest_url, title, id }) => {
+                let dest_url = assets.resolve(&dest_url).map_or(dest_url, |i| i.original.clone().into());
+                events.push(Event::Start(Tag::Link { link_type, dest_url, title, id }));
+            }
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) if options.highlight_code && !as_text => {
+                fence = Some((info.to_string(), String::new()));
+            }
+            Event::Text(text) if fence.is_some() => {
+                if let Some((_, code)) = fence.as_mut() {
+                    code.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) if fence.is_some() => {
+                if let Some((info, code)) = fence.take() {
+                    let block = highlight_block(&code, &parse_info(&info), options.line_numbers);
+                    events.push(Event::Html(block.into()));
+                }
+            }
+            Event::InlineMath(tex) => events.push(math_event(&tex, false, options.format)),
+            Event::DisplayMath(tex) => events.push(math_event(&tex, true, options.format)),
+            Event::Start(Tag::Heading { .. }) => {
+                heading = Some((events.len(), String::new()));
+                events.push(event);
+            }
+            Event::Text(ref text) | Event::Code(ref text) if heading.is_some() => {
+                if let Some((_, plain)) = heading.as_mut() {
+                    plain.push_str(text);
+                }
+                events.push(event);
+            }
+            Event::SoftBreak | Event::HardBreak if heading.is_some() => {
+                if let Some((_, plain)) = heading.as_mut() {
+                    plain.push(' ');
+                }
+                events.push(event);
+            }
+            Event::End(TagEnd::Heading(_)) if heading.is_some() => {
+                let (start, plain) = heading.take().unwrap_or_default();
+                let mut entry = None;
+                if let Event::Start(Tag::Heading { level, id, .. }) = &mut events[start] {
+                    let anchor = match id {
+                        Some(explicit) if explicit.chars().all(|c| c.is_alphanumeric() || "-_:.".contains(c)) => {
+                            explicit.to_string()
+                        }
+                        _ => anchors.anchor(&plain),
+                    };
+                    *id = Some(anchor.clone().into());
+                    entry = Some(TocEntry {
+                        level: heading_level(*level),
+                        text: plain.trim().to_string(),
+                        anchor,
+                        children: Vec::new(),
+                    });
+                }
+                if let Some(entry) = entry {
+                    if options.heading_anchors && !as_text {
+                        let link = format!(
+                            " <a class=\"heading-anchor\" href=\"#{}\" aria-hidden=\"true\">#</a>",
+                            entry.anchor
+                        );
+                        events.push(Event::Html(link.into()));
+                    }
+                    toc.push(entry);
+                }
+                events.push(event);
+            }
+            event => events.push(event),
+        }
+    }
+
+    let output = match options.format {
+        ContentFormat::PlainText => plain_text(events),
+        format => {
+            let mut html_output = String::new();
+            html::push_html(&mut html_output, events.into_iter());
+            if format == ContentFormat::AmpHtml { amp_safe(&html_output) } else { html_output }
+        }
+    };
+    RenderedMarkdown { html: output, toc: nest(toc) }
+}
+
+fn cached_document(key: CacheKey, markdown: &str, cache: &MarkdownCache, assets: &AssetImages) -> RenderedMarkdown {
+    if let Some(rendered) = cache.get(&key) {
+        return rendered;
     }
+    let rendered = render_document_with(markdown, &cache.options(), assets);
+    cache.set(key, rendered.clone());
+    rendered
+}
+
+/// A post's render from `posts.rendered_markdown`, if it was made from the
+/// same content and options.
+fn persisted_render(stored: Option<&Json>, key: &CacheKey) -> Option<RenderedMarkdown> {
+    let stored = PersistedRender::deserialize(stored?).ok()?;
+    (stored.key == key.to_hex()).then_some(stored.rendered)
+}
 
-    // Render and cache
-    let html = render_markdown(markdown);
-    cache.set(id, html.clone());
-    html
+/// Everything [`render_post`] needs from a post.
+pub struct PostSource<'a> {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub markdown: &'a str,
+    pub render_math: bool,
+    pub rendered_markdown: Option<&'a Json>,
+    pub format: ContentFormat,
+}
+
+/// Rendered content of a post: from memory, then from the post's persisted
+/// render, and only then rendered afresh, resolving the author's `asset:`
+/// references.
+pub async fn render_post(ctx: &Context<'_>, post: PostSource<'_>) -> RenderedMarkdown {
+    let default_cache = MarkdownCache::default();
+    let cache = ctx.data::<MarkdownCache>().unwrap_or(&default_cache).for_post(post.render_math);
+    let cache = cache.with_options(RenderOptions { format: post.format, ..cache.options() });
+    // Only HTML is persisted; the column holds one render per post.
+    let persist = cache.persists() && post.format == ContentFormat::Html;
+    let (Some(db), Some(driver)) = (ctx.data_opt::<DatabaseConnection>(), ctx.data_opt::<Arc<StorageDriver>>()) else {
+        return cached_document(cache.key(post.markdown, post.user_id), post.markdown, &cache, &AssetImages::default());
+    };
+    // The assets are looked up before the cache so that a replaced image or
+    // a new storage URL gives a new key. Posts without `asset:` references
+    // don't query.
+    let assets = match AssetImages::load(db, driver, post.user_id, &[post.markdown]).await {
+        Ok(assets) => assets,
+        Err(e) => {
+            // Don't cache a render with unresolved images.
+            tracing::warn!("resolving assets of post {}: {e}", post.id);
+            return render_document(post.markdown, &cache.options());
+        }
+    };
+    let key = cache.key_with(post.markdown, post.user_id, &assets);
+    if let Some(rendered) = cache.get(&key) {
+        return rendered;
+    }
+    if persist
+        && let Some(rendered) = persisted_render(post.rendered_markdown, &key)
+    {
+        cache.set(key, rendered.clone());
+        return rendered;
+    }
+    let rendered = cached_document(key, post.markdown, &cache, &assets);
+    if persist {
+        let stored = PersistedRender { key: key.to_hex(), rendered };
+        if let Ok(json) = serde_json::to_value(&stored)
+            && let Err(e) = PostRepository::set_rendered_markdown(db, post.id, json).await
+        {
+            tracing::warn!("persisting render of post {}: {e}", post.id);
+        }
+        return stored.rendered;
+    }
+    rendered
+}
+
+/// Render markdown with caching
+pub fn render_markdown_cached(markdown: &str, cache: &MarkdownCache) -> String {
+    cached_document(cache.key(markdown, Uuid::nil()), markdown, cache, &AssetImages::default()).html
 }
 
 #[cfg(test)]
@@ -75,23 +653,234 @@ mod tests {
     fn test_markdown_rendering() {
         let markdown = "# Hello\n\nThis is **bold** text.";
         let html = render_markdown(markdown);
-        assert!(html.contains("<h1>Hello</h1>"));
+        assert!(html.contains("<h1 id=\"hello\">Hello</h1>"));
         assert!(html.contains("<strong>bold</strong>"));
     }
 
     #[test]
     fn test_markdown_cache() {
         let cache = MarkdownCache::new();
-        let id = Uuid::new_v4();
         let markdown = "# Test";
 
         // First render should cache
-        let html1 = render_markdown_cached(id, markdown, &cache);
+        let html1 = render_markdown_cached(markdown, &cache);
 
         // Second render should use cache
-        let html2 = render_markdown_cached(id, markdown, &cache);
+        let html2 = render_markdown_cached(markdown, &cache);
 
         assert_eq!(html1, html2);
-        assert!(cache.get(&id).is_some());
+        assert!(cache.get(&cache.key(markdown, Uuid::nil())).is_some());
+        assert_eq!(cache.stats().hits, 2);
+        assert_eq!(cache.stats().misses, 1);
+    }
+
+    #[test]
+    fn test_markdown_cache_is_content_addressed() {
+        let cache = MarkdownCache::new();
+        let (author, other) = (Uuid::new_v4(), Uuid::new_v4());
+        let key = cache.key("# Before", author);
+        assert_eq!(key, cache.key("# Before", author));
+        assert_ne!(key, cache.key("# After", author));
+        // `asset:` references resolve per author, so authors don't share renders.
+        assert_ne!(key, cache.key("# Before", other));
+        assert_eq!(key.to_hex().len(), 64);
+
+        render_markdown_cached("# Before", &cache);
+        assert!(render_markdown_cached("# After", &cache).contains("After"));
+        assert_eq!(cache.stats().entries, 2);
+    }
+
+    #[test]
+    fn test_markdown_cache_evicts_by_size() {
+        let cache = MarkdownCache::with_capacity(2048);
+        let long = "word ".repeat(200);
+        render_markdown_cached(&format!("a {long}"), &cache);
+        render_markdown_cached(&format!("b {long}"), &cache);
+        render_markdown_cached(&format!("c {long}"), &cache);
+
+        let stats = cache.stats();
+        assert!(stats.entries < 3, "{stats:?}");
+        assert!(stats.bytes <= stats.capacity_bytes, "{stats:?}");
+        assert!(cache.get(&cache.key(&format!("c {long}"), Uuid::nil())).is_some());
+        assert!(cache.get(&cache.key(&format!("a {long}"), Uuid::nil())).is_none());
+
+        let disabled = MarkdownCache::with_capacity(0);
+        render_markdown_cached("# Hi", &disabled);
+        assert_eq!(disabled.stats().entries, 0);
+    }
+
+    #[test]
+    fn test_persisted_render_must_match_key() {
+        let cache = MarkdownCache::new();
+        let key = cache.key("# Hi", Uuid::nil());
+        let rendered = render_document("# Hi", &cache.options());
+        let json = serde_json::to_value(PersistedRender { key: key.to_hex(), rendered: rendered.clone() }).unwrap();
+
+        assert_eq!(persisted_render(Some(&json), &key), Some(rendered));
+        assert_eq!(persisted_render(Some(&json), &cache.key("# Edited", Uuid::nil())), None);
+        let numbered = cache.with_options(RenderOptions { line_numbers: true, ..Default::default() });
+        assert_eq!(persisted_render(Some(&json), &numbered.key("# Hi", Uuid::nil())), None);
+        assert_eq!(persisted_render(Some(&serde_json::json!({"html": 1})), &key), None);
+        assert_eq!(persisted_render(None, &key), None);
+    }
+
+    #[test]
+    fn test_fenced_code_is_highlighted() {
+        let html = render_markdown("```rust {1}\nlet x = 1;\n```\n\n    indented");
+        assert!(html.contains("<pre class=\"hl-code\"><code class=\"language-rust\">"), "{html}");
+        assert!(html.contains("<span class=\"line hl\">"), "{html}");
+        assert!(html.contains("<pre><code>indented"), "{html}");
+    }
+
+    #[test]
+    fn test_highlighting_can_be_disabled() {
+        let options = RenderOptions { highlight_code: false, ..Default::default() };
+        let html = render_markdown_with("```rust\nlet x = 1;\n```", &options);
+        assert_eq!(html, "<pre><code class=\"language-rust\">let x = 1;\n</code></pre>\n");
+    }
+
+    #[test]
+    fn test_markdown_cache_is_keyed_by_options() {
+        let cache = MarkdownCache::new();
+        let markdown = "```rust\nlet x = 1;\n```";
+        render_markdown_cached(markdown, &cache);
+
+        let numbered = cache.with_options(RenderOptions { line_numbers: true, ..Default::default() });
+        assert!(numbered.get(&numbered.key(markdown, Uuid::nil())).is_none());
+        let html = render_markdown_cached(markdown, &numbered);
+        assert!(html.contains("<span class=\"ln\">1</span>"), "{html}");
+    }
+
+    #[test]
+    fn test_headings_get_ids_and_toc() {
+        let markdown = "# Intro\n\n## Setup `cargo`\n\n### Linux\n\n## Setup cargo\n\n## Custom {#mine}\n";
+        let rendered = render_document(markdown, &RenderOptions::default());
+        assert!(rendered.html.contains("<h2 id=\"setup-cargo\">Setup <code>cargo</code></h2>"), "{}", rendered.html);
+        assert!(rendered.html.contains("<h2 id=\"setup-cargo-1\">"), "{}", rendered.html);
+        assert!(rendered.html.contains("<h2 id=\"mine\">Custom</h2>"), "{}", rendered.html);
+
+        assert_eq!(rendered.toc.len(), 1);
+        let intro = &rendered.toc[0];
+        assert_eq!((intro.level, intro.anchor.as_str()), (1, "intro"));
+        let children: Vec<_> = intro.children.iter().map(|c| (c.text.as_str(), c.anchor.as_str())).collect();
+        assert_eq!(children, [("Setup cargo", "setup-cargo"), ("Setup cargo", "setup-cargo-1"), ("Custom", "mine")]);
+        assert_eq!(intro.children[0].children[0].anchor, "linux");
+    }
+
+    #[test]
+    fn test_heading_anchor_links_are_optional() {
+        let options = RenderOptions { heading_anchors: true, ..Default::default() };
+        let html = render_markdown_with("## Hi", &options);
+        assert_eq!(html, "<h2 id=\"hi\">Hi <a class=\"heading-anchor\" href=\"#hi\" aria-hidden=\"true\">#</a></h2>\n");
+    }
+
+    #[test]
+    fn test_math_renders_to_mathml() {
+        let html = render_markdown("Euler: $e^{i\\pi} = -1$\n\n$$\\sum_{k=1}^n k$$");
+        assert!(html.contains("<math"), "{html}");
+        assert!(html.contains("display=\"block\""), "{html}");
+        assert!(!html.contains('$'), "{html}");
+    }
+
+    #[test]
+    fn test_math_errors_keep_escaped_source() {
+        let html = render_markdown("$x < \\nope$");
+        assert!(html.contains("<code class=\"math-error\">$x &lt; \\nope$</code>"), "{html}");
+    }
+
+    #[test]
+    fn test_math_can_be_disabled_per_post() {
+        let cache = MarkdownCache::new().for_post(false);
+        let html = render_markdown_cached("costs $5 and $6", &cache);
+        assert_eq!(html, "<p>costs $5 and $6</p>\n");
+    }
+
+    #[test]
+    fn test_asset_references_become_responsive_images() {
+        use super::super::assets::ResponsiveImage;
+        use crate::test_helpers::asset;
+        use services::assets::LocalStorageDriver;
+
+        let id = Uuid::new_v4();
+        let asset = asset(id, Some((800, 600)));
+        let driver = StorageDriver::Local(LocalStorageDriver::new("/tmp"));
+        let assets = AssetImages::new([&asset], &driver);
+        let markdown = format!("![A *cat*](asset:{id} \"Tom\") [full](asset:{id}) ![gone](asset:{})", Uuid::nil());
+
+        let html = render_document_with(&markdown, &RenderOptions::default(), &assets).html;
+        let img = ResponsiveImage::new(&asset, &driver);
+        assert!(html.contains(&img.to_html("A cat", Some("Tom"))), "{html}");
+        assert!(html.contains("medium.webp 800w\" alt=\"A cat\" title=\"Tom\" width=\"800\" height=\"600\""), "{html}");
+        assert!(html.contains(&format!("<a href=\"/assets/{id}/original.webp\">full</a>")), "{html}");
+        assert!(html.contains(&format!("<img src=\"asset:{}\" alt=\"gone\" />", Uuid::nil())), "{html}");
+    }
+
+    #[test]
+    fn test_cache_key_follows_the_resolved_assets() {
+        use crate::test_helpers::asset;
+        use services::assets::LocalStorageDriver;
+
+        let id = Uuid::new_v4();
+        let asset = asset(id, Some((800, 600)));
+        let driver = StorageDriver::Local(LocalStorageDriver::new("/tmp"));
+        let cache = MarkdownCache::new();
+        let markdown = format!("![cat](asset:{id})");
+        let key = cache.key_with(&markdown, Uuid::nil(), &AssetImages::new([&asset], &driver));
+        assert_eq!(key, cache.key_with(&markdown, Uuid::nil(), &AssetImages::new([&asset], &driver)));
+
+        // A reprocessed or replaced image renders differently
+        let replaced = models::assets::Model { width: Some(1600), height: Some(1200), ..asset.clone() };
+        assert_ne!(key, cache.key_with(&markdown, Uuid::nil(), &AssetImages::new([&replaced], &driver)));
+        // and so does a deleted one
+        assert_ne!(key, cache.key_with(&markdown, Uuid::nil(), &AssetImages::new([], &driver)));
+    }
+
+    #[test]
+    fn test_shortcodes_expand_in_place() {
+        let markdown = "Intro {{< youtube dQw4w9WgXcQ >}}\n\n{{< callout tip >}}\n**Bold** and `{{< code >}}`\n\n```\n{{< nope >}}\n```\n{{< /callout >}}\n\nAfter";
+        let html = render_markdown(markdown);
+        assert!(html.starts_with("<p>Intro <div class=\"embed embed-youtube\">"), "{html}");
+        assert!(
+            html.contains("<aside class=\"callout callout-tip\"><p><strong>Bold</strong> and <code>{{&lt; code &gt;}}</code></p>"),
+            "{html}"
+        );
+        assert!(html.contains("<span class=\"line\">{{&lt; nope &gt;}}</span>"), "{html}");
+        assert!(html.ends_with("</aside>\n<p>After</p>\n"), "{html}");
+        assert!(!html.contains("shortcode-error"), "{html}");
+    }
+
+    #[test]
+    fn test_plain_text_format() {
+        let options = RenderOptions { format: ContentFormat::PlainText, heading_anchors: true, ..Default::default() };
+        let markdown = "# Title\n\nSee [the docs](https://x.example) & $x^2$.\n\n\
+                        1. one\n2. two\n   - nested\n\n```rust\nlet x = 1;\n```\n\n\
+                        {{< callout note >}}\nCareful <b>now</b>\n{{< /callout >}}\n\nEnd";
+        assert_eq!(
+            render_markdown_with(markdown, &options),
+            "Title\n\nSee the docs (https://x.example) & $x^2$.\n\n1. one\n2. two\n  - nested\n\n\
+             let x = 1;\n\nCareful now\n\nEnd\n"
+        );
+    }
+
+    #[test]
+    fn test_amp_format() {
+        let options = RenderOptions { format: ContentFormat::AmpHtml, ..Default::default() };
+        let html = render_markdown_with("![cat](/cat.png) $x$ {{< youtube dQw4w9WgXcQ >}}", &options);
+        assert!(html.starts_with("<p><a href=\"/cat.png\">cat</a> <amp-mathml"), "{html}");
+        assert!(html.contains("<amp-youtube data-videoid=\"dQw4w9WgXcQ\""), "{html}");
+        assert!(!html.contains("<iframe") && !html.contains("<img") && !html.contains("<math"), "{html}");
+    }
+
+    #[test]
+    fn test_formats_are_cached_separately() {
+        let cache = MarkdownCache::new();
+        let text = cache.with_options(RenderOptions { format: ContentFormat::PlainText, ..Default::default() });
+        assert_ne!(cache.key("**hi**", Uuid::nil()), text.key("**hi**", Uuid::nil()));
+        assert_eq!(render_markdown_cached("**hi**", &cache), "<p><strong>hi</strong></p>\n");
+        assert_eq!(render_markdown_cached("**hi**", &text), "hi\n");
+        assert_eq!(cache.stats().entries, 2);
+
+        let markdown = RenderOptions { format: ContentFormat::Markdown, ..Default::default() };
+        assert_eq!(render_markdown_with("**hi**", &markdown), "**hi**");
     }
 }
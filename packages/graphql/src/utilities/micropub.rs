@@ -0,0 +1,560 @@
+use crate::utilities::publishing::Publishing;
+use crate::utilities::text::PlainText;
+use models::{posts, users};
+use repositories::{PostRepository, UserRepository};
+use sea_orm::DatabaseConnection;
+use sea_orm::entity::prelude::Uuid;
+use serde_json::{json, Map, Value};
+use services::api_keys::{self, Scope};
+use services::config::SiteConfig;
+use url::Url;
+
+/// Longest title made from the start of a note, which has no `name`.
+const NOTE_TITLE_LENGTH: usize = 60;
+
+/// Why a Micropub request failed, as the spec names it.
+#[derive(Debug, PartialEq)]
+pub enum MicropubError {
+    /// No token, or one that isn't a key
+    Unauthorized,
+    /// The key's owner can't post, e.g. before verifying their email
+    Forbidden(String),
+    /// The key wasn't given this scope
+    InsufficientScope(Scope),
+    /// The request is wrong; the message says how
+    InvalidRequest(String),
+    Database(String),
+}
+
+impl MicropubError {
+    pub fn status(&self) -> u16 {
+        match self {
+            MicropubError::Unauthorized => 401,
+            MicropubError::Forbidden(_) | MicropubError::InsufficientScope(_) => 403,
+            MicropubError::InvalidRequest(_) => 400,
+            MicropubError::Database(_) => 500,
+        }
+    }
+
+    /// The JSON body clients are told about it in.
+    pub fn body(&self) -> Value {
+        match self {
+            MicropubError::Unauthorized => json!({
+                "error": "unauthorized",
+                "error_description": "missing or unknown access token",
+            }),
+            MicropubError::Forbidden(d) => json!({ "error": "forbidden", "error_description": d }),
+            MicropubError::InsufficientScope(scope) => json!({
+                "error": "insufficient_scope",
+                "scope": scope.as_str(),
+                "error_description": format!("the key needs the {} scope", scope.as_str()),
+            }),
+            MicropubError::InvalidRequest(d) => json!({ "error": "invalid_request", "error_description": d }),
+            MicropubError::Database(_) => json!({ "error": "server_error", "error_description": "database error" }),
+        }
+    }
+}
+
+fn invalid(message: impl Into<String>) -> MicropubError {
+    MicropubError::InvalidRequest(message.into())
+}
+
+/// The owner of the API key `token`, if the key has `scope`. Queries pass
+/// `None`: any key may read its owner's posts.
+pub async fn authorize(
+    db: &DatabaseConnection,
+    token: Option<&str>,
+    scope: Option<Scope>,
+) -> Result<users::Model, MicropubError> {
+    let token = token.ok_or(MicropubError::Unauthorized)?;
+    let (user_id, scopes) = api_keys::validate_scoped(db, token).await.ok_or(MicropubError::Unauthorized)?;
+    if let Some(scope) = scope
+        && !scopes.contains(&scope)
+    {
+        return Err(MicropubError::InsufficientScope(scope));
+    }
+    let user = UserRepository::find_by_id(db, user_id)
+        .await
+        .map_err(|e| MicropubError::Database(format!("Database error: {e}")))?
+        .ok_or(MicropubError::Unauthorized)?;
+    if scope.is_some() && user.email_verified_at.is_none() {
+        return Err(MicropubError::Forbidden("Email not verified".to_string()));
+    }
+    Ok(user)
+}
+
+/// What a POST to the endpoint asks for.
+#[derive(Debug, PartialEq)]
+pub enum Action {
+    Create(Map<String, Value>),
+    Update {
+        url: String,
+        replace: Map<String, Value>,
+        add: Map<String, Value>,
+        /// Property names, or values to take out of properties by name
+        delete: Value,
+    },
+    Delete {
+        url: String,
+    },
+}
+
+impl Action {
+    /// The scope a key needs to do this.
+    pub fn scope(&self) -> Scope {
+        match self {
+            Action::Create(_) => Scope::Create,
+            Action::Update { .. } => Scope::Update,
+            Action::Delete { .. } => Scope::Delete,
+        }
+    }
+}
+
+/// A form-encoded request in the JSON syntax: `h` becomes the type, and
+/// every other field a property, `[]` suffixes gathering its values.
+pub fn form_to_json(pairs: &[(String, String)]) -> Value {
+    let mut request = Map::new();
+    let mut properties = Map::new();
+    for (key, value) in pairs {
+        match key.as_str() {
+            "h" => {
+                request.insert("type".to_string(), json!([format!("h-{value}")]));
+            }
+            "action" | "url" => {
+                request.insert(key.clone(), json!(value));
+            }
+            "access_token" => {}
+            _ => {
+                let name = key.strip_suffix("[]").unwrap_or(key);
+                let values = properties.entry(name).or_insert_with(|| json!([]));
+                if let Some(values) = values.as_array_mut() {
+                    values.push(json!(value));
+                }
+            }
+        }
+    }
+    if !properties.is_empty() {
+        request.insert("properties".to_string(), Value::Object(properties));
+    }
+    Value::Object(request)
+}
+
+/// `name` in `request` as an object whose members are all arrays, as
+/// properties are. Missing is empty.
+fn property_map(request: &Value, name: &str) -> Result<Map<String, Value>, MicropubError> {
+    let map = match request.get(name) {
+        None => return Ok(Map::new()),
+        Some(Value::Object(map)) => map.clone(),
+        Some(_) => return Err(invalid(format!("{name} must be an object"))),
+    };
+    if let Some((key, _)) = map.iter().find(|(_, v)| !v.is_array()) {
+        return Err(invalid(format!("{name}.{key} must be an array")));
+    }
+    Ok(map)
+}
+
+/// Read a request in the JSON syntax, or a form made into it.
+pub fn parse_action(request: &Value) -> Result<Action, MicropubError> {
+    let url = || {
+        request.get("url").and_then(Value::as_str).map(str::to_string).ok_or_else(|| invalid("url is missing"))
+    };
+    match request.get("action").and_then(Value::as_str) {
+        None | Some("create") => {
+            let kind = request.get("type").and_then(|t| t.get(0)).and_then(Value::as_str);
+            if kind.is_some_and(|k| k != "h-entry") {
+                return Err(invalid("only h-entry posts can be created"));
+            }
+            Ok(Action::Create(property_map(request, "properties")?))
+        }
+        Some("update") => {
+            let delete = request.get("delete").cloned().unwrap_or(json!([]));
+            if !(delete.is_array() || delete.is_object()) {
+                return Err(invalid("delete must be an array or an object"));
+            }
+            let replace = property_map(request, "replace")?;
+            let add = property_map(request, "add")?;
+            if replace.is_empty() && add.is_empty() && delete.as_array().is_some_and(|d| d.is_empty()) {
+                return Err(invalid("an update needs replace, add or delete, in a JSON body"));
+            }
+            Ok(Action::Update { url: url()?, replace, add, delete })
+        }
+        Some("delete") => Ok(Action::Delete { url: url()? }),
+        Some("undelete") => Err(invalid("deleted posts are gone, so undelete isn't supported")),
+        Some(other) => Err(invalid(format!("unknown action {other:?}"))),
+    }
+}
+
+/// The post fields that Micropub properties map onto.
+#[derive(Debug, PartialEq)]
+struct Entry {
+    title: String,
+    content: String,
+    description: Option<String>,
+    slug: Option<String>,
+    cover_image: Option<String>,
+    is_published: bool,
+}
+
+/// A property value as text: a string, or an object's `html` or `value`.
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Object(o) => o.get("html").or_else(|| o.get("value")).and_then(Value::as_str).map(str::to_string),
+        _ => None,
+    }
+}
+
+fn first_text(properties: &Map<String, Value>, name: &str) -> Option<String> {
+    properties.get(name).and_then(|v| v.get(0)).and_then(text).filter(|s| !s.trim().is_empty())
+}
+
+/// Map an h-entry's properties onto a post. The first `photo` is the cover
+/// image and the others are added to the end of the content; a note without
+/// a `name` is titled with the start of its text.
+fn entry(properties: &Map<String, Value>) -> Result<Entry, MicropubError> {
+    let mut content = first_text(properties, "content").unwrap_or_default();
+    let mut photos = Vec::new();
+    for photo in properties.get("photo").and_then(Value::as_array).into_iter().flatten() {
+        let url = text(photo).filter(|u| Url::parse(u).is_ok()).ok_or_else(|| invalid("photo must be a URL"))?;
+        let alt = photo.get("alt").and_then(Value::as_str).unwrap_or_default().to_string();
+        photos.push((url, alt));
+    }
+    let mut photos = photos.into_iter();
+    let cover_image = photos.next().map(|(url, _)| url);
+    for (url, alt) in photos {
+        content = format!("{}\n\n![{alt}]({url})", content.trim_end()).trim_start().to_string();
+    }
+
+    let title = match first_text(properties, "name") {
+        Some(name) => name.trim().to_string(),
+        None if content.trim().is_empty() && cover_image.is_none() => {
+            return Err(invalid("a post needs a name, content or a photo"));
+        }
+        None => Some(PlainText::new(&content).excerpt(NOTE_TITLE_LENGTH))
+            .filter(|t| !t.is_empty())
+            .unwrap_or_else(|| if cover_image.is_some() { "Photo" } else { "Note" }.to_string()),
+    };
+    let is_published = match first_text(properties, "post-status").as_deref() {
+        None | Some("published") => true,
+        Some("draft") => false,
+        Some(other) => return Err(invalid(format!("unknown post-status {other:?}"))),
+    };
+    let entry = Entry {
+        title,
+        content,
+        description: first_text(properties, "summary"),
+        slug: first_text(properties, "mp-slug"),
+        cover_image,
+        is_published,
+    };
+
+    if entry.title.len() > 500 {
+        return Err(invalid("name must be 500 characters or fewer"));
+    }
+    if entry.content.len() > 200_000 {
+        return Err(invalid("content must be 200000 characters or fewer"));
+    }
+    if entry.description.as_ref().is_some_and(|d| d.len() > 500) {
+        return Err(invalid("summary must be 500 characters or fewer"));
+    }
+    if entry.slug.as_ref().is_some_and(|s| s.len() > 200) {
+        return Err(invalid("mp-slug must be 200 characters or fewer"));
+    }
+    if entry.cover_image.as_ref().is_some_and(|c| c.len() > 2000) {
+        return Err(invalid("photo must be 2000 characters or fewer"));
+    }
+    Ok(entry)
+}
+
+/// A post as h-entry properties, as `q=source` shows it.
+fn properties(post: &posts::Model, site: &SiteConfig) -> Map<String, Value> {
+    let mut properties = Map::new();
+    properties.insert("name".to_string(), json!([post.title]));
+    properties.insert("content".to_string(), json!([post.markdown_content.clone().unwrap_or_default()]));
+    if let Some(description) = &post.description {
+        properties.insert("summary".to_string(), json!([description]));
+    }
+    if let Some(cover_image) = &post.cover_image {
+        properties.insert("photo".to_string(), json!([cover_image]));
+    }
+    let status = if post.is_published { "published" } else { "draft" };
+    properties.insert("post-status".to_string(), json!([status]));
+    if let Some(at) = post.first_published_at {
+        properties.insert("published".to_string(), json!([at.and_utc().to_rfc3339()]));
+    }
+    properties.insert("updated".to_string(), json!([post.updated_at.and_utc().to_rfc3339()]));
+    properties.insert("url".to_string(), json!([site.post_url(&post.id.to_string(), post.slug.as_deref())]));
+    properties
+}
+
+/// `user`'s post at `url`, a page of the blog.
+async fn find(
+    db: &DatabaseConnection,
+    site: &SiteConfig,
+    user: &users::Model,
+    url: &str,
+) -> Result<posts::Model, MicropubError> {
+    let not_found = || invalid(format!("{url} isn't one of your posts"));
+    let key = site.post_key(url).ok_or_else(not_found)?;
+    let post = PostRepository::find_by_slug(db, user.id, &key).await.map_err(MicropubError::Database)?;
+    let post = match (post, key.parse::<Uuid>()) {
+        (Some(post), _) => Some(post),
+        (None, Ok(id)) => PostRepository::get_post(db, user.id, id).await.map_err(MicropubError::Database)?,
+        (None, Err(_)) => None,
+    };
+    post.ok_or_else(not_found)
+}
+
+/// Do what `action` asks of `user`'s posts. Returns the post created or
+/// changed; `None` for a deletion.
+pub async fn perform(
+    publishing: &Publishing<'_>,
+    user: &users::Model,
+    action: Action,
+) -> Result<Option<posts::Model>, MicropubError> {
+    let db = publishing.db;
+    match action {
+        Action::Create(properties) => {
+            let entry = entry(&properties)?;
+            let post = PostRepository::create_post(
+                db,
+                user.id,
+                entry.title,
+                entry.content,
+                entry.is_published,
+                entry.description,
+                entry.slug,
+                entry.cover_image,
+            )
+            .await
+            .map_err(MicropubError::Database)?;
+            publishing.saved(&post, None).await;
+            Ok(Some(post))
+        }
+        Action::Update { url, replace, add, delete } => {
+            let post = find(db, &publishing.site, user, &url).await?;
+            let mut current = properties(&post, &publishing.site);
+            current.extend(replace);
+            for (name, values) in add {
+                let existing = current.entry(name).or_insert_with(|| json!([]));
+                if let (Some(existing), Value::Array(values)) = (existing.as_array_mut(), values) {
+                    existing.extend(values);
+                }
+            }
+            match delete {
+                Value::Object(values) => {
+                    for (name, values) in values {
+                        let Some(existing) = current.get_mut(&name).and_then(Value::as_array_mut) else { continue };
+                        existing.retain(|v| !values.as_array().is_some_and(|vs| vs.contains(v)));
+                    }
+                }
+                names => {
+                    for name in names.as_array().into_iter().flatten().filter_map(Value::as_str) {
+                        current.remove(name);
+                    }
+                }
+            }
+            let entry = entry(&current)?;
+            let description = (entry.description != post.description).then(|| entry.description.unwrap_or_default());
+            let updated = PostRepository::update_post(
+                db,
+                user.id,
+                post.id,
+                entry.title,
+                entry.content,
+                Some(entry.is_published),
+                description,
+                entry.slug,
+                entry.cover_image,
+            )
+            .await
+            .map_err(MicropubError::Database)?;
+            publishing.saved(&updated, Some(&post)).await;
+            Ok(Some(updated))
+        }
+        Action::Delete { url } => {
+            let post = find(db, &publishing.site, user, &url).await?;
+            PostRepository::delete_post(db, user.id, post.id).await.map_err(MicropubError::Database)?;
+            publishing.deleted(&post).await;
+            Ok(None)
+        }
+    }
+}
+
+/// Answer a `q=` query: the endpoint's `config`, the `source` of one of
+/// `user`'s posts at `url`, limited to `wanted` properties if any are
+/// named, or the (empty) `syndicate-to` list.
+pub async fn query(
+    db: &DatabaseConnection,
+    site: &SiteConfig,
+    user: &users::Model,
+    q: &str,
+    url: Option<&str>,
+    wanted: &[String],
+) -> Result<Value, MicropubError> {
+    match q {
+        "config" => Ok(json!({
+            "media-endpoint": format!("{}/micropub/media", site.api_url),
+            "syndicate-to": [],
+            "post-types": [
+                { "type": "note", "name": "Note" },
+                { "type": "article", "name": "Article" },
+                { "type": "photo", "name": "Photo" },
+            ],
+            "q": ["config", "source", "syndicate-to"],
+        })),
+        "syndicate-to" => Ok(json!({ "syndicate-to": [] })),
+        "source" => {
+            let url = url.ok_or_else(|| invalid("url is missing"))?;
+            let post = find(db, site, user, url).await?;
+            let mut properties = properties(&post, site);
+            if wanted.is_empty() {
+                return Ok(json!({ "type": ["h-entry"], "properties": properties }));
+            }
+            properties.retain(|name, _| wanted.contains(name));
+            Ok(json!({ "properties": properties }))
+        }
+        other => Err(invalid(format!("unknown query {other:?}"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::*;
+    use sea_orm::{ActiveModelTrait, ActiveValue, IntoActiveModel};
+
+    fn form(pairs: &[(&str, &str)]) -> Value {
+        let pairs: Vec<(String, String)> = pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        form_to_json(&pairs)
+    }
+
+    #[test]
+    fn forms_read_as_json_requests() {
+        let request = form(&[
+            ("h", "entry"),
+            ("content", "Hello"),
+            ("category[]", "a"),
+            ("category[]", "b"),
+            ("access_token", "slq_x"),
+        ]);
+        assert_eq!(
+            request,
+            json!({ "type": ["h-entry"], "properties": { "content": ["Hello"], "category": ["a", "b"] } })
+        );
+        assert_eq!(
+            parse_action(&form(&[("action", "delete"), ("url", "https://blog.example/posts/a")])),
+            Ok(Action::Delete { url: "https://blog.example/posts/a".to_string() })
+        );
+        assert!(matches!(parse_action(&form(&[("h", "event")])), Err(MicropubError::InvalidRequest(_))));
+        assert!(matches!(
+            parse_action(&form(&[("action", "update"), ("url", "https://blog.example/posts/a")])),
+            Err(MicropubError::InvalidRequest(m)) if m.contains("JSON")
+        ));
+    }
+
+    #[test]
+    fn properties_map_onto_posts() {
+        let Action::Create(properties) = parse_action(&json!({
+            "type": ["h-entry"],
+            "properties": {
+                "content": [{ "html": "Lunch by the *sea*, with far too many gulls about to be pleasant at all" }],
+                "photo": ["https://img.example/a.jpg", { "value": "https://img.example/b.jpg", "alt": "Gulls" }],
+                "post-status": ["draft"],
+                "mp-slug": ["lunch"],
+            },
+        }))
+        .unwrap() else {
+            panic!("not a create");
+        };
+        assert_eq!(
+            entry(&properties).unwrap(),
+            Entry {
+                title: "Lunch by the sea, with far too many gulls about to be…".to_string(),
+                content: "Lunch by the *sea*, with far too many gulls about to be pleasant at all\n\n![Gulls](https://img.example/b.jpg)".to_string(),
+                description: None,
+                slug: Some("lunch".to_string()),
+                cover_image: Some("https://img.example/a.jpg".to_string()),
+                is_published: false,
+            }
+        );
+        let mut named = properties.clone();
+        named.insert("name".to_string(), json!(["Lunch"]));
+        named.insert("post-status".to_string(), json!(["published"]));
+        let named = entry(&named).unwrap();
+        assert_eq!((named.title.as_str(), named.is_published), ("Lunch", true));
+        assert!(entry(&Map::new()).is_err());
+        let mut bad = Map::new();
+        bad.insert("photo".to_string(), json!(["not a url"]));
+        assert_eq!(entry(&bad), Err(invalid("photo must be a URL")));
+    }
+
+    #[tokio::test]
+    async fn test_micropub_creates_updates_and_deletes_posts() {
+        let db = setup_test_db().await;
+        let email = generate_unique_email("micropub");
+        let user = create_test_user_with_password(&db, &email, &valid_password()).await;
+
+        let (raw_key, key_hash) = api_keys::generate();
+        api_keys::create_scoped(&db, user.id, "phone".to_string(), key_hash, &[Scope::Create, Scope::Update])
+            .await
+            .unwrap();
+        assert_eq!(authorize(&db, None, None).await.unwrap_err(), MicropubError::Unauthorized);
+        assert_eq!(authorize(&db, Some("slq_nope"), None).await.unwrap_err(), MicropubError::Unauthorized);
+        assert_eq!(
+            authorize(&db, Some(&raw_key), Some(Scope::Delete)).await.unwrap_err(),
+            MicropubError::InsufficientScope(Scope::Delete)
+        );
+        assert!(matches!(
+            authorize(&db, Some(&raw_key), Some(Scope::Create)).await,
+            Err(MicropubError::Forbidden(_))
+        ));
+        let mut am = user.into_active_model();
+        am.email_verified_at = ActiveValue::Set(Some(chrono::Utc::now().naive_utc()));
+        am.update(&db).await.unwrap();
+        let user = authorize(&db, Some(&raw_key), Some(Scope::Create)).await.unwrap();
+
+        let site = SiteConfig::default();
+        let publishing = Publishing { db: &db, driver: None, site: site.clone(), feed_pings: None };
+        let create = form(&[("h", "entry"), ("name", "From my phone"), ("content", "Hi"), ("mp-slug", "from-phone")]);
+        let post = perform(&publishing, &user, parse_action(&create).unwrap()).await.unwrap().unwrap();
+        assert_eq!(post.title, "From my phone");
+        assert_eq!(post.markdown_content.as_deref(), Some("Hi"));
+        assert!(post.is_published);
+        let url = site.post_url(&post.id.to_string(), post.slug.as_deref());
+        assert!(url.ends_with("/posts/from-phone"), "{url}");
+
+        let update = json!({
+            "action": "update",
+            "url": url,
+            "replace": { "content": ["Hello again"], "post-status": ["draft"] },
+            "add": { "summary": ["A greeting"] },
+        });
+        let updated = perform(&publishing, &user, parse_action(&update).unwrap()).await.unwrap().unwrap();
+        assert_eq!(updated.markdown_content.as_deref(), Some("Hello again"));
+        assert_eq!(updated.description.as_deref(), Some("A greeting"));
+        assert!(!updated.is_published);
+
+        let source = query(&db, &site, &user, "source", Some(&url), &[]).await.unwrap();
+        assert_eq!(source["type"], json!(["h-entry"]));
+        assert_eq!(source["properties"]["name"], json!(["From my phone"]));
+        assert_eq!(source["properties"]["post-status"], json!(["draft"]));
+        let wanted = ["summary".to_string()];
+        let source = query(&db, &site, &user, "source", Some(&url), &wanted).await.unwrap();
+        assert_eq!(source, json!({ "properties": { "summary": ["A greeting"] } }));
+        let by_id = site.post_url(&post.id.to_string(), None);
+        assert!(query(&db, &site, &user, "source", Some(&by_id), &[]).await.is_ok());
+        assert!(matches!(
+            query(&db, &site, &user, "source", Some("https://elsewhere.example/"), &[]).await,
+            Err(MicropubError::InvalidRequest(_))
+        ));
+        let config = query(&db, &site, &user, "config", None, &[]).await.unwrap();
+        assert_eq!(config["media-endpoint"], "http://localhost:8000/micropub/media");
+
+        let delete = form(&[("action", "delete"), ("url", &url)]);
+        assert_eq!(perform(&publishing, &user, parse_action(&delete).unwrap()).await, Ok(None));
+        assert!(PostRepository::get_post(&db, user.id, post.id).await.unwrap().is_none());
+
+        cleanup_test_user_by_email(&db, &email).await;
+    }
+}
@@ -1,6 +1,25 @@
+pub mod activitypub;
+pub mod amp;
+pub mod assets;
+pub mod comments;
 pub mod cookies;
+pub mod headings;
+pub mod highlight;
+pub mod html;
+pub mod lru;
 pub mod markdown;
+pub mod micropub;
+pub mod newsletter;
+pub mod oembed;
+pub mod pings;
+pub mod preview;
+pub mod publishing;
 pub mod requires_auth;
+pub mod sanitize;
+pub mod shortcodes;
+pub mod text;
+pub mod webhooks;
+pub mod webmentions;
 
 pub use markdown::*;
 pub use requires_auth::*;
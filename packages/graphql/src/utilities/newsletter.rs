@@ -0,0 +1,322 @@
+//! New posts by email: the post rendered into HTML that mail clients show,
+//! and the pass over the queue that sends it to each subscriber.
+
+use super::assets::AssetImages;
+use super::highlight::escape_html;
+use super::html::{parse_tag, push_attr, skip_element, REMOVED_WITH_CONTENT};
+use super::markdown::{render_document_with, ContentFormat, RenderOptions};
+use models::sea_orm_active_enums::SubscriberStatus;
+use models::{newsletter_deliveries, posts};
+use repositories::{NewsletterRepository, PostRepository, SubscriberRepository};
+use sea_orm::DatabaseConnection;
+use services::assets::StorageDriver;
+use services::config::{NewsletterConfig, SiteConfig};
+use services::email::Newsletter;
+use services::newsletter::unsubscribe_token;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Elements mail clients don't show whose content can stay.
+const UNWRAPPED: &[&str] = &[
+    "form", "embed", "frame", "frameset", "base", "link", "meta", "video", "audio", "source", "picture", "div", "span",
+    "section", "article", "aside", "header", "footer", "nav", "main",
+];
+
+/// Inline styles, since mail clients ignore stylesheets.
+fn style(tag: &str) -> Option<&'static str> {
+    Some(match tag {
+        "a" => "color:#2563eb;",
+        "img" => "max-width:100%;height:auto;",
+        "pre" => "background:#f6f8fa;padding:12px;overflow-x:auto;font-size:14px;line-height:1.45;",
+        "code" => "font-family:Menlo,Consolas,monospace;font-size:0.9em;",
+        "blockquote" => "margin:0 0 16px;padding-left:12px;border-left:3px solid #d1d5db;color:#4b5563;",
+        "table" => "border-collapse:collapse;margin:0 0 16px;",
+        "th" | "td" => "border:1px solid #d1d5db;padding:6px 10px;",
+        "hr" => "border:none;border-top:1px solid #e5e7eb;",
+        _ => return None,
+    })
+}
+
+/// Rewrite rendered post HTML for email: scripts and other active content
+/// are dropped, iframes become links, classes and ids go, tags the client
+/// might style get inline styles, and URLs are made absolute: assets
+/// against this server, other paths against the blog and fragments
+/// against the post.
+pub fn email_safe(html: &str, site: &SiteConfig, post_url: &str) -> String {
+    let absolute = |name: &str, url: &str| -> String {
+        if url.starts_with('#') {
+            format!("{post_url}{url}")
+        } else if name == "src" || url.starts_with("/assets/") {
+            site.absolute_api_url(url)
+        } else if url.starts_with('/') && !url.starts_with("//") {
+            format!("{}{url}", site.url)
+        } else {
+            url.to_string()
+        }
+    };
+
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(at) = rest.find('<') {
+        out.push_str(&rest[..at]);
+        rest = &rest[at..];
+        if rest.starts_with("<!--") {
+            rest = &rest[rest.find("-->").map_or(rest.len(), |e| e + 3)..];
+            continue;
+        }
+        let Some(tag) = parse_tag(rest) else {
+            out.push_str("&lt;");
+            rest = &rest[1..];
+            continue;
+        };
+        let mut consumed = tag.len;
+        match tag.name.as_str() {
+            name if REMOVED_WITH_CONTENT.contains(&name) => {
+                if !tag.closing {
+                    consumed += skip_element(&rest[tag.len..], name);
+                }
+            }
+            name if UNWRAPPED.contains(&name) => {}
+            "iframe" if !tag.closing => {
+                if let Some(src) = tag.attr("src").filter(|s| !s.is_empty()) {
+                    let text = tag.attr("title").filter(|t| !t.is_empty()).unwrap_or(src);
+                    out.push_str(&format!("<p><a href=\"{}\" style=\"{}\">{text}</a></p>", absolute("href", src), style("a").unwrap()));
+                }
+                consumed += skip_element(&rest[tag.len..], "iframe");
+            }
+            "iframe" => {}
+            "input" => {
+                if tag.attr("type") == Some("checkbox") {
+                    out.push_str(if tag.attr("checked").is_some() { "\u{2611}" } else { "\u{2610}" });
+                }
+            }
+            _ if tag.closing => out.push_str(&rest[..tag.len]),
+            name => {
+                out.push('<');
+                out.push_str(name);
+                for (attr, value) in &tag.attrs {
+                    let lower = attr.to_ascii_lowercase();
+                    match lower.as_str() {
+                        "href" | "src" => {
+                            let value = value.as_deref().unwrap_or_default();
+                            if value.trim_start().to_ascii_lowercase().starts_with("javascript:") {
+                                continue;
+                            }
+                            push_attr(&mut out, &lower, Some(&absolute(&lower, value)));
+                        }
+                        "alt" | "title" | "width" | "height" | "colspan" | "rowspan" | "align" | "start" => {
+                            push_attr(&mut out, &lower, value.as_deref());
+                        }
+                        _ => {}
+                    }
+                }
+                if let Some(style) = style(name) {
+                    push_attr(&mut out, "style", Some(style));
+                }
+                out.push_str(if rest[..tag.len].ends_with("/>") { " />" } else { ">" });
+            }
+        }
+        rest = &rest[consumed..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// A post ready to email, but for each subscriber's unsubscribe link.
+pub struct RenderedNewsletter {
+    pub subject: String,
+    pub url: String,
+    /// Body of the post as email-safe HTML
+    pub html: String,
+    pub text: String,
+}
+
+/// Render `post` for email. Math stays as TeX and code isn't highlighted:
+/// mail clients show neither MathML nor stylesheet classes.
+pub fn render_newsletter(post: &posts::Model, site: &SiteConfig, assets: &AssetImages) -> RenderedNewsletter {
+    let markdown = post.markdown_content.as_deref().unwrap_or_default();
+    let options = RenderOptions {
+        highlight_code: false,
+        line_numbers: false,
+        heading_anchors: false,
+        math: false,
+        format: ContentFormat::Html,
+    };
+    let url = site.post_url(&post.id.to_string(), post.slug.as_deref());
+    let html = render_document_with(markdown, &options, assets).html;
+    let text = render_document_with(markdown, &RenderOptions { format: ContentFormat::PlainText, ..options }, assets).html;
+    RenderedNewsletter {
+        subject: post.title.clone(),
+        html: email_safe(&html, site, &url),
+        text,
+        url,
+    }
+}
+
+impl RenderedNewsletter {
+    /// The whole email, HTML and text, with `unsubscribe_url` in the footer.
+    pub fn for_subscriber(&self, site: &SiteConfig, unsubscribe_url: &str) -> (String, String) {
+        let mut title = String::new();
+        escape_html(&mut title, &self.subject);
+        let mut blog = String::new();
+        escape_html(&mut blog, &site.title);
+        let mut url = String::new();
+        escape_html(&mut url, &self.url);
+        let mut unsubscribe = String::new();
+        escape_html(&mut unsubscribe, unsubscribe_url);
+
+        let html = format!(
+            "<!DOCTYPE html>\
+             <html><head><meta charset=\"utf-8\"><meta name=\"viewport\" content=\"width=device-width, initial-scale=1\"><title>{title}</title></head>\
+             <body style=\"margin:0;padding:0;background:#f4f4f5;\">\
+             <table role=\"presentation\" width=\"100%\" cellpadding=\"0\" cellspacing=\"0\" style=\"background:#f4f4f5;\"><tr><td align=\"center\" style=\"padding:24px 12px;\">\
+             <table role=\"presentation\" width=\"100%\" cellpadding=\"0\" cellspacing=\"0\" style=\"max-width:640px;background:#ffffff;\"><tr>\
+             <td style=\"padding:32px;font-family:Georgia,serif;font-size:17px;line-height:1.6;color:#1f2937;\">\
+             <p style=\"margin:0 0 8px;font-size:14px;color:#6b7280;\">{blog}</p>\
+             <h1 style=\"margin:0 0 24px;font-size:28px;line-height:1.25;\"><a href=\"{url}\" style=\"color:#111827;text-decoration:none;\">{title}</a></h1>\
+             {body}\
+             <p style=\"margin:32px 0 0;\"><a href=\"{url}\" style=\"color:#2563eb;\">Read on the web</a></p>\
+             </td></tr></table>\
+             <p style=\"font-family:Helvetica,Arial,sans-serif;font-size:12px;color:#6b7280;\">\
+             You get this email because you subscribed to {blog}. <a href=\"{unsubscribe}\" style=\"color:#6b7280;\">Unsubscribe</a></p>\
+             </td></tr></table></body></html>",
+            body = self.html,
+        );
+        let text = format!(
+            "{}\n{}\n\n{}\n\n-- \nYou get this email because you subscribed to {}.\nUnsubscribe: {unsubscribe_url}\n",
+            self.subject,
+            self.url,
+            self.text.trim_end(),
+            site.title,
+        );
+        (html, text)
+    }
+}
+
+/// The blog's page that confirms a subscription.
+pub fn confirm_url(site: &SiteConfig, token: &str) -> String {
+    format!("{}/newsletter/confirm?token={token}", site.url)
+}
+
+/// The blog's unsubscribe page, linked from every email, and this server's
+/// one-click endpoint for the `List-Unsubscribe` header.
+pub fn unsubscribe_urls(site: &SiteConfig, config: &NewsletterConfig, subscriber_id: Uuid) -> (String, String) {
+    let query = format!("subscriber={subscriber_id}&token={}", unsubscribe_token(&config.secret, subscriber_id));
+    (
+        format!("{}/newsletter/unsubscribe?{query}", site.url),
+        format!("{}/newsletter/unsubscribe?{query}", site.api_url),
+    )
+}
+
+/// Send what's due in the queue, up to `config.batch_size` emails, through
+/// `send`. Returns how many were sent; failures are recorded on their
+/// deliveries and retried later.
+pub async fn send_due(
+    db: &DatabaseConnection,
+    driver: &StorageDriver,
+    site: &SiteConfig,
+    config: &NewsletterConfig,
+    send: impl AsyncFn(&Newsletter<'_>) -> Result<(), String>,
+) -> Result<usize, String> {
+    let deliveries = NewsletterRepository::claim_due(db, config.batch_size).await?;
+    let mut rendered: HashMap<Uuid, Option<RenderedNewsletter>> = HashMap::new();
+    let mut sent = 0;
+    for delivery in deliveries {
+        if let Entry::Vacant(slot) = rendered.entry(delivery.post_id) {
+            let newsletter = match PostRepository::find_by_id(db, delivery.post_id).await? {
+                Some(post) if post.is_published => {
+                    let markdown = post.markdown_content.as_deref().unwrap_or_default();
+                    let assets = AssetImages::load(db, driver, post.user_id, &[markdown]).await?;
+                    Some(render_newsletter(&post, site, &assets))
+                }
+                _ => None,
+            };
+            slot.insert(newsletter);
+        }
+        let Some(newsletter) = &rendered[&delivery.post_id] else {
+            give_up(db, &delivery, "Post unpublished").await?;
+            continue;
+        };
+        let subscriber = match SubscriberRepository::find_by_id(db, delivery.subscriber_id).await? {
+            Some(s) if s.status == SubscriberStatus::Active => s,
+            _ => {
+                give_up(db, &delivery, "Unsubscribed").await?;
+                continue;
+            }
+        };
+
+        let (page, one_click) = unsubscribe_urls(site, config, subscriber.id);
+        let (html, text) = newsletter.for_subscriber(site, &page);
+        let email = Newsletter {
+            to: &subscriber.email,
+            subject: &newsletter.subject,
+            html: &html,
+            text: &text,
+            unsubscribe_url: &one_click,
+        };
+        match send(&email).await {
+            Ok(()) => {
+                NewsletterRepository::mark_sent(db, delivery.id).await?;
+                sent += 1;
+            }
+            Err(e) => {
+                tracing::warn!(delivery_id = %delivery.id, attempts = delivery.attempts, "newsletter email: {e}");
+                NewsletterRepository::mark_failed(db, &delivery, e, config.max_attempts).await?;
+            }
+        }
+    }
+    Ok(sent)
+}
+
+async fn give_up(db: &DatabaseConnection, delivery: &newsletter_deliveries::Model, reason: &str) -> Result<(), String> {
+    NewsletterRepository::mark_failed(db, delivery, reason.to_string(), 0).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::post;
+
+    #[test]
+    fn email_html_is_inlined_and_absolute() {
+        let site = SiteConfig::default();
+        let html = email_safe(
+            "<p class=\"x\" onclick=\"evil()\">See <a href=\"/posts/other\">this</a>, <a href=\"#fn-1\">1</a> \
+             and <a href=\"javascript:alert(1)\">that</a></p><script>alert(1)</script>\
+             <img src=\"/assets/a/large.webp\" srcset=\"/assets/a/small.webp 320w\" alt=\"A\" loading=\"lazy\" />\
+             <div class=\"embed\"><iframe src=\"https://www.youtube.com/embed/x\" title=\"Video\"></iframe></div>",
+            &site,
+            "http://localhost:3000/posts/p",
+        );
+        assert_eq!(
+            html,
+            "<p>See <a href=\"http://localhost:3000/posts/other\" style=\"color:#2563eb;\">this</a>, \
+             <a href=\"http://localhost:3000/posts/p#fn-1\" style=\"color:#2563eb;\">1</a> \
+             and <a style=\"color:#2563eb;\">that</a></p>\
+             <img src=\"http://localhost:8000/assets/a/large.webp\" alt=\"A\" style=\"max-width:100%;height:auto;\" />\
+             <p><a href=\"https://www.youtube.com/embed/x\" style=\"color:#2563eb;\">Video</a></p>"
+        );
+    }
+
+    #[test]
+    fn newsletters_render_the_post_with_an_unsubscribe_footer() {
+        let site = SiteConfig::default();
+        let post = posts::Model {
+            markdown_content: Some("Some *fine* `code` and $x^2$.".to_string()),
+            render_math: true,
+            ..post("Fish & chips", "fish")
+        };
+        let newsletter = render_newsletter(&post, &site, &AssetImages::default());
+        assert_eq!(newsletter.url, "http://localhost:3000/posts/fish");
+        assert!(newsletter.html.contains("<em>fine</em>"));
+        assert!(newsletter.html.contains("$x^2$"));
+        assert_eq!(newsletter.text.trim(), "Some fine code and $x^2$.");
+
+        let (html, text) = newsletter.for_subscriber(&site, "https://blog.example/unsub?a=1&b=2");
+        assert!(html.contains("<title>Fish &amp; chips</title>"));
+        assert!(html.contains("href=\"https://blog.example/unsub?a=1&amp;b=2\""));
+        assert!(text.starts_with("Fish & chips\nhttp://localhost:3000/posts/fish\n\nSome fine code"));
+        assert!(text.ends_with("Unsubscribe: https://blog.example/unsub?a=1&b=2\n"));
+    }
+}
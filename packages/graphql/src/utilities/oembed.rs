@@ -0,0 +1,365 @@
+//! oEmbed (https://oembed.com/): how chat tools and other sites unfurl a
+//! link to a published post.
+
+use super::assets::asset_ref;
+use super::highlight::escape_html;
+use super::text::description_or_excerpt;
+use crate::types::seo::SeoOverrides;
+use models::assets::Model as Asset;
+use models::posts;
+use repositories::{AssetRepository, UserRepository, WebmentionRepository};
+use sea_orm::DatabaseConnection;
+use serde::Serialize;
+use services::assets::{resized_variants, StorageDriver};
+use services::config::SiteConfig;
+use url::form_urlencoded;
+
+/// How long consumers may cache an answer, in seconds.
+pub const CACHE_AGE_SECS: u32 = 3600;
+
+/// Size of the embedded card, unless the consumer wants it smaller.
+const EMBED_WIDTH: u32 = 550;
+const EMBED_HEIGHT: u32 = 200;
+
+/// Below these the card doesn't fit, and a plain link is given instead.
+const MIN_EMBED_WIDTH: u32 = 200;
+const MIN_EMBED_HEIGHT: u32 = 100;
+
+/// Longest edge of a thumbnail, unless the consumer wants it smaller.
+const THUMBNAIL_EDGE: u32 = 640;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OembedFormat {
+    Json,
+    Xml,
+}
+
+impl OembedFormat {
+    /// The `format` parameter's format; JSON when none is asked for.
+    pub fn parse(format: Option<&str>) -> Option<Self> {
+        match format.map(str::to_ascii_lowercase).as_deref() {
+            None | Some("json") => Some(Self::Json),
+            Some("xml") => Some(Self::Xml),
+            Some(_) => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Xml => "xml",
+        }
+    }
+
+    /// Media type of an answer in this format.
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json; charset=utf-8",
+            Self::Xml => "text/xml; charset=utf-8",
+        }
+    }
+
+    /// `type` of the `<link>` that advertises answers in this format.
+    pub fn discovery_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json+oembed",
+            Self::Xml => "text/xml+oembed",
+        }
+    }
+}
+
+/// Where to ask for `post_url`'s oEmbed in `format`.
+pub fn discovery_url(site: &SiteConfig, post_url: &str, format: OembedFormat) -> String {
+    let query = form_urlencoded::Serializer::new(String::new())
+        .append_pair("url", post_url)
+        .append_pair("format", format.as_str())
+        .finish();
+    format!("{}/oembed?{query}", site.api_url)
+}
+
+/// The `maxwidth` and `maxheight` a consumer asked for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MaxSize {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+impl MaxSize {
+    fn fits(self, width: u32, height: u32) -> bool {
+        self.width.is_none_or(|w| width <= w) && self.height.is_none_or(|h| height <= h)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Thumbnail {
+    pub url: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// The largest resized variant of `asset` that fits both `max` and
+/// [`THUMBNAIL_EDGE`]. Images of unknown size have none, since a thumbnail's
+/// size must be given.
+pub fn thumbnail(asset: &Asset, driver: &StorageDriver, max: MaxSize) -> Option<Thumbnail> {
+    let (width, height) = match (asset.width, asset.height) {
+        (Some(w), Some(h)) if w > 0 && h > 0 => (w as u32, h as u32),
+        _ => return None,
+    };
+    resized_variants(width, height)
+        .into_iter()
+        .rev()
+        .find(|&(_, w, h)| w.max(h) <= THUMBNAIL_EDGE && max.fits(w, h))
+        .map(|(name, width, height)| Thumbnail {
+            url: driver.url(&format!("{}/{name}.webp", asset.id)),
+            width,
+            height,
+        })
+}
+
+/// An oEmbed answer: a `rich` card, or a `link` when the card won't fit.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Oembed {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub version: &'static str,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author_url: Option<String>,
+    pub provider_name: String,
+    pub provider_url: String,
+    pub cache_age: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail_width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail_height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub html: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+}
+
+impl Oembed {
+    /// The answer as oEmbed's XML.
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\" standalone=\"yes\"?>\n<oembed>\n");
+        if let Ok(serde_json::Value::Object(fields)) = serde_json::to_value(self) {
+            for (name, value) in fields {
+                let text = match value {
+                    serde_json::Value::String(s) => s,
+                    other => other.to_string(),
+                };
+                xml.push_str("  <");
+                xml.push_str(&name);
+                xml.push('>');
+                escape_html(&mut xml, &text);
+                xml.push_str("</");
+                xml.push_str(&name);
+                xml.push_str(">\n");
+            }
+        }
+        xml.push_str("</oembed>\n");
+        xml
+    }
+}
+
+/// The card embedded for a post: its title, description and byline,
+/// linking back to it.
+fn card(site: &SiteConfig, url: &str, title: &str, description: Option<&str>, author: Option<&str>) -> String {
+    let mut html = String::from("<blockquote class=\"soliloquio-embed\" cite=\"");
+    escape_html(&mut html, url);
+    html.push_str("\"><p><strong><a href=\"");
+    escape_html(&mut html, url);
+    html.push_str("\">");
+    escape_html(&mut html, title);
+    html.push_str("</a></strong></p>");
+    if let Some(description) = description {
+        html.push_str("<p>");
+        escape_html(&mut html, description);
+        html.push_str("</p>");
+    }
+    html.push_str("<p>");
+    if let Some(author) = author {
+        escape_html(&mut html, author);
+        html.push_str(" · ");
+    }
+    html.push_str("<a href=\"");
+    escape_html(&mut html, &site.url);
+    html.push_str("\">");
+    escape_html(&mut html, &site.title);
+    html.push_str("</a></p></blockquote>");
+    html
+}
+
+/// The oEmbed answer for a published `post` by `author`. The title and
+/// description are those its share metadata uses.
+pub fn build(
+    site: &SiteConfig,
+    post: &posts::Model,
+    author: Option<String>,
+    thumbnail: Option<Thumbnail>,
+    max: MaxSize,
+) -> Oembed {
+    let overrides = SeoOverrides::from_json(post.seo.as_ref());
+    let url = site.post_url(&post.id.to_string(), post.slug.as_deref());
+    let title = overrides.title.clone().unwrap_or_else(|| post.title.clone());
+    let description = overrides.description.clone().or_else(|| {
+        description_or_excerpt(post.description.as_deref(), post.markdown_content.as_deref().unwrap_or_default())
+    });
+
+    let width = max.width.map_or(EMBED_WIDTH, |w| w.min(EMBED_WIDTH));
+    let height = max.height.map_or(EMBED_HEIGHT, |h| h.min(EMBED_HEIGHT));
+    let rich = width >= MIN_EMBED_WIDTH && height >= MIN_EMBED_HEIGHT;
+    Oembed {
+        kind: if rich { "rich" } else { "link" },
+        version: "1.0",
+        html: rich.then(|| card(site, &url, &title, description.as_deref(), author.as_deref())),
+        width: rich.then_some(width),
+        height: rich.then_some(height),
+        title,
+        author_url: author.is_some().then(|| site.url.clone()),
+        author_name: author,
+        provider_name: site.title.clone(),
+        provider_url: site.url.clone(),
+        cache_age: CACHE_AGE_SECS,
+        thumbnail_url: thumbnail.as_ref().map(|t| t.url.clone()),
+        thumbnail_width: thumbnail.as_ref().map(|t| t.width),
+        thumbnail_height: thumbnail.as_ref().map(|t| t.height),
+    }
+}
+
+/// The oEmbed answer for `url`, if it is the page of a published post. The
+/// thumbnail is taken from the share image override, then the cover image,
+/// when either is one of the author's uploads.
+pub async fn lookup(
+    db: &DatabaseConnection,
+    driver: &StorageDriver,
+    site: &SiteConfig,
+    url: &str,
+    max: MaxSize,
+) -> Result<Option<Oembed>, String> {
+    let Some(key) = site.post_key(url) else { return Ok(None) };
+    let Some(post) = WebmentionRepository::published_post(db, &key).await? else { return Ok(None) };
+    let author = UserRepository::find_by_id(db, post.user_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .and_then(|user| user.display_name)
+        .filter(|name| !name.trim().is_empty());
+
+    let overrides = SeoOverrides::from_json(post.seo.as_ref());
+    let image = overrides.image.as_deref().or(post.cover_image.as_deref()).and_then(asset_ref);
+    let thumbnail = match image {
+        Some(id) => AssetRepository::find_many_for_user(db, post.user_id, &[id])
+            .await?
+            .first()
+            .and_then(|asset| thumbnail(asset, driver, max)),
+        None => None,
+    };
+    Ok(Some(build(site, &post, author, thumbnail, max)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{self, asset};
+    use services::assets::LocalStorageDriver;
+    use uuid::Uuid;
+
+    fn post() -> posts::Model {
+        posts::Model {
+            markdown_content: Some("Some *words* here.".to_string()),
+            seo: Some(serde_json::json!({"description": "A better description"})),
+            first_published_at: None,
+            ..test_helpers::post("Hello & welcome", "hello")
+        }
+    }
+
+    #[test]
+    fn posts_embed_as_cards() {
+        let site = SiteConfig::default();
+        let oembed = build(&site, &post(), Some("Ada".to_string()), None, MaxSize::default());
+        assert_eq!(oembed.kind, "rich");
+        assert_eq!((oembed.width, oembed.height), (Some(550), Some(200)));
+        let html = oembed.html.as_deref().unwrap();
+        assert!(html.contains(r#"<a href="http://localhost:3000/posts/hello">Hello &amp; welcome</a>"#), "{html}");
+        assert!(html.contains("<p>A better description</p>"), "{html}");
+        assert!(html.contains("Ada · "), "{html}");
+        assert_eq!(oembed.author_url.as_deref(), Some("http://localhost:3000"));
+
+        let json = serde_json::to_value(&oembed).unwrap();
+        assert_eq!(json["type"], "rich");
+        assert_eq!(json["version"], "1.0");
+        assert!(json.get("thumbnail_url").is_none());
+
+        let narrow = build(&site, &post(), None, None, MaxSize { width: Some(120), height: None });
+        assert_eq!(narrow.kind, "link");
+        assert_eq!((narrow.html, narrow.width, narrow.author_name), (None, None, None));
+        let smaller = build(&site, &post(), None, None, MaxSize { width: Some(400), height: Some(150) });
+        assert_eq!((smaller.width, smaller.height), (Some(400), Some(150)));
+    }
+
+    #[test]
+    fn answers_render_as_xml() {
+        let oembed = build(&SiteConfig::default(), &post(), None, None, MaxSize { width: Some(120), height: None });
+        let xml = oembed.to_xml();
+        assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"utf-8\" standalone=\"yes\"?>\n<oembed>\n"));
+        assert!(xml.contains("  <type>link</type>\n"));
+        assert!(xml.contains("  <title>Hello &amp; welcome</title>\n"));
+        assert!(xml.contains("  <cache_age>3600</cache_age>\n"));
+        assert!(xml.ends_with("</oembed>\n"));
+    }
+
+    #[test]
+    fn thumbnails_are_the_largest_variant_that_fits() {
+        let driver = StorageDriver::Local(LocalStorageDriver::new("/tmp"));
+        let asset = asset(Uuid::nil(), Some((2000, 1000)));
+        let thumb = thumbnail(&asset, &driver, MaxSize::default()).unwrap();
+        assert!(thumb.url.ends_with("/small.webp"), "{}", thumb.url);
+        assert_eq!((thumb.width, thumb.height), (640, 320));
+        let thumb = thumbnail(&asset, &driver, MaxSize { width: Some(300), height: None }).unwrap();
+        assert!(thumb.url.ends_with("/thumbnail.webp"), "{}", thumb.url);
+        assert_eq!(thumbnail(&asset, &driver, MaxSize { width: Some(100), height: None }), None);
+        assert_eq!(thumbnail(&Asset { width: None, ..asset }, &driver, MaxSize::default()), None);
+    }
+
+    #[tokio::test]
+    async fn test_lookup_finds_published_posts() {
+        use crate::test_helpers::*;
+
+        let db = setup_test_db().await;
+        let email = generate_unique_email("oembed");
+        let user = create_test_user_with_password(&db, &email, &valid_password()).await;
+        let published = create_test_post(&db, user.id, "Hello", "Some words.", true).await;
+        let draft = create_test_post(&db, user.id, "Draft", "Not yet.", false).await;
+        let site = SiteConfig::default();
+        let driver = test_storage_driver();
+        let url = |post: &posts::Model| site.post_url(&post.id.to_string(), None);
+
+        let oembed = lookup(&db, &driver, &site, &format!("{}?utm_source=x", url(&published)), MaxSize::default())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(oembed.title, "Hello");
+        assert_eq!(oembed.kind, "rich");
+        assert!(oembed.html.unwrap().contains("<p>Some words.</p>"));
+        assert_eq!(lookup(&db, &driver, &site, &url(&draft), MaxSize::default()).await.unwrap(), None);
+        let elsewhere = format!("https://elsewhere.example/posts/{}", published.id);
+        assert_eq!(lookup(&db, &driver, &site, &elsewhere, MaxSize::default()).await.unwrap(), None);
+
+        cleanup_test_user_by_email(&db, &email).await;
+    }
+
+    #[test]
+    fn formats_and_discovery() {
+        assert_eq!(OembedFormat::parse(None), Some(OembedFormat::Json));
+        assert_eq!(OembedFormat::parse(Some("XML")), Some(OembedFormat::Xml));
+        assert_eq!(OembedFormat::parse(Some("yaml")), None);
+        let url = discovery_url(&SiteConfig::default(), "http://localhost:3000/posts/a b", OembedFormat::Xml);
+        assert_eq!(url, "http://localhost:8000/oembed?url=http%3A%2F%2Flocalhost%3A3000%2Fposts%2Fa+b&format=xml");
+    }
+}
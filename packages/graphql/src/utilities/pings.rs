@@ -0,0 +1,179 @@
+use models::posts;
+use models::sea_orm_active_enums::PingKind;
+use repositories::{NewPing, PingRepository};
+use sea_orm::DatabaseConnection;
+use services::config::{FeedPingConfig, QueueConfig, SiteConfig};
+use services::pings::{extended_ping_body, send, websub_body};
+use services::webhooks::Client;
+
+/// The pings that publishing `post` makes: every hub and ping service is
+/// told about every feed.
+pub fn pings_for(post: &posts::Model, site: &SiteConfig, config: &FeedPingConfig) -> Vec<NewPing> {
+    let post_url = site.post_url(&post.id.to_string(), post.slug.as_deref());
+    let mut pings = Vec::new();
+    for feed_url in &config.feed_urls {
+        for hub in &config.hubs {
+            pings.push(NewPing {
+                kind: PingKind::Websub,
+                endpoint: hub.clone(),
+                feed_url: feed_url.clone(),
+                body: websub_body(feed_url),
+            });
+        }
+        for endpoint in &config.ping_urls {
+            pings.push(NewPing {
+                kind: PingKind::Xmlrpc,
+                endpoint: endpoint.clone(),
+                feed_url: feed_url.clone(),
+                body: extended_ping_body(&site.title, &site.url, &post_url, feed_url),
+            });
+        }
+    }
+    pings
+}
+
+/// Queue the pings for a newly published `post`. A failure is logged rather
+/// than returned: the post is already published.
+pub async fn queue(db: &DatabaseConnection, post: &posts::Model, site: &SiteConfig, config: &FeedPingConfig) {
+    let pings = pings_for(post, site, config);
+    match PingRepository::enqueue(db, post.user_id, Some(post.id), pings).await {
+        Ok(0) => {}
+        Ok(queued) => tracing::info!(post_id = %post.id, queued, "queued feed pings"),
+        Err(e) => tracing::warn!(post_id = %post.id, "queueing feed pings: {e}"),
+    }
+}
+
+/// Send the pings that are due, up to `config.batch_size`, retrying failures
+/// with backoff. Returns how many endpoints took theirs.
+pub async fn send_due(db: &DatabaseConnection, client: &Client, config: &QueueConfig) -> Result<usize, String> {
+    let mut sent = 0;
+    for ping in PingRepository::claim_due(db, config.batch_size).await? {
+        let attempt = send(client, ping.kind, &ping.endpoint, ping.body.clone()).await;
+        let status = attempt.status.map(i32::from);
+        match attempt.error {
+            None => {
+                PingRepository::mark_sent(db, ping.id, status).await?;
+                sent += 1;
+            }
+            Some(e) => {
+                tracing::warn!(ping_id = %ping.id, endpoint = %ping.endpoint, attempts = ping.attempts, "feed ping: {e}");
+                PingRepository::mark_failed(db, &ping, status, e, config.max_attempts).await?;
+            }
+        }
+    }
+    Ok(sent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::*;
+    use async_graphql::Request;
+    use sea_orm::{ActiveModelTrait, ActiveValue, IntoActiveModel};
+    use services::authentication::Token;
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    /// Headers, lowercased, and body of a request.
+    type Received = (HashMap<String, String>, String);
+
+    /// Answer one request on a local port with `response`, handing back its
+    /// headers and body.
+    fn endpoint(response: &'static str) -> (String, std::thread::JoinHandle<Received>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut headers = HashMap::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    headers.insert(name.to_lowercase(), value.trim().to_string());
+                }
+            }
+            let mut body = vec![0; headers["content-length"].parse().unwrap()];
+            reader.read_exact(&mut body).unwrap();
+            let mut stream = stream;
+            stream.write_all(response.as_bytes()).unwrap();
+            (headers, String::from_utf8(body).unwrap())
+        });
+        (url, handle)
+    }
+
+    #[tokio::test]
+    async fn test_publishing_pings_hubs_and_ping_services() {
+        let db = setup_test_db().await;
+        let schema = create_test_schema(db.clone());
+        let email = generate_unique_email("feed_pings");
+        let user = create_test_user_with_password(&db, &email, &valid_password()).await;
+        let mut am = user.into_active_model();
+        am.email_verified_at = ActiveValue::Set(Some(chrono::Utc::now().naive_utc()));
+        let user = am.update(&db).await.unwrap();
+
+        let (hub, hub_request) = endpoint("HTTP/1.1 204 No Content\r\nconnection: close\r\n\r\n");
+        let refusal = "HTTP/1.1 200 OK\r\ncontent-type: text/xml\r\ncontent-length: 246\r\nconnection: close\r\n\r\n\
+            <methodResponse><params><param><value><struct><member><name>flerror</name><value><boolean>1</boolean></value></member>\
+            <member><name>message</name><value><string>Too soon</string></value></member></struct></value></param></params></methodResponse>";
+        let (rpc, rpc_request) = endpoint(refusal);
+        let config = FeedPingConfig {
+            hubs: vec![hub.clone()],
+            ping_urls: vec![rpc.clone()],
+            feed_urls: vec!["https://blog.example/feed.xml".to_string()],
+            queue: QueueConfig::default(),
+        };
+        let run = |query: String| {
+            Request::new(query).data(Token::new(create_access_token(&user))).data(config.clone())
+        };
+
+        // Only the first save publishes; the edit after it pings nobody.
+        let post = create_test_post(&db, user.id, "Hello", "content", false).await;
+        for _ in 0..2 {
+            let publish = format!(
+                r#"mutation {{ updatePost(post: {{ id: "{}", title: "Hello", content: "content", isPublished: true }}) {{ ... on Post {{ id }} }} }}"#,
+                post.id
+            );
+            let res = schema.execute(run(publish)).await;
+            assert!(res.errors.is_empty(), "{:?}", res.errors);
+        }
+
+        let log = format!(
+            r#"{{ feedPings(postId: "{}") {{ nodes {{ kind endpoint feedUrl status attempts responseStatus lastError }} }} }}"#,
+            post.id
+        );
+        let res = schema.execute(run(log.clone())).await;
+        let data = res.data.into_json().unwrap();
+        let nodes = data["feedPings"]["nodes"].as_array().unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert!(nodes.iter().all(|n| n["status"] == "QUEUED" && n["feedUrl"] == "https://blog.example/feed.xml"));
+
+        let sent = send_due(&db, &services::pings::client(&config.queue), &config.queue).await.unwrap();
+        assert!(sent >= 1);
+        let (headers, body) = hub_request.join().unwrap();
+        assert_eq!(headers["content-type"], "application/x-www-form-urlencoded");
+        assert_eq!(body, "hub.mode=publish&hub.url=https%3A%2F%2Fblog.example%2Ffeed.xml");
+        let (headers, body) = rpc_request.join().unwrap();
+        assert_eq!(headers["content-type"], "text/xml");
+        assert!(body.contains("<methodName>weblogUpdates.extendedPing</methodName>"));
+        assert!(body.contains(&format!("<string>{}</string>", SiteConfig::default().post_url(&post.id.to_string(), None))));
+
+        let res = schema.execute(run(log)).await;
+        let data = res.data.into_json().unwrap();
+        let nodes = data["feedPings"]["nodes"].as_array().unwrap();
+        let websub = nodes.iter().find(|n| n["kind"] == "WEBSUB").unwrap();
+        assert_eq!(websub["endpoint"], hub);
+        assert_eq!(websub["status"], "SENT");
+        assert_eq!(websub["responseStatus"], 204);
+        let xmlrpc = nodes.iter().find(|n| n["kind"] == "XMLRPC").unwrap();
+        assert_eq!(xmlrpc["status"], "QUEUED");
+        assert_eq!(xmlrpc["attempts"], 1);
+        assert_eq!(xmlrpc["lastError"], "Ping refused: Too soon");
+
+        cleanup_test_user_by_email(&db, &email).await;
+    }
+}
@@ -0,0 +1,56 @@
+use super::assets::asset_ref;
+use models::posts::Model;
+use repositories::{AssetRepository, PostRepository, UserRepository};
+use sea_orm::DatabaseConnection;
+use services::assets::{store_preview, PreviewCard, StorageDriver, PREVIEW_PREFIX};
+use services::config::SiteConfig;
+use uuid::Uuid;
+
+/// Storage key of the picture behind a post's card: the large variant of
+/// its cover, when the cover is one of the author's assets.
+async fn background_key(db: &DatabaseConnection, post: &Model) -> Result<Option<String>, String> {
+    let Some(id) = post.cover_image.as_deref().and_then(asset_ref) else {
+        return Ok(None);
+    };
+    Ok(AssetRepository::get(db, post.user_id, id)
+        .await?
+        .map(|asset| format!("{}/large.webp", asset.id)))
+}
+
+/// Make sure `post` has an up-to-date share card, drawing a new one when its
+/// title, author name, cover or the blog name changed since the stored card
+/// was made, or when `force` is set. Returns the card's storage key.
+pub async fn refresh_preview(
+    db: &DatabaseConnection,
+    driver: &StorageDriver,
+    site: &SiteConfig,
+    post: &Model,
+    force: bool,
+) -> Result<String, String> {
+    let author = UserRepository::find_by_id(db, post.user_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .and_then(|user| user.display_name);
+    let background = background_key(db, post).await?;
+    let card = PreviewCard {
+        title: &post.title,
+        author: author.as_deref(),
+        site_title: &site.title,
+        background: background.as_deref(),
+    };
+    let key = card.key(post.id);
+    if !force && post.preview_image.as_deref() == Some(key.as_str()) {
+        return Ok(key);
+    }
+    let key = store_preview(&card, post.id, driver).await?;
+    PostRepository::set_preview_image(db, post.id, Some(key.clone())).await?;
+    Ok(key)
+}
+
+/// Remove a deleted post's cards from storage.
+pub async fn delete_previews(driver: &StorageDriver, post_id: Uuid) -> Result<(), String> {
+    driver
+        .delete_dir(&format!("{PREVIEW_PREFIX}/{post_id}"))
+        .await
+        .map_err(|e| e.to_string())
+}
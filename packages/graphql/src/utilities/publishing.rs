@@ -0,0 +1,78 @@
+use crate::utilities::{activitypub, pings, preview, webhooks, webmentions};
+use models::posts;
+use sea_orm::DatabaseConnection;
+use services::assets::StorageDriver;
+use services::config::{FeedPingConfig, SiteConfig};
+use services::webhooks::WebhookEvent;
+use std::sync::Arc;
+
+/// What saving or deleting a post sets off, wherever the save came from: the
+/// GraphQL mutations and Micropub. Failures are logged rather than returned;
+/// the post is saved either way.
+pub struct Publishing<'a> {
+    pub db: &'a DatabaseConnection,
+    /// Share cards aren't drawn without one
+    pub driver: Option<&'a Arc<StorageDriver>>,
+    pub site: SiteConfig,
+    /// Hubs and ping services aren't told without one
+    pub feed_pings: Option<&'a FeedPingConfig>,
+}
+
+impl Publishing<'_> {
+    /// Follow up a save of `post`, which was `previous` before it, or new.
+    pub async fn saved(&self, post: &posts::Model, previous: Option<&posts::Model>) {
+        self.refresh_preview(post).await;
+        self.queue_newsletter(post, previous.is_some_and(|p| p.first_published_at.is_some())).await;
+        self.announce_save(post, previous.is_some_and(|p| p.is_published)).await;
+    }
+
+    /// Follow up the deletion of `post`.
+    pub async fn deleted(&self, post: &posts::Model) {
+        webhooks::emit(self.db, post.user_id, WebhookEvent::PostDeleted, webhooks::post_data(post, &self.site)).await;
+        if post.is_published {
+            activitypub::announce(self.db, self.driver, &self.site, post, WebhookEvent::PostDeleted).await;
+        }
+        if let Some(driver) = self.driver
+            && let Err(e) = preview::delete_previews(driver, post.id).await
+        {
+            tracing::warn!("removing share cards of post {}: {e}", post.id);
+        }
+    }
+
+    /// Bring the post's share card up to date.
+    async fn refresh_preview(&self, post: &posts::Model) {
+        let Some(driver) = self.driver else { return };
+        if let Err(e) = preview::refresh_preview(self.db, driver, &self.site, post, false).await {
+            tracing::warn!("share card for post {}: {e}", post.id);
+        }
+    }
+
+    /// Email a post to the blog's subscribers the first time it is published.
+    /// `was_published` says whether it had been published before this save.
+    async fn queue_newsletter(&self, post: &posts::Model, was_published: bool) {
+        if was_published || post.first_published_at.is_none() {
+            return;
+        }
+        match repositories::NewsletterRepository::enqueue_post(self.db, post).await {
+            Ok(queued) => tracing::info!(post_id = %post.id, queued, "queued post for subscribers"),
+            Err(e) => tracing::warn!("queueing post {} for subscribers: {e}", post.id),
+        }
+    }
+
+    /// Tell others about a save that published, unpublished or changed a
+    /// published post: the blog's webhooks, the pages the post links to or
+    /// linked to, the blog's fediverse followers, and feed hubs and ping
+    /// services if it published the post.
+    /// `was_live` says whether the post was published before this save.
+    async fn announce_save(&self, post: &posts::Model, was_live: bool) {
+        let Some(event) = webhooks::post_event(was_live, post.is_published) else { return };
+        webhooks::emit(self.db, post.user_id, event, webhooks::post_data(post, &self.site)).await;
+        if event == WebhookEvent::PostPublished
+            && let Some(config) = self.feed_pings
+        {
+            pings::queue(self.db, post, &self.site, config).await;
+        }
+        webmentions::queue_outgoing(self.db, post, &self.site).await;
+        activitypub::announce(self.db, self.driver, &self.site, post, event).await;
+    }
+}
@@ -1,5 +1,6 @@
 use async_graphql::{Context, Result};
 use services::authentication::token::Token;
+use services::config::AuthConfig;
 use std::fmt;
 
 #[derive(Debug)]
@@ -20,6 +21,7 @@ impl fmt::Display for AuthenticationError {
     }
 }
 
+#[allow(async_fn_in_trait)]
 pub trait RequiresAuth {
     async fn require_authenticate_as_user<'a>(
         &self,
@@ -36,7 +38,8 @@ pub trait RequiresAuth {
         };
 
         let db = ctx.data::<sea_orm::DatabaseConnection>().unwrap();
-        let user = services::authentication::authenticator::get_user(db, token).await?;
+        let auth = ctx.data::<AuthConfig>().unwrap();
+        let user = services::authentication::authenticator::get_user(db, auth, token).await?;
         Ok(user)
     }
 }
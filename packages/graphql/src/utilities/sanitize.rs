@@ -0,0 +1,132 @@
+use super::html::{parse_tag, skip_element, REMOVED_WITH_CONTENT};
+
+/// Elements that survive sanitizing. Anything else is unwrapped, keeping
+/// its text.
+const ALLOWED: &[&str] = &[
+    "p", "br", "a", "em", "strong", "del", "code", "pre", "blockquote", "ul", "ol", "li",
+];
+
+const VOID: &[&str] = &["br"];
+
+/// Links in untrusted markup are followed by neither search engines nor the
+/// opened page.
+const LINK_REL: &str = "nofollow ugc noopener";
+
+/// Whether `url` is relative or uses one of the schemes a reader's link may
+/// have. Whitespace and control characters are ignored the way browsers
+/// ignore them, and entity-encoded URLs are refused rather than decoded.
+fn is_safe_url(url: &str) -> bool {
+    let url: String = url.chars().filter(|c| !c.is_ascii_whitespace() && !c.is_control()).collect();
+    if url.replace("&amp;", "").contains('&') {
+        return false;
+    }
+    match url.find([':', '/', '?', '#']) {
+        Some(at) if url.as_bytes()[at] == b':' => {
+            matches!(url[..at].to_ascii_lowercase().as_str(), "http" | "https" | "mailto")
+        }
+        _ => true,
+    }
+}
+
+/// Reduce untrusted HTML to a small allowlist of text-level and block
+/// elements. Links keep only a safe `href` and get `rel="nofollow ugc
+/// noopener"`, ordered lists keep a numeric `start`, every other attribute
+/// is dropped, comments are removed and unclosed elements are closed.
+pub fn sanitize_html(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut open: Vec<String> = Vec::new();
+    let mut rest = html;
+    while let Some(at) = rest.find('<') {
+        out.push_str(&rest[..at]);
+        rest = &rest[at..];
+        if rest.starts_with("<!--") {
+            let end = rest.find("-->").map_or(rest.len(), |e| e + 3);
+            rest = &rest[end..];
+            continue;
+        }
+        let Some(tag) = parse_tag(rest) else {
+            out.push_str("&lt;");
+            rest = &rest[1..];
+            continue;
+        };
+        let mut consumed = tag.len;
+        match tag.name.as_str() {
+            name if REMOVED_WITH_CONTENT.contains(&name) => {
+                if !tag.closing {
+                    consumed += skip_element(&rest[tag.len..], name);
+                }
+            }
+            name if !ALLOWED.contains(&name) => {}
+            name if tag.closing => {
+                if let Some(depth) = open.iter().rposition(|n| n == name) {
+                    for name in open.drain(depth..).rev() {
+                        out.push_str(&format!("</{name}>"));
+                    }
+                }
+            }
+            name => {
+                out.push('<');
+                out.push_str(name);
+                match name {
+                    "a" => {
+                        if let Some(href) = tag.attr("href").filter(|h| is_safe_url(h)) {
+                            out.push_str(&format!(" href=\"{href}\""));
+                        }
+                        out.push_str(&format!(" rel=\"{LINK_REL}\""));
+                    }
+                    "ol" => {
+                        if let Some(start) = tag.attr("start").filter(|s| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit())) {
+                            out.push_str(&format!(" start=\"{start}\""));
+                        }
+                    }
+                    _ => {}
+                }
+                if VOID.contains(&name) {
+                    out.push_str(" />");
+                } else {
+                    out.push('>');
+                    open.push(name.to_string());
+                }
+            }
+        }
+        rest = &rest[consumed..];
+    }
+    out.push_str(rest);
+    for name in open.iter().rev() {
+        out.push_str(&format!("</{name}>"));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_allowlisted_markup_only() {
+        let html = sanitize_html(
+            "<h1 id=\"x\">Title</h1><p class=\"a\" onclick=\"x()\">Hi <em>there</em><script>alert(1)</script></p>\
+             <img src=\"/a.png\" alt=\"A\"><ol start=\"3\" type=\"i\"><li>three</ol><!-- hidden --> 1 < 2<br>",
+        );
+        assert_eq!(
+            html,
+            "Title<p>Hi <em>there</em></p><ol start=\"3\"><li>three</li></ol> 1 &lt; 2<br />"
+        );
+    }
+
+    #[test]
+    fn links_keep_only_safe_urls() {
+        let html = sanitize_html(
+            "<a href=\"https://example.com/?a=1&amp;b=2\" target=\"_blank\">ok</a>\
+             <a href=\" Java\tScript:alert(1)\">js</a><a href=\"javascript&#58;alert(1)\">encoded</a>\
+             <a href=\"/relative#x\">rel</a><a href=\"mailto:a@example.com\">mail</a><strong>open",
+        );
+        assert_eq!(
+            html,
+            "<a href=\"https://example.com/?a=1&amp;b=2\" rel=\"nofollow ugc noopener\">ok</a>\
+             <a rel=\"nofollow ugc noopener\">js</a><a rel=\"nofollow ugc noopener\">encoded</a>\
+             <a href=\"/relative#x\" rel=\"nofollow ugc noopener\">rel</a>\
+             <a href=\"mailto:a@example.com\" rel=\"nofollow ugc noopener\">mail</a><strong>open</strong>"
+        );
+    }
+}
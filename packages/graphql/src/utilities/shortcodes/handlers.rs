@@ -0,0 +1,188 @@
+use super::{Call, ShortcodeContext};
+use crate::utilities::assets::{asset_ref, ResponsiveImage};
+use crate::utilities::highlight::escape_html;
+
+const CALLOUT_KINDS: &[&str] = &["note", "tip", "info", "warning", "danger"];
+
+fn escaped(text: &str) -> String {
+    let mut out = String::new();
+    escape_html(&mut out, text);
+    out
+}
+
+/// An `asset:` reference, a root-relative path or an http(s) URL.
+fn image(src: &str, ctx: &ShortcodeContext) -> Result<ResponsiveImage, String> {
+    if asset_ref(src).is_some() {
+        return ctx.assets.resolve(src).cloned().ok_or_else(|| format!("no such asset `{src}`"));
+    }
+    let safe = (src.starts_with('/') && !src.starts_with("//"))
+        || src.starts_with("https://")
+        || src.starts_with("http://");
+    if !safe {
+        return Err(format!("`{src}` is not an asset, path or http(s) URL"));
+    }
+    Ok(ResponsiveImage::external(src))
+}
+
+/// `{{< figure src="asset:…" alt="…" caption="…" >}}`, or with the caption
+/// as the inner markdown.
+pub(super) fn figure(call: &Call, ctx: &ShortcodeContext) -> Result<String, String> {
+    let src = call.args.get_or("src", 0).ok_or("figure needs a `src`")?;
+    let img = image(src, ctx)?;
+    let mut html = String::from("<figure>");
+    html.push_str(&img.to_html(call.args.get("alt").unwrap_or_default(), call.args.get("title")));
+    if let Some(caption) = call.inner.or(call.args.get("caption")).filter(|c| !c.trim().is_empty()) {
+        html.push_str("<figcaption>");
+        html.push_str(&ctx.render_inline(caption));
+        html.push_str("</figcaption>");
+    }
+    html.push_str("</figure>");
+    Ok(html)
+}
+
+/// `{{< callout warning title="…" >}}markdown{{< /callout >}}`
+pub(super) fn callout(call: &Call, ctx: &ShortcodeContext) -> Result<String, String> {
+    let kind = call.args.get_or("type", 0).unwrap_or("note");
+    if !CALLOUT_KINDS.contains(&kind) {
+        return Err(format!("callout type must be one of {}", CALLOUT_KINDS.join(", ")));
+    }
+    let mut html = format!("<aside class=\"callout callout-{kind}\">");
+    if let Some(title) = call.args.get("title") {
+        html.push_str("<p class=\"callout-title\">");
+        html.push_str(&ctx.render_inline(title));
+        html.push_str("</p>");
+    }
+    html.push_str(&(ctx.render)(call.inner.unwrap_or_default()));
+    html.push_str("</aside>");
+    Ok(html)
+}
+
+/// The video id from an id, a `youtube.com/watch?v=` URL or a `youtu.be/` URL.
+fn youtube_id(value: &str) -> Option<&str> {
+    let id = if let Some((_, query)) = value.split_once("watch?") {
+        query.split('&').find_map(|pair| pair.strip_prefix("v="))?
+    } else if let Some((_, path)) = value.split_once("youtu.be/") {
+        path.split(['?', '&']).next()?
+    } else {
+        value
+    };
+    let valid = id.len() == 11 && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    valid.then_some(id)
+}
+
+/// `{{< youtube dQw4w9WgXcQ start=30 >}}`, embedded without tracking cookies.
+pub(super) fn youtube(call: &Call, _: &ShortcodeContext) -> Result<String, String> {
+    let value = call.args.get_or("id", 0).ok_or("youtube needs a video id")?;
+    let id = youtube_id(value).ok_or_else(|| format!("`{value}` is not a YouTube video id"))?;
+    let start = match call.args.get("start") {
+        Some(s) => format!("?start={}", s.parse::<u32>().map_err(|_| "start must be a number of seconds")?),
+        None => String::new(),
+    };
+    let title = escaped(call.args.get("title").unwrap_or("YouTube video"));
+    Ok(format!(
+        "<div class=\"embed embed-youtube\"><iframe src=\"https://www.youtube-nocookie.com/embed/{id}{start}\" \
+         title=\"{title}\" loading=\"lazy\" allow=\"encrypted-media; picture-in-picture\" allowfullscreen></iframe></div>"
+    ))
+}
+
+/// `{{< details "Summary" open >}}markdown{{< /details >}}`
+pub(super) fn details(call: &Call, ctx: &ShortcodeContext) -> Result<String, String> {
+    let summary = call.args.get_or("summary", 0).unwrap_or("Details");
+    let open = if call.args.flag("open") { " open" } else { "" };
+    Ok(format!(
+        "<details{open}><summary>{}</summary>{}</details>",
+        ctx.render_inline(summary),
+        (ctx.render)(call.inner.unwrap_or_default())
+    ))
+}
+
+/// `{{< gallery asset:… asset:… >}}`: uploaded images side by side.
+pub(super) fn gallery(call: &Call, ctx: &ShortcodeContext) -> Result<String, String> {
+    if call.args.positional.is_empty() {
+        return Err("gallery needs at least one asset".to_string());
+    }
+    let mut html = String::from("<div class=\"gallery\">");
+    for src in &call.args.positional {
+        if asset_ref(src).is_none() {
+            return Err(format!("`{src}` is not an asset: reference"));
+        }
+        html.push_str(&image(src, ctx)?.to_html("", None));
+    }
+    html.push_str("</div>");
+    Ok(html)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{expand, Registry};
+    use super::*;
+    use crate::test_helpers::asset;
+    use crate::utilities::assets::AssetImages;
+    use services::assets::{LocalStorageDriver, StorageDriver};
+    use uuid::Uuid;
+
+    fn assets(ids: &[Uuid]) -> AssetImages {
+        let models: Vec<_> = ids
+            .iter()
+            .map(|&id| asset(id, Some((100, 50))))
+            .collect();
+        AssetImages::new(&models, &StorageDriver::Local(LocalStorageDriver::new("/tmp")))
+    }
+
+    fn render(markdown: &str, assets: &AssetImages) -> String {
+        let render = |md: &str| format!("<p>{}</p>\n", md.trim());
+        let ctx = ShortcodeContext { assets, render: &render };
+        let (_, fragments) = expand(markdown, Registry::builtin(), &ctx);
+        fragments.concat()
+    }
+
+    #[test]
+    fn figure_resolves_assets_and_renders_caption() {
+        let id = Uuid::new_v4();
+        let html = render(&format!(r#"{{{{< figure asset:{id} alt="A <cat>" caption="*Tom*" >}}}}"#), &assets(&[id]));
+        assert!(html.starts_with(&format!("<figure><img src=\"/assets/{id}/large.webp\"")), "{html}");
+        assert!(html.contains("alt=\"A &lt;cat&gt;\""), "{html}");
+        assert!(html.ends_with("<figcaption>*Tom*</figcaption></figure>"), "{html}");
+
+        let html = render(r#"{{< figure src="javascript:alert(1)" >}}"#, &AssetImages::default());
+        assert!(html.starts_with("<code class=\"shortcode-error\""), "{html}");
+    }
+
+    #[test]
+    fn callout_wraps_inner_markdown() {
+        let html = render("{{< callout warning title=\"Careful\" >}}\nHot\n{{< /callout >}}", &AssetImages::default());
+        assert_eq!(
+            html,
+            "<aside class=\"callout callout-warning\"><p class=\"callout-title\">Careful</p><p>Hot</p>\n</aside>"
+        );
+        assert!(render("{{< callout \"><script>\" >}}", &AssetImages::default()).contains("shortcode-error"));
+    }
+
+    #[test]
+    fn youtube_accepts_ids_and_urls() {
+        let html = render("{{< youtube dQw4w9WgXcQ start=30 >}}", &AssetImages::default());
+        assert!(html.contains("src=\"https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ?start=30\""), "{html}");
+        let from_url = render("{{< youtube \"https://www.youtube.com/watch?t=1&v=dQw4w9WgXcQ\" >}}", &AssetImages::default());
+        assert!(from_url.contains("/embed/dQw4w9WgXcQ\""), "{from_url}");
+        assert_eq!(youtube_id("https://youtu.be/dQw4w9WgXcQ?si=x"), Some("dQw4w9WgXcQ"));
+        assert!(render("{{< youtube \"x\\\" onload=\\\"y\" >}}", &AssetImages::default()).contains("shortcode-error"));
+    }
+
+    #[test]
+    fn details_renders_summary_and_open_flag() {
+        let html = render("{{< details \"More <info>\" open >}}\nBody\n{{< /details >}}", &AssetImages::default());
+        assert_eq!(html, "<details open><summary>More <info></summary><p>Body</p>\n</details>");
+        let closed = render("{{< details >}}x{{< /details >}}", &AssetImages::default());
+        assert!(closed.starts_with("<details><summary>Details</summary>"), "{closed}");
+    }
+
+    #[test]
+    fn gallery_needs_resolvable_assets() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let html = render(&format!("{{{{< gallery asset:{a} asset:{b} >}}}}"), &assets(&[a, b]));
+        assert!(html.starts_with("<div class=\"gallery\"><img"), "{html}");
+        assert_eq!(html.matches("<img").count(), 2);
+        let missing = render(&format!("{{{{< gallery asset:{a} >}}}}"), &AssetImages::default());
+        assert!(missing.contains("no such asset"), "{missing}");
+    }
+}
@@ -0,0 +1,386 @@
+//! Hugo-style shortcodes: `{{< name arg key="value" >}}`, optionally wrapping
+//! markdown up to a matching `{{< /name >}}`. They are expanded before the
+//! markdown is parsed; each one becomes a placeholder comment that
+//! `render_document` swaps for the handler's HTML. `{{</* name */>}}` writes a
+//! shortcode literally, and code spans and fences are left alone.
+
+mod handlers;
+
+use super::assets::AssetImages;
+use super::highlight::escape_html;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+/// Arguments of a shortcode, in the order written.
+#[derive(Debug, Default, PartialEq)]
+pub struct Args {
+    pub positional: Vec<String>,
+    pub named: Vec<(String, String)>,
+}
+
+impl Args {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.named.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    /// A named argument, falling back to the `index`th positional one.
+    pub fn get_or(&self, key: &str, index: usize) -> Option<&str> {
+        self.get(key).or_else(|| self.positional.get(index).map(String::as_str))
+    }
+
+    pub fn flag(&self, key: &str) -> bool {
+        self.positional.iter().any(|p| p == key) || self.get(key).is_some_and(|v| v != "false")
+    }
+}
+
+/// One shortcode in a document.
+#[derive(Debug, PartialEq)]
+pub struct Call<'a> {
+    pub name: &'a str,
+    pub args: Args,
+    /// Markdown between the opening and closing tags of a paired shortcode
+    pub inner: Option<&'a str>,
+}
+
+/// What handlers may use besides their arguments.
+pub struct ShortcodeContext<'a> {
+    pub assets: &'a AssetImages,
+    /// Renders nested markdown with the document's options
+    pub render: &'a dyn Fn(&str) -> String,
+}
+
+impl ShortcodeContext<'_> {
+    /// Nested markdown as inline HTML, without the wrapping paragraph.
+    pub fn render_inline(&self, markdown: &str) -> String {
+        let html = (self.render)(markdown);
+        let trimmed = html.trim_end();
+        match trimmed.strip_prefix("<p>").and_then(|s| s.strip_suffix("</p>")) {
+            Some(inner) if !inner.contains("<p>") => inner.to_string(),
+            _ => html,
+        }
+    }
+}
+
+/// Returns the HTML for a call, or a message explaining why it can't.
+pub type Handler = fn(&Call, &ShortcodeContext) -> Result<String, String>;
+
+pub struct Registry {
+    handlers: HashMap<&'static str, Handler>,
+}
+
+static BUILTIN: LazyLock<Registry> = LazyLock::new(|| {
+    let mut registry = Registry::empty();
+    registry
+        .register("figure", handlers::figure)
+        .register("callout", handlers::callout)
+        .register("youtube", handlers::youtube)
+        .register("details", handlers::details)
+        .register("gallery", handlers::gallery);
+    registry
+});
+
+impl Registry {
+    pub fn empty() -> Self {
+        Self { handlers: HashMap::new() }
+    }
+
+    /// The handlers every post can use.
+    pub fn builtin() -> &'static Registry {
+        &BUILTIN
+    }
+
+    pub fn register(&mut self, name: &'static str, handler: Handler) -> &mut Self {
+        self.handlers.insert(name, handler);
+        self
+    }
+
+    fn call(&self, call: &Call, ctx: &ShortcodeContext) -> Result<String, String> {
+        let handler = self
+            .handlers
+            .get(call.name)
+            .ok_or_else(|| format!("unknown shortcode `{}`", call.name))?;
+        handler(call, ctx)
+    }
+}
+
+/// Marker left in the markdown for the `index`th expanded shortcode.
+pub fn placeholder(index: usize) -> String {
+    format!("<!--shortcode:{index}-->")
+}
+
+/// The index of a placeholder, given the HTML event that carries it.
+pub fn placeholder_index(html: &str) -> Option<usize> {
+    html.trim().strip_prefix("<!--shortcode:")?.strip_suffix("-->")?.parse().ok()
+}
+
+/// Failed shortcodes keep their source, escaped, so the author can spot them.
+fn error_html(source: &str, message: &str) -> String {
+    let mut html = String::from("<code class=\"shortcode-error\" title=\"");
+    escape_html(&mut html, message);
+    html.push_str("\">");
+    escape_html(&mut html, source);
+    html.push_str("</code>");
+    html
+}
+
+/// A parsed `{{< … >}}` tag.
+struct Tag<'a> {
+    closing: bool,
+    name: &'a str,
+    args: Args,
+    /// Byte length of the tag in the source
+    len: usize,
+}
+
+fn parse_tag(text: &str) -> Option<Tag<'_>> {
+    let body_start = 3;
+    let mut in_quote = false;
+    let mut escaped = false;
+    let mut end = None;
+    for (i, c) in text[body_start..].char_indices() {
+        let at = body_start + i;
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quote => escaped = true,
+            '"' => in_quote = !in_quote,
+            '>' if !in_quote && text[at..].starts_with(">}}") => {
+                end = Some(at);
+                break;
+            }
+            // A tag can't span a blank line.
+            '\n' if !in_quote && text[at + 1..].starts_with('\n') => return None,
+            _ => {}
+        }
+    }
+    let end = end?;
+    let body = text[body_start..end].trim();
+    let (closing, body) = match body.strip_prefix('/') {
+        Some(rest) => (true, rest.trim_start()),
+        None => (false, body),
+    };
+    let name_len = body.find(char::is_whitespace).unwrap_or(body.len());
+    let name = &body[..name_len];
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return None;
+    }
+    Some(Tag { closing, name, args: parse_args(&body[name_len..]), len: end + 3 })
+}
+
+fn parse_args(text: &str) -> Args {
+    let mut args = Args::default();
+    let mut chars = text.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            return args;
+        }
+        let mut token = String::new();
+        let mut value = None;
+        loop {
+            match chars.peek() {
+                None => break,
+                Some(c) if c.is_whitespace() => break,
+                Some('"') => {
+                    chars.next();
+                    let mut quoted = String::new();
+                    while let Some(c) = chars.next() {
+                        match c {
+                            '\\' => quoted.extend(chars.next()),
+                            '"' => break,
+                            c => quoted.push(c),
+                        }
+                    }
+                    match value.as_mut() {
+                        Some(v) => *v = quoted,
+                        None => token.push_str(&quoted),
+                    }
+                }
+                Some('=') if value.is_none() => {
+                    chars.next();
+                    value = Some(String::new());
+                }
+                Some(_) => {
+                    let c = chars.next().unwrap_or_default();
+                    match value.as_mut() {
+                        Some(v) => v.push(c),
+                        None => token.push(c),
+                    }
+                }
+            }
+        }
+        match value {
+            Some(v) => args.named.push((token, v)),
+            None => args.positional.push(token),
+        }
+    }
+}
+
+/// Where the `{{< /name >}}` matching an opening tag starts and ends, counting
+/// nested shortcodes of the same name.
+fn find_closing(text: &str, name: &str) -> Option<(usize, usize)> {
+    let mut depth = 0;
+    let mut from = 0;
+    while let Some(offset) = text[from..].find("{{<") {
+        let at = from + offset;
+        match parse_tag(&text[at..]) {
+            Some(tag) if tag.name == name && tag.closing => {
+                if depth == 0 {
+                    return Some((at, at + tag.len));
+                }
+                depth -= 1;
+                from = at + tag.len;
+            }
+            Some(tag) => {
+                if tag.name == name {
+                    depth += 1;
+                }
+                from = at + tag.len;
+            }
+            None => from = at + 3,
+        }
+    }
+    None
+}
+
+/// Length of the code span or fence starting at `text`, if it has an end.
+fn code_len(text: &str, line_start: bool) -> Option<usize> {
+    if line_start && text.starts_with("~~~") {
+        let close = text[3..].find("\n~~~").map(|i| i + 3 + 4)?;
+        return Some(close + text[close..].find('\n').unwrap_or(text.len() - close));
+    }
+    let ticks = text.len() - text.trim_start_matches('`').len();
+    let mut from = ticks;
+    while let Some(offset) = text[from..].find('`') {
+        let at = from + offset;
+        let run = text[at..].len() - text[at..].trim_start_matches('`').len();
+        if run == ticks {
+            return Some(at + run);
+        }
+        from = at + run;
+    }
+    None
+}
+
+/// Expand every shortcode in `markdown`, returning the markdown with
+/// placeholders and the HTML each placeholder stands for.
+pub fn expand(markdown: &str, registry: &Registry, ctx: &ShortcodeContext) -> (String, Vec<String>) {
+    let mut out = String::with_capacity(markdown.len());
+    let mut fragments = Vec::new();
+    let mut rest = markdown;
+    let mut line_start = true;
+    while !rest.is_empty() {
+        let skip = if rest.starts_with('`') || (line_start && rest.starts_with("~~~")) {
+            code_len(rest, line_start)
+        } else {
+            None
+        };
+        if let Some(len) = skip {
+            out.push_str(&rest[..len]);
+            line_start = rest[..len].ends_with('\n');
+            rest = &rest[len..];
+            continue;
+        }
+
+        if let Some(literal) = rest.strip_prefix("{{</*")
+            && let Some(end) = literal.find("*/>}}")
+        {
+            out.push_str("{{<");
+            out.push_str(&literal[..end]);
+            out.push_str(">}}");
+            rest = &literal[end + 5..];
+            line_start = false;
+            continue;
+        }
+
+        if rest.starts_with("{{<")
+            && let Some(tag) = parse_tag(rest)
+        {
+            let after = &rest[tag.len..];
+            let (inner, consumed) = match (tag.closing, find_closing(after, tag.name)) {
+                (false, Some((start, end))) => (Some(after[..start].trim_matches('\n')), tag.len + end),
+                _ => (None, tag.len),
+            };
+            let source = &rest[..consumed];
+            let html = if tag.closing {
+                error_html(source, &format!("`{}` has no opening tag", tag.name))
+            } else {
+                let call = Call { name: tag.name, args: tag.args, inner };
+                registry.call(&call, ctx).unwrap_or_else(|e| error_html(source, &e))
+            };
+            out.push_str(&placeholder(fragments.len()));
+            fragments.push(html);
+            rest = &rest[consumed..];
+            line_start = false;
+            continue;
+        }
+
+        let c = rest.chars().next().unwrap_or_default();
+        out.push(c);
+        line_start = c == '\n';
+        rest = &rest[c.len_utf8()..];
+    }
+    (out, fragments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn echo(call: &Call, _: &ShortcodeContext) -> Result<String, String> {
+        Ok(format!("[{} {:?} {:?}]", call.name, call.args, call.inner))
+    }
+
+    fn run(markdown: &str) -> (String, Vec<String>) {
+        let mut registry = Registry::empty();
+        registry.register("echo", echo);
+        let render = |md: &str| md.to_string();
+        let assets = AssetImages::default();
+        expand(markdown, &registry, &ShortcodeContext { assets: &assets, render: &render })
+    }
+
+    #[test]
+    fn parses_positional_and_named_arguments() {
+        let args = parse_args(r#" a key=v title="Hello \"you\"" "b c" empty="""#);
+        assert_eq!(args.positional, ["a", "b c"]);
+        assert_eq!(args.get("key"), Some("v"));
+        assert_eq!(args.get("title"), Some("Hello \"you\""));
+        assert_eq!(args.get("empty"), Some(""));
+        assert_eq!(args.get_or("missing", 1), Some("b c"));
+    }
+
+    #[test]
+    fn expands_single_and_paired_shortcodes() {
+        let (md, fragments) = run("x {{< echo 1 >}} y\n\n{{< echo >}}\ninner {{< echo >}}2{{< /echo >}}\n{{< /echo >}}\n");
+        assert_eq!(md, "x <!--shortcode:0--> y\n\n<!--shortcode:1-->\n");
+        assert_eq!(fragments[0], r#"[echo Args { positional: ["1"], named: [] } None]"#);
+        assert!(fragments[1].ends_with(r#"Some("inner {{< echo >}}2{{< /echo >}}")]"#), "{}", fragments[1]);
+    }
+
+    #[test]
+    fn leaves_code_and_escaped_shortcodes_alone() {
+        let source = "`{{< echo >}}` and\n```\n{{< echo >}}\n```\n~~~\n{{< echo >}}\n~~~\n{{</* echo x */>}}";
+        let (md, fragments) = run(source);
+        assert!(fragments.is_empty());
+        assert_eq!(
+            md,
+            "`{{< echo >}}` and\n```\n{{< echo >}}\n```\n~~~\n{{< echo >}}\n~~~\n{{< echo x >}}"
+        );
+    }
+
+    #[test]
+    fn unknown_shortcodes_render_escaped_source() {
+        let (_, fragments) = run(r#"{{< nope a="<b>" >}}"#);
+        assert_eq!(
+            fragments[0],
+            r#"<code class="shortcode-error" title="unknown shortcode `nope`">{{&lt; nope a=&quot;&lt;b&gt;&quot; &gt;}}</code>"#
+        );
+        let (_, fragments) = run("{{< /echo >}}");
+        assert!(fragments[0].contains("has no opening tag"));
+    }
+
+    #[test]
+    fn placeholders_round_trip() {
+        assert_eq!(placeholder_index(&format!("{}\n", placeholder(12))), Some(12));
+        assert_eq!(placeholder_index("<!-- shortcode -->"), None);
+    }
+}
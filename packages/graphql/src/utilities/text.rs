@@ -0,0 +1,157 @@
+use super::assets::AssetImages;
+use super::shortcodes::{self, Registry, ShortcodeContext};
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+
+/// Reading speed behind `readingTimeMinutes`.
+pub const WORDS_PER_MINUTE: usize = 200;
+
+/// Marks the end of a post's lead paragraphs, WordPress-style.
+pub const MORE_MARKER: &str = "<!--more-->";
+
+/// Excerpt length when a query doesn't ask for one.
+pub const DEFAULT_EXCERPT_LENGTH: usize = 200;
+
+/// The prose of a markdown document, without markup, for counting words and
+/// cutting excerpts.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlainText {
+    pub words: usize,
+    /// Paragraphs, headings and list items, separated by single spaces.
+    /// Code blocks, images, math and shortcodes are left out.
+    pub text: String,
+    /// Length of `text` before a `<!--more-->` marker, if there is one
+    pub lead: Option<usize>,
+}
+
+impl PlainText {
+    pub fn new(markdown: &str) -> Self {
+        // Shortcodes are markup too; with no handlers they expand to
+        // placeholder comments, which are skipped below.
+        let render = |_: &str| String::new();
+        let assets = AssetImages::default();
+        let ctx = ShortcodeContext { assets: &assets, render: &render };
+        let (markdown, _) = shortcodes::expand(markdown, &Registry::empty(), &ctx);
+
+        let mut plain = Self::default();
+        // Everything that counts as words, `text` plus what's hidden from it
+        let mut all = String::new();
+        // Nesting depth of code blocks, images and footnotes, whose text
+        // counts as words but isn't prose.
+        let mut hidden = 0usize;
+        for event in Parser::new_ext(&markdown, Options::all()) {
+            match event {
+                Event::Start(Tag::CodeBlock(_) | Tag::Image { .. } | Tag::FootnoteDefinition(_)) => hidden += 1,
+                Event::End(TagEnd::CodeBlock | TagEnd::Image | TagEnd::FootnoteDefinition) => {
+                    hidden = hidden.saturating_sub(1)
+                }
+                Event::Text(text) | Event::Code(text) => {
+                    push(&mut all, &text);
+                    if hidden == 0 {
+                        push(&mut plain.text, &text);
+                    }
+                }
+                Event::Html(html) | Event::InlineHtml(html) if html.trim() == MORE_MARKER => {
+                    plain.lead.get_or_insert(plain.text.trim_end().len());
+                }
+                Event::SoftBreak
+                | Event::HardBreak
+                | Event::End(
+                    TagEnd::Paragraph
+                    | TagEnd::Heading(_)
+                    | TagEnd::Item
+                    | TagEnd::BlockQuote(_)
+                    | TagEnd::TableCell
+                    | TagEnd::DefinitionListTitle
+                    | TagEnd::DefinitionListDefinition,
+                ) => {
+                    push(&mut all, " ");
+                    push(&mut plain.text, " ");
+                }
+                _ => {}
+            }
+        }
+        plain.words = all.split_whitespace().count();
+        plain.text.truncate(plain.text.trim_end().len());
+        plain
+    }
+
+    /// Minutes to read at [`WORDS_PER_MINUTE`], rounded up; at least 1 for
+    /// any post with words in it.
+    pub fn reading_time_minutes(&self) -> usize {
+        self.words.div_ceil(WORDS_PER_MINUTE)
+    }
+
+    /// Everything before the `<!--more-->` marker, or else the first `length`
+    /// characters cut back to a word boundary, with `…` if anything was cut.
+    pub fn excerpt(&self, length: usize) -> String {
+        if let Some(lead) = self.lead.filter(|&lead| lead > 0) {
+            return self.text[..lead].to_string();
+        }
+        let Some((cut, _)) = self.text.char_indices().nth(length) else {
+            return self.text.clone();
+        };
+        let head = &self.text[..cut];
+        let head = match head.rfind(' ') {
+            Some(space) if !self.text[cut..].starts_with(' ') => &head[..space],
+            _ => head,
+        };
+        format!("{}…", head.trim_end_matches(|c: char| c.is_whitespace() || ",;:".contains(c)))
+    }
+}
+
+/// A post's `description`, or an excerpt of its content when it has none.
+pub fn description_or_excerpt(description: Option<&str>, markdown: &str) -> Option<String> {
+    match description.map(str::trim) {
+        Some(description) if !description.is_empty() => Some(description.to_string()),
+        _ => Some(PlainText::new(markdown).excerpt(DEFAULT_EXCERPT_LENGTH)).filter(|e| !e.is_empty()),
+    }
+}
+
+/// Append `text` to `buf`, collapsing runs of whitespace.
+fn push(buf: &mut String, text: &str) {
+    for (i, word) in text.split(char::is_whitespace).enumerate() {
+        if i > 0 && !buf.is_empty() && !buf.ends_with(' ') {
+            buf.push(' ');
+        }
+        buf.push_str(word);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_markup_and_counts_words() {
+        let plain = PlainText::new(
+            "# Title\n\nSome *emphasis* and `code`.\n\n- one\n- two\n\n```rust\nlet x = 1;\n```\n\n\
+             ![alt text](a.png) {{< youtube dQw4w9WgXcQ >}} $x^2$",
+        );
+        assert_eq!(plain.text, "Title Some emphasis and code. one two");
+        assert_eq!(plain.words, 13);
+        assert_eq!(plain.lead, None);
+        assert_eq!(plain.reading_time_minutes(), 1);
+        assert_eq!(PlainText::new("").reading_time_minutes(), 0);
+        assert_eq!(PlainText::new(&"word ".repeat(401)).reading_time_minutes(), 3);
+    }
+
+    #[test]
+    fn excerpt_cuts_at_words_or_the_more_marker() {
+        let plain = PlainText::new("The quick brown fox jumps over the lazy dog.");
+        assert_eq!(plain.excerpt(12), "The quick…");
+        assert_eq!(plain.excerpt(9), "The quick…");
+        assert_eq!(plain.excerpt(3), "The…");
+        assert_eq!(plain.excerpt(100), "The quick brown fox jumps over the lazy dog.");
+        assert_eq!(PlainText::new("Ünïcödé wörds hère").excerpt(10), "Ünïcödé…");
+
+        let marked = PlainText::new("First *para*.\n\nSecond.\n\n<!--more-->\n\nThe rest.");
+        assert_eq!(marked.excerpt(5), "First para. Second.");
+        assert_eq!(marked.words, 5);
+        let inline = PlainText::new("Lead <!--more--> rest");
+        assert_eq!(inline.excerpt(200), "Lead");
+
+        assert_eq!(description_or_excerpt(Some("Set"), "Body"), Some("Set".to_string()));
+        assert_eq!(description_or_excerpt(Some(" "), "Body"), Some("Body".to_string()));
+        assert_eq!(description_or_excerpt(None, "```\ncode\n```"), None);
+    }
+}
@@ -0,0 +1,97 @@
+use models::posts;
+use repositories::{DeliveryResponse, WebhookRepository};
+use sea_orm::DatabaseConnection;
+use serde_json::{json, Value};
+use services::config::{QueueConfig, SiteConfig};
+use services::webhooks::{deliver, Client, WebhookEvent};
+use uuid::Uuid;
+
+/// The event a save of a post makes, given whether it was published before
+/// and is after. Edits to drafts don't make one.
+pub fn post_event(was_published: bool, is_published: bool) -> Option<WebhookEvent> {
+    match (was_published, is_published) {
+        (false, true) => Some(WebhookEvent::PostPublished),
+        (true, false) => Some(WebhookEvent::PostUnpublished),
+        (true, true) => Some(WebhookEvent::PostUpdated),
+        (false, false) => None,
+    }
+}
+
+/// A post as webhook payloads describe it.
+pub fn post_data(post: &posts::Model, site: &SiteConfig) -> Value {
+    let id = post.id.to_string();
+    json!({
+        "post": {
+            "id": post.id,
+            "title": post.title,
+            "slug": post.slug,
+            "description": post.description,
+            "url": site.post_url(&id, post.slug.as_deref()),
+            "isPublished": post.is_published,
+            "firstPublishedAt": post.first_published_at.map(|at| at.and_utc().to_rfc3339()),
+            "updatedAt": post.updated_at.and_utc().to_rfc3339(),
+        }
+    })
+}
+
+/// Queue `event` for `user_id`'s webhooks that listen for it. A failure is
+/// logged rather than returned: whatever made the event has already
+/// happened.
+pub async fn emit(db: &DatabaseConnection, user_id: Uuid, event: WebhookEvent, data: Value) {
+    let payload = json!({
+        "event": event.as_str(),
+        "createdAt": chrono::Utc::now().to_rfc3339(),
+        "data": data,
+    });
+    match WebhookRepository::enqueue(db, user_id, event.as_str(), payload).await {
+        Ok(0) => {}
+        Ok(queued) => tracing::info!(%user_id, event = event.as_str(), queued, "queued webhook deliveries"),
+        Err(e) => tracing::warn!(%user_id, event = event.as_str(), "queueing webhook deliveries: {e}"),
+    }
+}
+
+/// Make the deliveries that are due, up to `config.batch_size`. Returns how
+/// many endpoints took theirs; failures are recorded on their deliveries and
+/// retried later.
+pub async fn deliver_due(
+    db: &DatabaseConnection,
+    client: &Client,
+    config: &QueueConfig,
+) -> Result<usize, String> {
+    let mut delivered = 0;
+    for (delivery, webhook) in WebhookRepository::claim_due(db, config.batch_size).await? {
+        if !webhook.is_active {
+            let response = DeliveryResponse { status: None, body: None };
+            WebhookRepository::mark_failed(db, &delivery, response, "Webhook disabled".to_string(), 0).await?;
+            continue;
+        }
+
+        let body = serde_json::to_vec(&delivery.payload).map_err(|e| e.to_string())?;
+        let attempt = deliver(client, &webhook.url, &webhook.secret, delivery.id, &delivery.event, body).await;
+        let response = DeliveryResponse { status: attempt.status.map(i32::from), body: attempt.body };
+        match attempt.error {
+            None => {
+                WebhookRepository::mark_delivered(db, delivery.id, response).await?;
+                delivered += 1;
+            }
+            Some(e) => {
+                tracing::warn!(delivery_id = %delivery.id, attempts = delivery.attempts, "webhook delivery: {e}");
+                WebhookRepository::mark_failed(db, &delivery, response, e, config.max_attempts).await?;
+            }
+        }
+    }
+    Ok(delivered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_published_posts_make_events() {
+        assert_eq!(post_event(false, true), Some(WebhookEvent::PostPublished));
+        assert_eq!(post_event(true, true), Some(WebhookEvent::PostUpdated));
+        assert_eq!(post_event(true, false), Some(WebhookEvent::PostUnpublished));
+        assert_eq!(post_event(false, false), None);
+    }
+}
@@ -0,0 +1,508 @@
+//! Webmention (https://www.w3.org/TR/webmention/): telling pages our posts
+//! link to about it, and hearing from pages that link to our posts.
+
+use super::assets::AssetImages;
+use super::html::{parse_tag, skip_element, Tag, REMOVED_WITH_CONTENT};
+use super::markdown::{render_document_with, ContentFormat, RenderOptions};
+use models::sea_orm_active_enums::WebmentionKind;
+use models::{posts, webmentions};
+use repositories::{MentionDetails, WebmentionRepository};
+use sea_orm::DatabaseConnection;
+use services::config::{QueueConfig, SiteConfig};
+use services::webhooks::Client;
+use services::webmention::{fetch, link_header_endpoint, notify};
+use url::Url;
+
+/// Longest source or target URL taken.
+const MAX_URL_LENGTH: usize = 2048;
+
+/// Longest excerpt of a mention's content kept.
+const MAX_CONTENT_CHARS: usize = 500;
+
+/// Longest author name or title kept.
+const MAX_NAME_CHARS: usize = 200;
+
+/// Elements that never have a closing tag.
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr",
+];
+
+/// Why a mention was turned away.
+#[derive(Debug)]
+pub enum ReceiveError {
+    /// The request is wrong; the message says how
+    Invalid(String),
+    Database(String),
+}
+
+enum Token<'a> {
+    Open(Tag<'a>),
+    Close(String),
+    Text(&'a str),
+}
+
+/// `html` as a flat list of tags and text, without comments, scripts and
+/// the like.
+fn tokenize(html: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = html;
+    while !rest.is_empty() {
+        let Some(at) = rest.find('<') else {
+            tokens.push(Token::Text(rest));
+            break;
+        };
+        if at > 0 {
+            tokens.push(Token::Text(&rest[..at]));
+        }
+        rest = &rest[at..];
+        if rest.starts_with("<!--") {
+            rest = rest.find("-->").map_or("", |end| &rest[end + 3..]);
+            continue;
+        }
+        match parse_tag(rest) {
+            Some(tag) if tag.closing => {
+                rest = &rest[tag.len..];
+                tokens.push(Token::Close(tag.name));
+            }
+            Some(tag) if REMOVED_WITH_CONTENT.contains(&tag.name.as_str()) => {
+                let name = tag.name.clone();
+                rest = &rest[skip_element(rest, &name)..];
+            }
+            Some(tag) => {
+                rest = &rest[tag.len..];
+                tokens.push(Token::Open(tag));
+            }
+            None => {
+                tokens.push(Token::Text("<"));
+                rest = &rest[1..];
+            }
+        }
+    }
+    tokens
+}
+
+/// The few entities pages use in URLs and names.
+pub(crate) fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(at) = rest.find('&') {
+        out.push_str(&rest[..at]);
+        rest = &rest[at..];
+        let end = rest.find(';').filter(|&end| end <= 10);
+        let decoded = end.and_then(|end| match &rest[1..end] {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            n if n.starts_with("#x") || n.starts_with("#X") => u32::from_str_radix(&n[2..], 16).ok().and_then(char::from_u32),
+            n if n.starts_with('#') => n[1..].parse().ok().and_then(char::from_u32),
+            _ => None,
+        });
+        match (decoded, end) {
+            (Some(c), Some(end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            _ => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn attr(tag: &Tag, name: &str) -> Option<String> {
+    // The tokenizer escapes quotes inside values; undo that with the rest.
+    tag.attr(name).map(decode_entities)
+}
+
+fn has_class(tag: &Tag, class: &str) -> bool {
+    tag.attr("class").is_some_and(|c| c.split_ascii_whitespace().any(|c| c == class))
+}
+
+/// Index of the token that closes the element opened at `open`.
+fn element_end(tokens: &[Token], open: usize) -> usize {
+    let Token::Open(tag) = &tokens[open] else { return open };
+    if VOID_ELEMENTS.contains(&tag.name.as_str()) {
+        return open;
+    }
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(open + 1) {
+        match token {
+            Token::Open(t) if t.name == tag.name => depth += 1,
+            Token::Close(name) if *name == tag.name => {
+                if depth == 0 {
+                    return i;
+                }
+                depth -= 1;
+            }
+            _ => {}
+        }
+    }
+    tokens.len()
+}
+
+/// The text inside tokens `from..to`, whitespace collapsed, cut to `max`
+/// characters.
+fn text(tokens: &[Token], from: usize, to: usize, max: usize) -> Option<String> {
+    let raw: String = tokens[from..to.min(tokens.len())]
+        .iter()
+        .filter_map(|t| match t {
+            Token::Text(s) => Some(*s),
+            _ => None,
+        })
+        .collect();
+    let collapsed = decode_entities(&raw).split_whitespace().collect::<Vec<_>>().join(" ");
+    if collapsed.is_empty() {
+        return None;
+    }
+    if collapsed.chars().count() <= max {
+        return Some(collapsed);
+    }
+    let mut cut: String = collapsed.chars().take(max - 1).collect();
+    cut.push('…');
+    Some(cut)
+}
+
+/// Index of the first element in `from..to` with any of `classes`.
+fn find_class(tokens: &[Token], from: usize, to: usize, classes: &[&str]) -> Option<usize> {
+    (from..to.min(tokens.len())).find(|&i| matches!(&tokens[i], Token::Open(t) if classes.iter().any(|c| has_class(t, c))))
+}
+
+fn open_tag<'t, 'a>(tokens: &'t [Token<'a>], i: usize) -> &'t Tag<'a> {
+    match &tokens[i] {
+        Token::Open(tag) => tag,
+        _ => unreachable!("find_class only returns open tags"),
+    }
+}
+
+/// Whether two URLs name the same page, give or take a trailing slash.
+fn same_page(a: &str, b: &str) -> bool {
+    a.trim_end_matches('/') == b.trim_end_matches('/')
+}
+
+/// Absolute http(s) links in rendered post HTML, in order and without
+/// repeats, leaving out the blog's own pages.
+pub fn outbound_links(html: &str, site: &SiteConfig) -> Vec<String> {
+    let mut links: Vec<String> = Vec::new();
+    for token in tokenize(html) {
+        let Token::Open(tag) = token else { continue };
+        if tag.name != "a" {
+            continue;
+        }
+        let Some(href) = attr(&tag, "href") else { continue };
+        let Ok(url) = Url::parse(&href) else { continue };
+        let own = href.strip_prefix(site.url.as_str()).is_some_and(|rest| rest.is_empty() || rest.starts_with(['/', '?', '#']));
+        if !matches!(url.scheme(), "http" | "https") || own || href.len() > MAX_URL_LENGTH {
+            continue;
+        }
+        let href = href.split('#').next().unwrap_or_default().to_string();
+        if !links.contains(&href) {
+            links.push(href);
+        }
+    }
+    links
+}
+
+/// The Webmention endpoint a page's `<link>` or `<a>` names, resolved
+/// against the page's URL.
+pub fn html_endpoint(html: &str, base: &Url) -> Option<String> {
+    tokenize(html).into_iter().find_map(|token| {
+        let Token::Open(tag) = token else { return None };
+        if tag.name != "link" && tag.name != "a" {
+            return None;
+        }
+        let rel = tag.attr("rel")?;
+        if !rel.split_ascii_whitespace().any(|r| r.eq_ignore_ascii_case("webmention")) {
+            return None;
+        }
+        base.join(&attr(&tag, "href")?).ok().map(String::from)
+    })
+}
+
+/// What `html`, the source of a mention, says about its link to `target`,
+/// read from its h-entry. `None` if it doesn't link to `target` at all.
+pub fn read_source(html: &str, target: &str) -> Option<MentionDetails> {
+    let tokens = tokenize(html);
+    let link = tokens.iter().find_map(|token| match token {
+        Token::Open(tag) => {
+            let url = attr(tag, "href").or_else(|| attr(tag, "src"))?;
+            same_page(&url, target).then_some(tag)
+        }
+        _ => None,
+    })?;
+
+    let kind = [
+        ("u-in-reply-to", WebmentionKind::Reply),
+        ("u-like-of", WebmentionKind::Like),
+        ("u-repost-of", WebmentionKind::Repost),
+        ("u-bookmark-of", WebmentionKind::Bookmark),
+    ]
+    .into_iter()
+    .find(|(class, _)| has_class(link, class))
+    .map_or(WebmentionKind::Mention, |(_, kind)| kind);
+
+    let entry = find_class(&tokens, 0, tokens.len(), &["h-entry"]);
+    let (from, to) = entry.map_or((0, tokens.len()), |i| (i, element_end(&tokens, i)));
+    let mut details = MentionDetails { kind: Some(kind), ..Default::default() };
+
+    let author = find_class(&tokens, from, to, &["p-author", "u-author"]);
+    let author_range = author.map(|i| (i, element_end(&tokens, i)));
+    if let Some((start, end)) = author_range {
+        let tag = open_tag(&tokens, start);
+        let name = find_class(&tokens, start + 1, end, &["p-name"]);
+        details.author_name = match name {
+            Some(n) => text(&tokens, n, element_end(&tokens, n), MAX_NAME_CHARS),
+            None => text(&tokens, start, end, MAX_NAME_CHARS),
+        };
+        let url = find_class(&tokens, start + 1, end, &["u-url"]).map(|u| open_tag(&tokens, u));
+        details.author_url = url.and_then(|t| attr(t, "href")).or_else(|| attr(tag, "href"));
+        let photo = find_class(&tokens, start, end, &["u-photo"]).map(|p| open_tag(&tokens, p));
+        details.author_photo = photo.and_then(|t| attr(t, "src"));
+    }
+
+    let outside_author = |i: &usize| author_range.is_none_or(|(start, end)| *i < start || *i > end);
+    details.title = (from..to)
+        .filter(outside_author)
+        .find(|&i| matches!(&tokens[i], Token::Open(t) if has_class(t, "p-name")))
+        .and_then(|i| text(&tokens, i, element_end(&tokens, i), MAX_NAME_CHARS))
+        .or_else(|| {
+            let title = tokens.iter().position(|t| matches!(t, Token::Open(t) if t.name == "title"))?;
+            text(&tokens, title, element_end(&tokens, title), MAX_NAME_CHARS)
+        });
+    details.content = find_class(&tokens, from, to, &["e-content", "p-content", "p-summary"])
+        .and_then(|i| text(&tokens, i, element_end(&tokens, i), MAX_CONTENT_CHARS));
+    details.published_at = find_class(&tokens, from, to, &["dt-published"]).and_then(|i| {
+        let value = attr(open_tag(&tokens, i), "datetime").or_else(|| text(&tokens, i, element_end(&tokens, i), 64))?;
+        chrono::DateTime::parse_from_rfc3339(value.trim()).ok().map(|d| d.naive_utc())
+    });
+    Some(details)
+}
+
+/// Whether `url` is somewhere this server shouldn't be made to fetch on a
+/// stranger's say-so: its own machine or a private network. Names are
+/// checked again as requests resolve them.
+pub(crate) fn is_private(url: &Url) -> bool {
+    services::net::check_url(url).is_err()
+}
+
+fn parse_url(name: &str, value: &str) -> Result<Url, ReceiveError> {
+    if value.len() > MAX_URL_LENGTH {
+        return Err(ReceiveError::Invalid(format!("{name} is too long")));
+    }
+    match Url::parse(value) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(url),
+        _ => Err(ReceiveError::Invalid(format!("{name} must be an http(s) URL"))),
+    }
+}
+
+/// Take a mention of `target`, one of the blog's published posts, from
+/// `source`, to be checked later.
+pub async fn receive(
+    db: &DatabaseConnection,
+    site: &SiteConfig,
+    source: &str,
+    target: &str,
+) -> Result<webmentions::Model, ReceiveError> {
+    let source_url = parse_url("source", source)?;
+    parse_url("target", target)?;
+    if same_page(source, target) {
+        return Err(ReceiveError::Invalid("source and target must differ".to_string()));
+    }
+    if is_private(&source_url) {
+        return Err(ReceiveError::Invalid("source must be a public URL".to_string()));
+    }
+    let post = match site.post_key(target) {
+        Some(key) => WebmentionRepository::published_post(db, &key).await.map_err(ReceiveError::Database)?,
+        None => None,
+    };
+    let Some(post) = post else {
+        return Err(ReceiveError::Invalid("target is not a post on this blog".to_string()));
+    };
+    WebmentionRepository::receive(db, &post, source, target)
+        .await
+        .map_err(ReceiveError::Database)
+}
+
+/// Check the sources of received mentions that are due, up to
+/// `config.batch_size`. Returns how many were verified. A source that is
+/// gone, or no longer links to its target, takes a mention verified before
+/// with it.
+pub async fn verify_due(db: &DatabaseConnection, client: &Client, config: &QueueConfig) -> Result<usize, String> {
+    let mut verified = 0;
+    for mention in WebmentionRepository::claim_unverified(db, config.batch_size).await? {
+        let page = match fetch(client, &mention.source).await {
+            Ok(page) => page,
+            Err(e) => {
+                WebmentionRepository::mark_unverified(db, &mention, e, config.max_attempts).await?;
+                continue;
+            }
+        };
+        let details = match page.status {
+            200..=299 => read_source(&page.body, &mention.target),
+            404 | 410 => None,
+            status => {
+                let error = format!("HTTP {status}");
+                WebmentionRepository::mark_unverified(db, &mention, error, config.max_attempts).await?;
+                continue;
+            }
+        };
+        match details {
+            Some(details) => {
+                WebmentionRepository::mark_verified(db, mention.id, details).await?;
+                verified += 1;
+            }
+            None if mention.verified_at.is_some() => WebmentionRepository::remove(db, mention.id).await?,
+            None => {
+                let error = "Source doesn't link to the target".to_string();
+                WebmentionRepository::mark_unverified(db, &mention, error, 0).await?;
+            }
+        }
+    }
+    Ok(verified)
+}
+
+/// Queue mentions of the pages a saved `post` links to. A post that is no
+/// longer published links nowhere, which the pages it mentioned are told.
+/// A failure is logged rather than returned: the post is already saved.
+pub async fn queue_outgoing(db: &DatabaseConnection, post: &posts::Model, site: &SiteConfig) {
+    let targets = if post.is_published {
+        let options = RenderOptions {
+            highlight_code: false,
+            line_numbers: false,
+            heading_anchors: false,
+            math: false,
+            format: ContentFormat::Html,
+        };
+        let markdown = post.markdown_content.as_deref().unwrap_or_default();
+        outbound_links(&render_document_with(markdown, &options, &AssetImages::default()).html, site)
+    } else {
+        Vec::new()
+    };
+    let source = site.post_url(&post.id.to_string(), post.slug.as_deref());
+    match WebmentionRepository::queue_outgoing(db, post, &source, targets).await {
+        Ok(0) => {}
+        Ok(queued) => tracing::info!(post_id = %post.id, queued, "queued webmentions"),
+        Err(e) => tracing::warn!(post_id = %post.id, "queueing webmentions: {e}"),
+    }
+}
+
+/// Send the outgoing mentions that are due, up to `config.batch_size`,
+/// discovering each target's endpoint first. Returns how many endpoints
+/// took theirs. A target without an endpoint isn't tried again.
+pub async fn send_due(db: &DatabaseConnection, client: &Client, config: &QueueConfig) -> Result<usize, String> {
+    let mut sent = 0;
+    for mention in WebmentionRepository::claim_outgoing(db, config.batch_size).await? {
+        let page = match fetch(client, &mention.target).await {
+            Ok(page) => page,
+            Err(e) => {
+                WebmentionRepository::mark_send_failed(db, &mention, None, None, e, config.max_attempts).await?;
+                continue;
+            }
+        };
+        let endpoint = link_header_endpoint(&page.links, &page.url)
+            .or_else(|| html_endpoint(&page.body, &page.url))
+            .filter(|e| e.starts_with("http://") || e.starts_with("https://"));
+        let Some(endpoint) = endpoint else {
+            let (status, error) = match page.status {
+                200..=299 => (None, "No Webmention endpoint".to_string()),
+                status => (Some(i32::from(status)), format!("HTTP {status} discovering the endpoint")),
+            };
+            let max_attempts = if page.status >= 500 { config.max_attempts } else { 0 };
+            WebmentionRepository::mark_send_failed(db, &mention, None, status, error, max_attempts).await?;
+            continue;
+        };
+
+        let attempt = notify(client, &endpoint, &mention.source, &mention.target).await;
+        let status = attempt.status.map(i32::from);
+        match attempt.error {
+            None => {
+                WebmentionRepository::mark_sent(db, mention.id, endpoint, status).await?;
+                sent += 1;
+            }
+            Some(e) => {
+                tracing::warn!(target = %mention.target, attempts = mention.attempts, "webmention: {e}");
+                let endpoint = Some(endpoint);
+                WebmentionRepository::mark_send_failed(db, &mention, endpoint, status, e, config.max_attempts).await?;
+            }
+        }
+    }
+    Ok(sent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::site;
+
+    #[test]
+    fn outbound_links_leave_out_the_blog_itself() {
+        let html = r#"<p><a href="https://a.example/x?y=1&amp;z=2#top">a</a> <a href="/about">about</a>
+            <a href="https://blog.example/posts/other">other</a> <a href="https://blog.example.net/">net</a>
+            <a href="mailto:me@example.com">mail</a> <a href="https://a.example/x?y=1&amp;z=2">again</a></p>"#;
+        assert_eq!(outbound_links(html, &site()), vec!["https://a.example/x?y=1&z=2", "https://blog.example.net/"]);
+    }
+
+    #[test]
+    fn finds_the_endpoint_in_html() {
+        let base = Url::parse("https://site.example/posts/1").unwrap();
+        let html = r#"<head><!-- <link rel="webmention" href="/old"> --><link rel="stylesheet" href="/s.css">
+            <link href="/mention?a=1&amp;b=2" rel="webmention"></head>"#;
+        assert_eq!(html_endpoint(html, &base).as_deref(), Some("https://site.example/mention?a=1&b=2"));
+        assert_eq!(html_endpoint(r#"<a rel="webmention" href="">here</a>"#, &base).as_deref(), Some("https://site.example/posts/1"));
+        assert_eq!(html_endpoint("<p>nothing</p>", &base), None);
+    }
+
+    #[test]
+    fn reads_the_h_entry_of_a_source() {
+        let html = r#"<html><head><title>Page title</title></head><body>
+            <article class="h-entry">
+              <a class="p-author h-card" href="https://ana.example/"><img class="u-photo" src="https://ana.example/me.jpg"> Ana &amp; co</a>
+              <h1 class="p-name">Re: your post</h1>
+              <p>In reply to <a class="u-in-reply-to" href="https://blog.example/posts/hello/">this</a>.</p>
+              <div class="e-content"><p>I   agree <script>alert(1)</script>completely.</p></div>
+              <time class="dt-published" datetime="2024-05-01T10:00:00+02:00">May 1</time>
+            </article></body></html>"#;
+        let details = read_source(html, "https://blog.example/posts/hello").unwrap();
+        assert_eq!(details.kind, Some(WebmentionKind::Reply));
+        assert_eq!(details.author_name.as_deref(), Some("Ana & co"));
+        assert_eq!(details.author_url.as_deref(), Some("https://ana.example/"));
+        assert_eq!(details.author_photo.as_deref(), Some("https://ana.example/me.jpg"));
+        assert_eq!(details.title.as_deref(), Some("Re: your post"));
+        assert_eq!(details.content.as_deref(), Some("I agree completely."));
+        assert_eq!(details.published_at.unwrap().to_string(), "2024-05-01 08:00:00");
+
+        let plain = read_source(r#"<title>Links</title><a href="https://blog.example/posts/hello/">x</a>"#, "https://blog.example/posts/hello/").unwrap();
+        assert_eq!(plain.kind, Some(WebmentionKind::Mention));
+        assert_eq!(plain.title.as_deref(), Some("Links"));
+        assert!(read_source(r#"<a href="https://blog.example/posts/other/">x</a>"#, "https://blog.example/posts/hello/").is_none());
+    }
+
+    #[test]
+    fn private_sources_are_refused() {
+        for url in [
+            "http://localhost/",
+            "http://127.0.0.1:8000/",
+            "http://0.0.0.0/",
+            "http://10.1.2.3/",
+            "http://100.64.0.1/",
+            "http://169.254.169.254/",
+            "http://[::1]/",
+            "http://[fd00::1]/",
+            "http://[fec0::1]/",
+            "http://[64:ff9b::a01:203]/",
+            "http://[::ffff:192.168.0.1]/",
+        ] {
+            assert!(is_private(&Url::parse(url).unwrap()), "{url}");
+        }
+        assert!(!is_private(&Url::parse("https://ana.example/").unwrap()));
+        assert!(!is_private(&Url::parse("http://93.184.216.34/").unwrap()));
+    }
+}
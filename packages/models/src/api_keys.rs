@@ -12,6 +12,8 @@ pub struct Model {
     pub key_hash: String,
     #[sea_orm(column_type = "Text")]
     pub label: String,
+    #[sea_orm(column_type = "JsonBinary")]
+    pub scopes: Json,
     pub last_used_at: Option<DateTime>,
     pub created_at: DateTime,
 }
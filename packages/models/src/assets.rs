@@ -13,6 +13,8 @@ pub struct Model {
     #[sea_orm(column_type = "Text")]
     pub mime_type: String,
     pub size_bytes: i64,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
     pub created_at: DateTime,
 }
 
@@ -2,11 +2,26 @@
 
 pub mod prelude;
 
+pub mod activitypub_actors;
+pub mod activitypub_deliveries;
+pub mod activitypub_followers;
 pub mod api_keys;
 pub mod assets;
+pub mod comments;
+pub mod feed_pings;
+pub mod newsletter_deliveries;
+pub mod outgoing_webmentions;
 pub mod posts;
 pub mod refresh_tokens;
 pub mod sea_orm_active_enums;
+pub mod spam_corpora;
+pub mod spam_tokens;
 pub mod spatial_ref_sys;
+pub mod spent_pow_nonces;
+pub mod subscriber_tokens;
+pub mod subscribers;
 pub mod users;
 pub mod verification_tokens;
+pub mod webhook_deliveries;
+pub mod webhooks;
+pub mod webmentions;
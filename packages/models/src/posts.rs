@@ -19,6 +19,13 @@ pub struct Model {
     pub cover_image: Option<String>,
     pub user_id: Uuid,
     pub is_published: bool,
+    pub render_math: bool,
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub rendered_markdown: Option<Json>,
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub seo: Option<Json>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub preview_image: Option<String>,
     pub first_published_at: Option<DateTime>,
     pub created_at: DateTime,
     pub updated_at: DateTime,
@@ -26,6 +33,14 @@ pub struct Model {
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::comments::Entity")]
+    Comments,
+    #[sea_orm(has_many = "super::feed_pings::Entity")]
+    FeedPings,
+    #[sea_orm(has_many = "super::newsletter_deliveries::Entity")]
+    NewsletterDeliveries,
+    #[sea_orm(has_many = "super::outgoing_webmentions::Entity")]
+    OutgoingWebmentions,
     #[sea_orm(
         belongs_to = "super::users::Entity",
         from = "Column::UserId",
@@ -34,6 +49,32 @@ pub enum Relation {
         on_delete = "NoAction"
     )]
     Users,
+    #[sea_orm(has_many = "super::webmentions::Entity")]
+    Webmentions,
+}
+
+impl Related<super::comments::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Comments.def()
+    }
+}
+
+impl Related<super::feed_pings::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::FeedPings.def()
+    }
+}
+
+impl Related<super::newsletter_deliveries::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::NewsletterDeliveries.def()
+    }
+}
+
+impl Related<super::outgoing_webmentions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::OutgoingWebmentions.def()
+    }
 }
 
 impl Related<super::users::Entity> for Entity {
@@ -42,4 +83,10 @@ impl Related<super::users::Entity> for Entity {
     }
 }
 
+impl Related<super::webmentions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Webmentions.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
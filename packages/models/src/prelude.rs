@@ -1,9 +1,24 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.14
 
+pub use super::activitypub_actors::Entity as ActivitypubActors;
+pub use super::activitypub_deliveries::Entity as ActivitypubDeliveries;
+pub use super::activitypub_followers::Entity as ActivitypubFollowers;
 pub use super::api_keys::Entity as ApiKeys;
 pub use super::assets::Entity as Assets;
+pub use super::comments::Entity as Comments;
+pub use super::feed_pings::Entity as FeedPings;
+pub use super::newsletter_deliveries::Entity as NewsletterDeliveries;
+pub use super::outgoing_webmentions::Entity as OutgoingWebmentions;
 pub use super::posts::Entity as Posts;
 pub use super::refresh_tokens::Entity as RefreshTokens;
+pub use super::spam_corpora::Entity as SpamCorpora;
+pub use super::spam_tokens::Entity as SpamTokens;
 pub use super::spatial_ref_sys::Entity as SpatialRefSys;
+pub use super::spent_pow_nonces::Entity as SpentPowNonces;
+pub use super::subscriber_tokens::Entity as SubscriberTokens;
+pub use super::subscribers::Entity as Subscribers;
 pub use super::users::Entity as Users;
 pub use super::verification_tokens::Entity as VerificationTokens;
+pub use super::webhook_deliveries::Entity as WebhookDeliveries;
+pub use super::webhooks::Entity as Webhooks;
+pub use super::webmentions::Entity as Webmentions;
@@ -10,3 +10,84 @@ pub enum TokenKind {
     #[sea_orm(string_value = "password_reset")]
     PasswordReset,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "comment_policy")]
+pub enum CommentPolicy {
+    #[sea_orm(string_value = "closed")]
+    Closed,
+    #[sea_orm(string_value = "moderated")]
+    Moderated,
+    #[sea_orm(string_value = "open")]
+    Open,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "comment_status")]
+pub enum CommentStatus {
+    #[sea_orm(string_value = "approved")]
+    Approved,
+    #[sea_orm(string_value = "pending")]
+    Pending,
+    #[sea_orm(string_value = "rejected")]
+    Rejected,
+    #[sea_orm(string_value = "spam")]
+    Spam,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "subscriber_status")]
+pub enum SubscriberStatus {
+    #[sea_orm(string_value = "active")]
+    Active,
+    #[sea_orm(string_value = "pending")]
+    Pending,
+    #[sea_orm(string_value = "unsubscribed")]
+    Unsubscribed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "delivery_status")]
+pub enum DeliveryStatus {
+    #[sea_orm(string_value = "failed")]
+    Failed,
+    #[sea_orm(string_value = "queued")]
+    Queued,
+    #[sea_orm(string_value = "sent")]
+    Sent,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "ping_kind")]
+pub enum PingKind {
+    #[sea_orm(string_value = "websub")]
+    Websub,
+    #[sea_orm(string_value = "xmlrpc")]
+    Xmlrpc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "webmention_kind")]
+pub enum WebmentionKind {
+    #[sea_orm(string_value = "bookmark")]
+    Bookmark,
+    #[sea_orm(string_value = "like")]
+    Like,
+    #[sea_orm(string_value = "mention")]
+    Mention,
+    #[sea_orm(string_value = "reply")]
+    Reply,
+    #[sea_orm(string_value = "repost")]
+    Repost,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "webmention_verification")]
+pub enum WebmentionVerification {
+    #[sea_orm(string_value = "invalid")]
+    Invalid,
+    #[sea_orm(string_value = "queued")]
+    Queued,
+    #[sea_orm(string_value = "verified")]
+    Verified,
+}
@@ -1,5 +1,6 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.14
 
+use super::sea_orm_active_enums::CommentPolicy;
 use sea_orm::entity::prelude::*;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
@@ -18,20 +19,62 @@ pub struct Model {
     pub display_name: Option<String>,
     #[sea_orm(column_type = "Text", nullable)]
     pub bio: Option<String>,
+    pub comment_policy: CommentPolicy,
+    pub comments_close_after_days: Option<i32>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_one = "super::activitypub_actors::Entity")]
+    ActivitypubActors,
+    #[sea_orm(has_many = "super::activitypub_deliveries::Entity")]
+    ActivitypubDeliveries,
+    #[sea_orm(has_many = "super::activitypub_followers::Entity")]
+    ActivitypubFollowers,
     #[sea_orm(has_many = "super::api_keys::Entity")]
     ApiKeys,
     #[sea_orm(has_many = "super::assets::Entity")]
     Assets,
+    #[sea_orm(has_many = "super::comments::Entity")]
+    Comments,
+    #[sea_orm(has_many = "super::feed_pings::Entity")]
+    FeedPings,
+    #[sea_orm(has_many = "super::outgoing_webmentions::Entity")]
+    OutgoingWebmentions,
     #[sea_orm(has_many = "super::posts::Entity")]
     Posts,
     #[sea_orm(has_many = "super::refresh_tokens::Entity")]
     RefreshTokens,
+    #[sea_orm(has_one = "super::spam_corpora::Entity")]
+    SpamCorpora,
+    #[sea_orm(has_many = "super::spam_tokens::Entity")]
+    SpamTokens,
+    #[sea_orm(has_many = "super::subscribers::Entity")]
+    Subscribers,
     #[sea_orm(has_many = "super::verification_tokens::Entity")]
     VerificationTokens,
+    #[sea_orm(has_many = "super::webhooks::Entity")]
+    Webhooks,
+    #[sea_orm(has_many = "super::webmentions::Entity")]
+    Webmentions,
+}
+
+impl Related<super::activitypub_actors::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ActivitypubActors.def()
+    }
+}
+
+impl Related<super::activitypub_deliveries::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ActivitypubDeliveries.def()
+    }
+}
+
+impl Related<super::activitypub_followers::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ActivitypubFollowers.def()
+    }
 }
 
 impl Related<super::api_keys::Entity> for Entity {
@@ -46,6 +89,24 @@ impl Related<super::assets::Entity> for Entity {
     }
 }
 
+impl Related<super::comments::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Comments.def()
+    }
+}
+
+impl Related<super::feed_pings::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::FeedPings.def()
+    }
+}
+
+impl Related<super::outgoing_webmentions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::OutgoingWebmentions.def()
+    }
+}
+
 impl Related<super::posts::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Posts.def()
@@ -58,10 +119,40 @@ impl Related<super::refresh_tokens::Entity> for Entity {
     }
 }
 
+impl Related<super::spam_corpora::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::SpamCorpora.def()
+    }
+}
+
+impl Related<super::spam_tokens::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::SpamTokens.def()
+    }
+}
+
+impl Related<super::subscribers::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Subscribers.def()
+    }
+}
+
 impl Related<super::verification_tokens::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::VerificationTokens.def()
     }
 }
 
+impl Related<super::webhooks::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Webhooks.def()
+    }
+}
+
+impl Related<super::webmentions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Webmentions.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
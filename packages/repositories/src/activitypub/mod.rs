@@ -0,0 +1,485 @@
+use crate::comment::NewComment;
+use crate::queue::{self, Queue};
+use models::activitypub_actors::{self, Entity as Actors};
+use models::activitypub_deliveries::{self, Entity as Deliveries};
+use models::activitypub_followers::{self, Entity as Followers};
+use models::comments::{self, Entity as Comments};
+use models::sea_orm_active_enums::{CommentStatus, DeliveryStatus};
+use sea_orm::entity::prelude::Uuid;
+use sea_orm::*;
+use std::collections::HashMap;
+
+const DEFAULT_PAGE_SIZE: u64 = 20;
+const MAX_PAGE_SIZE: u64 = 100;
+
+pub const ACTIVITYPUB_DEFAULT_PAGE_SIZE: u64 = DEFAULT_PAGE_SIZE;
+
+const QUEUE: Queue = Queue {
+    table: "activitypub_deliveries",
+    state: "status",
+    returning: "id, user_id, inbox, activity, status::text as status, attempts, \
+                next_attempt_at, response_status, last_error, delivered_at, created_at",
+};
+
+pub struct ActivityPubRepository;
+
+impl ActivityPubRepository {
+    pub async fn actor_for_user(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+    ) -> Result<Option<activitypub_actors::Model>, String> {
+        Actors::find_by_id(user_id)
+            .one(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    pub async fn actor_by_username(
+        db: &DatabaseConnection,
+        username: &str,
+    ) -> Result<Option<activitypub_actors::Model>, String> {
+        Actors::find()
+            .filter(activitypub_actors::Column::Username.eq(username.to_lowercase()))
+            .one(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    /// Give `user_id`'s blog an actor named `username`, signing with the
+    /// given keys. Check the name is free first.
+    pub async fn create_actor(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        username: &str,
+        public_key_pem: String,
+        private_key_pem: String,
+    ) -> Result<activitypub_actors::Model, String> {
+        let am = activitypub_actors::ActiveModel {
+            user_id: ActiveValue::Set(user_id),
+            username: ActiveValue::Set(username.to_lowercase()),
+            public_key_pem: ActiveValue::Set(public_key_pem),
+            private_key_pem: ActiveValue::Set(private_key_pem),
+            created_at: ActiveValue::Set(chrono::Utc::now().naive_utc()),
+        };
+        Actors::insert(am)
+            .exec_with_returning(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    /// Record that `actor` follows `user_id`'s blog, or where its inboxes
+    /// are now if it already did.
+    pub async fn add_follower(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        actor: &str,
+        inbox: &str,
+        shared_inbox: Option<&str>,
+    ) -> Result<(), String> {
+        let am = activitypub_followers::ActiveModel {
+            id: ActiveValue::Set(Uuid::new_v4()),
+            user_id: ActiveValue::Set(user_id),
+            actor: ActiveValue::Set(actor.to_string()),
+            inbox: ActiveValue::Set(inbox.to_string()),
+            shared_inbox: ActiveValue::Set(shared_inbox.map(str::to_string)),
+            created_at: ActiveValue::Set(chrono::Utc::now().naive_utc()),
+        };
+        Followers::insert(am)
+            .on_conflict(
+                sea_query::OnConflict::columns([
+                    activitypub_followers::Column::UserId,
+                    activitypub_followers::Column::Actor,
+                ])
+                .update_columns([activitypub_followers::Column::Inbox, activitypub_followers::Column::SharedInbox])
+                .to_owned(),
+            )
+            .exec_without_returning(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        Ok(())
+    }
+
+    pub async fn remove_follower(db: &DatabaseConnection, user_id: Uuid, actor: &str) -> Result<bool, String> {
+        Followers::delete_many()
+            .filter(activitypub_followers::Column::UserId.eq(user_id))
+            .filter(activitypub_followers::Column::Actor.eq(actor))
+            .exec(db)
+            .await
+            .map(|r| r.rows_affected > 0)
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    pub async fn count_followers(db: &DatabaseConnection, user_id: Uuid) -> Result<u64, String> {
+        Followers::find()
+            .filter(activitypub_followers::Column::UserId.eq(user_id))
+            .count(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    /// `user_id`'s followers, newest first.
+    pub async fn list_followers(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        after_id: Option<Uuid>,
+        after_created_at: Option<chrono::NaiveDateTime>,
+        limit: Option<u64>,
+    ) -> Result<Vec<activitypub_followers::Model>, String> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+
+        let mut q = Followers::find()
+            .filter(activitypub_followers::Column::UserId.eq(user_id))
+            .order_by_desc(activitypub_followers::Column::CreatedAt)
+            .order_by_desc(activitypub_followers::Column::Id);
+        if let (Some(at), Some(aid)) = (after_created_at, after_id) {
+            q = q.filter(
+                Condition::any()
+                    .add(activitypub_followers::Column::CreatedAt.lt(at))
+                    .add(
+                        Condition::all()
+                            .add(activitypub_followers::Column::CreatedAt.eq(at))
+                            .add(activitypub_followers::Column::Id.lt(aid)),
+                    ),
+            );
+        }
+
+        q.limit(limit)
+            .all(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    /// The inboxes that reach all of `user_id`'s followers, a shared inbox
+    /// standing in for each follower on its server.
+    pub async fn follower_inboxes(db: &DatabaseConnection, user_id: Uuid) -> Result<Vec<String>, String> {
+        let followers = Followers::find()
+            .filter(activitypub_followers::Column::UserId.eq(user_id))
+            .all(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        let mut inboxes: Vec<String> = followers.into_iter().map(|f| f.shared_inbox.unwrap_or(f.inbox)).collect();
+        inboxes.sort();
+        inboxes.dedup();
+        Ok(inboxes)
+    }
+
+    /// Queue `activity` for each of `inboxes`. Returns how many deliveries
+    /// were queued.
+    pub async fn enqueue(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        inboxes: Vec<String>,
+        activity: serde_json::Value,
+    ) -> Result<u64, String> {
+        if inboxes.is_empty() {
+            return Ok(0);
+        }
+        let now = chrono::Utc::now().naive_utc();
+        let count = inboxes.len() as u64;
+        let rows = inboxes.into_iter().map(|inbox| activitypub_deliveries::ActiveModel {
+            id: ActiveValue::Set(Uuid::new_v4()),
+            user_id: ActiveValue::Set(user_id),
+            inbox: ActiveValue::Set(inbox),
+            activity: ActiveValue::Set(activity.clone()),
+            status: ActiveValue::Set(DeliveryStatus::Queued),
+            attempts: ActiveValue::Set(0),
+            next_attempt_at: ActiveValue::Set(now),
+            created_at: ActiveValue::Set(now),
+            ..Default::default()
+        });
+        Deliveries::insert_many(rows)
+            .exec(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        Ok(count)
+    }
+
+    /// Take up to `limit` queued deliveries that are due, counting an
+    /// attempt for each, along with the actor each is sent as.
+    pub async fn claim_due(
+        db: &DatabaseConnection,
+        limit: u64,
+    ) -> Result<Vec<(activitypub_deliveries::Model, activitypub_actors::Model)>, String> {
+        let deliveries = QUEUE.claim_due::<Deliveries>(db, limit).await?;
+        if deliveries.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let user_ids: Vec<Uuid> = deliveries.iter().map(|d| d.user_id).collect();
+        let actors: HashMap<Uuid, activitypub_actors::Model> = Actors::find()
+            .filter(activitypub_actors::Column::UserId.is_in(user_ids))
+            .all(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?
+            .into_iter()
+            .map(|a| (a.user_id, a))
+            .collect();
+        Ok(deliveries
+            .into_iter()
+            .filter_map(|d| {
+                let actor = actors.get(&d.user_id)?.clone();
+                Some((d, actor))
+            })
+            .collect())
+    }
+
+    pub async fn mark_delivered(
+        db: &DatabaseConnection,
+        id: Uuid,
+        response_status: Option<i32>,
+    ) -> Result<(), String> {
+        Deliveries::update_many()
+            .set(activitypub_deliveries::ActiveModel {
+                status: ActiveValue::Set(DeliveryStatus::Sent),
+                response_status: ActiveValue::Set(response_status),
+                last_error: ActiveValue::Set(None),
+                delivered_at: ActiveValue::Set(Some(chrono::Utc::now().naive_utc())),
+                ..Default::default()
+            })
+            .filter(activitypub_deliveries::Column::Id.eq(id))
+            .exec(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        Ok(())
+    }
+
+    /// Record a failed attempt. The delivery is retried later, backing off,
+    /// until it has had `max_attempts`.
+    pub async fn mark_failed(
+        db: &DatabaseConnection,
+        delivery: &activitypub_deliveries::Model,
+        response_status: Option<i32>,
+        error: String,
+        max_attempts: i32,
+    ) -> Result<(), String> {
+        let mut am = activitypub_deliveries::ActiveModel {
+            response_status: ActiveValue::Set(response_status),
+            last_error: ActiveValue::Set(Some(error)),
+            ..Default::default()
+        };
+        match queue::retry_at(delivery.attempts, max_attempts) {
+            Some(at) => am.next_attempt_at = ActiveValue::Set(at),
+            None => am.status = ActiveValue::Set(DeliveryStatus::Failed),
+        }
+        Deliveries::update_many()
+            .set(am)
+            .filter(activitypub_deliveries::Column::Id.eq(delivery.id))
+            .exec(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        Ok(())
+    }
+
+    /// Activities sent, or to be sent, as `user_id`'s actor, newest first.
+    pub async fn list_deliveries(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        status: Option<DeliveryStatus>,
+        after_id: Option<Uuid>,
+        after_created_at: Option<chrono::NaiveDateTime>,
+        limit: Option<u64>,
+    ) -> Result<Vec<activitypub_deliveries::Model>, String> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+
+        let mut q = Deliveries::find()
+            .filter(activitypub_deliveries::Column::UserId.eq(user_id))
+            .order_by_desc(activitypub_deliveries::Column::CreatedAt)
+            .order_by_desc(activitypub_deliveries::Column::Id);
+        if let Some(status) = status {
+            q = q.filter(activitypub_deliveries::Column::Status.eq(status));
+        }
+        if let (Some(at), Some(aid)) = (after_created_at, after_id) {
+            q = q.filter(
+                Condition::any()
+                    .add(activitypub_deliveries::Column::CreatedAt.lt(at))
+                    .add(
+                        Condition::all()
+                            .add(activitypub_deliveries::Column::CreatedAt.eq(at))
+                            .add(activitypub_deliveries::Column::Id.lt(aid)),
+                    ),
+            );
+        }
+
+        q.limit(limit)
+            .all(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    /// Record a reply from the fediverse, the object `object_id` sent by
+    /// `actor_id`, as a comment. `None` if the blog already recorded it.
+    pub async fn record_reply(
+        db: &DatabaseConnection,
+        comment: NewComment,
+        object_id: &str,
+        actor_id: &str,
+        status: CommentStatus,
+        spam_score: Option<f32>,
+    ) -> Result<Option<comments::Model>, String> {
+        let now = chrono::Utc::now().naive_utc();
+        let am = comments::ActiveModel {
+            id: ActiveValue::Set(Uuid::new_v4()),
+            post_id: ActiveValue::Set(comment.post_id),
+            user_id: ActiveValue::Set(comment.user_id),
+            parent_id: ActiveValue::Set(comment.parent_id),
+            author_name: ActiveValue::Set(comment.author_name),
+            author_email: ActiveValue::Set(comment.author_email),
+            author_url: ActiveValue::Set(comment.author_url),
+            body: ActiveValue::Set(comment.body),
+            status: ActiveValue::Set(status),
+            spam_score: ActiveValue::Set(spam_score),
+            spam_trained: ActiveValue::Set(None),
+            activitypub_id: ActiveValue::Set(Some(object_id.to_string())),
+            activitypub_actor: ActiveValue::Set(Some(actor_id.to_string())),
+            created_at: ActiveValue::Set(now),
+            moderated_at: ActiveValue::Set((status != CommentStatus::Pending).then_some(now)),
+        };
+        let id = am.id.clone().unwrap();
+        let result = Comments::insert(am)
+            .on_conflict(
+                sea_query::OnConflict::columns([comments::Column::UserId, comments::Column::ActivitypubId])
+                    .do_nothing()
+                    .to_owned(),
+            )
+            .do_nothing()
+            .exec(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        if !matches!(result, TryInsertResult::Inserted(_)) {
+            return Ok(None);
+        }
+        Comments::find_by_id(id).one(db).await.map_err(|e| format!("Database error: {e}"))
+    }
+
+    /// The comment the blog recorded from the object `object_id`.
+    pub async fn reply(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        object_id: &str,
+    ) -> Result<Option<comments::Model>, String> {
+        Comments::find()
+            .filter(comments::Column::UserId.eq(user_id))
+            .filter(comments::Column::ActivitypubId.eq(object_id))
+            .one(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    /// Forget the comment the blog recorded from `object_id`, its author
+    /// `actor_id` having deleted it. Nothing happens if another actor sent it.
+    pub async fn delete_reply(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        actor_id: &str,
+        object_id: &str,
+    ) -> Result<bool, String> {
+        Comments::delete_many()
+            .filter(comments::Column::UserId.eq(user_id))
+            .filter(comments::Column::ActivitypubActor.eq(actor_id))
+            .filter(comments::Column::ActivitypubId.eq(object_id))
+            .exec(db)
+            .await
+            .map(|r| r.rows_affected > 0)
+            .map_err(|e| format!("Database error: {e}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::*;
+
+    #[tokio::test]
+    async fn test_followers_deliveries_and_replies() {
+        let db = setup_test_db().await;
+        let (owner, email) = create_test_user(&db, "repo_activitypub").await;
+        let post = create_test_post(&db, owner.id, "Federated", "Body", true).await;
+
+        let username = format!("blog{}", owner.id.simple());
+        let actor = ActivityPubRepository::create_actor(&db, owner.id, &username, "public".into(), "private".into())
+            .await
+            .unwrap();
+        assert_eq!(ActivityPubRepository::actor_by_username(&db, &username.to_uppercase()).await.unwrap(), Some(actor));
+
+        let shared = Some("https://fedi.example/inbox");
+        ActivityPubRepository::add_follower(&db, owner.id, "https://fedi.example/users/a", "https://fedi.example/users/a/inbox", shared)
+            .await
+            .unwrap();
+        ActivityPubRepository::add_follower(&db, owner.id, "https://fedi.example/users/b", "https://fedi.example/users/b/inbox", shared)
+            .await
+            .unwrap();
+        ActivityPubRepository::add_follower(&db, owner.id, "https://solo.example/me", "https://solo.example/me/inbox", None)
+            .await
+            .unwrap();
+        // Following again only moves the inbox.
+        ActivityPubRepository::add_follower(&db, owner.id, "https://solo.example/me", "https://solo.example/inbox", None)
+            .await
+            .unwrap();
+        assert_eq!(ActivityPubRepository::count_followers(&db, owner.id).await.unwrap(), 3);
+        let inboxes = ActivityPubRepository::follower_inboxes(&db, owner.id).await.unwrap();
+        assert_eq!(inboxes, ["https://fedi.example/inbox", "https://solo.example/inbox"]);
+        assert!(ActivityPubRepository::remove_follower(&db, owner.id, "https://fedi.example/users/b").await.unwrap());
+        assert_eq!(ActivityPubRepository::list_followers(&db, owner.id, None, None, None).await.unwrap().len(), 2);
+
+        let queued = ActivityPubRepository::enqueue(&db, owner.id, inboxes, serde_json::json!({"type": "Create"}))
+            .await
+            .unwrap();
+        assert_eq!(queued, 2);
+        let log = ActivityPubRepository::list_deliveries(&db, owner.id, Some(DeliveryStatus::Queued), None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(log.len(), 2);
+        ActivityPubRepository::mark_failed(&db, &log[0], Some(410), "HTTP 410 Gone".to_string(), 0).await.unwrap();
+        let failed = ActivityPubRepository::list_deliveries(&db, owner.id, Some(DeliveryStatus::Failed), None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(failed.len(), 1);
+
+        let reply = NewComment {
+            post_id: post.id,
+            user_id: owner.id,
+            parent_id: None,
+            author_name: "Ana".to_string(),
+            author_email: None,
+            author_url: Some("https://fedi.example/@a".to_string()),
+            body: "Nice post".to_string(),
+        };
+        let object_id = format!("https://fedi.example/users/a/statuses/{}", post.id.simple());
+        let sender = "https://fedi.example/users/a";
+        let saved = ActivityPubRepository::record_reply(&db, reply.clone(), &object_id, sender, CommentStatus::Pending, Some(0.1))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(saved.activitypub_id.as_deref(), Some(object_id.as_str()));
+        assert_eq!(saved.activitypub_actor.as_deref(), Some(sender));
+        // The same object delivered twice is one comment.
+        assert!(ActivityPubRepository::record_reply(&db, reply.clone(), &object_id, sender, CommentStatus::Pending, None)
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(ActivityPubRepository::reply(&db, owner.id, &object_id).await.unwrap().map(|c| c.id), Some(saved.id));
+
+        // Another blog mentioned in the same object keeps its own copy.
+        let (other, other_email) = create_test_user(&db, "repo_activitypub_other").await;
+        let other_post = create_test_post(&db, other.id, "Mentioned", "Body", true).await;
+        let copy = NewComment { post_id: other_post.id, user_id: other.id, ..reply };
+        let copied = ActivityPubRepository::record_reply(&db, copy, &object_id, sender, CommentStatus::Pending, None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_ne!(copied.id, saved.id);
+
+        // Only the actor who sent it may delete it, and only from the blog that got it.
+        assert!(!ActivityPubRepository::delete_reply(&db, owner.id, "https://fedi.example/users/b", &object_id)
+            .await
+            .unwrap());
+        assert!(!ActivityPubRepository::delete_reply(&db, Uuid::new_v4(), sender, &object_id).await.unwrap());
+        assert!(ActivityPubRepository::reply(&db, owner.id, &object_id).await.unwrap().is_some());
+        assert!(ActivityPubRepository::delete_reply(&db, owner.id, sender, &object_id).await.unwrap());
+        assert!(ActivityPubRepository::reply(&db, owner.id, &object_id).await.unwrap().is_none());
+        assert!(ActivityPubRepository::reply(&db, other.id, &object_id).await.unwrap().is_some());
+
+        cleanup_user_by_email(&db, &other_email).await;
+        cleanup_user_by_email(&db, &email).await;
+    }
+}
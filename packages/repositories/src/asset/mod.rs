@@ -1,6 +1,7 @@
 use data_access_objects::verify_ownership;
 use models::assets::{self, ActiveModel, Column, Entity, Model};
 use sea_orm::entity::prelude::Uuid;
+use sea_orm::sea_query::Expr;
 use sea_orm::*;
 
 const DEFAULT_PAGE_SIZE: u64 = 20;
@@ -9,6 +10,7 @@ const MAX_PAGE_SIZE: u64 = 100;
 pub struct AssetRepository;
 
 impl AssetRepository {
+    #[allow(clippy::too_many_arguments)]
     pub async fn create(
         db: &DatabaseConnection,
         id: Uuid,
@@ -16,6 +18,8 @@ impl AssetRepository {
         original_filename: String,
         mime_type: String,
         size_bytes: i64,
+        width: i32,
+        height: i32,
     ) -> Result<Model, DbErr> {
         let am = ActiveModel {
             id: ActiveValue::Set(id),
@@ -23,6 +27,8 @@ impl AssetRepository {
             original_filename: ActiveValue::Set(original_filename),
             mime_type: ActiveValue::Set(mime_type),
             size_bytes: ActiveValue::Set(size_bytes),
+            width: ActiveValue::Set(Some(width)),
+            height: ActiveValue::Set(Some(height)),
             created_at: ActiveValue::Set(chrono::Utc::now().naive_utc()),
         };
         Entity::insert(am)
@@ -72,6 +78,73 @@ impl AssetRepository {
             .map_err(|e| format!("Database error: {e}"))
     }
 
+    /// `user_id`'s assets among `ids`; ids of other users' assets are ignored.
+    pub async fn find_many_for_user(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        ids: &[Uuid],
+    ) -> Result<Vec<Model>, String> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        Entity::find()
+            .filter(Column::UserId.eq(user_id))
+            .filter(Column::Id.is_in(ids.iter().copied()))
+            .all(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    /// Record the pixel size of an asset's original, e.g. after reprocessing.
+    pub async fn set_dimensions(
+        db: &DatabaseConnection,
+        id: Uuid,
+        width: i32,
+        height: i32,
+    ) -> Result<(), String> {
+        Entity::update_many()
+            .col_expr(Column::Width, Expr::value(width))
+            .col_expr(Column::Height, Expr::value(height))
+            .filter(Column::Id.eq(id))
+            .exec(db)
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    /// Unscoped lookup for admin tooling; prefer `get` for user-facing paths.
+    pub async fn find_by_id(
+        db: &DatabaseConnection,
+        id: Uuid,
+    ) -> Result<Option<Model>, String> {
+        Entity::find_by_id(id)
+            .one(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    pub async fn all_for_user(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+    ) -> Result<Vec<Model>, String> {
+        Entity::find()
+            .filter(Column::UserId.eq(user_id))
+            .order_by_asc(Column::CreatedAt)
+            .all(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    pub async fn all_ids(db: &DatabaseConnection) -> Result<Vec<Uuid>, String> {
+        Entity::find()
+            .select_only()
+            .column(Column::Id)
+            .into_tuple()
+            .all(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
     pub async fn delete(
         db: &DatabaseConnection,
         user_id: Uuid,
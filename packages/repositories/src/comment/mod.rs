@@ -0,0 +1,249 @@
+use data_access_objects::verify_ownership;
+use models::comments::{ActiveModel, Column, Entity, Model};
+use models::sea_orm_active_enums::CommentStatus;
+use sea_orm::entity::prelude::Uuid;
+use sea_orm::*;
+
+const DEFAULT_PAGE_SIZE: u64 = 20;
+const MAX_PAGE_SIZE: u64 = 100;
+
+pub const COMMENT_DEFAULT_PAGE_SIZE: u64 = DEFAULT_PAGE_SIZE;
+
+/// A submitted comment, before it has an id or a status.
+#[derive(Clone, Debug)]
+pub struct NewComment {
+    pub post_id: Uuid,
+    /// The blog owner, who moderates the comment
+    pub user_id: Uuid,
+    pub parent_id: Option<Uuid>,
+    pub author_name: String,
+    pub author_email: Option<String>,
+    pub author_url: Option<String>,
+    pub body: String,
+}
+
+pub struct CommentRepository;
+
+impl CommentRepository {
+    pub async fn create(
+        db: &DatabaseConnection,
+        comment: NewComment,
+        status: CommentStatus,
+        spam_score: Option<f32>,
+    ) -> Result<Model, String> {
+        let now = chrono::Utc::now().naive_utc();
+        let am = ActiveModel {
+            id: ActiveValue::Set(Uuid::new_v4()),
+            post_id: ActiveValue::Set(comment.post_id),
+            user_id: ActiveValue::Set(comment.user_id),
+            parent_id: ActiveValue::Set(comment.parent_id),
+            author_name: ActiveValue::Set(comment.author_name),
+            author_email: ActiveValue::Set(comment.author_email),
+            author_url: ActiveValue::Set(comment.author_url),
+            body: ActiveValue::Set(comment.body),
+            status: ActiveValue::Set(status),
+            spam_score: ActiveValue::Set(spam_score),
+            spam_trained: ActiveValue::Set(None),
+            activitypub_id: ActiveValue::Set(None),
+            activitypub_actor: ActiveValue::Set(None),
+            created_at: ActiveValue::Set(now),
+            moderated_at: ActiveValue::Set((status != CommentStatus::Pending).then_some(now)),
+        };
+        Entity::insert(am)
+            .exec_with_returning(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    pub async fn get(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        id: Uuid,
+    ) -> Result<Option<Model>, String> {
+        verify_ownership::<Entity>(db, id, user_id)
+            .await
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    /// Unscoped lookup, e.g. for a reply's parent on a public submission.
+    pub async fn find_by_id(
+        db: &DatabaseConnection,
+        id: Uuid,
+    ) -> Result<Option<Model>, String> {
+        Entity::find_by_id(id)
+            .one(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    /// Comments on `user_id`'s posts, newest first, for moderation.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn list(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        status: Option<CommentStatus>,
+        post_id: Option<Uuid>,
+        after_id: Option<Uuid>,
+        after_created_at: Option<chrono::NaiveDateTime>,
+        limit: Option<u64>,
+    ) -> Result<Vec<Model>, String> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+
+        let mut q = Entity::find()
+            .filter(Column::UserId.eq(user_id))
+            .order_by_desc(Column::CreatedAt)
+            .order_by_desc(Column::Id);
+        if let Some(status) = status {
+            q = q.filter(Column::Status.eq(status));
+        }
+        if let Some(post_id) = post_id {
+            q = q.filter(Column::PostId.eq(post_id));
+        }
+        if let (Some(at), Some(aid)) = (after_created_at, after_id) {
+            q = q.filter(
+                Condition::any()
+                    .add(Column::CreatedAt.lt(at))
+                    .add(
+                        Condition::all()
+                            .add(Column::CreatedAt.eq(at))
+                            .add(Column::Id.lt(aid)),
+                    ),
+            );
+        }
+
+        q.limit(limit)
+            .all(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    /// A post's approved comments, oldest first.
+    pub async fn approved_for_post(
+        db: &DatabaseConnection,
+        post_id: Uuid,
+    ) -> Result<Vec<Model>, String> {
+        Entity::find()
+            .filter(Column::PostId.eq(post_id))
+            .filter(Column::Status.eq(CommentStatus::Approved))
+            .order_by_asc(Column::CreatedAt)
+            .order_by_asc(Column::Id)
+            .all(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    pub async fn count_approved_for_post(
+        db: &DatabaseConnection,
+        post_id: Uuid,
+    ) -> Result<u64, String> {
+        Entity::find()
+            .filter(Column::PostId.eq(post_id))
+            .filter(Column::Status.eq(CommentStatus::Approved))
+            .count(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    /// Approve, reject or mark spam one of `user_id`'s comments. `None` if
+    /// there is no such comment.
+    pub async fn set_status(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        id: Uuid,
+        status: CommentStatus,
+    ) -> Result<Option<Model>, String> {
+        let result = Entity::update_many()
+            .set(ActiveModel {
+                status: ActiveValue::Set(status),
+                moderated_at: ActiveValue::Set(Some(chrono::Utc::now().naive_utc())),
+                ..Default::default()
+            })
+            .filter(Column::Id.eq(id))
+            .filter(Column::UserId.eq(user_id))
+            .exec(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        if result.rows_affected == 0 {
+            return Ok(None);
+        }
+        Self::get(db, user_id, id).await
+    }
+
+    /// Delete one of `user_id`'s comments along with its replies.
+    pub async fn delete(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        id: Uuid,
+    ) -> Result<bool, String> {
+        let result = Entity::delete_many()
+            .filter(Column::Id.eq(id))
+            .filter(Column::UserId.eq(user_id))
+            .exec(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        Ok(result.rows_affected > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::*;
+    use crate::PostRepository;
+
+    fn comment(post: &models::posts::Model, parent_id: Option<Uuid>, body: &str) -> NewComment {
+        NewComment {
+            post_id: post.id,
+            user_id: post.user_id,
+            parent_id,
+            author_name: "Reader".to_string(),
+            author_email: None,
+            author_url: None,
+            body: body.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_moderation_is_scoped_to_blog_owner() {
+        let db = setup_test_db().await;
+        let (owner, owner_email) = create_test_user(&db, "repo_comment_owner").await;
+        let (other, other_email) = create_test_user(&db, "repo_comment_other").await;
+        let post = create_test_post(&db, owner.id, "Title", "Body", true).await;
+
+        let pending = CommentRepository::create(&db, comment(&post, None, "first"), CommentStatus::Pending, None)
+            .await
+            .unwrap();
+        assert_eq!(pending.moderated_at, None);
+        let approved = CommentRepository::create(&db, comment(&post, None, "second"), CommentStatus::Approved, None)
+            .await
+            .unwrap();
+        let reply = CommentRepository::create(&db, comment(&post, Some(approved.id), "reply"), CommentStatus::Approved, None)
+            .await
+            .unwrap();
+
+        let queue = CommentRepository::list(&db, owner.id, Some(CommentStatus::Pending), None, None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(queue.iter().map(|c| c.id).collect::<Vec<_>>(), vec![pending.id]);
+        assert!(CommentRepository::list(&db, other.id, None, None, None, None, None).await.unwrap().is_empty());
+
+        assert_eq!(CommentRepository::set_status(&db, other.id, pending.id, CommentStatus::Spam).await.unwrap(), None);
+        let moderated = CommentRepository::set_status(&db, owner.id, pending.id, CommentStatus::Approved)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(moderated.status, CommentStatus::Approved);
+        assert!(moderated.moderated_at.is_some());
+
+        let shown = CommentRepository::approved_for_post(&db, post.id).await.unwrap();
+        assert_eq!(shown.iter().map(|c| c.id).collect::<Vec<_>>(), vec![pending.id, approved.id, reply.id]);
+
+        assert!(!CommentRepository::delete(&db, other.id, approved.id).await.unwrap());
+        assert!(CommentRepository::delete(&db, owner.id, approved.id).await.unwrap());
+        assert_eq!(CommentRepository::count_approved_for_post(&db, post.id).await.unwrap(), 1);
+
+        PostRepository::delete_post(&db, owner.id, post.id).await.unwrap();
+        cleanup_user_by_email(&db, &owner_email).await;
+        cleanup_user_by_email(&db, &other_email).await;
+    }
+}
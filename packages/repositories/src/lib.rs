@@ -1,10 +1,27 @@
+pub mod activitypub;
 pub mod asset;
+pub mod comment;
+pub mod newsletter;
+pub mod ping;
 pub mod post;
+pub mod pow;
+mod queue;
+pub mod spam;
 pub mod user;
+pub mod webhook;
+pub mod webmention;
 
+pub use activitypub::{ACTIVITYPUB_DEFAULT_PAGE_SIZE, ActivityPubRepository};
 pub use asset::{ASSET_DEFAULT_PAGE_SIZE, AssetModel, AssetRepository};
+pub use comment::{COMMENT_DEFAULT_PAGE_SIZE, CommentRepository, NewComment};
+pub use newsletter::{NewsletterRepository, SUBSCRIBER_DEFAULT_PAGE_SIZE, SubscriberRepository};
+pub use ping::{FEED_PING_DEFAULT_PAGE_SIZE, NewPing, PingRepository};
 pub use post::{PaginatedPosts, PostRepository, PostSortBy, SortDirection};
+pub use pow::PowRepository;
+pub use spam::SpamRepository;
 pub use user::UserRepository;
+pub use webhook::{DeliveryResponse, WEBHOOK_DELIVERY_DEFAULT_PAGE_SIZE, WebhookRepository};
+pub use webmention::{MentionDetails, WEBMENTION_DEFAULT_PAGE_SIZE, WebmentionRepository};
 
 #[cfg(test)]
 mod test_helpers;
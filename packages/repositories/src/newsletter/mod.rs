@@ -0,0 +1,347 @@
+use crate::queue::{self, Queue};
+use models::newsletter_deliveries::{self, Entity as Deliveries};
+use models::posts;
+use models::sea_orm_active_enums::{DeliveryStatus, SubscriberStatus};
+use models::subscribers::{self, Entity as Subscribers};
+use sea_orm::entity::prelude::Uuid;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::*;
+
+const DEFAULT_PAGE_SIZE: u64 = 20;
+const MAX_PAGE_SIZE: u64 = 100;
+
+pub const SUBSCRIBER_DEFAULT_PAGE_SIZE: u64 = DEFAULT_PAGE_SIZE;
+
+const QUEUE: Queue = Queue {
+    table: "newsletter_deliveries",
+    state: "status",
+    returning: "id, post_id, subscriber_id, status::text as status, attempts, next_attempt_at, \
+                last_error, sent_at, created_at",
+};
+
+pub struct SubscriberRepository;
+
+impl SubscriberRepository {
+    /// Sign `email` up for `user_id`'s posts, or sign a former subscriber up
+    /// again. The subscription is pending until confirmed; `None` if the
+    /// address is already subscribed.
+    pub async fn subscribe(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        email: String,
+    ) -> Result<Option<subscribers::Model>, String> {
+        Subscribers::insert(subscribers::ActiveModel {
+            id: ActiveValue::Set(Uuid::new_v4()),
+            user_id: ActiveValue::Set(user_id),
+            email: ActiveValue::Set(email.clone()),
+            status: ActiveValue::Set(SubscriberStatus::Pending),
+            created_at: ActiveValue::Set(chrono::Utc::now().naive_utc()),
+            ..Default::default()
+        })
+        .on_conflict(
+            OnConflict::columns([subscribers::Column::UserId, subscribers::Column::Email])
+                .do_nothing()
+                .to_owned(),
+        )
+        .do_nothing()
+        .exec(db)
+        .await
+        .map_err(|e| format!("Database error: {e}"))?;
+
+        let subscriber = Subscribers::find()
+            .filter(subscribers::Column::UserId.eq(user_id))
+            .filter(subscribers::Column::Email.eq(email))
+            .one(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?
+            .ok_or_else(|| "Subscriber not found".to_string())?;
+        match subscriber.status {
+            SubscriberStatus::Active => Ok(None),
+            SubscriberStatus::Pending => Ok(Some(subscriber)),
+            SubscriberStatus::Unsubscribed => {
+                let am = subscribers::ActiveModel {
+                    id: ActiveValue::Unchanged(subscriber.id),
+                    status: ActiveValue::Set(SubscriberStatus::Pending),
+                    ..Default::default()
+                };
+                am.update(db).await.map(Some).map_err(|e| format!("Database error: {e}"))
+            }
+        }
+    }
+
+    pub async fn find_by_id(
+        db: &DatabaseConnection,
+        id: Uuid,
+    ) -> Result<Option<subscribers::Model>, String> {
+        Subscribers::find_by_id(id)
+            .one(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    /// Start sending posts to a subscriber who followed their confirmation
+    /// link.
+    pub async fn confirm(
+        db: &DatabaseConnection,
+        id: Uuid,
+    ) -> Result<Option<subscribers::Model>, String> {
+        let am = subscribers::ActiveModel {
+            id: ActiveValue::Unchanged(id),
+            status: ActiveValue::Set(SubscriberStatus::Active),
+            confirmed_at: ActiveValue::Set(Some(chrono::Utc::now().naive_utc())),
+            unsubscribed_at: ActiveValue::Set(None),
+            ..Default::default()
+        };
+        match am.update(db).await {
+            Ok(subscriber) => Ok(Some(subscriber)),
+            Err(DbErr::RecordNotUpdated) => Ok(None),
+            Err(e) => Err(format!("Database error: {e}")),
+        }
+    }
+
+    /// Stop sending posts to a subscriber. Emails still queued for them are
+    /// marked failed rather than sent.
+    pub async fn unsubscribe(
+        db: &DatabaseConnection,
+        id: Uuid,
+    ) -> Result<Option<subscribers::Model>, String> {
+        let txn = db.begin().await.map_err(|e| format!("Database error: {e}"))?;
+        let am = subscribers::ActiveModel {
+            id: ActiveValue::Unchanged(id),
+            status: ActiveValue::Set(SubscriberStatus::Unsubscribed),
+            unsubscribed_at: ActiveValue::Set(Some(chrono::Utc::now().naive_utc())),
+            ..Default::default()
+        };
+        let subscriber = match am.update(&txn).await {
+            Ok(subscriber) => subscriber,
+            Err(DbErr::RecordNotUpdated) => return Ok(None),
+            Err(e) => return Err(format!("Database error: {e}")),
+        };
+        Deliveries::update_many()
+            .set(newsletter_deliveries::ActiveModel {
+                status: ActiveValue::Set(DeliveryStatus::Failed),
+                last_error: ActiveValue::Set(Some("Unsubscribed".to_string())),
+                ..Default::default()
+            })
+            .filter(newsletter_deliveries::Column::SubscriberId.eq(id))
+            .filter(newsletter_deliveries::Column::Status.eq(DeliveryStatus::Queued))
+            .exec(&txn)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        txn.commit().await.map_err(|e| format!("Database error: {e}"))?;
+        Ok(Some(subscriber))
+    }
+
+    /// `user_id`'s subscribers, newest first.
+    pub async fn list(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        status: Option<SubscriberStatus>,
+        after_id: Option<Uuid>,
+        after_created_at: Option<chrono::NaiveDateTime>,
+        limit: Option<u64>,
+    ) -> Result<Vec<subscribers::Model>, String> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+
+        let mut q = Subscribers::find()
+            .filter(subscribers::Column::UserId.eq(user_id))
+            .order_by_desc(subscribers::Column::CreatedAt)
+            .order_by_desc(subscribers::Column::Id);
+        if let Some(status) = status {
+            q = q.filter(subscribers::Column::Status.eq(status));
+        }
+        if let (Some(at), Some(aid)) = (after_created_at, after_id) {
+            q = q.filter(
+                Condition::any()
+                    .add(subscribers::Column::CreatedAt.lt(at))
+                    .add(
+                        Condition::all()
+                            .add(subscribers::Column::CreatedAt.eq(at))
+                            .add(subscribers::Column::Id.lt(aid)),
+                    ),
+            );
+        }
+
+        q.limit(limit)
+            .all(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    /// Delete one of `user_id`'s subscribers along with their deliveries.
+    pub async fn delete(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        id: Uuid,
+    ) -> Result<bool, String> {
+        let result = Subscribers::delete_many()
+            .filter(subscribers::Column::Id.eq(id))
+            .filter(subscribers::Column::UserId.eq(user_id))
+            .exec(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        Ok(result.rows_affected > 0)
+    }
+}
+
+pub struct NewsletterRepository;
+
+impl NewsletterRepository {
+    /// Queue `post` for every active subscriber of its blog. Subscribers it
+    /// was already queued for are skipped, so this is safe to repeat.
+    pub async fn enqueue_post(
+        db: &DatabaseConnection,
+        post: &posts::Model,
+    ) -> Result<u64, String> {
+        let stmt = Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            "insert into newsletter_deliveries (post_id, subscriber_id) \
+             select $1, id from subscribers where user_id = $2 and status = 'active' \
+             on conflict (post_id, subscriber_id) do nothing",
+            [post.id.into(), post.user_id.into()],
+        );
+        db.execute(stmt)
+            .await
+            .map(|r| r.rows_affected())
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    /// Take up to `limit` queued deliveries that are due, counting an attempt
+    /// for each. Claimed deliveries aren't due again for a while, so
+    /// concurrent senders don't send the same email.
+    pub async fn claim_due(
+        db: &DatabaseConnection,
+        limit: u64,
+    ) -> Result<Vec<newsletter_deliveries::Model>, String> {
+        QUEUE.claim_due::<Deliveries>(db, limit).await
+    }
+
+    pub async fn mark_sent(db: &DatabaseConnection, id: Uuid) -> Result<(), String> {
+        Deliveries::update_many()
+            .set(newsletter_deliveries::ActiveModel {
+                status: ActiveValue::Set(DeliveryStatus::Sent),
+                sent_at: ActiveValue::Set(Some(chrono::Utc::now().naive_utc())),
+                last_error: ActiveValue::Set(None),
+                ..Default::default()
+            })
+            .filter(newsletter_deliveries::Column::Id.eq(id))
+            .exec(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        Ok(())
+    }
+
+    /// Record a failed attempt. The delivery is retried later, backing off,
+    /// until it has had `max_attempts`.
+    pub async fn mark_failed(
+        db: &DatabaseConnection,
+        delivery: &newsletter_deliveries::Model,
+        error: String,
+        max_attempts: i32,
+    ) -> Result<(), String> {
+        let mut am = newsletter_deliveries::ActiveModel {
+            last_error: ActiveValue::Set(Some(error)),
+            ..Default::default()
+        };
+        match queue::retry_at(delivery.attempts, max_attempts) {
+            Some(at) => am.next_attempt_at = ActiveValue::Set(at),
+            None => am.status = ActiveValue::Set(DeliveryStatus::Failed),
+        }
+        Deliveries::update_many()
+            .set(am)
+            .filter(newsletter_deliveries::Column::Id.eq(delivery.id))
+            .exec(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        Ok(())
+    }
+
+    /// Deliveries of one of `user_id`'s posts with their subscribers, newest
+    /// first.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn list_for_post(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        post_id: Uuid,
+        status: Option<DeliveryStatus>,
+        after_id: Option<Uuid>,
+        after_created_at: Option<chrono::NaiveDateTime>,
+        limit: Option<u64>,
+    ) -> Result<Vec<(newsletter_deliveries::Model, subscribers::Model)>, String> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+
+        let mut q = Deliveries::find()
+            .find_also_related(Subscribers)
+            .filter(newsletter_deliveries::Column::PostId.eq(post_id))
+            .filter(subscribers::Column::UserId.eq(user_id))
+            .order_by_desc(newsletter_deliveries::Column::CreatedAt)
+            .order_by_desc(newsletter_deliveries::Column::Id);
+        if let Some(status) = status {
+            q = q.filter(newsletter_deliveries::Column::Status.eq(status));
+        }
+        if let (Some(at), Some(aid)) = (after_created_at, after_id) {
+            q = q.filter(
+                Condition::any()
+                    .add(newsletter_deliveries::Column::CreatedAt.lt(at))
+                    .add(
+                        Condition::all()
+                            .add(newsletter_deliveries::Column::CreatedAt.eq(at))
+                            .add(newsletter_deliveries::Column::Id.lt(aid)),
+                    ),
+            );
+        }
+
+        let rows = q
+            .limit(limit)
+            .all(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        Ok(rows.into_iter().filter_map(|(d, s)| Some((d, s?))).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::*;
+    use crate::PostRepository;
+
+    #[tokio::test]
+    async fn test_only_confirmed_subscribers_are_queued_once() {
+        let db = setup_test_db().await;
+        let (owner, email) = create_test_user(&db, "repo_newsletter").await;
+        let post = create_test_post(&db, owner.id, "Title", "Body", true).await;
+
+        let active = SubscriberRepository::subscribe(&db, owner.id, "a@example.com".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        SubscriberRepository::subscribe(&db, owner.id, "b@example.com".to_string()).await.unwrap().unwrap();
+        SubscriberRepository::confirm(&db, active.id).await.unwrap().unwrap();
+        assert!(SubscriberRepository::subscribe(&db, owner.id, "a@example.com".to_string())
+            .await
+            .unwrap()
+            .is_none());
+
+        assert_eq!(NewsletterRepository::enqueue_post(&db, &post).await.unwrap(), 1);
+        assert_eq!(NewsletterRepository::enqueue_post(&db, &post).await.unwrap(), 0);
+        let deliveries = NewsletterRepository::list_for_post(&db, owner.id, post.id, None, None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].1.email, "a@example.com");
+        assert_eq!(deliveries[0].0.status, DeliveryStatus::Queued);
+
+        let gone = SubscriberRepository::unsubscribe(&db, active.id).await.unwrap().unwrap();
+        assert_eq!(gone.status, SubscriberStatus::Unsubscribed);
+        let deliveries = NewsletterRepository::list_for_post(&db, owner.id, post.id, None, None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(deliveries[0].0.status, DeliveryStatus::Failed);
+        let again = SubscriberRepository::subscribe(&db, owner.id, "a@example.com".to_string()).await.unwrap();
+        assert_eq!(again.unwrap().status, SubscriberStatus::Pending);
+
+        PostRepository::delete_post(&db, owner.id, post.id).await.unwrap();
+        cleanup_user_by_email(&db, &email).await;
+    }
+}
@@ -0,0 +1,213 @@
+use crate::queue::{self, Queue};
+use models::feed_pings::{self, Entity as FeedPings};
+use models::sea_orm_active_enums::{DeliveryStatus, PingKind};
+use sea_orm::entity::prelude::Uuid;
+use sea_orm::*;
+
+const DEFAULT_PAGE_SIZE: u64 = 20;
+const MAX_PAGE_SIZE: u64 = 100;
+
+pub const FEED_PING_DEFAULT_PAGE_SIZE: u64 = DEFAULT_PAGE_SIZE;
+
+const QUEUE: Queue = Queue {
+    table: "feed_pings",
+    state: "status",
+    returning: "id, user_id, post_id, kind::text as kind, endpoint, feed_url, body, \
+                status::text as status, attempts, next_attempt_at, response_status, \
+                last_error, sent_at, created_at",
+};
+
+/// A ping to queue: where it goes and what it says.
+pub struct NewPing {
+    pub kind: PingKind,
+    pub endpoint: String,
+    pub feed_url: String,
+    pub body: String,
+}
+
+pub struct PingRepository;
+
+impl PingRepository {
+    /// Queue `pings` made by publishing `post_id`. Returns how many were
+    /// queued.
+    pub async fn enqueue(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        post_id: Option<Uuid>,
+        pings: Vec<NewPing>,
+    ) -> Result<u64, String> {
+        if pings.is_empty() {
+            return Ok(0);
+        }
+        let now = chrono::Utc::now().naive_utc();
+        let count = pings.len() as u64;
+        let rows = pings.into_iter().map(|ping| feed_pings::ActiveModel {
+            id: ActiveValue::Set(Uuid::new_v4()),
+            user_id: ActiveValue::Set(user_id),
+            post_id: ActiveValue::Set(post_id),
+            kind: ActiveValue::Set(ping.kind),
+            endpoint: ActiveValue::Set(ping.endpoint),
+            feed_url: ActiveValue::Set(ping.feed_url),
+            body: ActiveValue::Set(ping.body),
+            status: ActiveValue::Set(DeliveryStatus::Queued),
+            attempts: ActiveValue::Set(0),
+            next_attempt_at: ActiveValue::Set(now),
+            created_at: ActiveValue::Set(now),
+            ..Default::default()
+        });
+        FeedPings::insert_many(rows)
+            .exec(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        Ok(count)
+    }
+
+    /// Take up to `limit` queued pings that are due, counting an attempt for
+    /// each. Claimed pings aren't due again for a while, so concurrent
+    /// senders don't ping twice.
+    pub async fn claim_due(db: &DatabaseConnection, limit: u64) -> Result<Vec<feed_pings::Model>, String> {
+        QUEUE.claim_due::<FeedPings>(db, limit).await
+    }
+
+    pub async fn mark_sent(db: &DatabaseConnection, id: Uuid, response_status: Option<i32>) -> Result<(), String> {
+        FeedPings::update_many()
+            .set(feed_pings::ActiveModel {
+                status: ActiveValue::Set(DeliveryStatus::Sent),
+                response_status: ActiveValue::Set(response_status),
+                last_error: ActiveValue::Set(None),
+                sent_at: ActiveValue::Set(Some(chrono::Utc::now().naive_utc())),
+                ..Default::default()
+            })
+            .filter(feed_pings::Column::Id.eq(id))
+            .exec(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        Ok(())
+    }
+
+    /// Record a failed attempt. The ping is retried later, backing off,
+    /// until it has had `max_attempts`.
+    pub async fn mark_failed(
+        db: &DatabaseConnection,
+        ping: &feed_pings::Model,
+        response_status: Option<i32>,
+        error: String,
+        max_attempts: i32,
+    ) -> Result<(), String> {
+        let mut am = feed_pings::ActiveModel {
+            response_status: ActiveValue::Set(response_status),
+            last_error: ActiveValue::Set(Some(error)),
+            ..Default::default()
+        };
+        match queue::retry_at(ping.attempts, max_attempts) {
+            Some(at) => am.next_attempt_at = ActiveValue::Set(at),
+            None => am.status = ActiveValue::Set(DeliveryStatus::Failed),
+        }
+        FeedPings::update_many()
+            .set(am)
+            .filter(feed_pings::Column::Id.eq(ping.id))
+            .exec(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        Ok(())
+    }
+
+    /// `user_id`'s pings, or those made for one of their posts, newest first.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn list(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        post_id: Option<Uuid>,
+        status: Option<DeliveryStatus>,
+        after_id: Option<Uuid>,
+        after_created_at: Option<chrono::NaiveDateTime>,
+        limit: Option<u64>,
+    ) -> Result<Vec<feed_pings::Model>, String> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+
+        let mut q = FeedPings::find()
+            .filter(feed_pings::Column::UserId.eq(user_id))
+            .order_by_desc(feed_pings::Column::CreatedAt)
+            .order_by_desc(feed_pings::Column::Id);
+        if let Some(post_id) = post_id {
+            q = q.filter(feed_pings::Column::PostId.eq(post_id));
+        }
+        if let Some(status) = status {
+            q = q.filter(feed_pings::Column::Status.eq(status));
+        }
+        if let (Some(at), Some(aid)) = (after_created_at, after_id) {
+            q = q.filter(
+                Condition::any()
+                    .add(feed_pings::Column::CreatedAt.lt(at))
+                    .add(
+                        Condition::all()
+                            .add(feed_pings::Column::CreatedAt.eq(at))
+                            .add(feed_pings::Column::Id.lt(aid)),
+                    ),
+            );
+        }
+
+        q.limit(limit)
+            .all(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::*;
+
+    #[tokio::test]
+    async fn test_pings_are_listed_per_user_and_post() {
+        let db = setup_test_db().await;
+        let (owner, email) = create_test_user(&db, "repo_pings").await;
+        let (other, other_email) = create_test_user(&db, "repo_pings_other").await;
+        let post = create_test_post(&db, owner.id, "Pinged", "Body", true).await;
+
+        let ping = |kind, endpoint: &str| NewPing {
+            kind,
+            endpoint: endpoint.to_string(),
+            feed_url: "https://blog.example/feed.xml".to_string(),
+            body: "hub.mode=publish".to_string(),
+        };
+        let queued = PingRepository::enqueue(
+            &db,
+            owner.id,
+            Some(post.id),
+            vec![ping(PingKind::Websub, "https://hub.example/"), ping(PingKind::Xmlrpc, "https://rpc.example/")],
+        )
+        .await
+        .unwrap();
+        assert_eq!(queued, 2);
+        assert_eq!(PingRepository::enqueue(&db, owner.id, None, Vec::new()).await.unwrap(), 0);
+
+        let log = PingRepository::list(&db, owner.id, Some(post.id), None, None, None, None).await.unwrap();
+        assert_eq!(log.len(), 2);
+        assert!(log.iter().all(|p| p.status == DeliveryStatus::Queued && p.attempts == 0));
+        assert!(PingRepository::list(&db, other.id, None, None, None, None, None).await.unwrap().is_empty());
+
+        PingRepository::mark_failed(&db, &log[0], Some(503), "HTTP 503".to_string(), 8).await.unwrap();
+        PingRepository::mark_sent(&db, log[1].id, Some(204)).await.unwrap();
+        let sent = PingRepository::list(&db, owner.id, None, Some(DeliveryStatus::Sent), None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].response_status, Some(204));
+        let queued = PingRepository::list(&db, owner.id, None, Some(DeliveryStatus::Queued), None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(queued[0].last_error.as_deref(), Some("HTTP 503"));
+        assert!(queued[0].next_attempt_at > chrono::Utc::now().naive_utc());
+
+        let page = PingRepository::list(&db, owner.id, None, None, Some(log[0].id), Some(log[0].created_at), Some(1))
+            .await
+            .unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].id, log[1].id);
+
+        cleanup_user_by_email(&db, &email).await;
+        cleanup_user_by_email(&db, &other_email).await;
+    }
+}
@@ -62,6 +62,46 @@ impl PostRepository {
             .map_err(|e| format!("Database error: {}", e))
     }
 
+    /// Unscoped lookup for admin tooling; prefer `get_post` for user-facing paths.
+    pub async fn find_by_id(
+        db: &DatabaseConnection,
+        id: Uuid,
+    ) -> Result<Option<Model>, String> {
+        PostDao::find_by_id(db, id)
+            .await
+            .map_err(|e| format!("Database error: {}", e))
+    }
+
+    pub async fn all_for_user(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+    ) -> Result<Vec<Model>, String> {
+        PostDao::find_all_for_user(db, user_id)
+            .await
+            .map_err(|e| format!("Database error: {}", e))
+    }
+
+    /// Every post's id, for admin tooling that walks all posts.
+    pub async fn all_ids(db: &DatabaseConnection) -> Result<Vec<Uuid>, String> {
+        models::posts::Entity::find()
+            .select_only()
+            .column(models::posts::Column::Id)
+            .into_tuple()
+            .all(db)
+            .await
+            .map_err(|e| format!("Database error: {}", e))
+    }
+
+    pub async fn find_by_slug(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        slug: &str,
+    ) -> Result<Option<Model>, String> {
+        PostDao::find_by_slug(db, user_id, slug)
+            .await
+            .map_err(|e| format!("Database error: {}", e))
+    }
+
     pub async fn search_posts(
         db: &DatabaseConnection,
         user_id: Uuid,
@@ -112,6 +152,12 @@ impl PostRepository {
         })
     }
 
+    pub async fn count_published(db: &DatabaseConnection, user_id: Uuid) -> Result<u64, String> {
+        PostDao::count_published(db, user_id)
+            .await
+            .map_err(|e| format!("Database error: {}", e))
+    }
+
     pub async fn get_prev_published_post(
         db: &DatabaseConnection,
         user_id: Uuid,
@@ -495,4 +541,37 @@ mod tests {
         cleanup_user_by_email(&db, &email_a).await;
         cleanup_user_by_email(&db, &email_b).await;
     }
+
+    // ============= admin lookup tests =============
+
+    #[tokio::test]
+    async fn test_all_for_user_returns_drafts_and_published() {
+        let db = setup_test_db().await;
+        let (user, email) = create_test_user(&db, "all_for_user").await;
+        let (other, other_email) = create_test_user(&db, "all_for_user_other").await;
+        create_test_post(&db, user.id, "Draft", "c", false).await;
+        create_test_post(&db, user.id, "Live", "c", true).await;
+        create_test_post(&db, other.id, "Other", "c", true).await;
+
+        let posts = PostRepository::all_for_user(&db, user.id).await.unwrap();
+
+        assert_eq!(posts.len(), 2);
+        cleanup_user_by_email(&db, &email).await;
+        cleanup_user_by_email(&db, &other_email).await;
+    }
+
+    #[tokio::test]
+    async fn test_find_by_slug_scoped_to_user() {
+        let db = setup_test_db().await;
+        let (user, email) = create_test_user(&db, "by_slug").await;
+        let (other, other_email) = create_test_user(&db, "by_slug_other").await;
+        PostRepository::create_post(
+            &db, user.id, "T".into(), "c".into(), false, None, Some("hello".into()), None,
+        ).await.unwrap();
+
+        assert!(PostRepository::find_by_slug(&db, user.id, "hello").await.unwrap().is_some());
+        assert!(PostRepository::find_by_slug(&db, other.id, "hello").await.unwrap().is_none());
+        cleanup_user_by_email(&db, &email).await;
+        cleanup_user_by_email(&db, &other_email).await;
+    }
 }
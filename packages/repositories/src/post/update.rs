@@ -1,6 +1,7 @@
 use data_access_objects::PostDao;
-use models::posts::Model;
-use sea_orm::entity::prelude::Uuid;
+use models::posts::{self, Column, Model};
+use sea_orm::entity::prelude::{Json, Uuid};
+use sea_orm::sea_query::Expr;
 use sea_orm::*;
 
 use super::PostRepository;
@@ -53,6 +54,136 @@ impl PostRepository {
             .await
             .map_err(|e| format!("Database error: {}", e))
     }
+
+    /// Overwrite timestamps, e.g. to keep original dates when importing.
+    pub async fn set_timestamps(
+        db: &DatabaseConnection,
+        id: Uuid,
+        first_published_at: Option<chrono::NaiveDateTime>,
+        created_at: chrono::NaiveDateTime,
+        updated_at: chrono::NaiveDateTime,
+    ) -> Result<Model, String> {
+        let am = models::posts::ActiveModel {
+            id: ActiveValue::unchanged(id),
+            first_published_at: ActiveValue::set(first_published_at),
+            created_at: ActiveValue::set(created_at),
+            updated_at: ActiveValue::set(updated_at),
+            ..Default::default()
+        };
+        PostDao::update(db, am)
+            .await
+            .map_err(|e| format!("Database error: {}", e))
+    }
+
+    /// Publish or unpublish any post regardless of owner (admin tooling).
+    pub async fn set_published(
+        db: &DatabaseConnection,
+        id: Uuid,
+        publish: bool,
+    ) -> Result<Model, String> {
+        let existing = PostDao::find_by_id(db, id)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?
+            .ok_or_else(|| "Post not found".to_string())?;
+
+        let first_published_at = existing.first_published_at;
+        let mut am = existing.into_active_model();
+        am.is_published = ActiveValue::set(publish);
+        am.updated_at = ActiveValue::set(chrono::Utc::now().naive_utc());
+        if publish && first_published_at.is_none() {
+            am.first_published_at = ActiveValue::set(Some(chrono::Utc::now().naive_utc()));
+        }
+
+        PostDao::update(db, am)
+            .await
+            .map_err(|e| format!("Database error: {}", e))
+    }
+
+    /// Turn math rendering on or off for one of `user_id`'s posts.
+    pub async fn set_render_math(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        id: Uuid,
+        render_math: bool,
+    ) -> Result<Model, String> {
+        let existing = PostDao::find_by_id_for_user(db, id, user_id)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?
+            .ok_or_else(|| "Post not found".to_string())?;
+
+        let mut am = existing.into_active_model();
+        am.render_math = ActiveValue::set(render_math);
+        am.updated_at = ActiveValue::set(chrono::Utc::now().naive_utc());
+
+        PostDao::update(db, am)
+            .await
+            .map_err(|e| format!("Database error: {}", e))
+    }
+
+    /// Replace the share-metadata overrides of one of `user_id`'s posts.
+    pub async fn set_seo(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        id: Uuid,
+        seo: Option<Json>,
+    ) -> Result<Model, String> {
+        let existing = PostDao::find_by_id_for_user(db, id, user_id)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?
+            .ok_or_else(|| "Post not found".to_string())?;
+
+        let mut am = existing.into_active_model();
+        am.seo = ActiveValue::set(seo);
+        am.updated_at = ActiveValue::set(chrono::Utc::now().naive_utc());
+
+        PostDao::update(db, am)
+            .await
+            .map_err(|e| format!("Database error: {}", e))
+    }
+
+    /// Store a post's rendered markdown. Leaves `updated_at` alone: this is a
+    /// cache, not an edit.
+    pub async fn set_rendered_markdown(
+        db: &DatabaseConnection,
+        id: Uuid,
+        rendered: Json,
+    ) -> Result<(), String> {
+        posts::Entity::update_many()
+            .col_expr(Column::RenderedMarkdown, Expr::value(rendered))
+            .filter(Column::Id.eq(id))
+            .exec(db)
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("Database error: {}", e))
+    }
+
+    /// Record the storage key of a post's generated share card. Leaves
+    /// `updated_at` alone, like `set_rendered_markdown`.
+    pub async fn set_preview_image(
+        db: &DatabaseConnection,
+        id: Uuid,
+        key: Option<String>,
+    ) -> Result<(), String> {
+        posts::Entity::update_many()
+            .col_expr(Column::PreviewImage, Expr::value(key))
+            .filter(Column::Id.eq(id))
+            .exec(db)
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("Database error: {}", e))
+    }
+
+    /// Drop the stored renders of every post by `user_id`, e.g. when an asset
+    /// they may embed is deleted.
+    pub async fn clear_rendered_markdown(db: &DatabaseConnection, user_id: Uuid) -> Result<(), String> {
+        posts::Entity::update_many()
+            .col_expr(Column::RenderedMarkdown, Expr::value(Option::<Json>::None))
+            .filter(Column::UserId.eq(user_id))
+            .exec(db)
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("Database error: {}", e))
+    }
 }
 
 #[cfg(test)]
@@ -154,4 +285,92 @@ mod tests {
         cleanup_user_by_email(&db, &email_a).await;
         cleanup_user_by_email(&db, &email_b).await;
     }
+
+    #[tokio::test]
+    async fn test_set_published_toggles_and_keeps_first_published_at() {
+        let db = setup_test_db().await;
+        let (user, email) = create_test_user(&db, "repo_set_pub").await;
+        let post = create_test_post(&db, user.id, "Title", "content", false).await;
+
+        let published = PostRepository::set_published(&db, post.id, true).await.unwrap();
+        let first_pub = published.first_published_at.unwrap();
+        assert!(published.is_published);
+
+        let unpublished = PostRepository::set_published(&db, post.id, false).await.unwrap();
+        assert!(!unpublished.is_published);
+        assert_eq!(unpublished.first_published_at, Some(first_pub));
+
+        cleanup_user_by_email(&db, &email).await;
+    }
+
+    #[tokio::test]
+    async fn test_set_published_nonexistent_returns_error() {
+        let db = setup_test_db().await;
+
+        let result = PostRepository::set_published(&db, Uuid::new_v4(), true).await;
+
+        assert!(result.unwrap_err().contains("not found"));
+    }
+
+    #[tokio::test]
+    async fn test_set_render_math_is_scoped_to_owner() {
+        let db = setup_test_db().await;
+        let (user_a, email_a) = create_test_user(&db, "repo_math_a").await;
+        let (user_b, email_b) = create_test_user(&db, "repo_math_b").await;
+        let post = create_test_post(&db, user_a.id, "Title", "$x$", false).await;
+        assert!(post.render_math);
+
+        let updated = PostRepository::set_render_math(&db, user_a.id, post.id, false).await.unwrap();
+        assert!(!updated.render_math);
+
+        let result = PostRepository::set_render_math(&db, user_b.id, post.id, true).await;
+        assert!(result.unwrap_err().contains("not found"));
+
+        cleanup_user_by_email(&db, &email_a).await;
+        cleanup_user_by_email(&db, &email_b).await;
+    }
+
+    #[tokio::test]
+    async fn test_set_seo_is_scoped_to_owner() {
+        let db = setup_test_db().await;
+        let (user_a, email_a) = create_test_user(&db, "repo_seo_a").await;
+        let (user_b, email_b) = create_test_user(&db, "repo_seo_b").await;
+        let post = create_test_post(&db, user_a.id, "Title", "Body", false).await;
+        let seo = serde_json::json!({"title": "Share title"});
+
+        let updated = PostRepository::set_seo(&db, user_a.id, post.id, Some(seo.clone())).await.unwrap();
+        assert_eq!(updated.seo, Some(seo));
+        let cleared = PostRepository::set_seo(&db, user_a.id, post.id, None).await.unwrap();
+        assert_eq!(cleared.seo, None);
+
+        let result = PostRepository::set_seo(&db, user_b.id, post.id, None).await;
+        assert!(result.unwrap_err().contains("not found"));
+
+        cleanup_user_by_email(&db, &email_a).await;
+        cleanup_user_by_email(&db, &email_b).await;
+    }
+
+    #[tokio::test]
+    async fn test_rendered_markdown_is_stored_without_touching_updated_at() {
+        let db = setup_test_db().await;
+        let (user, email) = create_test_user(&db, "repo_rendered").await;
+        let post = create_test_post(&db, user.id, "Title", "# Hi", false).await;
+        let rendered = serde_json::json!({"key": "abc", "html": "<h1>Hi</h1>", "toc": []});
+
+        PostRepository::set_rendered_markdown(&db, post.id, rendered.clone()).await.unwrap();
+        let stored = PostRepository::get_post(&db, user.id, post.id).await.unwrap().unwrap();
+        assert_eq!(stored.rendered_markdown, Some(rendered));
+        assert_eq!(stored.updated_at, post.updated_at);
+
+        PostRepository::clear_rendered_markdown(&db, user.id).await.unwrap();
+        let cleared = PostRepository::get_post(&db, user.id, post.id).await.unwrap().unwrap();
+        assert_eq!(cleared.rendered_markdown, None);
+
+        PostRepository::set_preview_image(&db, post.id, Some("previews/x/1.png".into())).await.unwrap();
+        let stored = PostRepository::get_post(&db, user.id, post.id).await.unwrap().unwrap();
+        assert_eq!(stored.preview_image.as_deref(), Some("previews/x/1.png"));
+        assert_eq!(stored.updated_at, post.updated_at);
+
+        cleanup_user_by_email(&db, &email).await;
+    }
 }
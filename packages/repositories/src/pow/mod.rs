@@ -0,0 +1,58 @@
+use chrono::NaiveDateTime;
+use models::prelude::SpentPowNonces;
+use models::spent_pow_nonces;
+use sea_orm::*;
+
+pub struct PowRepository;
+
+impl PowRepository {
+    /// Mark the challenge `nonce` redeemed until `expires_at`. Returns false
+    /// if it already was.
+    pub async fn spend(db: &DatabaseConnection, nonce: &str, expires_at: NaiveDateTime) -> Result<bool, String> {
+        let am = spent_pow_nonces::ActiveModel {
+            nonce: ActiveValue::Set(nonce.to_string()),
+            expires_at: ActiveValue::Set(expires_at),
+        };
+        let result = SpentPowNonces::insert(am)
+            .on_conflict(sea_query::OnConflict::column(spent_pow_nonces::Column::Nonce).do_nothing().to_owned())
+            .do_nothing()
+            .exec(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        Ok(matches!(result, TryInsertResult::Inserted(_)))
+    }
+
+    /// Forget redeemed challenges that have expired, and so can't be
+    /// redeemed again anyway. Returns how many were dropped.
+    pub async fn prune_spent(db: &DatabaseConnection) -> Result<u64, String> {
+        let result = SpentPowNonces::delete_many()
+            .filter(spent_pow_nonces::Column::ExpiresAt.lt(chrono::Utc::now().naive_utc()))
+            .exec(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        Ok(result.rows_affected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::setup_test_db;
+
+    #[tokio::test]
+    async fn challenges_are_spent_once_until_they_expire() {
+        let db = setup_test_db().await;
+        let now = chrono::Utc::now().naive_utc();
+        let fresh = uuid::Uuid::new_v4().to_string();
+        let stale = uuid::Uuid::new_v4().to_string();
+
+        assert!(PowRepository::spend(&db, &fresh, now + chrono::Duration::minutes(5)).await.unwrap());
+        assert!(!PowRepository::spend(&db, &fresh, now + chrono::Duration::minutes(5)).await.unwrap());
+        assert!(PowRepository::spend(&db, &stale, now - chrono::Duration::minutes(5)).await.unwrap());
+
+        assert!(PowRepository::prune_spent(&db).await.unwrap() >= 1);
+        let left = |nonce: String| SpentPowNonces::find_by_id(nonce).one(&db);
+        assert!(left(stale).await.unwrap().is_none());
+        assert!(left(fresh).await.unwrap().is_some());
+    }
+}
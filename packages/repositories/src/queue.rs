@@ -0,0 +1,85 @@
+//! What the delivery queues have in common: feed pings, newsletter emails,
+//! webhook deliveries, Webmentions both ways and ActivityPub deliveries.
+//! Each row waits as `queued` until its `next_attempt_at`, counts its
+//! `attempts`, and is retried with backoff until it runs out of them.
+
+use chrono::NaiveDateTime;
+use sea_orm::*;
+
+/// How long a claimed row is left alone before another pass may retry it,
+/// should the one that claimed it never report back.
+const CLAIM_LEASE_MINUTES: i64 = 10;
+
+/// Longest wait between retries: 2^8 minutes, a little over four hours.
+const MAX_BACKOFF_EXPONENT: i32 = 8;
+
+/// A queue table.
+pub(crate) struct Queue {
+    pub table: &'static str,
+    /// The enum column that reads `queued` while a row waits
+    pub state: &'static str,
+    /// Columns of the claimed rows to return, with enums cast to text
+    pub returning: &'static str,
+}
+
+impl Queue {
+    /// Take up to `limit` queued rows that are due, counting an attempt for
+    /// each. Claimed rows aren't due again for a while, so concurrent
+    /// workers don't send the same thing twice.
+    pub async fn claim_due<E>(&self, db: &DatabaseConnection, limit: u64) -> Result<Vec<E::Model>, String>
+    where
+        E: EntityTrait,
+    {
+        let now = chrono::Utc::now().naive_utc();
+        let Queue { table, state, returning } = self;
+        let stmt = Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            format!(
+                "update {table} set attempts = attempts + 1, next_attempt_at = $1 \
+                 where id in ( \
+                     select id from {table} \
+                     where {state} = 'queued' and next_attempt_at <= $2 \
+                     order by next_attempt_at limit $3 for update skip locked \
+                 ) returning {returning}"
+            ),
+            [
+                (now + chrono::Duration::minutes(CLAIM_LEASE_MINUTES)).into(),
+                now.into(),
+                (limit as i64).into(),
+            ],
+        );
+        E::find()
+            .from_raw_sql(stmt)
+            .all(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))
+    }
+}
+
+/// When a row that failed its `attempts`th try is due again, backing off
+/// exponentially, or `None` once it has had `max_attempts`.
+pub(crate) fn retry_at(attempts: i32, max_attempts: i32) -> Option<NaiveDateTime> {
+    if attempts >= max_attempts {
+        return None;
+    }
+    let backoff = chrono::Duration::minutes(1 << attempts.clamp(0, MAX_BACKOFF_EXPONENT));
+    Some(chrono::Utc::now().naive_utc() + backoff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_back_off_until_attempts_run_out() {
+        let wait = |attempts| {
+            let before = chrono::Utc::now().naive_utc();
+            retry_at(attempts, 10).map(|at| (at - before).num_minutes())
+        };
+        assert_eq!(wait(1), Some(2));
+        assert_eq!(wait(3), Some(8));
+        assert_eq!(wait(9), Some(256));
+        assert_eq!(wait(10), None);
+        assert_eq!(retry_at(0, 0), None);
+    }
+}
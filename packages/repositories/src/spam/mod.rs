@@ -0,0 +1,172 @@
+use models::{comments, spam_corpora, spam_tokens};
+use sea_orm::entity::prelude::Uuid;
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::*;
+use services::spam::{tokenize, Corpus, Submission};
+use std::collections::BTreeSet;
+
+pub struct SpamRepository;
+
+impl SpamRepository {
+    /// What `user_id`'s filter has learned about `tokens`.
+    pub async fn corpus(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        tokens: &BTreeSet<String>,
+    ) -> Result<Corpus, String> {
+        let totals = spam_corpora::Entity::find_by_id(user_id)
+            .one(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        let counts = if tokens.is_empty() {
+            Vec::new()
+        } else {
+            spam_tokens::Entity::find()
+                .filter(spam_tokens::Column::UserId.eq(user_id))
+                .filter(spam_tokens::Column::Token.is_in(tokens.iter().cloned()))
+                .all(db)
+                .await
+                .map_err(|e| format!("Database error: {e}"))?
+        };
+        Ok(Corpus {
+            spam_messages: totals.as_ref().map_or(0, |t| t.spam_messages),
+            ham_messages: totals.as_ref().map_or(0, |t| t.ham_messages),
+            tokens: counts.into_iter().map(|t| (t.token, (t.spam_count, t.ham_count))).collect(),
+        })
+    }
+
+    /// Teach the filter a moderation decision on `comment`: `Some(true)` for
+    /// spam, `Some(false)` for ham, `None` for neither. Whatever the comment
+    /// taught before is unlearned first, so changing a decision doesn't
+    /// count the comment twice.
+    pub async fn learn_from_comment(
+        db: &DatabaseConnection,
+        comment: &comments::Model,
+        spam: Option<bool>,
+    ) -> Result<(), String> {
+        if comment.spam_trained == spam {
+            return Ok(());
+        }
+        let tokens = tokenize(&Submission::from(comment));
+        let txn = db.begin().await.map_err(|e| format!("Database error: {e}"))?;
+        if let Some(was_spam) = comment.spam_trained {
+            Self::count(&txn, comment.user_id, &tokens, was_spam, -1).await?;
+        }
+        if let Some(is_spam) = spam {
+            Self::count(&txn, comment.user_id, &tokens, is_spam, 1).await?;
+        }
+        comments::Entity::update_many()
+            .col_expr(comments::Column::SpamTrained, Expr::value(spam))
+            .filter(comments::Column::Id.eq(comment.id))
+            .exec(&txn)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        txn.commit().await.map_err(|e| format!("Database error: {e}"))
+    }
+
+    /// Add `delta` to the message total and each token's count for one class.
+    async fn count<C: ConnectionTrait>(
+        db: &C,
+        user_id: Uuid,
+        tokens: &BTreeSet<String>,
+        spam: bool,
+        delta: i32,
+    ) -> Result<(), String> {
+        let (spam_delta, ham_delta) = if spam { (delta, 0) } else { (0, delta) };
+
+        spam_corpora::Entity::insert(spam_corpora::ActiveModel {
+            user_id: ActiveValue::Set(user_id),
+            spam_messages: ActiveValue::Set(spam_delta.max(0)),
+            ham_messages: ActiveValue::Set(ham_delta.max(0)),
+        })
+        .on_conflict(
+            OnConflict::column(spam_corpora::Column::UserId)
+                .value(
+                    spam_corpora::Column::SpamMessages,
+                    Expr::col((spam_corpora::Entity, spam_corpora::Column::SpamMessages)).add(spam_delta),
+                )
+                .value(
+                    spam_corpora::Column::HamMessages,
+                    Expr::col((spam_corpora::Entity, spam_corpora::Column::HamMessages)).add(ham_delta),
+                )
+                .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await
+        .map_err(|e| format!("Database error: {e}"))?;
+
+        if tokens.is_empty() {
+            return Ok(());
+        }
+        let rows = tokens.iter().map(|token| spam_tokens::ActiveModel {
+            user_id: ActiveValue::Set(user_id),
+            token: ActiveValue::Set(token.clone()),
+            spam_count: ActiveValue::Set(spam_delta.max(0)),
+            ham_count: ActiveValue::Set(ham_delta.max(0)),
+        });
+        spam_tokens::Entity::insert_many(rows)
+            .on_conflict(
+                OnConflict::columns([spam_tokens::Column::UserId, spam_tokens::Column::Token])
+                    .value(
+                        spam_tokens::Column::SpamCount,
+                        Expr::col((spam_tokens::Entity, spam_tokens::Column::SpamCount)).add(spam_delta),
+                    )
+                    .value(
+                        spam_tokens::Column::HamCount,
+                        Expr::col((spam_tokens::Entity, spam_tokens::Column::HamCount)).add(ham_delta),
+                    )
+                    .to_owned(),
+            )
+            .exec_without_returning(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::*;
+    use crate::{CommentRepository, NewComment, PostRepository};
+    use models::sea_orm_active_enums::CommentStatus;
+
+    #[tokio::test]
+    async fn test_changing_a_decision_unlearns_the_old_one() {
+        let db = setup_test_db().await;
+        let (owner, email) = create_test_user(&db, "repo_spam_learn").await;
+        let post = create_test_post(&db, owner.id, "Title", "Body", true).await;
+        let comment = CommentRepository::create(
+            &db,
+            NewComment {
+                post_id: post.id,
+                user_id: owner.id,
+                parent_id: None,
+                author_name: "Bot".to_string(),
+                author_email: None,
+                author_url: None,
+                body: "cheap casino bonus".to_string(),
+            },
+            CommentStatus::Pending,
+            None,
+        )
+        .await
+        .unwrap();
+        let tokens = tokenize(&Submission::from(&comment));
+
+        SpamRepository::learn_from_comment(&db, &comment, Some(true)).await.unwrap();
+        let corpus = SpamRepository::corpus(&db, owner.id, &tokens).await.unwrap();
+        assert_eq!((corpus.spam_messages, corpus.ham_messages), (1, 0));
+        assert_eq!(corpus.tokens["casino"], (1, 0));
+
+        let comment = CommentRepository::get(&db, owner.id, comment.id).await.unwrap().unwrap();
+        assert_eq!(comment.spam_trained, Some(true));
+        SpamRepository::learn_from_comment(&db, &comment, Some(false)).await.unwrap();
+        let corpus = SpamRepository::corpus(&db, owner.id, &tokens).await.unwrap();
+        assert_eq!((corpus.spam_messages, corpus.ham_messages), (0, 1));
+        assert_eq!(corpus.tokens["casino"], (0, 1));
+
+        PostRepository::delete_post(&db, owner.id, post.id).await.unwrap();
+        cleanup_user_by_email(&db, &email).await;
+    }
+}
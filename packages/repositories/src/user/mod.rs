@@ -1,5 +1,6 @@
 use chrono::Utc;
 use data_access_objects::UserDao;
+use models::sea_orm_active_enums::CommentPolicy;
 use models::users::{ActiveModel, Model};
 use sea_orm::entity::prelude::Uuid;
 use sea_orm::*;
@@ -12,6 +13,10 @@ impl UserRepository {
         UserDao::count(db).await
     }
 
+    pub async fn list(db: &DatabaseConnection) -> Result<Vec<Model>, DbErr> {
+        UserDao::find_all(db).await
+    }
+
     pub async fn create(
         db: &DatabaseConnection,
         id: Uuid,
@@ -118,6 +123,24 @@ impl UserRepository {
         }
         UserDao::update(db, model).await.map_err(|e| e.to_string())
     }
+
+    /// Whether readers may comment on the user's posts, and for how long
+    /// after publishing.
+    pub async fn set_comment_settings(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        policy: CommentPolicy,
+        close_after_days: Option<i32>,
+    ) -> Result<Model, String> {
+        let model = ActiveModel {
+            id: ActiveValue::set(user_id),
+            comment_policy: ActiveValue::set(policy),
+            comments_close_after_days: ActiveValue::set(close_after_days),
+            updated_at: ActiveValue::set(Some(Utc::now().naive_utc())),
+            ..Default::default()
+        };
+        UserDao::update(db, model).await.map_err(|e| e.to_string())
+    }
 }
 
 #[cfg(test)]
@@ -161,6 +184,18 @@ mod tests {
         cleanup_user_by_email(&db, &email).await;
     }
 
+    #[tokio::test]
+    async fn test_list_includes_created_user() {
+        let db = setup_test_db().await;
+        let (user, email) = create_test_user(&db, "user_list").await;
+
+        let users = UserRepository::list(&db).await.unwrap();
+
+        assert!(users.iter().any(|u| u.id == user.id));
+
+        cleanup_user_by_email(&db, &email).await;
+    }
+
     #[tokio::test]
     async fn test_find_by_email_finds_user() {
         let db = setup_test_db().await;
@@ -343,4 +378,23 @@ mod tests {
 
         cleanup_user_by_email(&db, &new_email).await;
     }
+
+    #[tokio::test]
+    async fn test_comment_settings_default_to_moderated() {
+        use models::sea_orm_active_enums::CommentPolicy;
+
+        let db = setup_test_db().await;
+        let (user, email) = create_test_user(&db, "comment_settings").await;
+        assert_eq!(user.comment_policy, CommentPolicy::Moderated);
+        assert_eq!(user.comments_close_after_days, None);
+
+        let updated = UserRepository::set_comment_settings(&db, user.id, CommentPolicy::Open, Some(30))
+            .await
+            .unwrap();
+        assert_eq!(updated.comment_policy, CommentPolicy::Open);
+        assert_eq!(updated.comments_close_after_days, Some(30));
+        assert_eq!(updated.display_name, user.display_name);
+
+        cleanup_user_by_email(&db, &email).await;
+    }
 }
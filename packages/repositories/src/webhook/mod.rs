@@ -0,0 +1,350 @@
+use crate::queue::{self, Queue};
+use models::sea_orm_active_enums::DeliveryStatus;
+use models::webhook_deliveries::{self, Entity as Deliveries};
+use models::webhooks::{self, Entity as Webhooks};
+use sea_orm::entity::prelude::{Json, Uuid};
+use sea_orm::*;
+use std::collections::HashMap;
+
+const DEFAULT_PAGE_SIZE: u64 = 20;
+const MAX_PAGE_SIZE: u64 = 100;
+
+pub const WEBHOOK_DELIVERY_DEFAULT_PAGE_SIZE: u64 = DEFAULT_PAGE_SIZE;
+
+const QUEUE: Queue = Queue {
+    table: "webhook_deliveries",
+    state: "status",
+    returning: "id, webhook_id, event, payload, status::text as status, attempts, \
+                next_attempt_at, response_status, response_body, last_error, delivered_at, \
+                created_at",
+};
+
+/// What one attempt at a delivery got back.
+pub struct DeliveryResponse {
+    pub status: Option<i32>,
+    pub body: Option<String>,
+}
+
+pub struct WebhookRepository;
+
+impl WebhookRepository {
+    pub async fn create(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        url: String,
+        secret: String,
+        events: Vec<String>,
+    ) -> Result<webhooks::Model, String> {
+        let now = chrono::Utc::now().naive_utc();
+        webhooks::ActiveModel {
+            id: ActiveValue::Set(Uuid::new_v4()),
+            user_id: ActiveValue::Set(user_id),
+            url: ActiveValue::Set(url),
+            secret: ActiveValue::Set(secret),
+            events: ActiveValue::Set(Json::from(events)),
+            is_active: ActiveValue::Set(true),
+            created_at: ActiveValue::Set(now),
+            updated_at: ActiveValue::Set(now),
+        }
+        .insert(db)
+        .await
+        .map_err(|e| format!("Database error: {e}"))
+    }
+
+    /// Change the given fields of one of `user_id`'s webhooks; `None` if it
+    /// has no such webhook.
+    pub async fn update(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        id: Uuid,
+        url: Option<String>,
+        events: Option<Vec<String>>,
+        is_active: Option<bool>,
+    ) -> Result<Option<webhooks::Model>, String> {
+        let Some(webhook) = Self::find(db, user_id, id).await? else {
+            return Ok(None);
+        };
+        let mut am = webhook.into_active_model();
+        if let Some(url) = url {
+            am.url = ActiveValue::Set(url);
+        }
+        if let Some(events) = events {
+            am.events = ActiveValue::Set(Json::from(events));
+        }
+        if let Some(is_active) = is_active {
+            am.is_active = ActiveValue::Set(is_active);
+        }
+        am.updated_at = ActiveValue::Set(chrono::Utc::now().naive_utc());
+        am.update(db).await.map(Some).map_err(|e| format!("Database error: {e}"))
+    }
+
+    /// Delete one of `user_id`'s webhooks with its delivery log. `false` if
+    /// it has no such webhook.
+    pub async fn delete(db: &DatabaseConnection, user_id: Uuid, id: Uuid) -> Result<bool, String> {
+        Webhooks::delete_many()
+            .filter(webhooks::Column::Id.eq(id))
+            .filter(webhooks::Column::UserId.eq(user_id))
+            .exec(db)
+            .await
+            .map(|r| r.rows_affected > 0)
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    pub async fn find(db: &DatabaseConnection, user_id: Uuid, id: Uuid) -> Result<Option<webhooks::Model>, String> {
+        Webhooks::find_by_id(id)
+            .filter(webhooks::Column::UserId.eq(user_id))
+            .one(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    /// `user_id`'s webhooks, oldest first.
+    pub async fn list(db: &DatabaseConnection, user_id: Uuid) -> Result<Vec<webhooks::Model>, String> {
+        Webhooks::find()
+            .filter(webhooks::Column::UserId.eq(user_id))
+            .order_by_asc(webhooks::Column::CreatedAt)
+            .order_by_asc(webhooks::Column::Id)
+            .all(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    /// Queue `payload` for each of `user_id`'s active webhooks that listens
+    /// for `event`. Returns how many were queued.
+    pub async fn enqueue(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        event: &str,
+        payload: Json,
+    ) -> Result<u64, String> {
+        let stmt = Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            "insert into webhook_deliveries (webhook_id, event, payload) \
+             select id, $2, $3 from webhooks \
+             where user_id = $1 and is_active and events @> jsonb_build_array($2::text)",
+            [user_id.into(), event.into(), payload.into()],
+        );
+        db.execute(stmt)
+            .await
+            .map(|r| r.rows_affected())
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    /// Queue one of `user_id`'s past deliveries again, as a new delivery of
+    /// the same payload. `None` if they have no such delivery.
+    pub async fn redeliver(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        delivery_id: Uuid,
+    ) -> Result<Option<webhook_deliveries::Model>, String> {
+        let original = Deliveries::find_by_id(delivery_id)
+            .find_also_related(Webhooks)
+            .filter(webhooks::Column::UserId.eq(user_id))
+            .one(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        let Some((original, Some(_))) = original else {
+            return Ok(None);
+        };
+        let now = chrono::Utc::now().naive_utc();
+        webhook_deliveries::ActiveModel {
+            id: ActiveValue::Set(Uuid::new_v4()),
+            webhook_id: ActiveValue::Set(original.webhook_id),
+            event: ActiveValue::Set(original.event),
+            payload: ActiveValue::Set(original.payload),
+            status: ActiveValue::Set(DeliveryStatus::Queued),
+            attempts: ActiveValue::Set(0),
+            next_attempt_at: ActiveValue::Set(now),
+            created_at: ActiveValue::Set(now),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .map(Some)
+        .map_err(|e| format!("Database error: {e}"))
+    }
+
+    /// Take up to `limit` queued deliveries that are due, with their
+    /// webhooks, counting an attempt for each. Claimed deliveries aren't due
+    /// again for a while, so concurrent senders don't post the same event.
+    pub async fn claim_due(
+        db: &DatabaseConnection,
+        limit: u64,
+    ) -> Result<Vec<(webhook_deliveries::Model, webhooks::Model)>, String> {
+        let deliveries = QUEUE.claim_due::<Deliveries>(db, limit).await?;
+        if deliveries.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let webhook_ids: Vec<Uuid> = deliveries.iter().map(|d| d.webhook_id).collect();
+        let webhooks: HashMap<Uuid, webhooks::Model> = Webhooks::find()
+            .filter(webhooks::Column::Id.is_in(webhook_ids))
+            .all(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?
+            .into_iter()
+            .map(|w| (w.id, w))
+            .collect();
+        Ok(deliveries
+            .into_iter()
+            .filter_map(|d| {
+                let webhook = webhooks.get(&d.webhook_id)?.clone();
+                Some((d, webhook))
+            })
+            .collect())
+    }
+
+    pub async fn mark_delivered(
+        db: &DatabaseConnection,
+        id: Uuid,
+        response: DeliveryResponse,
+    ) -> Result<(), String> {
+        Deliveries::update_many()
+            .set(webhook_deliveries::ActiveModel {
+                status: ActiveValue::Set(DeliveryStatus::Sent),
+                response_status: ActiveValue::Set(response.status),
+                response_body: ActiveValue::Set(response.body),
+                last_error: ActiveValue::Set(None),
+                delivered_at: ActiveValue::Set(Some(chrono::Utc::now().naive_utc())),
+                ..Default::default()
+            })
+            .filter(webhook_deliveries::Column::Id.eq(id))
+            .exec(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        Ok(())
+    }
+
+    /// Record a failed attempt. The delivery is retried later, backing off,
+    /// until it has had `max_attempts`.
+    pub async fn mark_failed(
+        db: &DatabaseConnection,
+        delivery: &webhook_deliveries::Model,
+        response: DeliveryResponse,
+        error: String,
+        max_attempts: i32,
+    ) -> Result<(), String> {
+        let mut am = webhook_deliveries::ActiveModel {
+            response_status: ActiveValue::Set(response.status),
+            response_body: ActiveValue::Set(response.body),
+            last_error: ActiveValue::Set(Some(error)),
+            ..Default::default()
+        };
+        match queue::retry_at(delivery.attempts, max_attempts) {
+            Some(at) => am.next_attempt_at = ActiveValue::Set(at),
+            None => am.status = ActiveValue::Set(DeliveryStatus::Failed),
+        }
+        Deliveries::update_many()
+            .set(am)
+            .filter(webhook_deliveries::Column::Id.eq(delivery.id))
+            .exec(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        Ok(())
+    }
+
+    /// Deliveries to `user_id`'s webhooks, or to one of them, newest first.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn list_deliveries(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        webhook_id: Option<Uuid>,
+        status: Option<DeliveryStatus>,
+        after_id: Option<Uuid>,
+        after_created_at: Option<chrono::NaiveDateTime>,
+        limit: Option<u64>,
+    ) -> Result<Vec<webhook_deliveries::Model>, String> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+
+        let mut q = Deliveries::find()
+            .inner_join(Webhooks)
+            .filter(webhooks::Column::UserId.eq(user_id))
+            .order_by_desc(webhook_deliveries::Column::CreatedAt)
+            .order_by_desc(webhook_deliveries::Column::Id);
+        if let Some(webhook_id) = webhook_id {
+            q = q.filter(webhook_deliveries::Column::WebhookId.eq(webhook_id));
+        }
+        if let Some(status) = status {
+            q = q.filter(webhook_deliveries::Column::Status.eq(status));
+        }
+        if let (Some(at), Some(aid)) = (after_created_at, after_id) {
+            q = q.filter(
+                Condition::any()
+                    .add(webhook_deliveries::Column::CreatedAt.lt(at))
+                    .add(
+                        Condition::all()
+                            .add(webhook_deliveries::Column::CreatedAt.eq(at))
+                            .add(webhook_deliveries::Column::Id.lt(aid)),
+                    ),
+            );
+        }
+
+        q.limit(limit)
+            .all(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::*;
+
+    #[tokio::test]
+    async fn test_events_are_queued_for_listening_webhooks_and_redelivered() {
+        let db = setup_test_db().await;
+        let (owner, email) = create_test_user(&db, "repo_webhooks").await;
+        let (other, other_email) = create_test_user(&db, "repo_webhooks_other").await;
+
+        let publish = WebhookRepository::create(
+            &db,
+            owner.id,
+            "https://ci.example/hook".to_string(),
+            "whsec_a".to_string(),
+            vec!["post.published".to_string(), "post.deleted".to_string()],
+        )
+        .await
+        .unwrap();
+        let assets = WebhookRepository::create(
+            &db,
+            owner.id,
+            "https://cdn.example/hook".to_string(),
+            "whsec_b".to_string(),
+            vec!["asset.created".to_string()],
+        )
+        .await
+        .unwrap();
+
+        let payload = serde_json::json!({ "event": "post.published" });
+        let queued = WebhookRepository::enqueue(&db, owner.id, "post.published", payload.clone()).await.unwrap();
+        assert_eq!(queued, 1);
+        assert_eq!(WebhookRepository::enqueue(&db, other.id, "post.published", payload.clone()).await.unwrap(), 0);
+
+        WebhookRepository::update(&db, owner.id, publish.id, None, None, Some(false)).await.unwrap();
+        assert_eq!(WebhookRepository::enqueue(&db, owner.id, "post.published", payload).await.unwrap(), 0);
+        assert!(WebhookRepository::update(&db, other.id, assets.id, None, None, Some(false)).await.unwrap().is_none());
+
+        let log = WebhookRepository::list_deliveries(&db, owner.id, None, None, None, None, None).await.unwrap();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].webhook_id, publish.id);
+        assert_eq!(log[0].status, DeliveryStatus::Queued);
+
+        assert!(WebhookRepository::redeliver(&db, other.id, log[0].id).await.unwrap().is_none());
+        let again = WebhookRepository::redeliver(&db, owner.id, log[0].id).await.unwrap().unwrap();
+        assert_eq!(again.payload, log[0].payload);
+        assert_eq!(again.attempts, 0);
+        let log = WebhookRepository::list_deliveries(&db, owner.id, Some(publish.id), None, None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(log.len(), 2);
+
+        assert!(!WebhookRepository::delete(&db, other.id, publish.id).await.unwrap());
+        assert!(WebhookRepository::delete(&db, owner.id, publish.id).await.unwrap());
+        let log = WebhookRepository::list_deliveries(&db, owner.id, None, None, None, None, None).await.unwrap();
+        assert!(log.is_empty());
+
+        cleanup_user_by_email(&db, &email).await;
+        cleanup_user_by_email(&db, &other_email).await;
+    }
+}
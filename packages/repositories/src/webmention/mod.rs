@@ -0,0 +1,474 @@
+use crate::queue::{self, Queue};
+use models::outgoing_webmentions::{self, Entity as Outgoing};
+use models::posts;
+use models::sea_orm_active_enums::{CommentStatus, DeliveryStatus, WebmentionKind, WebmentionVerification};
+use models::webmentions::{self, Entity as Webmentions};
+use sea_orm::entity::prelude::Uuid;
+use sea_orm::*;
+
+const DEFAULT_PAGE_SIZE: u64 = 20;
+const MAX_PAGE_SIZE: u64 = 100;
+
+pub const WEBMENTION_DEFAULT_PAGE_SIZE: u64 = DEFAULT_PAGE_SIZE;
+
+const INCOMING: Queue = Queue {
+    table: "webmentions",
+    state: "verification",
+    returning: "id, post_id, user_id, source, target, verification::text as verification, \
+                attempts, next_attempt_at, last_error, verified_at, status::text as status, \
+                moderated_at, kind::text as kind, author_name, author_url, author_photo, \
+                title, content, published_at, created_at",
+};
+
+const OUTGOING: Queue = Queue {
+    table: "outgoing_webmentions",
+    state: "status",
+    returning: "id, user_id, post_id, source, target, endpoint, status::text as status, \
+                attempts, next_attempt_at, response_status, last_error, sent_at, created_at",
+};
+
+/// What a verified source says about itself.
+#[derive(Clone, Debug, Default)]
+pub struct MentionDetails {
+    pub kind: Option<WebmentionKind>,
+    pub author_name: Option<String>,
+    pub author_url: Option<String>,
+    pub author_photo: Option<String>,
+    pub title: Option<String>,
+    pub content: Option<String>,
+    pub published_at: Option<chrono::NaiveDateTime>,
+}
+
+pub struct WebmentionRepository;
+
+impl WebmentionRepository {
+    /// The published post `key`, a slug or an id, names.
+    pub async fn published_post(db: &DatabaseConnection, key: &str) -> Result<Option<posts::Model>, String> {
+        let mut matches = Condition::any().add(posts::Column::Slug.eq(key));
+        if let Ok(id) = key.parse::<Uuid>() {
+            matches = matches.add(posts::Column::Id.eq(id));
+        }
+        posts::Entity::find()
+            .filter(matches)
+            .filter(posts::Column::IsPublished.eq(true))
+            .one(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    /// Record that `source` says it links to `target`, a page of `post`, and
+    /// queue the source to be checked. Hearing of a known mention again
+    /// checks it again, keeping its moderation status.
+    pub async fn receive(
+        db: &DatabaseConnection,
+        post: &posts::Model,
+        source: &str,
+        target: &str,
+    ) -> Result<webmentions::Model, String> {
+        let stmt = Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            "insert into webmentions (post_id, user_id, source, target, next_attempt_at) \
+             values ($1, $2, $3, $4, $5) \
+             on conflict (source, target) do update set post_id = excluded.post_id, \
+             user_id = excluded.user_id, verification = 'queued', attempts = 0, \
+             next_attempt_at = excluded.next_attempt_at, last_error = null \
+             returning id",
+            [
+                post.id.into(),
+                post.user_id.into(),
+                source.into(),
+                target.into(),
+                chrono::Utc::now().naive_utc().into(),
+            ],
+        );
+        let row = db
+            .query_one(stmt)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?
+            .ok_or("Database error: no row returned")?;
+        let id: Uuid = row.try_get("", "id").map_err(|e| format!("Database error: {e}"))?;
+        Webmentions::find_by_id(id)
+            .one(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?
+            .ok_or_else(|| "Database error: mention vanished".to_string())
+    }
+
+    /// Take up to `limit` mentions whose sources are due to be checked,
+    /// counting an attempt for each.
+    pub async fn claim_unverified(db: &DatabaseConnection, limit: u64) -> Result<Vec<webmentions::Model>, String> {
+        INCOMING.claim_due::<Webmentions>(db, limit).await
+    }
+
+    pub async fn mark_verified(db: &DatabaseConnection, id: Uuid, details: MentionDetails) -> Result<(), String> {
+        Webmentions::update_many()
+            .set(webmentions::ActiveModel {
+                verification: ActiveValue::Set(WebmentionVerification::Verified),
+                verified_at: ActiveValue::Set(Some(chrono::Utc::now().naive_utc())),
+                last_error: ActiveValue::Set(None),
+                kind: ActiveValue::Set(details.kind.unwrap_or(WebmentionKind::Mention)),
+                author_name: ActiveValue::Set(details.author_name),
+                author_url: ActiveValue::Set(details.author_url),
+                author_photo: ActiveValue::Set(details.author_photo),
+                title: ActiveValue::Set(details.title),
+                content: ActiveValue::Set(details.content),
+                published_at: ActiveValue::Set(details.published_at),
+                ..Default::default()
+            })
+            .filter(webmentions::Column::Id.eq(id))
+            .exec(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        Ok(())
+    }
+
+    /// Record that a mention's source couldn't be checked. It is checked
+    /// again later, backing off, until it has had `max_attempts`; then it
+    /// is invalid, as it is at once with `max_attempts` 0.
+    pub async fn mark_unverified(
+        db: &DatabaseConnection,
+        mention: &webmentions::Model,
+        error: String,
+        max_attempts: i32,
+    ) -> Result<(), String> {
+        let mut am = webmentions::ActiveModel { last_error: ActiveValue::Set(Some(error)), ..Default::default() };
+        match queue::retry_at(mention.attempts, max_attempts) {
+            Some(at) => am.next_attempt_at = ActiveValue::Set(at),
+            None => am.verification = ActiveValue::Set(WebmentionVerification::Invalid),
+        }
+        Webmentions::update_many()
+            .set(am)
+            .filter(webmentions::Column::Id.eq(mention.id))
+            .exec(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        Ok(())
+    }
+
+    /// Forget a mention whose source no longer links to its target.
+    pub async fn remove(db: &DatabaseConnection, id: Uuid) -> Result<(), String> {
+        Webmentions::delete_by_id(id)
+            .exec(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        Ok(())
+    }
+
+    /// A post's verified, approved mentions, oldest first.
+    pub async fn approved_for_post(db: &DatabaseConnection, post_id: Uuid) -> Result<Vec<webmentions::Model>, String> {
+        Webmentions::find()
+            .filter(webmentions::Column::PostId.eq(post_id))
+            .filter(webmentions::Column::Verification.eq(WebmentionVerification::Verified))
+            .filter(webmentions::Column::Status.eq(CommentStatus::Approved))
+            .order_by_asc(webmentions::Column::CreatedAt)
+            .order_by_asc(webmentions::Column::Id)
+            .all(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    /// Verified mentions of `user_id`'s posts, newest first, for moderation.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn list(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        status: Option<CommentStatus>,
+        post_id: Option<Uuid>,
+        after_id: Option<Uuid>,
+        after_created_at: Option<chrono::NaiveDateTime>,
+        limit: Option<u64>,
+    ) -> Result<Vec<webmentions::Model>, String> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+
+        let mut q = Webmentions::find()
+            .filter(webmentions::Column::UserId.eq(user_id))
+            .filter(webmentions::Column::Verification.eq(WebmentionVerification::Verified))
+            .order_by_desc(webmentions::Column::CreatedAt)
+            .order_by_desc(webmentions::Column::Id);
+        if let Some(status) = status {
+            q = q.filter(webmentions::Column::Status.eq(status));
+        }
+        if let Some(post_id) = post_id {
+            q = q.filter(webmentions::Column::PostId.eq(post_id));
+        }
+        if let (Some(at), Some(aid)) = (after_created_at, after_id) {
+            q = q.filter(
+                Condition::any()
+                    .add(webmentions::Column::CreatedAt.lt(at))
+                    .add(
+                        Condition::all()
+                            .add(webmentions::Column::CreatedAt.eq(at))
+                            .add(webmentions::Column::Id.lt(aid)),
+                    ),
+            );
+        }
+
+        q.limit(limit)
+            .all(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    /// Approve, reject or mark spam one of `user_id`'s mentions. `None` if
+    /// there is no such mention.
+    pub async fn set_status(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        id: Uuid,
+        status: CommentStatus,
+    ) -> Result<Option<webmentions::Model>, String> {
+        let result = Webmentions::update_many()
+            .set(webmentions::ActiveModel {
+                status: ActiveValue::Set(status),
+                moderated_at: ActiveValue::Set(Some(chrono::Utc::now().naive_utc())),
+                ..Default::default()
+            })
+            .filter(webmentions::Column::Id.eq(id))
+            .filter(webmentions::Column::UserId.eq(user_id))
+            .exec(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        if result.rows_affected == 0 {
+            return Ok(None);
+        }
+        Webmentions::find_by_id(id)
+            .one(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    pub async fn delete(db: &DatabaseConnection, user_id: Uuid, id: Uuid) -> Result<bool, String> {
+        Webmentions::delete_many()
+            .filter(webmentions::Column::Id.eq(id))
+            .filter(webmentions::Column::UserId.eq(user_id))
+            .exec(db)
+            .await
+            .map(|r| r.rows_affected > 0)
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    /// Queue a mention of each of `targets` from `source`, the post's URL,
+    /// along with one to every page the post mentioned before, so pages it
+    /// no longer links to hear about that too. Returns how many were queued.
+    pub async fn queue_outgoing(
+        db: &DatabaseConnection,
+        post: &posts::Model,
+        source: &str,
+        targets: Vec<String>,
+    ) -> Result<u64, String> {
+        let now = chrono::Utc::now().naive_utc();
+        if !targets.is_empty() {
+            let rows = targets.into_iter().map(|target| outgoing_webmentions::ActiveModel {
+                id: ActiveValue::Set(Uuid::new_v4()),
+                user_id: ActiveValue::Set(post.user_id),
+                post_id: ActiveValue::Set(post.id),
+                source: ActiveValue::Set(source.to_string()),
+                target: ActiveValue::Set(target),
+                status: ActiveValue::Set(DeliveryStatus::Queued),
+                attempts: ActiveValue::Set(0),
+                next_attempt_at: ActiveValue::Set(now),
+                created_at: ActiveValue::Set(now),
+                ..Default::default()
+            });
+            Outgoing::insert_many(rows)
+                .on_conflict(
+                    sea_query::OnConflict::columns([
+                        outgoing_webmentions::Column::PostId,
+                        outgoing_webmentions::Column::Target,
+                    ])
+                    .do_nothing()
+                    .to_owned(),
+                )
+                .do_nothing()
+                .exec(db)
+                .await
+                .map_err(|e| format!("Database error: {e}"))?;
+        }
+        Outgoing::update_many()
+            .set(outgoing_webmentions::ActiveModel {
+                source: ActiveValue::Set(source.to_string()),
+                status: ActiveValue::Set(DeliveryStatus::Queued),
+                attempts: ActiveValue::Set(0),
+                next_attempt_at: ActiveValue::Set(now),
+                ..Default::default()
+            })
+            .filter(outgoing_webmentions::Column::PostId.eq(post.id))
+            .exec(db)
+            .await
+            .map(|r| r.rows_affected)
+            .map_err(|e| format!("Database error: {e}"))
+    }
+
+    /// Take up to `limit` queued outgoing mentions that are due, counting an
+    /// attempt for each.
+    pub async fn claim_outgoing(
+        db: &DatabaseConnection,
+        limit: u64,
+    ) -> Result<Vec<outgoing_webmentions::Model>, String> {
+        OUTGOING.claim_due::<Outgoing>(db, limit).await
+    }
+
+    pub async fn mark_sent(
+        db: &DatabaseConnection,
+        id: Uuid,
+        endpoint: String,
+        response_status: Option<i32>,
+    ) -> Result<(), String> {
+        Outgoing::update_many()
+            .set(outgoing_webmentions::ActiveModel {
+                endpoint: ActiveValue::Set(Some(endpoint)),
+                status: ActiveValue::Set(DeliveryStatus::Sent),
+                response_status: ActiveValue::Set(response_status),
+                last_error: ActiveValue::Set(None),
+                sent_at: ActiveValue::Set(Some(chrono::Utc::now().naive_utc())),
+                ..Default::default()
+            })
+            .filter(outgoing_webmentions::Column::Id.eq(id))
+            .exec(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        Ok(())
+    }
+
+    /// Record a failed attempt. The mention is retried later, backing off,
+    /// until it has had `max_attempts`.
+    pub async fn mark_send_failed(
+        db: &DatabaseConnection,
+        mention: &outgoing_webmentions::Model,
+        endpoint: Option<String>,
+        response_status: Option<i32>,
+        error: String,
+        max_attempts: i32,
+    ) -> Result<(), String> {
+        let mut am = outgoing_webmentions::ActiveModel {
+            endpoint: ActiveValue::Set(endpoint),
+            response_status: ActiveValue::Set(response_status),
+            last_error: ActiveValue::Set(Some(error)),
+            ..Default::default()
+        };
+        match queue::retry_at(mention.attempts, max_attempts) {
+            Some(at) => am.next_attempt_at = ActiveValue::Set(at),
+            None => am.status = ActiveValue::Set(DeliveryStatus::Failed),
+        }
+        Outgoing::update_many()
+            .set(am)
+            .filter(outgoing_webmentions::Column::Id.eq(mention.id))
+            .exec(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))?;
+        Ok(())
+    }
+
+    /// Mentions sent, or to be sent, for `user_id`'s posts, or for one of
+    /// them, newest first.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn list_outgoing(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        post_id: Option<Uuid>,
+        status: Option<DeliveryStatus>,
+        after_id: Option<Uuid>,
+        after_created_at: Option<chrono::NaiveDateTime>,
+        limit: Option<u64>,
+    ) -> Result<Vec<outgoing_webmentions::Model>, String> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+
+        let mut q = Outgoing::find()
+            .filter(outgoing_webmentions::Column::UserId.eq(user_id))
+            .order_by_desc(outgoing_webmentions::Column::CreatedAt)
+            .order_by_desc(outgoing_webmentions::Column::Id);
+        if let Some(post_id) = post_id {
+            q = q.filter(outgoing_webmentions::Column::PostId.eq(post_id));
+        }
+        if let Some(status) = status {
+            q = q.filter(outgoing_webmentions::Column::Status.eq(status));
+        }
+        if let (Some(at), Some(aid)) = (after_created_at, after_id) {
+            q = q.filter(
+                Condition::any()
+                    .add(outgoing_webmentions::Column::CreatedAt.lt(at))
+                    .add(
+                        Condition::all()
+                            .add(outgoing_webmentions::Column::CreatedAt.eq(at))
+                            .add(outgoing_webmentions::Column::Id.lt(aid)),
+                    ),
+            );
+        }
+
+        q.limit(limit)
+            .all(db)
+            .await
+            .map_err(|e| format!("Database error: {e}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::*;
+
+    #[tokio::test]
+    async fn test_mentions_are_received_moderated_and_queued() {
+        let db = setup_test_db().await;
+        let (owner, email) = create_test_user(&db, "repo_webmentions").await;
+        let post = create_test_post(&db, owner.id, "Mentioned", "Body", true).await;
+        let slug = format!("mentioned-{}", post.id.simple());
+        let mut am = post.clone().into_active_model();
+        am.slug = ActiveValue::Set(Some(slug.clone()));
+        let post = am.update(&db).await.unwrap();
+
+        assert_eq!(WebmentionRepository::published_post(&db, &slug).await.unwrap().unwrap().id, post.id);
+        assert_eq!(WebmentionRepository::published_post(&db, &post.id.to_string()).await.unwrap().unwrap().id, post.id);
+        assert!(WebmentionRepository::published_post(&db, "no-such-post").await.unwrap().is_none());
+
+        let source = format!("https://elsewhere.example/{}", post.id);
+        let mention = WebmentionRepository::receive(&db, &post, &source, "https://blog.example/p").await.unwrap();
+        assert_eq!(mention.verification, WebmentionVerification::Queued);
+        assert_eq!(mention.status, CommentStatus::Pending);
+
+        // Unverified mentions aren't listed or shown.
+        assert!(WebmentionRepository::list(&db, owner.id, None, None, None, None, None).await.unwrap().is_empty());
+        let details = MentionDetails {
+            kind: Some(WebmentionKind::Reply),
+            author_name: Some("Ana".to_string()),
+            ..Default::default()
+        };
+        WebmentionRepository::mark_verified(&db, mention.id, details).await.unwrap();
+        WebmentionRepository::set_status(&db, owner.id, mention.id, CommentStatus::Approved).await.unwrap();
+        let shown = WebmentionRepository::approved_for_post(&db, post.id).await.unwrap();
+        assert_eq!(shown.len(), 1);
+        assert_eq!(shown[0].kind, WebmentionKind::Reply);
+        assert_eq!(shown[0].author_name.as_deref(), Some("Ana"));
+
+        // Hearing of it again checks it again but keeps the approval.
+        let again = WebmentionRepository::receive(&db, &post, &source, "https://blog.example/p").await.unwrap();
+        assert_eq!(again.id, mention.id);
+        assert_eq!(again.verification, WebmentionVerification::Queued);
+        assert_eq!(again.status, CommentStatus::Approved);
+        assert!(WebmentionRepository::delete(&db, owner.id, mention.id).await.unwrap());
+
+        let source = "https://blog.example/mentioned/";
+        let queued = WebmentionRepository::queue_outgoing(&db, &post, source, vec!["https://a.example/".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(queued, 1);
+        // Saving again without the link still queues it, to say it's gone.
+        let queued = WebmentionRepository::queue_outgoing(&db, &post, source, vec!["https://b.example/".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(queued, 2);
+        let log = WebmentionRepository::list_outgoing(&db, owner.id, Some(post.id), None, None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(log.len(), 2);
+        WebmentionRepository::mark_send_failed(&db, &log[0], None, None, "No Webmention endpoint".to_string(), 0)
+            .await
+            .unwrap();
+        let failed = WebmentionRepository::list_outgoing(&db, owner.id, None, Some(DeliveryStatus::Failed), None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(failed.len(), 1);
+
+        cleanup_user_by_email(&db, &email).await;
+    }
+}
@@ -9,6 +9,39 @@ fn hash_key(raw: &str) -> String {
     hasher.finalize().iter().fold(String::new(), |mut s, b| { use std::fmt::Write; write!(s, "{:02x}", b).unwrap(); s })
 }
 
+/// What a key may do beyond reading the public API. Micropub clients ask
+/// for these by name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scope {
+    Create,
+    Update,
+    Delete,
+    Media,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 4] = [Scope::Create, Scope::Update, Scope::Delete, Scope::Media];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::Create => "create",
+            Scope::Update => "update",
+            Scope::Delete => "delete",
+            Scope::Media => "media",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|s| s.as_str() == name)
+    }
+}
+
+/// The scopes stored on `key`, leaving out any this build doesn't know.
+pub fn scopes(key: &api_keys::Model) -> Vec<Scope> {
+    let names: Vec<String> = serde_json::from_value(key.scopes.clone()).unwrap_or_default();
+    names.iter().filter_map(|n| Scope::parse(n)).collect()
+}
+
 /// Returns `(raw_key, key_hash)`. Raw key shown once to user.
 pub fn generate() -> (String, String) {
     let raw = format!("slq_{}", Uuid::new_v4().simple());
@@ -22,10 +55,28 @@ pub async fn create(
     label: String,
     key_hash: String,
 ) -> Result<api_keys::Model, DbErr> {
+    create_scoped(db, user_id, label, key_hash, &[]).await
+}
+
+/// Like `create`, for a key that may also do what `scopes` name.
+pub async fn create_scoped(
+    db: &DatabaseConnection,
+    user_id: Uuid,
+    label: String,
+    key_hash: String,
+    scopes: &[Scope],
+) -> Result<api_keys::Model, DbErr> {
+    let mut names: Vec<&str> = Vec::new();
+    for name in scopes.iter().map(|s| s.as_str()) {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
     api_keys::ActiveModel {
         user_id: ActiveValue::set(user_id),
         key_hash: ActiveValue::set(key_hash),
         label: ActiveValue::set(label),
+        scopes: ActiveValue::set(serde_json::json!(names)),
         ..Default::default()
     }
     .insert(db)
@@ -34,6 +85,11 @@ pub async fn create(
 
 /// Returns `Some(user_id)` if key is valid, updates last_used_at fire-and-forget.
 pub async fn validate(db: &DatabaseConnection, raw_key: &str) -> Option<Uuid> {
+    validate_scoped(db, raw_key).await.map(|(user_id, _)| user_id)
+}
+
+/// Like `validate`, also returning what the key may do.
+pub async fn validate_scoped(db: &DatabaseConnection, raw_key: &str) -> Option<(Uuid, Vec<Scope>)> {
     let hash = hash_key(raw_key);
     let record = ApiKeys::find()
         .filter(api_keys::Column::KeyHash.eq(&hash))
@@ -42,12 +98,13 @@ pub async fn validate(db: &DatabaseConnection, raw_key: &str) -> Option<Uuid> {
         .ok()??;
 
     let user_id = record.user_id;
+    let scopes = scopes(&record);
     // Fire-and-forget: update last_used_at
     let mut am = record.into_active_model();
     am.last_used_at = ActiveValue::set(Some(chrono::Utc::now().naive_utc()));
     let _ = am.update(db).await;
 
-    Some(user_id)
+    Some((user_id, scopes))
 }
 
 pub async fn revoke(db: &DatabaseConnection, key_id: Uuid, user_id: Uuid) -> Result<(), DbErr> {
@@ -44,6 +44,24 @@ impl LocalStorageDriver {
         Ok(())
     }
 
+    /// Top-level directory names under the base dir (one per asset).
+    pub fn list_prefixes(&self) -> Result<Vec<String>, StorageError> {
+        if !self.base_dir.exists() {
+            return Ok(Vec::new());
+        }
+        let entries = std::fs::read_dir(&self.base_dir)
+            .map_err(|e| StorageError(format!("read_dir: {e}")))?;
+        let mut prefixes = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(|e| StorageError(format!("read_dir: {e}")))?;
+            if entry.path().is_dir() {
+                prefixes.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+        prefixes.sort();
+        Ok(prefixes)
+    }
+
     pub fn url(&self, key: &str) -> String {
         format!("/assets/{key}")
     }
@@ -113,4 +131,23 @@ mod tests {
         let driver = LocalStorageDriver::new("/uploads");
         assert!(driver.delete_dir("../other_dir").is_err());
     }
+
+    #[test]
+    fn list_prefixes_returns_only_directories() {
+        let dir = std::env::temp_dir().join(format!("slq_list_{}", uuid::Uuid::new_v4()));
+        let driver = LocalStorageDriver::new(&dir);
+        driver.put("b/original.webp", vec![1]).unwrap();
+        driver.put("a/original.webp", vec![1]).unwrap();
+        driver.put("stray.txt", vec![1]).unwrap();
+
+        assert_eq!(driver.list_prefixes().unwrap(), vec!["a", "b"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn list_prefixes_missing_base_is_empty() {
+        let driver = LocalStorageDriver::new("/nonexistent/slq_uploads");
+        assert!(driver.list_prefixes().unwrap().is_empty());
+    }
 }
@@ -1,5 +1,7 @@
 mod local;
+mod preview;
 pub use local::LocalStorageDriver;
+pub use preview::{store_preview, PreviewCard, PREVIEW_HEIGHT, PREVIEW_PREFIX, PREVIEW_WIDTH};
 
 use image::imageops::FilterType;
 use image::ImageFormat;
@@ -37,6 +39,12 @@ impl StorageDriver {
         }
     }
 
+    pub async fn list_prefixes(&self) -> Result<Vec<String>, StorageError> {
+        match self {
+            StorageDriver::Local(d) => d.list_prefixes(),
+        }
+    }
+
     pub fn url(&self, key: &str) -> String {
         match self {
             StorageDriver::Local(d) => d.url(key),
@@ -53,12 +61,41 @@ const VARIANTS: &[(&str, Option<u32>)] = &[
     ("original", None),
 ];
 
-/// Decode image, produce 5 WebP variants, store via driver. Returns original byte count.
+/// Size of the original upload and its pixel dimensions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProcessedImage {
+    pub size_bytes: u64,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Dimensions of an image scaled to fit in `max`×`max`, rounded the way
+/// `DynamicImage::resize` rounds them. Smaller images keep their size.
+fn fit(width: u32, height: u32, max: u32) -> (u32, u32) {
+    if width <= max && height <= max {
+        return (width, height);
+    }
+    let ratio = f64::min(max as f64 / width as f64, max as f64 / height as f64);
+    let scale = |side: u32| ((side as f64 * ratio).round() as u32).max(1);
+    (scale(width), scale(height))
+}
+
+/// `(variant, width, height)` of every resized variant of a `width`×`height`
+/// original, smallest first.
+pub fn resized_variants(width: u32, height: u32) -> Vec<(&'static str, u32, u32)> {
+    VARIANTS
+        .iter()
+        .filter_map(|(name, max)| max.map(|max| (*name, fit(width, height, max))))
+        .map(|(name, (w, h))| (name, w, h))
+        .collect()
+}
+
+/// Decode image, produce 5 WebP variants, store via driver.
 pub async fn process_and_store(
     data: &[u8],
     asset_id: Uuid,
     driver: &StorageDriver,
-) -> Result<u64, String> {
+) -> Result<ProcessedImage, String> {
     let original_size = data.len() as u64;
     let img = image::load_from_memory(data).map_err(|e| format!("decode image: {e}"))?;
 
@@ -86,5 +123,31 @@ pub async fn process_and_store(
             .map_err(|e| format!("store {variant_name}: {e}"))?;
     }
 
-    Ok(original_size)
+    Ok(ProcessedImage {
+        size_bytes: original_size,
+        width: img.width(),
+        height: img.height(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, GenericImageView};
+
+    #[test]
+    fn resized_variants_match_resize() {
+        for (w, h) in [(3000, 2000), (700, 1900), (500, 300), (2001, 1)] {
+            let img = DynamicImage::new_rgb8(w, h);
+            for (name, vw, vh) in resized_variants(w, h) {
+                let max = VARIANTS.iter().find(|v| v.0 == name).unwrap().1.unwrap();
+                let expected = if w > max || h > max {
+                    img.resize(max, max, FilterType::Nearest).dimensions()
+                } else {
+                    (w, h)
+                };
+                assert_eq!((vw, vh), expected, "{name} of {w}x{h}");
+            }
+        }
+    }
 }
@@ -0,0 +1,280 @@
+use super::StorageDriver;
+use ab_glyph::{point, Font, FontRef, GlyphId, PxScale, ScaleFont};
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, Rgb, RgbImage};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+/// Storage prefix for generated share cards, kept apart from asset ids.
+pub const PREVIEW_PREFIX: &str = "previews";
+
+pub const PREVIEW_WIDTH: u32 = 1200;
+pub const PREVIEW_HEIGHT: u32 = 630;
+
+/// Bumped when the layout changes, so existing cards are regenerated.
+const LAYOUT_VERSION: &str = "1";
+
+static FONT: &[u8] = include_bytes!("../../fonts/DejaVuSans-Bold.ttf");
+
+const MARGIN: f32 = 80.0;
+const SMALL_SIZE: f32 = 32.0;
+const TITLE_SIZES: &[f32] = &[76.0, 64.0, 54.0, 46.0];
+const LINE_HEIGHT: f32 = 1.2;
+/// Top of the title's first line and lowest its last baseline may go, clear
+/// of the blog name above and the author below.
+const TITLE_TOP: f32 = MARGIN + SMALL_SIZE + 70.0;
+const TITLE_BOTTOM: f32 = PREVIEW_HEIGHT as f32 - MARGIN - SMALL_SIZE - 40.0;
+const WHITE: [u8; 3] = [255, 255, 255];
+const MUTED: [u8; 3] = [203, 213, 225];
+const ACCENT: [u8; 3] = [245, 158, 11];
+
+/// What goes on a post's share card.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PreviewCard<'a> {
+    pub title: &'a str,
+    pub author: Option<&'a str>,
+    pub site_title: &'a str,
+    /// Storage key of an image to darken behind the text
+    pub background: Option<&'a str>,
+}
+
+impl PreviewCard<'_> {
+    /// Storage key of this card for `post_id`. It hashes everything drawn on
+    /// the card, so a changed title gets a new key, and a new URL that share
+    /// caches haven't seen.
+    pub fn key(&self, post_id: Uuid) -> String {
+        let mut hasher = Sha256::new();
+        for part in [LAYOUT_VERSION, self.title, self.author.unwrap_or(""), self.site_title, self.background.unwrap_or("")] {
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part.as_bytes());
+        }
+        let digest = hasher.finalize();
+        let hash: String = digest[..8].iter().map(|b| format!("{b:02x}")).collect();
+        format!("{PREVIEW_PREFIX}/{post_id}/{hash}.png")
+    }
+
+    /// Draw the card as a PNG, over `background` if given.
+    pub fn render(&self, background: Option<&DynamicImage>) -> Result<Vec<u8>, String> {
+        let font = FontRef::try_from_slice(FONT).map_err(|e| format!("load font: {e}"))?;
+        let mut img = match background {
+            Some(bg) => {
+                let mut img = bg.resize_to_fill(PREVIEW_WIDTH, PREVIEW_HEIGHT, FilterType::Triangle).to_rgb8();
+                for px in img.pixels_mut() {
+                    px.0 = px.0.map(|c| (c as f32 * 0.4) as u8);
+                }
+                img
+            }
+            None => RgbImage::from_fn(PREVIEW_WIDTH, PREVIEW_HEIGHT, |_, y| {
+                let t = y as f32 / PREVIEW_HEIGHT as f32;
+                let mix = |top: f32, bottom: f32| (top + (bottom - top) * t) as u8;
+                Rgb([mix(30.0, 15.0), mix(41.0, 23.0), mix(59.0, 42.0)])
+            }),
+        };
+
+        let width = PREVIEW_WIDTH as f32 - 2.0 * MARGIN;
+        draw_text(&mut img, &font, SMALL_SIZE, MARGIN, MARGIN + SMALL_SIZE, &fit_line(&font, SMALL_SIZE, self.site_title, width), MUTED);
+
+        let (size, lines) = layout_title(&font, self.title, width);
+        for (i, line) in lines.iter().enumerate() {
+            let baseline = TITLE_TOP + size + i as f32 * size * LINE_HEIGHT;
+            draw_text(&mut img, &font, size, MARGIN, baseline, line, WHITE);
+        }
+
+        if let Some(author) = self.author.filter(|a| !a.trim().is_empty()) {
+            let y = PREVIEW_HEIGHT as f32 - MARGIN;
+            draw_text(&mut img, &font, SMALL_SIZE, MARGIN, y, &fit_line(&font, SMALL_SIZE, author, width), MUTED);
+        }
+        for y in PREVIEW_HEIGHT - 12..PREVIEW_HEIGHT {
+            for x in 0..PREVIEW_WIDTH {
+                img.put_pixel(x, y, Rgb(ACCENT));
+            }
+        }
+
+        let mut buf = Vec::new();
+        DynamicImage::ImageRgb8(img)
+            .write_to(&mut std::io::Cursor::new(&mut buf), ImageFormat::Png)
+            .map_err(|e| format!("encode png: {e}"))?;
+        Ok(buf)
+    }
+}
+
+/// Render `card` for `post_id` and store it, replacing the post's previous
+/// cards. Returns the new storage key.
+pub async fn store_preview(card: &PreviewCard<'_>, post_id: Uuid, driver: &StorageDriver) -> Result<String, String> {
+    let background = match card.background {
+        Some(key) => {
+            let data = driver.get(key).await.map_err(|e| format!("background: {e}"))?;
+            Some(image::load_from_memory(&data).map_err(|e| format!("decode background: {e}"))?)
+        }
+        None => None,
+    };
+    let png = card.render(background.as_ref())?;
+    let key = card.key(post_id);
+    driver
+        .delete_dir(&format!("{PREVIEW_PREFIX}/{post_id}"))
+        .await
+        .map_err(|e| format!("remove old preview: {e}"))?;
+    driver.put(&key, png).await.map_err(|e| format!("store preview: {e}"))?;
+    Ok(key)
+}
+
+fn text_width(font: &FontRef, size: f32, text: &str) -> f32 {
+    let scaled = font.as_scaled(PxScale::from(size));
+    let mut width = 0.0;
+    let mut last: Option<GlyphId> = None;
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(prev) = last {
+            width += scaled.kern(prev, id);
+        }
+        width += scaled.h_advance(id);
+        last = Some(id);
+    }
+    width
+}
+
+/// Greedily wrap `text` into lines no wider than `width`, breaking long
+/// words between characters.
+fn wrap(font: &FontRef, size: f32, text: &str, width: f32) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let candidate = if line.is_empty() { word.to_string() } else { format!("{line} {word}") };
+        if text_width(font, size, &candidate) <= width {
+            line = candidate;
+            continue;
+        }
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+        for c in word.chars() {
+            line.push(c);
+            if text_width(font, size, &line) > width && line.chars().count() > 1 {
+                line.pop();
+                lines.push(std::mem::replace(&mut line, c.to_string()));
+            }
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// `text` on one line, cut short with `…` if it is wider than `width`.
+fn fit_line(font: &FontRef, size: f32, text: &str, width: f32) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text_width(font, size, &text) <= width {
+        return text;
+    }
+    let mut cut = text;
+    while !cut.is_empty() && text_width(font, size, &format!("{cut}…")) > width {
+        cut.pop();
+    }
+    format!("{}…", cut.trim_end())
+}
+
+/// Lines of `size` text that fit between [`TITLE_TOP`] and [`TITLE_BOTTOM`].
+fn max_lines(size: f32) -> usize {
+    ((TITLE_BOTTOM - TITLE_TOP - size) / (size * LINE_HEIGHT)) as usize + 1
+}
+
+/// The largest title size at which the whole title fits; at the smallest
+/// size, the last line is cut short.
+fn layout_title(font: &FontRef, title: &str, width: f32) -> (f32, Vec<String>) {
+    for &size in TITLE_SIZES {
+        let lines = wrap(font, size, title, width);
+        if lines.len() <= max_lines(size) {
+            return (size, lines);
+        }
+    }
+    let size = TITLE_SIZES[TITLE_SIZES.len() - 1];
+    let mut lines = wrap(font, size, title, width);
+    let rest = lines.split_off(max_lines(size) - 1).join(" ");
+    lines.push(fit_line(font, size, &format!("{rest}…"), width));
+    (size, lines)
+}
+
+/// Draw one line of text with its baseline at `y`, blending it over `img`.
+fn draw_text(img: &mut RgbImage, font: &FontRef, size: f32, x: f32, y: f32, text: &str, color: [u8; 3]) {
+    let scale = PxScale::from(size);
+    let scaled = font.as_scaled(scale);
+    let mut caret = x;
+    let mut last: Option<GlyphId> = None;
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(prev) = last {
+            caret += scaled.kern(prev, id);
+        }
+        let glyph = id.with_scale_and_position(scale, point(caret, y));
+        caret += scaled.h_advance(id);
+        last = Some(id);
+        let Some(outlined) = font.outline_glyph(glyph) else { continue };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i32 + gx as i32;
+            let py = bounds.min.y as i32 + gy as i32;
+            if px < 0 || py < 0 || px >= img.width() as i32 || py >= img.height() as i32 {
+                return;
+            }
+            let pixel = img.get_pixel_mut(px as u32, py as u32);
+            let a = coverage.clamp(0.0, 1.0);
+            for (channel, target) in pixel.0.iter_mut().zip(color) {
+                *channel = (*channel as f32 * (1.0 - a) + target as f32 * a).round() as u8;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::GenericImageView;
+
+    fn card(title: &str) -> PreviewCard<'_> {
+        PreviewCard { title, author: Some("Ada Lovelace"), site_title: "Notes", background: None }
+    }
+
+    #[test]
+    fn renders_a_card_of_the_open_graph_size() {
+        let png = card("On the Analytical Engine").render(None).unwrap();
+        let img = image::load_from_memory(&png).unwrap();
+        assert_eq!(img.dimensions(), (PREVIEW_WIDTH, PREVIEW_HEIGHT));
+        // Title text lands on the dark background.
+        let lit = (170..260).flat_map(|y| (80..600).map(move |x| (x, y))).filter(|&(x, y)| img.get_pixel(x, y)[0] > 200).count();
+        assert!(lit > 1000, "{lit}");
+
+        let bg = DynamicImage::new_rgb8(40, 30);
+        assert_eq!(image::load_from_memory(&card("x").render(Some(&bg)).unwrap()).unwrap().dimensions(), (1200, 630));
+    }
+
+    #[test]
+    fn long_titles_shrink_then_truncate() {
+        let font = FontRef::try_from_slice(FONT).unwrap();
+        let width = PREVIEW_WIDTH as f32 - 2.0 * MARGIN;
+        let (size, lines) = layout_title(&font, "Short", width);
+        assert_eq!((size, lines.len()), (TITLE_SIZES[0], 1));
+
+        let long = "word ".repeat(200);
+        let (size, lines) = layout_title(&font, &long, width);
+        assert_eq!(size, TITLE_SIZES[TITLE_SIZES.len() - 1]);
+        assert_eq!(lines.len(), max_lines(size));
+        assert!(lines.last().unwrap().ends_with('…'));
+        assert!(TITLE_TOP + size + (lines.len() - 1) as f32 * size * LINE_HEIGHT <= TITLE_BOTTOM);
+        assert!(lines.iter().all(|l| text_width(&font, size, l) <= width));
+
+        let unbroken = wrap(&font, 76.0, &"W".repeat(60), width);
+        assert!(unbroken.len() > 1 && unbroken.iter().all(|l| text_width(&font, 76.0, l) <= width));
+    }
+
+    #[test]
+    fn key_changes_with_what_is_drawn() {
+        let id = Uuid::nil();
+        let key = card("A").key(id);
+        assert!(key.starts_with("previews/00000000-0000-0000-0000-000000000000/") && key.ends_with(".png"));
+        assert_eq!(key, card("A").key(id));
+        assert_ne!(key, card("B").key(id));
+        assert_ne!(key, PreviewCard { author: None, ..card("A") }.key(id));
+        assert_ne!(key, PreviewCard { background: Some("x/large.webp"), ..card("A") }.key(id));
+    }
+}
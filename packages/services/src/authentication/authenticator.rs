@@ -1,4 +1,5 @@
 use crate::authentication::token::Token;
+use crate::config::AuthConfig;
 use models::users::{Entity as users, Model};
 use sea_orm::*;
 use std::fmt;
@@ -44,8 +45,12 @@ impl fmt::Display for AuthenticationError {
     }
 }
 
-pub async fn get_user(db: &DatabaseConnection, token: &Token) -> Result<Model, AuthenticationError> {
-    let user_id = match token.get_user_id() {
+pub async fn get_user(
+    db: &DatabaseConnection,
+    auth: &AuthConfig,
+    token: &Token,
+) -> Result<Model, AuthenticationError> {
+    let user_id = match token.get_user_id(auth) {
         Ok(id) => id,
         Err(e) => {
             tracing::warn!("invalid token");
@@ -77,7 +82,7 @@ mod tests {
         let user = create_test_user(&db, &email, "hashed_password").await;
         let token = create_test_token(&user);
 
-        let result = get_user(&db, &token).await;
+        let result = get_user(&db, &test_auth_config(), &token).await;
 
         assert!(result.is_ok());
         let fetched_user = result.unwrap();
@@ -94,7 +99,7 @@ mod tests {
         let user = create_test_user(&db, &email, "hashed_password").await;
         let token = create_expired_token(&user);
 
-        let result = get_user(&db, &token).await;
+        let result = get_user(&db, &test_auth_config(), &token).await;
 
         assert!(result.is_err());
         match result {
@@ -112,7 +117,7 @@ mod tests {
         let user = create_test_user(&db, &email, "hashed_password").await;
         let token = create_invalid_signature_token(&user);
 
-        let result = get_user(&db, &token).await;
+        let result = get_user(&db, &test_auth_config(), &token).await;
 
         assert!(result.is_err());
         match result {
@@ -128,7 +133,7 @@ mod tests {
         let db = setup_test_db().await;
         let token = create_token_for_nonexistent_user();
 
-        let result = get_user(&db, &token).await;
+        let result = get_user(&db, &test_auth_config(), &token).await;
 
         assert!(result.is_err());
         match result {
@@ -144,7 +149,7 @@ mod tests {
         let db = setup_test_db().await;
         let token = create_malformed_token();
 
-        let result = get_user(&db, &token).await;
+        let result = get_user(&db, &test_auth_config(), &token).await;
 
         assert!(result.is_err());
         match result {
@@ -1,4 +1,5 @@
 use super::token::{generate_refresh_token, hash_token, AuthError, Token};
+use crate::config::AuthConfig;
 use chrono::Utc;
 use models::refresh_tokens::{self, Entity as RefreshTokens, Model as RefreshToken};
 use sea_orm::*;
@@ -7,12 +8,15 @@ use uuid::Uuid;
 /// Create a new refresh token and store it in the database
 pub async fn create_refresh_token(
     db: &DatabaseConnection,
+    auth: &AuthConfig,
     user_id: Uuid,
     device_info: Option<String>,
 ) -> Result<String, DbErr> {
-    let token = generate_refresh_token(user_id.to_string());
+    let token = generate_refresh_token(auth, user_id.to_string());
     let token_hash = hash_token(&token);
-    let expires_at = Token::new(token.clone()).get_expiration().ok().unwrap();
+    let expires_at = Token::new(token.clone())
+        .get_expiration(auth)
+        .map_err(|e| DbErr::Custom(format!("reading refresh token expiry: {}", e.message)))?;
     let refresh_token_model = refresh_tokens::ActiveModel {
         id: ActiveValue::set(Uuid::new_v4()),
         user_id: ActiveValue::set(user_id),
@@ -134,11 +138,11 @@ mod tests {
 
     #[test]
     fn test_refresh_token_functionality() {
-        dotenvy::dotenv().ok();
+        let auth = test_auth_config();
         let user_id = Uuid::new_v4();
 
-        let token1 = generate_refresh_token(user_id.to_string());
-        let token2 = generate_refresh_token(user_id.to_string());
+        let token1 = generate_refresh_token(&auth, user_id.to_string());
+        let token2 = generate_refresh_token(&auth, user_id.to_string());
 
         assert_ne!(token1, token2);
         assert!(!token1.is_empty());
@@ -151,15 +155,15 @@ mod tests {
 
     #[test]
     fn test_multi_device_token_generation() {
-        dotenvy::dotenv().ok();
+        let auth = test_auth_config();
         let user_id = Uuid::new_v4();
-        let devices = vec!["iPhone 15", "MacBook Pro", "iPad"];
+        let devices = ["iPhone 15", "MacBook Pro", "iPad"];
 
         let mut generated_tokens = Vec::new();
         let mut generated_hashes = Vec::new();
 
         for _ in devices.iter() {
-            let token = generate_refresh_token(user_id.to_string());
+            let token = generate_refresh_token(&auth, user_id.to_string());
             let token_hash = hash_token(&token);
 
             assert!(!token.is_empty());
@@ -182,7 +186,7 @@ mod tests {
         let email = format!("test_create_{}@example.com", Uuid::new_v4());
         let user = create_test_user(&db, &email, "password").await;
 
-        let token = create_refresh_token(&db, user.id, None).await.unwrap();
+        let token = create_refresh_token(&db, &test_auth_config(), user.id, None).await.unwrap();
 
         assert!(!token.is_empty());
 
@@ -204,7 +208,7 @@ mod tests {
         let email = format!("test_hash_{}@example.com", Uuid::new_v4());
         let user = create_test_user(&db, &email, "password").await;
 
-        let token = create_refresh_token(&db, user.id, None).await.unwrap();
+        let token = create_refresh_token(&db, &test_auth_config(), user.id, None).await.unwrap();
 
         // db should NOT contain the plaintext token
         let found_by_plaintext = RefreshTokens::find()
@@ -223,7 +227,7 @@ mod tests {
         let email = format!("test_userid_{}@example.com", Uuid::new_v4());
         let user = create_test_user(&db, &email, "password").await;
 
-        let token = create_refresh_token(&db, user.id, None).await.unwrap();
+        let token = create_refresh_token(&db, &test_auth_config(), user.id, None).await.unwrap();
         let token_hash = hash_token(&token);
 
         let record = RefreshTokens::find()
@@ -245,7 +249,7 @@ mod tests {
         let user = create_test_user(&db, &email, "password").await;
         let device = "iPhone 15 - Safari".to_string();
 
-        let token = create_refresh_token(&db, user.id, Some(device.clone()))
+        let token = create_refresh_token(&db, &test_auth_config(), user.id, Some(device.clone()))
             .await
             .unwrap();
         let token_hash = hash_token(&token);
@@ -268,7 +272,7 @@ mod tests {
         let email = format!("test_expires_{}@example.com", Uuid::new_v4());
         let user = create_test_user(&db, &email, "password").await;
 
-        let token = create_refresh_token(&db, user.id, None).await.unwrap();
+        let token = create_refresh_token(&db, &test_auth_config(), user.id, None).await.unwrap();
         let token_hash = hash_token(&token);
 
         let record = RefreshTokens::find()
@@ -296,7 +300,7 @@ mod tests {
         let email = format!("test_validate_{}@example.com", Uuid::new_v4());
         let user = create_test_user(&db, &email, "password").await;
 
-        let token = create_refresh_token(&db, user.id, None).await.unwrap();
+        let token = create_refresh_token(&db, &test_auth_config(), user.id, None).await.unwrap();
         let result = validate_refresh_token(&db, &token).await;
 
         assert!(result.is_ok());
@@ -312,7 +316,7 @@ mod tests {
         let email = format!("test_lastused_{}@example.com", Uuid::new_v4());
         let user = create_test_user(&db, &email, "password").await;
 
-        let token = create_refresh_token(&db, user.id, None).await.unwrap();
+        let token = create_refresh_token(&db, &test_auth_config(), user.id, None).await.unwrap();
         let token_hash = hash_token(&token);
 
         // check last_used_at is initially None
@@ -345,7 +349,7 @@ mod tests {
         let user = create_test_user(&db, &email, "password").await;
 
         // manually insert an expired token
-        let token = generate_refresh_token(user.id.to_string());
+        let token = generate_refresh_token(&test_auth_config(), user.id.to_string());
         let token_hash = hash_token(&token);
         let expired_at = Utc::now().naive_utc() - Duration::hours(1);
 
@@ -383,7 +387,7 @@ mod tests {
         let email = format!("test_revoke_{}@example.com", Uuid::new_v4());
         let user = create_test_user(&db, &email, "password").await;
 
-        let token = create_refresh_token(&db, user.id, None).await.unwrap();
+        let token = create_refresh_token(&db, &test_auth_config(), user.id, None).await.unwrap();
         let token_hash = hash_token(&token);
 
         revoke_refresh_token(&db, &token).await.unwrap();
@@ -413,10 +417,10 @@ mod tests {
         let email = format!("test_revoke_other_{}@example.com", Uuid::new_v4());
         let user = create_test_user(&db, &email, "password").await;
 
-        let token1 = create_refresh_token(&db, user.id, Some("Device 1".to_string()))
+        let token1 = create_refresh_token(&db, &test_auth_config(), user.id, Some("Device 1".to_string()))
             .await
             .unwrap();
-        let token2 = create_refresh_token(&db, user.id, Some("Device 2".to_string()))
+        let token2 = create_refresh_token(&db, &test_auth_config(), user.id, Some("Device 2".to_string()))
             .await
             .unwrap();
         let token2_hash = hash_token(&token2);
@@ -442,13 +446,13 @@ mod tests {
         let email = format!("test_revokeall_{}@example.com", Uuid::new_v4());
         let user = create_test_user(&db, &email, "password").await;
 
-        create_refresh_token(&db, user.id, Some("Device 1".to_string()))
+        create_refresh_token(&db, &test_auth_config(), user.id, Some("Device 1".to_string()))
             .await
             .unwrap();
-        create_refresh_token(&db, user.id, Some("Device 2".to_string()))
+        create_refresh_token(&db, &test_auth_config(), user.id, Some("Device 2".to_string()))
             .await
             .unwrap();
-        create_refresh_token(&db, user.id, Some("Device 3".to_string()))
+        create_refresh_token(&db, &test_auth_config(), user.id, Some("Device 3".to_string()))
             .await
             .unwrap();
 
@@ -472,8 +476,8 @@ mod tests {
         let user1 = create_test_user(&db, &email1, "password").await;
         let user2 = create_test_user(&db, &email2, "password").await;
 
-        create_refresh_token(&db, user1.id, None).await.unwrap();
-        create_refresh_token(&db, user2.id, None).await.unwrap();
+        create_refresh_token(&db, &test_auth_config(), user1.id, None).await.unwrap();
+        create_refresh_token(&db, &test_auth_config(), user2.id, None).await.unwrap();
 
         revoke_all_refresh_tokens(&db, user1.id).await.unwrap();
 
@@ -498,7 +502,7 @@ mod tests {
         let user = create_test_user(&db, &email, "password").await;
 
         // insert expired token
-        let token = generate_refresh_token(user.id.to_string());
+        let token = generate_refresh_token(&test_auth_config(), user.id.to_string());
         let token_hash = hash_token(&token);
         let expired_at = Utc::now().naive_utc() - Duration::hours(1);
 
@@ -533,7 +537,7 @@ mod tests {
         let email = format!("test_cleanup_valid_{}@example.com", Uuid::new_v4());
         let user = create_test_user(&db, &email, "password").await;
 
-        let token = create_refresh_token(&db, user.id, None).await.unwrap();
+        let token = create_refresh_token(&db, &test_auth_config(), user.id, None).await.unwrap();
         let token_hash = hash_token(&token);
 
         cleanup_expired_tokens(&db).await.unwrap();
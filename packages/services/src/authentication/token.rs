@@ -1,4 +1,5 @@
 use crate::claims::Claims;
+use crate::config::AuthConfig;
 use base64::{engine::general_purpose, Engine};
 use chrono::{DateTime, Duration, NaiveDateTime, Utc};
 use jsonwebtoken::{
@@ -6,7 +7,7 @@ use jsonwebtoken::{
 };
 use models::users::Model as User;
 use sha2::{Digest, Sha256};
-use std::{env, fmt};
+use std::fmt;
 use uuid::Uuid;
 
 pub struct Token(pub String);
@@ -34,10 +35,10 @@ impl fmt::Display for AuthError {
 }
 
 trait TokenTrait {
-    fn verify(&self, token: String) -> Result<TokenData<Claims>, Error> {
+    fn verify(&self, auth: &AuthConfig, token: String) -> Result<TokenData<Claims>, Error> {
         decode::<Claims>(
             &token,
-            &DecodingKey::from_secret(secret().as_ref()),
+            &DecodingKey::from_secret(auth.token_secret.as_ref()),
             &Validation::default(),
         )
     }
@@ -59,14 +60,14 @@ impl Token {
     }
 
     /// Get the token claims
-    pub fn get_claims(&self) -> Result<Claims, AuthError> {
-        let token_data = self.verify(self.get_token_string().to_string())?;
+    pub fn get_claims(&self, auth: &AuthConfig) -> Result<Claims, AuthError> {
+        let token_data = self.verify(auth, self.get_token_string().to_string())?;
         Ok(token_data.claims)
     }
 
     /// Get user ID from token
-    pub fn get_user_id(&self) -> Result<Uuid, AuthError> {
-        let claims = self.get_claims()?;
+    pub fn get_user_id(&self, auth: &AuthConfig) -> Result<Uuid, AuthError> {
+        let claims = self.get_claims(auth)?;
         let user_id = claims.sub.parse::<Uuid>().map_err(|_| AuthError {
             message: "Invalid user ID in token".to_string(),
         })?;
@@ -74,8 +75,8 @@ impl Token {
     }
 
     /// Get token expiration timestamp
-    pub fn get_expiration(&self) -> Result<NaiveDateTime, AuthError> {
-        let claims = self.get_claims()?;
+    pub fn get_expiration(&self, auth: &AuthConfig) -> Result<NaiveDateTime, AuthError> {
+        let claims = self.get_claims(auth)?;
         let expiration = DateTime::from_timestamp(claims.exp, 0)
             .ok_or(AuthError {
                 message: "Invalid expiration timestamp".to_string(),
@@ -85,8 +86,8 @@ impl Token {
     }
 
     /// Get token issued-at timestamp
-    pub fn get_issued_at(&self) -> Result<NaiveDateTime, AuthError> {
-        let claims = self.get_claims()?;
+    pub fn get_issued_at(&self, auth: &AuthConfig) -> Result<NaiveDateTime, AuthError> {
+        let claims = self.get_claims(auth)?;
         let issued_at = DateTime::from_timestamp(claims.iat, 0)
             .ok_or(AuthError {
                 message: "Invalid issued-at timestamp".to_string(),
@@ -96,33 +97,32 @@ impl Token {
     }
 
     /// Get token JTI (JWT ID)
-    pub fn get_jti(&self) -> Result<String, AuthError> {
-        let claims = self.get_claims()?;
+    pub fn get_jti(&self, auth: &AuthConfig) -> Result<String, AuthError> {
+        let claims = self.get_claims(auth)?;
         Ok(claims.jti)
     }
 
     /// Check if token is expired
-    pub fn is_expired(&self) -> Result<bool, AuthError> {
-        let expiration = self.get_expiration()?;
+    pub fn is_expired(&self, auth: &AuthConfig) -> Result<bool, AuthError> {
+        let expiration = self.get_expiration(auth)?;
         Ok(Utc::now().naive_utc() > expiration)
     }
 
     /// Check if token is valid (not expired and properly signed)
-    pub fn is_valid(&self) -> bool {
-        match self.get_claims() {
-            Ok(_) => !self.is_expired().unwrap_or(true),
+    pub fn is_valid(&self, auth: &AuthConfig) -> bool {
+        match self.get_claims(auth) {
+            Ok(_) => !self.is_expired(auth).unwrap_or(true),
             Err(_) => false,
         }
     }
 }
 
-pub fn generate_token(user: &User) -> String {
+pub fn generate_token(auth: &AuthConfig, user: &User) -> String {
     let expiration = Utc::now()
-        .checked_add_signed(Duration::seconds(expiration()))
-        .unwrap();
-    let host_name = env::var("HOST_NAME").unwrap();
+        .checked_add_signed(Duration::seconds(auth.token_expiration_seconds))
+        .expect("token_expiration_seconds is bounded by config validation");
     let claims = Claims {
-        iss: host_name,
+        iss: auth.host_name.clone(),
         sub: user.id.clone().to_string(),
         exp: expiration.timestamp(),
         iat: Utc::now().timestamp(),
@@ -132,18 +132,17 @@ pub fn generate_token(user: &User) -> String {
     encode(
         &Header::default(),
         &claims,
-        &EncodingKey::from_secret(secret().as_ref()),
+        &EncodingKey::from_secret(auth.token_secret.as_ref()),
     )
-    .unwrap()
+    .expect("HS256 encoding of plain claims can't fail")
 }
 
-pub fn generate_refresh_token(user_id: String) -> String {
+pub fn generate_refresh_token(auth: &AuthConfig, user_id: String) -> String {
     let expiration = Utc::now()
-        .checked_add_signed(Duration::days(refresh_token_expiration()))
-        .unwrap();
-    let host_name = env::var("HOST_NAME").unwrap();
+        .checked_add_signed(Duration::days(auth.refresh_token_expiration_days))
+        .expect("refresh_token_expiration_days is bounded by config validation");
     let claims = Claims {
-        iss: host_name,
+        iss: auth.host_name.clone(),
         sub: user_id,
         exp: expiration.timestamp(),
         iat: Utc::now().timestamp(),
@@ -153,9 +152,9 @@ pub fn generate_refresh_token(user_id: String) -> String {
     encode(
         &Header::default(),
         &claims,
-        &EncodingKey::from_secret(secret().as_ref()),
+        &EncodingKey::from_secret(auth.token_secret.as_ref()),
     )
-    .unwrap()
+    .expect("HS256 encoding of plain claims can't fail")
 }
 
 /// Hash a token string using SHA256 for secure storage
@@ -166,24 +165,6 @@ pub fn hash_token(token: &str) -> String {
     general_purpose::STANDARD_NO_PAD.encode(result)
 }
 
-fn secret() -> String {
-    env::var("TOKEN_SECRET").unwrap()
-}
-
-fn expiration() -> i64 {
-    env::var("TOKEN_EXPIRATION_SECONDS")
-        .unwrap()
-        .parse::<i64>()
-        .unwrap()
-}
-
-fn refresh_token_expiration() -> i64 {
-    env::var("REFRESH_TOKEN_EXPIRATION_DAYS")
-        .unwrap()
-        .parse::<i64>()
-        .unwrap()
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
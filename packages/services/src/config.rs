@@ -0,0 +1,1051 @@
+use ipnet::IpNet;
+use lettre::message::Mailbox;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Longest access token lifetime accepted: 30 days.
+pub const MAX_TOKEN_EXPIRATION_SECONDS: i64 = 30 * 24 * 60 * 60;
+/// Longest refresh token lifetime accepted: 10 years.
+pub const MAX_REFRESH_TOKEN_EXPIRATION_DAYS: i64 = 3650;
+
+/// Default config file looked up in the working directory when `CONFIG_FILE` is unset.
+const DEFAULT_CONFIG_FILE: &str = "soliloquio.toml";
+
+/// Every recognised setting. Environment variables use these names verbatim;
+/// the TOML file uses them lowercased, either at top level or inside any table.
+const KNOWN_KEYS: &[&str] = &[
+    "DATABASE_URL",
+    "TOKEN_SECRET",
+    "TOKEN_EXPIRATION_SECONDS",
+    "REFRESH_TOKEN_EXPIRATION_DAYS",
+    "HOST_NAME",
+    "BIND_ADDR",
+    "SECURE_COOKIES",
+    "SINGLE_USER_MODE",
+    "LOG_FORMAT",
+    "UPLOAD_DIR",
+    "ALLOWED_ORIGINS",
+    "PUBLIC_CORS_ORIGINS",
+    "TRUSTED_PROXIES",
+    "SMTP_HOST",
+    "SMTP_PORT",
+    "SMTP_USER",
+    "SMTP_PASSWORD",
+    "SMTP_FROM",
+    "APP_BASE_URL",
+    "PUBLIC_MAX_COMPLEXITY",
+    "PUBLIC_MAX_DEPTH",
+    "PUBLIC_COMPLEXITY_BUDGET",
+    "PUBLIC_COMPLEXITY_WINDOW_SECS",
+    "HIGHLIGHT_CODE",
+    "HIGHLIGHT_THEME",
+    "HIGHLIGHT_LINE_NUMBERS",
+    "HEADING_ANCHORS",
+    "RENDER_MATH",
+    "MARKDOWN_CACHE_MB",
+    "PERSIST_RENDERED_MARKDOWN",
+    "SITE_URL",
+    "SITE_TITLE",
+    "SITE_POST_PATH",
+    "API_BASE_URL",
+    "TWITTER_SITE",
+    "SPAM_THRESHOLD",
+    "SPAM_MIN_SUBMIT_SECS",
+    "POW_DIFFICULTY",
+    "POW_MAX_DIFFICULTY",
+    "POW_CHALLENGE_TTL_SECS",
+    "NEWSLETTER_CONFIRM_TTL_SECS",
+    "NEWSLETTER_BATCH_SIZE",
+    "NEWSLETTER_MAX_ATTEMPTS",
+    "NEWSLETTER_POLL_SECS",
+    "WEBHOOK_BATCH_SIZE",
+    "WEBHOOK_MAX_ATTEMPTS",
+    "WEBHOOK_POLL_SECS",
+    "WEBHOOK_TIMEOUT_SECS",
+    "PING_BATCH_SIZE",
+    "PING_MAX_ATTEMPTS",
+    "PING_POLL_SECS",
+    "PING_TIMEOUT_SECS",
+    "WEBSUB_HUBS",
+    "PING_URLS",
+    "FEED_URLS",
+    "WEBMENTION_BATCH_SIZE",
+    "WEBMENTION_MAX_ATTEMPTS",
+    "WEBMENTION_POLL_SECS",
+    "WEBMENTION_TIMEOUT_SECS",
+    "ACTIVITYPUB_BATCH_SIZE",
+    "ACTIVITYPUB_MAX_ATTEMPTS",
+    "ACTIVITYPUB_POLL_SECS",
+    "ACTIVITYPUB_TIMEOUT_SECS",
+];
+
+/// Themes bundled with the highlighter; `HIGHLIGHT_THEME` must be one of these.
+pub const HIGHLIGHT_THEMES: &[&str] = &[
+    "InspiredGitHub",
+    "Solarized (dark)",
+    "Solarized (light)",
+    "base16-eighties.dark",
+    "base16-mocha.dark",
+    "base16-ocean.dark",
+    "base16-ocean.light",
+];
+
+#[derive(Debug)]
+pub struct ConfigError {
+    pub problems: Vec<String>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "invalid configuration:")?;
+        for problem in &self.problems {
+            writeln!(f, "  - {problem}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[derive(Clone, Debug)]
+pub struct AuthConfig {
+    pub token_secret: String,
+    pub token_expiration_seconds: i64,
+    pub refresh_token_expiration_days: i64,
+    pub host_name: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    Pretty,
+    Json,
+}
+
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    pub bind_addr: String,
+    pub secure_cookies: bool,
+    pub single_user_mode: bool,
+    pub log_format: LogFormat,
+    pub upload_dir: PathBuf,
+    pub allowed_origins: Vec<String>,
+    /// `None` means any origin (`*`).
+    pub public_cors_origins: Option<Vec<String>>,
+    /// Reverse proxies whose forwarding headers name the client
+    pub trusted_proxies: Vec<IpNet>,
+}
+
+impl ServerConfig {
+    /// The client behind a request from `peer`. A trusted proxy's
+    /// `X-Forwarded-For`, or `Forwarded` without one, is read from the right,
+    /// and the first address not itself a trusted proxy is the client's.
+    pub fn client_ip(&self, peer: IpAddr, forwarded_for: Option<&str>, forwarded: Option<&str>) -> IpAddr {
+        let trusted = |ip: &IpAddr| self.trusted_proxies.iter().any(|net| net.contains(ip));
+        if !trusted(&peer) {
+            return peer;
+        }
+        let hops: Vec<&str> = match (forwarded_for, forwarded) {
+            (Some(list), _) => list.split(',').collect(),
+            (None, Some(list)) => list
+                .split(',')
+                .filter_map(|element| {
+                    element.split(';').find_map(|pair| {
+                        let (name, value) = pair.trim().split_once('=')?;
+                        name.eq_ignore_ascii_case("for").then_some(value)
+                    })
+                })
+                .collect(),
+            (None, None) => return peer,
+        };
+        let mut client = peer;
+        for hop in hops.iter().rev() {
+            match parse_hop(hop) {
+                Some(ip) => {
+                    client = ip;
+                    if !trusted(&ip) {
+                        break;
+                    }
+                }
+                // A hop we can't read ends what we can vouch for
+                None => break,
+            }
+        }
+        client
+    }
+}
+
+/// The address in one forwarding hop: `1.2.3.4`, `1.2.3.4:80`,
+/// `"[2001:db8::1]:80"` or a bare IPv6 address.
+fn parse_hop(hop: &str) -> Option<IpAddr> {
+    let hop = hop.trim().trim_matches('"');
+    if let Ok(ip) = hop.parse() {
+        return Some(ip);
+    }
+    if let Some(rest) = hop.strip_prefix('[') {
+        return rest.split_once(']')?.0.parse().ok();
+    }
+    hop.parse::<SocketAddr>().ok().map(|addr| addr.ip())
+}
+
+#[derive(Clone, Debug)]
+pub struct EmailConfig {
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub smtp_user: Option<String>,
+    pub smtp_password: Option<String>,
+    pub smtp_from: String,
+    pub app_base_url: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct PublicApiConfig {
+    pub max_complexity: usize,
+    pub max_depth: usize,
+    pub complexity_budget: usize,
+    pub complexity_window_secs: u64,
+}
+
+impl Default for PublicApiConfig {
+    fn default() -> Self {
+        Self {
+            max_complexity: 200,
+            max_depth: 5,
+            complexity_budget: 2000,
+            complexity_window_secs: 60,
+        }
+    }
+}
+
+/// Markdown rendering. Highlighting emits CSS classes only, so the theme just
+/// selects the stylesheet served at `/highlight.css`.
+#[derive(Clone, Debug)]
+pub struct MarkdownConfig {
+    pub highlight_code: bool,
+    pub highlight_theme: String,
+    pub line_numbers: bool,
+    pub heading_anchors: bool,
+    pub render_math: bool,
+    /// Memory for rendered posts, in MiB; 0 turns the cache off
+    pub cache_mb: u64,
+    /// Keep rendered HTML in `posts.rendered_markdown` so restarts start warm
+    pub persist_rendered: bool,
+}
+
+impl Default for MarkdownConfig {
+    fn default() -> Self {
+        Self {
+            highlight_code: true,
+            highlight_theme: "InspiredGitHub".to_string(),
+            line_numbers: false,
+            heading_anchors: false,
+            render_math: true,
+            cache_mb: 64,
+            persist_rendered: false,
+        }
+    }
+}
+
+/// The public blog that frontends serve, for canonical URLs and share
+/// metadata.
+#[derive(Clone, Debug)]
+pub struct SiteConfig {
+    /// Base URL of the blog, without a trailing slash; `APP_BASE_URL` unless
+    /// the blog is served elsewhere
+    pub url: String,
+    pub title: String,
+    /// Path of a post on the blog, with `{slug}` (or `{id}`) filled in
+    pub post_path: String,
+    /// Base URL of this server, without a trailing slash, for asset URLs
+    pub api_url: String,
+    /// `@handle` for `twitter:site`
+    pub twitter_site: Option<String>,
+}
+
+impl Default for SiteConfig {
+    fn default() -> Self {
+        Self {
+            url: "http://localhost:3000".to_string(),
+            title: "Soliloquio".to_string(),
+            post_path: "/posts/{slug}".to_string(),
+            api_url: "http://localhost:8000".to_string(),
+            twitter_site: None,
+        }
+    }
+}
+
+impl SiteConfig {
+    /// Absolute URL of a post: `{slug}` falls back to the id for posts
+    /// without one.
+    pub fn post_url(&self, id: &str, slug: Option<&str>) -> String {
+        let slug = slug.filter(|s| !s.is_empty()).unwrap_or(id);
+        format!("{}{}", self.url, self.post_path.replace("{slug}", slug).replace("{id}", id))
+    }
+
+    /// The slug, or id, of the post `url` is the page of: the inverse of
+    /// `post_url`. The query, fragment and a trailing slash don't matter.
+    pub fn post_key(&self, url: &str) -> Option<String> {
+        let url = url.split(['?', '#']).next().unwrap_or_default();
+        let path = url.strip_prefix(self.url.as_str())?;
+        let placeholder = ["{slug}", "{id}"].into_iter().find(|p| self.post_path.contains(p))?;
+        let (prefix, suffix) = self.post_path.split_once(placeholder)?;
+        let key = path.strip_prefix(prefix)?.trim_end_matches('/');
+        let key = key.strip_suffix(suffix.trim_end_matches('/'))?;
+        (!key.is_empty() && !key.contains('/')).then(|| key.to_string())
+    }
+
+    /// `path` made absolute against this server when it is root-relative,
+    /// e.g. an asset URL.
+    pub fn absolute_api_url(&self, path: &str) -> String {
+        match path.strip_prefix('/') {
+            Some(rest) if !rest.starts_with('/') => format!("{}/{rest}", self.api_url),
+            _ => path.to_string(),
+        }
+    }
+}
+
+/// When the spam filter files a comment as spam.
+#[derive(Clone, Debug)]
+pub struct SpamConfig {
+    /// Score, from 0 to 1, from which a submission is filed as spam
+    pub threshold: f64,
+    /// Submissions sent sooner than this after their form was shown are
+    /// suspect
+    pub min_submit_time: Duration,
+}
+
+impl Default for SpamConfig {
+    fn default() -> Self {
+        Self { threshold: 0.9, min_submit_time: Duration::from_secs(3) }
+    }
+}
+
+/// Proof-of-work challenges that public mutations must solve.
+#[derive(Clone, Debug)]
+pub struct PowConfig {
+    /// Key that signs challenges; `TOKEN_SECRET`
+    pub secret: String,
+    /// Leading zero bits a solution's hash needs when the caller is idle
+    pub difficulty: u32,
+    /// Leading zero bits needed once the caller's complexity budget is spent
+    pub max_difficulty: u32,
+    /// How long a challenge can be solved and used
+    pub ttl: Duration,
+}
+
+impl Default for PowConfig {
+    fn default() -> Self {
+        Self {
+            secret: String::new(),
+            difficulty: 16,
+            max_difficulty: 22,
+            ttl: Duration::from_secs(300),
+        }
+    }
+}
+
+/// Email subscriptions and the queue that sends new posts to subscribers.
+#[derive(Clone, Debug)]
+pub struct NewsletterConfig {
+    /// Key that signs unsubscribe links; `TOKEN_SECRET`
+    pub secret: String,
+    /// How long a subscription can be confirmed
+    pub confirm_ttl: Duration,
+    /// Emails sent per pass over the queue
+    pub batch_size: u64,
+    /// Tries before a delivery is marked failed
+    pub max_attempts: i32,
+    /// Time between passes over the queue
+    pub poll_interval: Duration,
+}
+
+impl Default for NewsletterConfig {
+    fn default() -> Self {
+        Self {
+            secret: String::new(),
+            confirm_ttl: Duration::from_secs(24 * 60 * 60),
+            batch_size: 50,
+            max_attempts: 5,
+            poll_interval: Duration::from_secs(30),
+        }
+    }
+}
+
+/// How a delivery queue is worked: webhook deliveries, feed pings,
+/// Webmentions and ActivityPub deliveries each have their own, read from
+/// `{PREFIX}_BATCH_SIZE`, `_MAX_ATTEMPTS`, `_POLL_SECS` and `_TIMEOUT_SECS`.
+#[derive(Clone, Debug)]
+pub struct QueueConfig {
+    /// Deliveries made per pass over the queue
+    pub batch_size: u64,
+    /// Tries before a delivery is marked failed
+    pub max_attempts: i32,
+    /// Time between passes over the queue
+    pub poll_interval: Duration,
+    /// How long an endpoint has to answer
+    pub timeout: Duration,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 50,
+            max_attempts: 8,
+            poll_interval: Duration::from_secs(10),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Services told when a post is published, so feed readers needn't wait
+/// for their next poll.
+#[derive(Clone, Debug, Default)]
+pub struct FeedPingConfig {
+    /// WebSub hubs sent `hub.mode=publish` for each feed
+    pub hubs: Vec<String>,
+    /// XML-RPC endpoints sent `weblogUpdates.extendedPing` for each feed
+    pub ping_urls: Vec<String>,
+    /// The blog's feeds
+    pub feed_urls: Vec<String>,
+    /// How queued pings are sent and retried
+    pub queue: QueueConfig,
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub database_url: String,
+    pub auth: AuthConfig,
+    pub server: ServerConfig,
+    pub email: EmailConfig,
+    pub public_api: PublicApiConfig,
+    pub markdown: MarkdownConfig,
+    pub site: SiteConfig,
+    pub spam: SpamConfig,
+    pub pow: PowConfig,
+    pub newsletter: NewsletterConfig,
+    pub webhooks: QueueConfig,
+    pub feed_pings: FeedPingConfig,
+    /// Sending Webmentions and verifying received ones
+    pub webmentions: QueueConfig,
+    /// Delivering activities to followers, and fetching actors
+    pub activitypub: QueueConfig,
+}
+
+impl Config {
+    /// Load from the optional TOML file (`CONFIG_FILE`, else `./soliloquio.toml`
+    /// if present) overlaid with environment variables, then validate.
+    pub fn load() -> Result<Self, ConfigError> {
+        let mut values = match std::env::var("CONFIG_FILE") {
+            Ok(path) if !path.is_empty() => read_file(Path::new(&path))?,
+            _ if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                read_file(Path::new(DEFAULT_CONFIG_FILE))?
+            }
+            _ => HashMap::new(),
+        };
+        for key in KNOWN_KEYS {
+            if let Ok(v) = std::env::var(key) {
+                values.insert(key.to_string(), v);
+            }
+        }
+        Self::from_values(&values)
+    }
+
+    /// Build from raw `KEY -> value` pairs, collecting every problem instead of
+    /// stopping at the first one.
+    pub fn from_values(values: &HashMap<String, String>) -> Result<Self, ConfigError> {
+        let mut r = Reader { values, problems: Vec::new() };
+
+        let database_url = r.required("DATABASE_URL");
+
+        let auth = AuthConfig {
+            token_secret: r.required("TOKEN_SECRET"),
+            token_expiration_seconds: r.bounded("TOKEN_EXPIRATION_SECONDS", 3600, MAX_TOKEN_EXPIRATION_SECONDS),
+            refresh_token_expiration_days: r.bounded("REFRESH_TOKEN_EXPIRATION_DAYS", 7, MAX_REFRESH_TOKEN_EXPIRATION_DAYS),
+            host_name: r.string("HOST_NAME", "localhost"),
+        };
+
+        let bind_addr = r.string("BIND_ADDR", "127.0.0.1:8000");
+        if bind_addr.rsplit_once(':').is_none_or(|(_, port)| port.parse::<u16>().is_err()) {
+            r.problem("BIND_ADDR", format!("expected host:port, got {bind_addr:?}"));
+        }
+        let log_format = match r.string("LOG_FORMAT", "pretty").to_ascii_lowercase().as_str() {
+            "pretty" | "" => LogFormat::Pretty,
+            "json" => LogFormat::Json,
+            other => {
+                r.problem("LOG_FORMAT", format!("expected \"pretty\" or \"json\", got {other:?}"));
+                LogFormat::Pretty
+            }
+        };
+        let public_cors = r.string("PUBLIC_CORS_ORIGINS", "*");
+        let server = ServerConfig {
+            bind_addr,
+            secure_cookies: r.boolean("SECURE_COOKIES", false),
+            single_user_mode: r.boolean("SINGLE_USER_MODE", false),
+            log_format,
+            upload_dir: PathBuf::from(r.string("UPLOAD_DIR", "./uploads")),
+            allowed_origins: split_list(&r.string("ALLOWED_ORIGINS", "http://localhost:8001")),
+            public_cors_origins: if public_cors.trim() == "*" {
+                None
+            } else {
+                Some(split_list(&public_cors))
+            },
+            trusted_proxies: split_list(&r.string("TRUSTED_PROXIES", ""))
+                .into_iter()
+                .filter_map(|entry| {
+                    let net = entry.parse::<IpNet>().ok().or_else(|| entry.parse::<IpAddr>().ok().map(IpNet::from));
+                    if net.is_none() {
+                        r.problem("TRUSTED_PROXIES", format!("expected an IP address or CIDR range, got {entry:?}"));
+                    }
+                    net
+                })
+                .collect(),
+        };
+
+        let smtp_from = r.string("SMTP_FROM", "noreply@soliloquio.local");
+        if smtp_from.parse::<Mailbox>().is_err() {
+            r.problem("SMTP_FROM", format!("not a valid email address: {smtp_from:?}"));
+        }
+        let app_base_url = r.string("APP_BASE_URL", "http://localhost:3000");
+        if !app_base_url.starts_with("http://") && !app_base_url.starts_with("https://") {
+            r.problem("APP_BASE_URL", format!("must start with http:// or https://, got {app_base_url:?}"));
+        }
+        let email = EmailConfig {
+            smtp_host: r.string("SMTP_HOST", "localhost"),
+            smtp_port: r.parsed("SMTP_PORT", 1025u16),
+            smtp_user: r.optional("SMTP_USER"),
+            smtp_password: r.optional("SMTP_PASSWORD"),
+            smtp_from,
+            app_base_url: app_base_url.trim_end_matches('/').to_string(),
+        };
+
+        let defaults = PublicApiConfig::default();
+        let public_api = PublicApiConfig {
+            max_complexity: r.positive("PUBLIC_MAX_COMPLEXITY", defaults.max_complexity),
+            max_depth: r.positive("PUBLIC_MAX_DEPTH", defaults.max_depth),
+            complexity_budget: r.positive("PUBLIC_COMPLEXITY_BUDGET", defaults.complexity_budget),
+            complexity_window_secs: r.positive(
+                "PUBLIC_COMPLEXITY_WINDOW_SECS",
+                defaults.complexity_window_secs,
+            ),
+        };
+
+        let defaults = MarkdownConfig::default();
+        let highlight_theme = r.string("HIGHLIGHT_THEME", &defaults.highlight_theme);
+        if !HIGHLIGHT_THEMES.contains(&highlight_theme.as_str()) {
+            r.problem(
+                "HIGHLIGHT_THEME",
+                format!("unknown theme {highlight_theme:?}, expected one of {HIGHLIGHT_THEMES:?}"),
+            );
+        }
+        let markdown = MarkdownConfig {
+            highlight_code: r.boolean("HIGHLIGHT_CODE", defaults.highlight_code),
+            highlight_theme,
+            line_numbers: r.boolean("HIGHLIGHT_LINE_NUMBERS", defaults.line_numbers),
+            heading_anchors: r.boolean("HEADING_ANCHORS", defaults.heading_anchors),
+            render_math: r.boolean("RENDER_MATH", defaults.render_math),
+            cache_mb: r.parsed("MARKDOWN_CACHE_MB", defaults.cache_mb),
+            persist_rendered: r.boolean("PERSIST_RENDERED_MARKDOWN", defaults.persist_rendered),
+        };
+
+        let defaults = SiteConfig::default();
+        let mut base_url = |key: &str, default: &str| {
+            let url = r.string(key, default);
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                r.problem(key, format!("must start with http:// or https://, got {url:?}"));
+            }
+            url.trim_end_matches('/').to_string()
+        };
+        let site_url = base_url("SITE_URL", &email.app_base_url);
+        let api_url = base_url("API_BASE_URL", &defaults.api_url);
+        let post_path = r.string("SITE_POST_PATH", &defaults.post_path);
+        if !post_path.starts_with('/') || !(post_path.contains("{slug}") || post_path.contains("{id}")) {
+            r.problem("SITE_POST_PATH", format!("must start with / and contain {{slug}} or {{id}}, got {post_path:?}"));
+        }
+        let site = SiteConfig {
+            url: site_url,
+            title: r.string("SITE_TITLE", &defaults.title),
+            post_path,
+            api_url,
+            twitter_site: r.optional("TWITTER_SITE"),
+        };
+
+        let defaults = SpamConfig::default();
+        let threshold = r.parsed("SPAM_THRESHOLD", defaults.threshold);
+        if !(threshold > 0.0 && threshold <= 1.0) {
+            r.problem("SPAM_THRESHOLD", format!("must be above 0 and at most 1, got {threshold}"));
+        }
+        let spam = SpamConfig {
+            threshold,
+            min_submit_time: Duration::from_secs(r.parsed("SPAM_MIN_SUBMIT_SECS", defaults.min_submit_time.as_secs())),
+        };
+
+        let defaults = PowConfig::default();
+        let difficulty = r.parsed("POW_DIFFICULTY", defaults.difficulty);
+        let max_difficulty = r.parsed("POW_MAX_DIFFICULTY", defaults.max_difficulty.max(difficulty));
+        if max_difficulty > 32 {
+            r.problem("POW_MAX_DIFFICULTY", format!("must be 32 or less, got {max_difficulty}"));
+        } else if max_difficulty < difficulty {
+            r.problem("POW_MAX_DIFFICULTY", format!("must be at least POW_DIFFICULTY ({difficulty}), got {max_difficulty}"));
+        }
+        let pow = PowConfig {
+            secret: auth.token_secret.clone(),
+            difficulty,
+            max_difficulty,
+            ttl: Duration::from_secs(r.positive("POW_CHALLENGE_TTL_SECS", defaults.ttl.as_secs())),
+        };
+
+        let defaults = NewsletterConfig::default();
+        let newsletter = NewsletterConfig {
+            secret: auth.token_secret.clone(),
+            confirm_ttl: Duration::from_secs(r.positive("NEWSLETTER_CONFIRM_TTL_SECS", defaults.confirm_ttl.as_secs())),
+            batch_size: r.positive("NEWSLETTER_BATCH_SIZE", defaults.batch_size),
+            max_attempts: r.positive("NEWSLETTER_MAX_ATTEMPTS", defaults.max_attempts),
+            poll_interval: Duration::from_secs(r.positive("NEWSLETTER_POLL_SECS", defaults.poll_interval.as_secs())),
+        };
+
+        let webhooks = r.queue("WEBHOOK");
+        let ping_queue = r.queue("PING");
+        let webmentions = r.queue("WEBMENTION");
+        let activitypub = r.queue("ACTIVITYPUB");
+
+        let mut url_list = |key: &str| {
+            let urls = split_list(&r.string(key, ""));
+            for url in &urls {
+                if !url.starts_with("http://") && !url.starts_with("https://") {
+                    r.problem(key, format!("must be http:// or https:// URLs, got {url:?}"));
+                }
+            }
+            urls
+        };
+        let mut feed_pings = FeedPingConfig {
+            hubs: url_list("WEBSUB_HUBS"),
+            ping_urls: url_list("PING_URLS"),
+            feed_urls: url_list("FEED_URLS"),
+            queue: ping_queue,
+        };
+        if feed_pings.feed_urls.is_empty() {
+            feed_pings.feed_urls = vec![format!("{}/feed.xml", site.url), format!("{}/feed.json", site.url)];
+        }
+
+        if !r.problems.is_empty() {
+            return Err(ConfigError { problems: r.problems });
+        }
+        Ok(Config { database_url, auth, server, email, public_api, markdown, site, spam, pow, newsletter, webhooks, feed_pings, webmentions, activitypub })
+    }
+}
+
+struct Reader<'a> {
+    values: &'a HashMap<String, String>,
+    problems: Vec<String>,
+}
+
+impl Reader<'_> {
+    fn problem(&mut self, key: &str, msg: String) {
+        self.problems.push(format!("{key}: {msg}"));
+    }
+
+    fn raw(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|v| v.trim()).filter(|v| !v.is_empty())
+    }
+
+    fn required(&mut self, key: &str) -> String {
+        match self.raw(key) {
+            Some(v) => v.to_string(),
+            None => {
+                self.problem(key, "is required".to_string());
+                String::new()
+            }
+        }
+    }
+
+    fn optional(&self, key: &str) -> Option<String> {
+        self.raw(key).map(str::to_string)
+    }
+
+    fn string(&self, key: &str, default: &str) -> String {
+        self.raw(key).unwrap_or(default).to_string()
+    }
+
+    fn boolean(&mut self, key: &str, default: bool) -> bool {
+        match self.raw(key).map(str::to_ascii_lowercase).as_deref() {
+            None => default,
+            Some("true" | "1" | "yes") => true,
+            Some("false" | "0" | "no") => false,
+            Some(other) => {
+                self.problem(key, format!("expected true or false, got {other:?}"));
+                default
+            }
+        }
+    }
+
+    fn parsed<T: std::str::FromStr>(&mut self, key: &str, default: T) -> T {
+        let Some(raw) = self.raw(key) else { return default };
+        match raw.parse() {
+            Ok(v) => v,
+            Err(_) => {
+                let raw = raw.to_string();
+                self.problem(key, format!("expected a number, got {raw:?}"));
+                default
+            }
+        }
+    }
+
+    fn positive<T: std::str::FromStr + PartialOrd + Default + Copy>(
+        &mut self,
+        key: &str,
+        default: T,
+    ) -> T {
+        let v = self.parsed(key, default);
+        if v <= T::default() {
+            self.problem(key, "must be greater than zero".to_string());
+        }
+        v
+    }
+
+    /// A queue's settings, each `{prefix}_…` key falling back to the default.
+    fn queue(&mut self, prefix: &str) -> QueueConfig {
+        let defaults = QueueConfig::default();
+        let mut secs = |key: &str, default: Duration| {
+            Duration::from_secs(self.positive(&format!("{prefix}_{key}"), default.as_secs()))
+        };
+        let poll_interval = secs("POLL_SECS", defaults.poll_interval);
+        let timeout = secs("TIMEOUT_SECS", defaults.timeout);
+        QueueConfig {
+            batch_size: self.positive(&format!("{prefix}_BATCH_SIZE"), defaults.batch_size),
+            max_attempts: self.positive(&format!("{prefix}_MAX_ATTEMPTS"), defaults.max_attempts),
+            poll_interval,
+            timeout,
+        }
+    }
+
+    /// Like `positive`, and no more than `max`.
+    fn bounded<T: std::str::FromStr + PartialOrd + Default + Copy + std::fmt::Display>(
+        &mut self,
+        key: &str,
+        default: T,
+        max: T,
+    ) -> T {
+        let v = self.positive(key, default);
+        if v > max {
+            self.problem(key, format!("must be at most {max}"));
+        }
+        v
+    }
+}
+
+fn split_list(raw: &str) -> Vec<String> {
+    raw.split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Flatten a TOML document into `KEY -> value`. Tables are only used for
+/// grouping, so `[auth] token_secret = ".."` and `token_secret = ".."` are equivalent.
+fn read_file(path: &Path) -> Result<HashMap<String, String>, ConfigError> {
+    let fail = |msg: String| ConfigError {
+        problems: vec![format!("{}: {msg}", path.display())],
+    };
+    let text = std::fs::read_to_string(path).map_err(|e| fail(e.to_string()))?;
+    let table: toml::Table = text.parse().map_err(|e: toml::de::Error| fail(e.to_string()))?;
+
+    let mut out = HashMap::new();
+    let mut problems = Vec::new();
+    flatten(&table, &mut out, &mut problems);
+    if !problems.is_empty() {
+        return Err(ConfigError {
+            problems: problems.into_iter().map(|p| format!("{}: {p}", path.display())).collect(),
+        });
+    }
+    Ok(out)
+}
+
+fn flatten(table: &toml::Table, out: &mut HashMap<String, String>, problems: &mut Vec<String>) {
+    for (key, value) in table {
+        let name = key.to_ascii_uppercase();
+        let rendered = match value {
+            toml::Value::Table(inner) => {
+                flatten(inner, out, problems);
+                continue;
+            }
+            toml::Value::String(s) => s.clone(),
+            toml::Value::Integer(i) => i.to_string(),
+            toml::Value::Float(f) => f.to_string(),
+            toml::Value::Boolean(b) => b.to_string(),
+            toml::Value::Array(items) => {
+                let strings: Option<Vec<&str>> = items.iter().map(|i| i.as_str()).collect();
+                match strings {
+                    Some(s) => s.join(","),
+                    None => {
+                        problems.push(format!("{key}: arrays may only contain strings"));
+                        continue;
+                    }
+                }
+            }
+            _ => {
+                problems.push(format!("{key}: unsupported value type"));
+                continue;
+            }
+        };
+        if !KNOWN_KEYS.contains(&name.as_str()) {
+            problems.push(format!("unknown setting {key:?}"));
+            continue;
+        }
+        out.insert(name, rendered);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn minimal() -> HashMap<String, String> {
+        HashMap::from([
+            ("DATABASE_URL".to_string(), "postgres://localhost/test".to_string()),
+            ("TOKEN_SECRET".to_string(), "secret".to_string()),
+        ])
+    }
+
+    #[test]
+    fn minimal_values_use_defaults() {
+        let config = Config::from_values(&minimal()).unwrap();
+        assert_eq!(config.auth.token_expiration_seconds, 3600);
+        assert_eq!(config.auth.refresh_token_expiration_days, 7);
+        assert_eq!(config.auth.host_name, "localhost");
+        assert_eq!(config.server.bind_addr, "127.0.0.1:8000");
+        assert_eq!(config.server.log_format, LogFormat::Pretty);
+        assert!(config.server.public_cors_origins.is_none());
+        assert_eq!(config.email.smtp_port, 1025);
+    }
+
+    #[test]
+    fn missing_required_values_are_all_reported() {
+        let err = Config::from_values(&HashMap::new()).unwrap_err();
+        assert_eq!(err.problems.len(), 2);
+        assert!(err.problems.iter().any(|p| p.starts_with("DATABASE_URL")));
+        assert!(err.problems.iter().any(|p| p.starts_with("TOKEN_SECRET")));
+    }
+
+    #[test]
+    fn rejects_non_numeric_and_non_positive_values() {
+        let mut values = minimal();
+        values.insert("TOKEN_EXPIRATION_SECONDS".into(), "soon".into());
+        values.insert("REFRESH_TOKEN_EXPIRATION_DAYS".into(), "0".into());
+        let err = Config::from_values(&values).unwrap_err();
+        assert!(err.problems.iter().any(|p| p.starts_with("TOKEN_EXPIRATION_SECONDS")));
+        assert!(err.problems.iter().any(|p| p.starts_with("REFRESH_TOKEN_EXPIRATION_DAYS")));
+
+        let mut values = minimal();
+        values.insert("REFRESH_TOKEN_EXPIRATION_DAYS".into(), "100000000000000".into());
+        let err = Config::from_values(&values).unwrap_err();
+        assert_eq!(err.problems.len(), 1);
+        assert!(err.problems[0].contains("at most 3650"), "{}", err.problems[0]);
+    }
+
+    #[test]
+    fn rejects_bad_bind_addr_log_format_and_sender() {
+        let mut values = minimal();
+        values.insert("BIND_ADDR".into(), "localhost".into());
+        values.insert("LOG_FORMAT".into(), "xml".into());
+        values.insert("SMTP_FROM".into(), "not an address".into());
+        let err = Config::from_values(&values).unwrap_err();
+        assert_eq!(err.problems.len(), 3);
+    }
+
+    #[test]
+    fn rejects_unknown_highlight_theme() {
+        let mut values = minimal();
+        values.insert("HIGHLIGHT_THEME".into(), "Monokai Extended".into());
+        let err = Config::from_values(&values).unwrap_err();
+        assert_eq!(err.problems.len(), 1);
+        assert!(err.problems[0].starts_with("HIGHLIGHT_THEME"));
+    }
+
+    #[test]
+    fn site_urls_are_validated_and_joined() {
+        let mut values = minimal();
+        values.insert("SITE_URL".into(), "https://blog.example/".into());
+        values.insert("SITE_POST_PATH".into(), "/{slug}/".into());
+        values.insert("APP_BASE_URL".into(), "https://app.example".into());
+        let site = Config::from_values(&values).unwrap().site;
+        assert_eq!(site.post_url("1234", Some("hello")), "https://blog.example/hello/");
+        assert_eq!(site.post_url("1234", None), "https://blog.example/1234/");
+        assert_eq!(site.post_key("https://blog.example/hello/").as_deref(), Some("hello"));
+        assert_eq!(site.post_key("https://blog.example/hello?utm=x#top").as_deref(), Some("hello"));
+        assert_eq!(site.post_key("https://blog.example/a/b/"), None);
+        assert_eq!(site.post_key("https://other.example/hello/"), None);
+        values.remove("SITE_URL");
+        assert_eq!(Config::from_values(&values).unwrap().site.url, "https://app.example");
+        assert_eq!(site.absolute_api_url("/assets/x.webp"), "http://localhost:8000/assets/x.webp");
+        assert_eq!(site.absolute_api_url("https://cdn.example/x.webp"), "https://cdn.example/x.webp");
+
+        values.insert("SITE_URL".into(), "blog.example".into());
+        values.insert("SITE_POST_PATH".into(), "/posts".into());
+        let err = Config::from_values(&values).unwrap_err();
+        assert_eq!(err.problems.len(), 2);
+    }
+
+    #[test]
+    fn spam_threshold_must_be_a_probability() {
+        let mut values = minimal();
+        values.insert("SPAM_THRESHOLD".into(), "0.75".into());
+        values.insert("SPAM_MIN_SUBMIT_SECS".into(), "5".into());
+        let spam = Config::from_values(&values).unwrap().spam;
+        assert_eq!(spam.threshold, 0.75);
+        assert_eq!(spam.min_submit_time, Duration::from_secs(5));
+
+        values.insert("SPAM_THRESHOLD".into(), "1.5".into());
+        let err = Config::from_values(&values).unwrap_err();
+        assert!(err.problems[0].starts_with("SPAM_THRESHOLD"));
+    }
+
+    #[test]
+    fn pow_difficulty_range_is_checked() {
+        let mut values = minimal();
+        values.insert("POW_DIFFICULTY".into(), "24".into());
+        let pow = Config::from_values(&values).unwrap().pow;
+        assert_eq!((pow.difficulty, pow.max_difficulty), (24, 24));
+        assert_eq!(pow.secret, "secret");
+
+        values.insert("POW_MAX_DIFFICULTY".into(), "20".into());
+        let err = Config::from_values(&values).unwrap_err();
+        assert!(err.problems[0].starts_with("POW_MAX_DIFFICULTY"));
+    }
+
+    #[test]
+    fn newsletter_settings_must_be_positive() {
+        let mut values = minimal();
+        values.insert("NEWSLETTER_BATCH_SIZE".into(), "10".into());
+        let newsletter = Config::from_values(&values).unwrap().newsletter;
+        assert_eq!(newsletter.batch_size, 10);
+        assert_eq!(newsletter.confirm_ttl, Duration::from_secs(86400));
+
+        values.insert("NEWSLETTER_MAX_ATTEMPTS".into(), "0".into());
+        let err = Config::from_values(&values).unwrap_err();
+        assert!(err.problems[0].starts_with("NEWSLETTER_MAX_ATTEMPTS"));
+    }
+
+    #[test]
+    fn queue_settings_must_be_positive() {
+        let mut values = minimal();
+        values.insert("WEBHOOK_TIMEOUT_SECS".into(), "3".into());
+        values.insert("PING_POLL_SECS".into(), "60".into());
+        values.insert("WEBMENTION_BATCH_SIZE".into(), "5".into());
+        values.insert("ACTIVITYPUB_MAX_ATTEMPTS".into(), "12".into());
+        let config = Config::from_values(&values).unwrap();
+        assert_eq!(config.webhooks.timeout, Duration::from_secs(3));
+        assert_eq!(config.webhooks.max_attempts, 8);
+        assert_eq!(config.webhooks.poll_interval, Duration::from_secs(10));
+        // Each queue has its own settings
+        assert_eq!(config.feed_pings.queue.poll_interval, Duration::from_secs(60));
+        assert_eq!(config.feed_pings.queue.timeout, Duration::from_secs(10));
+        assert_eq!(config.webmentions.batch_size, 5);
+        assert_eq!(config.webhooks.batch_size, 50);
+        assert_eq!(config.activitypub.max_attempts, 12);
+
+        values.insert("WEBHOOK_POLL_SECS".into(), "0".into());
+        let err = Config::from_values(&values).unwrap_err();
+        assert!(err.problems[0].starts_with("WEBHOOK_POLL_SECS"));
+    }
+
+    #[test]
+    fn feed_pings_default_to_the_site_feeds() {
+        let mut values = minimal();
+        values.insert("SITE_URL".into(), "https://blog.example".into());
+        values.insert("WEBSUB_HUBS".into(), "https://hub.example/, https://pubsubhubbub.appspot.com".into());
+        let feed_pings = Config::from_values(&values).unwrap().feed_pings;
+        assert_eq!(feed_pings.hubs.len(), 2);
+        assert!(feed_pings.ping_urls.is_empty());
+        assert_eq!(feed_pings.feed_urls, ["https://blog.example/feed.xml", "https://blog.example/feed.json"]);
+
+        values.insert("PING_URLS".into(), "rpc.example".into());
+        let err = Config::from_values(&values).unwrap_err();
+        assert!(err.problems[0].starts_with("PING_URLS"));
+    }
+
+    #[test]
+    fn splits_origin_lists() {
+        let mut values = minimal();
+        values.insert("ALLOWED_ORIGINS".into(), "https://a.example, https://b.example".into());
+        values.insert("PUBLIC_CORS_ORIGINS".into(), "https://c.example".into());
+        let config = Config::from_values(&values).unwrap();
+        assert_eq!(config.server.allowed_origins, vec!["https://a.example", "https://b.example"]);
+        assert_eq!(config.server.public_cors_origins, Some(vec!["https://c.example".to_string()]));
+    }
+
+    #[test]
+    fn trusted_proxies_name_the_client() {
+        let mut values = minimal();
+        values.insert("TRUSTED_PROXIES".into(), "127.0.0.1, 10.0.0.0/8".into());
+        let server = Config::from_values(&values).unwrap().server;
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+
+        // Anyone else's headers are ignored
+        assert_eq!(server.client_ip(ip("203.0.113.9"), Some("198.51.100.1"), None), ip("203.0.113.9"));
+        assert_eq!(server.client_ip(ip("127.0.0.1"), None, None), ip("127.0.0.1"));
+        // Hops the client made up sit left of the ones our proxies added
+        assert_eq!(
+            server.client_ip(ip("127.0.0.1"), Some("198.51.100.1, 203.0.113.9, 10.1.2.3"), None),
+            ip("203.0.113.9")
+        );
+        assert_eq!(
+            server.client_ip(ip("127.0.0.1"), None, Some("for=198.51.100.1, for=\"[2001:db8::1]:443\";proto=https")),
+            ip("2001:db8::1")
+        );
+        assert_eq!(server.client_ip(ip("127.0.0.1"), Some("203.0.113.9:5000"), None), ip("203.0.113.9"));
+        assert_eq!(server.client_ip(ip("127.0.0.1"), Some("unknown"), None), ip("127.0.0.1"));
+
+        values.insert("TRUSTED_PROXIES".into(), "10.0.0.0/33".into());
+        let err = Config::from_values(&values).unwrap_err();
+        assert!(err.problems[0].starts_with("TRUSTED_PROXIES"));
+    }
+
+    #[test]
+    fn toml_tables_are_flattened() {
+        let table: toml::Table = r#"
+            database_url = "postgres://localhost/test"
+            [auth]
+            token_secret = "secret"
+            token_expiration_seconds = 60
+            [server]
+            allowed_origins = ["https://a.example", "https://b.example"]
+            secure_cookies = true
+        "#
+        .parse()
+        .unwrap();
+        let mut values = HashMap::new();
+        let mut problems = Vec::new();
+        flatten(&table, &mut values, &mut problems);
+        assert!(problems.is_empty(), "{problems:?}");
+
+        let config = Config::from_values(&values).unwrap();
+        assert_eq!(config.auth.token_expiration_seconds, 60);
+        assert!(config.server.secure_cookies);
+        assert_eq!(config.server.allowed_origins.len(), 2);
+    }
+
+    #[test]
+    fn toml_files_take_floats() {
+        let path = std::env::temp_dir().join(format!("soliloquio-config-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "database_url = \"postgres://localhost/test\"\ntoken_secret = \"secret\"\n[spam]\nspam_threshold = 0.75\n",
+        )
+        .unwrap();
+        let values = read_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        let config = Config::from_values(&values.unwrap()).unwrap();
+        assert_eq!(config.spam.threshold, 0.75);
+    }
+
+    #[test]
+    fn toml_unknown_keys_are_rejected() {
+        let table: toml::Table = "token_secrt = \"typo\"".parse().unwrap();
+        let mut values = HashMap::new();
+        let mut problems = Vec::new();
+        flatten(&table, &mut values, &mut problems);
+        assert_eq!(problems.len(), 1);
+    }
+}
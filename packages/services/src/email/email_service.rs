@@ -1,7 +1,56 @@
-use lettre::message::header::ContentType;
+use crate::config::EmailConfig;
+use lettre::message::header::{ContentType, Header, HeaderName, HeaderValue};
+use lettre::message::MultiPart;
 use lettre::transport::smtp::authentication::Credentials;
 use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
 
+/// `List-Unsubscribe`, with the URL that unsubscribes in one click.
+#[derive(Clone)]
+struct ListUnsubscribe(String);
+
+impl Header for ListUnsubscribe {
+    fn name() -> HeaderName {
+        HeaderName::new_from_ascii_str("List-Unsubscribe")
+    }
+
+    fn parse(s: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Self(s.trim_start_matches('<').trim_end_matches('>').to_string()))
+    }
+
+    fn display(&self) -> HeaderValue {
+        HeaderValue::new(Self::name(), format!("<{}>", self.0))
+    }
+}
+
+/// `List-Unsubscribe-Post`, telling mail clients a POST to the
+/// `List-Unsubscribe` URL is enough (RFC 8058).
+#[derive(Clone)]
+struct ListUnsubscribePost;
+
+impl Header for ListUnsubscribePost {
+    fn name() -> HeaderName {
+        HeaderName::new_from_ascii_str("List-Unsubscribe-Post")
+    }
+
+    fn parse(_: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Self)
+    }
+
+    fn display(&self) -> HeaderValue {
+        HeaderValue::new(Self::name(), "List-Unsubscribe=One-Click".to_string())
+    }
+}
+
+/// A post emailed to one subscriber.
+pub struct Newsletter<'a> {
+    pub to: &'a str,
+    pub subject: &'a str,
+    pub html: &'a str,
+    pub text: &'a str,
+    /// Unsubscribes with a POST, for the `List-Unsubscribe` header
+    pub unsubscribe_url: &'a str,
+}
+
 #[derive(Clone)]
 pub struct EmailService {
     smtp_host: String,
@@ -13,26 +62,14 @@ pub struct EmailService {
 }
 
 impl EmailService {
-    pub fn from_env() -> Self {
-        let smtp_host = std::env::var("SMTP_HOST").unwrap_or_else(|_| "localhost".to_string());
-        let smtp_port = std::env::var("SMTP_PORT")
-            .ok()
-            .and_then(|p| p.parse().ok())
-            .unwrap_or(1025u16);
-        let smtp_from = std::env::var("SMTP_FROM")
-            .unwrap_or_else(|_| "noreply@soliloquio.local".to_string());
-        let smtp_user = std::env::var("SMTP_USER").ok().filter(|s| !s.is_empty());
-        let smtp_password = std::env::var("SMTP_PASSWORD").ok().filter(|s| !s.is_empty());
-        let base_url =
-            std::env::var("APP_BASE_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
-
+    pub fn new(config: &EmailConfig) -> Self {
         EmailService {
-            smtp_host,
-            smtp_port,
-            smtp_from,
-            smtp_user,
-            smtp_password,
-            base_url,
+            smtp_host: config.smtp_host.clone(),
+            smtp_port: config.smtp_port,
+            smtp_from: config.smtp_from.clone(),
+            smtp_user: config.smtp_user.clone(),
+            smtp_password: config.smtp_password.clone(),
+            base_url: config.app_base_url.clone(),
         }
     }
 
@@ -55,6 +92,50 @@ impl EmailService {
         self.send(to, "Verify your email address", &body).await
     }
 
+    /// Ask a new subscriber to confirm. `link` leads to the blog's
+    /// confirmation page.
+    pub async fn send_subscription_confirmation(&self, to: &str, blog_title: &str, link: &str) -> Result<(), String> {
+        let mut title = String::new();
+        html_escape(&mut title, blog_title);
+        let body = format!(
+            "<p>Click the link below to get new posts from {title} by email.</p>\
+             <p><a href=\"{link}\">{link}</a></p>\
+             <p>If you did not subscribe, ignore this email.</p>"
+        );
+        self.send(to, &format!("Confirm your subscription to {blog_title}"), &body).await
+    }
+
+    pub async fn send_newsletter(&self, newsletter: &Newsletter<'_>) -> Result<(), String> {
+        let email = self.newsletter_message(newsletter)?;
+        self.build_transport()
+            .send(email)
+            .await
+            .map_err(|e| format!("failed to send email: {e}"))?;
+        Ok(())
+    }
+
+    fn newsletter_message(&self, newsletter: &Newsletter<'_>) -> Result<Message, String> {
+        Message::builder()
+            .from(
+                self.smtp_from
+                    .parse()
+                    .map_err(|e| format!("invalid from address: {e}"))?,
+            )
+            .to(newsletter
+                .to
+                .parse()
+                .map_err(|e| format!("invalid to address: {e}"))?)
+            .message_id(None)
+            .subject(newsletter.subject)
+            .header(ListUnsubscribe(newsletter.unsubscribe_url.to_string()))
+            .header(ListUnsubscribePost)
+            .multipart(MultiPart::alternative_plain_html(
+                newsletter.text.to_string(),
+                newsletter.html.to_string(),
+            ))
+            .map_err(|e| format!("failed to build email: {e}"))
+    }
+
     async fn send(&self, to: &str, subject: &str, html_body: &str) -> Result<(), String> {
         let email = Message::builder()
             .from(
@@ -95,3 +176,46 @@ impl EmailService {
         builder.build()
     }
 }
+
+fn html_escape(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn newsletters_carry_one_click_unsubscribe_headers() {
+        let service = EmailService::new(&EmailConfig {
+            smtp_host: "localhost".to_string(),
+            smtp_port: 25,
+            smtp_user: None,
+            smtp_password: None,
+            smtp_from: "blog@example.com".to_string(),
+            app_base_url: "https://example.com".to_string(),
+        });
+        let message = service
+            .newsletter_message(&Newsletter {
+                to: "reader@example.com",
+                subject: "New post",
+                html: "<p>Hello</p>",
+                text: "Hello",
+                unsubscribe_url: "https://api.example.com/newsletter/unsubscribe?subscriber=1&token=t",
+            })
+            .unwrap();
+        let raw = String::from_utf8(message.formatted()).unwrap();
+        assert!(raw.contains("List-Unsubscribe: <https://api.example.com/newsletter/unsubscribe?subscriber=1&token=t>"));
+        assert!(raw.contains("List-Unsubscribe-Post: List-Unsubscribe=One-Click"));
+        assert!(raw.contains("multipart/alternative"));
+        assert!(raw.contains("text/plain") && raw.contains("text/html"));
+    }
+}
@@ -1,9 +1,18 @@
+pub mod activitypub;
 pub mod api_keys;
 pub mod assets;
 pub mod authentication;
+pub mod config;
 pub mod validation;
 pub mod email;
+pub mod newsletter;
+pub mod net;
+pub mod pings;
+pub mod pow;
+pub mod spam;
 pub mod verification_token;
+pub mod webhooks;
+pub mod webmention;
 
 #[cfg(test)]
 pub mod test_helpers;
@@ -0,0 +1,207 @@
+//! Guards for requests made on a stranger's or a user's say-so: webhook
+//! deliveries, Webmention fetches and ActivityPub deliveries. They must not
+//! reach this machine or the networks behind it, however the URL is dressed
+//! up: a literal address, a name that resolves to one, or a redirect to one.
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect::{Attempt, Policy};
+use reqwest::{ClientBuilder, Response, Url};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
+
+/// Whether `ip` is somewhere outgoing requests may not go: loopback, private,
+/// shared (CGNAT), link-local, reserved, multicast, or an IPv6 address that
+/// leads to one of those.
+pub fn is_reserved(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_reserved_v4(ip),
+        IpAddr::V6(ip) => is_reserved_v6(ip),
+    }
+}
+
+fn is_reserved_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    a == 0
+        || a == 10
+        || a == 127
+        || (a == 100 && (64..128).contains(&b))
+        || (a == 169 && b == 254)
+        || (a == 172 && (16..32).contains(&b))
+        || (a == 192 && b == 168)
+        || (a == 192 && b == 0 && c == 0)
+        || (a == 192 && b == 0 && c == 2)
+        || (a == 198 && (18..20).contains(&b))
+        || (a == 198 && b == 51 && c == 100)
+        || (a == 203 && b == 0 && c == 113)
+        || a >= 224
+}
+
+fn is_reserved_v6(ip: Ipv6Addr) -> bool {
+    if let Some(v4) = ip.to_ipv4_mapped() {
+        return is_reserved_v4(v4);
+    }
+    let segments = ip.segments();
+    let first = segments[0];
+    ip.is_loopback()
+        || ip.is_unspecified()
+        // Unique local fc00::/7, link-local fe80::/10, site-local fec0::/10
+        || first & 0xfe00 == 0xfc00
+        || first & 0xffc0 == 0xfe80
+        || first & 0xffc0 == 0xfec0
+        || first & 0xff00 == 0xff00
+        // NAT64 64:ff9b::/96 and 64:ff9b:1::/48 reach IPv4 addresses
+        || (first == 0x64 && segments[1] == 0xff9b)
+        // IPv4-compatible ::/96
+        || segments[..6].iter().all(|s| *s == 0)
+        // Documentation 2001:db8::/32
+        || (first == 0x2001 && segments[1] == 0xdb8)
+}
+
+/// Check a URL before requesting it: http(s), and not a literal reserved
+/// address or `localhost`. Names are checked as they resolve, by
+/// [`PublicResolver`].
+pub fn check_url(url: &Url) -> Result<(), String> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("{url} isn't an http(s) URL"));
+    }
+    let reserved = match url.host() {
+        Some(url::Host::Domain(domain)) => {
+            let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+            domain == "localhost" || domain.ends_with(".localhost")
+        }
+        Some(url::Host::Ipv4(ip)) => is_reserved_v4(ip),
+        Some(url::Host::Ipv6(ip)) => is_reserved_v6(ip),
+        None => true,
+    };
+    if reserved {
+        return Err(format!("{url} isn't a public address"));
+    }
+    Ok(())
+}
+
+/// [`check_url`], and also look the name up now, for early word when a URL
+/// is saved. A name that doesn't resolve yet passes; requests check it again.
+pub async fn check_destination(url: &Url) -> Result<(), String> {
+    check_url(url)?;
+    let Some(url::Host::Domain(domain)) = url.host() else { return Ok(()) };
+    let port = url.port_or_known_default().unwrap_or(443);
+    if let Ok(addrs) = tokio::net::lookup_host((domain, port)).await
+        && addrs.into_iter().any(|addr| is_reserved(addr.ip()))
+    {
+        return Err(format!("{url} isn't a public address"));
+    }
+    Ok(())
+}
+
+/// DNS resolution that drops reserved addresses, so a public-looking name
+/// can't lead a request somewhere private. The check happens on the
+/// addresses actually connected to, leaving no gap between check and use.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let addrs: Vec<_> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|addr| !is_reserved(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{host} has no public address").into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Follow at most `max` redirects, each to a URL that passes [`check_url`].
+pub fn redirect_policy(max: usize) -> Policy {
+    Policy::custom(move |attempt: Attempt| {
+        if attempt.previous().len() > max {
+            return attempt.error(format!("more than {max} redirects"));
+        }
+        match check_url(attempt.url()) {
+            Ok(()) => attempt.follow(),
+            Err(e) => attempt.error(e),
+        }
+    })
+}
+
+/// A client builder that only connects to public addresses. Proxies from
+/// the environment are ignored, since they would resolve names for us.
+pub fn client_builder() -> ClientBuilder {
+    reqwest::Client::builder().dns_resolver(Arc::new(PublicResolver)).no_proxy()
+}
+
+/// Up to `max` bytes of a response's body, without buffering any more of it.
+pub async fn read_capped(mut response: Response, max: usize) -> Result<Vec<u8>, reqwest::Error> {
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        body.extend_from_slice(&chunk[..chunk.len().min(max - body.len())]);
+        if body.len() >= max {
+            break;
+        }
+    }
+    Ok(body)
+}
+
+/// At most the first `max` bytes of `body` as text, for a delivery log.
+pub fn excerpt(body: &[u8], max: usize) -> String {
+    let text = String::from_utf8_lossy(&body[..body.len().min(max)]);
+    // A cut through a character leaves a replacement at the end
+    text.trim_end_matches('\u{fffd}').to_string()
+}
+
+/// The start of a response's body as text, at most `max` bytes of it, for
+/// a delivery log.
+pub async fn body_excerpt(response: Response, max: usize) -> Option<String> {
+    let body = read_capped(response, max).await.ok()?;
+    Some(excerpt(&body, max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserved_addresses_are_refused() {
+        for url in [
+            "http://localhost/",
+            "http://api.LOCALHOST./",
+            "http://127.0.0.1/",
+            "http://0.0.0.0/",
+            "http://10.1.2.3/",
+            "http://100.64.0.1/",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://172.16.0.1/",
+            "http://192.168.1.1/",
+            "http://198.18.0.1/",
+            "http://224.0.0.1/",
+            "http://255.255.255.255/",
+            "http://[::1]/",
+            "http://[::]/",
+            "http://[fd00::1]/",
+            "http://[fe80::1]/",
+            "http://[fec0::1]/",
+            "http://[64:ff9b::7f00:1]/",
+            "http://[::ffff:127.0.0.1]/",
+            "http://[::127.0.0.1]/",
+            "ftp://example.com/",
+        ] {
+            assert!(check_url(&Url::parse(url).unwrap()).is_err(), "{url}");
+        }
+        for url in ["https://ana.example/", "http://93.184.216.34/", "http://[2606:4700::1]/"] {
+            assert!(check_url(&Url::parse(url).unwrap()).is_ok(), "{url}");
+        }
+    }
+
+    #[tokio::test]
+    async fn names_are_checked_as_they_resolve() {
+        let url = Url::parse("http://localhost.example.invalid/").unwrap();
+        // Unresolvable names pass until they are requested
+        assert!(check_destination(&url).await.is_ok());
+        let name: Name = "localhost".parse().unwrap();
+        assert!(PublicResolver.resolve(name).await.is_err());
+    }
+}
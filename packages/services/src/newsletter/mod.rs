@@ -0,0 +1,103 @@
+use crate::authentication::hash_token;
+use crate::verification_token::TokenError;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Utc;
+use hmac::{Hmac, KeyInit, Mac};
+use models::subscriber_tokens::{self, Entity as SubscriberTokens};
+use models::subscribers::Entity as Subscribers;
+use sea_orm::*;
+use sha2::Sha256;
+use std::time::Duration;
+use uuid::Uuid;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// A double opt-in token for `subscriber_id`, to send in the confirmation
+/// email. Only its hash is stored.
+pub async fn create_confirmation_token(
+    db: &DatabaseConnection,
+    subscriber_id: Uuid,
+    ttl: Duration,
+) -> Result<String, TokenError> {
+    let raw_token = Uuid::new_v4().to_string();
+    let expires_at = Utc::now().naive_utc() + chrono::Duration::seconds(ttl.as_secs() as i64);
+
+    let record = subscriber_tokens::ActiveModel {
+        subscriber_id: ActiveValue::set(subscriber_id),
+        token_hash: ActiveValue::set(hash_token(&raw_token)),
+        expires_at: ActiveValue::set(expires_at),
+        ..Default::default()
+    };
+
+    record.insert(db).await?;
+    Ok(raw_token)
+}
+
+/// Use up a confirmation token for one of `user_id`'s subscribers,
+/// returning the subscriber it confirms. A token for another blog's
+/// subscriber is refused and left unused.
+pub async fn redeem_confirmation_token(
+    db: &DatabaseConnection,
+    raw_token: &str,
+    user_id: Uuid,
+) -> Result<Uuid, TokenError> {
+    let record = SubscriberTokens::find()
+        .filter(subscriber_tokens::Column::TokenHash.eq(hash_token(raw_token)))
+        .one(db)
+        .await?
+        .ok_or_else(|| TokenError::new("Invalid or expired token"))?;
+
+    if record.used_at.is_some() {
+        return Err(TokenError::new("Token already used"));
+    }
+    if record.expires_at < Utc::now().naive_utc() {
+        return Err(TokenError::new("Token expired"));
+    }
+    let owned = Subscribers::find_by_id(record.subscriber_id)
+        .one(db)
+        .await?
+        .is_some_and(|s| s.user_id == user_id);
+    if !owned {
+        return Err(TokenError::new("Invalid or expired token"));
+    }
+
+    let mut active = record.clone().into_active_model();
+    active.used_at = ActiveValue::set(Some(Utc::now().naive_utc()));
+    active.update(db).await?;
+
+    Ok(record.subscriber_id)
+}
+
+fn unsubscribe_mac(secret: &str, subscriber_id: Uuid) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(b"unsubscribe\0");
+    mac.update(subscriber_id.as_bytes());
+    mac
+}
+
+/// Token for the unsubscribe links in every email to `subscriber_id`. It is
+/// signed rather than stored, so it never expires and needs no lookup.
+pub fn unsubscribe_token(secret: &str, subscriber_id: Uuid) -> String {
+    URL_SAFE_NO_PAD.encode(unsubscribe_mac(secret, subscriber_id).finalize().into_bytes())
+}
+
+pub fn verify_unsubscribe_token(secret: &str, subscriber_id: Uuid, token: &str) -> bool {
+    URL_SAFE_NO_PAD
+        .decode(token)
+        .is_ok_and(|signature| unsubscribe_mac(secret, subscriber_id).verify_slice(&signature).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsubscribe_tokens_are_bound_to_subscriber_and_secret() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let token = unsubscribe_token("secret", a);
+        assert!(verify_unsubscribe_token("secret", a, &token));
+        assert!(!verify_unsubscribe_token("secret", b, &token));
+        assert!(!verify_unsubscribe_token("other", a, &token));
+        assert!(!verify_unsubscribe_token("secret", a, "not base64!"));
+    }
+}
@@ -0,0 +1,141 @@
+use crate::config::QueueConfig;
+use crate::net;
+use crate::webhooks::{Attempt, Client, MAX_LOGGED_BODY};
+use models::sea_orm_active_enums::PingKind;
+
+/// Most of a response read; an XML-RPC answer is a few hundred bytes.
+const MAX_RESPONSE_BYTES: usize = 64 * 1024;
+
+/// The HTTP client pings go through. Hubs and ping services are the
+/// operator's choice, so unlike webhooks they may be on a private network.
+pub fn client(config: &QueueConfig) -> Client {
+    Client::builder()
+        .timeout(config.timeout)
+        .redirect(reqwest::redirect::Policy::none())
+        .user_agent("Soliloquio-Pings/1.0")
+        .build()
+        .expect("TLS backend is available")
+}
+
+/// The form a WebSub hub takes as notice that `feed_url` has new content.
+pub fn websub_body(feed_url: &str) -> String {
+    form_urlencoded::Serializer::new(String::new())
+        .append_pair("hub.mode", "publish")
+        .append_pair("hub.url", feed_url)
+        .finish()
+}
+
+/// A `weblogUpdates.extendedPing` call: the blog's name and address, the
+/// page that changed and the feed it appears in.
+pub fn extended_ping_body(site_title: &str, site_url: &str, changes_url: &str, feed_url: &str) -> String {
+    let mut body = String::from(
+        "<?xml version=\"1.0\"?>\n<methodCall><methodName>weblogUpdates.extendedPing</methodName><params>",
+    );
+    for param in [site_title, site_url, changes_url, feed_url] {
+        body.push_str("<param><value><string>");
+        xml_escape(&mut body, param);
+        body.push_str("</string></value></param>");
+    }
+    body.push_str("</params></methodCall>\n");
+    body
+}
+
+/// POST a prebuilt ping `body` to `endpoint`. Anything but a 2xx answer is a
+/// failure, as is an XML-RPC answer that reports a fault or sets `flerror`.
+pub async fn send(client: &Client, kind: PingKind, endpoint: &str, body: String) -> Attempt {
+    let content_type = match kind {
+        PingKind::Websub => "application/x-www-form-urlencoded",
+        PingKind::Xmlrpc => "text/xml",
+    };
+    let response = client
+        .post(endpoint)
+        .header("Content-Type", content_type)
+        .body(body)
+        .send()
+        .await;
+    let response = match response {
+        Ok(r) => r,
+        Err(e) => return Attempt { error: Some(e.to_string()), ..Default::default() },
+    };
+
+    let status = response.status();
+    let body = net::read_capped(response, MAX_RESPONSE_BYTES).await.unwrap_or_default();
+    let text = String::from_utf8_lossy(&body);
+    let error = if !status.is_success() {
+        Some(format!("HTTP {status}"))
+    } else if kind == PingKind::Xmlrpc {
+        xmlrpc_error(&text)
+    } else {
+        None
+    };
+    Attempt { status: Some(status.as_u16()), body: Some(net::excerpt(&body, MAX_LOGGED_BODY)), error }
+}
+
+/// Why an XML-RPC ping response counts as a refusal, if it does.
+fn xmlrpc_error(response: &str) -> Option<String> {
+    if response.contains("<fault>") {
+        return Some("XML-RPC fault".to_string());
+    }
+    let compact: String = response.chars().filter(|c| !c.is_whitespace()).collect();
+    let flerror = compact.find("<name>flerror</name>")?;
+    let rest = &compact[flerror..];
+    if rest.starts_with("<name>flerror</name><value><boolean>1</boolean>") {
+        let message = value_after(response, "message").unwrap_or("ping refused");
+        Some(format!("Ping refused: {message}"))
+    } else {
+        None
+    }
+}
+
+/// The string value of the struct member `name` in an XML-RPC response.
+fn value_after<'a>(response: &'a str, name: &str) -> Option<&'a str> {
+    let member = response.find(&format!("<name>{name}</name>"))?;
+    let rest = &response[member..];
+    let start = rest.find("<string>")? + "<string>".len();
+    let end = rest[start..].find("</string>")?;
+    Some(rest[start..start + end].trim())
+}
+
+fn xml_escape(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_ping_bodies() {
+        assert_eq!(
+            websub_body("https://blog.example/feed.xml?a=1"),
+            "hub.mode=publish&hub.url=https%3A%2F%2Fblog.example%2Ffeed.xml%3Fa%3D1"
+        );
+        let body = extended_ping_body("Tom & Jerry", "https://blog.example/", "https://blog.example/p/1", "https://blog.example/feed.xml");
+        assert!(body.contains("<methodName>weblogUpdates.extendedPing</methodName>"));
+        assert!(body.contains("<string>Tom &amp; Jerry</string>"));
+        assert!(body.contains("<string>https://blog.example/feed.xml</string></value></param></params>"));
+    }
+
+    #[test]
+    fn reads_xmlrpc_refusals() {
+        let ok = "<methodResponse><params><param><value><struct>\
+            <member><name>flerror</name><value><boolean>0</boolean></value></member>\
+            <member><name>message</name><value><string>Thanks for the ping.</string></value></member>\
+            </struct></value></param></params></methodResponse>";
+        assert_eq!(xmlrpc_error(ok), None);
+
+        let refused = ok.replace("<boolean>0</boolean>", "<boolean>1</boolean>").replace("Thanks for the ping.", "Slow down");
+        assert_eq!(xmlrpc_error(&refused).as_deref(), Some("Ping refused: Slow down"));
+
+        let fault = "<methodResponse><fault><value><struct></struct></value></fault></methodResponse>";
+        assert_eq!(xmlrpc_error(fault).as_deref(), Some("XML-RPC fault"));
+    }
+}
@@ -0,0 +1,160 @@
+use crate::config::PowConfig;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, KeyInit, Mac};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use uuid::Uuid;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Version tag at the start of every challenge, so the format can change.
+const VERSION: &str = "v1";
+
+/// A signed puzzle: find a `solution` for which
+/// `sha256("{token}:{solution}")` starts with `difficulty` zero bits.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Challenge {
+    /// `v1.{nonce}.{difficulty}.{expires_at}.{signature}`
+    pub token: String,
+    pub difficulty: u32,
+    /// Unix time after which the challenge is refused
+    pub expires_at: i64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PowError {
+    Malformed,
+    BadSignature,
+    Expired,
+    WrongSolution,
+}
+
+impl fmt::Display for PowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PowError::Malformed => "Malformed challenge",
+            PowError::BadSignature => "Challenge was not issued for this API key",
+            PowError::Expired => "Challenge expired",
+            PowError::WrongSolution => "Wrong proof-of-work solution",
+        })
+    }
+}
+
+impl std::error::Error for PowError {}
+
+fn sign(secret: &str, subject: &str, payload: &str) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(b"pow\0");
+    mac.update(subject.as_bytes());
+    mac.update(b"\0");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+/// Difficulty for a caller that has used `pressure` (0 to 1) of its
+/// complexity budget: the base difficulty when idle, rising to the maximum.
+pub fn difficulty_for(config: &PowConfig, pressure: f64) -> u32 {
+    let extra = f64::from(config.max_difficulty.saturating_sub(config.difficulty)) * pressure.clamp(0.0, 1.0);
+    config.difficulty + extra.round() as u32
+}
+
+/// A challenge for `subject`, e.g. an API key, that only it can redeem.
+pub fn issue(config: &PowConfig, subject: &str, difficulty: u32, now: i64) -> Challenge {
+    let expires_at = now + config.ttl.as_secs() as i64;
+    let payload = format!("{VERSION}.{}.{difficulty}.{expires_at}", Uuid::new_v4().simple());
+    let signature = URL_SAFE_NO_PAD.encode(sign(&config.secret, subject, &payload).finalize().into_bytes());
+    Challenge { token: format!("{payload}.{signature}"), difficulty, expires_at }
+}
+
+/// Number of leading zero bits in `hash`.
+pub fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+fn solution_hash(token: &str, solution: &str) -> [u8; 32] {
+    Sha256::digest(format!("{token}:{solution}").as_bytes()).into()
+}
+
+/// Check `solution` against a challenge issued to `subject`. Returns the
+/// challenge's nonce and expiry, for refusing it a second time.
+pub fn verify(config: &PowConfig, subject: &str, token: &str, solution: &str, now: i64) -> Result<(String, i64), PowError> {
+    let (payload, signature) = token.rsplit_once('.').ok_or(PowError::Malformed)?;
+    let parts: Vec<&str> = payload.split('.').collect();
+    let [VERSION, nonce, difficulty, expires_at] = parts[..] else {
+        return Err(PowError::Malformed);
+    };
+    let difficulty: u32 = difficulty.parse().map_err(|_| PowError::Malformed)?;
+    let expires_at: i64 = expires_at.parse().map_err(|_| PowError::Malformed)?;
+    let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| PowError::Malformed)?;
+    sign(&config.secret, subject, payload)
+        .verify_slice(&signature)
+        .map_err(|_| PowError::BadSignature)?;
+    if now > expires_at {
+        return Err(PowError::Expired);
+    }
+    if solution.len() > 64 || leading_zero_bits(&solution_hash(token, solution)) < difficulty {
+        return Err(PowError::WrongSolution);
+    }
+    Ok((nonce.to_string(), expires_at))
+}
+
+/// Find a solution by counting up from zero, as a client would.
+pub fn solve(token: &str, difficulty: u32) -> String {
+    (0u64..)
+        .map(|n| n.to_string())
+        .find(|n| leading_zero_bits(&solution_hash(token, n)) >= difficulty)
+        .expect("a solution exists below u64::MAX")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn config() -> PowConfig {
+        PowConfig { secret: "secret".to_string(), difficulty: 4, max_difficulty: 12, ttl: Duration::from_secs(60) }
+    }
+
+    #[test]
+    fn solved_challenges_verify_for_their_subject_only() {
+        let config = config();
+        let challenge = issue(&config, "slq_a", 8, 1000);
+        assert_eq!(challenge.expires_at, 1060);
+        let solution = solve(&challenge.token, challenge.difficulty);
+        let (nonce, expires_at) = verify(&config, "slq_a", &challenge.token, &solution, 1010).unwrap();
+        assert!(challenge.token.contains(&nonce));
+        assert_eq!(expires_at, 1060);
+
+        assert_eq!(verify(&config, "slq_b", &challenge.token, &solution, 1010), Err(PowError::BadSignature));
+        assert_eq!(verify(&config, "slq_a", &challenge.token, &solution, 1061), Err(PowError::Expired));
+        let easier = challenge.token.replacen(".8.", ".0.", 1);
+        assert_eq!(verify(&config, "slq_a", &easier, "0", 1010), Err(PowError::BadSignature));
+        assert_eq!(verify(&config, "slq_a", "nonsense", "0", 1010), Err(PowError::Malformed));
+    }
+
+    #[test]
+    fn wrong_solutions_are_refused() {
+        let config = config();
+        let challenge = issue(&config, "slq_a", 12, 0);
+        let solution: u64 = solve(&challenge.token, 12).parse().unwrap();
+        let wrong = (0..solution).find(|n| leading_zero_bits(&solution_hash(&challenge.token, &n.to_string())) < 12);
+        let wrong = wrong.map_or("x".to_string(), |n| n.to_string());
+        assert_eq!(verify(&config, "slq_a", &challenge.token, &wrong, 0), Err(PowError::WrongSolution));
+    }
+
+    #[test]
+    fn difficulty_rises_with_pressure() {
+        let config = config();
+        assert_eq!(difficulty_for(&config, 0.0), 4);
+        assert_eq!(difficulty_for(&config, 0.5), 8);
+        assert_eq!(difficulty_for(&config, 3.0), 12);
+        assert_eq!(leading_zero_bits(&[0, 0x1f, 0xff]), 11);
+    }
+}
@@ -0,0 +1,255 @@
+use crate::config::SpamConfig;
+use std::collections::{BTreeSet, HashMap};
+use std::time::Duration;
+
+/// Messages of each kind the filter must have learned from before its word
+/// statistics count; until then only the heuristics score.
+pub const MIN_TRAINING_MESSAGES: i32 = 5;
+
+/// Tokens that decide the Bayes score: the ones furthest from neutral.
+const INTERESTING_TOKENS: usize = 15;
+
+/// Limit on the tokens kept from one submission.
+const MAX_TOKENS: usize = 200;
+
+/// Something a reader sent through a public form.
+#[derive(Clone, Debug, Default)]
+pub struct Submission<'a> {
+    pub author_name: &'a str,
+    pub author_email: Option<&'a str>,
+    pub author_url: Option<&'a str>,
+    pub body: &'a str,
+    /// A form field hidden from people; bots fill it in
+    pub honeypot: Option<&'a str>,
+    /// Time between the form being shown and sent, when the frontend says
+    pub elapsed: Option<Duration>,
+}
+
+impl<'a> From<&'a models::comments::Model> for Submission<'a> {
+    /// A stored comment, for learning from: what the form sent beyond the
+    /// comment itself isn't kept.
+    fn from(comment: &'a models::comments::Model) -> Self {
+        Submission {
+            author_name: &comment.author_name,
+            author_email: comment.author_email.as_deref(),
+            author_url: comment.author_url.as_deref(),
+            body: &comment.body,
+            honeypot: None,
+            elapsed: None,
+        }
+    }
+}
+
+/// One reason a submission looks like spam, as log-odds added to its score.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Signal {
+    pub name: &'static str,
+    pub weight: f64,
+}
+
+/// What the filter has learned for one blog.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Corpus {
+    pub spam_messages: i32,
+    pub ham_messages: i32,
+    /// `token -> (spam count, ham count)`, for the tokens being scored
+    pub tokens: HashMap<String, (i32, i32)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Verdict {
+    /// From 0, surely ham, to 1, surely spam
+    pub score: f64,
+    /// The naive Bayes log-odds, if the corpus is big enough to use
+    pub bayes: Option<f64>,
+    pub signals: Vec<Signal>,
+}
+
+fn push_words(tokens: &mut BTreeSet<String>, prefix: &str, text: &str) {
+    for word in text.split(|c: char| !(c.is_alphanumeric() || c == '\'' || c == '$')) {
+        let word = word.trim_matches('\'').to_lowercase();
+        if (2..=30).contains(&word.chars().count()) && !word.chars().all(|c| c.is_ascii_digit()) {
+            tokens.insert(format!("{prefix}{word}"));
+        }
+    }
+}
+
+fn strip_prefix_ignore_case<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    let bytes = text.as_bytes();
+    (bytes.len() >= prefix.len() && bytes[..prefix.len()].eq_ignore_ascii_case(prefix.as_bytes()))
+        .then(|| &text[prefix.len()..])
+}
+
+/// Host of an `http(s)://` or `www.` link at the start of `text`, and the
+/// length of the link up to the end of the host.
+fn link_host(text: &str) -> Option<(&str, usize)> {
+    let rest = strip_prefix_ignore_case(text, "https://")
+        .or_else(|| strip_prefix_ignore_case(text, "http://"))
+        .or_else(|| strip_prefix_ignore_case(text, "www.").map(|_| text))?;
+    let end = rest
+        .find(|c: char| matches!(c, '/' | '?' | '#' | ')' | ']' | '>' | '"' | '\'' | ',') || c.is_whitespace())
+        .unwrap_or(rest.len());
+    let host = &rest[..end];
+    (!host.is_empty()).then(|| (host, text.len() - rest.len() + end))
+}
+
+fn normalize_host(host: &str) -> String {
+    let host = host.to_ascii_lowercase();
+    host.strip_prefix("www.").map(str::to_string).unwrap_or(host)
+}
+
+/// Hosts of the links in `text`, in order.
+pub fn links(text: &str) -> Vec<String> {
+    let mut hosts = Vec::new();
+    let mut next = 0;
+    for (at, _) in text.char_indices() {
+        if at < next {
+            continue;
+        }
+        if let Some((host, len)) = link_host(&text[at..]) {
+            hosts.push(normalize_host(host));
+            next = at + len;
+        }
+    }
+    hosts
+}
+
+/// The distinct tokens the filter learns from and scores: the words of the
+/// body and name, the email's domain and the hosts of every link.
+pub fn tokenize(submission: &Submission<'_>) -> BTreeSet<String> {
+    let mut tokens = BTreeSet::new();
+    push_words(&mut tokens, "", submission.body);
+    push_words(&mut tokens, "name:", submission.author_name);
+    if let Some((_, domain)) = submission.author_email.and_then(|e| e.rsplit_once('@')) {
+        tokens.insert(format!("email:{}", domain.to_lowercase()));
+    }
+    for host in links(submission.body) {
+        tokens.insert(format!("link:{host}"));
+    }
+    if let Some((host, _)) = submission.author_url.and_then(link_host) {
+        tokens.insert(format!("url:{}", normalize_host(host)));
+    }
+    while tokens.len() > MAX_TOKENS {
+        tokens.pop_last();
+    }
+    tokens
+}
+
+/// Naive Bayes log-odds that `tokens` are spam, from the most telling of
+/// them, or `None` while the corpus is too small to trust.
+pub fn bayes_log_odds(corpus: &Corpus, tokens: &BTreeSet<String>) -> Option<f64> {
+    if corpus.spam_messages < MIN_TRAINING_MESSAGES || corpus.ham_messages < MIN_TRAINING_MESSAGES {
+        return None;
+    }
+    let spam = f64::from(corpus.spam_messages);
+    let ham = f64::from(corpus.ham_messages);
+    // Laplace-smoothed likelihood ratio of each token seen in training
+    let mut ratios: Vec<f64> = tokens
+        .iter()
+        .filter_map(|t| corpus.tokens.get(t))
+        .filter(|(s, h)| s + h > 0)
+        .map(|&(s, h)| ((f64::from(s) + 1.0) / (spam + 2.0)).ln() - ((f64::from(h) + 1.0) / (ham + 2.0)).ln())
+        .collect();
+    ratios.sort_by(|a, b| b.abs().total_cmp(&a.abs()));
+    let prior = (spam / ham).ln();
+    Some(prior + ratios.iter().take(INTERESTING_TOKENS).sum::<f64>())
+}
+
+/// Reasons to suspect `submission` that don't depend on what was learned.
+pub fn heuristics(submission: &Submission<'_>, config: &SpamConfig) -> Vec<Signal> {
+    let mut signals = Vec::new();
+    let links = links(submission.body).len();
+    if links > 1 {
+        signals.push(Signal { name: "links", weight: (links - 1).min(4) as f64 });
+    }
+    if submission.author_url.is_some() && links > 0 {
+        signals.push(Signal { name: "url_and_links", weight: 0.5 });
+    }
+    if let Some(elapsed) = submission.elapsed
+        && elapsed < config.min_submit_time
+    {
+        signals.push(Signal { name: "too_fast", weight: 3.0 });
+    }
+    let letters: Vec<char> = submission.body.chars().filter(|c| c.is_alphabetic()).collect();
+    if letters.len() >= 20 && letters.iter().all(|c| !c.is_lowercase()) {
+        signals.push(Signal { name: "shouting", weight: 1.0 });
+    }
+    signals
+}
+
+fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+/// Score a submission. A filled-in honeypot is spam outright; otherwise the
+/// Bayes log-odds and the heuristics add up.
+pub fn score(submission: &Submission<'_>, corpus: &Corpus, config: &SpamConfig) -> Verdict {
+    if submission.honeypot.is_some_and(|h| !h.trim().is_empty()) {
+        return Verdict { score: 1.0, bayes: None, signals: vec![Signal { name: "honeypot", weight: f64::INFINITY }] };
+    }
+    let bayes = bayes_log_odds(corpus, &tokenize(submission));
+    let signals = heuristics(submission, config);
+    let log_odds = bayes.unwrap_or(0.0) + signals.iter().map(|s| s.weight).sum::<f64>();
+    Verdict { score: sigmoid(log_odds), bayes, signals }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn submission(body: &str) -> Submission<'_> {
+        Submission { author_name: "Ada", body, ..Default::default() }
+    }
+
+    #[test]
+    fn tokenizes_words_and_link_hosts() {
+        let tokens = tokenize(&Submission {
+            author_email: Some("x@Mail.example"),
+            author_url: Some("https://www.Site.example/about"),
+            ..submission("Buy CHEAP pills at https://pills.example/x or www.deals.example, 100% off!")
+        });
+        for token in ["buy", "cheap", "pills", "name:ada", "email:mail.example", "link:pills.example", "link:deals.example", "url:site.example"] {
+            assert!(tokens.contains(token), "{token} missing from {tokens:?}");
+        }
+        assert!(!tokens.contains("100"));
+        assert_eq!(links("see http://a.example and http://b.example/p"), ["a.example", "b.example"]);
+    }
+
+    #[test]
+    fn heuristics_score_without_training() {
+        let config = SpamConfig::default();
+        let corpus = Corpus::default();
+
+        let ham = score(&submission("Lovely post, thanks!"), &corpus, &config);
+        assert_eq!(ham.score, 0.5);
+        assert!(ham.bayes.is_none());
+
+        let links = "http://a.example http://b.example http://c.example http://d.example http://e.example";
+        assert!(score(&submission(links), &corpus, &config).score > config.threshold);
+
+        let fast = Submission { elapsed: Some(Duration::from_secs(1)), ..submission("Lovely post") };
+        assert!(score(&fast, &corpus, &config).score > config.threshold);
+
+        let trap = Submission { honeypot: Some("http://x.example"), ..submission("hi") };
+        assert_eq!(score(&trap, &corpus, &config).score, 1.0);
+    }
+
+    #[test]
+    fn bayes_learns_from_counts() {
+        let mut corpus = Corpus { spam_messages: 10, ham_messages: 10, tokens: HashMap::new() };
+        corpus.tokens.insert("casino".to_string(), (9, 0));
+        corpus.tokens.insert("bonus".to_string(), (8, 1));
+        corpus.tokens.insert("thanks".to_string(), (1, 9));
+        corpus.tokens.insert("post".to_string(), (2, 8));
+        let config = SpamConfig::default();
+
+        let spam = score(&submission("Casino bonus today"), &corpus, &config);
+        assert!(spam.bayes.unwrap() > 0.0);
+        assert!(spam.score > config.threshold, "{spam:?}");
+        let ham = score(&submission("Thanks for the post"), &corpus, &config);
+        assert!(ham.score < 0.1, "{ham:?}");
+
+        let small = Corpus { spam_messages: 4, ..corpus };
+        assert_eq!(bayes_log_odds(&small, &tokenize(&submission("casino"))), None);
+    }
+}
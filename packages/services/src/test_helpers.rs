@@ -1,3 +1,4 @@
+use crate::config::AuthConfig;
 use chrono::Utc;
 use models::users;
 use sea_orm::*;
@@ -12,6 +13,15 @@ pub async fn setup_test_db() -> DatabaseConnection {
         .expect("Failed to connect to test database")
 }
 
+pub fn test_auth_config() -> AuthConfig {
+    AuthConfig {
+        token_secret: "test-token-secret".to_string(),
+        token_expiration_seconds: 3600,
+        refresh_token_expiration_days: 7,
+        host_name: "localhost".to_string(),
+    }
+}
+
 pub async fn create_test_user(
     db: &DatabaseConnection,
     email: &str,
@@ -34,7 +44,7 @@ pub async fn cleanup_test_user(db: &DatabaseConnection, user_id: Uuid) {
 }
 
 pub fn create_test_token(user: &users::Model) -> crate::authentication::token::Token {
-    let token_string = crate::authentication::token::generate_token(user);
+    let token_string = crate::authentication::token::generate_token(&test_auth_config(), user);
     crate::authentication::token::Token::new(token_string)
 }
 
@@ -44,7 +54,7 @@ pub fn create_expired_token(user: &users::Model) -> crate::authentication::token
     use jsonwebtoken::{encode, EncodingKey, Header};
 
     let expiration = Utc::now().checked_sub_signed(Duration::hours(1)).unwrap();
-    let secret = std::env::var("TOKEN_SECRET").unwrap_or_else(|_| "secret".to_string());
+    let secret = test_auth_config().token_secret;
 
     let claims = Claims {
         iss: "localhost".to_string(),
@@ -99,7 +109,7 @@ pub fn create_token_for_nonexistent_user() -> crate::authentication::token::Toke
     use jsonwebtoken::{encode, EncodingKey, Header};
 
     let expiration = Utc::now().checked_add_signed(Duration::hours(1)).unwrap();
-    let secret = std::env::var("TOKEN_SECRET").unwrap_or_else(|_| "secret".to_string());
+    let secret = test_auth_config().token_secret;
 
     let claims = Claims {
         iss: "localhost".to_string(),
@@ -12,7 +12,7 @@ pub struct TokenError {
 }
 
 impl TokenError {
-    fn new(msg: &str) -> Self {
+    pub(crate) fn new(msg: &str) -> Self {
         TokenError { message: msg.to_string() }
     }
 }
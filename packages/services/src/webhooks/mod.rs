@@ -0,0 +1,150 @@
+use crate::config::QueueConfig;
+use crate::net;
+use hmac::{Hmac, KeyInit, Mac};
+use sha2::Sha256;
+use std::fmt::Write;
+use uuid::Uuid;
+
+pub use reqwest::Client;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Most bytes of a response body kept in the delivery log.
+pub(crate) const MAX_LOGGED_BODY: usize = 2000;
+
+/// Something that happened to a blog's content that webhooks can ask to be
+/// told about.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WebhookEvent {
+    PostPublished,
+    PostUpdated,
+    PostUnpublished,
+    PostDeleted,
+    AssetCreated,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 5] = [
+        WebhookEvent::PostPublished,
+        WebhookEvent::PostUpdated,
+        WebhookEvent::PostUnpublished,
+        WebhookEvent::PostDeleted,
+        WebhookEvent::AssetCreated,
+    ];
+
+    /// The name endpoints see, in payloads and the `X-Soliloquio-Event`
+    /// header.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WebhookEvent::PostPublished => "post.published",
+            WebhookEvent::PostUpdated => "post.updated",
+            WebhookEvent::PostUnpublished => "post.unpublished",
+            WebhookEvent::PostDeleted => "post.deleted",
+            WebhookEvent::AssetCreated => "asset.created",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|e| e.as_str() == name)
+    }
+}
+
+/// A new signing secret, shown to the user so their endpoint can check
+/// signatures.
+pub fn generate_secret() -> String {
+    format!("whsec_{}", Uuid::new_v4().simple())
+}
+
+/// The `X-Soliloquio-Signature` of `body` sent at `timestamp`: `sha256=` and
+/// the hex HMAC-SHA256 of `"{timestamp}.{body}"` keyed with `secret`.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac.finalize().into_bytes().iter().fold("sha256=".to_string(), |mut s, b| {
+        write!(s, "{b:02x}").unwrap();
+        s
+    })
+}
+
+/// How one POST to an endpoint went.
+#[derive(Debug, Default)]
+pub struct Attempt {
+    pub status: Option<u16>,
+    /// The start of the response body
+    pub body: Option<String>,
+    /// Why the attempt counts as failed; `None` for a 2xx response
+    pub error: Option<String>,
+}
+
+/// The HTTP client deliveries go through, shared between passes over the
+/// queue. It only connects to public addresses.
+pub fn client(config: &QueueConfig) -> Client {
+    net::client_builder()
+        .timeout(config.timeout)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("TLS backend is available")
+}
+
+/// POST a signed JSON `body` to `url`. Anything but a 2xx answer is a
+/// failure, as is a URL that leads somewhere private.
+pub async fn deliver(
+    client: &Client,
+    url: &str,
+    secret: &str,
+    delivery_id: Uuid,
+    event: &str,
+    body: Vec<u8>,
+) -> Attempt {
+    let url = match reqwest::Url::parse(url) {
+        Ok(url) => url,
+        Err(e) => return Attempt { error: Some(format!("bad URL: {e}")), ..Default::default() },
+    };
+    if let Err(e) = net::check_url(&url) {
+        return Attempt { error: Some(e), ..Default::default() };
+    }
+    let timestamp = chrono::Utc::now().timestamp();
+    let signature = sign(secret, timestamp, &body);
+    let response = client
+        .post(url)
+        .header("Content-Type", "application/json")
+        .header("User-Agent", "Soliloquio-Webhooks/1.0")
+        .header("X-Soliloquio-Event", event)
+        .header("X-Soliloquio-Delivery", delivery_id.to_string())
+        .header("X-Soliloquio-Timestamp", timestamp.to_string())
+        .header("X-Soliloquio-Signature", signature)
+        .body(body)
+        .send()
+        .await;
+    let response = match response {
+        Ok(r) => r,
+        Err(e) => return Attempt { error: Some(e.to_string()), ..Default::default() },
+    };
+
+    let status = response.status();
+    let body = net::body_excerpt(response, MAX_LOGGED_BODY).await;
+    Attempt {
+        status: Some(status.as_u16()),
+        body,
+        error: (!status.is_success()).then(|| format!("HTTP {status}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signs_timestamp_and_body() {
+        assert_eq!(
+            sign("whsec_test", 1700000000, br#"{"event":"post.published"}"#),
+            "sha256=7b75d2efbd85f56f9d78d15cce3ed1b6a66b9deace99aae9bbbef7cc25692285"
+        );
+        for event in WebhookEvent::ALL {
+            assert_eq!(WebhookEvent::parse(event.as_str()), Some(event));
+        }
+        assert_eq!(WebhookEvent::parse("post.liked"), None);
+    }
+}
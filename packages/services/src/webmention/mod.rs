@@ -0,0 +1,122 @@
+use crate::config::QueueConfig;
+use crate::net;
+use crate::webhooks::{Attempt, Client, MAX_LOGGED_BODY};
+use reqwest::Url;
+
+/// Most redirects followed fetching a page.
+const MAX_REDIRECTS: usize = 5;
+
+/// Most of a page read; the links that matter are near the top, and a page
+/// this size is already suspect.
+const MAX_PAGE_BYTES: usize = 1024 * 1024;
+
+/// A fetched page: where it ended up after redirects, and what it said.
+pub struct Page {
+    pub status: u16,
+    pub url: Url,
+    /// Values of its `Link` headers
+    pub links: Vec<String>,
+    pub body: String,
+}
+
+/// The HTTP client Webmention fetches and sends go through. Unlike webhook
+/// deliveries, pages may redirect, though only to public addresses.
+pub fn client(config: &QueueConfig) -> Client {
+    net::client_builder()
+        .timeout(config.timeout)
+        .redirect(net::redirect_policy(MAX_REDIRECTS))
+        .user_agent("Soliloquio-Webmention/1.0")
+        .build()
+        .expect("TLS backend is available")
+}
+
+/// GET `url`, reading at most `MAX_PAGE_BYTES` of the body.
+pub async fn fetch(client: &Client, url: &str) -> Result<Page, String> {
+    let url = Url::parse(url).map_err(|e| format!("bad URL: {e}"))?;
+    net::check_url(&url)?;
+    let response = client
+        .get(url)
+        .header("Accept", "text/html, application/xhtml+xml;q=0.9, */*;q=0.1")
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    let status = response.status().as_u16();
+    let final_url = response.url().clone();
+    let links = response
+        .headers()
+        .get_all("Link")
+        .iter()
+        .filter_map(|v| v.to_str().ok().map(str::to_string))
+        .collect();
+    let body = net::read_capped(response, MAX_PAGE_BYTES).await.map_err(|e| e.to_string())?;
+    Ok(Page { status, url: final_url, links, body: String::from_utf8_lossy(&body).into_owned() })
+}
+
+/// The Webmention endpoint a page's `Link` headers name, resolved against
+/// the page's URL.
+pub fn link_header_endpoint(links: &[String], base: &Url) -> Option<String> {
+    links
+        .iter()
+        .flat_map(|header| header.split(','))
+        .find_map(|link| {
+            let (target, params) = link.trim().split_once(';')?;
+            let target = target.trim().strip_prefix('<')?.strip_suffix('>')?;
+            let is_webmention = params.split(';').any(|param| {
+                let Some((name, value)) = param.split_once('=') else { return false };
+                name.trim().eq_ignore_ascii_case("rel")
+                    && value.trim().trim_matches('"').split_ascii_whitespace().any(|rel| rel.eq_ignore_ascii_case("webmention"))
+            });
+            is_webmention.then(|| base.join(target).ok()).flatten()
+        })
+        .map(String::from)
+}
+
+/// Tell `endpoint` that `source` links to `target`. Anything but a 2xx
+/// answer is a failure, as is an endpoint somewhere private.
+pub async fn notify(client: &Client, endpoint: &str, source: &str, target: &str) -> Attempt {
+    let endpoint = match Url::parse(endpoint) {
+        Ok(url) => url,
+        Err(e) => return Attempt { error: Some(format!("bad URL: {e}")), ..Default::default() },
+    };
+    if let Err(e) = net::check_url(&endpoint) {
+        return Attempt { error: Some(e), ..Default::default() };
+    }
+    let body = form_urlencoded::Serializer::new(String::new())
+        .append_pair("source", source)
+        .append_pair("target", target)
+        .finish();
+    let response = client
+        .post(endpoint)
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body(body)
+        .send()
+        .await;
+    let response = match response {
+        Ok(r) => r,
+        Err(e) => return Attempt { error: Some(e.to_string()), ..Default::default() },
+    };
+
+    let status = response.status();
+    Attempt {
+        status: Some(status.as_u16()),
+        body: net::body_excerpt(response, MAX_LOGGED_BODY).await,
+        error: (!status.is_success()).then(|| format!("HTTP {status}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_endpoint_in_link_headers() {
+        let base = Url::parse("https://site.example/posts/1").unwrap();
+        let links = vec![
+            r#"<https://site.example/feed>; rel="alternate", </mention?x=1>; rel="other webmention""#.to_string(),
+        ];
+        assert_eq!(link_header_endpoint(&links, &base).as_deref(), Some("https://site.example/mention?x=1"));
+        let links = vec![r#"<https://site.example/>; rel="webmentions""#.to_string()];
+        assert_eq!(link_header_endpoint(&links, &base), None);
+        assert_eq!(link_header_endpoint(&[r#"<>; rel=webmention"#.to_string()], &base).as_deref(), Some("https://site.example/posts/1"));
+    }
+}
@@ -7,11 +7,17 @@ create table posts (
     cover_image text,
     user_id uuid not null,
     is_published boolean default false not null,
+    render_math boolean default true not null,
+    rendered_markdown jsonb,
+    seo jsonb,
+    preview_image text,
     first_published_at timestamp,
     created_at timestamp default current_timestamp not null,
     updated_at timestamp default current_timestamp not null
 );
 
+create type comment_policy as enum ('open', 'moderated', 'closed');
+
 create table users (
     id uuid primary key,
     email text not null unique,
@@ -19,6 +25,9 @@ create table users (
     email_verified_at timestamp,
     display_name text,
     bio text,
+    comment_policy comment_policy not null default 'moderated',
+    -- Close comments this many days after a post is first published
+    comments_close_after_days integer,
     created_at timestamp default current_timestamp,
     updated_at timestamp default current_timestamp
 );
@@ -65,6 +74,8 @@ create table api_keys (
     user_id uuid not null references users(id) on delete cascade,
     key_hash varchar(255) not null unique,
     label text not null,
+    -- What the key may do beyond reading the public API, e.g. ["create", "media"]
+    scopes jsonb not null default '[]',
     last_used_at timestamp,
     created_at timestamp default current_timestamp not null
 );
@@ -76,10 +87,260 @@ CREATE TABLE assets (
     original_filename TEXT NOT NULL,
     mime_type TEXT NOT NULL,
     size_bytes BIGINT NOT NULL,
+    width INTEGER,
+    height INTEGER,
     created_at TIMESTAMP DEFAULT current_timestamp NOT NULL
 );
 CREATE INDEX idx_assets_user_id ON assets(user_id, created_at DESC);
 
+create type comment_status as enum ('pending', 'approved', 'rejected', 'spam');
+
+create table comments (
+    id uuid primary key default gen_random_uuid(),
+    post_id uuid not null references posts(id) on delete cascade,
+    -- The blog owner, who moderates the comment
+    user_id uuid not null references users(id) on delete cascade,
+    parent_id uuid references comments(id) on delete cascade,
+    author_name text not null,
+    author_email text,
+    author_url text,
+    body text not null,
+    status comment_status not null default 'pending',
+    -- Spam filter's verdict at submission, from 0 (ham) to 1 (spam)
+    spam_score real,
+    -- What the comment taught the spam filter: true spam, false ham, null nothing
+    spam_trained boolean,
+    -- The ActivityPub object the comment was made from, for a reply from the fediverse
+    activitypub_id text,
+    -- The actor who sent that object, the only one who may delete it
+    activitypub_actor text,
+    created_at timestamp default current_timestamp not null,
+    moderated_at timestamp,
+    unique (user_id, activitypub_id)
+);
+create index idx_comments_post on comments(post_id, status, created_at);
+create index idx_comments_user_status on comments(user_id, status, created_at desc, id desc);
+
+-- Naive Bayes spam filter, per blog, trained on moderation decisions
+create table spam_corpora (
+    user_id uuid primary key references users(id) on delete cascade,
+    spam_messages integer not null default 0,
+    ham_messages integer not null default 0
+);
+create table spam_tokens (
+    user_id uuid not null references users(id) on delete cascade,
+    token text not null,
+    spam_count integer not null default 0,
+    ham_count integer not null default 0,
+    primary key (user_id, token)
+);
+
+-- Proof-of-work challenges already redeemed, kept until they would have
+-- expired anyway, so each is good once across restarts and instances
+create table spent_pow_nonces (
+    nonce text primary key,
+    expires_at timestamp not null
+);
+create index idx_spent_pow_nonces_expires_at on spent_pow_nonces(expires_at);
+
+create type subscriber_status as enum ('pending', 'active', 'unsubscribed');
+
+-- Readers who get new posts by email, per blog
+create table subscribers (
+    id uuid primary key default gen_random_uuid(),
+    -- The blog subscribed to
+    user_id uuid not null references users(id) on delete cascade,
+    email text not null,
+    status subscriber_status not null default 'pending',
+    created_at timestamp default current_timestamp not null,
+    confirmed_at timestamp,
+    unsubscribed_at timestamp,
+    unique (user_id, email)
+);
+create index idx_subscribers_user_status on subscribers(user_id, status, created_at desc, id desc);
+
+-- Double opt-in links, like verification_tokens
+create table subscriber_tokens (
+    id uuid primary key default gen_random_uuid(),
+    subscriber_id uuid not null references subscribers(id) on delete cascade,
+    token_hash varchar(255) not null,
+    expires_at timestamp not null,
+    used_at timestamp,
+    created_at timestamp default current_timestamp not null
+);
+create index idx_subscriber_tokens_token_hash on subscriber_tokens(token_hash);
+
+create type delivery_status as enum ('queued', 'sent', 'failed');
+
+-- One email of a newly published post to one subscriber
+create table newsletter_deliveries (
+    id uuid primary key default gen_random_uuid(),
+    post_id uuid not null references posts(id) on delete cascade,
+    subscriber_id uuid not null references subscribers(id) on delete cascade,
+    status delivery_status not null default 'queued',
+    attempts integer not null default 0,
+    -- When a queued delivery may next be tried; pushed back on each attempt
+    next_attempt_at timestamp default current_timestamp not null,
+    last_error text,
+    sent_at timestamp,
+    created_at timestamp default current_timestamp not null,
+    unique (post_id, subscriber_id)
+);
+create index idx_newsletter_deliveries_due on newsletter_deliveries(next_attempt_at) where status = 'queued';
+
+-- Endpoints told about content events, per blog
+create table webhooks (
+    id uuid primary key default gen_random_uuid(),
+    user_id uuid not null references users(id) on delete cascade,
+    url text not null,
+    -- Signs each payload; kept in the clear since signing needs it
+    secret varchar(255) not null,
+    -- The event names the endpoint wants, e.g. ["post.published"]
+    events jsonb not null,
+    is_active boolean not null default true,
+    created_at timestamp default current_timestamp not null,
+    updated_at timestamp default current_timestamp not null
+);
+create index idx_webhooks_user on webhooks(user_id, created_at);
+
+-- One event sent, or to be sent, to one webhook
+create table webhook_deliveries (
+    id uuid primary key default gen_random_uuid(),
+    webhook_id uuid not null references webhooks(id) on delete cascade,
+    event varchar(50) not null,
+    payload jsonb not null,
+    status delivery_status not null default 'queued',
+    attempts integer not null default 0,
+    next_attempt_at timestamp default current_timestamp not null,
+    -- From the latest attempt
+    response_status integer,
+    response_body text,
+    last_error text,
+    delivered_at timestamp,
+    created_at timestamp default current_timestamp not null
+);
+create index idx_webhook_deliveries_webhook on webhook_deliveries(webhook_id, created_at desc, id desc);
+create index idx_webhook_deliveries_due on webhook_deliveries(next_attempt_at) where status = 'queued';
+
+create type ping_kind as enum ('websub', 'xmlrpc');
+
+-- A WebSub hub or ping service told that a feed changed
+create table feed_pings (
+    id uuid primary key default gen_random_uuid(),
+    user_id uuid not null references users(id) on delete cascade,
+    -- The post whose publishing changed the feed
+    post_id uuid references posts(id) on delete set null,
+    kind ping_kind not null,
+    endpoint text not null,
+    feed_url text not null,
+    -- The request body, built when the ping is queued
+    body text not null,
+    status delivery_status not null default 'queued',
+    attempts integer not null default 0,
+    next_attempt_at timestamp default current_timestamp not null,
+    response_status integer,
+    last_error text,
+    sent_at timestamp,
+    created_at timestamp default current_timestamp not null
+);
+create index idx_feed_pings_user on feed_pings(user_id, created_at desc, id desc);
+create index idx_feed_pings_due on feed_pings(next_attempt_at) where status = 'queued';
+
+create type webmention_kind as enum ('mention', 'reply', 'like', 'repost', 'bookmark');
+create type webmention_verification as enum ('queued', 'verified', 'invalid');
+
+-- A page elsewhere that says it links to one of our posts
+create table webmentions (
+    id uuid primary key default gen_random_uuid(),
+    post_id uuid not null references posts(id) on delete cascade,
+    -- The blog owner, who moderates the mention
+    user_id uuid not null references users(id) on delete cascade,
+    source text not null,
+    target text not null,
+    -- Whether the source has been fetched and found to link to the target
+    verification webmention_verification not null default 'queued',
+    attempts integer not null default 0,
+    next_attempt_at timestamp default current_timestamp not null,
+    last_error text,
+    verified_at timestamp,
+    status comment_status not null default 'pending',
+    moderated_at timestamp,
+    -- What the source's h-entry says about itself, as of the last verification
+    kind webmention_kind not null default 'mention',
+    author_name text,
+    author_url text,
+    author_photo text,
+    title text,
+    content text,
+    published_at timestamp,
+    created_at timestamp default current_timestamp not null,
+    unique (source, target)
+);
+create index idx_webmentions_post on webmentions(post_id, status, created_at);
+create index idx_webmentions_user_status on webmentions(user_id, status, created_at desc, id desc);
+create index idx_webmentions_due on webmentions(next_attempt_at) where verification = 'queued';
+
+-- A page our post links to, told about the link
+create table outgoing_webmentions (
+    id uuid primary key default gen_random_uuid(),
+    user_id uuid not null references users(id) on delete cascade,
+    post_id uuid not null references posts(id) on delete cascade,
+    source text not null,
+    target text not null,
+    -- The endpoint discovered on the target, once it has been looked for
+    endpoint text,
+    status delivery_status not null default 'queued',
+    attempts integer not null default 0,
+    next_attempt_at timestamp default current_timestamp not null,
+    response_status integer,
+    last_error text,
+    sent_at timestamp,
+    created_at timestamp default current_timestamp not null,
+    unique (post_id, target)
+);
+create index idx_outgoing_webmentions_user on outgoing_webmentions(user_id, created_at desc, id desc);
+create index idx_outgoing_webmentions_due on outgoing_webmentions(next_attempt_at) where status = 'queued';
+
+-- The ActivityPub actor a blog federates as, once its owner has picked a name
+create table activitypub_actors (
+    user_id uuid primary key references users(id) on delete cascade,
+    username text not null unique,
+    -- Signs deliveries and fetches; the public half is in the actor document
+    public_key_pem text not null,
+    private_key_pem text not null,
+    created_at timestamp default current_timestamp not null
+);
+
+-- A fediverse account following a blog
+create table activitypub_followers (
+    id uuid primary key default gen_random_uuid(),
+    user_id uuid not null references users(id) on delete cascade,
+    -- The follower's actor id
+    actor text not null,
+    inbox text not null,
+    shared_inbox text,
+    created_at timestamp default current_timestamp not null,
+    unique (user_id, actor)
+);
+create index idx_activitypub_followers_user on activitypub_followers(user_id, created_at desc, id desc);
+
+-- One activity sent, or to be sent, to one inbox
+create table activitypub_deliveries (
+    id uuid primary key default gen_random_uuid(),
+    user_id uuid not null references users(id) on delete cascade,
+    inbox text not null,
+    activity jsonb not null,
+    status delivery_status not null default 'queued',
+    attempts integer not null default 0,
+    next_attempt_at timestamp default current_timestamp not null,
+    response_status integer,
+    last_error text,
+    delivered_at timestamp,
+    created_at timestamp default current_timestamp not null
+);
+create index idx_activitypub_deliveries_user on activitypub_deliveries(user_id, created_at desc, id desc);
+create index idx_activitypub_deliveries_due on activitypub_deliveries(next_attempt_at) where status = 'queued';
+
 CREATE EXTENSION IF NOT EXISTS pg_search;
 CREATE EXTENSION IF NOT EXISTS pg_ivm;
 CREATE EXTENSION IF NOT EXISTS vector;
@@ -0,0 +1,205 @@
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse};
+use graphql::utilities::activitypub::{
+    actor_document, article, deliver_due, followers_collection, outbox as outbox_collection, receive, render_content,
+    tombstone, webfinger as webfinger_document, webfinger_username, with_context, InboxError, InboxRequest,
+};
+use models::activitypub_actors;
+use repositories::{ActivityPubRepository, PostRepository, UserRepository};
+use sea_orm::DatabaseConnection;
+use services::activitypub::ACTIVITY_JSON;
+use services::assets::StorageDriver;
+use services::config::{QueueConfig, SiteConfig, SpamConfig};
+use services::webhooks::Client;
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+fn activity_json(document: serde_json::Value) -> HttpResponse {
+    HttpResponse::Ok().content_type(ACTIVITY_JSON).json(document)
+}
+
+fn server_error(what: &str, e: String) -> HttpResponse {
+    tracing::error!(error = %e, "{what} failed");
+    HttpResponse::InternalServerError().json(serde_json::json!({"error": format!("{what} failed")}))
+}
+
+/// Whether the request asks for an ActivityPub document rather than a page.
+fn wants_json(req: &HttpRequest) -> bool {
+    let accept = req.headers().get(header::ACCEPT).and_then(|v| v.to_str().ok()).unwrap_or_default();
+    accept.contains("json")
+}
+
+/// People following a link to an actor or object land on the blog itself.
+fn see_other(location: &str) -> HttpResponse {
+    HttpResponse::SeeOther().insert_header((header::LOCATION, location)).finish()
+}
+
+async fn find_actor(db: &DatabaseConnection, username: &str) -> Result<activitypub_actors::Model, HttpResponse> {
+    match ActivityPubRepository::actor_by_username(db, username).await {
+        Ok(Some(actor)) => Ok(actor),
+        Ok(None) => Err(HttpResponse::NotFound().finish()),
+        Err(e) => Err(server_error("finding actor", e)),
+    }
+}
+
+/// `GET /.well-known/webfinger?resource=acct:name@host`: where a blog's
+/// actor is.
+pub async fn webfinger(
+    query: web::Query<HashMap<String, String>>,
+    db: web::Data<DatabaseConnection>,
+    site: web::Data<SiteConfig>,
+) -> HttpResponse {
+    let Some(resource) = query.get("resource") else {
+        return HttpResponse::BadRequest().json(serde_json::json!({"error": "missing resource"}));
+    };
+    let Some(username) = webfinger_username(&site, resource) else {
+        return HttpResponse::NotFound().finish();
+    };
+    match find_actor(&db, &username).await {
+        Ok(actor) => HttpResponse::Ok()
+            .content_type("application/jrd+json")
+            .insert_header(("Access-Control-Allow-Origin", "*"))
+            .json(webfinger_document(&site, &actor)),
+        Err(response) => response,
+    }
+}
+
+/// `GET /ap/users/{username}`: the blog's actor.
+pub async fn actor(
+    req: HttpRequest,
+    path: web::Path<String>,
+    db: web::Data<DatabaseConnection>,
+    site: web::Data<SiteConfig>,
+) -> HttpResponse {
+    let actor = match find_actor(&db, &path).await {
+        Ok(actor) => actor,
+        Err(response) => return response,
+    };
+    if !wants_json(&req) {
+        return see_other(&site.url);
+    }
+    match UserRepository::find_by_id(&db, actor.user_id).await {
+        Ok(Some(user)) => activity_json(actor_document(&site, &actor, &user)),
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(e) => server_error("finding user", e.to_string()),
+    }
+}
+
+/// `POST /ap/users/{username}/inbox`: activities from other servers.
+pub async fn inbox(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Bytes,
+    db: web::Data<DatabaseConnection>,
+    client: web::Data<Client>,
+    site: web::Data<SiteConfig>,
+    spam: web::Data<SpamConfig>,
+) -> HttpResponse {
+    let mut headers: HashMap<String, String> = HashMap::new();
+    for (name, value) in req.headers() {
+        let Ok(value) = value.to_str() else { continue };
+        headers
+            .entry(name.as_str().to_lowercase())
+            .and_modify(|v| {
+                v.push_str(", ");
+                v.push_str(value);
+            })
+            .or_insert_with(|| value.to_string());
+    }
+    let path_and_query = req.uri().path_and_query().map_or(req.path(), |p| p.as_str());
+    let request = InboxRequest {
+        method: req.method().as_str(),
+        path: path_and_query,
+        headers,
+        body: &body,
+    };
+    match receive(&db, &client, &site, &spam, &path, request).await {
+        Ok(()) => HttpResponse::Accepted().finish(),
+        Err(InboxError::NotFound) => HttpResponse::NotFound().finish(),
+        Err(InboxError::Unauthorized(e)) => {
+            tracing::info!(error = %e, "activity refused");
+            HttpResponse::Unauthorized().json(serde_json::json!({"error": e}))
+        }
+        Err(InboxError::Invalid(e)) => HttpResponse::BadRequest().json(serde_json::json!({"error": e})),
+        Err(InboxError::Database(e)) => server_error("taking activity", e),
+    }
+}
+
+/// `GET /ap/users/{username}/outbox`: the blog's latest posts.
+pub async fn outbox(
+    path: web::Path<String>,
+    db: web::Data<DatabaseConnection>,
+    driver: web::Data<Arc<StorageDriver>>,
+    site: web::Data<SiteConfig>,
+) -> HttpResponse {
+    let actor = match find_actor(&db, &path).await {
+        Ok(actor) => actor,
+        Err(response) => return response,
+    };
+    match outbox_collection(&db, Some(&driver), &site, &actor).await {
+        Ok(document) => activity_json(document),
+        Err(e) => server_error("listing outbox", e),
+    }
+}
+
+/// `GET /ap/users/{username}/followers`: how many follow the blog.
+pub async fn followers(
+    path: web::Path<String>,
+    db: web::Data<DatabaseConnection>,
+    site: web::Data<SiteConfig>,
+) -> HttpResponse {
+    let actor = match find_actor(&db, &path).await {
+        Ok(actor) => actor,
+        Err(response) => return response,
+    };
+    match ActivityPubRepository::count_followers(&db, actor.user_id).await {
+        Ok(total) => activity_json(followers_collection(&site, &actor, total)),
+        Err(e) => server_error("counting followers", e),
+    }
+}
+
+/// `GET /ap/posts/{id}`: a published post as an `Article`, or a
+/// `Tombstone` once it's unpublished.
+pub async fn post(
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    db: web::Data<DatabaseConnection>,
+    driver: web::Data<Arc<StorageDriver>>,
+    site: web::Data<SiteConfig>,
+) -> HttpResponse {
+    let post = match PostRepository::find_by_id(&db, *path).await {
+        Ok(Some(post)) if post.first_published_at.is_some() => post,
+        Ok(_) => return HttpResponse::NotFound().finish(),
+        Err(e) => return server_error("finding post", e),
+    };
+    let actor = match ActivityPubRepository::actor_for_user(&db, post.user_id).await {
+        Ok(Some(actor)) => actor,
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(e) => return server_error("finding actor", e),
+    };
+    if !post.is_published {
+        return HttpResponse::Gone().content_type(ACTIVITY_JSON).json(with_context(tombstone(&site, post.id)));
+    }
+    if !wants_json(&req) {
+        return see_other(&site.post_url(&post.id.to_string(), post.slug.as_deref()));
+    }
+    match render_content(&db, Some(&driver), &site, &post).await {
+        Ok(content) => activity_json(with_context(article(&site, &actor, &post, &content))),
+        Err(e) => server_error("rendering post", e),
+    }
+}
+
+/// Send due activities to followers' inboxes every `config.poll_interval`,
+/// forever.
+pub async fn run_worker(db: DatabaseConnection, client: Client, site: SiteConfig, config: QueueConfig) {
+    let mut interval = actix_web::rt::time::interval(config.poll_interval);
+    loop {
+        interval.tick().await;
+        match deliver_due(&db, &client, &site, &config).await {
+            Ok(0) => {}
+            Ok(delivered) => tracing::info!(delivered, "activitypub deliveries made"),
+            Err(e) => tracing::error!(error = %e, "activitypub delivery failed"),
+        }
+    }
+}
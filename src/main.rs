@@ -1,43 +1,58 @@
+// The merged GraphQL roots nest deeply enough to pass the default limit
+// when the server future is laid out.
+#![recursion_limit = "256"]
+
 use actix_cors::Cors;
 use actix_web::{guard, web, App, HttpRequest, HttpResponse, HttpServer, Result};
 use async_graphql::{http::GraphiQLSource, Schema};
 use async_graphql_actix_web::{GraphQLRequest, GraphQLResponse, GraphQLSubscription};
 use tracing_actix_web::TracingLogger;
 use request_id::RequestIdSpanBuilder;
+mod activitypub;
+mod micropub;
+mod newsletter;
+mod oembed;
+mod pings;
+mod pow;
 mod request_id;
 mod setup;
 mod upload;
-use graphql::config::SingleUserMode;
+mod webhooks;
+mod webmention;
+use graphql::config::{SecureCookies, SingleUserMode};
 use graphql::authenticated::mutations::Mutations as MutationRoot;
 use graphql::authenticated::queries::Queries as QueryRoot;
 use graphql::authenticated::subscriptions::{on_connection_init, Subscriptions as SubscriptionRoot};
-use graphql::public::{build_public_schema, PublicApiKey, PublicSchema};
+use graphql::public::{build_public_schema, ClientIp, PublicApiKey, PublicSchema};
+use graphql::utilities::highlight::theme_css;
 use graphql::utilities::MarkdownCache;
 use services::assets::{LocalStorageDriver, StorageDriver};
 use services::authentication::Token;
+use services::config::{Config, LogFormat, MarkdownConfig, ServerConfig};
 use services::email::EmailService;
 use setup::set_up_db;
+use std::collections::HashMap;
 use std::sync::Arc;
 
 type SchemaType = Schema<QueryRoot, MutationRoot, SubscriptionRoot>;
 
 fn get_token_from_request(req: &HttpRequest) -> Option<Token> {
     // First check Authorization header
-    if let Some(auth_header) = req.headers().get("Authorization") {
-        if let Ok(auth_str) = auth_header.to_str() {
-            return Some(Token(auth_str.to_string()));
-        }
+    if let Some(auth_header) = req.headers().get("Authorization")
+        && let Ok(auth_str) = auth_header.to_str()
+    {
+        return Some(Token(auth_str.to_string()));
     }
 
     // Then check for access_token cookie
-    if let Some(cookie_header) = req.headers().get("cookie") {
-        if let Ok(cookie_str) = cookie_header.to_str() {
-            for cookie_pair in cookie_str.split(';') {
-                let cookie_pair = cookie_pair.trim();
-                if cookie_pair.starts_with("access_token=") {
-                    let token = cookie_pair.trim_start_matches("access_token=");
-                    return Some(Token(token.to_string()));
-                }
+    if let Some(cookie_header) = req.headers().get("cookie")
+        && let Ok(cookie_str) = cookie_header.to_str()
+    {
+        for cookie_pair in cookie_str.split(';') {
+            let cookie_pair = cookie_pair.trim();
+            if cookie_pair.starts_with("access_token=") {
+                let token = cookie_pair.trim_start_matches("access_token=");
+                return Some(Token(token.to_string()));
             }
         }
     }
@@ -47,18 +62,18 @@ fn get_token_from_request(req: &HttpRequest) -> Option<Token> {
 
 fn get_api_key_from_request(req: &HttpRequest) -> Option<PublicApiKey> {
     // Check X-API-Key header first
-    if let Some(v) = req.headers().get("X-API-Key") {
-        if let Ok(s) = v.to_str() {
-            return Some(PublicApiKey(s.to_string()));
-        }
+    if let Some(v) = req.headers().get("X-API-Key")
+        && let Ok(s) = v.to_str()
+    {
+        return Some(PublicApiKey(s.to_string()));
     }
     // Fall back to Authorization: Bearer slq_...
-    if let Some(v) = req.headers().get("Authorization") {
-        if let Ok(s) = v.to_str() {
-            let stripped = s.strip_prefix("Bearer ").unwrap_or(s);
-            if stripped.starts_with("slq_") {
-                return Some(PublicApiKey(stripped.to_string()));
-            }
+    if let Some(v) = req.headers().get("Authorization")
+        && let Ok(s) = v.to_str()
+    {
+        let stripped = s.strip_prefix("Bearer ").unwrap_or(s);
+        if stripped.starts_with("slq_") {
+            return Some(PublicApiKey(stripped.to_string()));
         }
     }
     None
@@ -97,12 +112,28 @@ async fn index_ws(
         .start(&req, payload)
 }
 
-async fn health() -> HttpResponse {
-    HttpResponse::Ok().json(serde_json::json!({"status": "ok"}))
+async fn health(markdown_cache: web::Data<MarkdownCache>) -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({"status": "ok", "markdown_cache": markdown_cache.stats()}))
+}
+
+/// Stylesheet for highlighted code blocks; `?theme=` picks another bundled theme.
+async fn highlight_css(
+    config: web::Data<MarkdownConfig>,
+    query: web::Query<HashMap<String, String>>,
+) -> HttpResponse {
+    let theme = query.get("theme").unwrap_or(&config.highlight_theme);
+    match theme_css(theme) {
+        Some(css) => HttpResponse::Ok()
+            .content_type("text/css; charset=utf-8")
+            .insert_header(("Cache-Control", "public, max-age=86400"))
+            .body(css),
+        None => HttpResponse::NotFound().body(format!("unknown theme {theme:?}")),
+    }
 }
 
 async fn public_index(
     schema: web::Data<PublicSchema>,
+    server: web::Data<ServerConfig>,
     req: HttpRequest,
     gql_request: GraphQLRequest,
 ) -> GraphQLResponse {
@@ -110,16 +141,28 @@ async fn public_index(
     if let Some(key) = get_api_key_from_request(&req) {
         request = request.data(key);
     }
+    if let Some(addr) = req.peer_addr() {
+        let header = |name| req.headers().get(name).and_then(|v| v.to_str().ok());
+        let ip = server.client_ip(addr.ip(), header("X-Forwarded-For"), header("Forwarded"));
+        request = request.data(ClientIp(ip.to_string()));
+    }
     schema.execute(request).await.into()
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+
     let env_filter = tracing_subscriber::EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"));
 
-    let log_format = std::env::var("LOG_FORMAT").unwrap_or_default();
-    if log_format.eq_ignore_ascii_case("json") {
+    if config.server.log_format == LogFormat::Json {
         tracing_subscriber::fmt()
             .with_env_filter(env_filter)
             .json()
@@ -130,38 +173,23 @@ async fn main() -> std::io::Result<()> {
             .init();
     }
 
-    let db = match set_up_db().await {
+    let db = match set_up_db(&config.database_url).await {
         Ok(db) => db,
         Err(err) => panic!("{}", err),
     };
 
-    let allowed_origins: Vec<String> = std::env::var("ALLOWED_ORIGINS")
-        .unwrap_or_else(|_| "http://localhost:8001".to_string())
-        .split(',')
-        .map(|s| s.trim().to_string())
-        .collect();
-
-    let public_cors_origins: Option<Vec<String>> = {
-        let raw = std::env::var("PUBLIC_CORS_ORIGINS")
-            .unwrap_or_else(|_| "*".to_string());
-        if raw.trim() == "*" {
-            None // wildcard
-        } else {
-            Some(raw.split(',').map(|s| s.trim().to_string()).collect())
-        }
-    };
+    let allowed_origins = config.server.allowed_origins.clone();
+    let public_cors_origins = config.server.public_cors_origins.clone();
 
-    let upload_dir = std::env::var("UPLOAD_DIR").unwrap_or_else(|_| "./uploads".to_string());
-    let storage_driver: Arc<StorageDriver> =
-        Arc::new(StorageDriver::Local(LocalStorageDriver::new(upload_dir)));
+    let storage_driver: Arc<StorageDriver> = Arc::new(StorageDriver::Local(
+        LocalStorageDriver::new(config.server.upload_dir.clone()),
+    ));
 
-    let markdown_cache = MarkdownCache::new();
-    let email_service = EmailService::from_env();
-    let single_user_mode = SingleUserMode(
-        std::env::var("SINGLE_USER_MODE")
-            .unwrap_or_default()
-            .eq_ignore_ascii_case("true"),
-    );
+    let markdown_cache = MarkdownCache::from_config(&config.markdown);
+    let markdown_config = config.markdown.clone();
+    let email_service = EmailService::new(&config.email);
+    let single_user_mode = SingleUserMode(config.server.single_user_mode);
+    let auth_config = config.auth.clone();
 
     let schema = Schema::build(
         QueryRoot::default(),
@@ -170,12 +198,50 @@ async fn main() -> std::io::Result<()> {
     )
     .data(db.clone())
     .data(markdown_cache.clone())
-    .data(email_service)
+    .data(email_service.clone())
     .data(single_user_mode)
+    .data(SecureCookies(config.server.secure_cookies))
+    .data(auth_config.clone())
     .data(storage_driver.clone())
+    .data(config.site.clone())
+    .data(config.feed_pings.clone())
     .finish();
 
-    let public_schema = build_public_schema(db.clone(), markdown_cache);
+    let public_schema = build_public_schema(
+        db.clone(),
+        markdown_cache.clone(),
+        storage_driver.clone(),
+        config.site.clone(),
+        config.spam.clone(),
+        config.pow.clone(),
+        config.newsletter.clone(),
+        Some(email_service.clone()),
+        &config.public_api,
+    );
+
+    actix_web::rt::spawn(newsletter::run_worker(
+        db.clone(),
+        storage_driver.clone(),
+        config.site.clone(),
+        config.newsletter.clone(),
+        email_service,
+    ));
+    actix_web::rt::spawn(webhooks::run_worker(db.clone(), config.webhooks.clone()));
+    actix_web::rt::spawn(pings::run_worker(db.clone(), config.feed_pings.queue.clone()));
+    actix_web::rt::spawn(pow::run_worker(db.clone(), config.pow.ttl));
+    actix_web::rt::spawn(webmention::run_worker(db.clone(), config.webmentions.clone()));
+    let activitypub_client = services::activitypub::client(&config.activitypub);
+    actix_web::rt::spawn(activitypub::run_worker(
+        db.clone(),
+        activitypub_client.clone(),
+        config.site.clone(),
+        config.activitypub.clone(),
+    ));
+    let newsletter_config = config.newsletter.clone();
+    let site_config = config.site.clone();
+    let feed_ping_config = config.feed_pings.clone();
+    let spam_config = config.spam.clone();
+    let server_config = config.server.clone();
 
     tracing::info!("GraphiQL IDE: http://localhost:8000");
 
@@ -210,12 +276,36 @@ async fn main() -> std::io::Result<()> {
             .app_data(web::Data::new(public_schema.clone()))
             .app_data(web::Data::new(db.clone()))
             .app_data(web::Data::new(storage_driver.clone()))
+            .app_data(web::Data::new(auth_config.clone()))
+            .app_data(web::Data::new(markdown_config.clone()))
+            .app_data(web::Data::new(markdown_cache.clone()))
+            .app_data(web::Data::new(newsletter_config.clone()))
+            .app_data(web::Data::new(site_config.clone()))
+            .app_data(web::Data::new(feed_ping_config.clone()))
+            .app_data(web::Data::new(spam_config.clone()))
+            .app_data(web::Data::new(server_config.clone()))
+            .app_data(web::Data::new(activitypub_client.clone()))
             .app_data(actix_multipart::form::MultipartFormConfig::default().total_limit(10 * 1024 * 1024))
             .service(
                 web::scope("/public")
                     .wrap(public_cors)
                     .service(web::resource("").guard(guard::Post()).to(public_index)),
             )
+            .service(web::resource("/newsletter/unsubscribe").guard(guard::Post()).to(newsletter::unsubscribe))
+            .service(web::resource("/webmention").guard(guard::Post()).to(webmention::receive))
+            .service(
+                web::resource("/micropub")
+                    .route(web::get().to(micropub::get))
+                    .route(web::post().to(micropub::post)),
+            )
+            .service(web::resource("/micropub/media").guard(guard::Post()).to(micropub::media))
+            .service(web::resource("/.well-known/webfinger").guard(guard::Get()).to(activitypub::webfinger))
+            .service(web::resource("/ap/users/{username}").guard(guard::Get()).to(activitypub::actor))
+            .service(web::resource("/ap/users/{username}/inbox").guard(guard::Post()).to(activitypub::inbox))
+            .service(web::resource("/ap/users/{username}/outbox").guard(guard::Get()).to(activitypub::outbox))
+            .service(web::resource("/ap/users/{username}/followers").guard(guard::Get()).to(activitypub::followers))
+            .service(web::resource("/ap/posts/{id}").guard(guard::Get()).to(activitypub::post))
+            .service(web::resource("/oembed").guard(guard::Get()).to(oembed::oembed))
             .service(
                 web::scope("")
                     .wrap(main_cors)
@@ -223,11 +313,12 @@ async fn main() -> std::io::Result<()> {
                     .service(web::resource("/").guard(guard::Post()).to(index))
                     .service(web::resource("/ws").to(index_ws))
                     .service(web::resource("/health").guard(guard::Get()).to(health))
+                    .service(web::resource("/highlight.css").guard(guard::Get()).to(highlight_css))
                     .service(web::resource("/upload").guard(guard::Post()).to(upload::upload))
                     .service(web::resource("/assets/{key:.*}").guard(guard::Get()).to(upload::serve_asset)),
             )
     })
-    .bind(std::env::var("BIND_ADDR").unwrap_or_else(|_| "127.0.0.1:8000".to_string()))?
+    .bind(&config.server.bind_addr)?
     .run()
     .await
 }
@@ -0,0 +1,134 @@
+use crate::upload::store_image;
+use actix_multipart::Multipart;
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse};
+use graphql::utilities::micropub::{authorize, form_to_json, parse_action, perform, query, MicropubError};
+use graphql::utilities::publishing::Publishing;
+use sea_orm::DatabaseConnection;
+use services::api_keys::Scope;
+use services::assets::StorageDriver;
+use services::config::{FeedPingConfig, SiteConfig};
+use std::sync::Arc;
+
+type Pairs = Vec<(String, String)>;
+
+fn error_response(e: MicropubError) -> HttpResponse {
+    if let MicropubError::Database(message) = &e {
+        tracing::error!(error = %message, "micropub request failed");
+    }
+    let status = StatusCode::from_u16(e.status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    HttpResponse::build(status).json(e.body())
+}
+
+/// The API key from `Authorization: Bearer`, or else from an
+/// `access_token` parameter.
+fn access_token<'a>(req: &'a HttpRequest, params: &'a Pairs) -> Option<&'a str> {
+    let header = req.headers().get("Authorization").and_then(|v| v.to_str().ok());
+    header
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .or_else(|| params.iter().find(|(k, _)| k == "access_token").map(|(_, v)| v.as_str()))
+        .map(str::trim)
+}
+
+/// `GET /micropub?q=…`: the endpoint's config, or a post's source.
+pub async fn get(
+    req: HttpRequest,
+    params: web::Query<Pairs>,
+    db: web::Data<DatabaseConnection>,
+    site: web::Data<SiteConfig>,
+) -> HttpResponse {
+    let user = match authorize(&db, access_token(&req, &params), None).await {
+        Ok(user) => user,
+        Err(e) => return error_response(e),
+    };
+    let param = |name: &str| params.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str());
+    let wanted: Vec<String> = params
+        .iter()
+        .filter(|(k, _)| k == "properties" || k == "properties[]")
+        .map(|(_, v)| v.clone())
+        .collect();
+    let Some(q) = param("q") else {
+        return error_response(MicropubError::InvalidRequest("q is missing".to_string()));
+    };
+    match query(&db, &site, &user, q, param("url"), &wanted).await {
+        Ok(answer) => HttpResponse::Ok().json(answer),
+        Err(e) => error_response(e),
+    }
+}
+
+/// `POST /micropub`: create, update or delete a post, from a form or JSON.
+pub async fn post(
+    req: HttpRequest,
+    body: web::Bytes,
+    db: web::Data<DatabaseConnection>,
+    driver: web::Data<Arc<StorageDriver>>,
+    site: web::Data<SiteConfig>,
+    feed_pings: web::Data<FeedPingConfig>,
+) -> HttpResponse {
+    let content_type = req.headers().get("Content-Type").and_then(|v| v.to_str().ok()).unwrap_or_default();
+    let (request, form) = if content_type.starts_with("application/json") {
+        match serde_json::from_slice(&body) {
+            Ok(request) => (request, Pairs::new()),
+            Err(e) => return error_response(MicropubError::InvalidRequest(format!("invalid JSON: {e}"))),
+        }
+    } else if content_type.starts_with("application/x-www-form-urlencoded") {
+        let form = std::str::from_utf8(&body).ok().and_then(|b| web::Query::<Pairs>::from_query(b).ok());
+        match form {
+            Some(form) => (form_to_json(&form), form.into_inner()),
+            None => return error_response(MicropubError::InvalidRequest("invalid form".to_string())),
+        }
+    } else {
+        let message = "send a form or JSON; upload files to the media endpoint first";
+        return error_response(MicropubError::InvalidRequest(message.to_string()));
+    };
+
+    let action = match parse_action(&request) {
+        Ok(action) => action,
+        Err(e) => return error_response(e),
+    };
+    let user = match authorize(&db, access_token(&req, &form), Some(action.scope())).await {
+        Ok(user) => user,
+        Err(e) => return error_response(e),
+    };
+    let publishing = Publishing {
+        db: &db,
+        driver: Some(&driver),
+        site: site.get_ref().clone(),
+        feed_pings: Some(&feed_pings),
+    };
+    let url = request.get("url").and_then(|u| u.as_str()).map(str::to_string);
+    match perform(&publishing, &user, action).await {
+        Ok(Some(post)) => {
+            let location = site.post_url(&post.id.to_string(), post.slug.as_deref());
+            // Updates that keep the post where it was have nothing to say
+            if url.as_deref() == Some(location.as_str()) {
+                return HttpResponse::NoContent().finish();
+            }
+            HttpResponse::Created().insert_header(("Location", location)).finish()
+        }
+        Ok(None) => HttpResponse::NoContent().finish(),
+        Err(e) => error_response(e),
+    }
+}
+
+/// `POST /micropub/media`: store the image in the `file` field, as
+/// `/upload` does, and point at it.
+pub async fn media(
+    req: HttpRequest,
+    mut multipart: Multipart,
+    db: web::Data<DatabaseConnection>,
+    driver: web::Data<Arc<StorageDriver>>,
+    site: web::Data<SiteConfig>,
+) -> HttpResponse {
+    let user = match authorize(&db, access_token(&req, &Pairs::new()), Some(Scope::Media)).await {
+        Ok(user) => user,
+        Err(e) => return error_response(e),
+    };
+    match store_image(&mut multipart, &db, &driver, user.id).await {
+        Ok(asset) => {
+            let url = asset["urls"]["original"].as_str().unwrap_or_default();
+            HttpResponse::Created().insert_header(("Location", site.absolute_api_url(url))).json(asset)
+        }
+        Err(response) => response,
+    }
+}
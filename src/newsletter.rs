@@ -0,0 +1,55 @@
+use actix_web::{web, HttpResponse};
+use graphql::utilities::newsletter::send_due;
+use repositories::SubscriberRepository;
+use sea_orm::DatabaseConnection;
+use services::assets::StorageDriver;
+use services::config::{NewsletterConfig, SiteConfig};
+use services::email::EmailService;
+use services::newsletter::verify_unsubscribe_token;
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// One-click unsubscribe (RFC 8058): mail clients POST to the URL in the
+/// `List-Unsubscribe` header with no further confirmation.
+pub async fn unsubscribe(
+    query: web::Query<HashMap<String, String>>,
+    db: web::Data<DatabaseConnection>,
+    config: web::Data<NewsletterConfig>,
+) -> HttpResponse {
+    let subscriber_id = query.get("subscriber").and_then(|s| s.parse::<Uuid>().ok());
+    let token = query.get("token");
+    let (Some(subscriber_id), Some(token)) = (subscriber_id, token) else {
+        return HttpResponse::BadRequest().json(serde_json::json!({"error": "missing subscriber or token"}));
+    };
+    if !verify_unsubscribe_token(&config.secret, subscriber_id, token) {
+        return HttpResponse::Forbidden().json(serde_json::json!({"error": "invalid unsubscribe link"}));
+    }
+    match SubscriberRepository::unsubscribe(&db, subscriber_id).await {
+        Ok(_) => HttpResponse::Ok().json(serde_json::json!({"unsubscribed": true})),
+        Err(e) => {
+            tracing::error!(%subscriber_id, error = %e, "unsubscribe failed");
+            HttpResponse::InternalServerError().json(serde_json::json!({"error": "unsubscribe failed"}))
+        }
+    }
+}
+
+/// Send queued newsletter emails every `config.poll_interval`, forever.
+pub async fn run_worker(
+    db: DatabaseConnection,
+    driver: Arc<StorageDriver>,
+    site: SiteConfig,
+    config: NewsletterConfig,
+    email_service: EmailService,
+) {
+    let mut interval = actix_web::rt::time::interval(config.poll_interval);
+    loop {
+        interval.tick().await;
+        let send = async |newsletter: &services::email::Newsletter<'_>| email_service.send_newsletter(newsletter).await;
+        match send_due(&db, &driver, &site, &config, send).await {
+            Ok(0) => {}
+            Ok(sent) => tracing::info!(sent, "newsletter emails sent"),
+            Err(e) => tracing::error!(error = %e, "newsletter worker failed"),
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use actix_web::{web, HttpResponse};
+use graphql::utilities::oembed::{lookup, MaxSize, OembedFormat, CACHE_AGE_SECS};
+use sea_orm::DatabaseConnection;
+use services::assets::StorageDriver;
+use services::config::SiteConfig;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// `GET /oembed?url=…`: how to embed a published post, as JSON or, with
+/// `format=xml`, XML. `maxwidth` and `maxheight` bound the card and the
+/// thumbnail. URLs that aren't a published post's page are `404 Not Found`,
+/// and other formats `501 Not Implemented`, as oEmbed asks.
+pub async fn oembed(
+    query: web::Query<HashMap<String, String>>,
+    db: web::Data<DatabaseConnection>,
+    driver: web::Data<Arc<StorageDriver>>,
+    site: web::Data<SiteConfig>,
+) -> HttpResponse {
+    let Some(url) = query.get("url") else {
+        return HttpResponse::BadRequest().json(serde_json::json!({"error": "missing url"}));
+    };
+    let Some(format) = OembedFormat::parse(query.get("format").map(String::as_str)) else {
+        return HttpResponse::NotImplemented().json(serde_json::json!({"error": "format must be json or xml"}));
+    };
+    let size = |name: &str| query.get(name).and_then(|v| v.parse().ok()).filter(|&v| v > 0);
+    let max = MaxSize { width: size("maxwidth"), height: size("maxheight") };
+
+    let oembed = match lookup(&db, &driver, &site, url, max).await {
+        Ok(Some(oembed)) => oembed,
+        Ok(None) => return HttpResponse::NotFound().json(serde_json::json!({"error": "no published post at url"})),
+        Err(e) => {
+            tracing::error!(%url, error = %e, "oembed lookup failed");
+            return HttpResponse::InternalServerError().json(serde_json::json!({"error": "oembed lookup failed"}));
+        }
+    };
+    let mut response = HttpResponse::Ok();
+    response
+        .content_type(format.content_type())
+        .insert_header(("Access-Control-Allow-Origin", "*"))
+        .insert_header(("Cache-Control", format!("public, max-age={CACHE_AGE_SECS}")));
+    match format {
+        OembedFormat::Json => response.json(oembed),
+        OembedFormat::Xml => response.body(oembed.to_xml()),
+    }
+}
@@ -0,0 +1,18 @@
+use graphql::utilities::pings::send_due;
+use sea_orm::DatabaseConnection;
+use services::config::QueueConfig;
+use services::pings::client;
+
+/// Send due feed pings every `config.poll_interval`, forever.
+pub async fn run_worker(db: DatabaseConnection, config: QueueConfig) {
+    let client = client(&config);
+    let mut interval = actix_web::rt::time::interval(config.poll_interval);
+    loop {
+        interval.tick().await;
+        match send_due(&db, &client, &config).await {
+            Ok(0) => {}
+            Ok(sent) => tracing::info!(sent, "feed pings sent"),
+            Err(e) => tracing::error!(error = %e, "feed ping worker failed"),
+        }
+    }
+}
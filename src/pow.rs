@@ -0,0 +1,17 @@
+use repositories::PowRepository;
+use sea_orm::DatabaseConnection;
+use std::time::Duration;
+
+/// Forget spent proof-of-work challenges once they have expired, every
+/// `ttl`, forever.
+pub async fn run_worker(db: DatabaseConnection, ttl: Duration) {
+    let mut interval = actix_web::rt::time::interval(ttl);
+    loop {
+        interval.tick().await;
+        match PowRepository::prune_spent(&db).await {
+            Ok(0) => {}
+            Ok(pruned) => tracing::debug!(pruned, "spent challenges pruned"),
+            Err(e) => tracing::error!(error = %e, "pruning spent challenges failed"),
+        }
+    }
+}
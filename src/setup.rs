@@ -2,8 +2,7 @@
 
 use sea_orm::*;
 
-pub(super) async fn set_up_db() -> Result<DatabaseConnection, DbErr> {
-    let url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+pub(super) async fn set_up_db(url: &str) -> Result<DatabaseConnection, DbErr> {
     let mut opts = ConnectOptions::new(url);
     opts.sqlx_logging(true)
         .sqlx_logging_level(tracing::log::LevelFilter::Debug);
@@ -1,25 +1,28 @@
 use actix_multipart::Multipart;
 use actix_web::{web, HttpRequest, HttpResponse};
 use futures::StreamExt;
+use graphql::utilities::webhooks;
 use sea_orm::DatabaseConnection;
 use services::assets::StorageDriver;
 use services::authentication::{authenticator::get_user, token::Token};
+use services::config::AuthConfig;
+use services::webhooks::WebhookEvent;
 use std::sync::Arc;
 use uuid::Uuid;
 
 fn get_token(req: &HttpRequest) -> Option<Token> {
-    if let Some(v) = req.headers().get("Authorization") {
-        if let Ok(s) = v.to_str() {
-            return Some(Token(s.to_string()));
-        }
+    if let Some(v) = req.headers().get("Authorization")
+        && let Ok(s) = v.to_str()
+    {
+        return Some(Token(s.to_string()));
     }
-    if let Some(v) = req.headers().get("cookie") {
-        if let Ok(s) = v.to_str() {
-            for pair in s.split(';') {
-                let pair = pair.trim();
-                if let Some(val) = pair.strip_prefix("access_token=") {
-                    return Some(Token(val.to_string()));
-                }
+    if let Some(v) = req.headers().get("cookie")
+        && let Ok(s) = v.to_str()
+    {
+        for pair in s.split(';') {
+            let pair = pair.trim();
+            if let Some(val) = pair.strip_prefix("access_token=") {
+                return Some(Token(val.to_string()));
             }
         }
     }
@@ -31,22 +34,38 @@ pub async fn upload(
     mut multipart: Multipart,
     db: web::Data<DatabaseConnection>,
     driver: web::Data<Arc<StorageDriver>>,
+    auth: web::Data<AuthConfig>,
 ) -> HttpResponse {
     let token = match get_token(&req) {
         Some(t) => t,
         None => return HttpResponse::Unauthorized().json(serde_json::json!({"error": "missing token"})),
     };
 
-    let user = match get_user(db.get_ref(), &token).await {
+    let user = match get_user(db.get_ref(), auth.get_ref(), &token).await {
         Ok(u) => u,
         Err(e) => return HttpResponse::Unauthorized().json(serde_json::json!({"error": e.to_string()})),
     };
 
+    match store_image(&mut multipart, &db, &driver, user.id).await {
+        Ok(body) => HttpResponse::Ok().json(body),
+        Err(response) => response,
+    }
+}
+
+/// Process and store the image in the `file` field of `multipart` as a new
+/// asset of `user_id`'s. Returns the asset as the upload endpoint describes
+/// it, or the response to refuse it with.
+pub(crate) async fn store_image(
+    multipart: &mut Multipart,
+    db: &DatabaseConnection,
+    driver: &Arc<StorageDriver>,
+    user_id: Uuid,
+) -> Result<serde_json::Value, HttpResponse> {
     // Read multipart field "file"
     while let Some(field) = multipart.next().await {
         let mut field = match field {
             Ok(f) => f,
-            Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({"error": e.to_string()})),
+            Err(e) => return Err(HttpResponse::BadRequest().json(serde_json::json!({"error": e.to_string()}))),
         };
 
         let content_disposition = field.content_disposition();
@@ -68,8 +87,8 @@ pub async fn upload(
             "image/jpeg" | "image/png" | "image/gif" | "image/webp" | "image/avif"
         );
         if !allowed_type {
-            return HttpResponse::UnprocessableEntity()
-                .json(serde_json::json!({"error": "only jpeg/png/gif/webp/avif accepted"}));
+            return Err(HttpResponse::UnprocessableEntity()
+                .json(serde_json::json!({"error": "only jpeg/png/gif/webp/avif accepted"})));
         }
 
         let original_filename = content_disposition
@@ -90,33 +109,35 @@ pub async fn upload(
             match chunk {
                 Ok(data) => {
                     if bytes.len() + data.len() > MAX_BYTES {
-                        return HttpResponse::PayloadTooLarge()
-                            .json(serde_json::json!({"error": "image exceeds 10 MiB limit"}));
+                        return Err(HttpResponse::PayloadTooLarge()
+                            .json(serde_json::json!({"error": "image exceeds 10 MiB limit"})));
                     }
                     bytes.extend_from_slice(&data);
                 }
-                Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({"error": e.to_string()})),
+                Err(e) => return Err(HttpResponse::BadRequest().json(serde_json::json!({"error": e.to_string()}))),
             }
         }
 
         let asset_id = Uuid::new_v4();
-        let original_size = match services::assets::process_and_store(&bytes, asset_id, &driver).await {
+        let processed = match services::assets::process_and_store(&bytes, asset_id, driver).await {
             Ok(s) => s,
-            Err(e) => return HttpResponse::UnprocessableEntity().json(serde_json::json!({"error": e})),
+            Err(e) => return Err(HttpResponse::UnprocessableEntity().json(serde_json::json!({"error": e}))),
         };
 
         let asset = match repositories::AssetRepository::create(
-            db.get_ref(),
+            db,
             asset_id,
-            user.id,
+            user_id,
             original_filename,
             content_type.clone(),
-            original_size as i64,
+            processed.size_bytes as i64,
+            processed.width as i32,
+            processed.height as i32,
         )
         .await
         {
             Ok(a) => a,
-            Err(e) => return HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()})),
+            Err(e) => return Err(HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()}))),
         };
 
         let base = format!("{asset_id}");
@@ -128,17 +149,23 @@ pub async fn upload(
             "original": driver.url(&format!("{base}/original.webp")),
         });
 
-        return HttpResponse::Ok().json(serde_json::json!({
+        let body = serde_json::json!({
             "id": asset.id,
             "originalFilename": asset.original_filename,
             "mimeType": asset.mime_type,
             "sizeBytes": asset.size_bytes,
+            "width": asset.width,
+            "height": asset.height,
             "urls": urls,
             "createdAt": asset.created_at,
-        }));
+        });
+        let data = serde_json::json!({ "asset": body.clone() });
+        webhooks::emit(db, user_id, WebhookEvent::AssetCreated, data).await;
+
+        return Ok(body);
     }
 
-    HttpResponse::BadRequest().json(serde_json::json!({"error": "no file field found"}))
+    Err(HttpResponse::BadRequest().json(serde_json::json!({"error": "no file field found"})))
 }
 
 pub async fn serve_asset(
@@ -146,9 +173,11 @@ pub async fn serve_asset(
     driver: web::Data<Arc<StorageDriver>>,
 ) -> HttpResponse {
     let key = path.into_inner();
+    // Uploads are stored as WebP; only generated share cards are PNG.
+    let content_type = if key.ends_with(".png") { "image/png" } else { "image/webp" };
     match driver.get(&key).await {
         Ok(bytes) => HttpResponse::Ok()
-            .content_type("image/webp")
+            .content_type(content_type)
             .insert_header(("X-Content-Type-Options", "nosniff"))
             .body(bytes),
         Err(_) => HttpResponse::NotFound().finish(),
@@ -0,0 +1,18 @@
+use graphql::utilities::webhooks::deliver_due;
+use sea_orm::DatabaseConnection;
+use services::config::QueueConfig;
+use services::webhooks::client;
+
+/// Make due webhook deliveries every `config.poll_interval`, forever.
+pub async fn run_worker(db: DatabaseConnection, config: QueueConfig) {
+    let client = client(&config);
+    let mut interval = actix_web::rt::time::interval(config.poll_interval);
+    loop {
+        interval.tick().await;
+        match deliver_due(&db, &client, &config).await {
+            Ok(0) => {}
+            Ok(delivered) => tracing::info!(delivered, "webhook deliveries made"),
+            Err(e) => tracing::error!(error = %e, "webhook worker failed"),
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use actix_web::{web, HttpResponse};
+use graphql::utilities::webmentions::{receive as record, send_due, verify_due, ReceiveError};
+use sea_orm::DatabaseConnection;
+use services::config::{QueueConfig, SiteConfig};
+use std::collections::HashMap;
+
+/// The Webmention endpoint: takes a form-encoded `source` and `target`,
+/// checks what can be checked without fetching anything and queues the
+/// mention for verification. Sources are fetched later by the worker, so a
+/// valid request is only ever `202 Accepted`.
+pub async fn receive(
+    form: web::Form<HashMap<String, String>>,
+    db: web::Data<DatabaseConnection>,
+    site: web::Data<SiteConfig>,
+) -> HttpResponse {
+    let (Some(source), Some(target)) = (form.get("source"), form.get("target")) else {
+        return HttpResponse::BadRequest().json(serde_json::json!({"error": "missing source or target"}));
+    };
+    match record(&db, &site, source, target).await {
+        Ok(mention) => HttpResponse::Accepted().json(serde_json::json!({"id": mention.id, "status": "queued"})),
+        Err(ReceiveError::Invalid(e)) => HttpResponse::BadRequest().json(serde_json::json!({"error": e})),
+        Err(ReceiveError::Database(e)) => {
+            tracing::error!(%source, %target, error = %e, "recording webmention failed");
+            HttpResponse::InternalServerError().json(serde_json::json!({"error": "recording webmention failed"}))
+        }
+    }
+}
+
+/// Send due Webmentions and verify received ones every
+/// `config.poll_interval`, forever.
+pub async fn run_worker(db: DatabaseConnection, config: QueueConfig) {
+    let client = services::webmention::client(&config);
+    let mut interval = actix_web::rt::time::interval(config.poll_interval);
+    loop {
+        interval.tick().await;
+        match send_due(&db, &client, &config).await {
+            Ok(0) => {}
+            Ok(sent) => tracing::info!(sent, "webmentions sent"),
+            Err(e) => tracing::error!(error = %e, "webmention sending failed"),
+        }
+        match verify_due(&db, &client, &config).await {
+            Ok(0) => {}
+            Ok(verified) => tracing::info!(verified, "webmentions verified"),
+            Err(e) => tracing::error!(error = %e, "webmention verification failed"),
+        }
+    }
+}
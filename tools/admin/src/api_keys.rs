@@ -0,0 +1,48 @@
+use crate::users::find_user;
+use crate::Context;
+use clap::Subcommand;
+use services::api_keys;
+use uuid::Uuid;
+
+#[derive(Subcommand)]
+pub enum ApiKeysCommand {
+    /// List a user's API keys
+    List { email: String },
+    /// Create an API key; the raw key is printed once
+    Create { email: String, label: String },
+    /// Revoke one of a user's API keys
+    Revoke { email: String, key_id: Uuid },
+}
+
+pub async fn run(ctx: &Context, cmd: ApiKeysCommand) -> Result<(), String> {
+    match cmd {
+        ApiKeysCommand::List { email } => {
+            let user = find_user(ctx, &email).await?;
+            let keys = api_keys::list(&ctx.db, user.id).await.map_err(|e| e.to_string())?;
+            for k in keys {
+                let last_used = k
+                    .last_used_at
+                    .map(|t| t.and_utc().to_rfc3339())
+                    .unwrap_or_else(|| "never".to_string());
+                println!("{}\t{}\tlast used: {}", k.id, k.label, last_used);
+            }
+        }
+        ApiKeysCommand::Create { email, label } => {
+            let user = find_user(ctx, &email).await?;
+            let (raw, hash) = api_keys::generate();
+            let key = api_keys::create(&ctx.db, user.id, label, hash)
+                .await
+                .map_err(|e| e.to_string())?;
+            println!("created key {}", key.id);
+            println!("{raw}");
+        }
+        ApiKeysCommand::Revoke { email, key_id } => {
+            let user = find_user(ctx, &email).await?;
+            api_keys::revoke(&ctx.db, key_id, user.id)
+                .await
+                .map_err(|e| e.to_string())?;
+            println!("revoked key {key_id}");
+        }
+    }
+    Ok(())
+}
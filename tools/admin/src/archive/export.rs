@@ -0,0 +1,75 @@
+use super::{front_matter, AssetEntry, Manifest, PostEntry, FORMAT, MANIFEST_PATH, VERSION};
+use crate::users::find_user;
+use crate::Context;
+use repositories::{AssetRepository, PostRepository};
+use services::assets::{LocalStorageDriver, StorageDriver};
+use std::io::Write;
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+fn zip_err(e: impl std::fmt::Display) -> String {
+    format!("write archive: {e}")
+}
+
+pub async fn export(ctx: &Context, email: &str, output: &Path) -> Result<(), String> {
+    let user = find_user(ctx, email).await?;
+    let driver = StorageDriver::Local(LocalStorageDriver::new(&ctx.config.server.upload_dir));
+    let posts = PostRepository::all_for_user(&ctx.db, user.id).await?;
+    let assets = AssetRepository::all_for_user(&ctx.db, user.id).await?;
+
+    let file = std::fs::File::create(output)
+        .map_err(|e| format!("create {}: {e}", output.display()))?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default();
+
+    let mut manifest = Manifest {
+        format: FORMAT.to_string(),
+        version: VERSION,
+        exported_at: chrono::Utc::now(),
+        posts: Vec::with_capacity(posts.len()),
+        assets: Vec::with_capacity(assets.len()),
+    };
+
+    for post in &posts {
+        let name = post.slug.clone().unwrap_or_else(|| post.id.to_string());
+        let path = format!("posts/{name}.md");
+        zip.start_file(path.as_str(), options).map_err(zip_err)?;
+        zip.write_all(front_matter::render_post(post).as_bytes()).map_err(zip_err)?;
+        manifest.posts.push(PostEntry { id: post.id, path });
+    }
+
+    for asset in &assets {
+        let key = format!("{}/original.webp", asset.id);
+        let data = match driver.get(&key).await {
+            Ok(d) => d,
+            Err(e) => {
+                eprintln!("skipping asset {}: {e}", asset.id);
+                continue;
+            }
+        };
+        let path = format!("assets/{key}");
+        zip.start_file(path.as_str(), options).map_err(zip_err)?;
+        zip.write_all(&data).map_err(zip_err)?;
+        manifest.assets.push(AssetEntry {
+            id: asset.id,
+            original_filename: asset.original_filename.clone(),
+            mime_type: asset.mime_type.clone(),
+            size_bytes: asset.size_bytes,
+            path,
+        });
+    }
+
+    zip.start_file(MANIFEST_PATH, options).map_err(zip_err)?;
+    let json = serde_json::to_vec_pretty(&manifest).map_err(zip_err)?;
+    zip.write_all(&json).map_err(zip_err)?;
+    zip.finish().map_err(zip_err)?;
+
+    println!(
+        "exported {} post(s) and {} asset(s) to {}",
+        manifest.posts.len(),
+        manifest.assets.len(),
+        output.display()
+    );
+    Ok(())
+}
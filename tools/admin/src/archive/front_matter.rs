@@ -0,0 +1,179 @@
+use chrono::{DateTime, Utc};
+use graphql::types::seo::SeoOverrides;
+use models::posts::Model as Post;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// YAML front matter written at the top of every exported post.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FrontMatter {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Uuid>,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slug: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cover_image: Option<String>,
+    #[serde(default)]
+    pub published: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub published_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+    /// Only written when math rendering is turned off for the post.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub math: Option<bool>,
+    /// Share-metadata overrides, when the post has any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seo: Option<SeoOverrides>,
+    /// Posts have no tags yet; kept so archives from other tools round-trip.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl From<&Post> for FrontMatter {
+    fn from(post: &Post) -> Self {
+        Self {
+            id: Some(post.id),
+            title: post.title.clone(),
+            slug: post.slug.clone(),
+            description: post.description.clone(),
+            cover_image: post.cover_image.clone(),
+            published: post.is_published,
+            published_at: post.first_published_at.map(|t| t.and_utc()),
+            created_at: Some(post.created_at.and_utc()),
+            updated_at: Some(post.updated_at.and_utc()),
+            math: (!post.render_math).then_some(false),
+            seo: post.seo.is_some().then(|| SeoOverrides::from_json(post.seo.as_ref())),
+            tags: Vec::new(),
+        }
+    }
+}
+
+pub fn render(front: &FrontMatter, body: &str) -> String {
+    let yaml = serde_yaml_ng::to_string(front).unwrap();
+    format!("---\n{yaml}---\n\n{body}\n")
+}
+
+pub fn render_post(post: &Post) -> String {
+    render(&FrontMatter::from(post), post.markdown_content.as_deref().unwrap_or(""))
+}
+
+/// Split `---\n<yaml>\n---\n<body>` into its parts. Text without front matter
+/// is returned as the body.
+pub fn split(text: &str) -> (Option<&str>, &str) {
+    split_with(text, "---")
+}
+
+/// Like `split` with a custom fence, e.g. `+++` for Hugo's TOML front matter.
+pub fn split_with<'a>(text: &'a str, fence: &str) -> (Option<&'a str>, &'a str) {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let Some(rest) = text
+        .strip_prefix(fence)
+        .and_then(|r| r.strip_prefix('\n').or_else(|| r.strip_prefix("\r\n")))
+    else {
+        return (None, text);
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == fence {
+            let body = &rest[offset + line.len()..];
+            return (Some(&rest[..offset]), body.trim_start_matches(['\r', '\n']));
+        }
+        offset += line.len();
+    }
+    (None, text)
+}
+
+pub fn parse(text: &str) -> Result<(FrontMatter, String), String> {
+    match split(text) {
+        (Some(yaml), body) => {
+            let front: FrontMatter =
+                serde_yaml_ng::from_str(yaml).map_err(|e| format!("front matter: {e}"))?;
+            // `render` always ends the file with a newline after the body.
+            Ok((front, body.strip_suffix('\n').unwrap_or(body).to_string()))
+        }
+        (None, _) => Err("missing front matter".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post() -> Post {
+        let now = DateTime::from_timestamp(0, 0).unwrap().naive_utc();
+        Post {
+            id: Uuid::nil(),
+            title: "Say \"hi\": a post".to_string(),
+            markdown_content: Some("# Body".to_string()),
+            description: None,
+            slug: Some("say-hi".to_string()),
+            cover_image: None,
+            user_id: Uuid::nil(),
+            is_published: true,
+            render_math: true,
+            rendered_markdown: None,
+            seo: None,
+            preview_image: None,
+            first_published_at: Some(now),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn render_then_parse_round_trips() {
+        let text = render_post(&post());
+        let (front, body) = parse(&text).unwrap();
+        assert_eq!(front, FrontMatter::from(&post()));
+        assert_eq!(body, "# Body");
+    }
+
+    #[test]
+    fn render_omits_empty_fields() {
+        let text = render_post(&post());
+        assert!(text.starts_with("---\n"));
+        assert!(!text.contains("description:"));
+        assert!(!text.contains("tags:"));
+        assert!(!text.contains("math:"));
+        assert!(!text.contains("seo:"));
+    }
+
+    #[test]
+    fn render_keeps_disabled_math() {
+        let post = Post { render_math: false, ..post() };
+        let (front, _) = parse(&render_post(&post)).unwrap();
+        assert_eq!(front.math, Some(false));
+    }
+
+    #[test]
+    fn render_keeps_seo_overrides() {
+        let post = Post { seo: Some(serde_json::json!({"title": "Share me", "noindex": true})), ..post() };
+        let text = render_post(&post);
+        assert!(text.contains("seo:\n  title: Share me\n  noindex: true\n"), "{text}");
+        let (front, _) = parse(&text).unwrap();
+        assert_eq!(front.seo.and_then(|s| s.title).as_deref(), Some("Share me"));
+    }
+
+    #[test]
+    fn split_without_front_matter_returns_body() {
+        assert_eq!(split("# Just text\n"), (None, "# Just text\n"));
+    }
+
+    #[test]
+    fn split_unterminated_front_matter_returns_body() {
+        assert_eq!(split("---\ntitle: x\n"), (None, "---\ntitle: x\n"));
+    }
+
+    #[test]
+    fn parse_accepts_tags() {
+        let (front, body) = parse("---\ntitle: T\ntags: [a, b]\n---\nbody").unwrap();
+        assert_eq!(front.tags, vec!["a", "b"]);
+        assert_eq!(body, "body");
+    }
+}
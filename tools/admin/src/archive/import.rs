@@ -0,0 +1,195 @@
+use super::{front_matter, Manifest, FORMAT, MANIFEST_PATH, VERSION};
+use crate::assets::store_image;
+use crate::users::find_user;
+use crate::Context;
+use graphql::types::seo::SeoOverrides;
+use repositories::PostRepository;
+use services::assets::{LocalStorageDriver, StorageDriver};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use uuid::Uuid;
+use zip::ZipArchive;
+
+fn read_entry(zip: &mut ZipArchive<File>, path: &str) -> Result<Vec<u8>, String> {
+    let mut entry = zip.by_name(path).map_err(|e| format!("{path}: {e}"))?;
+    let mut buf = Vec::with_capacity(entry.size() as usize);
+    entry.read_to_end(&mut buf).map_err(|e| format!("{path}: {e}"))?;
+    Ok(buf)
+}
+
+/// Same suffix scheme as `PostRepository::create_post`: `slug`, `slug-2`, ...
+pub(crate) fn resolve_slug(base: &str, taken: &HashSet<String>) -> String {
+    if !taken.contains(base) {
+        return base.to_string();
+    }
+    (2..)
+        .map(|n| format!("{base}-{n}"))
+        .find(|candidate| !taken.contains(candidate))
+        .unwrap()
+}
+
+/// Point references to exported asset IDs at their newly assigned IDs.
+pub(crate) fn remap_ids(text: &str, ids: &HashMap<Uuid, Uuid>) -> String {
+    ids.iter()
+        .fold(text.to_string(), |acc, (old, new)| acc.replace(&old.to_string(), &new.to_string()))
+}
+
+struct PlannedPost {
+    title: String,
+    body: String,
+    front: front_matter::FrontMatter,
+    slug: Option<String>,
+}
+
+pub async fn import(ctx: &Context, email: &str, input: &Path, dry_run: bool) -> Result<(), String> {
+    let user = find_user(ctx, email).await?;
+    let file = File::open(input).map_err(|e| format!("open {}: {e}", input.display()))?;
+    let mut zip = ZipArchive::new(file).map_err(|e| format!("read archive: {e}"))?;
+
+    let manifest: Manifest = serde_json::from_slice(&read_entry(&mut zip, MANIFEST_PATH)?)
+        .map_err(|e| format!("{MANIFEST_PATH}: {e}"))?;
+    if manifest.format != FORMAT || manifest.version > VERSION {
+        return Err(format!(
+            "unsupported archive {} v{} (expected {FORMAT} v{VERSION})",
+            manifest.format, manifest.version
+        ));
+    }
+
+    let asset_ids: HashMap<Uuid, Uuid> =
+        manifest.assets.iter().map(|a| (a.id, Uuid::new_v4())).collect();
+
+    let mut taken: HashSet<String> = PostRepository::all_for_user(&ctx.db, user.id)
+        .await?
+        .into_iter()
+        .filter_map(|p| p.slug)
+        .collect();
+
+    let mut planned = Vec::with_capacity(manifest.posts.len());
+    for entry in &manifest.posts {
+        let text = String::from_utf8(read_entry(&mut zip, &entry.path)?)
+            .map_err(|_| format!("{}: not valid UTF-8", entry.path))?;
+        let (mut front, body) = front_matter::parse(&text).map_err(|e| format!("{}: {e}", entry.path))?;
+        front.cover_image = front.cover_image.map(|c| remap_ids(&c, &asset_ids));
+        if let Some(seo) = &mut front.seo {
+            seo.image = seo.image.as_ref().map(|i| remap_ids(i, &asset_ids));
+            seo.validate().map_err(|e| format!("{}: {e}", entry.path))?;
+        }
+
+        let slug = front.slug.as_deref().filter(|s| !s.is_empty()).map(|wanted| {
+            let slug = resolve_slug(wanted, &taken);
+            if slug != wanted {
+                println!("slug conflict: {wanted} -> {slug}");
+            }
+            taken.insert(slug.clone());
+            slug
+        });
+        planned.push(PlannedPost {
+            title: front.title.clone(),
+            body: remap_ids(&body, &asset_ids),
+            front,
+            slug,
+        });
+    }
+
+    if dry_run {
+        for post in &planned {
+            let state = if post.front.published { "published" } else { "draft" };
+            println!("would import post {:?} ({state}, slug: {})", post.title, post.slug.as_deref().unwrap_or("-"));
+        }
+        println!(
+            "dry run: {} post(s) and {} asset(s) would be imported for {email}",
+            planned.len(),
+            manifest.assets.len()
+        );
+        return Ok(());
+    }
+
+    let driver = StorageDriver::Local(LocalStorageDriver::new(&ctx.config.server.upload_dir));
+    for asset in &manifest.assets {
+        let new_id = asset_ids[&asset.id];
+        let data = read_entry(&mut zip, &asset.path)?;
+        store_image(
+            ctx,
+            &driver,
+            new_id,
+            user.id,
+            &data,
+            asset.original_filename.clone(),
+            asset.mime_type.clone(),
+        )
+        .await
+        .map_err(|e| format!("asset {}: {e}", asset.id))?;
+        println!("asset {} -> {new_id}", asset.id);
+    }
+
+    for (entry, post) in manifest.posts.iter().zip(planned) {
+        let front = post.front;
+        let created = PostRepository::create_post(
+            &ctx.db,
+            user.id,
+            post.title,
+            post.body,
+            front.published,
+            front.description,
+            post.slug,
+            front.cover_image,
+        )
+        .await
+        .map_err(|e| format!("{}: {e}", entry.path))?;
+
+        if front.math == Some(false) {
+            PostRepository::set_render_math(&ctx.db, user.id, created.id, false)
+                .await
+                .map_err(|e| format!("{}: {e}", entry.path))?;
+        }
+        if let Some(seo) = front.seo.as_ref().and_then(SeoOverrides::to_json) {
+            PostRepository::set_seo(&ctx.db, user.id, created.id, Some(seo))
+                .await
+                .map_err(|e| format!("{}: {e}", entry.path))?;
+        }
+
+        let created_at = front.created_at.map(|t| t.naive_utc()).unwrap_or(created.created_at);
+        let updated_at = front.updated_at.map(|t| t.naive_utc()).unwrap_or(created.updated_at);
+        let first_published_at = front
+            .published_at
+            .map(|t| t.naive_utc())
+            .or(created.first_published_at);
+        PostRepository::set_timestamps(&ctx.db, created.id, first_published_at, created_at, updated_at)
+            .await
+            .map_err(|e| format!("{}: {e}", entry.path))?;
+        println!("post {} -> {}", entry.id, created.id);
+    }
+
+    println!(
+        "imported {} post(s) and {} asset(s) for {email}",
+        manifest.posts.len(),
+        manifest.assets.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_slug_keeps_free_slug() {
+        assert_eq!(resolve_slug("hello", &HashSet::new()), "hello");
+    }
+
+    #[test]
+    fn resolve_slug_appends_first_free_suffix() {
+        let taken = HashSet::from(["hello".to_string(), "hello-2".to_string()]);
+        assert_eq!(resolve_slug("hello", &taken), "hello-3");
+    }
+
+    #[test]
+    fn remap_ids_rewrites_asset_urls() {
+        let (old, new) = (Uuid::new_v4(), Uuid::new_v4());
+        let ids = HashMap::from([(old, new)]);
+        let text = format!("![x](/assets/{old}/medium.webp)");
+        assert_eq!(remap_ids(&text, &ids), format!("![x](/assets/{new}/medium.webp)"));
+    }
+}
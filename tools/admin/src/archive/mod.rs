@@ -0,0 +1,42 @@
+//! Portable blog archive: a zip holding `manifest.json`, one Markdown file per
+//! post under `posts/` and each asset's stored original under `assets/`.
+
+mod export;
+pub mod front_matter;
+mod import;
+
+pub use export::export;
+pub use import::import;
+pub(crate) use import::resolve_slug;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+pub const FORMAT: &str = "soliloquio-archive";
+pub const VERSION: u32 = 1;
+pub const MANIFEST_PATH: &str = "manifest.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub posts: Vec<PostEntry>,
+    pub assets: Vec<AssetEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PostEntry {
+    pub id: Uuid,
+    pub path: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AssetEntry {
+    pub id: Uuid,
+    pub original_filename: String,
+    pub mime_type: String,
+    pub size_bytes: i64,
+    pub path: String,
+}
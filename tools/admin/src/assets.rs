@@ -0,0 +1,103 @@
+use crate::Context;
+use clap::Subcommand;
+use repositories::AssetRepository;
+use services::assets::{process_and_store, LocalStorageDriver, StorageDriver, PREVIEW_PREFIX};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+#[derive(Subcommand)]
+pub enum AssetsCommand {
+    /// Regenerate the resized variants of assets from their stored original,
+    /// recording the original's dimensions if they are missing
+    Reprocess {
+        #[arg(required_unless_present = "all")]
+        ids: Vec<Uuid>,
+        #[arg(long, conflicts_with = "ids")]
+        all: bool,
+    },
+    /// Delete stored files that no asset row refers to
+    Gc {
+        /// Only report what would be deleted
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+/// Run an image through the upload pipeline and record it as `user_id`'s asset.
+pub(crate) async fn store_image(
+    ctx: &Context,
+    driver: &StorageDriver,
+    id: Uuid,
+    user_id: Uuid,
+    data: &[u8],
+    original_filename: String,
+    mime_type: String,
+) -> Result<(), String> {
+    let image = process_and_store(data, id, driver).await?;
+    let (width, height) = (image.width as i32, image.height as i32);
+    AssetRepository::create(&ctx.db, id, user_id, original_filename, mime_type, image.size_bytes as i64, width, height)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub async fn run(ctx: &Context, cmd: AssetsCommand) -> Result<(), String> {
+    let driver = StorageDriver::Local(LocalStorageDriver::new(&ctx.config.server.upload_dir));
+    match cmd {
+        AssetsCommand::Reprocess { ids, all } => {
+            let ids = if all { AssetRepository::all_ids(&ctx.db).await? } else { ids };
+            let mut failed = 0;
+            for id in ids {
+                if AssetRepository::find_by_id(&ctx.db, id).await?.is_none() {
+                    eprintln!("{id}: no such asset");
+                    failed += 1;
+                    continue;
+                }
+                let original = match driver.get(&format!("{id}/original.webp")).await {
+                    Ok(data) => data,
+                    Err(e) => {
+                        eprintln!("{id}: {e}");
+                        failed += 1;
+                        continue;
+                    }
+                };
+                match process_and_store(&original, id, &driver).await {
+                    Ok(image) => {
+                        AssetRepository::set_dimensions(&ctx.db, id, image.width as i32, image.height as i32).await?;
+                        println!("{id}: reprocessed");
+                    }
+                    Err(e) => {
+                        eprintln!("{id}: {e}");
+                        failed += 1;
+                    }
+                }
+            }
+            if failed > 0 {
+                return Err(format!("{failed} asset(s) failed"));
+            }
+        }
+        AssetsCommand::Gc { dry_run } => {
+            let known: HashSet<String> = AssetRepository::all_ids(&ctx.db)
+                .await?
+                .into_iter()
+                .map(|id| id.to_string())
+                .collect();
+            let prefixes = driver.list_prefixes().await.map_err(|e| e.to_string())?;
+            let mut removed = 0;
+            // Share cards live under their own prefix and go with their posts.
+            let orphaned = prefixes.iter().filter(|p| !known.contains(*p) && *p != PREVIEW_PREFIX);
+            for prefix in orphaned {
+                if dry_run {
+                    println!("would remove {prefix}");
+                } else {
+                    driver.delete_dir(prefix).await.map_err(|e| e.to_string())?;
+                    println!("removed {prefix}");
+                }
+                removed += 1;
+            }
+            let missing = known.iter().filter(|id| !prefixes.contains(id)).count();
+            eprintln!("{removed} orphaned director(ies); {missing} asset row(s) without files");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,97 @@
+use super::{parse_date, ImportedPost};
+use serde_json::Value;
+
+fn str_field<'a>(post: &'a Value, key: &str) -> Option<&'a str> {
+    post.get(key).and_then(Value::as_str).filter(|s| !s.trim().is_empty())
+}
+
+/// Parse a Ghost export. Accepts both the `{"db": [{"data": ...}]}` wrapper
+/// written by Ghost Admin and a bare `{"data": ...}` object.
+pub fn parse(json: &str) -> Result<Vec<ImportedPost>, String> {
+    let root: Value = serde_json::from_str(json).map_err(|e| format!("Ghost export: {e}"))?;
+    let data = root
+        .pointer("/db/0/data")
+        .or_else(|| root.get("data"))
+        .ok_or("Ghost export: missing data")?;
+    let posts = data
+        .get("posts")
+        .and_then(Value::as_array)
+        .ok_or("Ghost export: missing posts")?;
+
+    Ok(posts
+        .iter()
+        .filter(|p| {
+            let is_page = p.get("type").and_then(Value::as_str) == Some("page")
+                || p.get("page").and_then(Value::as_bool) == Some(true)
+                || p.get("page").and_then(Value::as_i64) == Some(1);
+            !is_page
+        })
+        .map(|p| {
+            let published = str_field(p, "status") == Some("published");
+            let body = match str_field(p, "html") {
+                Some(html) => html2md::parse_html(html),
+                None => str_field(p, "plaintext").unwrap_or_default().to_string(),
+            };
+            ImportedPost {
+                title: str_field(p, "title").unwrap_or("Untitled").to_string(),
+                slug: str_field(p, "slug").map(str::to_string),
+                description: str_field(p, "custom_excerpt").map(str::to_string),
+                cover_image: str_field(p, "feature_image").map(str::to_string),
+                body: body.trim().to_string(),
+                published,
+                published_at: str_field(p, "published_at").and_then(parse_date).filter(|_| published),
+                created_at: str_field(p, "created_at").and_then(parse_date),
+                updated_at: str_field(p, "updated_at").and_then(parse_date),
+                source_dir: None,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPORT: &str = r#"{"db": [{"meta": {"version": "5.0.0"}, "data": {"posts": [
+        {"id": "1", "title": "Hello", "slug": "hello", "type": "post", "status": "published",
+         "html": "<p>Hi <em>there</em></p><img src=\"__GHOST_URL__/content/images/2021/01/a.png\">",
+         "feature_image": "__GHOST_URL__/content/images/2021/01/cover.png",
+         "custom_excerpt": "Intro",
+         "created_at": "2021-01-01T00:00:00.000Z", "updated_at": "2021-01-03T00:00:00.000Z",
+         "published_at": "2021-01-02T00:00:00.000Z"},
+        {"id": "2", "title": "Draft", "slug": "draft", "type": "post", "status": "draft",
+         "html": null, "plaintext": "Plain body", "published_at": null},
+        {"id": "3", "title": "About", "slug": "about", "type": "page", "status": "published"}
+    ]}}]}"#;
+
+    #[test]
+    fn parse_skips_pages() {
+        let posts = parse(EXPORT).unwrap();
+        assert_eq!(posts.iter().map(|p| p.title.as_str()).collect::<Vec<_>>(), ["Hello", "Draft"]);
+    }
+
+    #[test]
+    fn parse_maps_published_post() {
+        let post = &parse(EXPORT).unwrap()[0];
+        assert!(post.published);
+        assert_eq!(post.slug.as_deref(), Some("hello"));
+        assert_eq!(post.description.as_deref(), Some("Intro"));
+        assert_eq!(post.published_at, parse_date("2021-01-02T00:00:00Z"));
+        assert_eq!(post.cover_image.as_deref(), Some("__GHOST_URL__/content/images/2021/01/cover.png"));
+        assert!(post.body.contains("*there*"), "{}", post.body);
+        assert!(post.body.contains("__GHOST_URL__/content/images/2021/01/a.png"), "{}", post.body);
+    }
+
+    #[test]
+    fn parse_falls_back_to_plaintext() {
+        let draft = &parse(EXPORT).unwrap()[1];
+        assert!(!draft.published);
+        assert_eq!(draft.body, "Plain body");
+        assert_eq!(draft.published_at, None);
+    }
+
+    #[test]
+    fn parse_rejects_unrelated_json() {
+        assert!(parse("{}").is_err());
+    }
+}
@@ -0,0 +1,230 @@
+use super::{parse_date, ImportedPost};
+use crate::archive::front_matter::split_with;
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Flavor {
+    Hugo,
+    Jekyll,
+}
+
+fn is_markdown(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("md" | "markdown" | "mdown")
+    )
+}
+
+fn collect(dir: &Path, out: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = std::fs::read_dir(dir).map_err(|e| format!("read {}: {e}", dir.display()))?;
+    for entry in entries {
+        let path = entry.map_err(|e| format!("read {}: {e}", dir.display()))?.path();
+        let hidden = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with('.'));
+        if hidden {
+            continue;
+        }
+        if path.is_dir() {
+            collect(&path, out)?;
+        } else if is_markdown(&path) {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::from(i),
+        toml::Value::Float(f) => Value::from(f),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(d) => Value::String(d.to_string()),
+        toml::Value::Array(a) => Value::Array(a.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(t) => Value::Object(t.into_iter().map(|(k, v)| (k, toml_to_json(v))).collect()),
+    }
+}
+
+/// Front matter as a JSON object (YAML `---` or Hugo's TOML `+++`) and the body.
+fn read_front_matter(text: &str) -> Result<(Map<String, Value>, &str), String> {
+    let (front, body) = match split_with(text, "+++") {
+        (Some(toml_src), body) => {
+            let table: toml::Table = toml::from_str(toml_src).map_err(|e| e.to_string())?;
+            (toml_to_json(toml::Value::Table(table)), body)
+        }
+        (None, _) => match split_with(text, "---") {
+            (Some(yaml), body) => {
+                let value: Value = serde_yaml_ng::from_str(yaml).map_err(|e| e.to_string())?;
+                (value, body)
+            }
+            (None, body) => (Value::Null, body),
+        },
+    };
+    match front {
+        Value::Object(map) => Ok((map, body)),
+        Value::Null => Ok((Map::new(), body)),
+        _ => Err("front matter is not a mapping".to_string()),
+    }
+}
+
+fn first_str<'a>(front: &'a Map<String, Value>, keys: &[&str]) -> Option<&'a str> {
+    keys.iter()
+        .filter_map(|k| match front.get(*k)? {
+            Value::String(s) => Some(s.as_str()),
+            // Hugo themes often use `cover: {image: ...}`
+            Value::Object(o) => o.get("image").and_then(Value::as_str),
+            _ => None,
+        })
+        .find(|s| !s.trim().is_empty())
+}
+
+/// `2020-01-02-my-post` -> (date, slug) for Jekyll post filenames.
+fn jekyll_name(stem: &str) -> Option<(&str, &str)> {
+    let (date, slug) = (stem.get(..10)?, stem.get(11..)?);
+    let well_formed = stem.as_bytes()[10] == b'-' && parse_date(date).is_some() && !slug.is_empty();
+    well_formed.then_some((date, slug))
+}
+
+fn to_post(path: &Path, text: &str, flavor: Flavor) -> Result<ImportedPost, String> {
+    let (front, body) = read_front_matter(text).map_err(|e| format!("{}: {e}", path.display()))?;
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    let parent = path.parent().map(Path::to_path_buf);
+
+    let (name_date, name_slug) = match flavor {
+        Flavor::Jekyll => jekyll_name(stem).map_or((None, stem), |(d, s)| (Some(d), s)),
+        // Page bundles keep their content in `<slug>/index.md`.
+        Flavor::Hugo if stem == "index" => (
+            None,
+            parent
+                .as_deref()
+                .and_then(|p| p.file_name())
+                .and_then(|n| n.to_str())
+                .unwrap_or(stem),
+        ),
+        Flavor::Hugo => (None, stem),
+    };
+
+    let published = match flavor {
+        Flavor::Hugo => !front.get("draft").and_then(Value::as_bool).unwrap_or(false),
+        Flavor::Jekyll => {
+            let in_drafts = path.components().any(|c| c.as_os_str() == "_drafts");
+            !in_drafts && front.get("published").and_then(Value::as_bool).unwrap_or(true)
+        }
+    };
+    let date = first_str(&front, &["date"]).or(name_date).and_then(parse_date);
+
+    Ok(ImportedPost {
+        title: first_str(&front, &["title"]).unwrap_or(name_slug).to_string(),
+        slug: Some(first_str(&front, &["slug"]).unwrap_or(name_slug).to_string()),
+        description: first_str(&front, &["description", "summary", "excerpt"]).map(str::to_string),
+        cover_image: first_str(
+            &front,
+            &["cover_image", "image", "cover", "featured_image", "feature_image"],
+        )
+        .map(str::to_string),
+        body: body.trim().to_string(),
+        published,
+        published_at: date.filter(|_| published),
+        created_at: date,
+        updated_at: first_str(&front, &["lastmod", "last_modified_at", "updated"]).and_then(parse_date),
+        source_dir: parent,
+    })
+}
+
+/// Load every Markdown post under `dir`. For a Jekyll site root only
+/// `_posts` and `_drafts` are read; Hugo section `_index.md` files are skipped.
+pub fn load(dir: &Path, flavor: Flavor) -> Result<Vec<ImportedPost>, String> {
+    let mut files = Vec::new();
+    if flavor == Flavor::Jekyll && dir.join("_posts").is_dir() {
+        collect(&dir.join("_posts"), &mut files)?;
+        if dir.join("_drafts").is_dir() {
+            collect(&dir.join("_drafts"), &mut files)?;
+        }
+    } else {
+        collect(dir, &mut files)?;
+    }
+    files.sort();
+
+    files
+        .iter()
+        .filter(|p| p.file_stem().and_then(|s| s.to_str()) != Some("_index"))
+        .map(|path| {
+            let text = std::fs::read_to_string(path).map_err(|e| format!("read {}: {e}", path.display()))?;
+            to_post(path, &text, flavor)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hugo_toml_front_matter() {
+        let text = "+++\ntitle = \"Hello\"\ndate = 2020-01-02T03:04:05Z\ndraft = true\n[cover]\nimage = \"/img/c.png\"\n+++\nBody\n";
+        let post = to_post(Path::new("/site/content/posts/hello.md"), text, Flavor::Hugo).unwrap();
+        assert_eq!(post.title, "Hello");
+        assert_eq!(post.slug.as_deref(), Some("hello"));
+        assert!(!post.published);
+        assert_eq!(post.created_at, parse_date("2020-01-02T03:04:05Z"));
+        assert_eq!(post.published_at, None);
+        assert_eq!(post.cover_image.as_deref(), Some("/img/c.png"));
+        assert_eq!(post.body, "Body");
+    }
+
+    #[test]
+    fn hugo_page_bundle_uses_directory_as_slug() {
+        let text = "---\ntitle: Bundle\nslug: \"\"\n---\n![pic](pic.jpg)\n";
+        let post = to_post(Path::new("/site/content/posts/my-bundle/index.md"), text, Flavor::Hugo).unwrap();
+        assert_eq!(post.slug.as_deref(), Some("my-bundle"));
+        assert!(post.published);
+        assert_eq!(post.source_dir.as_deref(), Some(Path::new("/site/content/posts/my-bundle")));
+    }
+
+    #[test]
+    fn jekyll_filename_supplies_date_and_slug() {
+        let text = "---\nlayout: post\ntitle: Trip\nexcerpt: Went places\n---\nText";
+        let post = to_post(Path::new("/site/_posts/2019-05-06-a-trip.md"), text, Flavor::Jekyll).unwrap();
+        assert_eq!(post.slug.as_deref(), Some("a-trip"));
+        assert_eq!(post.published_at, parse_date("2019-05-06"));
+        assert_eq!(post.description.as_deref(), Some("Went places"));
+    }
+
+    #[test]
+    fn jekyll_drafts_and_unpublished_are_drafts() {
+        let draft = to_post(Path::new("/site/_drafts/idea.md"), "---\ntitle: Idea\n---\n", Flavor::Jekyll).unwrap();
+        assert!(!draft.published);
+        let hidden = to_post(
+            Path::new("/site/_posts/2020-01-01-x.md"),
+            "---\npublished: false\n---\n",
+            Flavor::Jekyll,
+        )
+        .unwrap();
+        assert!(!hidden.published);
+    }
+
+    #[test]
+    fn file_without_front_matter_uses_name() {
+        let post = to_post(Path::new("/c/plain-note.md"), "# Note", Flavor::Hugo).unwrap();
+        assert_eq!(post.title, "plain-note");
+        assert_eq!(post.body, "# Note");
+    }
+
+    #[test]
+    fn load_skips_section_indexes() {
+        let dir = std::env::temp_dir().join(format!("slq_hugo_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("posts")).unwrap();
+        std::fs::write(dir.join("posts/_index.md"), "---\ntitle: Posts\n---\n").unwrap();
+        std::fs::write(dir.join("posts/a.md"), "---\ntitle: A\n---\n").unwrap();
+        std::fs::write(dir.join("posts/notes.txt"), "ignored").unwrap();
+
+        let posts = load(&dir, Flavor::Hugo).unwrap();
+
+        assert_eq!(posts.len(), 1);
+        assert_eq!(posts[0].title, "A");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,361 @@
+//! Importers for other blogging platforms. Each parser turns its source into
+//! `ImportedPost`s; `run` then ingests referenced images and creates the posts.
+
+mod ghost;
+mod markdown_dir;
+mod wordpress;
+
+use crate::archive::resolve_slug;
+use crate::assets::store_image;
+use crate::users::find_user;
+use crate::Context;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use clap::{Args, ValueEnum};
+use regex::Regex;
+use repositories::PostRepository;
+use services::assets::{LocalStorageDriver, StorageDriver};
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::sync::LazyLock;
+use uuid::Uuid;
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Source {
+    /// WordPress export (WXR XML file)
+    Wordpress,
+    /// Ghost export (JSON file)
+    Ghost,
+    /// Hugo content directory
+    Hugo,
+    /// Jekyll site or `_posts` directory
+    Jekyll,
+}
+
+#[derive(Args)]
+pub struct ImportFromArgs {
+    #[arg(value_enum)]
+    source: Source,
+    email: String,
+    /// Export file (WordPress, Ghost) or content directory (Hugo, Jekyll)
+    path: PathBuf,
+    /// Directory holding the site's images, searched by URL path (repeatable)
+    #[arg(long = "media-dir")]
+    media_dirs: Vec<PathBuf>,
+    /// Report what would be imported without writing anything
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportedPost {
+    pub title: String,
+    pub slug: Option<String>,
+    pub description: Option<String>,
+    pub cover_image: Option<String>,
+    /// Markdown
+    pub body: String,
+    pub published: bool,
+    pub published_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    /// Directory of the source file, searched first for relative image paths.
+    pub source_dir: Option<PathBuf>,
+}
+
+static MARKDOWN_IMAGE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"!\[[^\]]*\]\(\s*<?([^)\s>]+)"#).unwrap());
+static HTML_IMAGE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"<img\b[^>]*?\bsrc\s*=\s*["']([^"']+)["']"#).unwrap());
+
+/// Image URLs referenced by Markdown image syntax or raw `<img>` tags.
+pub(crate) fn image_refs(markdown: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    MARKDOWN_IMAGE
+        .captures_iter(markdown)
+        .chain(HTML_IMAGE.captures_iter(markdown))
+        .map(|c| c[1].to_string())
+        .filter(|url| !url.starts_with("data:") && seen.insert(url.clone()))
+        .collect()
+}
+
+/// Replace the image URLs `image_refs` finds with what `rewrite` gives for
+/// them. Only the URLs themselves are touched, so a URL that happens to be
+/// part of another, or of the text, is left alone.
+pub(crate) fn rewrite_image_refs(markdown: &str, rewrite: impl Fn(&str) -> Option<String>) -> String {
+    let replace = |caps: &regex::Captures| {
+        let (whole, url) = (caps.get(0).unwrap(), caps.get(1).unwrap());
+        match rewrite(url.as_str()) {
+            Some(new) => {
+                let text = whole.as_str();
+                let (start, end) = (url.start() - whole.start(), url.end() - whole.start());
+                format!("{}{new}{}", &text[..start], &text[end..])
+            }
+            None => whole.as_str().to_string(),
+        }
+    };
+    let markdown = MARKDOWN_IMAGE.replace_all(markdown, replace);
+    HTML_IMAGE.replace_all(&markdown, replace).into_owned()
+}
+
+/// Find the file behind an image URL: the URL path, minus scheme and host,
+/// is tried against each root, dropping leading segments until one exists
+/// (so `https://x/wp-content/uploads/2020/01/a.jpg` matches `uploads/2020/01/a.jpg`
+/// or `a.jpg` inside a media directory).
+pub(crate) fn resolve_local(url: &str, roots: &[PathBuf]) -> Option<PathBuf> {
+    let path = url.split(['?', '#']).next()?;
+    let path = path.strip_prefix("__GHOST_URL__").unwrap_or(path);
+    let path = match path.split_once("://") {
+        Some((_, rest)) => rest.split_once('/').map(|(_, p)| p).unwrap_or(""),
+        None => path,
+    };
+    let rel = Path::new(path.trim_start_matches('/'));
+    if rel.components().any(|c| !matches!(c, Component::Normal(_))) {
+        return None;
+    }
+    let segments: Vec<_> = rel.components().collect();
+    roots.iter().find_map(|root| {
+        (0..segments.len())
+            .map(|start| segments[start..].iter().fold(root.clone(), |p, s| p.join(s)))
+            .find(|candidate| candidate.is_file())
+    })
+}
+
+fn image_mime(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    match ext.as_str() {
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "png" => Some("image/png"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        "avif" => Some("image/avif"),
+        _ => None,
+    }
+}
+
+pub(crate) fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+/// Accepts RFC 3339 plus the looser forms used by WordPress, Hugo and Jekyll.
+pub(crate) fn parse_date(s: &str) -> Option<DateTime<Utc>> {
+    let s = s.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    for fmt in ["%Y-%m-%d %H:%M:%S %z", "%Y-%m-%d %H:%M:%S%z", "%Y-%m-%dT%H:%M:%S%z"] {
+        if let Ok(dt) = DateTime::parse_from_str(s, fmt) {
+            return Some(dt.with_timezone(&Utc));
+        }
+    }
+    for fmt in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(dt) = NaiveDateTime::parse_from_str(s, fmt) {
+            return Some(dt.and_utc());
+        }
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|dt| dt.and_utc())
+}
+
+fn read_to_string(path: &Path) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|e| format!("read {}: {e}", path.display()))
+}
+
+fn load(args: &ImportFromArgs) -> Result<Vec<ImportedPost>, String> {
+    match args.source {
+        Source::Wordpress => wordpress::parse(&read_to_string(&args.path)?),
+        Source::Ghost => ghost::parse(&read_to_string(&args.path)?),
+        Source::Hugo => markdown_dir::load(&args.path, markdown_dir::Flavor::Hugo),
+        Source::Jekyll => markdown_dir::load(&args.path, markdown_dir::Flavor::Jekyll),
+    }
+}
+
+pub async fn run(ctx: &Context, args: ImportFromArgs) -> Result<(), String> {
+    let user = find_user(ctx, &args.email).await?;
+    let posts = load(&args)?;
+    let driver = StorageDriver::Local(LocalStorageDriver::new(&ctx.config.server.upload_dir));
+
+    let mut taken: HashSet<String> = PostRepository::all_for_user(&ctx.db, user.id)
+        .await?
+        .into_iter()
+        .filter_map(|p| p.slug)
+        .collect();
+    // Local file -> asset ID, so an image shared by several posts is stored once.
+    let mut stored: HashMap<PathBuf, Uuid> = HashMap::new();
+    let mut found: HashSet<PathBuf> = HashSet::new();
+    let (mut post_count, mut missing_images) = (0, 0);
+
+    for post in posts {
+        let wanted = post.slug.clone().unwrap_or_else(|| slugify(&post.title));
+        let slug = (!wanted.is_empty()).then(|| resolve_slug(&wanted, &taken));
+        if let Some(s) = &slug {
+            if *s != wanted {
+                println!("slug conflict: {wanted} -> {s}");
+            }
+            taken.insert(s.clone());
+        }
+
+        let mut roots: Vec<PathBuf> = post.source_dir.iter().cloned().collect();
+        roots.extend(args.media_dirs.iter().cloned());
+
+        let mut urls = image_refs(&post.body);
+        urls.extend(post.cover_image.iter().cloned());
+        let mut rewrites: HashMap<String, Uuid> = HashMap::new();
+        for url in urls {
+            let Some(file) = resolve_local(&url, &roots).filter(|f| image_mime(f).is_some()) else {
+                println!("  {:?}: image not found locally: {url}", post.title);
+                missing_images += 1;
+                continue;
+            };
+            found.insert(file.clone());
+            if args.dry_run {
+                continue;
+            }
+            let id = match stored.get(&file) {
+                Some(id) => *id,
+                None => {
+                    let id = Uuid::new_v4();
+                    let data = std::fs::read(&file).map_err(|e| format!("read {}: {e}", file.display()))?;
+                    let filename = file
+                        .file_name()
+                        .ok_or_else(|| format!("{}: not a file name", file.display()))?
+                        .to_string_lossy()
+                        .into_owned();
+                    let mime = image_mime(&file).unwrap().to_string();
+                    store_image(ctx, &driver, id, user.id, &data, filename, mime)
+                        .await
+                        .map_err(|e| format!("{}: {e}", file.display()))?;
+                    stored.insert(file, id);
+                    id
+                }
+            };
+            rewrites.insert(url, id);
+        }
+
+        let state = if post.published { "published" } else { "draft" };
+        if args.dry_run {
+            println!("would import {:?} ({state}, slug: {})", post.title, slug.as_deref().unwrap_or("-"));
+            post_count += 1;
+            continue;
+        }
+
+        let body = rewrite_image_refs(&post.body, |url| {
+            rewrites.get(url).map(|id| driver.url(&format!("{id}/original.webp")))
+        });
+        let cover_image = post.cover_image.map(|url| match rewrites.get(&url) {
+            Some(id) => driver.url(&format!("{id}/large.webp")),
+            None => url,
+        });
+
+        let created = PostRepository::create_post(
+            &ctx.db,
+            user.id,
+            post.title.clone(),
+            body,
+            post.published,
+            post.description,
+            slug,
+            cover_image,
+        )
+        .await
+        .map_err(|e| format!("{:?}: {e}", post.title))?;
+
+        let created_at = post.created_at.or(post.published_at).map(|t| t.naive_utc());
+        let updated_at = post.updated_at.map(|t| t.naive_utc());
+        let published_at = post.published_at.map(|t| t.naive_utc());
+        PostRepository::set_timestamps(
+            &ctx.db,
+            created.id,
+            published_at.or(created.first_published_at),
+            created_at.unwrap_or(created.created_at),
+            updated_at.or(created_at).unwrap_or(created.updated_at),
+        )
+        .await
+        .map_err(|e| format!("{:?}: {e}", post.title))?;
+        println!("imported {:?} ({state}) as {}", post.title, created.id);
+        post_count += 1;
+    }
+
+    let verb = if args.dry_run { "would import" } else { "imported" };
+    println!(
+        "{verb} {post_count} post(s) and {} image(s); {missing_images} image reference(s) not found",
+        found.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_refs_finds_markdown_and_html_images_once() {
+        let md = "![a](/img/a.png) ![b](<b.jpg>) <img class=\"x\" src='/img/d.gif'> ![a](/img/a.png)";
+        assert_eq!(image_refs(md), vec!["/img/a.png", "b.jpg", "/img/d.gif"]);
+    }
+
+    #[test]
+    fn rewrite_image_refs_only_touches_whole_urls() {
+        let md = "![a](a.png) ![b](/img/a.png) <img src=\"a.png\"> see a.png";
+        let rewritten = rewrite_image_refs(md, |url| match url {
+            "a.png" => Some("/uploads/1".to_string()),
+            "/img/a.png" => Some("/uploads/2".to_string()),
+            _ => None,
+        });
+        assert_eq!(rewritten, "![a](/uploads/1) ![b](/uploads/2) <img src=\"/uploads/1\"> see a.png");
+    }
+
+    #[test]
+    fn image_refs_skips_data_urls() {
+        assert!(image_refs("![x](data:image/png;base64,AAAA)").is_empty());
+    }
+
+    #[test]
+    fn resolve_local_strips_host_and_leading_segments() {
+        let root = std::env::temp_dir().join(format!("slq_media_{}", Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("2020/01")).unwrap();
+        std::fs::write(root.join("2020/01/a.jpg"), b"x").unwrap();
+        let roots = vec![root.clone()];
+
+        let found = resolve_local("https://example.com/wp-content/uploads/2020/01/a.jpg?w=300", &roots);
+        assert_eq!(found, Some(root.join("2020/01/a.jpg")));
+        assert_eq!(resolve_local("__GHOST_URL__/content/images/2020/01/a.jpg", &roots), found);
+        assert_eq!(resolve_local("/missing.jpg", &roots), None);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn resolve_local_rejects_traversal() {
+        assert_eq!(resolve_local("../../etc/passwd", &[std::env::temp_dir()]), None);
+    }
+
+    #[test]
+    fn slugify_collapses_separators() {
+        assert_eq!(slugify("  Hello, World! 2024 "), "hello-world-2024");
+        assert_eq!(slugify("Ünïcode Title"), "ünïcode-title");
+    }
+
+    #[test]
+    fn parse_date_accepts_common_formats() {
+        let expected = NaiveDate::from_ymd_opt(2020, 1, 2)
+            .unwrap()
+            .and_hms_opt(3, 4, 5)
+            .unwrap()
+            .and_utc();
+        assert_eq!(parse_date("2020-01-02T03:04:05Z"), Some(expected));
+        assert_eq!(parse_date("2020-01-02 03:04:05"), Some(expected));
+        assert_eq!(parse_date("2020-01-02 05:04:05 +0200"), Some(expected));
+        assert!(parse_date("2020-01-02").is_some());
+        assert_eq!(parse_date("0000-00-00 00:00:00"), None);
+    }
+}
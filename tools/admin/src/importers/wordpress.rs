@@ -0,0 +1,212 @@
+use super::{parse_date, ImportedPost};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::collections::HashMap;
+
+/// One `<item>` of a WXR file: direct child text by qualified element name,
+/// plus `<wp:postmeta>` key/value pairs.
+#[derive(Default)]
+struct Item {
+    fields: HashMap<String, String>,
+    meta: HashMap<String, String>,
+}
+
+impl Item {
+    fn get(&self, name: &str) -> Option<&str> {
+        self.fields.get(name).map(|s| s.as_str()).filter(|s| !s.trim().is_empty())
+    }
+}
+
+fn read_items(xml: &str) -> Result<Vec<Item>, String> {
+    let mut reader = Reader::from_str(xml);
+    let mut items = Vec::new();
+    let mut current: Option<Item> = None;
+    let mut text = String::new();
+    let mut meta_key = String::new();
+
+    loop {
+        match reader.read_event().map_err(|e| format!("WXR parse error: {e}"))? {
+            Event::Start(e) => {
+                if e.name().as_ref() == b"item" {
+                    current = Some(Item::default());
+                }
+                text.clear();
+            }
+            Event::Text(t) => {
+                let t = t.unescape().map_err(|e| format!("WXR parse error: {e}"))?;
+                text.push_str(&t);
+            }
+            Event::CData(c) => text.push_str(&String::from_utf8_lossy(&c.into_inner())),
+            Event::End(e) => {
+                let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+                if name == "item" {
+                    items.extend(current.take());
+                } else if let Some(item) = current.as_mut() {
+                    match name.as_str() {
+                        "wp:meta_key" => meta_key = std::mem::take(&mut text),
+                        "wp:meta_value" => {
+                            item.meta.insert(std::mem::take(&mut meta_key), std::mem::take(&mut text));
+                        }
+                        _ => {
+                            item.fields.entry(name).or_insert_with(|| std::mem::take(&mut text));
+                        }
+                    }
+                }
+                text.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(items)
+}
+
+/// WordPress stores post bodies without `<p>` tags (they are added on
+/// render), so blank-line separated blocks are wrapped before conversion.
+fn autop(html: &str) -> String {
+    if html.contains("<p") {
+        return html.to_string();
+    }
+    html.replace("\r\n", "\n")
+        .split("\n\n")
+        .map(str::trim)
+        .filter(|block| !block.is_empty())
+        .map(|block| {
+            if block.starts_with('<') && !block.starts_with("<a ") && !block.starts_with("<img") {
+                block.to_string()
+            } else {
+                format!("<p>{}</p>", block.replace('\n', "<br>"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn parse(xml: &str) -> Result<Vec<ImportedPost>, String> {
+    let items = read_items(xml)?;
+
+    let attachments: HashMap<&str, &str> = items
+        .iter()
+        .filter(|i| i.get("wp:post_type") == Some("attachment"))
+        .filter_map(|i| Some((i.get("wp:post_id")?, i.get("wp:attachment_url")?)))
+        .collect();
+
+    let posts = items
+        .iter()
+        .filter(|i| i.get("wp:post_type") == Some("post"))
+        .map(|item| {
+            let published = item.get("wp:status") == Some("publish");
+            let date = item
+                .get("wp:post_date_gmt")
+                .and_then(parse_date)
+                .or_else(|| item.get("wp:post_date").and_then(parse_date));
+            let body = item
+                .get("content:encoded")
+                .map(|html| html2md::parse_html(&autop(html)))
+                .unwrap_or_default();
+            ImportedPost {
+                title: item.get("title").unwrap_or("Untitled").trim().to_string(),
+                slug: item.get("wp:post_name").map(|s| s.trim().to_string()),
+                description: item.get("excerpt:encoded").map(|s| s.trim().to_string()),
+                cover_image: item
+                    .meta
+                    .get("_thumbnail_id")
+                    .and_then(|id| attachments.get(id.as_str()))
+                    .map(|url| url.to_string()),
+                body: body.trim().to_string(),
+                published,
+                published_at: if published { date } else { None },
+                created_at: date,
+                updated_at: item.get("wp:post_modified_gmt").and_then(parse_date),
+                source_dir: None,
+            }
+        })
+        .collect();
+    Ok(posts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WXR: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:content="http://purl.org/rss/1.0/modules/content/"
+     xmlns:excerpt="http://wordpress.org/export/1.2/excerpt/" xmlns:wp="http://wordpress.org/export/1.2/">
+<channel>
+  <title>My Blog</title>
+  <item>
+    <title>Hello &amp; welcome</title>
+    <content:encoded><![CDATA[First paragraph with <strong>bold</strong>.
+
+<img src="https://example.com/wp-content/uploads/2020/01/a.jpg" />]]></content:encoded>
+    <excerpt:encoded><![CDATA[Short intro]]></excerpt:encoded>
+    <wp:post_id>10</wp:post_id>
+    <wp:post_date_gmt><![CDATA[2020-01-02 03:04:05]]></wp:post_date_gmt>
+    <wp:post_modified_gmt><![CDATA[2020-02-01 00:00:00]]></wp:post_modified_gmt>
+    <wp:post_name><![CDATA[hello-welcome]]></wp:post_name>
+    <wp:status><![CDATA[publish]]></wp:status>
+    <wp:post_type><![CDATA[post]]></wp:post_type>
+    <wp:postmeta>
+      <wp:meta_key><![CDATA[_thumbnail_id]]></wp:meta_key>
+      <wp:meta_value><![CDATA[11]]></wp:meta_value>
+    </wp:postmeta>
+  </item>
+  <item>
+    <title>cover.jpg</title>
+    <wp:post_id>11</wp:post_id>
+    <wp:post_type><![CDATA[attachment]]></wp:post_type>
+    <wp:attachment_url><![CDATA[https://example.com/wp-content/uploads/2020/01/cover.jpg]]></wp:attachment_url>
+  </item>
+  <item>
+    <title>Draft</title>
+    <content:encoded><![CDATA[<p>WIP</p>]]></content:encoded>
+    <wp:post_date_gmt><![CDATA[0000-00-00 00:00:00]]></wp:post_date_gmt>
+    <wp:post_name><![CDATA[]]></wp:post_name>
+    <wp:status><![CDATA[draft]]></wp:status>
+    <wp:post_type><![CDATA[post]]></wp:post_type>
+  </item>
+  <item>
+    <title>About</title>
+    <wp:post_type><![CDATA[page]]></wp:post_type>
+  </item>
+</channel>
+</rss>"#;
+
+    #[test]
+    fn parse_extracts_posts_only() {
+        let posts = parse(WXR).unwrap();
+        assert_eq!(posts.len(), 2);
+        assert_eq!(posts[0].title, "Hello & welcome");
+        assert_eq!(posts[1].title, "Draft");
+    }
+
+    #[test]
+    fn parse_maps_metadata() {
+        let post = &parse(WXR).unwrap()[0];
+        assert_eq!(post.slug.as_deref(), Some("hello-welcome"));
+        assert_eq!(post.description.as_deref(), Some("Short intro"));
+        assert!(post.published);
+        assert_eq!(post.published_at, parse_date("2020-01-02 03:04:05"));
+        assert_eq!(post.updated_at, parse_date("2020-02-01 00:00:00"));
+        assert_eq!(
+            post.cover_image.as_deref(),
+            Some("https://example.com/wp-content/uploads/2020/01/cover.jpg")
+        );
+    }
+
+    #[test]
+    fn parse_converts_html_body_to_markdown() {
+        let post = &parse(WXR).unwrap()[0];
+        assert!(post.body.contains("**bold**"), "{}", post.body);
+        assert!(post.body.contains("![](https://example.com/wp-content/uploads/2020/01/a.jpg)"), "{}", post.body);
+    }
+
+    #[test]
+    fn parse_draft_has_no_dates_or_slug() {
+        let draft = &parse(WXR).unwrap()[1];
+        assert!(!draft.published);
+        assert_eq!(draft.published_at, None);
+        assert_eq!(draft.slug, None);
+        assert_eq!(draft.body, "WIP");
+    }
+}
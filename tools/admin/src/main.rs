@@ -0,0 +1,110 @@
+mod api_keys;
+mod archive;
+mod assets;
+mod importers;
+mod posts;
+mod site;
+mod tokens;
+mod users;
+
+use clap::{Parser, Subcommand};
+use sea_orm::{Database, DatabaseConnection};
+use services::config::Config;
+use std::io::BufRead;
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(name = "soliloquio-admin", about = "Maintenance tasks for a soliloquio instance")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create users, reset passwords, verify emails
+    #[command(subcommand)]
+    Users(users::UsersCommand),
+    /// List, create and revoke public API keys
+    #[command(subcommand, name = "api-keys")]
+    ApiKeys(api_keys::ApiKeysCommand),
+    /// Publish, unpublish and export posts, and draw their share cards
+    #[command(subcommand)]
+    Posts(posts::PostsCommand),
+    /// Re-run image processing and remove orphaned files
+    #[command(subcommand)]
+    Assets(assets::AssetsCommand),
+    /// Remove expired refresh and verification tokens
+    #[command(subcommand)]
+    Tokens(tokens::TokensCommand),
+    /// Write a user's posts and assets to a portable zip archive
+    Export { email: String, output: PathBuf },
+    /// Recreate posts and assets from an archive under a user
+    Import {
+        email: String,
+        input: PathBuf,
+        /// Report what would be imported without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Import posts and images from WordPress, Ghost, Hugo or Jekyll
+    ImportFrom(importers::ImportFromArgs),
+    /// Render a user's published posts to a static site
+    Site(site::SiteArgs),
+}
+
+pub(crate) struct Context {
+    pub db: DatabaseConnection,
+    pub config: Config,
+}
+
+/// Read a single line from stdin, e.g. a password piped in by a script.
+pub(crate) fn read_line(prompt: &str) -> Result<String, String> {
+    eprint!("{prompt}");
+    let mut line = String::new();
+    std::io::stdin()
+        .lock()
+        .read_line(&mut line)
+        .map_err(|e| format!("read stdin: {e}"))?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+
+    let config = match Config::load() {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+    let db = match Database::connect(config.database_url.as_str()).await {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("database connection failed: {e}");
+            std::process::exit(1);
+        }
+    };
+    let ctx = Context { db, config };
+
+    let result = match cli.command {
+        Command::Users(cmd) => users::run(&ctx, cmd).await,
+        Command::ApiKeys(cmd) => api_keys::run(&ctx, cmd).await,
+        Command::Posts(cmd) => posts::run(&ctx, cmd).await,
+        Command::Assets(cmd) => assets::run(&ctx, cmd).await,
+        Command::Tokens(cmd) => tokens::run(&ctx, cmd).await,
+        Command::Export { email, output } => archive::export(&ctx, &email, &output).await,
+        Command::Import { email, input, dry_run } => {
+            archive::import(&ctx, &email, &input, dry_run).await
+        }
+        Command::ImportFrom(args) => importers::run(&ctx, args).await,
+        Command::Site(args) => site::run(&ctx, args).await,
+    };
+
+    if let Err(e) = result {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,105 @@
+use crate::archive::front_matter;
+use crate::Context;
+use clap::Subcommand;
+use graphql::utilities::preview::refresh_preview;
+use graphql::utilities::publishing::Publishing;
+use repositories::PostRepository;
+use services::assets::{LocalStorageDriver, StorageDriver};
+use std::path::PathBuf;
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Subcommand)]
+pub enum PostsCommand {
+    /// Publish a post (sets first_published_at on first publish)
+    Publish { id: Uuid },
+    /// Unpublish a post
+    Unpublish { id: Uuid },
+    /// Export posts as Markdown with front matter; prints to stdout unless --out-dir is given
+    Export {
+        #[arg(required = true)]
+        ids: Vec<Uuid>,
+        #[arg(long)]
+        out_dir: Option<PathBuf>,
+    },
+    /// Draw share cards for posts without an up-to-date one, e.g. after changing SITE_TITLE
+    Previews {
+        /// Redraw every card, even ones that look current
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+pub async fn run(ctx: &Context, cmd: PostsCommand) -> Result<(), String> {
+    match cmd {
+        PostsCommand::Publish { id } => {
+            let post = set_published(ctx, id, true).await?;
+            println!("published {} ({})", post.title, post.id);
+        }
+        PostsCommand::Unpublish { id } => {
+            let post = set_published(ctx, id, false).await?;
+            println!("unpublished {} ({})", post.title, post.id);
+        }
+        PostsCommand::Export { ids, out_dir } => {
+            if let Some(dir) = &out_dir {
+                std::fs::create_dir_all(dir).map_err(|e| format!("create {}: {e}", dir.display()))?;
+            }
+            for id in ids {
+                let post = PostRepository::find_by_id(&ctx.db, id)
+                    .await?
+                    .ok_or_else(|| format!("post {id} not found"))?;
+                let md = front_matter::render_post(&post);
+                match &out_dir {
+                    Some(dir) => {
+                        let name = post.slug.clone().unwrap_or_else(|| post.id.to_string());
+                        let path = dir.join(format!("{name}.md"));
+                        std::fs::write(&path, md).map_err(|e| format!("write {}: {e}", path.display()))?;
+                        eprintln!("wrote {}", path.display());
+                    }
+                    None => print!("{md}"),
+                }
+            }
+        }
+        PostsCommand::Previews { force } => {
+            let driver = StorageDriver::Local(LocalStorageDriver::new(&ctx.config.server.upload_dir));
+            let (mut drawn, mut failed) = (0, 0);
+            for id in PostRepository::all_ids(&ctx.db).await? {
+                let Some(post) = PostRepository::find_by_id(&ctx.db, id).await? else { continue };
+                match refresh_preview(&ctx.db, &driver, &ctx.config.site, &post, force).await {
+                    Ok(key) if post.preview_image.as_deref() != Some(key.as_str()) || force => {
+                        println!("{id}: {key}");
+                        drawn += 1;
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        eprintln!("{id}: {e}");
+                        failed += 1;
+                    }
+                }
+            }
+            eprintln!("{drawn} card(s) drawn");
+            if failed > 0 {
+                return Err(format!("{failed} post(s) failed"));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Publish or unpublish a post and set off what the GraphQL mutations would:
+/// share cards, the newsletter, webhooks, pings, webmentions and followers.
+async fn set_published(ctx: &Context, id: Uuid, published: bool) -> Result<models::posts::Model, String> {
+    let previous = PostRepository::find_by_id(&ctx.db, id)
+        .await?
+        .ok_or_else(|| format!("post {id} not found"))?;
+    let post = PostRepository::set_published(&ctx.db, id, published).await?;
+    let driver = Arc::new(StorageDriver::Local(LocalStorageDriver::new(&ctx.config.server.upload_dir)));
+    let publishing = Publishing {
+        db: &ctx.db,
+        driver: Some(&driver),
+        site: ctx.config.site.clone(),
+        feed_pings: Some(&ctx.config.feed_pings),
+    };
+    publishing.saved(&post, Some(&previous)).await;
+    Ok(post)
+}
@@ -0,0 +1,199 @@
+use super::{PostMeta, SiteInfo};
+use quick_xml::escape::escape;
+use rss::extension::atom::{AtomExtension, Link};
+use rss::{ChannelBuilder, GuidBuilder, ItemBuilder};
+use regex::Regex;
+use serde_json::json;
+use std::sync::LazyLock;
+
+/// The feeds the site publishes, by path.
+const FEEDS: [&str; 2] = ["/feed.xml", "/feed.json"];
+
+static ROOT_RELATIVE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"(src|href)="/([^/])"#).unwrap());
+
+/// Feed readers resolve links against the feed, not the page, so root-relative
+/// `src`/`href` attributes (e.g. asset URLs) are made absolute.
+fn absolutize(site: &SiteInfo, html: &str) -> String {
+    ROOT_RELATIVE
+        .replace_all(html, |c: &regex::Captures| format!("{}=\"{}{}", &c[1], site.url, &c[2]))
+        .into_owned()
+}
+
+/// RSS 2.0 feed of `items`, newest first, with full HTML content. It names
+/// itself and the site's WebSub hubs with `atom:link`s.
+pub fn rss(site: &SiteInfo, items: &[(PostMeta, String)]) -> String {
+    let items = items
+        .iter()
+        .map(|(post, html)| {
+            ItemBuilder::default()
+                .title(Some(post.title.clone()))
+                .link(Some(post.url.clone()))
+                .guid(Some(GuidBuilder::default().value(post.url.clone()).permalink(true).build()))
+                .description(post.description.clone())
+                .content(Some(absolutize(site, html)))
+                .pub_date(Some(post.published_at.to_rfc2822()))
+                .build()
+        })
+        .collect::<Vec<_>>();
+    let link = |rel: &str, href: String| Link { rel: rel.to_string(), href, ..Default::default() };
+    let mut links = vec![Link { mime_type: Some("application/rss+xml".to_string()), ..link("self", site.absolute("/feed.xml")) }];
+    links.extend(site.hubs.iter().map(|hub| link("hub", hub.clone())));
+    ChannelBuilder::default()
+        .title(site.title.clone())
+        .link(site.url.clone())
+        .description(site.description.clone().unwrap_or_default())
+        .atom_ext(Some(AtomExtension { links }))
+        .items(items)
+        .build()
+        .to_string()
+}
+
+/// JSON Feed 1.1 (https://jsonfeed.org/version/1.1).
+pub fn json_feed(site: &SiteInfo, items: &[(PostMeta, String)]) -> String {
+    let items = items
+        .iter()
+        .map(|(post, html)| {
+            json!({
+                "id": post.id,
+                "url": post.url,
+                "title": post.title,
+                "summary": post.description,
+                "content_html": absolutize(site, html),
+                "image": post.cover_image.as_deref().map(|u| site.absolute(u)),
+                "date_published": post.published_at.to_rfc3339(),
+                "date_modified": post.updated_at.to_rfc3339(),
+            })
+        })
+        .collect::<Vec<_>>();
+    let feed = json!({
+        "version": "https://jsonfeed.org/version/1.1",
+        "title": site.title,
+        "home_page_url": site.url,
+        "feed_url": site.absolute("/feed.json"),
+        "description": site.description,
+        "hubs": site.hubs.iter().map(|url| json!({ "type": "WebSub", "url": url })).collect::<Vec<_>>(),
+        "items": items,
+    });
+    serde_json::to_string_pretty(&feed).unwrap_or_default()
+}
+
+/// A `_headers` file, as Netlify and Cloudflare Pages read it, that
+/// advertises the Webmention endpoint on every page and the site's WebSub
+/// hubs, if any, on its feeds with `Link` headers.
+pub fn headers(site: &SiteInfo) -> String {
+    let mut headers = format!("/*\n  Link: <{}>; rel=\"webmention\"\n", site.webmention_url);
+    if site.hubs.is_empty() {
+        return headers;
+    }
+    for feed in FEEDS {
+        headers.push_str(&format!("{feed}\n"));
+        for hub in &site.hubs {
+            headers.push_str(&format!("  Link: <{hub}>; rel=\"hub\"\n"));
+        }
+        headers.push_str(&format!("  Link: <{}>; rel=\"self\"\n", site.absolute(feed)));
+    }
+    headers
+}
+
+/// `urlset` listing the index pages and every post with its `lastmod`.
+pub fn sitemap(pages: &[String], posts: &[PostMeta]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    for page in pages {
+        xml.push_str(&format!("  <url><loc>{}</loc></url>\n", escape(page.as_str())));
+    }
+    for post in posts {
+        xml.push_str(&format!(
+            "  <url><loc>{}</loc><lastmod>{}</lastmod></url>\n",
+            escape(post.url.as_str()),
+            post.updated_at.format("%Y-%m-%d")
+        ));
+    }
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use uuid::Uuid;
+
+    fn site() -> SiteInfo {
+        SiteInfo {
+            title: "Notes".to_string(),
+            description: Some("Things".to_string()),
+            url: "https://blog.example/".to_string(),
+            hubs: Vec::new(),
+            webmention_url: "https://api.example/webmention".to_string(),
+        }
+    }
+
+    fn post() -> PostMeta {
+        PostMeta {
+            id: Uuid::nil(),
+            title: "Fish & chips".to_string(),
+            slug: Some("fish".to_string()),
+            description: Some("Lunch".to_string()),
+            cover_image: Some("/assets/x/large.webp".to_string()),
+            url: "https://blog.example/posts/fish/".to_string(),
+            word_count: 120,
+            reading_time_minutes: 1,
+            published_at: Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap(),
+            created_at: Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap(),
+            updated_at: Utc.with_ymd_and_hms(2024, 3, 2, 8, 0, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn rss_escapes_and_includes_content() {
+        let xml = rss(&site(), &[(post(), "<p>Hi</p>".to_string())]);
+        assert!(xml.contains("<title>Fish &amp; chips</title>"), "{xml}");
+        assert!(xml.contains("<link>https://blog.example/posts/fish/</link>"), "{xml}");
+        assert!(xml.contains("Fri, 1 Mar 2024 12:00:00 +0000"), "{xml}");
+        assert!(xml.contains("<p>Hi</p>"), "{xml}");
+    }
+
+    #[test]
+    fn json_feed_makes_image_urls_absolute() {
+        let feed: serde_json::Value =
+            serde_json::from_str(&json_feed(&site(), &[(post(), "<p>Hi</p>".to_string())])).unwrap();
+        assert_eq!(feed["feed_url"], "https://blog.example/feed.json");
+        assert_eq!(feed["items"][0]["image"], "https://blog.example/assets/x/large.webp");
+        assert_eq!(feed["items"][0]["content_html"], "<p>Hi</p>");
+    }
+
+    #[test]
+    fn feeds_advertise_websub_hubs() {
+        let hubbed = SiteInfo { hubs: vec!["https://hub.example/".to_string()], ..site() };
+        let xml = rss(&hubbed, &[]);
+        assert!(xml.contains(r#"<atom:link href="https://hub.example/" rel="hub""#), "{xml}");
+        assert!(xml.contains(r#"<atom:link href="https://blog.example/feed.xml" rel="self""#), "{xml}");
+        let feed: serde_json::Value = serde_json::from_str(&json_feed(&hubbed, &[])).unwrap();
+        assert_eq!(feed["hubs"], json!([{ "type": "WebSub", "url": "https://hub.example/" }]));
+        assert_eq!(
+            headers(&hubbed),
+            "/*\n  Link: <https://api.example/webmention>; rel=\"webmention\"\n\
+             /feed.xml\n  Link: <https://hub.example/>; rel=\"hub\"\n  Link: <https://blog.example/feed.xml>; rel=\"self\"\n\
+             /feed.json\n  Link: <https://hub.example/>; rel=\"hub\"\n  Link: <https://blog.example/feed.json>; rel=\"self\"\n"
+        );
+        assert_eq!(headers(&site()), "/*\n  Link: <https://api.example/webmention>; rel=\"webmention\"\n");
+    }
+
+    #[test]
+    fn absolutize_rewrites_root_relative_links_only() {
+        let html = r#"<img src="/assets/a/original.webp"><a href="//cdn.example/x"><a href="https://o.example/">"#;
+        assert_eq!(
+            absolutize(&site(), html),
+            r#"<img src="https://blog.example/assets/a/original.webp"><a href="//cdn.example/x"><a href="https://o.example/">"#
+        );
+    }
+
+    #[test]
+    fn sitemap_lists_pages_and_posts() {
+        let xml = sitemap(&["https://blog.example/".to_string()], &[post()]);
+        assert!(xml.contains("<url><loc>https://blog.example/</loc></url>"));
+        assert!(xml.contains("<loc>https://blog.example/posts/fish/</loc><lastmod>2024-03-02</lastmod>"));
+    }
+}
@@ -0,0 +1,501 @@
+//! Static site generator: renders a user's published posts through
+//! user-supplied minijinja templates into a directory any static host can
+//! serve. A state file in the output directory records what was rendered so
+//! later runs only rewrite pages whose post (or neighbours) changed.
+
+mod feeds;
+
+use crate::users::find_user;
+use crate::Context;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use clap::Args;
+use graphql::utilities::highlight::theme_css;
+use graphql::utilities::assets::AssetImages;
+use graphql::utilities::markdown::{render_document_with, RenderOptions, RenderedMarkdown};
+use graphql::utilities::text::{description_or_excerpt, PlainText};
+use minijinja::{context, path_loader, Environment, Value};
+use models::posts::Model as Post;
+use regex::Regex;
+use repositories::{AssetRepository, PostRepository, PostSortBy, SortDirection};
+use serde::{Deserialize, Serialize};
+use services::assets::{LocalStorageDriver, StorageDriver};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use uuid::Uuid;
+
+const STATE_FILE: &str = ".soliloquio-site.json";
+const FEED_ITEMS: usize = 20;
+
+#[derive(Args)]
+pub struct SiteArgs {
+    email: String,
+    /// Directory holding `index.html`, `post.html` and an optional `static/`
+    #[arg(long)]
+    templates: PathBuf,
+    /// Directory to write the site to
+    #[arg(long)]
+    out: PathBuf,
+    /// Public URL the site is served from [default: APP_BASE_URL]
+    #[arg(long)]
+    base_url: Option<String>,
+    /// Site title [default: the user's display name]
+    #[arg(long)]
+    title: Option<String>,
+    /// Posts per index page
+    #[arg(long, default_value_t = 20)]
+    per_page: i32,
+    /// Render every page, ignoring what the previous run wrote
+    #[arg(long)]
+    full: bool,
+}
+
+/// `site` in every template.
+#[derive(Serialize)]
+pub struct SiteInfo {
+    pub title: String,
+    pub description: Option<String>,
+    /// Base URL with a trailing slash.
+    pub url: String,
+    /// WebSub hubs the feeds name, from `WEBSUB_HUBS`.
+    pub hubs: Vec<String>,
+    /// Where the server takes Webmentions, for `<link rel="webmention">`.
+    pub webmention_url: String,
+}
+
+impl SiteInfo {
+    /// Resolve a root-relative path such as `/assets/...` against the base URL.
+    pub fn absolute(&self, path: &str) -> String {
+        if path.starts_with("http://") || path.starts_with("https://") {
+            return path.to_string();
+        }
+        format!("{}{}", self.url, path.trim_start_matches('/'))
+    }
+}
+
+/// A post as seen by templates; `content` is passed separately to post pages.
+#[derive(Clone, Serialize)]
+pub struct PostMeta {
+    pub id: Uuid,
+    pub title: String,
+    pub slug: Option<String>,
+    pub description: Option<String>,
+    pub cover_image: Option<String>,
+    pub url: String,
+    pub word_count: usize,
+    pub reading_time_minutes: usize,
+    pub published_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Inputs that affect every page; a change forces a full rebuild.
+#[derive(PartialEq, Serialize, Deserialize)]
+struct Settings {
+    url: String,
+    title: String,
+    per_page: i32,
+    templates: String,
+    render: RenderOptions,
+}
+
+#[derive(PartialEq, Serialize, Deserialize)]
+struct Rendered {
+    updated_at: NaiveDateTime,
+    dir: String,
+    prev: Option<Uuid>,
+    next: Option<Uuid>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct State {
+    settings: Settings,
+    posts: BTreeMap<Uuid, Rendered>,
+}
+
+/// Output directory for a post: `posts/<slug>`, or the id when the slug is
+/// missing or would escape `posts/`.
+fn post_dir(post: &Post) -> String {
+    let name = post
+        .slug
+        .as_deref()
+        .filter(|s| !s.is_empty() && !s.starts_with('.') && !s.contains(['/', '\\']))
+        .map_or_else(|| post.id.to_string(), str::to_string);
+    format!("posts/{name}")
+}
+
+fn published_at(post: &Post) -> NaiveDateTime {
+    post.first_published_at.unwrap_or(post.created_at)
+}
+
+fn meta(site: &SiteInfo, images: &AssetImages, post: &Post) -> PostMeta {
+    let markdown = post.markdown_content.as_deref().unwrap_or_default();
+    let plain = PlainText::new(markdown);
+    PostMeta {
+        id: post.id,
+        title: post.title.clone(),
+        slug: post.slug.clone(),
+        description: description_or_excerpt(post.description.as_deref(), markdown),
+        cover_image: post.cover_image.as_deref().map(|c| images.resolve_url(c)),
+        url: site.absolute(&format!("{}/", post_dir(post))),
+        word_count: plain.words,
+        reading_time_minutes: plain.reading_time_minutes(),
+        published_at: published_at(post).and_utc(),
+        created_at: post.created_at.and_utc(),
+        updated_at: post.updated_at.and_utc(),
+    }
+}
+
+fn page_url(site: &SiteInfo, number: u32) -> String {
+    match number {
+        1 => site.url.clone(),
+        n => site.absolute(&format!("page/{n}/")),
+    }
+}
+
+static ASSET_URL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"/assets/([0-9a-fA-F-]{36}/[a-z]+\.webp)").unwrap());
+
+/// Storage keys of asset variants referenced by rendered HTML or a cover URL.
+fn asset_keys(text: &str) -> impl Iterator<Item = &str> {
+    ASSET_URL.captures_iter(text).map(|c| c.get(1).unwrap().as_str())
+}
+
+/// Hash of every file under the templates directory, so editing a template
+/// or a static file triggers a full rebuild.
+fn fingerprint(dir: &Path) -> Result<String, String> {
+    fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+        let entries = std::fs::read_dir(dir).map_err(|e| format!("read {}: {e}", dir.display()))?;
+        for entry in entries {
+            let path = entry.map_err(|e| format!("read {}: {e}", dir.display()))?.path();
+            if path.is_dir() {
+                walk(&path, files)?;
+            } else {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+    let mut files = Vec::new();
+    walk(dir, &mut files)?;
+    files.sort();
+    let mut hasher = Sha256::new();
+    for file in files {
+        hasher.update(file.strip_prefix(dir).unwrap_or(&file).to_string_lossy().as_bytes());
+        hasher.update(std::fs::read(&file).map_err(|e| format!("read {}: {e}", file.display()))?);
+    }
+    Ok(hasher.finalize().iter().map(|b| format!("{b:02x}")).collect())
+}
+
+fn write(out: &Path, rel: &str, contents: impl AsRef<[u8]>) -> Result<(), String> {
+    let path = out.join(rel);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("create {}: {e}", parent.display()))?;
+    }
+    std::fs::write(&path, contents).map_err(|e| format!("write {}: {e}", path.display()))
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<(), String> {
+    let entries = std::fs::read_dir(from).map_err(|e| format!("read {}: {e}", from.display()))?;
+    for entry in entries {
+        let path = entry.map_err(|e| format!("read {}: {e}", from.display()))?.path();
+        let target = to.join(path.file_name().unwrap_or_default());
+        if path.is_dir() {
+            std::fs::create_dir_all(&target).map_err(|e| format!("create {}: {e}", target.display()))?;
+            copy_dir(&path, &target)?;
+        } else {
+            std::fs::copy(&path, &target).map_err(|e| format!("copy {}: {e}", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+fn render_template(env: &Environment, name: &str, ctx: Value) -> Result<String, String> {
+    env.get_template(name)
+        .and_then(|t| t.render(ctx))
+        .map_err(|e| format!("template {name}: {e:#}"))
+}
+
+fn render_post(
+    env: &Environment,
+    site: &SiteInfo,
+    post: &PostMeta,
+    rendered: &RenderedMarkdown,
+    prev: Option<&PostMeta>,
+    next: Option<&PostMeta>,
+) -> Result<String, String> {
+    render_template(
+        env,
+        "post.html",
+        context! {
+            site => site,
+            post => post,
+            content => Value::from_safe_string(rendered.html.clone()),
+            toc => &rendered.toc,
+            prev => prev,
+            next => next,
+        },
+    )
+}
+
+/// Every published post, page by page, exactly as the public `posts` query
+/// returns them with default ordering.
+async fn published_pages(ctx: &Context, user_id: Uuid, per_page: i32) -> Result<Vec<Vec<Post>>, String> {
+    let mut pages = Vec::new();
+    loop {
+        let page = PostRepository::get_published_posts(
+            &ctx.db,
+            user_id,
+            Some(pages.len() as i32 + 1),
+            Some(per_page),
+            PostSortBy::CreatedAt,
+            SortDirection::Desc,
+        )
+        .await?;
+        let more = page.has_next_page;
+        pages.push(page.posts);
+        if !more {
+            return Ok(pages);
+        }
+    }
+}
+
+pub async fn run(ctx: &Context, args: SiteArgs) -> Result<(), String> {
+    if args.per_page < 1 {
+        return Err("--per-page must be at least 1".to_string());
+    }
+    for required in ["index.html", "post.html"] {
+        if !args.templates.join(required).is_file() {
+            return Err(format!("{} is missing {required}", args.templates.display()));
+        }
+    }
+
+    let user = find_user(ctx, &args.email).await?;
+    let driver = StorageDriver::Local(LocalStorageDriver::new(&ctx.config.server.upload_dir));
+    let base = args.base_url.as_deref().unwrap_or(&ctx.config.email.app_base_url);
+    let site = SiteInfo {
+        title: args.title.or(user.display_name.clone()).unwrap_or_else(|| "Blog".to_string()),
+        description: user.bio.clone(),
+        url: format!("{}/", base.trim_end_matches('/')),
+        hubs: ctx.config.feed_pings.hubs.clone(),
+        webmention_url: format!("{}/webmention", ctx.config.site.api_url),
+    };
+
+    let mut env = Environment::new();
+    env.set_loader(path_loader(&args.templates));
+
+    let options = RenderOptions::from(&ctx.config.markdown);
+    let assets = AssetRepository::all_for_user(&ctx.db, user.id).await?;
+    let images = AssetImages::new(&assets, &driver);
+    let render = |post: &Post| {
+        let options = RenderOptions { math: options.math && post.render_math, ..options };
+        render_document_with(post.markdown_content.as_deref().unwrap_or_default(), &options, &images)
+    };
+
+    let settings = Settings {
+        url: site.url.clone(),
+        title: site.title.clone(),
+        per_page: args.per_page,
+        templates: fingerprint(&args.templates)?,
+        render: options,
+    };
+    let state_path = args.out.join(STATE_FILE);
+    let previous: Option<State> = match std::fs::read(&state_path) {
+        Ok(bytes) if !args.full => serde_json::from_slice(&bytes).ok(),
+        _ => None,
+    };
+    let full = previous.as_ref().is_none_or(|p| p.settings != settings);
+    let previous_posts = previous.map(|p| p.posts).unwrap_or_default();
+
+    let pages = published_pages(ctx, user.id, args.per_page).await?;
+    let posts: Vec<&Post> = pages.iter().flatten().collect();
+    let metas: BTreeMap<Uuid, PostMeta> = posts.iter().map(|p| (p.id, meta(&site, &images, p))).collect();
+
+    // Neighbours in publication order, matching the prev/next post queries.
+    let mut chronological = posts.clone();
+    chronological.sort_by_key(|p| (published_at(p), p.id));
+
+    let mut state = State { settings, posts: BTreeMap::new() };
+    let mut rendered = 0;
+    let mut wanted_assets = HashSet::new();
+    for (i, post) in chronological.iter().enumerate() {
+        let prev = i.checked_sub(1).map(|j| chronological[j].id);
+        let next = chronological.get(i + 1).map(|p| p.id);
+        let entry = Rendered { updated_at: post.updated_at, dir: post_dir(post), prev, next };
+
+        if full || previous_posts.get(&post.id) != Some(&entry) {
+            let document = render(post);
+            let page = render_post(
+                &env,
+                &site,
+                &metas[&post.id],
+                &document,
+                prev.map(|id| &metas[&id]),
+                next.map(|id| &metas[&id]),
+            )?;
+            write(&args.out, &format!("{}/index.html", entry.dir), page)?;
+            wanted_assets.extend(asset_keys(&document.html).map(str::to_string));
+            let cover = metas[&post.id].cover_image.as_deref();
+            wanted_assets.extend(cover.into_iter().flat_map(asset_keys).map(str::to_string));
+            rendered += 1;
+        }
+        state.posts.insert(post.id, entry);
+    }
+
+    // Drop pages of posts that were unpublished, deleted or renamed.
+    let live: HashSet<&str> = state.posts.values().map(|r| r.dir.as_str()).collect();
+    for old in previous_posts.values() {
+        if !live.contains(old.dir.as_str()) && old.dir.starts_with("posts/") {
+            let _ = std::fs::remove_dir_all(args.out.join(&old.dir));
+        }
+    }
+
+    let mut copied = 0;
+    for key in &wanted_assets {
+        let target = args.out.join("assets").join(key);
+        if target.exists() && !full {
+            continue;
+        }
+        match driver.get(key).await {
+            Ok(data) => {
+                write(&args.out, &format!("assets/{key}"), data)?;
+                copied += 1;
+            }
+            Err(e) => eprintln!("skipping asset {key}: {e}"),
+        }
+    }
+
+    // Index pages, feeds and the sitemap depend on every post; always rewrite them.
+    let total_pages = pages.len() as u32;
+    let mut page_urls = Vec::new();
+    for (i, page) in pages.iter().enumerate() {
+        let number = i as u32 + 1;
+        let listed: Vec<&PostMeta> = page.iter().map(|p| &metas[&p.id]).collect();
+        let html = render_template(
+            &env,
+            "index.html",
+            context! {
+                site => &site,
+                posts => listed,
+                pagination => context! {
+                    number => number,
+                    total_pages => total_pages,
+                    prev_url => (number > 1).then(|| page_url(&site, number - 1)),
+                    next_url => (number < total_pages).then(|| page_url(&site, number + 1)),
+                },
+            },
+        )?;
+        let rel = if number == 1 { "index.html".to_string() } else { format!("page/{number}/index.html") };
+        write(&args.out, &rel, html)?;
+        page_urls.push(page_url(&site, number));
+    }
+    if let Ok(entries) = std::fs::read_dir(args.out.join("page")) {
+        for entry in entries.flatten() {
+            let stale = entry.file_name().to_str().and_then(|n| n.parse::<u32>().ok()).is_some_and(|n| n > total_pages);
+            if stale {
+                let _ = std::fs::remove_dir_all(entry.path());
+            }
+        }
+    }
+
+    let feed_items: Vec<(PostMeta, String)> = chronological
+        .iter()
+        .rev()
+        .take(FEED_ITEMS)
+        .map(|p| (metas[&p.id].clone(), render(p).html))
+        .collect();
+    write(&args.out, "feed.xml", feeds::rss(&site, &feed_items))?;
+    write(&args.out, "feed.json", feeds::json_feed(&site, &feed_items))?;
+    // A `_headers` among the templates' static files wins.
+    if !args.templates.join("static/_headers").exists() {
+        write(&args.out, "_headers", feeds::headers(&site))?;
+    }
+    let all: Vec<PostMeta> = posts.iter().map(|p| metas[&p.id].clone()).collect();
+    write(&args.out, "sitemap.xml", feeds::sitemap(&page_urls, &all))?;
+
+    let static_dir = args.templates.join("static");
+    if full && static_dir.is_dir() {
+        copy_dir(&static_dir, &args.out)?;
+    }
+    if let Some(css) = theme_css(&ctx.config.markdown.highlight_theme) {
+        write(&args.out, "highlight.css", css)?;
+    }
+
+    let json = serde_json::to_vec_pretty(&state).map_err(|e| e.to_string())?;
+    write(&args.out, STATE_FILE, json)?;
+
+    println!(
+        "{} post(s): {rendered} page(s) rendered, {copied} asset file(s) copied{}",
+        posts.len(),
+        if full { " (full build)" } else { "" }
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(slug: Option<&str>) -> Post {
+        let now = chrono::Utc::now().naive_utc();
+        Post {
+            id: Uuid::new_v4(),
+            title: "T".to_string(),
+            markdown_content: None,
+            description: None,
+            slug: slug.map(str::to_string),
+            cover_image: None,
+            user_id: Uuid::nil(),
+            is_published: true,
+            render_math: true,
+            rendered_markdown: None,
+            seo: None,
+            preview_image: None,
+            first_published_at: Some(now),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn post_dir_falls_back_to_id_for_unsafe_slugs() {
+        assert_eq!(post_dir(&post(Some("hello"))), "posts/hello");
+        for slug in [None, Some(""), Some(".."), Some("a/b")] {
+            let p = post(slug);
+            assert_eq!(post_dir(&p), format!("posts/{}", p.id));
+        }
+    }
+
+    #[test]
+    fn asset_keys_finds_variant_urls() {
+        let id = Uuid::new_v4();
+        let html = format!(r#"<img src="/assets/{id}/original.webp"><a href="/assets/{id}/../x">"#);
+        assert_eq!(asset_keys(&html).collect::<Vec<_>>(), [format!("{id}/original.webp")]);
+    }
+
+    #[test]
+    fn site_absolute_joins_base() {
+        let site = SiteInfo { title: String::new(), description: None, url: "https://x.example/blog/".to_string(), hubs: Vec::new(), webmention_url: String::new() };
+        assert_eq!(site.absolute("/assets/a.webp"), "https://x.example/blog/assets/a.webp");
+        assert_eq!(site.absolute("https://cdn.example/a.png"), "https://cdn.example/a.png");
+        assert_eq!(page_url(&site, 1), "https://x.example/blog/");
+        assert_eq!(page_url(&site, 3), "https://x.example/blog/page/3/");
+    }
+
+    #[test]
+    fn post_page_renders_content_unescaped() {
+        let mut env = Environment::new();
+        env.add_template("post.html", "<h1>{{ post.title }}</h1>{{ content }}{% if next %}{{ next.url }}{% endif %}")
+            .unwrap();
+        let site = SiteInfo { title: "S".to_string(), description: None, url: "https://x.example/".to_string(), hubs: Vec::new(), webmention_url: String::new() };
+        let mut p = post(Some("a"));
+        p.title = "A & B".to_string();
+        let (a, b) = (meta(&site, &AssetImages::default(), &p), meta(&site, &AssetImages::default(), &post(Some("b"))));
+
+        let rendered = RenderedMarkdown { html: "<p>hi</p>".to_string(), toc: Vec::new() };
+        let html = render_post(&env, &site, &a, &rendered, None, Some(&b)).unwrap();
+
+        assert_eq!(html, "<h1>A &amp; B</h1><p>hi</p>https:&#x2f;&#x2f;x.example&#x2f;posts&#x2f;b&#x2f;");
+    }
+}
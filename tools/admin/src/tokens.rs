@@ -0,0 +1,21 @@
+use crate::Context;
+use clap::Subcommand;
+use services::authentication::refresh_token::cleanup_expired_tokens;
+use services::verification_token::cleanup_expired;
+
+#[derive(Subcommand)]
+pub enum TokensCommand {
+    /// Delete expired refresh tokens and email verification / reset tokens
+    Cleanup,
+}
+
+pub async fn run(ctx: &Context, cmd: TokensCommand) -> Result<(), String> {
+    match cmd {
+        TokensCommand::Cleanup => {
+            let refresh = cleanup_expired_tokens(&ctx.db).await.map_err(|e| e.to_string())?;
+            let verification = cleanup_expired(&ctx.db).await.map_err(|e| e.to_string())?;
+            println!("removed {refresh} refresh token(s), {verification} verification token(s)");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use crate::{read_line, Context};
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
+    Argon2,
+};
+use clap::Subcommand;
+use models::users::Model as User;
+use repositories::UserRepository;
+use services::authentication::refresh_token::revoke_all_refresh_tokens;
+use services::validation::validate_password;
+use uuid::Uuid;
+
+#[derive(Subcommand)]
+pub enum UsersCommand {
+    /// List all users
+    List,
+    /// Create a user; the password is read from stdin unless --password is given
+    Create {
+        email: String,
+        #[arg(long)]
+        password: Option<String>,
+        /// Mark the email address as verified immediately
+        #[arg(long)]
+        verified: bool,
+    },
+    /// Set a new password and sign the user out of every device
+    ResetPassword {
+        email: String,
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Mark a user's email address as verified
+    VerifyEmail { email: String },
+}
+
+pub(crate) async fn find_user(ctx: &Context, email: &str) -> Result<User, String> {
+    UserRepository::find_by_email(&ctx.db, email)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("no user with email {email}"))
+}
+
+fn hash_password(password: Option<String>) -> Result<String, String> {
+    let password = match password {
+        Some(p) => p,
+        None => read_line("Password: ")?,
+    };
+    validate_password(&password).map_err(|e| e.to_string())?;
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|h| h.to_string())
+        .map_err(|_| "Failed to hash password".to_string())
+}
+
+pub async fn run(ctx: &Context, cmd: UsersCommand) -> Result<(), String> {
+    match cmd {
+        UsersCommand::List => {
+            let users = UserRepository::list(&ctx.db).await.map_err(|e| e.to_string())?;
+            for u in users {
+                let verified = if u.email_verified_at.is_some() { "verified" } else { "unverified" };
+                println!("{}\t{}\t{}", u.id, u.email, verified);
+            }
+        }
+        UsersCommand::Create { email, password, verified } => {
+            let hash = hash_password(password)?;
+            let user = UserRepository::create(&ctx.db, Uuid::new_v4(), email, hash).await?;
+            if verified {
+                UserRepository::verify_email(&ctx.db, user.id).await?;
+            }
+            println!("created user {} ({})", user.email, user.id);
+        }
+        UsersCommand::ResetPassword { email, password } => {
+            let user = find_user(ctx, &email).await?;
+            let hash = hash_password(password)?;
+            UserRepository::update_password(&ctx.db, user.id, hash).await?;
+            revoke_all_refresh_tokens(&ctx.db, user.id)
+                .await
+                .map_err(|e| e.to_string())?;
+            println!("password reset for {email}; existing sessions revoked");
+        }
+        UsersCommand::VerifyEmail { email } => {
+            let user = find_user(ctx, &email).await?;
+            UserRepository::verify_email(&ctx.db, user.id).await?;
+            println!("verified {email}");
+        }
+    }
+    Ok(())
+}
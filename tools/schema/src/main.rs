@@ -2,7 +2,7 @@ use async_graphql::*;
 use graphql::authenticated::mutations::Mutations as MutationRoot;
 use graphql::authenticated::queries::Queries as QueryRoot;
 use graphql::authenticated::subscriptions::Subscriptions as SubscriptionRoot;
-use graphql::public::PublicQueryRoot;
+use graphql::public::{PublicMutationRoot, PublicQueryRoot};
 use std::fs;
 
 fn main() -> std::io::Result<()> {
@@ -15,7 +15,7 @@ fn main() -> std::io::Result<()> {
 
     let public_schema = Schema::build(
         PublicQueryRoot::default(),
-        EmptyMutation,
+        PublicMutationRoot,
         EmptySubscription,
     )
     .finish();